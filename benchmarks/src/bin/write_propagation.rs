@@ -29,7 +29,7 @@ use query_generator::ColumnGenerationSpec;
 use readyset_adapter::backend::noria_connector::{NoriaConnector, ReadBehavior};
 use readyset_adapter::backend::{Backend, BackendBuilder};
 use readyset_adapter::query_status_cache::QueryStatusCache;
-use readyset_adapter::{UpstreamConfig, UpstreamDatabase};
+use readyset_adapter::{ProxyOptions, UpstreamConfig, UpstreamDatabase};
 use readyset_client::consensus::AuthorityType;
 use readyset_client::{KeyComparison, ReadySetHandle, View, ViewCreateRequest, ViewQuery};
 use readyset_data::{DfValue, Dialect};
@@ -105,9 +105,15 @@ impl Writer {
 
         let auto_increments: Arc<RwLock<HashMap<Relation, AtomicUsize>>> = Arc::default();
         let query_cache: Arc<RwLock<HashMap<ViewCreateRequest, Relation>>> = Arc::default();
-        let query_status_cache: &'static _ = Box::leak(Box::new(QueryStatusCache::new()));
-        let upstream =
-            Some(MySqlUpstream::connect(UpstreamConfig::from_url(&self.database_url), None).await?);
+        let query_status_cache = Arc::new(QueryStatusCache::new());
+        let upstream = Some(
+            MySqlUpstream::connect(
+                UpstreamConfig::from_url(&self.database_url),
+                ProxyOptions::default(),
+                None,
+            )
+            .await?,
+        );
         let server_supports_pagination = ch.supports_pagination().await?;
         let noria = NoriaConnector::new(
             ch.clone(),
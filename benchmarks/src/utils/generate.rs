@@ -329,6 +329,7 @@ pub async fn load_to_backend(
                 .collect(),
             ignore: false,
             on_duplicate: None,
+            on_conflict: None,
         };
 
         db.query(&insert.to_string())
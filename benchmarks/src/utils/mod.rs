@@ -1,7 +1,5 @@
 use std::convert::TryFrom;
 use std::future::Future;
-use std::num::ParseIntError;
-use std::str::FromStr;
 use std::time::Duration;
 
 use anyhow::Result;
@@ -10,6 +8,7 @@ use mysql_async::ServerError;
 use readyset_client::status::{ReadySetStatus, SnapshotStatus};
 use readyset_client::ReadySetResult;
 use readyset_tracing::info;
+use readyset_util::duration::{parse_duration_with_suffix, ParseDurationError};
 
 pub mod generate;
 pub mod multi_thread;
@@ -22,8 +21,12 @@ pub fn us_to_ms(us: u64) -> f64 {
     us as f64 / 1000.
 }
 
-pub fn seconds_as_str_to_duration(input: &str) -> std::result::Result<Duration, ParseIntError> {
-    Ok(Duration::from_secs(u64::from_str(input)?))
+/// Parse a duration from a command-line argument, either as a bare number of seconds or with one
+/// of the unit suffixes supported by [`parse_duration_with_suffix`], such as `500ms` or `5m`
+pub fn seconds_as_str_to_duration(
+    input: &str,
+) -> std::result::Result<Duration, ParseDurationError> {
+    parse_duration_with_suffix(input)
 }
 
 pub async fn run_for(
@@ -234,6 +234,7 @@ impl MultithreadBenchmark for WriteBenchmark {
                         .collect(),
                     ignore: false,
                     on_duplicate: None,
+                    on_conflict: None,
                 }
             };
 
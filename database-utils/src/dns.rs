@@ -0,0 +1,198 @@
+//! Caching of DNS resolution for upstream database hosts.
+//!
+//! By default, every new connection to the upstream database resolves the upstream host afresh,
+//! which under heavy connection churn puts a lot of load on DNS, and can stall new connections on
+//! a slow resolver. If `--upstream-dns-refresh-seconds` is set, the addresses the upstream host
+//! resolves to are instead stored in the global [`DnsCache`] and reused for new connections until
+//! they're older than the refresh interval, at which point the host is resolved again.
+//!
+//! If a connection can't be made to any of the cached addresses for a host, the cache entry is
+//! invalidated so that the host is resolved afresh.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use tokio::net::TcpStream;
+
+use crate::tcp::TcpBufferSizes;
+
+lazy_static! {
+    static ref GLOBAL_DNS_CACHE: DnsCache = DnsCache::new();
+}
+
+struct CachedAddrs {
+    addrs: Vec<SocketAddr>,
+    resolved_at: Instant,
+}
+
+/// A cache of the addresses that a set of `(host, port)` pairs resolve to
+#[derive(Default)]
+pub struct DnsCache {
+    entries: Mutex<HashMap<(String, u16), CachedAddrs>>,
+}
+
+impl DnsCache {
+    /// Create a new, empty, [`DnsCache`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a reference to the process-wide [`DnsCache`] used for connections to the upstream
+    /// database
+    pub fn global() -> &'static DnsCache {
+        &GLOBAL_DNS_CACHE
+    }
+
+    /// Returns the addresses that `host` resolves to on `port`, resolving it if it hasn't been
+    /// resolved in the last `refresh_interval`
+    pub async fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+        refresh_interval: Duration,
+    ) -> io::Result<Vec<SocketAddr>> {
+        self.resolve_with(host, port, refresh_interval, |host, port| async move {
+            Ok(tokio::net::lookup_host((host.as_str(), port))
+                .await?
+                .collect())
+        })
+        .await
+    }
+
+    async fn resolve_with<F, Fut>(
+        &self,
+        host: &str,
+        port: u16,
+        refresh_interval: Duration,
+        lookup: F,
+    ) -> io::Result<Vec<SocketAddr>>
+    where
+        F: FnOnce(String, u16) -> Fut,
+        Fut: Future<Output = io::Result<Vec<SocketAddr>>>,
+    {
+        let cached = self
+            .entries
+            .lock()
+            .unwrap()
+            .get(&(host.to_owned(), port))
+            .filter(|cached| cached.resolved_at.elapsed() < refresh_interval)
+            .map(|cached| cached.addrs.clone());
+        if let Some(addrs) = cached {
+            return Ok(addrs);
+        }
+
+        let addrs = lookup(host.to_owned(), port).await?;
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{host} did not resolve to any addresses"),
+            ));
+        }
+        self.entries.lock().unwrap().insert(
+            (host.to_owned(), port),
+            CachedAddrs {
+                addrs: addrs.clone(),
+                resolved_at: Instant::now(),
+            },
+        );
+        Ok(addrs)
+    }
+
+    /// Remove the cached addresses for `host` on `port`, if any, so that they're resolved afresh
+    /// the next time they're needed
+    pub fn invalidate(&self, host: &str, port: u16) {
+        self.entries
+            .lock()
+            .unwrap()
+            .remove(&(host.to_owned(), port));
+    }
+
+    /// Open a TCP connection to `host` on `port` with the given `buffer_sizes`, using its cached
+    /// addresses if they're fresh.
+    ///
+    /// If no connection can be made to any of the cached addresses, the cache entry is invalidated
+    /// and the host is resolved afresh before trying again.
+    pub async fn connect(
+        &self,
+        host: &str,
+        port: u16,
+        refresh_interval: Duration,
+        buffer_sizes: TcpBufferSizes,
+    ) -> io::Result<TcpStream> {
+        let addrs = self.resolve(host, port, refresh_interval).await?;
+        if let Ok(stream) = buffer_sizes.connect(&addrs[..]).await {
+            return Ok(stream);
+        }
+
+        self.invalidate(host, port);
+        let addrs = self.resolve(host, port, refresh_interval).await?;
+        buffer_sizes.connect(&addrs[..]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn cached_addresses_are_reused() {
+        let cache = DnsCache::new();
+        let lookups = AtomicUsize::new(0);
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 5432);
+        let lookup = |_, _| {
+            lookups.fetch_add(1, Ordering::Relaxed);
+            async move { Ok(vec![addr]) }
+        };
+        let refresh_interval = Duration::from_secs(60);
+
+        for _ in 0..3 {
+            let addrs = cache
+                .resolve_with("upstream", 5432, refresh_interval, lookup)
+                .await
+                .unwrap();
+            assert_eq!(addrs, vec![addr]);
+        }
+        assert_eq!(lookups.load(Ordering::Relaxed), 1);
+
+        cache.invalidate("upstream", 5432);
+        cache
+            .resolve_with("upstream", 5432, refresh_interval, lookup)
+            .await
+            .unwrap();
+        assert_eq!(lookups.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn stale_addresses_are_refreshed() {
+        let cache = DnsCache::new();
+        let lookups = AtomicUsize::new(0);
+        let lookup = |_, port| {
+            let n = lookups.fetch_add(1, Ordering::Relaxed) as u8;
+            async move {
+                Ok(vec![SocketAddr::new(
+                    IpAddr::V4(Ipv4Addr::new(10, 0, 0, n)),
+                    port,
+                )])
+            }
+        };
+
+        let first = cache
+            .resolve_with("upstream", 3306, Duration::ZERO, lookup)
+            .await
+            .unwrap();
+        let second = cache
+            .resolve_with("upstream", 3306, Duration::ZERO, lookup)
+            .await
+            .unwrap();
+        assert_ne!(first, second);
+        assert_eq!(lookups.load(Ordering::Relaxed), 2);
+    }
+}
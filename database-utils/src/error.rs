@@ -56,3 +56,11 @@ pub struct DatabaseTypeParseError {
     /// The value that was originally being parsed
     pub value: String,
 }
+
+/// Error type for the [`FromStr`] implementation for [`MaxResultRowsBehavior`]
+#[derive(Debug, Error)]
+#[error("Invalid max result rows behavior `{value}`, expected one of `error` or `truncate`")]
+pub struct MaxResultRowsBehaviorParseError {
+    /// The value that was originally being parsed
+    pub value: String,
+}
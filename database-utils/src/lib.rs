@@ -8,9 +8,9 @@ use std::path::PathBuf;
 use std::str::{self, FromStr};
 use std::time::Duration;
 
-use clap::Parser;
+use clap::{ArgGroup, Parser};
 use derive_more::From;
-use error::{ConnectionType, DatabaseTypeParseError};
+use error::{ConnectionType, DatabaseTypeParseError, MaxResultRowsBehaviorParseError};
 use futures::{StreamExt, TryStreamExt};
 use mysql_async::prelude::Queryable;
 use mysql_async::OptsBuilder;
@@ -21,10 +21,15 @@ use {mysql_async as mysql, tokio_postgres as pgsql};
 
 use crate::error::{DatabaseError, DatabaseURLParseError};
 
+pub mod dns;
 pub mod error;
+pub mod tcp;
 
 #[allow(missing_docs)] // If we add docs they get added into --help binary text which is confusing
 #[derive(Debug, Clone, Parser, PartialEq, Eq, Serialize, Deserialize)]
+#[clap(group(
+    ArgGroup::new("upstream-db").args(&["upstream-db-url", "upstream-db-url-file"])
+))]
 pub struct UpstreamConfig {
     /// URL for the upstream database to connect to. Should include username and password if
     /// necessary
@@ -32,6 +37,13 @@ pub struct UpstreamConfig {
     #[serde(default)]
     pub upstream_db_url: Option<RedactedString>,
 
+    /// Path to a file containing the URL for the upstream database to connect to, such as a
+    /// mounted Kubernetes secret, to avoid passing credentials on the command line or in the
+    /// environment. Read once at startup. Cannot be passed along with `--upstream-db-url`.
+    #[clap(long, env = "UPSTREAM_DB_URL_FILE")]
+    #[serde(default)]
+    pub upstream_db_url_file: Option<PathBuf>,
+
     /// Disable verification of SSL certificates supplied by the upstream database (postgres
     /// only, ignored for mysql). Ignored if `--upstream-db-url` is not passed.
     ///
@@ -81,6 +93,12 @@ pub struct UpstreamConfig {
     #[clap(long, default_value = "50")]
     #[serde(default)]
     pub replication_pool_size: usize,
+
+    /// If set, resolve the upstream database's hostname once and reuse the resulting addresses
+    /// for new upstream connections, resolving it again every this many seconds.
+    #[clap(long, env = "UPSTREAM_DNS_REFRESH_SECONDS")]
+    #[serde(default)]
+    pub upstream_dns_refresh_seconds: Option<u64>,
 }
 
 impl UpstreamConfig {
@@ -102,6 +120,32 @@ impl UpstreamConfig {
         }
     }
 
+    /// If [`Self::upstream_db_url_file`] is set, read the upstream database URL from that file
+    /// into [`Self::upstream_db_url`], ignoring any leading or trailing whitespace.
+    pub fn load_upstream_db_url_file(&mut self) -> std::io::Result<()> {
+        let path = match &self.upstream_db_url_file {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let url = std::fs::read_to_string(path)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        let url = url.trim();
+        if url.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} is empty", path.display()),
+            ));
+        }
+        self.upstream_db_url = Some(url.to_owned().into());
+        Ok(())
+    }
+
+    /// Returns the interval at which cached DNS resolutions of the upstream database's hostname
+    /// should be refreshed, or `None` if they shouldn't be cached at all
+    pub fn upstream_dns_refresh_interval(&self) -> Option<Duration> {
+        self.upstream_dns_refresh_seconds.map(Duration::from_secs)
+    }
+
     pub fn from_url<S: AsRef<str>>(url: S) -> Self {
         UpstreamConfig {
             upstream_db_url: Some(url.as_ref().to_string().into()),
@@ -126,6 +170,7 @@ impl Default for UpstreamConfig {
     fn default() -> Self {
         Self {
             upstream_db_url: Default::default(),
+            upstream_db_url_file: None,
             disable_upstream_ssl_verification: false,
             disable_setup_ddl_replication: false,
             replication_server_id: Default::default(),
@@ -134,6 +179,32 @@ impl Default for UpstreamConfig {
             snapshot_report_interval_secs: 30,
             ssl_root_cert: None,
             replication_pool_size: 50,
+            upstream_dns_refresh_seconds: None,
+        }
+    }
+}
+
+/// What to do with a result set proxied from the upstream database which has more rows than the
+/// adapter's `--max-result-rows`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MaxResultRowsBehavior {
+    /// Return an error to the client
+    #[default]
+    Error,
+    /// Send only the first `--max-result-rows` rows to the client, and log a warning
+    Truncate,
+}
+
+impl FromStr for MaxResultRowsBehavior {
+    type Err = MaxResultRowsBehaviorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(Self::Error),
+            "truncate" => Ok(Self::Truncate),
+            _ => Err(MaxResultRowsBehaviorParseError {
+                value: s.to_owned(),
+            }),
         }
     }
 }
@@ -369,6 +440,15 @@ impl DatabaseURL {
         }
     }
 
+    /// Returns the host name and port for this database URL, if it connects over TCP to a single
+    /// host
+    pub fn tcp_host(&self) -> Option<(&str, u16)> {
+        match self {
+            DatabaseURL::MySQL(opts) => Some((opts.ip_or_hostname(), opts.tcp_port())),
+            DatabaseURL::PostgreSQL(config) => postgresql_tcp_host(config),
+        }
+    }
+
     /// Returns the underlying database name.
     pub fn db_name(&self) -> Option<&str> {
         match self {
@@ -392,6 +472,16 @@ impl DatabaseURL {
     }
 }
 
+/// Returns the host name and port that the given PostgreSQL config connects to, if it connects over
+/// TCP to a single host
+pub fn postgresql_tcp_host(config: &pgsql::Config) -> Option<(&str, u16)> {
+    match (config.get_hosts(), config.get_ports()) {
+        ([pgsql::config::Host::Tcp(host)], [port]) => Some((host.as_str(), *port)),
+        ([pgsql::config::Host::Tcp(host)], []) => Some((host.as_str(), 5432)),
+        _ => None,
+    }
+}
+
 /// An enum wrapper around either a MySQL or PostgreSQL connection.
 pub enum DatabaseConnection {
     /// A MySQL database connection.
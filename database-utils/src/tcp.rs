@@ -0,0 +1,184 @@
+//! Configuration of the TCP sockets used for client and upstream database connections.
+//!
+//! The TCP window scale of a connection is negotiated during its handshake, based on the size of
+//! the receive buffer at the time, so buffer sizes have to be set on a socket before it connects -
+//! or, for connections accepted from clients, on the listening socket they're accepted from, whose
+//! buffer sizes they inherit - to take full effect.
+
+use std::io;
+use std::net::SocketAddr;
+
+use readyset_tracing::{debug, warn};
+use socket2::SockRef;
+use tokio::net::{lookup_host, TcpListener, TcpSocket, TcpStream, ToSocketAddrs};
+
+/// The maximum number of pending connections on a listener bound with [`TcpBufferSizes::bind`],
+/// which matches the backlog used by [`TcpListener::bind`]
+const LISTEN_BACKLOG: u32 = 1024;
+
+/// Requested sizes for the kernel send and receive buffers of a TCP socket.
+///
+/// Sizes which are `None` are left at the operating system's default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TcpBufferSizes {
+    /// Requested size of the send buffer (`SO_SNDBUF`), in bytes
+    pub send: Option<usize>,
+    /// Requested size of the receive buffer (`SO_RCVBUF`), in bytes
+    pub recv: Option<usize>,
+}
+
+impl TcpBufferSizes {
+    /// Returns `true` if neither buffer size has been requested
+    pub fn is_default(&self) -> bool {
+        self.send.is_none() && self.recv.is_none()
+    }
+
+    /// Request the configured buffer sizes on the given socket.
+    ///
+    /// The operating system is free to adjust the requested sizes - for example, Linux doubles
+    /// them to allow for bookkeeping overhead and clamps them to `net.core.{w,r}mem_max`, and some
+    /// platforms ignore the request entirely. If the size the operating system reports back is
+    /// smaller than the one requested, a message is logged, but that isn't considered an error.
+    pub fn apply<S>(&self, socket: &S) -> io::Result<()>
+    where
+        for<'s> SockRef<'s>: From<&'s S>,
+    {
+        let socket = SockRef::from(socket);
+        if let Some(requested) = self.send {
+            socket.set_send_buffer_size(requested)?;
+            let actual = socket.send_buffer_size()?;
+            if actual < requested {
+                debug!(
+                    requested,
+                    actual, "Operating system reduced TCP send buffer size"
+                );
+            }
+        }
+        if let Some(requested) = self.recv {
+            socket.set_recv_buffer_size(requested)?;
+            let actual = socket.recv_buffer_size()?;
+            if actual < requested {
+                debug!(
+                    requested,
+                    actual, "Operating system reduced TCP receive buffer size"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a new socket for connecting to or listening on `addr`, with the configured buffer
+    /// sizes applied. Failing to apply the buffer sizes is logged, but isn't considered an error.
+    fn socket(&self, addr: SocketAddr) -> io::Result<TcpSocket> {
+        let socket = match addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        if let Err(error) = self.apply(&socket) {
+            warn!(%error, %addr, "Could not set TCP buffer sizes");
+        }
+        Ok(socket)
+    }
+
+    /// Open a TCP connection to `addrs` with the configured buffer sizes, which are set before
+    /// connecting.
+    ///
+    /// Like [`TcpStream::connect`], each of the addresses `addrs` resolves to is tried in turn, and
+    /// the error from the last one is returned if none of them can be connected to.
+    pub async fn connect<A: ToSocketAddrs>(&self, addrs: A) -> io::Result<TcpStream> {
+        let mut last_error = None;
+        for addr in lookup_host(addrs).await? {
+            match self.socket(addr)?.connect(addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any addresses",
+            )
+        }))
+    }
+
+    /// Bind a TCP listener to `addr` with the configured buffer sizes, which are set before the
+    /// socket starts listening so that connections accepted from it inherit them
+    pub fn bind(&self, addr: SocketAddr) -> io::Result<TcpListener> {
+        let socket = self.socket(addr)?;
+        // Matches the behavior of `TcpListener::bind`
+        #[cfg(unix)]
+        socket.set_reuseaddr(true)?;
+        socket.bind(addr)?;
+        socket.listen(LISTEN_BACKLOG)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    async fn connected_stream() -> TcpStream {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stream, _) = tokio::join!(TcpStream::connect(addr), listener.accept());
+        stream.unwrap()
+    }
+
+    #[tokio::test]
+    async fn default_leaves_sizes_alone() {
+        let stream = connected_stream().await;
+        let socket = SockRef::from(&stream);
+        let before = (
+            socket.send_buffer_size().unwrap(),
+            socket.recv_buffer_size().unwrap(),
+        );
+
+        TcpBufferSizes::default().apply(&stream).unwrap();
+        assert_eq!(
+            (
+                socket.send_buffer_size().unwrap(),
+                socket.recv_buffer_size().unwrap(),
+            ),
+            before
+        );
+    }
+
+    const SIZES: TcpBufferSizes = TcpBufferSizes {
+        send: Some(48 * 1024),
+        recv: Some(40 * 1024),
+    };
+
+    fn assert_sizes_applied<S>(socket: &S)
+    where
+        for<'s> SockRef<'s>: From<&'s S>,
+    {
+        let socket = SockRef::from(socket);
+        assert!(socket.send_buffer_size().unwrap() >= 48 * 1024);
+        assert!(socket.recv_buffer_size().unwrap() >= 40 * 1024);
+    }
+
+    // Linux always honors (and doubles) requests below `net.core.{w,r}mem_max`, which default to
+    // well over the sizes used here, so it's the one platform where we can check the result
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn sizes_are_applied() {
+        let stream = connected_stream().await;
+        SIZES.apply(&stream).unwrap();
+        assert_sizes_applied(&stream);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn sizes_are_set_before_connecting_and_listening() {
+        let listener = SIZES.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert_sizes_applied(&listener);
+
+        let (stream, accepted) = tokio::join!(SIZES.connect(addr), listener.accept());
+        assert_sizes_applied(&stream.unwrap());
+        // Accepted connections inherit the listener's buffer sizes
+        assert_sizes_applied(&accepted.unwrap().0);
+    }
+}
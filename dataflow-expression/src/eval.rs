@@ -369,12 +369,7 @@ mod tests {
     pub(crate) fn try_eval_expr(expr: &str, dialect: nom_sql::Dialect) -> ReadySetResult<DfValue> {
         let ast = expr_unwrap(parse_expr(dialect, expr), expr);
 
-        let expr_dialect = match dialect {
-            PostgreSQL => crate::Dialect::DEFAULT_POSTGRESQL,
-            MySQL => crate::Dialect::DEFAULT_MYSQL,
-        };
-
-        expr_unwrap(Expr::lower(ast, expr_dialect, no_op_lower_context()), expr)
+        expr_unwrap(Expr::lower(ast, dialect.into(), no_op_lower_context()), expr)
             .eval::<DfValue>(&[])
     }
 
@@ -27,6 +27,9 @@ impl Health {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum State {
     Healthy,
+    /// Still serving, but some component is failing repeatedly and the process may soon need to
+    /// be restarted
+    Degraded,
     Unhealthy,
     ShuttingDown,
     Unknown,
@@ -36,6 +39,7 @@ impl Display for State {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
             State::Healthy => "healthy",
+            State::Degraded => "degraded",
             State::Unhealthy => "unhealthy",
             State::ShuttingDown => "shutting down",
             State::Unknown => "unknown",
@@ -90,6 +94,22 @@ impl HealthReporter {
         let new_health = Health::new(new_state);
         *self.health.write() = new_health;
     }
+
+    /// Atomically updates the state of the HealthReporter to `to`, but only if the current state
+    /// is `from`. Returns `true` if the state was updated.
+    ///
+    /// This allows a component to report its own health without clobbering a state set by some
+    /// other component, such as [`State::ShuttingDown`].
+    pub fn transition(&mut self, from: State, to: State) -> bool {
+        let mut health = self.health.write();
+        if health.state != from {
+            return false;
+        }
+        if from != to {
+            *health = Health::new(to);
+        }
+        true
+    }
 }
 
 #[cfg(test)]
@@ -128,4 +148,17 @@ mod tests {
         let second = reporter.health().transition_time;
         assert_eq!(first, second);
     }
+
+    #[test]
+    fn transition_only_from_expected_state() {
+        let mut reporter = HealthReporter::new();
+        reporter.set_state(State::Healthy);
+
+        assert!(reporter.transition(State::Healthy, State::Degraded));
+        assert_eq!(reporter.state(), State::Degraded);
+
+        reporter.set_state(State::ShuttingDown);
+        assert!(!reporter.transition(State::Degraded, State::Healthy));
+        assert_eq!(reporter.state(), State::ShuttingDown);
+    }
 }
@@ -17,6 +17,7 @@ pub struct ClientHandshake<'a> {
     pub password: &'a [u8],
     pub database: Option<&'a str>,
     pub auth_plugin_name: Option<&'a str>,
+    pub connect_attributes: Vec<(&'a str, &'a str)>,
 }
 
 /// Parse a "length-encoded integer" as specified by the [mysql binary protocol documentation][docs]
@@ -52,6 +53,30 @@ fn null_terminated_string(i: &[u8]) -> IResult<&[u8], &str> {
     Ok((i, res))
 }
 
+/// Parse a "length-encoded string" as specified by the [mysql binary protocol
+/// documentation][docs]
+///
+/// [docs]: https://dev.mysql.com/doc/internals/en/string.html#packet-Protocol::LengthEncodedString
+fn lenenc_str(i: &[u8]) -> IResult<&[u8], &str> {
+    let (i, len) = lenenc_int(i)?;
+    map_res(take(len as usize), parse_bytes_to_string)(i)
+}
+
+/// Parse the key-value connection attributes sent by clients with the `CLIENT_CONNECT_ATTRS`
+/// capability at the end of the handshake response
+fn connect_attributes(i: &[u8]) -> IResult<&[u8], Vec<(&str, &str)>> {
+    let (i, len) = lenenc_int(i)?;
+    let (i, mut attrs) = take(len as usize)(i)?;
+    let mut res = vec![];
+    while !attrs.is_empty() {
+        let (rest, key) = lenenc_str(attrs)?;
+        let (rest, value) = lenenc_str(rest)?;
+        res.push((key, value));
+        attrs = rest;
+    }
+    Ok((i, res))
+}
+
 /// <https://dev.mysql.com/doc/internals/en/connection-phase-packets.html#packet-Protocol::HandshakeResponse41>
 pub fn client_handshake(i: &[u8]) -> IResult<&[u8], ClientHandshake<'_>> {
     let (i, capabilities) = map(le_u32, CapabilityFlags::from_bits_truncate)(i)?;
@@ -82,6 +107,14 @@ pub fn client_handshake(i: &[u8]) -> IResult<&[u8], ClientHandshake<'_>> {
         (i, None)
     };
 
+    // Connection attributes are purely informational, so don't fail the handshake if they're
+    // malformed
+    let (i, connect_attributes) = if capabilities.contains(CapabilityFlags::CLIENT_CONNECT_ATTRS) {
+        map(opt(connect_attributes), Option::unwrap_or_default)(i)?
+    } else {
+        (i, vec![])
+    };
+
     Ok((
         i,
         ClientHandshake {
@@ -92,6 +125,7 @@ pub fn client_handshake(i: &[u8]) -> IResult<&[u8], ClientHandshake<'_>> {
             password,
             database,
             auth_plugin_name,
+            connect_attributes,
         },
     ))
 }
@@ -114,6 +148,7 @@ pub enum Command<'a> {
         param: u16,
         data: &'a [u8],
     },
+    ProcessKill(u32),
     Ping,
     Quit,
 }
@@ -173,6 +208,10 @@ pub fn parse(i: &[u8]) -> IResult<&[u8], Command<'_>> {
             preceded(tag(&[CommandByte::COM_STMT_CLOSE as u8]), le_u32),
             Command::Close,
         ),
+        map(
+            preceded(tag(&[CommandByte::COM_PROCESS_KILL as u8]), le_u32),
+            Command::ProcessKill,
+        ),
         map(tag(&[CommandByte::COM_QUIT as u8]), |_| Command::Quit),
         map(tag(&[CommandByte::COM_PING as u8]), |_| Command::Ping),
     ))(i)
@@ -215,6 +254,52 @@ mod tests {
         assert_eq!(handshake.maxps, 16777216);
     }
 
+    #[test]
+    fn it_parses_handshake_connect_attributes() {
+        let capabilities = CapabilityFlags::CLIENT_PROTOCOL_41
+            | CapabilityFlags::CLIENT_SECURE_CONNECTION
+            | CapabilityFlags::CLIENT_PLUGIN_AUTH
+            | CapabilityFlags::CLIENT_CONNECT_ATTRS;
+        let mut data = vec![];
+        data.extend_from_slice(&capabilities.bits().to_le_bytes());
+        data.extend_from_slice(&16777216u32.to_le_bytes());
+        data.push(0x21);
+        data.extend_from_slice(&[0; 23]);
+        data.extend_from_slice(b"jon\0");
+        data.push(0); // empty auth response
+        data.extend_from_slice(b"mysql_native_password\0");
+        let attrs = b"\x0c_client_name\x08libmysql\x0cprogram_name\x05mysql";
+        data.push(attrs.len() as u8);
+        data.extend_from_slice(attrs);
+
+        let (_, handshake) = client_handshake(&data).unwrap();
+        assert_eq!(handshake.username, "jon");
+        assert_eq!(handshake.auth_plugin_name, Some("mysql_native_password"));
+        assert_eq!(
+            handshake.connect_attributes,
+            vec![("_client_name", "libmysql"), ("program_name", "mysql")]
+        );
+    }
+
+    #[test]
+    fn it_ignores_malformed_connect_attributes() {
+        let capabilities = CapabilityFlags::CLIENT_PROTOCOL_41
+            | CapabilityFlags::CLIENT_SECURE_CONNECTION
+            | CapabilityFlags::CLIENT_CONNECT_ATTRS;
+        let mut data = vec![];
+        data.extend_from_slice(&capabilities.bits().to_le_bytes());
+        data.extend_from_slice(&16777216u32.to_le_bytes());
+        data.push(0x21);
+        data.extend_from_slice(&[0; 23]);
+        data.extend_from_slice(b"jon\0");
+        data.push(0); // empty auth response
+        data.extend_from_slice(b"\x10\x0cprogram_name");
+
+        let (_, handshake) = client_handshake(&data).unwrap();
+        assert_eq!(handshake.username, "jon");
+        assert!(handshake.connect_attributes.is_empty());
+    }
+
     #[tokio::test]
     async fn it_parses_request() {
         let data = &[
@@ -232,6 +317,13 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_parses_process_kill() {
+        let data = &[0x0c, 0x2a, 0x00, 0x00, 0x00];
+        let (_, cmd) = parse(data).unwrap();
+        assert_eq!(cmd, Command::ProcessKill(42));
+    }
+
     #[tokio::test]
     async fn it_handles_list_fields() {
         // mysql_list_fields (CommandByte::COM_FIELD_LIST / 0x04) has been deprecated in mysql 5.7
@@ -164,7 +164,9 @@ use std::io;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use constants::{CLIENT_PLUGIN_AUTH, PROTOCOL_41, RESERVED, SECURE_CONNECTION};
+use constants::{
+    CLIENT_PLUGIN_AUTH, COMPRESS, CONNECT_ATTRS, PROTOCOL_41, RESERVED, SECURE_CONNECTION,
+};
 use error::{other_error, OtherErrorKind};
 use mysql_common::constants::CapabilityFlags;
 use readyset_data::DfType;
@@ -231,6 +233,10 @@ pub use crate::params::{ParamParser, ParamValue, Params};
 pub use crate::resultset::{InitWriter, QueryResultWriter, RowWriter, StatementMetaWriter};
 pub use crate::value::{ToMySqlValue, Value, ValueInner};
 
+/// The connection ID sent to clients in the initial handshake by [`MySqlShim`]s which don't
+/// override [`MySqlShim::connection_id`]
+pub const DEFAULT_CONNECTION_ID: u32 = 8;
+
 /// Implementors of this trait can be used to drive a MySQL-compatible database backend.
 #[async_trait]
 pub trait MySqlShim<W: AsyncWrite + Unpin + Send> {
@@ -280,10 +286,42 @@ pub trait MySqlShim<W: AsyncWrite + Unpin + Send> {
     /// If the user doesn't exist, return [`None`].
     fn password_for_username(&self, username: &str) -> Option<Vec<u8>>;
 
+    /// Called during the handshake with the connection attributes sent by the client (such as
+    /// `program_name`), if any
+    fn on_connect_attributes(&mut self, _attributes: &[(&str, &str)]) {}
+
+    /// Called once the client has successfully authenticated as the user with the given username
+    fn on_authenticated(&mut self, _username: &str) {}
+
+    /// Called after each command from the client has been handled, and its response written
+    fn on_command_complete(&mut self) {}
+
     /// Return false if password checking should be skipped entirely
     fn require_authentication(&self) -> bool {
         true
     }
+
+    /// Return true to advertise support for the compressed protocol to clients. Connections from
+    /// clients which request compression (by setting `CLIENT_COMPRESS`) switch to the compressed
+    /// protocol once they've authenticated.
+    fn enable_protocol_compression(&self) -> bool {
+        false
+    }
+
+    /// The connection ID to send to the client in the initial handshake, which clients use to
+    /// identify this connection in `KILL QUERY` statements and `COM_PROCESS_KILL` commands
+    fn connection_id(&self) -> u32 {
+        DEFAULT_CONNECTION_ID
+    }
+
+    /// Called when the client sends a `COM_PROCESS_KILL` command to kill the connection with the
+    /// given `connection_id`.
+    ///
+    /// Returns `true` if a connection with the given ID was found, and the client is allowed to
+    /// kill it.
+    async fn on_process_kill(&mut self, _connection_id: u32) -> bool {
+        false
+    }
 }
 
 /// Stores a preencoded result schema for a prepared MySQL statement
@@ -346,13 +384,18 @@ struct StatementData {
     params: u16,
 }
 
-const CAPABILITIES: u32 = PROTOCOL_41 | SECURE_CONNECTION | RESERVED | CLIENT_PLUGIN_AUTH;
+const CAPABILITIES: u32 =
+    PROTOCOL_41 | SECURE_CONNECTION | RESERVED | CLIENT_PLUGIN_AUTH | CONNECT_ATTRS;
 
 impl<B: MySqlShim<W> + Send, R: AsyncRead + Unpin, W: AsyncWrite + Unpin + Send>
     MySqlIntermediary<B, R, W>
 {
     /// Create a new server over two one-way channels and process client commands until the client
     /// disconnects or an error occurs.
+    ///
+    /// If reading the next command from `reader` fails with [`io::ErrorKind::ConnectionAborted`],
+    /// the server is assumed to be shutting down (or closing this connection): the client is sent
+    /// an `ER_SERVER_SHUTDOWN` error, with the read error's message, and the connection is closed.
     pub async fn run_on(shim: B, reader: R, writer: W) -> Result<(), io::Error> {
         let r = packet::PacketReader::new(reader);
         let w = packet::PacketWriter::new(writer);
@@ -384,19 +427,24 @@ impl<B: MySqlShim<W> + Send, R: AsyncRead + Unpin, W: AsyncWrite + Unpin + Send>
     async fn init(&mut self) -> Result<(bool, Option<String>), io::Error> {
         let auth_data =
             generate_auth_data().map_err(|_| other_error(OtherErrorKind::AuthDataErr))?;
+        let capabilities = if self.shim.enable_protocol_compression() {
+            CAPABILITIES | COMPRESS
+        } else {
+            CAPABILITIES
+        };
 
         let mut init_packet = Vec::with_capacity(
             1 + 16 + 4 + 8 + 1 + 2 + 1 + 2 + 2 + 1 + 6 + 4 + 12 + 1 + AUTH_PLUGIN_NAME.len() + 1,
         );
         init_packet.extend_from_slice(&[10]); // protocol 10
         init_packet.extend_from_slice(self.shim.version().as_bytes());
-        init_packet.extend_from_slice(&[0x08, 0x00, 0x00, 0x00]); // TODO: connection ID
+        init_packet.extend_from_slice(&self.shim.connection_id().to_le_bytes());
         init_packet.extend_from_slice(&auth_data[..8]);
         init_packet.push(0);
-        init_packet.extend_from_slice(&CAPABILITIES.to_le_bytes()[..2]);
+        init_packet.extend_from_slice(&capabilities.to_le_bytes()[..2]);
         init_packet.extend_from_slice(&[0x21]); // UTF8_GENERAL_CI
         init_packet.extend_from_slice(&[0x00, 0x00]); // status flags
-        init_packet.extend_from_slice(&CAPABILITIES.to_le_bytes()[2..]);
+        init_packet.extend_from_slice(&capabilities.to_le_bytes()[2..]);
         init_packet.extend_from_slice(&[auth_data.len() as u8]);
         init_packet.extend_from_slice(&[0x00; 10][..]); // filler
         init_packet.extend_from_slice(&auth_data[8..]);
@@ -442,6 +490,13 @@ impl<B: MySqlShim<W> + Send, R: AsyncRead + Unpin, W: AsyncWrite + Unpin + Send>
         let password = handshake.password.to_vec();
         let database = handshake.database.map(String::from);
         let client_auth_plugin = handshake.auth_plugin_name.map(|s| s.to_owned());
+        let client_compression = handshake
+            .capabilities
+            .contains(CapabilityFlags::CLIENT_COMPRESS);
+        if !handshake.connect_attributes.is_empty() {
+            self.shim
+                .on_connect_attributes(&handshake.connect_attributes);
+        }
 
         let handshake_password = if client_auth_plugin.iter().all(|apn| apn != AUTH_PLUGIN_NAME)
             // Some clients (at the very least certain versions of PHP's MySQL PDO library) send an
@@ -509,6 +564,7 @@ impl<B: MySqlShim<W> + Send, R: AsyncRead + Unpin, W: AsyncWrite + Unpin + Send>
 
         if auth_success {
             debug!(%username, "Successfully authenticated client");
+            self.shim.on_authenticated(&username);
             writers::write_ok_packet(&mut self.writer, 0, 0, StatusFlags::empty()).await?;
         } else {
             debug!(%username, ?client_auth_plugin, "Received incorrect password");
@@ -521,6 +577,13 @@ impl<B: MySqlShim<W> + Send, R: AsyncRead + Unpin, W: AsyncWrite + Unpin + Send>
         }
         self.writer.flush().await?;
 
+        // The compressed protocol starts with the first command after authentication succeeds
+        if auth_success && capabilities & COMPRESS != 0 && client_compression {
+            debug!("Client requested compression, switching to compressed protocol");
+            self.reader.enable_compression();
+            self.writer.enable_compression();
+        }
+
         Ok((auth_success, database))
     }
 
@@ -528,8 +591,32 @@ impl<B: MySqlShim<W> + Send, R: AsyncRead + Unpin, W: AsyncWrite + Unpin + Send>
         use crate::commands::Command;
 
         let mut stmts: HashMap<u32, _> = HashMap::new();
-        while let Some((seq, packet)) = self.reader.next().await? {
+        loop {
+            let (seq, packet) = match self.reader.next().await {
+                Ok(Some(packet)) => packet,
+                Ok(None) => break,
+                Err(error) if error.kind() == io::ErrorKind::ConnectionAborted => {
+                    // Reads are aborted while waiting for the next command when the server is
+                    // shutting down, so tell the client why its connection is being closed
+                    debug!(%error, "Closing aborted connection");
+                    self.writer.set_seq(0);
+                    self.writer.set_compressed_seq(0);
+                    writers::write_err(
+                        ErrorKind::ER_SERVER_SHUTDOWN,
+                        error.to_string().as_bytes(),
+                        &mut self.writer,
+                    )
+                    .await?;
+                    self.writer.flush().await?;
+                    return Ok(());
+                }
+                Err(error) => return Err(error),
+            };
             self.writer.set_seq(seq + 1);
+            if let Some(compressed_seq) = self.reader.compressed_seq() {
+                self.writer
+                    .set_compressed_seq(compressed_seq.wrapping_add(1));
+            }
             let cmd = commands::parse(&packet)
                 .map_err(|e| {
                     other_error(OtherErrorKind::GenericErr {
@@ -638,6 +725,19 @@ impl<B: MySqlShim<W> + Send, R: AsyncRead + Unpin, W: AsyncWrite + Unpin + Send>
                     writers::write_ok_packet(&mut self.writer, 0, 0, StatusFlags::empty()).await?;
                     self.writer.flush().await?;
                 }
+                Command::ProcessKill(connection_id) => {
+                    if self.shim.on_process_kill(connection_id).await {
+                        writers::write_ok_packet(&mut self.writer, 0, 0, StatusFlags::empty())
+                            .await?;
+                    } else {
+                        writers::write_err(
+                            ErrorKind::ER_NO_SUCH_THREAD,
+                            format!("Unknown thread id: {connection_id}").as_bytes(),
+                            &mut self.writer,
+                        )
+                        .await?;
+                    }
+                }
                 Command::ComSetOption(_) => {
                     // ReadySet already support multi-statement support for the MySQL protocol, so
                     // we can simply respond with ok. We parse an incoming query as multiple single
@@ -653,6 +753,7 @@ impl<B: MySqlShim<W> + Send, R: AsyncRead + Unpin, W: AsyncWrite + Unpin + Send>
             }
 
             self.writer.flush().await?;
+            self.shim.on_command_complete();
         }
 
         Ok(())
@@ -1,6 +1,9 @@
-use std::io::{self, IoSlice};
+use std::io::{self, IoSlice, Read, Write};
 use std::sync::Arc;
 
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use crate::error::{other_error, OtherErrorKind};
@@ -8,6 +11,13 @@ use crate::resultset::{MAX_POOL_ROWS, MAX_POOL_ROW_CAPACITY};
 
 const U24_MAX: usize = 16_777_215;
 
+/// Length of the header of a packet in the compressed protocol
+const COMPRESSED_HEADER_LEN: usize = 7;
+
+/// Payloads shorter than this are sent uncompressed in the compressed protocol, since compressing
+/// them isn't worth the overhead. Matches `MIN_COMPRESS_LENGTH` in the MySQL server.
+const MIN_COMPRESS_LENGTH: usize = 50;
+
 pub struct PacketWriter<W> {
     pub seq: u8,
     w: W,
@@ -15,6 +25,10 @@ pub struct PacketWriter<W> {
 
     /// Reusable packets
     preallocated: Vec<QueuedPacket>,
+
+    /// The sequence id of the next compressed packet, if the compressed protocol has been
+    /// negotiated
+    compressed_seq: Option<u8>,
 }
 
 /// Type for packets being enqueued in the packet writer.
@@ -55,6 +69,57 @@ async fn write_all_vectored<'a, W: AsyncWrite + Unpin>(
     }
 }
 
+/// Write `slices` to `w` in full, wrapping them in compressed packets starting at sequence id
+/// `compressed_seq` if the compressed protocol is in use.
+async fn write_packets<'a, W: AsyncWrite + Unpin>(
+    w: &'a mut W,
+    compressed_seq: &mut Option<u8>,
+    slices: &'a mut [IoSlice<'a>],
+) -> io::Result<()> {
+    match compressed_seq {
+        None => write_all_vectored(w, slices).await,
+        Some(seq) => {
+            let mut payload = Vec::with_capacity(slices.iter().map(|s| s.len()).sum());
+            for slice in slices.iter() {
+                payload.extend_from_slice(slice);
+            }
+            w.write_all(&compress_packets(seq, &payload)?).await
+        }
+    }
+}
+
+/// Wrap `payload`, a sequence of complete packets including their headers, in packets of the
+/// compressed protocol, starting at compressed sequence id `seq`, and return the bytes to write to
+/// the wire.
+///
+/// Each compressed packet consists of a 7 byte header - the 3 byte length of its payload, a 1 byte
+/// sequence id, and the 3 byte length of the payload before compression - followed by the
+/// zlib-compressed payload. Payloads which are too short to be worth compressing, or which don't
+/// get any smaller when compressed, are sent as-is with an uncompressed length of 0.
+fn compress_packets(seq: &mut u8, payload: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(payload.len() + COMPRESSED_HEADER_LEN);
+    for chunk in payload.chunks(U24_MAX) {
+        let compressed = if chunk.len() >= MIN_COMPRESS_LENGTH {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(chunk)?;
+            Some(encoder.finish()?).filter(|compressed| compressed.len() < chunk.len())
+        } else {
+            None
+        };
+
+        let (body, uncompressed_len) = match &compressed {
+            Some(compressed) => (&compressed[..], chunk.len()),
+            None => (chunk, 0),
+        };
+        out.extend_from_slice(&(body.len() as u32).to_le_bytes()[..3]);
+        out.push(*seq);
+        out.extend_from_slice(&(uncompressed_len as u32).to_le_bytes()[..3]);
+        out.extend_from_slice(body);
+        *seq = seq.wrapping_add(1);
+    }
+    Ok(out)
+}
+
 // Gets an IoSlice to each of the packets currently enqueued in `queue`.
 fn queued_packet_slices(queue: &[QueuedPacket]) -> Vec<IoSlice<'_>> {
     if queue.is_empty() {
@@ -82,6 +147,7 @@ impl<W: AsyncWrite + Unpin> PacketWriter<W> {
             w,
             queue: Vec::new(),
             preallocated: Vec::new(),
+            compressed_seq: None,
         }
     }
 
@@ -89,6 +155,19 @@ impl<W: AsyncWrite + Unpin> PacketWriter<W> {
         self.seq = seq;
     }
 
+    /// Switch to the compressed protocol for all subsequent writes
+    pub fn enable_compression(&mut self) {
+        self.compressed_seq = Some(0);
+    }
+
+    /// Set the sequence id of the next compressed packet. Has no effect if the compressed protocol
+    /// is not in use.
+    pub fn set_compressed_seq(&mut self, seq: u8) {
+        if let Some(compressed_seq) = &mut self.compressed_seq {
+            *compressed_seq = seq;
+        }
+    }
+
     /// Flushes the writer. This function *must* be called before dropping the internal writer
     /// or writes may be lossed.
     pub async fn flush(&mut self) -> Result<(), tokio::io::Error> {
@@ -131,7 +210,7 @@ impl<W: AsyncWrite + Unpin> PacketWriter<W> {
     pub async fn write_queued_packets(&mut self) -> Result<(), tokio::io::Error> {
         let mut slices = queued_packet_slices(&self.queue);
         if !slices.is_empty() {
-            write_all_vectored(&mut self.w, &mut slices).await?;
+            write_packets(&mut self.w, &mut self.compressed_seq, &mut slices).await?;
             self.return_queued_to_pool();
         }
 
@@ -172,7 +251,7 @@ impl<W: AsyncWrite + Unpin> PacketWriter<W> {
             }
         }
 
-        write_all_vectored(&mut self.w, &mut slices).await?;
+        write_packets(&mut self.w, &mut self.compressed_seq, &mut slices).await?;
         self.return_queued_to_pool();
 
         Ok(())
@@ -207,7 +286,7 @@ impl<W: AsyncWrite + Unpin> PacketWriter<W> {
             IoSlice::new(packet),
         ]);
 
-        write_all_vectored(&mut self.w, &mut slices).await?;
+        write_packets(&mut self.w, &mut self.compressed_seq, &mut slices).await?;
 
         self.seq = self.seq.wrapping_add(1);
         Ok(())
@@ -232,6 +311,10 @@ pub struct PacketReader<R> {
     start: usize,
     remaining: usize,
     r: R,
+
+    /// The sequence id of the last compressed packet read, if the compressed protocol has been
+    /// negotiated
+    compressed_seq: Option<u8>,
 }
 
 impl<R> PacketReader<R> {
@@ -241,8 +324,58 @@ impl<R> PacketReader<R> {
             start: 0,
             remaining: 0,
             r,
+            compressed_seq: None,
         }
     }
+
+    /// Switch to the compressed protocol for all subsequent reads
+    pub fn enable_compression(&mut self) {
+        self.compressed_seq = Some(0);
+    }
+
+    /// The sequence id of the last compressed packet read, or `None` if the compressed protocol is
+    /// not in use
+    pub fn compressed_seq(&self) -> Option<u8> {
+        self.compressed_seq
+    }
+}
+
+/// Read a single packet of the compressed protocol from `r`, appending its decompressed payload to
+/// `buf` and returning its sequence id along with the number of bytes appended, or `None` if `r`
+/// is at EOF.
+async fn read_compressed_packet<R: AsyncRead + Unpin>(
+    r: &mut R,
+    buf: &mut Vec<u8>,
+) -> io::Result<Option<(u8, usize)>> {
+    let mut header = [0u8; COMPRESSED_HEADER_LEN];
+    let read = r.read(&mut header).await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    r.read_exact(&mut header[read..]).await?;
+
+    let compressed_len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+    let seq = header[3];
+    let uncompressed_len = u32::from_le_bytes([header[4], header[5], header[6], 0]) as usize;
+
+    let mut payload = vec![0; compressed_len];
+    r.read_exact(&mut payload).await?;
+    if uncompressed_len == 0 {
+        buf.extend_from_slice(&payload);
+        return Ok(Some((seq, compressed_len)));
+    }
+
+    let read = ZlibDecoder::new(&payload[..]).read_to_end(buf)?;
+    if read != uncompressed_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "compressed packet decompressed to {} bytes, expected {}",
+                read, uncompressed_len
+            ),
+        ));
+    }
+    Ok(Some((seq, read)))
 }
 
 impl<R: AsyncRead + Unpin> PacketReader<R> {
@@ -284,19 +417,30 @@ impl<R: AsyncRead + Unpin> PacketReader<R> {
             self.bytes.drain(0..self.start);
             self.start = 0;
             let end = self.bytes.len();
-            let new_len = std::cmp::max(4096, end * 2);
-            self.bytes.resize(new_len, 0);
-            let read = {
-                let buf = self.bytes.get_mut(end..).ok_or_else(|| {
-                    other_error(OtherErrorKind::IndexErr {
-                        data: "self.bytes".to_string(),
-                        index: end,
-                        length: new_len,
-                    })
-                })?;
-                self.r.read(buf).await?
+            let read = if self.compressed_seq.is_some() {
+                match read_compressed_packet(&mut self.r, &mut self.bytes).await? {
+                    Some((seq, read)) => {
+                        self.compressed_seq = Some(seq);
+                        read
+                    }
+                    None => 0,
+                }
+            } else {
+                let new_len = std::cmp::max(4096, end * 2);
+                self.bytes.resize(new_len, 0);
+                let read = {
+                    let buf = self.bytes.get_mut(end..).ok_or_else(|| {
+                        other_error(OtherErrorKind::IndexErr {
+                            data: "self.bytes".to_string(),
+                            index: end,
+                            length: new_len,
+                        })
+                    })?;
+                    self.r.read(buf).await?
+                };
+                self.bytes.truncate(end + read);
+                read
             };
-            self.bytes.truncate(end + read);
             self.remaining = self.bytes.len();
 
             if read == 0 {
@@ -462,6 +606,64 @@ mod tests {
         assert_eq!(&p.1[U24_MAX..], &[0x10]);
     }
 
+    #[tokio::test]
+    async fn compressed_round_trip() {
+        let (u_out, u_in) = tokio::net::UnixStream::pair().unwrap();
+
+        let packets = vec![
+            vec![0u8; 10],
+            b"SELECT * FROM t WHERE a = 1".repeat(1000),
+            vec![1u8; 100_000],
+            vec![2u8; 30],
+        ];
+        let uncompressed_len = packets.iter().map(|p| p.len() + 4).sum::<usize>();
+
+        let p = packets.clone();
+        tokio::spawn(async move {
+            let mut writer = PacketWriter::new(u_out);
+            writer.enable_compression();
+            writer.set_compressed_seq(1);
+
+            writer.enqueue_packet(p[0].clone());
+            writer.enqueue_packet(p[1].clone());
+            writer.write_queued_packets().await.unwrap();
+            writer.write_packet(&p[2]).await.unwrap();
+            writer.write_packet(&p[3]).await.unwrap();
+            writer.flush().await.unwrap();
+        });
+
+        let mut raw = Vec::new();
+        let mut u_in = u_in;
+        u_in.read_to_end(&mut raw).await.unwrap();
+        assert!(raw.len() < uncompressed_len / 10);
+        assert_eq!(raw[3], 1);
+
+        let mut reader = PacketReader::new(&raw[..]);
+        reader.enable_compression();
+        for (seq, encoded) in packets.iter().enumerate() {
+            let decoded = reader.next().await.unwrap().unwrap();
+            assert_eq!(decoded.0, seq as u8);
+            assert_eq!(&decoded.1[..], encoded);
+        }
+        assert!(reader.next().await.unwrap().is_none());
+        assert_eq!(reader.compressed_seq(), Some(3));
+    }
+
+    #[test]
+    fn short_and_incompressible_payloads_sent_uncompressed() {
+        let mut seq = 0;
+        let short = [7u8; MIN_COMPRESS_LENGTH - 1];
+        let out = compress_packets(&mut seq, &short).unwrap();
+        assert_eq!(&out[..7], &[49, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&out[7..], &short[..]);
+
+        let incompressible = (0..=255u8).collect::<Vec<_>>();
+        let out = compress_packets(&mut seq, &incompressible).unwrap();
+        assert_eq!(&out[..7], &[0, 1, 0, 1, 0, 0, 0]);
+        assert_eq!(&out[7..], &incompressible[..]);
+        assert_eq!(seq, 2);
+    }
+
     #[tokio::test]
     #[slow]
     async fn test_large_packet_write() {
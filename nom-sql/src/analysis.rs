@@ -83,6 +83,10 @@ impl<'a> ReferredColumnsIter<'a> {
             }),
             Expr::NestedSelect(_) => None,
             Expr::Variable(_) => None,
+            Expr::WindowFunction { function, window } => {
+                self.exprs_to_visit.extend(window.expressions());
+                self.visit_function_expression(function)
+            }
         }
     }
 
@@ -192,6 +196,10 @@ impl<'a> ReferredColumnsMut<'a> {
             }),
             Expr::NestedSelect(_) => None,
             Expr::Variable(_) => None,
+            Expr::WindowFunction { function, window } => {
+                self.exprs_to_visit.extend(window.expressions_mut());
+                self.visit_function_expression(function)
+            }
         }
     }
 
@@ -382,6 +390,12 @@ pub fn contains_aggregate(expr: &Expr) -> bool {
         }
         Expr::Array(exprs) => exprs.iter().any(contains_aggregate),
         Expr::Variable(_) => false,
+        // Window functions are evaluated per-row, after aggregation, so only count as aggregates
+        // if they contain an aggregate themselves
+        Expr::WindowFunction { function, window } => function
+            .arguments()
+            .chain(window.expressions())
+            .any(contains_aggregate),
     }
 }
 
@@ -467,6 +481,9 @@ impl Expr {
                 ..
             } => Box::new(iter::once(lhs.as_ref())) as _,
             Expr::Array(exprs) => Box::new(exprs.iter()),
+            Expr::WindowFunction { function, window } => {
+                Box::new(function.arguments().chain(window.expressions())) as _
+            }
         }
     }
 
@@ -14,17 +14,20 @@ use crate::create_table_options::CreateTableOption;
 use crate::rename::{RenameTableOperation, RenameTableStatement};
 use crate::select::LimitClause;
 use crate::set::Variable;
-use crate::transaction::{CommitStatement, RollbackStatement, StartTransactionStatement};
+use crate::transaction::{
+    CommitStatement, RollbackStatement, SavepointStatement, StartTransactionStatement,
+};
 use crate::{
     AlterColumnOperation, AlterTableDefinition, AlterTableStatement, CacheInner, CaseWhenBranch,
     Column, ColumnConstraint, ColumnSpecification, CommonTableExpr, CompoundSelectStatement,
     CreateCacheStatement, CreateTableStatement, CreateViewStatement, DeleteStatement,
     DropAllCachesStatement, DropCacheStatement, DropTableStatement, DropViewStatement,
     ExplainStatement, Expr, FieldDefinitionExpr, FieldReference, FunctionExpr, GroupByClause,
-    InValue, InsertStatement, JoinClause, JoinConstraint, JoinRightSide, Literal, OrderClause,
-    Relation, SelectSpecification, SelectStatement, SetNames, SetPostgresParameter, SetStatement,
-    SetVariables, ShowStatement, SqlIdentifier, SqlQuery, SqlType, TableExpr, TableExprInner,
-    TableKey, UpdateStatement, UseStatement,
+    InValue, InsertStatement, JoinClause, JoinConstraint, JoinRightSide, Literal, OnConflictAction,
+    OnConflictTarget, OrderClause, Relation, SelectSpecification, SelectStatement, SetNames,
+    SetPostgresParameter, SetStatement, SetVariables, ShowStatement, SqlIdentifier, SqlQuery,
+    SqlType, TableExpr, TableExprInner, TableKey, UpdateStatement, UseStatement, WindowFrameBound,
+    WindowSpecification,
 };
 
 /// Each method of the `Visitor` trait is a hook to be potentially overridden when recursively
@@ -120,6 +123,13 @@ pub trait Visitor<'ast>: Sized {
         walk_in_value(self, in_value)
     }
 
+    fn visit_window_specification(
+        &mut self,
+        window: &'ast WindowSpecification,
+    ) -> Result<(), Self::Error> {
+        walk_window_specification(self, window)
+    }
+
     fn visit_expr(&mut self, expr: &'ast Expr) -> Result<(), Self::Error> {
         walk_expr(self, expr)
     }
@@ -333,6 +343,13 @@ pub trait Visitor<'ast>: Sized {
         Ok(())
     }
 
+    fn visit_savepoint_statement(
+        &mut self,
+        savepoint_statement: &'ast SavepointStatement,
+    ) -> Result<(), Self::Error> {
+        self.visit_sql_identifier(&savepoint_statement.name)
+    }
+
     fn visit_rename_table_statement(
         &mut self,
         rename_table_statement: &'ast RenameTableStatement,
@@ -453,6 +470,10 @@ pub fn walk_expr<'ast, V: Visitor<'ast>>(
             Ok(())
         }
         Expr::Variable(var) => visitor.visit_variable(var),
+        Expr::WindowFunction { function, window } => {
+            visitor.visit_function_expr(function)?;
+            visitor.visit_window_specification(window)
+        }
     }
 }
 
@@ -495,6 +516,27 @@ pub fn walk_function_expr<'ast, V: Visitor<'ast>>(
     }
 }
 
+pub fn walk_window_specification<'ast, V: Visitor<'ast>>(
+    visitor: &mut V,
+    window: &'ast WindowSpecification,
+) -> Result<(), V::Error> {
+    for expr in &window.partition_by {
+        visitor.visit_expr(expr)?;
+    }
+    if let Some(order_by) = &window.order_by {
+        visitor.visit_order_clause(order_by)?;
+    }
+    if let Some(frame) = &window.frame {
+        for bound in std::iter::once(&frame.start).chain(frame.end.as_ref()) {
+            if let WindowFrameBound::Preceding(offset) | WindowFrameBound::Following(offset) = bound
+            {
+                visitor.visit_expr(offset.as_ref())?;
+            }
+        }
+    }
+    Ok(())
+}
+
 pub fn walk_in_value<'ast, V: Visitor<'ast>>(
     visitor: &mut V,
     in_value: &'ast InValue,
@@ -957,6 +999,28 @@ pub fn walk_insert_statement<'a, V: Visitor<'a>>(
         }
     }
 
+    if let Some(on_conflict) = &insert_statement.on_conflict {
+        if let Some(OnConflictTarget::Columns(columns)) = &on_conflict.target {
+            for column in columns {
+                visitor.visit_column(column)?;
+            }
+        }
+
+        if let OnConflictAction::DoUpdate {
+            assignments,
+            where_clause,
+        } = &on_conflict.action
+        {
+            for (column, expr) in assignments {
+                visitor.visit_column(column)?;
+                visitor.visit_expr(expr)?;
+            }
+            if let Some(where_clause) = where_clause {
+                visitor.visit_where_clause(where_clause)?;
+            }
+        }
+    }
+
     Ok(())
 }
 
@@ -1102,6 +1166,7 @@ pub fn walk_sql_query<'a, V: Visitor<'a>>(
         }
         SqlQuery::Commit(statement) => visitor.visit_commit_statement(statement),
         SqlQuery::Rollback(statement) => visitor.visit_rollback_statement(statement),
+        SqlQuery::Savepoint(statement) => visitor.visit_savepoint_statement(statement),
         SqlQuery::RenameTable(statement) => visitor.visit_rename_table_statement(statement),
         SqlQuery::CreateCache(statement) => visitor.visit_create_cache_statement(statement),
         SqlQuery::DropCache(statement) => visitor.visit_drop_cache_statement(statement),
@@ -14,17 +14,20 @@ use crate::create_table_options::CreateTableOption;
 use crate::rename::{RenameTableOperation, RenameTableStatement};
 use crate::select::LimitClause;
 use crate::set::Variable;
-use crate::transaction::{CommitStatement, RollbackStatement, StartTransactionStatement};
+use crate::transaction::{
+    CommitStatement, RollbackStatement, SavepointStatement, StartTransactionStatement,
+};
 use crate::{
     AlterColumnOperation, AlterTableDefinition, AlterTableStatement, CacheInner, CaseWhenBranch,
     Column, ColumnConstraint, ColumnSpecification, CommonTableExpr, CompoundSelectStatement,
     CreateCacheStatement, CreateTableStatement, CreateViewStatement, DeleteStatement,
     DropAllCachesStatement, DropCacheStatement, DropTableStatement, DropViewStatement,
     ExplainStatement, Expr, FieldDefinitionExpr, FieldReference, FunctionExpr, GroupByClause,
-    InValue, InsertStatement, JoinClause, JoinConstraint, JoinRightSide, Literal, OrderClause,
-    Relation, SelectSpecification, SelectStatement, SetNames, SetPostgresParameter, SetStatement,
-    SetVariables, ShowStatement, SqlIdentifier, SqlQuery, SqlType, TableExpr, TableExprInner,
-    TableKey, UpdateStatement, UseStatement,
+    InValue, InsertStatement, JoinClause, JoinConstraint, JoinRightSide, Literal, OnConflictAction,
+    OnConflictTarget, OrderClause, Relation, SelectSpecification, SelectStatement, SetNames,
+    SetPostgresParameter, SetStatement, SetVariables, ShowStatement, SqlIdentifier, SqlQuery,
+    SqlType, TableExpr, TableExprInner, TableKey, UpdateStatement, UseStatement, WindowFrameBound,
+    WindowSpecification,
 };
 
 /// Each method of the `VisitorMut` trait is a hook to be potentially overridden when recursively
@@ -123,6 +126,13 @@ pub trait VisitorMut<'ast>: Sized {
         walk_in_value(self, in_value)
     }
 
+    fn visit_window_specification(
+        &mut self,
+        window: &'ast mut WindowSpecification,
+    ) -> Result<(), Self::Error> {
+        walk_window_specification(self, window)
+    }
+
     fn visit_expr(&mut self, expr: &'ast mut Expr) -> Result<(), Self::Error> {
         walk_expr(self, expr)
     }
@@ -348,6 +358,13 @@ pub trait VisitorMut<'ast>: Sized {
         Ok(())
     }
 
+    fn visit_savepoint_statement(
+        &mut self,
+        savepoint_statement: &'ast mut SavepointStatement,
+    ) -> Result<(), Self::Error> {
+        self.visit_sql_identifier(&mut savepoint_statement.name)
+    }
+
     fn visit_rename_table_statement(
         &mut self,
         rename_table_statement: &'ast mut RenameTableStatement,
@@ -468,6 +485,10 @@ pub fn walk_expr<'ast, V: VisitorMut<'ast>>(
             Ok(())
         }
         Expr::Variable(var) => visitor.visit_variable(var),
+        Expr::WindowFunction { function, window } => {
+            visitor.visit_function_expr(function)?;
+            visitor.visit_window_specification(window)
+        }
     }
 }
 
@@ -510,6 +531,27 @@ pub fn walk_function_expr<'ast, V: VisitorMut<'ast>>(
     }
 }
 
+pub fn walk_window_specification<'ast, V: VisitorMut<'ast>>(
+    visitor: &mut V,
+    window: &'ast mut WindowSpecification,
+) -> Result<(), V::Error> {
+    for expr in &mut window.partition_by {
+        visitor.visit_expr(expr)?;
+    }
+    if let Some(order_by) = &mut window.order_by {
+        visitor.visit_order_clause(order_by)?;
+    }
+    if let Some(frame) = &mut window.frame {
+        for bound in std::iter::once(&mut frame.start).chain(frame.end.as_mut()) {
+            if let WindowFrameBound::Preceding(offset) | WindowFrameBound::Following(offset) = bound
+            {
+                visitor.visit_expr(offset.as_mut())?;
+            }
+        }
+    }
+    Ok(())
+}
+
 pub fn walk_in_value<'ast, V: VisitorMut<'ast>>(
     visitor: &mut V,
     in_value: &'ast mut InValue,
@@ -974,6 +1016,28 @@ pub fn walk_insert_statement<'a, V: VisitorMut<'a>>(
         }
     }
 
+    if let Some(on_conflict) = &mut insert_statement.on_conflict {
+        if let Some(OnConflictTarget::Columns(columns)) = &mut on_conflict.target {
+            for column in columns {
+                visitor.visit_column(column)?;
+            }
+        }
+
+        if let OnConflictAction::DoUpdate {
+            assignments,
+            where_clause,
+        } = &mut on_conflict.action
+        {
+            for (column, expr) in assignments {
+                visitor.visit_column(column)?;
+                visitor.visit_expr(expr)?;
+            }
+            if let Some(where_clause) = where_clause {
+                visitor.visit_where_clause(where_clause)?;
+            }
+        }
+    }
+
     Ok(())
 }
 
@@ -1119,6 +1183,7 @@ pub fn walk_sql_query<'a, V: VisitorMut<'a>>(
         }
         SqlQuery::Commit(statement) => visitor.visit_commit_statement(statement),
         SqlQuery::Rollback(statement) => visitor.visit_rollback_statement(statement),
+        SqlQuery::Savepoint(statement) => visitor.visit_savepoint_statement(statement),
         SqlQuery::RenameTable(statement) => visitor.visit_rename_table_statement(statement),
         SqlQuery::CreateCache(statement) => visitor.visit_create_cache_statement(statement),
         SqlQuery::DropCache(statement) => visitor.visit_drop_cache_statement(statement),
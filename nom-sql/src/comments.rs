@@ -0,0 +1,277 @@
+//! Extraction of the comments surrounding a SQL statement.
+//!
+//! Comments are otherwise discarded by the parser, but are commonly used by applications and ORMs
+//! to tag queries for attribution - for example `/* app:checkout */ SELECT ...`, or the
+//! [sqlcommenter](https://google.github.io/sqlcommenter/) format `SELECT ... /*app='checkout'*/`.
+
+use crate::Dialect;
+
+/// The comments appearing before and after the body of a single SQL statement.
+///
+/// The text of each comment is stored without its delimiters (`/*`, `*/`, `--`, or `#`), and with
+/// leading and trailing whitespace removed. Comments appearing in the middle of the statement are
+/// not recorded.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StatementComments {
+    /// Comments appearing before the first token of the statement
+    pub leading: Vec<String>,
+    /// Comments appearing after the last token of the statement (ignoring any terminating `;`)
+    pub trailing: Vec<String>,
+}
+
+impl StatementComments {
+    /// Extract the leading and trailing comments from the given query, according to the comment
+    /// and quoting rules of `dialect`.
+    ///
+    /// This never fails - if the query is malformed (for example, if it contains an unterminated
+    /// string or comment), whatever comments could be found before the malformed portion are
+    /// returned.
+    pub fn extract(dialect: Dialect, query: &str) -> Self {
+        let bytes = query.as_bytes();
+        let mut res = Self::default();
+        let mut pending = vec![];
+        let mut seen_token = false;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let rest = &bytes[i..];
+            if let Some((comment, len)) = comment(dialect, rest) {
+                let comment = String::from_utf8_lossy(comment).trim().to_owned();
+                if seen_token {
+                    pending.push(comment);
+                } else {
+                    res.leading.push(comment);
+                }
+                i += len;
+                continue;
+            }
+
+            match rest[0] {
+                b' ' | b'\t' | b'\r' | b'\n' | b';' => i += 1,
+                quote @ (b'\'' | b'"') => {
+                    seen_token = true;
+                    pending.clear();
+                    i += quoted_len(dialect, rest, quote);
+                }
+                b'`' if matches!(dialect, Dialect::MySQL | Dialect::SQLite) => {
+                    seen_token = true;
+                    pending.clear();
+                    i += quoted_len(dialect, rest, b'`');
+                }
+                _ => {
+                    seen_token = true;
+                    pending.clear();
+                    i += 1;
+                }
+            }
+        }
+
+        res.trailing = pending;
+        res
+    }
+
+    /// Returns `true` if the statement had neither leading nor trailing comments
+    pub fn is_empty(&self) -> bool {
+        self.leading.is_empty() && self.trailing.is_empty()
+    }
+
+    /// Look up the value of the tag with the given key in the statement's comments.
+    ///
+    /// Each comment is treated as a list of `key:value` or `key=value` pairs, separated by commas
+    /// or whitespace, with values optionally wrapped in single or double quotes. Leading comments
+    /// are searched before trailing comments, and the first match is returned.
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.leading
+            .iter()
+            .chain(&self.trailing)
+            .flat_map(|comment| comment.split(|c: char| c == ',' || c.is_whitespace()))
+            .find_map(|pair| {
+                let (k, v) = pair.split_once(|c| c == ':' || c == '=')?;
+                if k != key {
+                    return None;
+                }
+                let v = v
+                    .strip_prefix('\'')
+                    .and_then(|v| v.strip_suffix('\''))
+                    .or_else(|| v.strip_prefix('"').and_then(|v| v.strip_suffix('"')))
+                    .unwrap_or(v);
+                Some(v)
+            })
+    }
+
+    /// Look up the value of the directive with the given name in the statement's comments.
+    ///
+    /// A directive is a comment consisting entirely of `name: value`, such as
+    /// `/* readyset: proxy */`. The name is matched case-insensitively, and the value is returned
+    /// with surrounding whitespace removed. Leading comments are searched before trailing
+    /// comments, and the first match is returned.
+    pub fn directive(&self, name: &str) -> Option<&str> {
+        self.leading
+            .iter()
+            .chain(&self.trailing)
+            .find_map(|comment| {
+                let (n, v) = comment.split_once(':')?;
+                n.trim().eq_ignore_ascii_case(name).then_some(v.trim())
+            })
+    }
+}
+
+/// If `input` starts with a comment, returns the body of that comment and the total length of the
+/// comment including its delimiters
+fn comment(dialect: Dialect, input: &[u8]) -> Option<(&[u8], usize)> {
+    if input.starts_with(b"/*") {
+        // Postgres allows block comments to nest, MySQL does not
+        let mut depth = 1;
+        let mut i = 2;
+        while i < input.len() {
+            if input[i..].starts_with(b"*/") {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((&input[2..i], i + 2));
+                }
+                i += 2;
+            } else if dialect == Dialect::PostgreSQL && input[i..].starts_with(b"/*") {
+                depth += 1;
+                i += 2;
+            } else {
+                i += 1;
+            }
+        }
+        return Some((&input[2..], input.len()));
+    }
+
+    let body_start = if input.starts_with(b"--") {
+        // MySQL requires the `--` to be followed by whitespace or a control character
+        if dialect == Dialect::MySQL && input.get(2).map_or(false, |c| !c.is_ascii_whitespace()) {
+            return None;
+        }
+        2
+    } else if dialect == Dialect::MySQL && input.starts_with(b"#") {
+        1
+    } else {
+        return None;
+    };
+
+    let end = input
+        .iter()
+        .position(|&c| c == b'\n' || c == b'\r')
+        .unwrap_or(input.len());
+    Some((&input[body_start..end], end))
+}
+
+/// Returns the length of the quoted string or identifier at the start of `input`, including the
+/// quotes
+fn quoted_len(dialect: Dialect, input: &[u8], quote: u8) -> usize {
+    let mut i = 1;
+    while i < input.len() {
+        match input[i] {
+            b'\\' if dialect == Dialect::MySQL && quote != b'`' => i += 2,
+            c if c == quote => {
+                // A doubled quote is an escaped quote, not the end of the string
+                if input.get(i + 1) == Some(&quote) {
+                    i += 2;
+                } else {
+                    return i + 1;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    input.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leading_and_trailing() {
+        let comments = StatementComments::extract(
+            Dialect::MySQL,
+            "/* app:checkout */ -- first\n SELECT * FROM t /* x */ WHERE a = 1; # done",
+        );
+        assert_eq!(comments.leading, vec!["app:checkout", "first"]);
+        assert_eq!(comments.trailing, vec!["done"]);
+    }
+
+    #[test]
+    fn no_comments() {
+        let comments = StatementComments::extract(Dialect::PostgreSQL, "SELECT 1");
+        assert!(comments.is_empty());
+    }
+
+    #[test]
+    fn comment_markers_in_strings_are_ignored() {
+        let comments = StatementComments::extract(
+            Dialect::MySQL,
+            "SELECT '/* not a comment */', 'it''s -- also not', \"# nor this\"",
+        );
+        assert!(comments.is_empty());
+
+        let comments =
+            StatementComments::extract(Dialect::PostgreSQL, "SELECT \"a -- b\" FROM t /* c */");
+        assert!(comments.leading.is_empty());
+        assert_eq!(comments.trailing, vec!["c"]);
+    }
+
+    #[test]
+    fn dialect_specific_comments() {
+        // `#` is an operator, not a comment, in Postgres
+        let comments = StatementComments::extract(Dialect::PostgreSQL, "SELECT 1 # 2");
+        assert!(comments.is_empty());
+        let comments = StatementComments::extract(Dialect::MySQL, "SELECT 1 # 2");
+        assert_eq!(comments.trailing, vec!["2"]);
+
+        // MySQL requires whitespace after `--`
+        let comments = StatementComments::extract(Dialect::MySQL, "SELECT 1 --2");
+        assert!(comments.is_empty());
+        let comments = StatementComments::extract(Dialect::PostgreSQL, "SELECT 1 --2");
+        assert_eq!(comments.trailing, vec!["2"]);
+
+        // Postgres block comments nest
+        let comments =
+            StatementComments::extract(Dialect::PostgreSQL, "/* a /* b */ c */ SELECT 1");
+        assert_eq!(comments.leading, vec!["a /* b */ c"]);
+    }
+
+    #[test]
+    fn extract_tag() {
+        let comments =
+            StatementComments::extract(Dialect::MySQL, "/* app:checkout */ SELECT * FROM t");
+        assert_eq!(comments.tag("app"), Some("checkout"));
+        assert_eq!(comments.tag("route"), None);
+
+        let comments = StatementComments::extract(
+            Dialect::PostgreSQL,
+            "SELECT * FROM t /*route='/cart',app='checkout'*/",
+        );
+        assert_eq!(comments.tag("app"), Some("checkout"));
+        assert_eq!(comments.tag("route"), Some("/cart"));
+    }
+
+    #[test]
+    fn leading_tags_take_precedence() {
+        let comments =
+            StatementComments::extract(Dialect::MySQL, "/* app=first */ SELECT 1 /* app=second */");
+        assert_eq!(comments.tag("app"), Some("first"));
+    }
+
+    #[test]
+    fn extract_directive() {
+        let comments =
+            StatementComments::extract(Dialect::MySQL, "/* ReadySet: proxy */ SELECT * FROM t");
+        assert_eq!(comments.directive("readyset"), Some("proxy"));
+        assert_eq!(comments.directive("other"), None);
+
+        let comments = StatementComments::extract(
+            Dialect::PostgreSQL,
+            "/* app:checkout */ SELECT * FROM t -- readyset:cache",
+        );
+        assert_eq!(comments.directive("readyset"), Some("cache"));
+        assert_eq!(comments.directive("app"), Some("checkout"));
+
+        let comments =
+            StatementComments::extract(Dialect::MySQL, "/* not readyset: proxy */ SELECT 1");
+        assert_eq!(comments.directive("readyset"), None);
+    }
+}
@@ -817,9 +817,9 @@ pub fn field_reference(
     move |i| {
         match dialect {
             Dialect::PostgreSQL => map(expression(dialect), FieldReference::Expr)(i),
-            // Only MySQL supports numeric field references (postgresql considers them integer
-            // literals, I'm pretty sure)
-            Dialect::MySQL => alt((
+            // Only MySQL and SQLite support numeric field references (postgresql considers them
+            // integer literals, I'm pretty sure)
+            Dialect::MySQL | Dialect::SQLite => alt((
                 map(
                     map_res(
                         map_res(digit1, |i: LocatedSpan<&[u8]>| str::from_utf8(&i)),
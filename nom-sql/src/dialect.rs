@@ -1,33 +1,111 @@
+use std::borrow::Cow;
 use std::fmt;
 use std::str::{self, FromStr};
 
 use bit_vec::BitVec;
 use nom::branch::alt;
-use nom::bytes::complete::{tag, tag_no_case, take, take_while1};
-use nom::character::complete::char;
-use nom::character::is_alphanumeric;
-use nom::combinator::{map, map_res, not, opt, peek};
+use nom::bytes::complete::{
+    tag, tag_no_case, take, take_until, take_while, take_while1, take_while_m_n,
+};
+use nom::character::complete::{char, digit1};
+use nom::character::{is_alphabetic, is_alphanumeric, is_oct_digit};
+use nom::combinator::{map, map_res, not, opt, peek, recognize};
 use nom::error::ErrorKind;
 use nom::multi::fold_many0;
-use nom::sequence::{delimited, preceded};
+use nom::sequence::{delimited, pair, preceded, terminated};
 use nom_locate::LocatedSpan;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::keywords::{sql_keyword, sql_keyword_or_builtin_function, POSTGRES_NOT_RESERVED};
-use crate::literal::{raw_string_literal, QuotingStyle};
+use crate::keywords::{
+    sql_keyword, sql_keyword_or_builtin_function, KEYWORDS, POSTGRES_KEYWORDS,
+    POSTGRES_NOT_RESERVED,
+};
+use crate::literal::{raw_standard_string_literal, raw_string_literal, QuotingStyle};
 use crate::select::LimitClause;
-use crate::whitespace::whitespace0;
-use crate::{literal, NomSqlError, NomSqlResult, SqlIdentifier};
+use crate::whitespace::{whitespace0, whitespace1};
+use crate::{literal, ItemPlaceholder, NomSqlError, NomSqlResult, SqlIdentifier};
 
 #[inline]
 pub(crate) fn is_sql_identifier(chr: u8) -> bool {
     is_alphanumeric(chr) || chr == b'_'
 }
 
-/// Byte array literal value (PostgreSQL)
+/// Byte array literal value in the hex format (PostgreSQL), eg `E'\\x0102'::bytea` or
+/// `'\x0102'::bytea`
 fn raw_hex_bytes_psql(input: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Vec<u8>> {
-    delimited(tag("E'\\\\x"), hex_bytes, tag("'::bytea"))(input)
+    delimited(
+        alt((tag("E'\\\\x"), tag("'\\x"))),
+        hex_bytes,
+        tag("'::bytea"),
+    )(input)
+}
+
+/// Byte array literal value in the traditional escape format (PostgreSQL), eg
+/// `'\001\\abc'::bytea`.
+///
+/// Within an escape string (`E'...'`) each backslash in the bytea escape format is itself escaped,
+/// so the same value is written `E'\\001\\\\abc'::bytea`.
+fn raw_escape_bytes_psql(input: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Vec<u8>> {
+    alt((
+        delimited(tag("E'"), escaped_bytes("\\\\"), tag("'::bytea")),
+        delimited(tag("'"), escaped_bytes("\\"), tag("'::bytea")),
+    ))(input)
+}
+
+/// The content of a bytea literal in the escape format, in which each byte is either a literal
+/// character, an escaped backslash, or a backslash followed by its value as three octal digits.
+/// `backslash` is the sequence which represents a single backslash in the enclosing string.
+fn escaped_bytes(
+    backslash: &'static str,
+) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Vec<u8>> {
+    move |i| {
+        fold_many0(
+            alt((
+                map(preceded(tag(backslash), tag(backslash)), |_| vec![b'\\']),
+                map(preceded(tag(backslash), octal_byte), |b| vec![b]),
+                map(tag("''"), |_| vec![b'\'']),
+                map(
+                    take_while1(|c| c != b'\\' && c != b'\''),
+                    |s: LocatedSpan<&[u8]>| s.to_vec(),
+                ),
+            )),
+            Vec::new,
+            |mut acc: Vec<u8>, bytes: Vec<u8>| {
+                acc.extend(bytes);
+                acc
+            },
+        )(i)
+    }
+}
+
+/// A single byte written as three octal digits, eg `377`
+fn octal_byte(input: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], u8> {
+    map_res(
+        map_res(
+            take_while_m_n(3, 3, is_oct_digit),
+            |i: LocatedSpan<&[u8]>| str::from_utf8(*i),
+        ),
+        |s: &str| u8::from_str_radix(s, 8),
+    )(input)
+}
+
+/// Dollar-quoted string literal value (PostgreSQL), eg `$$text$$` or `$tag$text$tag$`.
+///
+/// The string ends at the first occurrence of the exact opening tag, and its content is taken
+/// verbatim, without processing any escape sequences.
+fn raw_dollar_quoted_string(input: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Vec<u8>> {
+    let (i, dollar_tag) = recognize(delimited(
+        char('$'),
+        opt(pair(
+            take_while_m_n(1, 1, |c| is_alphabetic(c) || c == b'_'),
+            take_while(is_sql_identifier),
+        )),
+        char('$'),
+    ))(input)?;
+    let (i, content) = take_until(*dollar_tag)(i)?;
+    let (i, _) = tag(*dollar_tag)(i)?;
+    Ok((i, content.to_vec()))
 }
 
 /// Blob literal value (MySQL)
@@ -35,6 +113,11 @@ fn raw_hex_bytes_mysql(input: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Vec<u8>
     delimited(tag("X'"), hex_bytes, tag("'"))(input)
 }
 
+/// Blob literal value (SQLite), eg `x'0102'` or `X'0102'`
+fn raw_hex_bytes_sqlite(input: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Vec<u8>> {
+    delimited(tag_no_case("x'"), hex_bytes, tag("'"))(input)
+}
+
 fn hex_bytes(input: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Vec<u8>> {
     fold_many0(
         map_res(take(2_usize), |i: LocatedSpan<&[u8]>| hex::decode(*i)),
@@ -62,11 +145,22 @@ fn bits(input: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], BitVec> {
     )(input)
 }
 
+/// The (1-based) index of a numbered placeholder, such as the `3` in `$3`
+fn placeholder_index(input: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], u32> {
+    map_res(
+        map_res(digit1, |i: LocatedSpan<&[u8]>| str::from_utf8(&i)),
+        |s| match u32::from_str(s) {
+            Ok(0) | Err(_) => Err(ErrorKind::Digit),
+            Ok(i) => Ok(i),
+        },
+    )(input)
+}
+
 /// Specification for a SQL dialect to use when parsing
 ///
 /// Currently, Dialect controls the escape characters used for identifiers, and the quotes used to
 /// surround string literals, but may be extended to cover more dialect differences in the future
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 pub enum Dialect {
     /// The SQL dialect used by PostgreSQL.
     ///
@@ -78,10 +172,68 @@ pub enum Dialect {
     /// Identifiers are escaped with backticks (`\``) or square brackets (`[` and `]`) and strings
     /// use either single quotes (`'`) or double quotes (`"`)
     MySQL,
+
+    /// The SQL dialect used by SQLite.
+    ///
+    /// Identifiers are escaped with double quotes (`"`), backticks (`\``) or square brackets (`[`
+    /// and `]`), and strings use only single quotes (`'`), with no backslash escape sequences
+    SQLite,
+}
+
+/// The raw (byte) content of a string literal, along with the character set introducer and
+/// collation which were attached to it, if any.
+///
+/// Character set introducers and collations are only supported by MySQL, eg:
+///
+/// ```sql
+/// _utf8mb4'x' COLLATE utf8mb4_bin
+/// ```
+///
+/// The character set (with the leading underscore removed) and collation are normalized to
+/// lowercase.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CharsetStringLiteral {
+    /// The character set introducer preceding the string literal
+    pub charset: Option<SqlIdentifier>,
+    /// The raw (unescaped) content of the string literal
+    pub bytes: Vec<u8>,
+    /// The collation given in a `COLLATE` clause following the string literal
+    pub collation: Option<SqlIdentifier>,
+}
+
+/// Parse a MySQL character set introducer (eg `_utf8mb4`) which must be immediately followed by a
+/// string literal, returning the normalized name of the character set
+fn charset_introducer(i: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], SqlIdentifier> {
+    map_res(
+        terminated(
+            preceded(tag("_"), take_while1(is_sql_identifier)),
+            peek(alt((tag("'"), tag("\"")))),
+        ),
+        |v: LocatedSpan<&[u8]>| str::from_utf8(&v).map(|s| s.to_ascii_lowercase().into()),
+    )(i)
+}
+
+/// Parse a MySQL `COLLATE <collation>` clause following a string literal, returning the
+/// normalized name of the collation
+fn collate_clause(
+    dialect: Dialect,
+) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], SqlIdentifier> {
+    move |i| {
+        let (i, _) = whitespace1(i)?;
+        let (i, _) = tag_no_case("collate")(i)?;
+        let (i, _) = whitespace1(i)?;
+        let (i, collation) = alt((
+            map_res(take_while1(is_sql_identifier), |v: LocatedSpan<&[u8]>| {
+                str::from_utf8(&v).map(str::to_owned)
+            }),
+            dialect.utf8_string_literal(),
+        ))(i)?;
+        Ok((i, collation.to_ascii_lowercase().into()))
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Error)]
-#[error("Unknown dialect `{0}`, expected one of mysql or postgresql")]
+#[error("Unknown dialect `{0}`, expected one of mysql, postgresql or sqlite")]
 pub struct UnknownDialect(String);
 
 impl FromStr for Dialect {
@@ -91,6 +243,7 @@ impl FromStr for Dialect {
         match s.to_lowercase().as_str() {
             "mysql" => Ok(Dialect::MySQL),
             "postgresql" => Ok(Dialect::PostgreSQL),
+            "sqlite" => Ok(Dialect::SQLite),
             _ => Err(UnknownDialect(s.to_owned())),
         }
     }
@@ -98,7 +251,19 @@ impl FromStr for Dialect {
 
 impl Dialect {
     /// All SQL dialects.
-    pub const ALL: &[Self] = &[Self::MySQL, Self::PostgreSQL];
+    pub const ALL: &[Self] = &[Self::MySQL, Self::PostgreSQL, Self::SQLite];
+
+    /// Returns all the keywords (including the names of built-in functions) recognized when
+    /// parsing this Dialect, sorted alphabetically and in uppercase.
+    ///
+    /// This is intended for tooling such as autocompletion. Note that for PostgreSQL this
+    /// includes keywords which are not reserved, and so can also be used as identifiers.
+    pub fn keywords(&self) -> &'static [&'static str] {
+        match self {
+            Dialect::PostgreSQL => &POSTGRES_KEYWORDS,
+            Dialect::MySQL | Dialect::SQLite => &KEYWORDS,
+        }
+    }
 
     /// Parse a SQL identifier using this Dialect
     pub fn identifier(self) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], SqlIdentifier> {
@@ -137,6 +302,43 @@ impl Dialect {
                     |v: LocatedSpan<&[u8]>| str::from_utf8(&v).map(Into::into),
                 ),
             ))(i),
+            Dialect::SQLite => map_res(
+                alt((
+                    preceded(
+                        not(peek(sql_keyword_or_builtin_function)),
+                        take_while1(is_sql_identifier),
+                    ),
+                    delimited(tag("\""), take_while1(|c| c != 0 && c != b'"'), tag("\"")),
+                    delimited(tag("`"), take_while1(|c| c != 0 && c != b'`'), tag("`")),
+                    delimited(tag("["), take_while1(|c| c != 0 && c != b']'), tag("]")),
+                )),
+                |v| str::from_utf8(&v).map(Into::into),
+            )(i),
+        }
+    }
+
+    /// Parse a SQL identifier using this Dialect, folding it to lowercase if `fold` is true.
+    ///
+    /// In MySQL, whether table and database names are case sensitive depends on the
+    /// `lower_case_table_names` system variable, which is typically set on case-insensitive
+    /// filesystems. Passing `fold = true` folds both quoted and unquoted MySQL identifiers to
+    /// lowercase, so that eg `Foo` and `foo` refer to the same relation. For other dialects `fold`
+    /// has no effect, since PostgreSQL always folds unquoted identifiers and SQLite never folds
+    /// identifiers.
+    ///
+    /// This is only used to parse the names of relations and their schemas, with `fold` set by
+    /// [`with_folded_table_names`](crate::with_folded_table_names).
+    pub fn identifier_with_case(
+        self,
+        fold: bool,
+    ) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], SqlIdentifier> {
+        move |i| {
+            let (i, ident) = self.identifier()(i)?;
+            if fold && self == Dialect::MySQL {
+                Ok((i, ident.to_ascii_lowercase().into()))
+            } else {
+                Ok((i, ident))
+            }
         }
     }
 
@@ -158,13 +360,22 @@ impl Dialect {
                 )),
                 |i| str::from_utf8(&i),
             )(i),
+            Dialect::SQLite => map_res(
+                alt((
+                    preceded(not(peek(sql_keyword)), take_while1(is_sql_identifier)),
+                    delimited(tag("\""), take_while1(is_sql_identifier), tag("\"")),
+                    delimited(tag("`"), take_while1(is_sql_identifier), tag("`")),
+                    delimited(tag("["), take_while1(is_sql_identifier), tag("]")),
+                )),
+                |i| str::from_utf8(&i),
+            )(i),
         }
     }
 
     /// Returns the [`QuotingStyle`] for this dialect
     pub fn quoting_style(self) -> QuotingStyle {
         match self {
-            Dialect::PostgreSQL => QuotingStyle::Single,
+            Dialect::PostgreSQL | Dialect::SQLite => QuotingStyle::Single,
             Dialect::MySQL => QuotingStyle::SingleOrDouble,
         }
     }
@@ -172,7 +383,7 @@ impl Dialect {
     /// Returns the table/column identifier quoting character for this dialect.
     pub fn quote_identifier_char(self) -> char {
         match self {
-            Self::PostgreSQL => '"',
+            Self::PostgreSQL | Self::SQLite => '"',
             Self::MySQL => '`',
         }
     }
@@ -183,20 +394,86 @@ impl Dialect {
         fmty::fmt_args!("{quote}{ident}{quote}")
     }
 
-    /// Parse the raw (byte) content of a string literal using this Dialect
+    /// Removes the identifier quoting characters for this dialect from `ident`, un-escaping any
+    /// doubled quote characters within it (eg `` `a``b` `` becomes `` a`b `` in MySQL). This is
+    /// the inverse of [`quote_identifier`](Self::quote_identifier).
+    ///
+    /// If `ident` is not quoted, it is returned unchanged.
+    pub fn unquote_identifier(self, ident: &str) -> Cow<'_, str> {
+        let quotes: &[(char, char)] = match self {
+            Self::PostgreSQL => &[('"', '"')],
+            Self::MySQL => &[('`', '`')],
+            Self::SQLite => &[('"', '"'), ('`', '`'), ('[', ']')],
+        };
+
+        for &(open, close) in quotes {
+            if let Some(inner) = ident
+                .strip_prefix(open)
+                .and_then(|ident| ident.strip_suffix(close))
+            {
+                // Bracket-quoted identifiers have no escape sequences
+                return if open == close && inner.contains(close) {
+                    Cow::Owned(inner.replace(&format!("{close}{close}"), &close.to_string()))
+                } else {
+                    Cow::Borrowed(inner)
+                };
+            }
+        }
+
+        Cow::Borrowed(ident)
+    }
+
+    /// Parse the raw (byte) content of a string literal using this Dialect.
+    ///
+    /// For PostgreSQL, backslash escape sequences are only interpreted in escape strings, which
+    /// are prefixed with `E` (eg `E'\n'`), as when `standard_conforming_strings` is on (the
+    /// default since PostgreSQL 9.1). In all other strings backslashes are literal characters.
+    /// Dollar-quoted strings (eg `$tag$text$tag$`) are also supported.
+    ///
+    /// SQLite has no escape strings, so the only escape sequence is a doubled single quote (`''`).
     pub fn string_literal(self) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Vec<u8>> {
         move |i| match self {
-            // Currently we allow escape sequences in all string constants. If we support postgres'
-            // standard_conforming_strings setting, then the below should be changed to check for
-            // the presence of a preceding 'E' instead of matching and discarding the match result.
-            Dialect::PostgreSQL => preceded(
-                opt(tag_no_case("E")),
-                raw_string_literal(self.quoting_style()),
-            )(i),
+            Dialect::PostgreSQL => alt((
+                preceded(tag_no_case("E"), raw_string_literal(self.quoting_style())),
+                raw_standard_string_literal,
+                raw_dollar_quoted_string,
+            ))(i),
             Dialect::MySQL => preceded(
                 opt(alt((tag("_utf8mb4"), tag("_utf8"), tag("_binary")))),
                 raw_string_literal(self.quoting_style()),
             )(i),
+            Dialect::SQLite => raw_standard_string_literal(i),
+        }
+    }
+
+    /// Parse a string literal using this Dialect, retaining any character set introducer and
+    /// `COLLATE` clause attached to it.
+    ///
+    /// For PostgreSQL and SQLite, the returned charset and collation are always [`None`]
+    pub fn string_literal_with_charset(
+        self,
+    ) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], CharsetStringLiteral> {
+        move |i| match self {
+            Dialect::PostgreSQL | Dialect::SQLite => {
+                map(self.string_literal(), |bytes| CharsetStringLiteral {
+                    charset: None,
+                    bytes,
+                    collation: None,
+                })(i)
+            }
+            Dialect::MySQL => {
+                let (i, charset) = opt(charset_introducer)(i)?;
+                let (i, bytes) = raw_string_literal(self.quoting_style())(i)?;
+                let (i, collation) = opt(collate_clause(self))(i)?;
+                Ok((
+                    i,
+                    CharsetStringLiteral {
+                        charset,
+                        bytes,
+                        collation,
+                    },
+                ))
+            }
         }
     }
 
@@ -205,12 +482,14 @@ impl Dialect {
     }
 
     /// Parse the raw (byte) content of a bytes literal using this Dialect.
-    // TODO(fran): Improve this. This is very naive, and for Postgres specifically, it only
-    //  parses the hex-formatted byte array. We need to also add support for the escaped format.
+    ///
+    /// For PostgreSQL, both the hex format (`E'\\x0102'::bytea`) and the traditional escape format
+    /// (`'\001\002'::bytea`) are supported.
     pub fn bytes_literal(self) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Vec<u8>> {
         move |i| match self {
-            Dialect::PostgreSQL => raw_hex_bytes_psql(i),
+            Dialect::PostgreSQL => alt((raw_hex_bytes_psql, raw_escape_bytes_psql))(i),
             Dialect::MySQL => raw_hex_bytes_mysql(i),
+            Dialect::SQLite => raw_hex_bytes_sqlite(i),
         }
     }
 
@@ -218,14 +497,18 @@ impl Dialect {
     pub fn bitvec_literal(self) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], BitVec> {
         move |input| match self {
             Dialect::PostgreSQL => raw_bit_vector_psql(input),
-            Dialect::MySQL => Err(nom::Err::Error(NomSqlError {
+            Dialect::MySQL | Dialect::SQLite => Err(nom::Err::Error(NomSqlError {
                 input,
                 kind: nom::error::ErrorKind::Many0,
             })),
         }
     }
 
-    /// Parses the MySQL specific `{offset}, {limit}` part in a `LIMIT` clause
+    /// Parses the `{offset}, {limit}` part in a `LIMIT` clause, which is supported by MySQL and
+    /// SQLite
+    ///
+    /// The standard `{limit} OFFSET {offset}` form is accepted by every dialect, and is parsed
+    /// separately as a [`LimitClause::LimitOffset`].
     pub fn offset_limit(self) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], LimitClause> {
         move |i| {
             if self == Dialect::PostgreSQL {
@@ -245,11 +528,119 @@ impl Dialect {
             Ok((i, LimitClause::OffsetCommaLimit { offset, limit }))
         }
     }
+
+    /// Returns a parser for a placeholder marker using this Dialect.
+    ///
+    /// MySQL only supports anonymous `?` placeholders, and PostgreSQL only supports numbered `$N`
+    /// placeholders. SQLite supports both `?` and `:N`. Anonymous placeholders aren't numbered
+    /// while parsing, since the parser may backtrack - once a statement has been parsed, use
+    /// [`placeholder_indices`](crate::placeholder_indices) to find the index of the parameter each
+    /// of its placeholders refers to.
+    pub fn placeholder(
+        self,
+    ) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], ItemPlaceholder> {
+        move |i| match self {
+            Dialect::MySQL => map(tag("?"), |_| ItemPlaceholder::QuestionMark)(i),
+            Dialect::PostgreSQL => map(
+                preceded(tag("$"), placeholder_index),
+                ItemPlaceholder::DollarNumber,
+            )(i),
+            Dialect::SQLite => alt((
+                map(tag("?"), |_| ItemPlaceholder::QuestionMark),
+                map(
+                    preceded(tag(":"), placeholder_index),
+                    ItemPlaceholder::ColonNumber,
+                ),
+            ))(i),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::to_nom_result;
+
+    #[test]
+    fn keywords() {
+        for dialect in Dialect::ALL {
+            let keywords = dialect.keywords();
+            for keyword in ["SELECT", "FROM", "WHERE", "CURRENT_TIMESTAMP"] {
+                assert!(
+                    keywords.contains(&keyword),
+                    "{dialect:?} is missing {keyword}"
+                );
+            }
+            assert!(keywords.windows(2).all(|w| w[0] < w[1]));
+        }
+
+        assert!(Dialect::PostgreSQL.keywords().contains(&"XMLTABLE"));
+        assert!(!Dialect::MySQL.keywords().contains(&"XMLTABLE"));
+        assert_ne!(Dialect::MySQL.keywords(), Dialect::PostgreSQL.keywords());
+    }
+
+    #[test]
+    fn placeholders() {
+        fn parse_all(dialect: Dialect, placeholders: &[&str]) -> Vec<u32> {
+            let parser = dialect.placeholder();
+            let placeholders = placeholders
+                .iter()
+                .map(|p| {
+                    to_nom_result(parser(LocatedSpan::new(p.as_bytes())))
+                        .unwrap()
+                        .1
+                })
+                .collect::<Vec<_>>();
+            crate::placeholder_indices(&placeholders)
+        }
+
+        assert_eq!(parse_all(Dialect::MySQL, &["?", "?", "?"]), vec![1, 2, 3]);
+        assert_eq!(
+            parse_all(Dialect::PostgreSQL, &["$2", "$1", "$3", "$1"]),
+            vec![2, 1, 3, 1]
+        );
+        assert_eq!(
+            parse_all(Dialect::SQLite, &["?", ":5", "?", ":2"]),
+            vec![1, 5, 6, 2]
+        );
+
+        // Backtracking over a placeholder doesn't affect the numbering of later placeholders
+        let mut backtracking = alt((
+            terminated(Dialect::MySQL.placeholder(), tag("!")),
+            Dialect::MySQL.placeholder(),
+        ));
+        let (_, placeholder) = to_nom_result(backtracking(LocatedSpan::new(b"?"))).unwrap();
+        assert_eq!(placeholder, ItemPlaceholder::QuestionMark);
+
+        Dialect::MySQL.placeholder()(LocatedSpan::new(b"$1")).unwrap_err();
+        Dialect::PostgreSQL.placeholder()(LocatedSpan::new(b"?")).unwrap_err();
+        Dialect::PostgreSQL.placeholder()(LocatedSpan::new(b"$0")).unwrap_err();
+        Dialect::PostgreSQL.placeholder()(LocatedSpan::new(b"$")).unwrap_err();
+    }
+
+    #[test]
+    fn unquote_identifier() {
+        assert_eq!(Dialect::MySQL.unquote_identifier("`a``b`"), "a`b");
+        assert_eq!(Dialect::MySQL.unquote_identifier("`ab`"), "ab");
+        assert_eq!(Dialect::MySQL.unquote_identifier("\"ab\""), "\"ab\"");
+        assert_eq!(Dialect::PostgreSQL.unquote_identifier("\"a\"\"b\""), "a\"b");
+        assert_eq!(Dialect::PostgreSQL.unquote_identifier("`ab`"), "`ab`");
+        assert_eq!(Dialect::SQLite.unquote_identifier("[a]]b]"), "a]]b");
+        assert_eq!(Dialect::SQLite.unquote_identifier("`a``b`"), "a`b");
+        assert_eq!(Dialect::SQLite.unquote_identifier("\"a\"\"b\""), "a\"b");
+
+        for dialect in Dialect::ALL {
+            assert!(matches!(
+                dialect.unquote_identifier("ab"),
+                Cow::Borrowed("ab")
+            ));
+            assert_eq!(dialect.unquote_identifier("\""), "\"");
+            assert_eq!(
+                dialect.unquote_identifier(&dialect.quote_identifier("ab").to_string()),
+                "ab"
+            );
+        }
+    }
 
     mod mysql {
         use super::*;
@@ -276,6 +667,27 @@ mod tests {
             Dialect::MySQL.identifier()(LocatedSpan::new(id8)).unwrap_err();
         }
 
+        #[test]
+        fn identifier_case_folding() {
+            for input in [&b"Foo"[..], b"`Foo`"] {
+                let res = to_nom_result(Dialect::MySQL.identifier_with_case(true)(
+                    LocatedSpan::new(input),
+                ));
+                assert_eq!(res.unwrap().1, "foo");
+
+                let res = to_nom_result(Dialect::MySQL.identifier_with_case(false)(
+                    LocatedSpan::new(input),
+                ));
+                assert_eq!(res.unwrap().1, "Foo");
+            }
+
+            // Folding has no effect on other dialects
+            let res = to_nom_result(Dialect::PostgreSQL.identifier_with_case(true)(
+                LocatedSpan::new(b"\"Foo\""),
+            ));
+            assert_eq!(res.unwrap().1, "Foo");
+        }
+
         #[test]
         fn literal_string_single_backslash_escape() {
             let all_escaped = br#"\0\'\"\b\n\r\t\Z\\\%\_"#;
@@ -296,6 +708,60 @@ mod tests {
             assert_eq!(res, Ok((&b""[..], expected)));
         }
 
+        #[test]
+        fn literal_string_charset_and_collation() {
+            let res = to_nom_result(Dialect::MySQL.string_literal_with_charset()(
+                LocatedSpan::new(b"_utf8mb4'x' COLLATE utf8mb4_bin"),
+            ));
+            assert_eq!(
+                res,
+                Ok((
+                    &b""[..],
+                    CharsetStringLiteral {
+                        charset: Some("utf8mb4".into()),
+                        bytes: b"x".to_vec(),
+                        collation: Some("utf8mb4_bin".into()),
+                    }
+                ))
+            );
+        }
+
+        #[test]
+        fn literal_string_charset_normalized() {
+            let res = to_nom_result(Dialect::MySQL.string_literal_with_charset()(
+                LocatedSpan::new(b"_LATIN1\"x\" collate 'Latin1_General_CS' rest"),
+            ));
+            assert_eq!(
+                res,
+                Ok((
+                    &b" rest"[..],
+                    CharsetStringLiteral {
+                        charset: Some("latin1".into()),
+                        bytes: b"x".to_vec(),
+                        collation: Some("latin1_general_cs".into()),
+                    }
+                ))
+            );
+        }
+
+        #[test]
+        fn literal_string_without_charset() {
+            let res = to_nom_result(Dialect::MySQL.string_literal_with_charset()(
+                LocatedSpan::new(b"'x' AND y"),
+            ));
+            assert_eq!(
+                res,
+                Ok((
+                    &b" AND y"[..],
+                    CharsetStringLiteral {
+                        charset: None,
+                        bytes: b"x".to_vec(),
+                        collation: None,
+                    }
+                ))
+            );
+        }
+
         #[test]
         fn literal_string_double_quote() {
             let res = to_nom_result(Dialect::MySQL.string_literal()(LocatedSpan::new(
@@ -378,8 +844,7 @@ mod tests {
         #[test]
         fn literal_string_single_backslash_escape() {
             let all_escaped = br#"\0\'\"\b\n\r\t\Z\\\%\_"#;
-            let quote = &b"'"[..];
-            let quoted = &[quote, &all_escaped[..], quote].concat();
+            let quoted = &[&b"E'"[..], &all_escaped[..], &b"'"[..]].concat();
             let res = to_nom_result(Dialect::PostgreSQL.string_literal()(LocatedSpan::new(
                 quoted,
             )));
@@ -387,6 +852,40 @@ mod tests {
             assert_eq!(res, Ok((&b""[..], expected)));
         }
 
+        #[test]
+        fn standard_conforming_strings() {
+            let res = Dialect::PostgreSQL.string_literal()(LocatedSpan::new(b"'\\n'"));
+            assert_eq!(res.unwrap().1, b"\\n");
+
+            let res = Dialect::PostgreSQL.string_literal()(LocatedSpan::new(b"E'\\n'"));
+            assert_eq!(res.unwrap().1, b"\n");
+
+            let res = Dialect::PostgreSQL.string_literal()(LocatedSpan::new(b"'it''s \\'"));
+            assert_eq!(res.unwrap().1, b"it's \\");
+        }
+
+        #[test]
+        fn dollar_quoted_strings() {
+            for (input, expected, remaining) in [
+                (&b"$$text$$"[..], &b"text"[..], &b""[..]),
+                (b"$$$$ rest", b"", b" rest"),
+                (b"$tag$it's \\n$tag$", b"it's \\n", b""),
+                (b"$a$ x $b$ y $b$ z $a$ rest", b" x $b$ y $b$ z ", b" rest"),
+                (b"$a$ $A$ $ab$ $a$", b" $A$ $ab$ ", b""),
+                (b"$_1$$$$_1$", b"$$", b""),
+            ] {
+                let res = to_nom_result(Dialect::PostgreSQL.string_literal()(LocatedSpan::new(
+                    input,
+                )));
+                assert_eq!(res, Ok((remaining, expected.to_vec())), "{input:?}");
+            }
+
+            for input in [&b"$a$text$b$"[..], b"$1$text$1$", b"$$text"] {
+                Dialect::PostgreSQL.string_literal()(LocatedSpan::new(input)).unwrap_err();
+            }
+            Dialect::MySQL.string_literal()(LocatedSpan::new(b"$$text$$")).unwrap_err();
+        }
+
         #[test]
         fn literal_string_with_escape_character() {
             let lit = b"E'string'";
@@ -417,5 +916,91 @@ mod tests {
             let res = Dialect::PostgreSQL.bytes_literal()(LocatedSpan::new(b"E'\\\\'::btea"));
             res.unwrap_err();
         }
+
+        #[test]
+        fn escape_bytes_parsing() {
+            let hex = to_nom_result(Dialect::PostgreSQL.bytes_literal()(LocatedSpan::new(
+                b"E'\\\\x0102615c27ff'::bytea",
+            )));
+            let expected = vec![1, 2, b'a', b'\\', b'\'', 255];
+            assert_eq!(hex, Ok((&b""[..], expected.clone())));
+
+            for input in [
+                &b"'\\001\\002a\\\\''\\377'::bytea"[..],
+                &b"E'\\\\001\\\\002a\\\\\\\\''\\\\377'::bytea"[..],
+                &b"'\\x0102615c27ff'::bytea"[..],
+            ] {
+                let res =
+                    to_nom_result(Dialect::PostgreSQL.bytes_literal()(LocatedSpan::new(input)));
+                assert_eq!(res, Ok((&b""[..], expected.clone())), "{input:?}");
+            }
+
+            let res = to_nom_result(Dialect::PostgreSQL.bytes_literal()(LocatedSpan::new(
+                b"'abc'::bytea",
+            )));
+            assert_eq!(res, Ok((&b""[..], b"abc".to_vec())));
+
+            // Empty
+            let res = to_nom_result(Dialect::PostgreSQL.bytes_literal()(LocatedSpan::new(
+                b"''::bytea",
+            )));
+            assert_eq!(res, Ok((&b""[..], vec![])));
+
+            // Invalid escapes
+            for input in [&b"'\\400'::bytea"[..], b"'\\01'::bytea", b"'\\q'::bytea"] {
+                Dialect::PostgreSQL.bytes_literal()(LocatedSpan::new(input)).unwrap_err();
+            }
+        }
+    }
+
+    mod sqlite {
+        use super::*;
+        use crate::to_nom_result;
+
+        #[test]
+        fn sql_identifiers() {
+            for (input, expected) in [
+                (&b"foo"[..], "foo"),
+                (b"FoO", "FoO"),
+                (b"\"primary\"", "primary"),
+                (b"`state-province`", "state-province"),
+                (b"[state province]", "state province"),
+            ] {
+                assert_eq!(
+                    Dialect::SQLite.identifier()(LocatedSpan::new(input))
+                        .unwrap()
+                        .1,
+                    expected
+                );
+            }
+
+            Dialect::SQLite.identifier()(LocatedSpan::new(b":fo oo")).unwrap_err();
+            Dialect::SQLite.identifier()(LocatedSpan::new(b"primary ")).unwrap_err();
+        }
+
+        #[test]
+        fn literal_string() {
+            let res = to_nom_result(Dialect::SQLite.string_literal()(LocatedSpan::new(
+                br"'a''b\n'",
+            )));
+            assert_eq!(res, Ok((&b""[..], br"a'b\n".to_vec())));
+
+            Dialect::SQLite.string_literal()(LocatedSpan::new(br#""a""#)).unwrap_err();
+        }
+
+        #[test]
+        fn bytes_parsing() {
+            for input in [&b"x'0008275c6480'"[..], b"X'0008275c6480'"] {
+                let res = to_nom_result(Dialect::SQLite.bytes_literal()(LocatedSpan::new(input)));
+                assert_eq!(res, Ok((&b""[..], vec![0, 8, 39, 92, 100, 128])));
+            }
+
+            Dialect::SQLite.bytes_literal()(LocatedSpan::new(b"''")).unwrap_err();
+        }
+
+        #[test]
+        fn from_str() {
+            assert_eq!("SQLite".parse::<Dialect>(), Ok(Dialect::SQLite));
+        }
     }
 }
@@ -8,8 +8,9 @@ use nom_locate::LocatedSpan;
 use serde::{Deserialize, Serialize};
 
 use crate::common::statement_terminator;
+use crate::table::{relation, Relation};
 use crate::whitespace::whitespace1;
-use crate::NomSqlResult;
+use crate::{Dialect, NomSqlResult};
 
 /// EXPLAIN statements
 ///
@@ -20,6 +21,8 @@ pub enum ExplainStatement {
     Graphviz { simplified: bool },
     /// Provides metadata about the last statement that was executed.
     LastStatement,
+    /// Print a simplified graphviz representation of the dataflow plan for the named cache
+    Cache { name: Relation },
 }
 
 impl Display for ExplainStatement {
@@ -33,6 +36,7 @@ impl Display for ExplainStatement {
                 write!(f, "GRAPHVIZ;")
             }
             ExplainStatement::LastStatement => write!(f, "LAST STATEMENT;"),
+            ExplainStatement::Cache { name } => write!(f, "CACHE {};", name),
         }
     }
 }
@@ -48,18 +52,34 @@ fn explain_graphviz(i: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], ExplainStateme
     ))
 }
 
-pub(crate) fn explain_statement(i: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], ExplainStatement> {
-    let (i, _) = tag_no_case("explain")(i)?;
-    let (i, _) = whitespace1(i)?;
-    let (i, stmt) = alt((
-        explain_graphviz,
-        map(
-            tuple((tag_no_case("last"), whitespace1, tag_no_case("statement"))),
-            |_| ExplainStatement::LastStatement,
-        ),
-    ))(i)?;
-    let (i, _) = statement_terminator(i)?;
-    Ok((i, stmt))
+fn explain_cache(
+    dialect: Dialect,
+) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], ExplainStatement> {
+    move |i| {
+        let (i, _) = tag_no_case("cache")(i)?;
+        let (i, _) = whitespace1(i)?;
+        let (i, name) = relation(dialect)(i)?;
+        Ok((i, ExplainStatement::Cache { name }))
+    }
+}
+
+pub(crate) fn explain_statement(
+    dialect: Dialect,
+) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], ExplainStatement> {
+    move |i| {
+        let (i, _) = tag_no_case("explain")(i)?;
+        let (i, _) = whitespace1(i)?;
+        let (i, stmt) = alt((
+            explain_graphviz,
+            map(
+                tuple((tag_no_case("last"), whitespace1, tag_no_case("statement"))),
+                |_| ExplainStatement::LastStatement,
+            ),
+            explain_cache(dialect),
+        ))(i)?;
+        let (i, _) = statement_terminator(i)?;
+        Ok((i, stmt))
+    }
 }
 
 #[cfg(test)]
@@ -69,7 +89,7 @@ mod tests {
     #[test]
     fn explain_graphviz() {
         assert_eq!(
-            explain_statement(LocatedSpan::new(b"explain graphviz;"))
+            explain_statement(Dialect::MySQL)(LocatedSpan::new(b"explain graphviz;"))
                 .unwrap()
                 .1,
             ExplainStatement::Graphviz { simplified: false }
@@ -79,10 +99,33 @@ mod tests {
     #[test]
     fn explain_last_statement() {
         assert_eq!(
-            explain_statement(LocatedSpan::new(b"explain last statement;"))
+            explain_statement(Dialect::MySQL)(LocatedSpan::new(b"explain last statement;"))
                 .unwrap()
                 .1,
             ExplainStatement::LastStatement
         );
     }
+
+    #[test]
+    fn explain_cache() {
+        let res = explain_statement(Dialect::MySQL)(LocatedSpan::new(b"EXPLAIN CACHE `q_1`;"))
+            .unwrap()
+            .1;
+        assert_eq!(res, ExplainStatement::Cache { name: "q_1".into() });
+        assert_eq!(res.to_string(), "EXPLAIN CACHE `q_1`;");
+
+        let res =
+            explain_statement(Dialect::PostgreSQL)(LocatedSpan::new(b"explain cache s1.my_cache"))
+                .unwrap()
+                .1;
+        assert_eq!(
+            res,
+            ExplainStatement::Cache {
+                name: Relation {
+                    schema: Some("s1".into()),
+                    name: "my_cache".into(),
+                }
+            }
+        );
+    }
 }
@@ -22,7 +22,11 @@ use crate::select::nested_selection;
 use crate::set::{variable_scope_prefix, Variable};
 use crate::sql_type::{mysql_int_cast_targets, type_identifier};
 use crate::whitespace::{whitespace0, whitespace1};
-use crate::{Column, Dialect, Literal, NomSqlResult, SelectStatement, SqlIdentifier, SqlType};
+use crate::window::over_clause;
+use crate::{
+    Column, Dialect, Literal, NomSqlResult, SelectStatement, SqlIdentifier, SqlType,
+    WindowSpecification,
+};
 
 /// Function call expressions
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Hash, Serialize, Deserialize)]
@@ -453,6 +457,12 @@ pub enum Expr {
 
     /// A variable reference
     Variable(Variable),
+
+    /// A window function call: `<function> OVER (<window specification>)`
+    WindowFunction {
+        function: FunctionExpr,
+        window: WindowSpecification,
+    },
 }
 
 impl Display for Expr {
@@ -541,6 +551,9 @@ impl Display for Expr {
                 write!(f, "]")
             }
             Expr::Variable(var) => write!(f, "{}", var),
+            Expr::WindowFunction { function, window } => {
+                write!(f, "{} OVER ({})", function, window)
+            }
         }
     }
 }
@@ -742,48 +755,52 @@ fn primary_inner(
     }
 }
 
-fn primary(dialect: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], TokenTree> {
-    enum PrimarySuffix {
-        Cast(SqlType),
-        OpSuffix(BinaryOperator, OperatorSuffix, Box<TokenTree>),
+/// Parse a PostgreSQL-style `::type` cast suffix, returning the type being cast to
+fn pgsql_cast_suffix(
+    dialect: Dialect,
+) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], SqlType> {
+    move |i| {
+        let (i, _) = whitespace0(i)?;
+        let (i, _) = tag("::")(i)?;
+        let (i, _) = whitespace0(i)?;
+        type_identifier(dialect)(i)
     }
+}
 
+fn primary(dialect: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], TokenTree> {
     move |i| {
-        let (i, lhs) = primary_inner(dialect)(i)?;
-        let (i, suffix) = opt(alt((
-            move |i| {
-                let (i, _) = whitespace0(i)?;
-                let (i, _) = tag("::")(i)?;
-                let (i, _) = whitespace0(i)?;
-                map(type_identifier(dialect), PrimarySuffix::Cast)(i)
-            },
-            move |i| {
-                let (i, _) = whitespace0(i)?;
-                let (i, op) = binary_operator(i)?;
-                let (i, _) = whitespace0(i)?;
-                let (i, suffix) = operator_suffix(i)?;
-                let (i, _) = whitespace0(i)?;
-                let (i, _) = tag("(")(i)?;
-                let (i, _) = whitespace0(i)?;
-                let (i, rhs) = token_tree(dialect)(i)?;
-                let (i, _) = whitespace0(i)?;
-                let (i, _) = tag(")")(i)?;
+        let (mut i, mut lhs) = primary_inner(dialect)(i)?;
+
+        // The `expr::type` cast syntax is PostgreSQL-specific, and casts can be chained (eg
+        // `expr::text::int`), in which case they're applied left to right
+        if dialect == Dialect::PostgreSQL {
+            let (rem, tys) = many0(pgsql_cast_suffix(dialect))(i)?;
+            i = rem;
+            for ty in tys {
+                lhs = TokenTree::PgsqlCast(Box::new(lhs), ty);
+            }
+        }
 
-                Ok((
-                    i,
-                    PrimarySuffix::OpSuffix(op, suffix, Box::new(TokenTree::Group(rhs))),
-                ))
-            },
-        )))(i)?;
+        let (i, suffix) = opt(move |i| {
+            let (i, _) = whitespace0(i)?;
+            let (i, op) = binary_operator(i)?;
+            let (i, _) = whitespace0(i)?;
+            let (i, suffix) = operator_suffix(i)?;
+            let (i, _) = whitespace0(i)?;
+            let (i, _) = tag("(")(i)?;
+            let (i, _) = whitespace0(i)?;
+            let (i, rhs) = token_tree(dialect)(i)?;
+            let (i, _) = whitespace0(i)?;
+            let (i, _) = tag(")")(i)?;
+
+            Ok((i, (op, suffix, Box::new(TokenTree::Group(rhs)))))
+        })(i)?;
 
         Ok((
             i,
             match suffix {
                 None => lhs,
-                Some(PrimarySuffix::Cast(ty)) => TokenTree::PgsqlCast(Box::new(lhs), ty),
-                Some(PrimarySuffix::OpSuffix(op, suffix, rhs)) => {
-                    TokenTree::OpSuffix(Box::new(lhs), op, suffix, rhs)
-                }
+                Some((op, suffix, rhs)) => TokenTree::OpSuffix(Box::new(lhs), op, suffix, rhs),
             },
         ))
     }
@@ -996,7 +1013,7 @@ where
 fn in_lhs(dialect: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Expr> {
     move |i| {
         alt((
-            map(function_expr(dialect), Expr::Call),
+            function_call_or_window_function(dialect),
             map(literal(dialect), Expr::Literal),
             case_when_expr(dialect),
             map(column_identifier_no_alias(dialect), Expr::Column),
@@ -1047,7 +1064,7 @@ fn between_operand(dialect: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlRes
     move |i| {
         alt((
             parenthesized_expr(dialect),
-            map(function_expr(dialect), Expr::Call),
+            function_call_or_window_function(dialect),
             map(literal(dialect), Expr::Literal),
             case_when_expr(dialect),
             map(column_identifier_no_alias(dialect), Expr::Column),
@@ -1256,6 +1273,23 @@ fn array_expr(dialect: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&
     }
 }
 
+/// Parse a function call, which is a window function call if followed by an `OVER` clause
+fn function_call_or_window_function(
+    dialect: Dialect,
+) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Expr> {
+    move |i| {
+        let (i, function) = function_expr(dialect)(i)?;
+        let (i, window) = opt(preceded(whitespace0, over_clause(dialect)))(i)?;
+        Ok((
+            i,
+            match window {
+                Some(window) => Expr::WindowFunction { function, window },
+                None => Expr::Call(function),
+            },
+        ))
+    }
+}
+
 // Expressions without (binary or unary) operators
 pub(crate) fn simple_expr(
     dialect: Dialect,
@@ -1267,7 +1301,7 @@ pub(crate) fn simple_expr(
             exists_expr(dialect),
             between_expr(dialect),
             in_expr(dialect),
-            map(function_expr(dialect), Expr::Call),
+            function_call_or_window_function(dialect),
             map(literal(dialect), Expr::Literal),
             case_when_expr(dialect),
             array_expr(dialect),
@@ -1404,6 +1438,60 @@ mod tests {
             );
         }
 
+        /// Asserts that `pg_cast`, using the `::` syntax, parses to the same expression as `cast`,
+        /// using the `CAST(... AS ...)` syntax, apart from the `postgres_style` flag
+        fn parses_same_as_cast(pg_cast: &str, cast: &str) {
+            let mut pg_cast_expr = test_parse!(expression(Dialect::PostgreSQL), pg_cast.as_bytes());
+            match &mut pg_cast_expr {
+                Expr::Cast { postgres_style, .. } => {
+                    assert!(*postgres_style);
+                    *postgres_style = false;
+                }
+                e => panic!("Expected a cast, got {e:?}"),
+            }
+            let cast_expr = test_parse!(expression(Dialect::PostgreSQL), cast.as_bytes());
+            assert_eq!(pg_cast_expr, cast_expr);
+        }
+
+        #[test]
+        fn postgres_cast_string_literal() {
+            parses_same_as_cast("'2020-01-01'::date", "CAST('2020-01-01' AS date)");
+        }
+
+        #[test]
+        fn postgres_cast_placeholder() {
+            parses_same_as_cast("$1::int", "CAST($1 AS int)");
+            parses_same_as_cast("$1 :: int", "CAST($1 AS int)");
+        }
+
+        #[test]
+        fn postgres_cast_column() {
+            parses_same_as_cast("t.created_at::timestamp", "CAST(t.created_at AS timestamp)");
+        }
+
+        #[test]
+        fn postgres_chained_casts() {
+            parses_same_as_cast("$1::text::int", "CAST(($1::text) AS int)");
+        }
+
+        #[test]
+        fn postgres_cast_with_operator_suffix() {
+            let res = test_parse!(expression(Dialect::PostgreSQL), b"x::int = ANY ($1)");
+            assert!(
+                matches!(
+                    res,
+                    Expr::OpAny { lhs: box Expr::Cast { .. }, .. }
+                ),
+                "{res:?}"
+            );
+        }
+
+        #[test]
+        fn mysql_does_not_support_postgres_cast() {
+            let res = expression(Dialect::MySQL)(LocatedSpan::new(b"x::int"));
+            assert!(res.is_err() || !res.unwrap().0.is_empty());
+        }
+
         #[test]
         fn mysql_cast() {
             let res = expression(Dialect::MySQL)(LocatedSpan::new(br#"CAST(-128 AS UNSIGNED)"#));
@@ -1,7 +1,9 @@
 use std::{fmt, str};
 
+use nom::branch::alt;
 use nom::bytes::complete::{tag, tag_no_case};
-use nom::combinator::opt;
+use nom::combinator::{map, opt};
+use nom::error::ErrorKind;
 use nom::multi::separated_list1;
 use nom::sequence::{delimited, preceded, terminated, tuple};
 use nom_locate::LocatedSpan;
@@ -9,11 +11,97 @@ use serde::{Deserialize, Serialize};
 
 use crate::column::Column;
 use crate::common::{
-    assignment_expr_list, field_list, statement_terminator, value_list, ws_sep_comma,
+    assignment_expr_list, column_identifier_no_alias, field_list, statement_terminator, value_list,
+    ws_sep_comma,
 };
+use crate::select::where_clause;
 use crate::table::{relation, Relation};
 use crate::whitespace::{whitespace0, whitespace1};
-use crate::{Dialect, Expr, NomSqlResult};
+use crate::{Dialect, Expr, NomSqlError, NomSqlResult, SqlIdentifier};
+
+/// The conflict target of a PostgreSQL `ON CONFLICT` clause, which identifies the unique index
+/// or constraint whose violations are handled by the clause
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum OnConflictTarget {
+    /// `ON CONFLICT (col1, col2, ...)`
+    Columns(Vec<Column>),
+    /// `ON CONFLICT ON CONSTRAINT constraint_name`
+    Constraint(SqlIdentifier),
+}
+
+impl fmt::Display for OnConflictTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OnConflictTarget::Columns(columns) => write!(
+                f,
+                "({})",
+                columns
+                    .iter()
+                    .map(|col| format!("`{}`", col.name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            OnConflictTarget::Constraint(name) => write!(f, "ON CONSTRAINT `{}`", name),
+        }
+    }
+}
+
+/// The action taken by a PostgreSQL `ON CONFLICT` clause when inserting a row would violate a
+/// unique index or constraint
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum OnConflictAction {
+    /// `DO NOTHING`
+    DoNothing,
+    /// `DO UPDATE SET col1 = expr1, ... [WHERE condition]`
+    DoUpdate {
+        assignments: Vec<(Column, Expr)>,
+        where_clause: Option<Expr>,
+    },
+}
+
+impl fmt::Display for OnConflictAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OnConflictAction::DoNothing => write!(f, "DO NOTHING"),
+            OnConflictAction::DoUpdate {
+                assignments,
+                where_clause,
+            } => {
+                write!(
+                    f,
+                    "DO UPDATE SET {}",
+                    assignments
+                        .iter()
+                        .map(|(col, expr)| format!("{} = {}", col, expr))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )?;
+                if let Some(where_clause) = where_clause {
+                    write!(f, " WHERE {}", where_clause)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A PostgreSQL `ON CONFLICT` clause, specifying what to do when inserting a row would violate a
+/// unique index or constraint
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct OnConflict {
+    pub target: Option<OnConflictTarget>,
+    pub action: OnConflictAction,
+}
+
+impl fmt::Display for OnConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ON CONFLICT ")?;
+        if let Some(target) = &self.target {
+            write!(f, "{} ", target)?;
+        }
+        write!(f, "{}", self.action)
+    }
+}
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct InsertStatement {
@@ -21,7 +109,10 @@ pub struct InsertStatement {
     pub fields: Option<Vec<Column>>,
     pub data: Vec<Vec<Expr>>,
     pub ignore: bool,
+    /// The assignments in a MySQL `ON DUPLICATE KEY UPDATE` clause
     pub on_duplicate: Option<Vec<(Column, Expr)>>,
+    /// A PostgreSQL `ON CONFLICT` clause
+    pub on_conflict: Option<OnConflict>,
 }
 
 impl fmt::Display for InsertStatement {
@@ -53,7 +144,22 @@ impl fmt::Display for InsertStatement {
                 ))
                 .collect::<Vec<_>>()
                 .join(", ")
-        )
+        )?;
+        if let Some(ref on_duplicate) = self.on_duplicate {
+            write!(
+                f,
+                " ON DUPLICATE KEY UPDATE {}",
+                on_duplicate
+                    .iter()
+                    .map(|(col, expr)| format!("{} = {}", col, expr))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
+        if let Some(ref on_conflict) = self.on_conflict {
+            write!(f, " {}", on_conflict)?;
+        }
+        Ok(())
     }
 }
 
@@ -91,6 +197,83 @@ fn on_duplicate(
     }
 }
 
+fn on_conflict_target(
+    dialect: Dialect,
+) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], OnConflictTarget> {
+    move |i| {
+        alt((
+            map(
+                delimited(
+                    terminated(tag("("), whitespace0),
+                    separated_list1(ws_sep_comma, column_identifier_no_alias(dialect)),
+                    preceded(whitespace0, tag(")")),
+                ),
+                OnConflictTarget::Columns,
+            ),
+            map(
+                preceded(
+                    tuple((
+                        tag_no_case("on"),
+                        whitespace1,
+                        tag_no_case("constraint"),
+                        whitespace1,
+                    )),
+                    dialect.identifier(),
+                ),
+                OnConflictTarget::Constraint,
+            ),
+        ))(i)
+    }
+}
+
+fn on_conflict_action(
+    dialect: Dialect,
+) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], OnConflictAction> {
+    move |i| {
+        let (i, _) = tag_no_case("do")(i)?;
+        let (i, _) = whitespace1(i)?;
+        alt((
+            map(tag_no_case("nothing"), |_| OnConflictAction::DoNothing),
+            map(
+                tuple((
+                    tag_no_case("update"),
+                    whitespace1,
+                    tag_no_case("set"),
+                    whitespace1,
+                    assignment_expr_list(dialect),
+                    opt(where_clause(dialect)),
+                )),
+                |(_, _, _, _, assignments, where_clause)| OnConflictAction::DoUpdate {
+                    assignments,
+                    where_clause,
+                },
+            ),
+        ))(i)
+    }
+}
+
+/// Parse a PostgreSQL `ON CONFLICT [conflict_target] conflict_action` clause. Fails for all other
+/// dialects.
+fn on_conflict(dialect: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], OnConflict> {
+    move |i| {
+        if dialect != Dialect::PostgreSQL {
+            return Err(nom::Err::Error(NomSqlError {
+                input: i,
+                kind: ErrorKind::Fail,
+            }));
+        }
+
+        let (i, _) = whitespace0(i)?;
+        let (i, _) = tag_no_case("on")(i)?;
+        let (i, _) = whitespace1(i)?;
+        let (i, _) = tag_no_case("conflict")(i)?;
+        let (i, _) = whitespace1(i)?;
+        let (i, target) = opt(terminated(on_conflict_target(dialect), whitespace0))(i)?;
+        let (i, action) = on_conflict_action(dialect)(i)?;
+        Ok((i, OnConflict { target, action }))
+    }
+}
+
 // Parse rule for a SQL insert query.
 // TODO(malte): support REPLACE, nested selection, DEFAULT VALUES
 pub fn insertion(
@@ -99,7 +282,7 @@ pub fn insertion(
     move |i| {
         let (
             remaining_input,
-            (_, ignore_res, _, _, _, table, _, fields, _, _, data, on_duplicate, _),
+            (_, ignore_res, _, _, _, table, _, fields, _, _, data, on_duplicate, on_conflict, _),
         ) = tuple((
             tag_no_case("insert"),
             opt(preceded(whitespace1, tag_no_case("ignore"))),
@@ -113,6 +296,7 @@ pub fn insertion(
             whitespace0,
             separated_list1(ws_sep_comma, data(dialect)),
             opt(on_duplicate(dialect)),
+            opt(on_conflict(dialect)),
             statement_terminator,
         ))(i)?;
         let ignore = ignore_res.is_some();
@@ -125,6 +309,7 @@ pub fn insertion(
                 data,
                 ignore,
                 on_duplicate,
+                on_conflict,
             },
         ))
     }
@@ -153,6 +338,7 @@ mod tests {
                     Expr::Literal(Literal::Placeholder(ItemPlaceholder::QuestionMark))
                 ]],
                 on_duplicate: None,
+                on_conflict: None,
                 ignore: false
             }
         );
@@ -180,6 +366,7 @@ mod tests {
                         Expr::Literal("test".into())
                     ]],
                     on_duplicate: None,
+                    on_conflict: None,
                     ignore: false
                 }
             );
@@ -207,6 +394,7 @@ mod tests {
                         }),
                     ],],
                     on_duplicate: None,
+                    on_conflict: None,
                     ignore: false
                 }
             );
@@ -227,6 +415,7 @@ mod tests {
                         Expr::Literal("test".into())
                     ]],
                     on_duplicate: None,
+                    on_conflict: None,
                     ignore: false
                 }
             );
@@ -248,6 +437,7 @@ mod tests {
                         Expr::Literal("test".into())
                     ]],
                     on_duplicate: None,
+                    on_conflict: None,
                     ignore: false
                 }
             );
@@ -271,6 +461,7 @@ mod tests {
                         Expr::Literal("test".into())
                     ]],
                     on_duplicate: None,
+                    on_conflict: None,
                     ignore: false,
                 }
             );
@@ -291,6 +482,7 @@ mod tests {
                         vec![Expr::Literal(21_u32.into()), Expr::Literal("test2".into())],
                     ],
                     on_duplicate: None,
+                    on_conflict: None,
                     ignore: false,
                 }
             );
@@ -311,6 +503,7 @@ mod tests {
                         Expr::Literal(Literal::Placeholder(ItemPlaceholder::DollarNumber(1))),
                         Expr::Literal(Literal::Placeholder(ItemPlaceholder::ColonNumber(2)))
                     ]],
+                    on_conflict: None,
                     on_duplicate: Some(vec![(
                         Column::from("value"),
                         Expr::BinaryOp {
@@ -339,6 +532,7 @@ mod tests {
                         Expr::Literal("test".into())
                     ]],
                     on_duplicate: None,
+                    on_conflict: None,
                     ignore: false,
                 }
             );
@@ -352,6 +546,24 @@ mod tests {
             let parsed_again = test_parse!(insertion(Dialect::MySQL), stringified.as_bytes());
             assert_eq!(parsed, parsed_again);
         }
+
+        #[test]
+        fn stringify_insert_with_on_dup_update() {
+            let orig =
+                b"INSERT INTO t (`a`, `b`) VALUES (1, 2) ON DUPLICATE KEY UPDATE `b` = `b` + 1";
+            let parsed = test_parse!(insertion(Dialect::MySQL), orig);
+            let stringified = parsed.to_string();
+            let parsed_again = test_parse!(insertion(Dialect::MySQL), stringified.as_bytes());
+            assert_eq!(parsed, parsed_again);
+        }
+
+        #[test]
+        fn on_conflict_not_supported() {
+            insertion(Dialect::MySQL)(LocatedSpan::new(
+                b"INSERT INTO t (a) VALUES (1) ON CONFLICT DO NOTHING",
+            ))
+            .unwrap_err();
+        }
     }
 
     mod postgres {
@@ -375,6 +587,7 @@ mod tests {
                         Expr::Literal("test".into())
                     ]],
                     on_duplicate: None,
+                    on_conflict: None,
                     ignore: false,
                 }
             );
@@ -400,6 +613,7 @@ mod tests {
                         }),
                     ],],
                     on_duplicate: None,
+                    on_conflict: None,
                     ignore: false,
                 }
             );
@@ -420,6 +634,7 @@ mod tests {
                         Expr::Literal("test".into())
                     ]],
                     on_duplicate: None,
+                    on_conflict: None,
                     ignore: false,
                 }
             );
@@ -441,6 +656,7 @@ mod tests {
                         Expr::Literal("test".into())
                     ]],
                     on_duplicate: None,
+                    on_conflict: None,
                     ignore: false,
                 }
             );
@@ -464,6 +680,7 @@ mod tests {
                         Expr::Literal("test".into())
                     ]],
                     on_duplicate: None,
+                    on_conflict: None,
                     ignore: false,
                 }
             );
@@ -484,7 +701,8 @@ mod tests {
                         vec![Expr::Literal(21_u32.into()), Expr::Literal("test2".into())],
                     ],
                     ignore: false,
-                    on_duplicate: None
+                    on_duplicate: None,
+                    on_conflict: None
                 }
             );
         }
@@ -504,6 +722,7 @@ mod tests {
                         Expr::Literal(Literal::Placeholder(ItemPlaceholder::DollarNumber(1))),
                         Expr::Literal(Literal::Placeholder(ItemPlaceholder::ColonNumber(2)))
                     ]],
+                    on_conflict: None,
                     on_duplicate: Some(vec![(
                         Column::from("value"),
                         Expr::BinaryOp {
@@ -532,9 +751,89 @@ mod tests {
                         Expr::Literal("test".into())
                     ]],
                     ignore: false,
-                    on_duplicate: None
+                    on_duplicate: None,
+                    on_conflict: None
                 }
             );
         }
+
+        #[test]
+        fn insert_with_on_conflict_do_update() {
+            let res = test_parse!(
+                insertion(Dialect::PostgreSQL),
+                b"INSERT INTO users (id, name) VALUES (1, 'bob') \
+                  ON CONFLICT (id) DO UPDATE SET name = excluded.name WHERE users.id > 0"
+            );
+            assert_eq!(
+                res.on_conflict,
+                Some(OnConflict {
+                    target: Some(OnConflictTarget::Columns(vec![Column::from("id")])),
+                    action: OnConflictAction::DoUpdate {
+                        assignments: vec![(
+                            Column::from("name"),
+                            Expr::Column(Column::from("excluded.name"))
+                        )],
+                        where_clause: Some(Expr::BinaryOp {
+                            lhs: Box::new(Expr::Column(Column::from("users.id"))),
+                            op: BinaryOperator::Greater,
+                            rhs: Box::new(Expr::Literal(0_u32.into())),
+                        }),
+                    },
+                })
+            );
+            assert_eq!(res.on_duplicate, None);
+        }
+
+        #[test]
+        fn insert_with_on_conflict_on_constraint() {
+            let res = test_parse!(
+                insertion(Dialect::PostgreSQL),
+                b"INSERT INTO users (id, name) VALUES (1, 'bob') \
+                  ON CONFLICT ON CONSTRAINT users_pkey DO UPDATE SET name = 'alice', id = 2;"
+            );
+            assert_eq!(
+                res.on_conflict,
+                Some(OnConflict {
+                    target: Some(OnConflictTarget::Constraint("users_pkey".into())),
+                    action: OnConflictAction::DoUpdate {
+                        assignments: vec![
+                            (Column::from("name"), Expr::Literal("alice".into())),
+                            (Column::from("id"), Expr::Literal(2_u32.into())),
+                        ],
+                        where_clause: None,
+                    },
+                })
+            );
+        }
+
+        #[test]
+        fn insert_with_on_conflict_do_nothing() {
+            let res = test_parse!(
+                insertion(Dialect::PostgreSQL),
+                b"INSERT INTO users (id, name) VALUES (1, 'bob') ON CONFLICT DO NOTHING"
+            );
+            assert_eq!(
+                res.on_conflict,
+                Some(OnConflict {
+                    target: None,
+                    action: OnConflictAction::DoNothing,
+                })
+            );
+
+            let res = test_parse!(
+                insertion(Dialect::PostgreSQL),
+                b"INSERT INTO users (id, name) VALUES (1, 'bob') ON CONFLICT (id, name) DO NOTHING"
+            );
+            assert_eq!(
+                res.on_conflict,
+                Some(OnConflict {
+                    target: Some(OnConflictTarget::Columns(vec![
+                        Column::from("id"),
+                        Column::from("name")
+                    ])),
+                    action: OnConflictAction::DoNothing,
+                })
+            );
+        }
     }
 }
@@ -218,6 +218,145 @@ fn builtin_function_a_to_z(i: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], &[u8]>
     )(i)
 }
 
+/// The SQL reserved keywords matched by [`sql_keyword`]
+pub(crate) const SQL_KEYWORDS: &[&str] = &[
+    "ABORT",
+    "ACTION",
+    "ADD",
+    "AFTER",
+    "ALL",
+    "ALTER",
+    "ANALYZE",
+    "AND",
+    "AS",
+    "ASC",
+    "ATTACH",
+    "AUTOINCREMENT",
+    "BEFORE",
+    "BEGIN",
+    "BETWEEN",
+    "BY",
+    "CASCADE",
+    "CASE",
+    "CAST",
+    "CHANGE",
+    "CHECK",
+    "COLLATE",
+    "COLUMN",
+    "COMMIT",
+    "CONFLICT",
+    "CONSTRAINT",
+    "CREATE",
+    "CROSS",
+    "DEFERRABLE",
+    "DEFERRED",
+    "DELETE",
+    "DESC",
+    "DETACH",
+    "DISTINCT",
+    "DROP",
+    "EACH",
+    "ELSE",
+    "END",
+    "ESCAPE",
+    "EXCEPT",
+    "EXCLUSIVE",
+    "EXISTS",
+    "EXPLAIN",
+    "FAIL",
+    "FOR",
+    "FOREIGN",
+    "FROM",
+    "FULL",
+    "FULLTEXT",
+    "GLOB",
+    "GROUP",
+    "GROUPS",
+    "HAVING",
+    "ILIKE",
+    "IGNORE",
+    "IMMEDIATE",
+    "IN",
+    "INDEX",
+    "INDEXED",
+    "INITIALLY",
+    "INNER",
+    "INSTEAD",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "JOIN",
+    "KEY",
+    "LIKE",
+    "LIMIT",
+    "MATCH",
+    "MODIFY",
+    "NATURAL",
+    "NO",
+    "NOT",
+    "NOTNULL",
+    "NULL",
+    "OF",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "PLAN",
+    "PRAGMA",
+    "PRIMARY",
+    "QUERY",
+    "RAISE",
+    "RECURSIVE",
+    "REFERENCES",
+    "REGEXP",
+    "REINDEX",
+    "RELEASE",
+    "RENAME",
+    "RESTRICT",
+    "RIGHT",
+    "ROLLBACK",
+    "ROW",
+    "SAVEPOINT",
+    "SELECT",
+    "SET",
+    "TABLE",
+    "TEMP",
+    "TEMPORARY",
+    "THEN",
+    "TO",
+    "TRANSACTION",
+    "TRIGGER",
+    "UNION",
+    "UNIQUE",
+    "UPDATE",
+    "USING",
+    "VACUUM",
+    "VIEW",
+    "VIRTUAL",
+    "WHEN",
+    "WHERE",
+    "WITH",
+    "WITHOUT",
+];
+
+/// The built-in SQL functions matched by [`sql_builtin_function`]
+pub(crate) const SQL_BUILTIN_FUNCTIONS: &[&str] = &[
+    "CURRENT_DATE",
+    "CURRENT_TIME",
+    "CURRENT_TIMESTAMP",
+    "DATABASE",
+    "DEFAULT",
+    "IF",
+    "IN",
+    "INSERT",
+    "ISNULL",
+    "LEFT",
+    "REPLACE",
+    "RIGHT",
+    "VALUES",
+];
+
 // Matches any SQL reserved keyword
 pub fn sql_keyword(i: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], &[u8]> {
     alt((
@@ -603,4 +742,48 @@ lazy_static! {
         b"WITHOUT",
         b"YEAR",
     ]);
+
+    /// All the keywords recognized when parsing MySQL (or SQLite), sorted alphabetically
+    pub(crate) static ref KEYWORDS: Vec<&'static str> =
+        sorted_keywords(SQL_KEYWORDS.iter().chain(SQL_BUILTIN_FUNCTIONS).copied());
+
+    /// All the keywords recognized when parsing PostgreSQL, including those that are not
+    /// reserved, sorted alphabetically
+    pub(crate) static ref POSTGRES_KEYWORDS: Vec<&'static str> = sorted_keywords(
+        KEYWORDS.iter().copied().chain(
+            POSTGRES_NOT_RESERVED
+                .iter()
+                .map(|kw| std::str::from_utf8(kw).expect("keywords are ASCII")),
+        ),
+    );
+}
+
+fn sorted_keywords(keywords: impl Iterator<Item = &'static str>) -> Vec<&'static str> {
+    let mut keywords = keywords.collect::<Vec<_>>();
+    keywords.sort_unstable();
+    keywords.dedup();
+    keywords
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyword_lists_match_parsers() {
+        for keyword in SQL_KEYWORDS {
+            let input = format!("{} ", keyword.to_ascii_lowercase());
+            assert!(
+                sql_keyword(LocatedSpan::new(input.as_bytes())).is_ok(),
+                "{keyword} should be parsed as a keyword"
+            );
+        }
+        for function in SQL_BUILTIN_FUNCTIONS {
+            let input = format!("{function}(");
+            assert!(
+                sql_builtin_function(LocatedSpan::new(input.as_bytes())).is_ok(),
+                "{function} should be parsed as a builtin function"
+            );
+        }
+    }
 }
@@ -14,6 +14,7 @@ use nom_locate::LocatedSpan;
 
 pub use self::alter::{AlterColumnOperation, AlterTableDefinition, AlterTableStatement};
 pub use self::column::{Column, ColumnConstraint, ColumnSpecification};
+pub use self::comments::StatementComments;
 pub use self::common::{FieldDefinitionExpr, FieldReference, IndexType, TableKey};
 pub use self::compound_select::{CompoundSelectOperator, CompoundSelectStatement};
 pub use self::create::{
@@ -22,7 +23,7 @@ pub use self::create::{
 };
 pub use self::create_table_options::CreateTableOption;
 pub use self::delete::DeleteStatement;
-pub use self::dialect::Dialect;
+pub use self::dialect::{CharsetStringLiteral, Dialect};
 pub use self::drop::{
     DropAllCachesStatement, DropCacheStatement, DropTableStatement, DropViewStatement,
 };
@@ -30,11 +31,11 @@ pub use self::explain::ExplainStatement;
 pub use self::expression::{
     BinaryOperator, CaseWhenBranch, Expr, FunctionExpr, InValue, UnaryOperator,
 };
-pub use self::insert::InsertStatement;
+pub use self::insert::{InsertStatement, OnConflict, OnConflictAction, OnConflictTarget};
 pub use self::join::{JoinConstraint, JoinOperator, JoinRightSide};
 pub use self::literal::{
-    embedded_literal, literal, raw_string_literal, utf8_string_literal, Double, Float,
-    ItemPlaceholder, Literal, QuotingStyle,
+    embedded_literal, literal, placeholder_indices, raw_string_literal, utf8_string_literal,
+    Double, Float, ItemPlaceholder, Literal, LiteralDisplay, QuotingStyle,
 };
 pub use self::order::{OrderClause, OrderType};
 pub use self::parser::*;
@@ -47,9 +48,12 @@ pub use self::set::{
 pub use self::show::ShowStatement;
 pub use self::sql_identifier::SqlIdentifier;
 pub use self::sql_type::{EnumVariants, SqlType};
-pub use self::table::{replicator_table_list, Relation, TableExpr, TableExprInner};
+pub use self::table::{
+    replicator_table_list, with_folded_table_names, Relation, TableExpr, TableExprInner,
+};
 pub use self::update::UpdateStatement;
 pub use self::use_statement::UseStatement;
+pub use self::window::{WindowFrame, WindowFrameBound, WindowFrameUnits, WindowSpecification};
 
 pub mod parser;
 
@@ -60,6 +64,7 @@ mod macros;
 mod alter;
 pub mod analysis;
 mod column;
+mod comments;
 mod common;
 mod compound_select;
 mod create;
@@ -84,6 +89,7 @@ mod transaction;
 mod update;
 mod use_statement;
 pub mod whitespace;
+mod window;
 
 pub type NomSqlResult<I, O> = IResult<LocatedSpan<I>, O, NomSqlError<I>>;
 
@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::fmt::{self, Display};
 use std::hash::{Hash, Hasher};
 use std::net::IpAddr;
@@ -10,7 +11,7 @@ use itertools::Itertools;
 use nom::branch::alt;
 use nom::bytes::complete::{is_not, tag, tag_no_case, take};
 use nom::character::complete::{digit1, satisfy};
-use nom::combinator::{map, map_parser, map_res, not, opt, peek, recognize};
+use nom::combinator::{map, map_res, not, opt, peek, recognize};
 use nom::error::ErrorKind;
 use nom::multi::fold_many0;
 use nom::sequence::{delimited, pair, preceded, terminated, tuple};
@@ -76,6 +77,31 @@ pub enum ItemPlaceholder {
     ColonNumber(u32),
 }
 
+/// Returns the (1-based) index of the parameter each of `placeholders` refers to, given all the
+/// placeholders in a statement in the order they appear.
+///
+/// Numbered placeholders refer to the parameter with their number, and may appear in any order and
+/// any number of times. As in SQLite, each anonymous `?` placeholder refers to one more than the
+/// largest index before it, so if a statement only has anonymous placeholders they're numbered
+/// sequentially.
+pub fn placeholder_indices<'a, I>(placeholders: I) -> Vec<u32>
+where
+    I: IntoIterator<Item = &'a ItemPlaceholder>,
+{
+    let mut max_index = 0;
+    placeholders
+        .into_iter()
+        .map(|placeholder| {
+            let index = match placeholder {
+                ItemPlaceholder::QuestionMark => max_index + 1,
+                ItemPlaceholder::DollarNumber(n) | ItemPlaceholder::ColonNumber(n) => *n,
+            };
+            max_index = max_index.max(index);
+            index
+        })
+        .collect()
+}
+
 impl ToString for ItemPlaceholder {
     fn to_string(&self) -> String {
         match *self {
@@ -163,7 +189,42 @@ impl<'a> From<&'a str> for Literal {
     }
 }
 
+thread_local! {
+    /// The dialect that [`Literal`]'s [`Display`] implementation displays literals in. This is
+    /// MySQL, except while a statement is being displayed in a particular dialect with
+    /// [`SqlQuery::display`](crate::SqlQuery::display), since the [`Display`] implementations of
+    /// the nodes between the statement and its literals have no way to pass the dialect down.
+    static DISPLAY_DIALECT: Cell<Dialect> = const { Cell::new(Dialect::MySQL) };
+}
+
+/// Run `f`, displaying all [`Literal`]s in `dialect` while it runs
+pub(crate) fn with_display_dialect<R>(dialect: Dialect, f: impl FnOnce() -> R) -> R {
+    /// Restores the previous display dialect, even if `f` panics
+    struct Restore(Dialect);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            DISPLAY_DIALECT.with(|d| d.set(self.0));
+        }
+    }
+
+    let _restore = Restore(DISPLAY_DIALECT.with(|d| d.replace(dialect)));
+    f()
+}
+
 impl Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.display(DISPLAY_DIALECT.with(Cell::get)))
+    }
+}
+
+/// Displays a [`Literal`] as SQL in a particular [`Dialect`]. Returned by [`Literal::display`].
+pub struct LiteralDisplay<'a> {
+    literal: &'a Literal,
+    dialect: Dialect,
+}
+
+impl<'a> Display for LiteralDisplay<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         macro_rules! write_real {
             ($real:expr, $prec:expr) => {{
@@ -178,7 +239,7 @@ impl Display for Literal {
                 }
             }};
         }
-        match self {
+        match self.literal {
             Literal::Null => write!(f, "NULL"),
             Literal::Boolean(true) => write!(f, "TRUE"),
             Literal::Boolean(false) => write!(f, "FALSE"),
@@ -189,9 +250,17 @@ impl Display for Literal {
             Literal::Numeric(val, scale) => {
                 write!(f, "{}", Decimal::from_i128_with_scale(*val, *scale))
             }
-            Literal::String(ref s) => {
-                write!(f, "'{}'", s.replace('\'', "''").replace('\\', "\\\\"))
-            }
+            // Backslashes are only interpreted as escapes in PostgreSQL escape strings, and never
+            // in SQLite
+            Literal::String(s) => match self.dialect {
+                Dialect::MySQL => {
+                    write!(f, "'{}'", s.replace('\'', "''").replace('\\', "\\\\"))
+                }
+                Dialect::PostgreSQL if s.contains('\\') => {
+                    write!(f, "E'{}'", s.replace('\'', "''").replace('\\', "\\\\"))
+                }
+                Dialect::PostgreSQL | Dialect::SQLite => write!(f, "'{}'", s.replace('\'', "''")),
+            },
             Literal::Blob(ref bv) => write!(
                 f,
                 "{}",
@@ -219,6 +288,19 @@ impl Display for Literal {
 }
 
 impl Literal {
+    /// Returns a value which displays this literal as SQL in the given dialect.
+    ///
+    /// The [`Display`] implementation for `Literal` escapes backslashes in strings, as MySQL
+    /// requires, which PostgreSQL only interprets within escape strings (`E'...'`), unless it's
+    /// being displayed as part of a statement displayed with
+    /// [`SqlQuery::display`](crate::SqlQuery::display).
+    pub fn display(&self, dialect: Dialect) -> LiteralDisplay<'_> {
+        LiteralDisplay {
+            literal: self,
+            dialect,
+        }
+    }
+
     pub fn arbitrary_with_type(sql_type: &SqlType) -> impl Strategy<Value = Self> + 'static {
         use proptest::prelude::*;
 
@@ -331,19 +413,33 @@ impl Literal {
     }
 }
 
-// Integer literal value
+/// Integer literal value.
+///
+/// Integers too large to fit in a `u64` (or, if negative, an `i64`) are parsed as a
+/// [`Literal::Numeric`] with a scale of 0, rather than overflowing.
 pub fn integer_literal(i: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Literal> {
-    let (i, sign) = opt(tag("-"))(i)?;
-    let (i, num) = map_parser(digit1, nom::character::complete::u64)(i)?;
-
-    // Default to Unsigned unless the value is negative
-    let res = if sign.is_some() {
-        Literal::Integer(-(num as i64))
-    } else {
-        Literal::UnsignedInteger(num)
-    };
+    map_res(
+        pair(
+            opt(tag("-")),
+            map_res(digit1, |i: LocatedSpan<&[u8]>| str::from_utf8(&i)),
+        ),
+        |(sign, digits)| {
+            let negative = sign.is_some();
+            if let Ok(num) = u64::from_str(digits) {
+                // Default to Unsigned unless the value is negative
+                if !negative {
+                    return Ok(Literal::UnsignedInteger(num));
+                }
+                if let Ok(num) = i64::try_from(-(num as i128)) {
+                    return Ok(Literal::Integer(num));
+                }
+            }
 
-    Ok((i, res))
+            let num = Decimal::from_str_exact(digits)?;
+            let num = if negative { -num } else { num };
+            Ok::<_, rust_decimal::Error>(Literal::Numeric(num.mantissa(), num.scale()))
+        },
+    )(i)
 }
 
 #[allow(clippy::type_complexity)]
@@ -377,6 +473,33 @@ pub fn float_literal(i: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Literal> {
     )(i)
 }
 
+/// Exact decimal literal value, such as `1.2300`, parsed into a [`Literal::Numeric`] whose scale
+/// is the number of digits written after the decimal point.
+///
+/// Unlike [`float_literal`], the value never goes through an `f64`, so no precision is lost.
+/// Literals with more digits than can be represented by a [`Decimal`] fail to parse.
+pub fn numeric_literal(i: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Literal> {
+    map_res(
+        map_res(recognize(float), |i: LocatedSpan<&[u8]>| str::from_utf8(&i)),
+        |s| {
+            let num = Decimal::from_str_exact(s)?;
+            Ok::<_, rust_decimal::Error>(Literal::Numeric(num.mantissa(), num.scale()))
+        },
+    )(i)
+}
+
+/// Literal value for a number with a decimal point, in the given dialect.
+///
+/// In PostgreSQL, such literals are of type `numeric`, so they're parsed exactly with
+/// [`numeric_literal`], falling back to [`float_literal`] if they have too many digits. In MySQL
+/// and SQLite, they're parsed as doubles with [`float_literal`].
+fn real_literal(dialect: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Literal> {
+    move |i| match dialect {
+        Dialect::PostgreSQL => alt((numeric_literal, float_literal))(i),
+        Dialect::MySQL | Dialect::SQLite => float_literal(i),
+    }
+}
+
 fn boolean_literal(i: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Literal> {
     alt((
         map(tag_no_case("true"), |_| Literal::Boolean(true)),
@@ -424,6 +547,27 @@ fn raw_string_double_quoted(i: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Vec<u8
     raw_string_quoted(b"\"", b"\\\"")(i)
 }
 
+/// Parse a raw (binary) single-quoted string literal in which backslashes aren't escape
+/// characters, as in SQL-standard strings. The only escape sequence is a doubled quote (`''`),
+/// which represents a single quote.
+pub fn raw_standard_string_literal(i: LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Vec<u8>> {
+    delimited(
+        tag("'"),
+        fold_many0(
+            alt((
+                map(is_not("'"), |i: LocatedSpan<&[u8]>| *i),
+                map(tag("''"), |_| &b"'"[..]),
+            )),
+            Vec::new,
+            |mut acc: Vec<u8>, bytes: &[u8]| {
+                acc.extend(bytes);
+                acc
+            },
+        ),
+        tag("'"),
+    )(i)
+}
+
 /// Specification for how string literals may be quoted
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum QuotingStyle {
@@ -462,7 +606,7 @@ pub fn utf8_string_literal(
 fn simple_literal(dialect: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Literal> {
     move |i| {
         alt((
-            float_literal,
+            real_literal(dialect),
             integer_literal,
             boolean_literal,
             map(dialect.bytes_literal(), Literal::ByteArray),
@@ -585,6 +729,65 @@ mod tests {
         }
     }
 
+    #[test]
+    fn numeric_literal_preserves_scale() {
+        assert_eq!(
+            test_parse!(literal(Dialect::PostgreSQL), b"1.2300"),
+            Literal::Numeric(12300, 4)
+        );
+        assert_eq!(
+            test_parse!(literal(Dialect::PostgreSQL), b"-123.4500"),
+            Literal::Numeric(-1234500, 4)
+        );
+        assert_eq!(
+            test_parse!(literal(Dialect::PostgreSQL), b"1.2300").to_string(),
+            "1.2300"
+        );
+    }
+
+    #[test]
+    fn mysql_real_literals_are_doubles() {
+        assert!(matches!(
+            test_parse!(literal(Dialect::MySQL), b"1.2300"),
+            Literal::Double(Double { precision: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn numeric_literal_too_precise_falls_back_to_double() {
+        let res = test_parse!(
+            literal(Dialect::PostgreSQL),
+            b"1.500000000000000000000000000000000000"
+        );
+        if let Literal::Double(Double { value, .. }) = res {
+            assert_approx_eq!(value, 1.5);
+        } else {
+            panic!("Expected a double, got {res:?}")
+        }
+    }
+
+    #[test]
+    fn large_integer_literals() {
+        for &dialect in Dialect::ALL {
+            assert_eq!(
+                test_parse!(literal(dialect), b"18446744073709551615"),
+                Literal::UnsignedInteger(u64::MAX)
+            );
+            assert_eq!(
+                test_parse!(literal(dialect), b"-9223372036854775808"),
+                Literal::Integer(i64::MIN)
+            );
+            assert_eq!(
+                test_parse!(literal(dialect), b"18446744073709551616"),
+                Literal::Numeric(18446744073709551616, 0)
+            );
+            assert_eq!(
+                test_parse!(literal(dialect), b"-9223372036854775809"),
+                Literal::Numeric(-9223372036854775809, 0)
+            );
+        }
+    }
+
     #[proptest]
     fn real_hash_matches_eq(real1: Double, real2: Double) {
         assert_eq!(real1 == real2, hash(&real1) == hash(&real2));
@@ -616,6 +819,16 @@ mod tests {
                     Literal::UnsignedInteger(i as u64)
                 )
             }
+            // Strings are quoted and escaped differently in each dialect
+            Literal::String(_) => {
+                for &dialect in Dialect::ALL {
+                    let s = lit.display(dialect).to_string();
+                    assert_eq!(
+                        literal(dialect)(LocatedSpan::new(s.as_bytes())).unwrap().1,
+                        lit
+                    )
+                }
+            }
             _ => {
                 for &dialect in Dialect::ALL {
                     let s = lit.to_string();
@@ -628,6 +841,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn display_strings_per_dialect() {
+        let lit = Literal::String("it's a\\b".into());
+        assert_eq!(lit.to_string(), "'it''s a\\\\b'");
+        assert_eq!(lit.display(Dialect::MySQL).to_string(), "'it''s a\\\\b'");
+        assert_eq!(
+            lit.display(Dialect::PostgreSQL).to_string(),
+            "E'it''s a\\\\b'"
+        );
+        assert_eq!(lit.display(Dialect::SQLite).to_string(), "'it''s a\\b'");
+
+        let lit = Literal::String("plain".into());
+        assert_eq!(lit.display(Dialect::PostgreSQL).to_string(), "'plain'");
+    }
+
     #[test]
     fn boolean_literals() {
         for &dialect in Dialect::ALL {
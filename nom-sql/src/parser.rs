@@ -20,14 +20,15 @@ use crate::drop::{
 use crate::explain::{explain_statement, ExplainStatement};
 use crate::expression::expression;
 use crate::insert::{insertion, InsertStatement};
+use crate::literal::with_display_dialect;
 use crate::rename::{rename_table, RenameTableStatement};
 use crate::select::{selection, SelectStatement};
 use crate::set::{set, SetStatement};
 use crate::show::{show, ShowStatement};
 use crate::sql_type::type_identifier;
 use crate::transaction::{
-    commit, rollback, start_transaction, CommitStatement, RollbackStatement,
-    StartTransactionStatement,
+    commit, rollback, savepoint, start_transaction, CommitStatement, RollbackStatement,
+    SavepointStatement, StartTransactionStatement,
 };
 use crate::update::{updating, UpdateStatement};
 use crate::use_statement::{use_statement, UseStatement};
@@ -54,6 +55,7 @@ pub enum SqlQuery {
     StartTransaction(StartTransactionStatement),
     Commit(CommitStatement),
     Rollback(RollbackStatement),
+    Savepoint(SavepointStatement),
     RenameTable(RenameTableStatement),
     Use(UseStatement),
     Show(ShowStatement),
@@ -80,6 +82,7 @@ impl fmt::Display for SqlQuery {
             SqlQuery::StartTransaction(ref tx) => write!(f, "{}", tx),
             SqlQuery::Commit(ref commit) => write!(f, "{}", commit),
             SqlQuery::Rollback(ref rollback) => write!(f, "{}", rollback),
+            SqlQuery::Savepoint(ref savepoint) => write!(f, "{}", savepoint),
             SqlQuery::RenameTable(ref rename) => write!(f, "{}", rename),
             SqlQuery::Use(ref use_db) => write!(f, "{}", use_db),
             SqlQuery::Show(ref show) => write!(f, "{}", show),
@@ -88,6 +91,18 @@ impl fmt::Display for SqlQuery {
     }
 }
 
+/// Displays a [`SqlQuery`] as SQL in a particular [`Dialect`]. Returned by [`SqlQuery::display`].
+pub struct SqlQueryDisplay<'a> {
+    query: &'a SqlQuery,
+    dialect: Dialect,
+}
+
+impl<'a> fmt::Display for SqlQueryDisplay<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        with_display_dialect(self.dialect, || write!(f, "{}", self.query))
+    }
+}
+
 impl str::FromStr for SqlQuery {
     type Err = String;
 
@@ -97,6 +112,18 @@ impl str::FromStr for SqlQuery {
 }
 
 impl SqlQuery {
+    /// Returns a value which displays this query as SQL in the given dialect.
+    ///
+    /// The [`Display`](fmt::Display) implementation for `SqlQuery` displays literals the way MySQL
+    /// expects them, which differs from PostgreSQL for strings containing backslashes (see
+    /// [`Literal::display`](crate::Literal::display)). Identifiers are quoted the same way in both.
+    pub fn display(&self, dialect: Dialect) -> SqlQueryDisplay<'_> {
+        SqlQueryDisplay {
+            query: self,
+            dialect,
+        }
+    }
+
     /// Returns the type of the query, e.g. "CREATE TABLE" or "SELECT"
     pub fn query_type(&self) -> &'static str {
         match self {
@@ -117,6 +144,7 @@ impl SqlQuery {
             Self::StartTransaction(_) => "START TRANSACTION",
             Self::Commit(_) => "COMMIT",
             Self::Rollback(_) => "ROLLBACK",
+            Self::Savepoint(_) => "SAVEPOINT",
             Self::RenameTable(_) => "RENAME",
             Self::Use(_) => "USE",
             Self::Show(_) => "SHOW",
@@ -128,6 +156,15 @@ impl SqlQuery {
     pub fn is_select(&self) -> bool {
         matches!(self, Self::Select(_))
     }
+
+    /// Returns whether the provided SqlQuery is a transaction control statement, ie one that
+    /// starts or ends a transaction or manipulates a savepoint within one
+    pub fn is_transaction_control(&self) -> bool {
+        matches!(
+            self,
+            Self::StartTransaction(_) | Self::Commit(_) | Self::Rollback(_) | Self::Savepoint(_)
+        )
+    }
 }
 
 pub fn sql_query(dialect: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], SqlQuery> {
@@ -149,13 +186,18 @@ pub fn sql_query(dialect: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResul
             map(drop_cached_query(dialect), SqlQuery::DropCache),
             map(drop_all_caches, SqlQuery::DropAllCaches),
             map(alter_table_statement(dialect), SqlQuery::AlterTable),
-            map(start_transaction(dialect), SqlQuery::StartTransaction),
-            map(commit(dialect), SqlQuery::Commit),
-            map(rollback(dialect), SqlQuery::Rollback),
+            alt((
+                map(start_transaction(dialect), SqlQuery::StartTransaction),
+                map(commit(dialect), SqlQuery::Commit),
+                // Must come before `rollback`, which would otherwise match the `ROLLBACK` prefix
+                // of `ROLLBACK TO SAVEPOINT`
+                map(savepoint(dialect), SqlQuery::Savepoint),
+                map(rollback(dialect), SqlQuery::Rollback),
+            )),
             map(rename_table(dialect), SqlQuery::RenameTable),
             map(use_statement(dialect), SqlQuery::Use),
             map(show(dialect), SqlQuery::Show),
-            map(explain_statement, SqlQuery::Explain),
+            map(explain_statement(dialect), SqlQuery::Explain),
         ))(i)
     }
 }
@@ -361,7 +403,8 @@ mod tests {
         use std::hash::{Hash, Hasher};
 
         use super::*;
-        use crate::table::Relation;
+        use crate::table::{Relation, TableExpr};
+        use crate::{with_folded_table_names, FieldDefinitionExpr};
 
         #[test]
         fn trim_query() {
@@ -399,6 +442,7 @@ mod tests {
                 ]],
                 ignore: false,
                 on_duplicate: None,
+                on_conflict: None,
             });
             let mut h0 = DefaultHasher::new();
             let mut h1 = DefaultHasher::new();
@@ -407,6 +451,42 @@ mod tests {
             assert_eq!(h0.finish(), h1.finish());
         }
 
+        #[test]
+        fn fold_table_names() {
+            let query = "SELECT Bar FROM Schema1.Foo";
+            let expected = |schema: &str, table: &str| {
+                SqlQuery::Select(SelectStatement {
+                    tables: vec![TableExpr::from(Relation {
+                        schema: Some(schema.into()),
+                        name: table.into(),
+                    })],
+                    fields: vec![FieldDefinitionExpr::Expr {
+                        expr: Expr::Column("Bar".into()),
+                        alias: None,
+                    }],
+                    ..Default::default()
+                })
+            };
+
+            assert_eq!(
+                parse_query(Dialect::MySQL, query).unwrap(),
+                expected("Schema1", "Foo")
+            );
+            // Only the names of relations are folded, not other identifiers
+            assert_eq!(
+                with_folded_table_names(|| parse_query(Dialect::MySQL, query)).unwrap(),
+                expected("schema1", "foo")
+            );
+            assert_eq!(
+                with_folded_table_names(|| parse_query(
+                    Dialect::MySQL,
+                    "SELECT Bar FROM `Schema1`.`Foo`"
+                ))
+                .unwrap(),
+                expected("schema1", "foo")
+            );
+        }
+
         #[test]
         fn format_query_with_escaped_keyword() {
             let qstring0 = "delete from articles where `key`='aaa'";
@@ -429,7 +509,8 @@ mod tests {
         use std::hash::{Hash, Hasher};
 
         use super::*;
-        use crate::table::Relation;
+        use crate::table::{Relation, TableExpr};
+        use crate::{with_folded_table_names, FieldDefinitionExpr};
 
         #[test]
         fn trim_query() {
@@ -473,6 +554,7 @@ mod tests {
                 ]],
                 ignore: false,
                 on_duplicate: None,
+                on_conflict: None,
             });
             let mut h0 = DefaultHasher::new();
             let mut h1 = DefaultHasher::new();
@@ -496,5 +578,53 @@ mod tests {
             assert_eq!(expected0, res0.unwrap().to_string());
             assert_eq!(expected1, res1.unwrap().to_string());
         }
+
+        #[test]
+        fn select_from_mixed_case_table() {
+            let expected = SqlQuery::Select(SelectStatement {
+                tables: vec![TableExpr::from(Relation::from("foo"))],
+                fields: vec![FieldDefinitionExpr::All],
+                ..Default::default()
+            });
+            assert_eq!(
+                parse_query(Dialect::PostgreSQL, "SELECT * FROM Foo").unwrap(),
+                expected
+            );
+            // Folding table names has no effect on PostgreSQL, which always folds unquoted
+            // identifiers and never folds quoted ones
+            assert_eq!(
+                with_folded_table_names(|| parse_query(Dialect::PostgreSQL, "SELECT * FROM Foo"))
+                    .unwrap(),
+                expected
+            );
+            assert_eq!(
+                with_folded_table_names(|| parse_query(
+                    Dialect::PostgreSQL,
+                    r#"SELECT * FROM "Foo""#
+                ))
+                .unwrap(),
+                SqlQuery::Select(SelectStatement {
+                    tables: vec![TableExpr::from(Relation::from("Foo"))],
+                    fields: vec![FieldDefinitionExpr::All],
+                    ..Default::default()
+                })
+            );
+        }
+
+        #[test]
+        fn display_escape_string_round_trip() {
+            let query = parse_query(Dialect::PostgreSQL, r"SELECT E'\\'").unwrap();
+            let displayed = query.display(Dialect::PostgreSQL).to_string();
+            assert_eq!(displayed, r"SELECT E'\\'");
+            assert_eq!(parse_query(Dialect::PostgreSQL, &displayed).unwrap(), query);
+
+            // Plain strings keep their backslashes as-is
+            let query = parse_query(Dialect::PostgreSQL, r"SELECT '\'").unwrap();
+            assert_eq!(
+                query.display(Dialect::PostgreSQL).to_string(),
+                r"SELECT E'\\'"
+            );
+            assert_eq!(query.to_string(), r"SELECT '\\'");
+        }
     }
 }
@@ -833,6 +833,43 @@ mod tests {
         res3_pgsql.unwrap_err();
     }
 
+    #[test]
+    fn mysql_limit_offset_forms_are_equivalent() {
+        let comma = test_parse!(
+            selection(Dialect::MySQL),
+            b"select * from users limit 10, 5"
+        );
+        let offset = test_parse!(
+            selection(Dialect::MySQL),
+            b"select * from users limit 5 offset 10"
+        );
+        assert_eq!(comma.limit_clause.limit(), Some(&5_u32.into()));
+        assert_eq!(comma.limit_clause.offset(), Some(&10_u32.into()));
+        assert_eq!(comma.limit_clause.limit(), offset.limit_clause.limit());
+        assert_eq!(comma.limit_clause.offset(), offset.limit_clause.offset());
+
+        let placeholder = Literal::Placeholder(ItemPlaceholder::QuestionMark);
+        let comma = test_parse!(selection(Dialect::MySQL), b"select * from users limit ?, ?");
+        assert_eq!(
+            comma.limit_clause,
+            LimitClause::OffsetCommaLimit {
+                offset: placeholder.clone(),
+                limit: placeholder.clone(),
+            }
+        );
+        let offset = test_parse!(
+            selection(Dialect::MySQL),
+            b"select * from users limit ? offset ?"
+        );
+        assert_eq!(
+            offset.limit_clause,
+            LimitClause::LimitOffset {
+                limit: Some(placeholder.clone()),
+                offset: Some(placeholder),
+            }
+        );
+    }
+
     #[test]
     fn table_alias() {
         let qstring1 = "select * from PaperTag as t;";
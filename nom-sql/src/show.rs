@@ -22,6 +22,8 @@ pub enum ShowStatement {
     ReadySetStatus,
     ReadySetVersion,
     ReadySetTables,
+    ReadySetConnectionStats,
+    ReadySetSearchPath,
 }
 
 impl fmt::Display for ShowStatement {
@@ -47,6 +49,8 @@ impl fmt::Display for ShowStatement {
             Self::ReadySetStatus => write!(f, "READYSET STATUS"),
             Self::ReadySetVersion => write!(f, "READYSET VERSION"),
             Self::ReadySetTables => write!(f, "READYSET TABLES"),
+            Self::ReadySetConnectionStats => write!(f, "READYSET CONNECTION STATS"),
+            Self::ReadySetSearchPath => write!(f, "READYSET SEARCH_PATH"),
         }
     }
 }
@@ -106,6 +110,24 @@ pub fn show(dialect: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u
                 ShowStatement::ReadySetTables,
                 tuple((tag_no_case("readyset"), whitespace1, tag_no_case("tables"))),
             ),
+            value(
+                ShowStatement::ReadySetConnectionStats,
+                tuple((
+                    tag_no_case("readyset"),
+                    whitespace1,
+                    tag_no_case("connection"),
+                    whitespace1,
+                    tag_no_case("stats"),
+                )),
+            ),
+            value(
+                ShowStatement::ReadySetSearchPath,
+                tuple((
+                    tag_no_case("readyset"),
+                    whitespace1,
+                    tag_no_case("search_path"),
+                )),
+            ),
             map(show_tables(dialect), ShowStatement::Tables),
             value(ShowStatement::Events, tag_no_case("events")),
         ))(i)?;
@@ -368,4 +390,22 @@ mod tests {
         let res = test_parse!(show(Dialect::MySQL), b"SHOW READYSET TABLES");
         assert_eq!(res, ShowStatement::ReadySetTables);
     }
+
+    #[test]
+    fn show_readyset_connection_stats() {
+        for &dialect in Dialect::ALL {
+            let res = test_parse!(show(dialect), b"SHOW READYSET CONNECTION STATS");
+            assert_eq!(res, ShowStatement::ReadySetConnectionStats);
+            assert_eq!(res.to_string(), "SHOW READYSET CONNECTION STATS");
+        }
+    }
+
+    #[test]
+    fn show_readyset_search_path() {
+        for &dialect in Dialect::ALL {
+            let res = test_parse!(show(dialect), b"SHOW READYSET SEARCH_PATH");
+            assert_eq!(res, ShowStatement::ReadySetSearchPath);
+            assert_eq!(res.to_string(), "SHOW READYSET SEARCH_PATH");
+        }
+    }
 }
@@ -654,7 +654,7 @@ fn type_identifier_part3(
 fn other_type(dialect: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Relation> {
     move |i| match dialect {
         Dialect::PostgreSQL => relation(dialect)(i),
-        Dialect::MySQL => Err(nom::Err::Error(ParseError::from_error_kind(
+        Dialect::MySQL | Dialect::SQLite => Err(nom::Err::Error(ParseError::from_error_kind(
             i,
             ErrorKind::IsNot,
         ))),
@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::fmt::Display;
 use std::hash::Hash;
 use std::{fmt, str};
@@ -143,10 +144,43 @@ impl From<Relation> for TableExpr {
 }
 
 // Parse a reference to a named schema.table
+thread_local! {
+    /// Whether the names of relations and their schemas are folded to lowercase when parsing
+    /// MySQL, as set by [`with_folded_table_names`]. Threading this through every parser which
+    /// parses a relation isn't practical, so it's set for the duration of a parse instead.
+    static FOLD_TABLE_NAMES: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f`, folding the names of all relations (and their schemas) parsed in the MySQL dialect
+/// while it runs to lowercase, as MySQL does when `lower_case_table_names` is set. Other
+/// identifiers, such as column names, are unaffected.
+///
+/// See [`Dialect::identifier_with_case`]
+pub fn with_folded_table_names<R>(f: impl FnOnce() -> R) -> R {
+    /// Restores the previous setting, even if `f` panics
+    struct Restore(bool);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            FOLD_TABLE_NAMES.with(|fold| fold.set(self.0));
+        }
+    }
+
+    let _restore = Restore(FOLD_TABLE_NAMES.with(|fold| fold.replace(true)));
+    f()
+}
+
+/// Parse the name of a table, view, or schema
+fn relation_identifier(
+    dialect: Dialect,
+) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], SqlIdentifier> {
+    move |i| dialect.identifier_with_case(FOLD_TABLE_NAMES.with(Cell::get))(i)
+}
+
 pub fn relation(dialect: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Relation> {
     move |i| {
-        let (i, schema) = opt(terminated(dialect.identifier(), tag(".")))(i)?;
-        let (i, name) = dialect.identifier()(i)?;
+        let (i, schema) = opt(terminated(relation_identifier(dialect), tag(".")))(i)?;
+        let (i, name) = relation_identifier(dialect)(i)?;
         Ok((i, Relation { schema, name }))
     }
 }
@@ -207,9 +241,9 @@ pub fn replicator_table_reference(
     dialect: Dialect,
 ) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], Relation> {
     move |i| {
-        let (i, schema) = opt(terminated(dialect.identifier(), tag(".")))(i)?;
+        let (i, schema) = opt(terminated(relation_identifier(dialect), tag(".")))(i)?;
         let (i, name) = alt((
-            dialect.identifier(),
+            relation_identifier(dialect),
             map(tag("*"), |_| SqlIdentifier::from("*")),
         ))(i)?;
         Ok((i, Relation { schema, name }))
@@ -3,12 +3,12 @@ use std::fmt;
 use nom::branch::alt;
 use nom::bytes::complete::tag_no_case;
 use nom::combinator::{map, opt};
-use nom::sequence::tuple;
+use nom::sequence::{terminated, tuple};
 use nom_locate::LocatedSpan;
 use serde::{Deserialize, Serialize};
 
 use crate::whitespace::{whitespace0, whitespace1};
-use crate::{Dialect, NomSqlResult};
+use crate::{Dialect, NomSqlResult, SqlIdentifier};
 
 // TODO(peter): Handle dialect differences.
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
@@ -38,6 +38,34 @@ impl fmt::Display for RollbackStatement {
     }
 }
 
+/// The operation performed on a savepoint by a [`SavepointStatement`]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum SavepointAction {
+    /// `SAVEPOINT <name>`
+    Create,
+    /// `RELEASE SAVEPOINT <name>`
+    Release,
+    /// `ROLLBACK TO SAVEPOINT <name>`
+    RollbackTo,
+}
+
+/// A statement which creates, releases, or rolls back to a savepoint within a transaction
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct SavepointStatement {
+    pub action: SavepointAction,
+    pub name: SqlIdentifier,
+}
+
+impl fmt::Display for SavepointStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.action {
+            SavepointAction::Create => write!(f, "SAVEPOINT {}", self.name),
+            SavepointAction::Release => write!(f, "RELEASE SAVEPOINT {}", self.name),
+            SavepointAction::RollbackTo => write!(f, "ROLLBACK TO SAVEPOINT {}", self.name),
+        }
+    }
+}
+
 // Parse rule for a START TRANSACTION query.
 // TODO(peter): Handle dialect differences.
 pub fn start_transaction(
@@ -78,6 +106,8 @@ pub fn start_transaction(
 // [AND [NO] CHAIN] [[NO] RELEASE]
 // [PostgreSQL](https://www.postgresql.org/docs/current/sql-commit.html) allows:
 // [ AND [ NO ] CHAIN ]
+// [SQLite](https://www.sqlite.org/lang_transaction.html) allows `END` in place of `COMMIT`, and
+// an optional trailing `TRANSACTION`, like PostgreSQL
 pub fn commit(d: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], CommitStatement> {
     move |i| {
         let (remaining_input, (_, _)) = match d {
@@ -88,7 +118,7 @@ pub fn commit(d: Dialect) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8],
                     opt(tuple((whitespace1, tag_no_case("work")))),
                 )),
             ))(i)?,
-            Dialect::PostgreSQL => tuple((
+            Dialect::PostgreSQL | Dialect::SQLite => tuple((
                 whitespace0,
                 tuple((
                     alt((tag_no_case("commit"), tag_no_case("end"))),
@@ -122,6 +152,49 @@ pub fn rollback(
     }
 }
 
+// Parse rule for a SAVEPOINT, RELEASE SAVEPOINT, or ROLLBACK TO SAVEPOINT query.
+//
+// MySQL requires the SAVEPOINT keyword in RELEASE SAVEPOINT, but since PostgreSQL doesn't and
+// we only need to recognize these statements (not validate them), we accept it as optional in
+// both dialects.
+pub fn savepoint(
+    d: Dialect,
+) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], SavepointStatement> {
+    move |i| {
+        let (i, _) = whitespace0(i)?;
+        let (i, action) = alt((
+            map(terminated(tag_no_case("savepoint"), whitespace1), |_| {
+                SavepointAction::Create
+            }),
+            map(
+                tuple((
+                    tag_no_case("release"),
+                    whitespace1,
+                    opt(terminated(tag_no_case("savepoint"), whitespace1)),
+                )),
+                |_| SavepointAction::Release,
+            ),
+            map(
+                tuple((
+                    tag_no_case("rollback"),
+                    whitespace1,
+                    opt(terminated(
+                        alt((tag_no_case("work"), tag_no_case("transaction"))),
+                        whitespace1,
+                    )),
+                    tag_no_case("to"),
+                    whitespace1,
+                    opt(terminated(tag_no_case("savepoint"), whitespace1)),
+                )),
+                |_| SavepointAction::RollbackTo,
+            ),
+        ))(i)?;
+        let (i, name) = d.identifier()(i)?;
+
+        Ok((i, SavepointStatement { action, name }))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -209,4 +282,59 @@ mod tests {
         let res = rollback(Dialect::MySQL)(LocatedSpan::new(qstring.as_bytes()));
         assert_eq!(res.unwrap().1, RollbackStatement,);
     }
+
+    #[test]
+    fn savepoints() {
+        for (dialect, qstring, action) in [
+            (Dialect::MySQL, "SAVEPOINT sp1", SavepointAction::Create),
+            (
+                Dialect::MySQL,
+                "RELEASE SAVEPOINT sp1",
+                SavepointAction::Release,
+            ),
+            (
+                Dialect::MySQL,
+                "ROLLBACK TO sp1",
+                SavepointAction::RollbackTo,
+            ),
+            (
+                Dialect::MySQL,
+                "  rollback work to savepoint `sp1`",
+                SavepointAction::RollbackTo,
+            ),
+            (Dialect::PostgreSQL, "RELEASE sp1", SavepointAction::Release),
+            (
+                Dialect::PostgreSQL,
+                "ROLLBACK TRANSACTION TO SAVEPOINT \"sp1\"",
+                SavepointAction::RollbackTo,
+            ),
+        ] {
+            let res = savepoint(dialect)(LocatedSpan::new(qstring.as_bytes()))
+                .unwrap()
+                .1;
+            assert_eq!(
+                res,
+                SavepointStatement {
+                    action,
+                    name: "sp1".into()
+                },
+                "{qstring}"
+            );
+        }
+
+        assert!(savepoint(Dialect::MySQL)(LocatedSpan::new(b"ROLLBACK")).is_err());
+    }
+
+    #[test]
+    fn rollback_to_savepoint_is_not_rollback() {
+        let res = crate::parse_query(Dialect::MySQL, "ROLLBACK TO SAVEPOINT sp1").unwrap();
+        assert_eq!(
+            res,
+            crate::SqlQuery::Savepoint(SavepointStatement {
+                action: SavepointAction::RollbackTo,
+                name: "sp1".into()
+            })
+        );
+        assert!(res.is_transaction_control());
+    }
 }
@@ -250,7 +250,7 @@ mod tests {
         use crate::column::Column;
         use crate::table::Relation;
         use crate::Expr::UnaryOp;
-        use crate::{BinaryOperator, Double, UnaryOperator};
+        use crate::{BinaryOperator, UnaryOperator};
 
         #[test]
         fn updated_with_neg_float() {
@@ -274,10 +274,7 @@ mod tests {
                         Column::from("hotness"),
                         UnaryOp {
                             op: UnaryOperator::Neg,
-                            rhs: Box::new(Expr::Literal(Literal::Double(Double {
-                                value: 19216.5479744,
-                                precision: 7,
-                            })))
+                            rhs: Box::new(Expr::Literal(Literal::Numeric(192165479744, 7)))
                         },
                     ),],
                     where_clause: expected_where_cond,
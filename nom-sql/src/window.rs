@@ -0,0 +1,397 @@
+use std::fmt::{self, Display};
+use std::iter;
+
+use itertools::Itertools;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, tag_no_case};
+use nom::combinator::{map, opt, value};
+use nom::multi::separated_list1;
+use nom::sequence::{preceded, terminated, tuple};
+use nom_locate::LocatedSpan;
+use serde::{Deserialize, Serialize};
+
+use crate::common::ws_sep_comma;
+use crate::expression::expression;
+use crate::order::order_clause;
+use crate::whitespace::{whitespace0, whitespace1};
+use crate::{Dialect, Expr, FieldReference, NomSqlResult, OrderClause};
+
+/// The units in which the extent of a [`WindowFrame`] is measured
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum WindowFrameUnits {
+    /// `ROWS` - the frame is a number of rows before and after the current row
+    Rows,
+    /// `RANGE` - the frame is all the rows whose `ORDER BY` value is within a range of the current
+    /// row's
+    Range,
+}
+
+impl Display for WindowFrameUnits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WindowFrameUnits::Rows => write!(f, "ROWS"),
+            WindowFrameUnits::Range => write!(f, "RANGE"),
+        }
+    }
+}
+
+/// One end of a [`WindowFrame`]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum WindowFrameBound {
+    /// `UNBOUNDED PRECEDING`
+    UnboundedPreceding,
+    /// `<expr> PRECEDING`
+    Preceding(Box<Expr>),
+    /// `CURRENT ROW`
+    CurrentRow,
+    /// `<expr> FOLLOWING`
+    Following(Box<Expr>),
+    /// `UNBOUNDED FOLLOWING`
+    UnboundedFollowing,
+}
+
+impl Display for WindowFrameBound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WindowFrameBound::UnboundedPreceding => write!(f, "UNBOUNDED PRECEDING"),
+            WindowFrameBound::Preceding(offset) => write!(f, "{} PRECEDING", offset),
+            WindowFrameBound::CurrentRow => write!(f, "CURRENT ROW"),
+            WindowFrameBound::Following(offset) => write!(f, "{} FOLLOWING", offset),
+            WindowFrameBound::UnboundedFollowing => write!(f, "UNBOUNDED FOLLOWING"),
+        }
+    }
+}
+
+/// The frame clause of a [`WindowSpecification`], which determines the subset of the rows in the
+/// current row's partition that the window function is evaluated over
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct WindowFrame {
+    pub units: WindowFrameUnits,
+    pub start: WindowFrameBound,
+    /// The end of the frame, if specified with `BETWEEN <start> AND <end>`. If omitted, the frame
+    /// ends at the current row
+    pub end: Option<WindowFrameBound>,
+}
+
+impl Display for WindowFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.end {
+            Some(end) => write!(f, "{} BETWEEN {} AND {}", self.units, self.start, end),
+            None => write!(f, "{} {}", self.units, self.start),
+        }
+    }
+}
+
+/// The window specification in the `OVER (...)` clause of a window function call
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct WindowSpecification {
+    /// The expressions in the `PARTITION BY` clause, which is omitted if this is empty
+    pub partition_by: Vec<Expr>,
+    pub order_by: Option<OrderClause>,
+    pub frame: Option<WindowFrame>,
+}
+
+impl WindowSpecification {
+    /// Returns an iterator over all the expressions directly contained within this window
+    /// specification
+    pub fn expressions(&self) -> impl Iterator<Item = &Expr> {
+        let order_by = self
+            .order_by
+            .iter()
+            .flat_map(|oc| oc.order_by.iter())
+            .filter_map(|(field, _)| match field {
+                FieldReference::Expr(expr) => Some(expr),
+                FieldReference::Numeric(_) => None,
+            });
+        let frame = self
+            .frame
+            .iter()
+            .flat_map(|frame| iter::once(&frame.start).chain(frame.end.as_ref()))
+            .filter_map(|bound| match bound {
+                WindowFrameBound::Preceding(offset) | WindowFrameBound::Following(offset) => {
+                    Some(offset.as_ref())
+                }
+                _ => None,
+            });
+        self.partition_by.iter().chain(order_by).chain(frame)
+    }
+
+    /// Returns an iterator over mutable references to all the expressions directly contained
+    /// within this window specification
+    pub fn expressions_mut(&mut self) -> impl Iterator<Item = &mut Expr> {
+        let order_by = self
+            .order_by
+            .iter_mut()
+            .flat_map(|oc| oc.order_by.iter_mut())
+            .filter_map(|(field, _)| match field {
+                FieldReference::Expr(expr) => Some(expr),
+                FieldReference::Numeric(_) => None,
+            });
+        let frame = self
+            .frame
+            .iter_mut()
+            .flat_map(|frame| iter::once(&mut frame.start).chain(frame.end.as_mut()))
+            .filter_map(|bound| match bound {
+                WindowFrameBound::Preceding(offset) | WindowFrameBound::Following(offset) => {
+                    Some(offset.as_mut())
+                }
+                _ => None,
+            });
+        self.partition_by.iter_mut().chain(order_by).chain(frame)
+    }
+}
+
+impl Display for WindowSpecification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut clauses = vec![];
+        if !self.partition_by.is_empty() {
+            clauses.push(format!(
+                "PARTITION BY {}",
+                self.partition_by.iter().join(", ")
+            ));
+        }
+        if let Some(order_by) = &self.order_by {
+            clauses.push(order_by.to_string());
+        }
+        if let Some(frame) = &self.frame {
+            clauses.push(frame.to_string());
+        }
+        write!(f, "{}", clauses.join(" "))
+    }
+}
+
+fn window_frame_bound(
+    dialect: Dialect,
+) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], WindowFrameBound> {
+    move |i| {
+        alt((
+            value(
+                WindowFrameBound::UnboundedPreceding,
+                tuple((
+                    tag_no_case("unbounded"),
+                    whitespace1,
+                    tag_no_case("preceding"),
+                )),
+            ),
+            value(
+                WindowFrameBound::UnboundedFollowing,
+                tuple((
+                    tag_no_case("unbounded"),
+                    whitespace1,
+                    tag_no_case("following"),
+                )),
+            ),
+            value(
+                WindowFrameBound::CurrentRow,
+                tuple((tag_no_case("current"), whitespace1, tag_no_case("row"))),
+            ),
+            map(
+                terminated(
+                    expression(dialect),
+                    tuple((whitespace1, tag_no_case("preceding"))),
+                ),
+                |offset| WindowFrameBound::Preceding(Box::new(offset)),
+            ),
+            map(
+                terminated(
+                    expression(dialect),
+                    tuple((whitespace1, tag_no_case("following"))),
+                ),
+                |offset| WindowFrameBound::Following(Box::new(offset)),
+            ),
+        ))(i)
+    }
+}
+
+fn window_frame(
+    dialect: Dialect,
+) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], WindowFrame> {
+    move |i| {
+        let (i, units) = alt((
+            value(WindowFrameUnits::Rows, tag_no_case("rows")),
+            value(WindowFrameUnits::Range, tag_no_case("range")),
+        ))(i)?;
+        let (i, _) = whitespace1(i)?;
+        let (i, (start, end)) = alt((
+            map(
+                tuple((
+                    tag_no_case("between"),
+                    whitespace1,
+                    window_frame_bound(dialect),
+                    whitespace1,
+                    tag_no_case("and"),
+                    whitespace1,
+                    window_frame_bound(dialect),
+                )),
+                |(_, _, start, _, _, _, end)| (start, Some(end)),
+            ),
+            map(window_frame_bound(dialect), |start| (start, None)),
+        ))(i)?;
+
+        Ok((i, WindowFrame { units, start, end }))
+    }
+}
+
+/// Parse a parenthesized window specification, as found after `OVER` in a window function call
+pub fn window_specification(
+    dialect: Dialect,
+) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], WindowSpecification> {
+    move |i| {
+        let (i, _) = tag("(")(i)?;
+        let (i, _) = whitespace0(i)?;
+        let (i, partition_by) = opt(terminated(
+            preceded(
+                tuple((
+                    tag_no_case("partition"),
+                    whitespace1,
+                    tag_no_case("by"),
+                    whitespace1,
+                )),
+                separated_list1(ws_sep_comma, expression(dialect)),
+            ),
+            whitespace0,
+        ))(i)?;
+        let (i, order_by) = opt(terminated(order_clause(dialect), whitespace0))(i)?;
+        let (i, frame) = opt(terminated(window_frame(dialect), whitespace0))(i)?;
+        let (i, _) = tag(")")(i)?;
+
+        Ok((
+            i,
+            WindowSpecification {
+                partition_by: partition_by.unwrap_or_default(),
+                order_by,
+                frame,
+            },
+        ))
+    }
+}
+
+/// Parse the `OVER (...)` clause following a window function call
+pub fn over_clause(
+    dialect: Dialect,
+) -> impl Fn(LocatedSpan<&[u8]>) -> NomSqlResult<&[u8], WindowSpecification> {
+    move |i| {
+        let (i, _) = tag_no_case("over")(i)?;
+        let (i, _) = whitespace0(i)?;
+        window_specification(dialect)(i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_query, FunctionExpr, Literal, OrderType, SqlQuery};
+
+    fn window_fields(dialect: Dialect, query: &str) -> Vec<Expr> {
+        match parse_query(dialect, query).unwrap() {
+            SqlQuery::Select(stmt) => stmt
+                .fields
+                .into_iter()
+                .filter_map(|field| match field {
+                    crate::FieldDefinitionExpr::Expr { expr, .. } => Some(expr),
+                    _ => None,
+                })
+                .collect(),
+            q => panic!("Expected select, got {:?}", q),
+        }
+    }
+
+    #[test]
+    fn empty_over() {
+        for dialect in [Dialect::MySQL, Dialect::PostgreSQL] {
+            let fields = window_fields(dialect, "SELECT count(*) OVER () FROM t");
+            assert_eq!(
+                fields,
+                vec![Expr::WindowFunction {
+                    function: FunctionExpr::CountStar,
+                    window: WindowSpecification::default(),
+                }]
+            );
+            assert_eq!(fields[0].to_string(), "count(*) OVER ()");
+        }
+    }
+
+    #[test]
+    fn partition_and_order() {
+        for dialect in [Dialect::MySQL, Dialect::PostgreSQL] {
+            let fields = window_fields(
+                dialect,
+                "SELECT x, sum(x) OVER (PARTITION BY y, w ORDER BY z DESC) AS s FROM t",
+            );
+            assert_eq!(
+                fields[1],
+                Expr::WindowFunction {
+                    function: FunctionExpr::Sum {
+                        expr: Box::new(Expr::Column("x".into())),
+                        distinct: false,
+                    },
+                    window: WindowSpecification {
+                        partition_by: vec![Expr::Column("y".into()), Expr::Column("w".into())],
+                        order_by: Some(OrderClause {
+                            order_by: vec![(
+                                FieldReference::Expr(Expr::Column("z".into())),
+                                Some(OrderType::OrderDescending)
+                            )]
+                        }),
+                        frame: None,
+                    },
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn rows_between_frame() {
+        for dialect in [Dialect::MySQL, Dialect::PostgreSQL] {
+            let fields = window_fields(
+                dialect,
+                "SELECT avg(x) over (order by z rows between 2 preceding and current row) FROM t",
+            );
+            match &fields[0] {
+                Expr::WindowFunction { window, .. } => assert_eq!(
+                    window.frame,
+                    Some(WindowFrame {
+                        units: WindowFrameUnits::Rows,
+                        start: WindowFrameBound::Preceding(Box::new(Expr::Literal(
+                            Literal::UnsignedInteger(2)
+                        ))),
+                        end: Some(WindowFrameBound::CurrentRow),
+                    })
+                ),
+                e => panic!("Expected window function, got {:?}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn frame_without_between() {
+        let fields = window_fields(
+            Dialect::PostgreSQL,
+            "SELECT max(x) OVER (RANGE UNBOUNDED PRECEDING) FROM t",
+        );
+        match &fields[0] {
+            Expr::WindowFunction { window, .. } => assert_eq!(
+                window.frame,
+                Some(WindowFrame {
+                    units: WindowFrameUnits::Range,
+                    start: WindowFrameBound::UnboundedPreceding,
+                    end: None,
+                })
+            ),
+            e => panic!("Expected window function, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn format_round_trip() {
+        for query in [
+            "SELECT sum(x) OVER (PARTITION BY y ORDER BY z ASC) FROM t",
+            "SELECT avg(x) OVER (ORDER BY z ROWS BETWEEN UNBOUNDED PRECEDING AND 1 FOLLOWING) FROM t",
+            "SELECT count(*) OVER (RANGE CURRENT ROW) FROM t",
+        ] {
+            let parsed = parse_query(Dialect::MySQL, query).unwrap();
+            let reparsed = parse_query(Dialect::MySQL, parsed.to_string()).unwrap();
+            assert_eq!(parsed, reparsed);
+        }
+    }
+}
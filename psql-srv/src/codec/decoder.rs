@@ -27,6 +27,9 @@ use crate::value::Value;
 const ID_AUTHENTICATE: u8 = b'p';
 const ID_BIND: u8 = b'B';
 const ID_CLOSE: u8 = b'C';
+const ID_COPY_DATA: u8 = b'd';
+const ID_COPY_DONE: u8 = b'c';
+const ID_COPY_FAIL: u8 = b'f';
 const ID_DESCRIBE: u8 = b'D';
 const ID_EXECUTE: u8 = b'E';
 const ID_FLUSH: u8 = b'H';
@@ -42,7 +45,9 @@ const DESCRIBE_TYPE_PORTAL: u8 = b'P';
 const DESCRIBE_TYPE_PREPARED_STATEMENT: u8 = b'S';
 
 const SSL_REQUEST_CODE: i32 = 80877103;
+const CANCEL_REQUEST_CODE: i32 = 80877102;
 
+const STARTUP_MESSAGE_APPLICATION_NAME_PARAMETER: &str = "application_name";
 const STARTUP_MESSAGE_DATABASE_PARAMETER: &str = "database";
 const STARTUP_MESSAGE_TERMINATOR: &str = "";
 const STARTUP_MESSAGE_USER_PARAMETER: &str = "user";
@@ -54,6 +59,24 @@ const NUL_BYTE: u8 = b'\0';
 const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
 const TIMESTAMP_TZ_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f %:z";
 
+/// The length of a `CancelRequest` message, in bytes
+pub const CANCEL_REQUEST_LENGTH: usize = 16;
+
+/// If `msg` is a complete `CancelRequest` message, returns the process ID and secret key it
+/// contains.
+///
+/// Clients send a `CancelRequest` as the only message on a new connection, so this can be used to
+/// handle cancel requests without setting up a backend for the connection.
+pub fn parse_cancel_request(mut msg: &[u8]) -> Option<(i32, i32)> {
+    if msg.len() != CANCEL_REQUEST_LENGTH
+        || msg.get_i32() != CANCEL_REQUEST_LENGTH as i32
+        || msg.get_i32() != CANCEL_REQUEST_CODE
+    {
+        return None;
+    }
+    Some((msg.get_i32(), msg.get_i32()))
+}
+
 impl<R: IntoIterator<Item: TryInto<Value, Error = BackendError>>> Decoder for Codec<R> {
     type Item = FrontendMessage;
     type Error = Error;
@@ -95,10 +118,16 @@ impl<R: IntoIterator<Item: TryInto<Value, Error = BackendError>>> Decoder for Co
             let ret = match token {
                 SSL_REQUEST_CODE => Ok(Some(SSLRequest)),
 
+                CANCEL_REQUEST_CODE => Ok(Some(CancelRequest {
+                    process_id: get_i32(msg)?,
+                    secret_key: get_i32(msg)?,
+                })),
+
                 // Parse StartupMessage
                 protocol_version => {
                     let mut user: Option<BytesStr> = None;
                     let mut database: Option<BytesStr> = None;
+                    let mut application_name: Option<BytesStr> = None;
                     loop {
                         let key = get_str(msg)?;
                         if key.borrow() as &str == STARTUP_MESSAGE_TERMINATOR {
@@ -109,12 +138,16 @@ impl<R: IntoIterator<Item: TryInto<Value, Error = BackendError>>> Decoder for Co
                             user = Some(val);
                         } else if key.borrow() as &str == STARTUP_MESSAGE_DATABASE_PARAMETER {
                             database = Some(val);
+                        } else if key.borrow() as &str == STARTUP_MESSAGE_APPLICATION_NAME_PARAMETER
+                        {
+                            application_name = Some(val);
                         }
                     }
                     Ok(Some(StartupMessage {
                         protocol_version,
                         user,
                         database,
+                        application_name,
                     }))
                 }
             };
@@ -205,6 +238,16 @@ impl<R: IntoIterator<Item: TryInto<Value, Error = BackendError>>> Decoder for Co
                 Ok(Some(Close { name }))
             }
 
+            ID_COPY_DATA => Ok(Some(CopyData {
+                data: msg.split_to(msg.len()),
+            })),
+
+            ID_COPY_DONE => Ok(Some(CopyDone)),
+
+            ID_COPY_FAIL => Ok(Some(CopyFail {
+                message: get_str(msg)?,
+            })),
+
             ID_DESCRIBE => {
                 let statement_type = get_u8(msg)?;
                 let name_str = get_str(msg)?;
@@ -477,6 +520,41 @@ mod tests {
         codec.decode(&mut buf).unwrap_err();
     }
 
+    #[test]
+    fn test_decode_cancel_request() {
+        let mut codec = Codec::<Vec<Value>>::new();
+        let mut buf = BytesMut::new();
+        buf.put_i32(16); // size
+        buf.put_i32(80877102); // cancel request code
+        buf.put_i32(42); // process id
+        buf.put_i32(-7); // secret key
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(CancelRequest {
+                process_id: 42,
+                secret_key: -7
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_cancel_request() {
+        let mut buf = BytesMut::new();
+        buf.put_i32(16); // size
+        buf.put_i32(80877102); // cancel request code
+        buf.put_i32(42); // process id
+        buf.put_i32(-7); // secret key
+        assert_eq!(parse_cancel_request(&buf), Some((42, -7)));
+        assert_eq!(parse_cancel_request(&buf[..12]), None);
+
+        let mut buf = BytesMut::new();
+        buf.put_i32(16); // size
+        buf.put_i32(80877103); // ssl request code
+        buf.put_i32(42);
+        buf.put_i32(-7);
+        assert_eq!(parse_cancel_request(&buf), None);
+    }
+
     #[test]
     fn test_decode_startup_message() {
         let mut codec = Codec::<Vec<Value>>::new();
@@ -492,6 +570,29 @@ mod tests {
             protocol_version: 196608,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
+        });
+        assert_eq!(codec.decode(&mut buf).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_decode_startup_message_with_application_name() {
+        let mut codec = Codec::<Vec<Value>>::new();
+        let mut buf = BytesMut::new();
+        buf.put_i32(4 + 4 + 5 + 10 + 9 + 14 + 17 + 9 + 1); // size
+        buf.put_i32(196608); // standard protocol version
+        buf.extend_from_slice(b"user\0");
+        buf.extend_from_slice(b"user_name\0");
+        buf.extend_from_slice(b"database\0");
+        buf.extend_from_slice(b"database_name\0");
+        buf.extend_from_slice(b"application_name\0");
+        buf.extend_from_slice(b"checkout\0");
+        buf.put_u8(b'\0');
+        let expected = Some(StartupMessage {
+            protocol_version: 196608,
+            user: Some(bytes_str("user_name")),
+            database: Some(bytes_str("database_name")),
+            application_name: Some(bytes_str("checkout")),
         });
         assert_eq!(codec.decode(&mut buf).unwrap(), expected);
     }
@@ -900,6 +1001,35 @@ mod tests {
         codec.decode(&mut buf).unwrap_err();
     }
 
+    #[test]
+    fn test_decode_copy_messages() {
+        let mut codec = Codec::<Vec<Value>>::new();
+        codec.set_start_up_complete();
+        let mut buf = BytesMut::new();
+        buf.put_u8(b'd'); // message id
+        buf.put_i32(4 + 6); // size
+        buf.extend_from_slice(b"1\tfoo\n");
+        buf.put_u8(b'c'); // message id
+        buf.put_i32(4); // size
+        buf.put_u8(b'f'); // message id
+        buf.put_i32(4 + 8); // size
+        buf.extend_from_slice(b"aborted\0");
+
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(CopyData {
+                data: Bytes::from_static(b"1\tfoo\n")
+            })
+        );
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(CopyDone));
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(CopyFail {
+                message: bytes_str("aborted")
+            })
+        );
+    }
+
     #[test]
     fn test_decode_sync() {
         let mut codec = Codec::<Vec<Value>>::new();
@@ -15,9 +15,14 @@ use crate::message::TransferFormat::{self, *};
 use crate::value::Value;
 
 const ID_AUTHENTICATION_REQUEST: u8 = b'R';
+const ID_BACKEND_KEY_DATA: u8 = b'K';
 const ID_BIND_COMPLETE: u8 = b'2';
 const ID_CLOSE_COMPLETE: u8 = b'3';
 const ID_COMMAND_COMPLETE: u8 = b'C';
+const ID_COPY_DATA: u8 = b'd';
+const ID_COPY_DONE: u8 = b'c';
+const ID_COPY_IN_RESPONSE: u8 = b'G';
+const ID_COPY_OUT_RESPONSE: u8 = b'H';
 const ID_DATA_ROW: u8 = b'D';
 const ID_ERROR_RESPONSE: u8 = b'E';
 const ID_PARAMETER_DESCRIPTION: u8 = b't';
@@ -29,6 +34,9 @@ const ID_ROW_DESCRIPTION: u8 = b'T';
 const AUTHENTICATION_OK_SUCCESS: i32 = 0;
 const AUTHENTICATION_CLEARTEXT_REQUIRED: i32 = 3;
 
+const COMMAND_COMPLETE_COPY_TAG: &str = "COPY";
+const COMMAND_COMPLETE_DEALLOCATE_TAG: &str = "DEALLOCATE";
+const COMMAND_COMPLETE_DEALLOCATE_ALL_TAG: &str = "DEALLOCATE ALL";
 const COMMAND_COMPLETE_DELETE_TAG: &str = "DELETE";
 const COMMAND_COMPLETE_INSERT_TAG: &str = "INSERT";
 const COMMAND_COMPLETE_INSERT_LEGACY_OID: &str = "0";
@@ -100,6 +108,16 @@ where
             put_i32(AUTHENTICATION_OK_SUCCESS, dst);
         }
 
+        BackendKeyData {
+            process_id,
+            secret_key,
+        } => {
+            put_u8(ID_BACKEND_KEY_DATA, dst);
+            put_i32(LENGTH_PLACEHOLDER, dst);
+            put_i32(process_id, dst);
+            put_i32(secret_key, dst);
+        }
+
         BindComplete => {
             put_u8(ID_BIND_COMPLETE, dst);
             put_i32(LENGTH_PLACEHOLDER, dst);
@@ -116,6 +134,11 @@ where
             // Format command complete "tag" (eg "DELETE 5" to indicate 5 rows deleted).
             let mut tag_buf = [0u8; COMMAND_COMPLETE_TAG_BUF_LEN];
             match tag {
+                Copy(n) => write!(&mut tag_buf[..], "{} {}", COMMAND_COMPLETE_COPY_TAG, n)?,
+                Deallocate => write!(&mut tag_buf[..], "{}", COMMAND_COMPLETE_DEALLOCATE_TAG)?,
+                DeallocateAll => {
+                    write!(&mut tag_buf[..], "{}", COMMAND_COMPLETE_DEALLOCATE_ALL_TAG)?
+                }
                 Delete(n) => write!(&mut tag_buf[..], "{} {}", COMMAND_COMPLETE_DELETE_TAG, n)?,
                 Empty => {}
                 Insert(n) => write!(
@@ -147,6 +170,29 @@ where
             put_str(tag_str, dst);
         }
 
+        CopyInResponse { format } => {
+            put_u8(ID_COPY_IN_RESPONSE, dst);
+            put_i32(LENGTH_PLACEHOLDER, dst);
+            put_copy_format(format, dst);
+        }
+
+        CopyOutResponse { format } => {
+            put_u8(ID_COPY_OUT_RESPONSE, dst);
+            put_i32(LENGTH_PLACEHOLDER, dst);
+            put_copy_format(format, dst);
+        }
+
+        CopyData(data) => {
+            put_u8(ID_COPY_DATA, dst);
+            put_i32(LENGTH_PLACEHOLDER, dst);
+            put_slice(&data, dst);
+        }
+
+        CopyDone => {
+            put_u8(ID_COPY_DONE, dst);
+            put_i32(LENGTH_PLACEHOLDER, dst);
+        }
+
         DataRow {
             values,
             explicit_transfer_formats,
@@ -338,6 +384,17 @@ fn put_format(val: TransferFormat, dst: &mut BytesMut) {
     put_i16(format_code, dst)
 }
 
+/// Put the body of a `CopyInResponse` or `CopyOutResponse` message: the overall format of the
+/// copy, followed by a count of zero per-column formats
+fn put_copy_format(val: TransferFormat, dst: &mut BytesMut) {
+    let format_code = match val {
+        Binary => 1,
+        Text => 0,
+    };
+    put_u8(format_code, dst);
+    put_i16(0, dst);
+}
+
 fn put_type(val: Type, dst: &mut BytesMut) -> Result<(), Error> {
     let oid = i32::try_from(val.oid())?;
     put_i32(oid, dst);
@@ -559,7 +616,7 @@ mod tests {
     use std::sync::Arc;
 
     use bit_vec::BitVec;
-    use bytes::{BufMut, BytesMut};
+    use bytes::{BufMut, Bytes, BytesMut};
     use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
     use eui48::MacAddress;
     use postgres::SimpleQueryRow;
@@ -606,6 +663,27 @@ mod tests {
         assert_eq!(buf, exp);
     }
 
+    #[test]
+    fn test_encode_backend_key_data() {
+        let mut codec = Codec::<Vec<Value>>::new();
+        let mut buf = BytesMut::new();
+        codec
+            .encode(
+                BackendKeyData {
+                    process_id: 42,
+                    secret_key: -7,
+                },
+                &mut buf,
+            )
+            .unwrap();
+        let mut exp = BytesMut::new();
+        exp.put_u8(b'K'); // message id
+        exp.put_i32(12); // message length
+        exp.put_i32(42); // process id
+        exp.put_i32(-7); // secret key
+        assert_eq!(buf, exp);
+    }
+
     #[test]
     fn test_encode_authentication_cleartext_password() {
         let mut codec = Codec::<Vec<Value>>::new();
@@ -642,6 +720,62 @@ mod tests {
         assert_eq!(buf, exp);
     }
 
+    #[test]
+    fn test_encode_command_complete_copy() {
+        let mut codec = Codec::<Vec<Value>>::new();
+        let mut buf = BytesMut::new();
+        codec
+            .encode(CommandComplete { tag: Copy(3) }, &mut buf)
+            .unwrap();
+        let mut exp = BytesMut::new();
+        exp.put_u8(b'C'); // message id
+        exp.put_i32(4 + 7); // message length
+        exp.extend_from_slice(b"COPY 3\0");
+        assert_eq!(buf, exp);
+    }
+
+    #[test]
+    fn test_encode_copy_messages() {
+        let mut codec = Codec::<Vec<Value>>::new();
+        let mut buf = BytesMut::new();
+        codec
+            .encode(
+                CopyInResponse {
+                    format: TransferFormat::Text,
+                },
+                &mut buf,
+            )
+            .unwrap();
+        codec
+            .encode(
+                CopyOutResponse {
+                    format: TransferFormat::Binary,
+                },
+                &mut buf,
+            )
+            .unwrap();
+        codec
+            .encode(CopyData(Bytes::from_static(b"1\tfoo\n")), &mut buf)
+            .unwrap();
+        codec.encode(CopyDone, &mut buf).unwrap();
+
+        let mut exp = BytesMut::new();
+        exp.put_u8(b'G'); // message id
+        exp.put_i32(4 + 1 + 2); // message length
+        exp.put_u8(0); // text format
+        exp.put_i16(0); // no column formats
+        exp.put_u8(b'H'); // message id
+        exp.put_i32(4 + 1 + 2); // message length
+        exp.put_u8(1); // binary format
+        exp.put_i16(0); // no column formats
+        exp.put_u8(b'd'); // message id
+        exp.put_i32(4 + 6); // message length
+        exp.extend_from_slice(b"1\tfoo\n");
+        exp.put_u8(b'c'); // message id
+        exp.put_i32(4); // message length
+        assert_eq!(buf, exp);
+    }
+
     #[test]
     fn test_encode_command_complete_delete() {
         let mut codec = Codec::<Vec<Value>>::new();
@@ -6,6 +6,7 @@ use std::collections::HashMap;
 use std::convert::TryInto;
 use std::marker::PhantomData;
 
+pub use decoder::{parse_cancel_request, CANCEL_REQUEST_LENGTH};
 pub use error::{DecodeError, EncodeError};
 use postgres_types::Type;
 
@@ -11,6 +11,21 @@ pub enum Error {
     #[error("password authentication failed for user \"{0}\"")]
     AuthenticationFailure(String),
 
+    /// The connection was terminated by an administrator. The error is reported to the frontend
+    /// as fatal, and the connection is closed.
+    #[error("{0}")]
+    AdminShutdown(String),
+
+    /// The frontend aborted a `COPY ... FROM STDIN` by sending a `CopyFail` message with the
+    /// given reason
+    #[error("COPY from stdin failed: {0}")]
+    CopyFailed(String),
+
+    /// The frontend tried to create a named prepared statement with the same name as an existing
+    /// one
+    #[error("prepared statement \"{0}\" already exists")]
+    DuplicatePreparedStatement(String),
+
     #[error("decode error: {0}")]
     DecodeError(#[from] DecodeError),
 
@@ -38,6 +53,9 @@ pub enum Error {
     #[error("parse error: {0}")]
     ParseError(String),
 
+    #[error("canceling statement due to user request")]
+    QueryCancelled,
+
     #[error("unimplemented: {0}")]
     Unimplemented(String),
 
@@ -56,3 +74,11 @@ pub enum Error {
     #[error(transparent)]
     PostgresError(#[from] tokio_postgres::error::Error),
 }
+
+impl Error {
+    /// Returns `true` if this error should terminate the connection once it has been reported to
+    /// the frontend
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Error::AdminShutdown(_))
+    }
+}
@@ -28,12 +28,15 @@ mod value;
 use std::convert::TryInto;
 
 use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::Stream;
 use postgres::SimpleQueryMessage;
 use postgres_types::Type;
 use protocol::Protocol;
 use tokio::io::{AsyncRead, AsyncWrite};
 
 pub use crate::bytes::BytesStr;
+pub use crate::codec::{parse_cancel_request, CANCEL_REQUEST_LENGTH};
 pub use crate::error::Error;
 pub use crate::value::Value;
 
@@ -59,9 +62,10 @@ pub trait Backend {
     /// produce `Self::Value`s.
     type Row: IntoIterator<Item = Self::Value>;
 
-    /// An associated type representing a resultset returned by a SQL query, which can be iterated
-    /// to produce `Self::Row`s.
-    type Resultset: IntoIterator<Item = Self::Row>;
+    /// An associated type representing a resultset returned by a SQL query, which can be polled to
+    /// produce `Self::Row`s. Rows are written to the frontend as they're produced, so a resultset
+    /// doesn't need to hold all of its rows in memory at once.
+    type Resultset: Stream<Item = Result<Self::Row, Error>> + Unpin;
 
     /// The postgresql server version number to send to the client on startup, along with ReadySet
     /// info
@@ -78,6 +82,14 @@ pub trait Backend {
     ///   instance.
     async fn on_init(&mut self, database: &str) -> Result<CredentialsNeeded, Error>;
 
+    /// Called on startup, before [`on_init`](Backend::on_init), with the name of the client
+    /// application if the client sent one in the `application_name` startup parameter
+    fn on_application_name(&mut self, _application_name: &str) {}
+
+    /// Called on startup, before [`on_init`](Backend::on_init), with the name of the user the
+    /// client is connecting as
+    fn on_user(&mut self, _user: &str) {}
+
     /// Validate authentication credentials provided by connected client
     ///
     /// * `credentials` - Authentication info provided by the client
@@ -113,8 +125,66 @@ pub trait Backend {
     ///
     /// * `statement_id` - The identifier of the prepared statement to close.
     async fn on_close(&mut self, statement_id: u32) -> Result<(), Error>;
+
+    /// The process ID and secret key to send to the client in a `BackendKeyData` message on
+    /// startup, which the client can later use to cancel queries running on this connection. If
+    /// `None`, no `BackendKeyData` message is sent.
+    fn backend_key_data(&self) -> Option<(i32, i32)> {
+        None
+    }
+
+    /// Cancels the query currently running on the connection identified by `process_id` and
+    /// `secret_key`, as previously sent to the client in a `BackendKeyData` message.
+    ///
+    /// This is called on the backend for a new connection, which is closed once the cancel request
+    /// has been handled. Servers which would rather not set up a backend for such connections can
+    /// instead check for a cancel request themselves, using [`parse_cancel_request`].
+    async fn on_cancel(&mut self, _process_id: i32, _secret_key: i32) {}
+
+    /// Called after each request from the client has been handled, and its response sent
+    fn on_request_complete(&mut self) {}
+
+    /// Starts a `COPY ... FROM STDIN` statement. Once this returns successfully, the data sent by
+    /// the client is passed to [`on_copy_data`](Backend::on_copy_data) until the client either
+    /// completes the copy, in which case [`on_copy_done`](Backend::on_copy_done) is called, or
+    /// aborts it, in which case [`on_copy_fail`](Backend::on_copy_fail) is called.
+    ///
+    /// By default, `COPY` statements are unsupported.
+    async fn on_copy_in(&mut self, _query: &str) -> Result<(), Error> {
+        Err(Error::Unsupported("COPY FROM STDIN".to_string()))
+    }
+
+    /// Receives a chunk of the data being copied in by the current `COPY ... FROM STDIN`
+    /// statement.
+    async fn on_copy_data(&mut self, _data: ::bytes::Bytes) -> Result<(), Error> {
+        Err(Error::Unsupported("COPY FROM STDIN".to_string()))
+    }
+
+    /// Completes the current `COPY ... FROM STDIN` statement, returning the number of rows copied.
+    async fn on_copy_done(&mut self) -> Result<u64, Error> {
+        Err(Error::Unsupported("COPY FROM STDIN".to_string()))
+    }
+
+    /// Aborts the current `COPY ... FROM STDIN` statement, either because the client sent a
+    /// `CopyFail` message or because copying the data failed.
+    async fn on_copy_fail(&mut self) {}
+
+    /// Runs a `COPY ... TO STDOUT` statement, returning the copied data to send to the client.
+    ///
+    /// By default, `COPY` statements are unsupported.
+    async fn on_copy_out(&mut self, _query: &str) -> Result<CopyOutStream, Error> {
+        Err(Error::Unsupported("COPY TO STDOUT".to_string()))
+    }
 }
 
+/// The data produced by a `COPY ... TO STDOUT` statement, as a stream of the chunks of data to send
+/// to the client in `CopyData` messages
+pub type CopyOutStream = BoxStream<'static, Result<::bytes::Bytes, Error>>;
+
+/// The messages produced by a statement run with the simple query protocol, which are sent to the
+/// client as they're read from the stream
+pub type SimpleQueryStream = BoxStream<'static, Result<SimpleQueryMessage, Error>>;
+
 /// A description of a column, either in the parameters to a query or in a resultset
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Column {
@@ -157,7 +227,7 @@ pub enum QueryResponse<S> {
     /// The response to a SimpleQuery statement. The statement may contain one or more SQL
     /// commands (e.g., SELECT, INSERT, DELETE, etc.). The SimpleQuery protocol is distinct from
     /// the prepare/execute protocol.
-    SimpleQuery(Vec<SimpleQueryMessage>),
+    SimpleQuery(SimpleQueryStream),
 }
 
 /// Run a `Backend` on the provided bytestream until the bytestream is remotely closed.
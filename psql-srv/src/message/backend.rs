@@ -29,12 +29,29 @@ const SSL_RESPONSE_N: u8 = b'N';
 pub enum BackendMessage<R> {
     AuthenticationCleartextPassword,
     AuthenticationOk,
+    BackendKeyData {
+        process_id: i32,
+        secret_key: i32,
+    },
     BindComplete,
     CloseComplete,
     CommandComplete {
         tag: CommandCompleteTag,
     },
     PassThroughCommandComplete(Bytes),
+    /// Sent in response to a `COPY ... FROM STDIN` statement, to indicate that the backend is
+    /// ready to receive `CopyData` messages from the frontend. Since the backend doesn't know the
+    /// number of columns being copied, no per-column formats are sent.
+    CopyInResponse {
+        format: TransferFormat,
+    },
+    /// Sent in response to a `COPY ... TO STDOUT` statement, before the copied data. As with
+    /// `CopyInResponse`, no per-column formats are sent.
+    CopyOutResponse {
+        format: TransferFormat,
+    },
+    CopyData(Bytes),
+    CopyDone,
     DataRow {
         values: R,
         explicit_transfer_formats: Option<Arc<Vec<TransferFormat>>>,
@@ -81,6 +98,9 @@ impl<R: IntoIterator<Item: TryInto<Value, Error = Error>>> BackendMessage<R> {
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CommandCompleteTag {
+    Copy(u64),
+    Deallocate,
+    DeallocateAll,
     Delete(u64),
     Empty,
     Insert(u64),
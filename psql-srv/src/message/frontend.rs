@@ -1,5 +1,6 @@
 use std::fmt;
 
+use bytes::Bytes;
 use postgres_types::Type;
 
 use crate::bytes::BytesStr;
@@ -27,9 +28,20 @@ pub enum FrontendMessage {
         params: Vec<Value>,
         result_transfer_formats: Vec<TransferFormat>,
     },
+    CancelRequest {
+        process_id: i32,
+        secret_key: i32,
+    },
     Close {
         name: StatementName,
     },
+    CopyData {
+        data: Bytes,
+    },
+    CopyDone,
+    CopyFail {
+        message: BytesStr,
+    },
     Describe {
         name: StatementName,
     },
@@ -53,6 +65,7 @@ pub enum FrontendMessage {
         protocol_version: i32,
         user: Option<BytesStr>,
         database: Option<BytesStr>,
+        application_name: Option<BytesStr>,
     },
     Sync,
     Flush,
@@ -69,7 +82,11 @@ impl fmt::Display for FrontendMessage {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Bind { .. } => write!(f, "Bind"),
+            Self::CancelRequest { .. } => write!(f, "CancelRequest"),
             Self::Close { .. } => write!(f, "Close"),
+            Self::CopyData { .. } => write!(f, "CopyData"),
+            Self::CopyDone => write!(f, "CopyDone"),
+            Self::CopyFail { .. } => write!(f, "CopyFail"),
             Self::Describe { .. } => write!(f, "Describe"),
             Self::Execute { .. } => write!(f, "Execute"),
             Self::Parse { .. } => write!(f, "Parse"),
@@ -2,12 +2,12 @@ use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use futures::TryStreamExt;
 use postgres::SimpleQueryMessage;
 use postgres_protocol::Oid;
 use postgres_types::{Kind, Type};
-use smallvec::smallvec;
+use smallvec::{smallvec, SmallVec};
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio_postgres::CommandCompleteContents;
 
 use crate::bytes::BytesStr;
 use crate::channel::Channel;
@@ -17,7 +17,7 @@ use crate::message::FrontendMessage::{self, *};
 use crate::message::StatementName::*;
 use crate::message::TransferFormat::{self, *};
 use crate::message::{CommandCompleteTag, ErrorSeverity, FieldDescription, SqlState};
-use crate::response::Response;
+use crate::response::{CopyOutData, CopyTextOptions, Response, SimpleQueryData};
 use crate::value::Value;
 use crate::QueryResponse::*;
 use crate::{Backend, Column, PrepareResponse};
@@ -46,8 +46,10 @@ const UNKNOWN_TABLE: i32 = 0;
 /// * StartingUp -> Authenticating
 /// * Authenticating -> Ready
 /// * Ready -> Extended
+/// * Ready -> CopyIn
 /// * Extended -> Error
 /// * Error -> Ready
+/// * CopyIn -> Ready
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum State {
     /// The server is starting up
@@ -70,6 +72,12 @@ pub(crate) enum State {
     /// [0]: https://www.postgresql.org/docs/13/protocol-flow.html#PROTOCOL-FLOW-EXT-QUERY
     /// [1]: psql_srv::message::frontend::FrontendMessage::Sync
     Error,
+
+    /// The server is receiving the data for a `COPY ... FROM STDIN` statement from the client, in
+    /// the [copy-in sub-protocol][0]
+    ///
+    /// [0]: https://www.postgresql.org/docs/13/protocol-flow.html#PROTOCOL-COPY
+    CopyIn,
 }
 
 /// A struct to maintain state for an implementation of the backend side of the PostgreSQL
@@ -103,6 +111,121 @@ struct PreparedStatementData {
     prepared_statement_id: u32,
     param_schema: Vec<Type>,
     row_schema: Vec<Column>,
+    /// The query text and parameter types the statement was parsed with, used to reuse the
+    /// statement if the frontend parses the same query under the same name again
+    query: String,
+    parameter_data_types: Vec<Type>,
+}
+
+/// The target of a `DEALLOCATE` statement
+#[derive(Debug, PartialEq, Eq)]
+enum Deallocate {
+    /// `DEALLOCATE name`
+    Name(String),
+    /// `DEALLOCATE ALL`
+    All,
+}
+
+/// If `query` is a `DEALLOCATE [PREPARE] { name | ALL }` statement, returns what it deallocates.
+///
+/// Unquoted names are folded to lowercase, as they are by PostgreSQL.
+fn parse_deallocate(query: &str) -> Option<Deallocate> {
+    let query = query.trim().trim_end_matches(';').trim_end();
+    let (keyword, rest) = query.split_once(char::is_whitespace)?;
+    if !keyword.eq_ignore_ascii_case("deallocate") {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let target = match rest.split_once(char::is_whitespace) {
+        Some((prepare, target)) if prepare.eq_ignore_ascii_case("prepare") => target.trim_start(),
+        _ => rest,
+    };
+
+    if let Some(quoted) = target
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .filter(|t| !t.is_empty())
+    {
+        return Some(Deallocate::Name(quoted.replace("\"\"", "\"")));
+    }
+    if target.is_empty() || target.contains(|c: char| c.is_whitespace() || c == '"') {
+        return None;
+    }
+    if target.eq_ignore_ascii_case("all") {
+        Some(Deallocate::All)
+    } else {
+        Some(Deallocate::Name(target.to_lowercase()))
+    }
+}
+
+/// The direction data is copied in by a `COPY` statement
+#[derive(Debug, PartialEq, Eq)]
+enum CopyDirection {
+    /// `COPY ... FROM STDIN`
+    In,
+    /// `COPY ... TO STDOUT`
+    Out,
+}
+
+/// If `query` is a `COPY ... FROM STDIN` or `COPY ... TO STDOUT` statement, returns the direction
+/// it copies data in, along with the format of the copied data.
+///
+/// `COPY` statements that read from or write to a file on the server aren't matched, since they
+/// don't transfer any data over the connection.
+fn parse_copy(query: &str) -> Option<(CopyDirection, TransferFormat)> {
+    let mut words = query.trim().trim_end_matches(';').split_whitespace();
+    if !words.next()?.eq_ignore_ascii_case("copy") {
+        return None;
+    }
+    let words = words.collect::<Vec<_>>();
+    let (options_start, direction) =
+        words
+            .windows(2)
+            .enumerate()
+            .find_map(|(i, pair)| match (pair[0], pair[1]) {
+                (from, stdin)
+                    if from.eq_ignore_ascii_case("from") && stdin.eq_ignore_ascii_case("stdin") =>
+                {
+                    Some((i + 2, CopyDirection::In))
+                }
+                (to, stdout)
+                    if to.eq_ignore_ascii_case("to") && stdout.eq_ignore_ascii_case("stdout") =>
+                {
+                    Some((i + 2, CopyDirection::Out))
+                }
+                _ => None,
+            })?;
+
+    // Either `BINARY` or `(FORMAT binary)`
+    let binary = words[options_start..].iter().any(|w| {
+        w.trim_matches(|c: char| !c.is_ascii_alphabetic())
+            .eq_ignore_ascii_case("binary")
+    });
+    Some((direction, if binary { Binary } else { Text }))
+}
+
+/// Parses the options of a `COPY` statement which determine how rows are laid out in text format
+/// data: whether it's in CSV format (either `CSV` or `(FORMAT csv)`), and whether it has a header
+/// line (either `HEADER` or `(HEADER true)`, or similar).
+fn parse_copy_text_options(query: &str) -> CopyTextOptions {
+    let words = query
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == ',' || c == ';')
+        .filter(|w| !w.is_empty())
+        .skip_while(|w| !w.eq_ignore_ascii_case("stdout"))
+        .collect::<Vec<_>>();
+    let mut options = CopyTextOptions::default();
+    for (i, word) in words.iter().enumerate() {
+        if word.eq_ignore_ascii_case("csv") {
+            options.csv = true;
+        } else if word.eq_ignore_ascii_case("header") {
+            options.header = !words.get(i + 1).map_or(false, |value| {
+                ["false", "off", "0"]
+                    .iter()
+                    .any(|off| value.eq_ignore_ascii_case(off))
+            });
+        }
+    }
+    options
 }
 
 /// A portal is a combination of a prepared statement and a list of values provided by the frontend
@@ -150,8 +273,8 @@ impl Protocol {
         channel: &mut Channel<C, B::Row>,
     ) -> Result<Response<B::Row, B::Resultset>, Error> {
         // TODO(grfn): Discard if self.state.is_error()?
-        let get_ready_message = |version| {
-            smallvec![
+        let get_ready_message = |version, backend_key_data: Option<(i32, i32)>| {
+            let mut messages: SmallVec<[BackendMessage<B::Row>; 2]> = smallvec![
                 AuthenticationOk,
                 BackendMessage::ParameterStatus {
                     parameter_name: "client_encoding".to_owned(),
@@ -173,8 +296,15 @@ impl Protocol {
                     parameter_name: "server_version".to_owned(),
                     parameter_value: version,
                 },
-                BackendMessage::ready_for_query_idle(),
-            ]
+            ];
+            if let Some((process_id, secret_key)) = backend_key_data {
+                messages.push(BackendMessage::BackendKeyData {
+                    process_id,
+                    secret_key,
+                });
+            }
+            messages.push(BackendMessage::ready_for_query_idle());
+            messages
         };
         match self.state {
             State::StartingUp => match message {
@@ -184,14 +314,35 @@ impl Protocol {
                     Ok(Response::Message(BackendMessage::ssl_response_n()))
                 }
 
+                // A request, sent on a new connection, to cancel the query currently running on
+                // another connection. No response is sent; the frontend closes the connection.
+                CancelRequest {
+                    process_id,
+                    secret_key,
+                } => {
+                    backend.on_cancel(process_id, secret_key).await;
+                    Ok(Response::Empty)
+                }
+
                 // A request to start up a connection, with some metadata provided.
-                StartupMessage { database, user, .. } => {
+                StartupMessage {
+                    database,
+                    user,
+                    application_name,
+                    ..
+                } => {
                     let database = database
                         .ok_or_else(|| Error::Unsupported("database is required".to_string()))?;
+                    if let Some(application_name) = application_name {
+                        backend.on_application_name(application_name.borrow());
+                    }
+                    if let Some(user) = &user {
+                        backend.on_user(user.borrow());
+                    }
                     let response = match backend.on_init(database.borrow()).await? {
                         crate::CredentialsNeeded::None => {
                             self.state = State::Ready;
-                            get_ready_message(backend.version())
+                            get_ready_message(backend.version(), backend.backend_key_data())
                         }
                         crate::CredentialsNeeded::Cleartext => {
                             self.state = State::Authenticating {
@@ -221,12 +372,52 @@ impl Protocol {
                         .await?;
                     self.state = State::Ready;
 
-                    Ok(Response::Messages(get_ready_message(backend.version())))
+                    Ok(Response::Messages(get_ready_message(
+                        backend.version(),
+                        backend.backend_key_data(),
+                    )))
                 }
 
                 m => Err(Error::UnsupportedMessage(m)),
             },
 
+            State::CopyIn => match message {
+                FrontendMessage::CopyData { data } => {
+                    if let Err(e) = backend.on_copy_data(data).await {
+                        self.state = State::Ready;
+                        backend.on_copy_fail().await;
+                        return Err(e);
+                    }
+                    Ok(Response::Empty)
+                }
+
+                FrontendMessage::CopyDone => {
+                    self.state = State::Ready;
+                    let n_rows = backend.on_copy_done().await?;
+                    Ok(Response::Messages(smallvec![
+                        CommandComplete {
+                            tag: CommandCompleteTag::Copy(n_rows)
+                        },
+                        BackendMessage::ready_for_query_idle(),
+                    ]))
+                }
+
+                CopyFail { message } => {
+                    self.state = State::Ready;
+                    backend.on_copy_fail().await;
+                    Err(Error::CopyFailed(message.to_string()))
+                }
+
+                // Flush and Sync are allowed, and ignored, during copy-in
+                Flush | Sync => Ok(Response::Empty),
+
+                m => {
+                    self.state = State::Ready;
+                    backend.on_copy_fail().await;
+                    Err(Error::UnsupportedMessage(m))
+                }
+            },
+
             _ => match message {
                 // A request to bind parameters to a prepared statement, creating a portal.
                 Bind {
@@ -280,16 +471,8 @@ impl Protocol {
                         }
 
                         PreparedStatement(name) => {
-                            if let Some(id) = self
-                                .prepared_statements
-                                .get(name.borrow() as &str)
-                                .map(|d| d.prepared_statement_id)
-                            {
-                                backend.on_close(id).await?;
-                                channel.clear_statement_param_types(name.borrow() as &str);
-                                self.prepared_statements.remove(name.borrow() as &str);
-                                // TODO Remove all portals referencing this prepared statement.
-                            }
+                            self.close_prepared_statement(name.borrow(), backend, channel)
+                                .await?;
                         }
                     };
                     Ok(Response::Message(CloseComplete))
@@ -407,6 +590,55 @@ impl Protocol {
                 // A request to directly execute a complete SQL statement, without creating a
                 // prepared statement.
                 Query { query } => {
+                    // COPY statements switch the connection into a sub-protocol for transferring
+                    // the copied data, which the backend can't express as a query response
+                    match parse_copy(query.borrow()) {
+                        Some((CopyDirection::In, format)) => {
+                            backend.on_copy_in(query.borrow()).await?;
+                            self.state = State::CopyIn;
+                            return Ok(Response::Message(CopyInResponse { format }));
+                        }
+                        Some((CopyDirection::Out, format)) => {
+                            let data = backend.on_copy_out(query.borrow()).await?;
+                            return Ok(Response::CopyOut {
+                                format,
+                                options: parse_copy_text_options(query.borrow()),
+                                data: CopyOutData(data),
+                            });
+                        }
+                        None => {}
+                    }
+
+                    // Prepared statements created with the extended query protocol can also be
+                    // deallocated with SQL, but only the protocol knows about them. Statements we
+                    // don't know about may have been prepared with SQL `PREPARE`, so we let the
+                    // backend handle those.
+                    let deallocated = match parse_deallocate(query.borrow()) {
+                        Some(Deallocate::All) => {
+                            // `DEALLOCATE ALL` also deallocates any statements prepared with SQL
+                            // `PREPARE`, so it's passed through to the backend as well
+                            backend.on_query(query.borrow()).await?;
+                            let names =
+                                self.prepared_statements.keys().cloned().collect::<Vec<_>>();
+                            for name in names {
+                                self.close_prepared_statement(&name, backend, channel)
+                                    .await?;
+                            }
+                            Some(CommandCompleteTag::DeallocateAll)
+                        }
+                        Some(Deallocate::Name(name)) => self
+                            .close_prepared_statement(&name, backend, channel)
+                            .await?
+                            .then_some(CommandCompleteTag::Deallocate),
+                        None => None,
+                    };
+                    if let Some(tag) = deallocated {
+                        return Ok(Response::Messages(smallvec![
+                            CommandComplete { tag },
+                            BackendMessage::ready_for_query_idle(),
+                        ]));
+                    }
+
                     let response = backend.on_query(query.borrow()).await?;
                     if let Select { schema, resultset } = response {
                         let mut field_descriptions = Vec::with_capacity(schema.len());
@@ -423,46 +655,8 @@ impl Protocol {
                             result_transfer_formats: None,
                             trailer: Some(BackendMessage::ready_for_query_idle()),
                         })
-                    } else if let SimpleQuery(resp) = response {
-                        let mut messages = smallvec![];
-                        let mut processing_select = false;
-                        for msg in resp {
-                            match msg {
-                                SimpleQueryMessage::Row(row) => {
-                                    if !processing_select {
-                                        // Create a message for the RowDescription. We use the
-                                        // PassThrough version since this message comes directly
-                                        // from tokio-postgres.
-                                        messages.push(BackendMessage::PassThroughRowDescription(
-                                            row.fields().to_vec(),
-                                        ));
-                                        processing_select = true;
-                                    }
-                                    // Create a message for each row
-                                    messages.push(BackendMessage::PassThroughDataRow(row))
-                                }
-                                SimpleQueryMessage::CommandComplete(CommandCompleteContents {
-                                    fields,
-                                    tag,
-                                    ..
-                                }) => {
-                                    if let Some(f) = fields {
-                                        messages.push(BackendMessage::PassThroughRowDescription(
-                                            f.to_vec(),
-                                        ));
-                                    }
-                                    messages.push(BackendMessage::PassThroughCommandComplete(tag));
-                                    processing_select = false;
-                                }
-                                _ => {
-                                    return Err(Error::InternalError(
-                                        "Unexpected SimpleQuery message variant".to_string(),
-                                    ));
-                                }
-                            }
-                        }
-                        messages.push(BackendMessage::ready_for_query_idle());
-                        Ok(Response::Messages(messages))
+                    } else if let SimpleQuery(messages) = response {
+                        Ok(Response::SimpleQuery(SimpleQueryData(messages)))
                     } else {
                         let tag = match response {
                             Insert(n) => CommandCompleteTag::Insert(n),
@@ -488,23 +682,43 @@ impl Protocol {
                 Parse {
                     prepared_statement_name,
                     query,
-                    ..
+                    parameter_data_types,
                 } => {
+                    let name: &str = prepared_statement_name.borrow();
+                    let query: &str = query.borrow();
+
+                    // Named statements must be closed before their name can be reused, but the
+                    // unnamed statement is replaced by each Parse. Frontends commonly re-parse
+                    // the same query as the unnamed statement before every execution - if the
+                    // query is unchanged, reuse the statement we already prepared rather than
+                    // preparing it again.
+                    if let Some(existing) = self.prepared_statements.get(name) {
+                        if !name.is_empty() {
+                            return Err(Error::DuplicatePreparedStatement(name.to_owned()));
+                        }
+                        if existing.query == query
+                            && existing.parameter_data_types == parameter_data_types
+                        {
+                            return Ok(Response::Message(ParseComplete));
+                        }
+                        self.close_prepared_statement(name, backend, channel)
+                            .await?;
+                    }
+
                     let PrepareResponse {
                         prepared_statement_id,
                         param_schema,
                         row_schema,
-                    } = backend.on_prepare(query.borrow()).await?;
-                    channel.set_statement_param_types(
-                        prepared_statement_name.borrow() as &str,
-                        param_schema.clone(),
-                    );
+                    } = backend.on_prepare(query).await?;
+                    channel.set_statement_param_types(name, param_schema.clone());
                     self.prepared_statements.insert(
-                        prepared_statement_name.to_string(),
+                        name.to_owned(),
                         PreparedStatementData {
                             prepared_statement_id,
                             param_schema,
                             row_schema,
+                            query: query.to_owned(),
+                            parameter_data_types,
                         },
                     );
                     Ok(Response::Message(ParseComplete))
@@ -522,11 +736,39 @@ impl Protocol {
                 // A request to terminate the connection.
                 Terminate => Ok(Response::Empty),
 
+                // The client may keep sending copy data after a `COPY ... FROM STDIN` has failed,
+                // which should be ignored
+                FrontendMessage::CopyData { .. } | FrontendMessage::CopyDone | CopyFail { .. } => {
+                    Ok(Response::Empty)
+                }
+
                 m => Err(Error::UnsupportedMessage(m)),
             },
         }
     }
 
+    /// Close (deallocate) the prepared statement with the given name, along with any portals
+    /// referencing it.
+    ///
+    /// Returns `false` if there was no prepared statement with that name
+    async fn close_prepared_statement<B: Backend, C: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        name: &str,
+        backend: &mut B,
+        channel: &mut Channel<C, B::Row>,
+    ) -> Result<bool, Error> {
+        let id = match self.prepared_statements.get(name) {
+            Some(data) => data.prepared_statement_id,
+            None => return Ok(false),
+        };
+        backend.on_close(id).await?;
+        channel.clear_statement_param_types(name);
+        self.prepared_statements.remove(name);
+        self.portals
+            .retain(|_, portal| portal.prepared_statement_name != name);
+        Ok(true)
+    }
+
     /// An error handler producing an `ErrorResponse` message.
     ///
     /// * `error` - an `Error` that has occurred while communicating with the frontend or handling
@@ -537,6 +779,10 @@ impl Protocol {
         error: Error,
     ) -> Result<Response<B::Row, B::Resultset>, Error> {
         match self.state {
+            _ if error.is_fatal() => {
+                self.state = State::Error;
+                Ok(Response::Message(make_error_response(error)))
+            }
             State::StartingUp | State::Extended => {
                 self.state = State::Error;
                 Ok(Response::Message(make_error_response(error)))
@@ -549,10 +795,13 @@ impl Protocol {
     }
 }
 
-fn make_error_response<R>(error: Error) -> BackendMessage<R> {
+pub(crate) fn make_error_response<R>(error: Error) -> BackendMessage<R> {
     let sqlstate = match error {
         Error::AuthenticationFailure(_) => SqlState::INVALID_PASSWORD,
+        Error::AdminShutdown(_) => SqlState::ADMIN_SHUTDOWN,
+        Error::CopyFailed(_) => SqlState::QUERY_CANCELED,
         Error::DecodeError(_) => SqlState::IO_ERROR,
+        Error::DuplicatePreparedStatement(_) => SqlState::DUPLICATE_PSTATEMENT,
         Error::EncodeError(_) => SqlState::IO_ERROR,
         Error::IncorrectFormatCount(_) => SqlState::IO_ERROR,
         Error::InternalError(_) => SqlState::INTERNAL_ERROR,
@@ -561,6 +810,7 @@ fn make_error_response<R>(error: Error) -> BackendMessage<R> {
         Error::MissingPortal(_) => SqlState::UNDEFINED_PSTATEMENT,
         Error::MissingPreparedStatement(_) => SqlState::UNDEFINED_PSTATEMENT,
         Error::ParseError(_) => SqlState::INVALID_PSTATEMENT_DEFINITION,
+        Error::QueryCancelled => SqlState::QUERY_CANCELED,
         Error::Unimplemented(_) => SqlState::FEATURE_NOT_SUPPORTED,
         Error::Unknown(_) => SqlState::INTERNAL_ERROR,
         Error::Unsupported(_) => SqlState::FEATURE_NOT_SUPPORTED,
@@ -568,8 +818,13 @@ fn make_error_response<R>(error: Error) -> BackendMessage<R> {
         Error::UnsupportedType(_) => SqlState::FEATURE_NOT_SUPPORTED,
         Error::PostgresError(ref e) => e.code().cloned().unwrap_or(SqlState::INTERNAL_ERROR),
     };
+    let severity = if error.is_fatal() {
+        ErrorSeverity::Fatal
+    } else {
+        ErrorSeverity::Error
+    };
     ErrorResponse {
-        severity: ErrorSeverity::Error,
+        severity,
         sqlstate,
         message: error.to_string(),
     }
@@ -588,6 +843,8 @@ async fn load_extended_types<B: Backend>(backend: &mut B) -> Result<HashMap<Oid,
 
     match response {
         SimpleQuery(r) => r
+            .try_collect::<Vec<_>>()
+            .await?
             .into_iter()
             .filter_map(|m| match m {
                 SimpleQueryMessage::Row(row) => Some(row),
@@ -740,8 +997,9 @@ mod tests {
     use std::task::Poll;
 
     use async_trait::async_trait;
-    use bytes::BytesMut;
+    use bytes::{Bytes, BytesMut};
     use futures::task::Context;
+    use futures::Stream;
     use tokio::io::ReadBuf;
     use tokio_test::block_on;
 
@@ -767,6 +1025,23 @@ mod tests {
         }
     }
 
+    // A resultset whose rows are all held in memory, so that responses containing it can be
+    // compared in assertions
+    #[derive(Debug, PartialEq)]
+    struct Resultset(Vec<Vec<Value>>);
+
+    impl Stream for Resultset {
+        type Item = Result<Vec<Value>, Error>;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            if self.0.is_empty() {
+                Poll::Ready(None)
+            } else {
+                Poll::Ready(Some(Ok(self.0.remove(0))))
+            }
+        }
+    }
+
     // A dummy `Backend` that records the values passed to it and can return a few hard-coded
     // responses.
     struct Backend {
@@ -778,10 +1053,15 @@ mod tests {
         database: Option<String>,
         last_query: Option<String>,
         last_prepare: Option<String>,
+        prepare_count: usize,
         last_close: Option<u32>,
         last_execute_id: Option<u32>,
         last_execute_params: Option<Vec<DataValue>>,
+        last_cancel: Option<(i32, i32)>,
         needed_credentials: Option<Credentials>,
+        last_copy_in: Option<String>,
+        copied_data: Vec<Bytes>,
+        copy_failed: bool,
     }
 
     impl Backend {
@@ -793,10 +1073,15 @@ mod tests {
                 database: None,
                 last_query: None,
                 last_prepare: None,
+                prepare_count: 0,
                 last_close: None,
                 last_execute_id: None,
                 last_execute_params: None,
+                last_cancel: None,
                 needed_credentials: None,
+                last_copy_in: None,
+                copied_data: vec![],
+                copy_failed: false,
             }
         }
     }
@@ -805,7 +1090,7 @@ mod tests {
     impl crate::Backend for Backend {
         type Value = Value;
         type Row = Vec<Self::Value>;
-        type Resultset = Vec<Self::Row>;
+        type Resultset = Resultset;
 
         async fn on_init(&mut self, database: &str) -> Result<CredentialsNeeded, Error> {
             self.database = Some(database.to_string());
@@ -849,10 +1134,10 @@ mod tests {
                             col_type: Type::FLOAT8,
                         },
                     ],
-                    resultset: vec![
+                    resultset: Resultset(vec![
                         vec![Value(DataValue::Int(88)), Value(DataValue::Double(0.123))],
                         vec![Value(DataValue::Int(22)), Value(DataValue::Double(0.456))],
-                    ],
+                    ]),
                 })
             } else {
                 Ok(QueryResponse::Delete(5))
@@ -861,6 +1146,7 @@ mod tests {
 
         async fn on_prepare(&mut self, query: &str) -> Result<PrepareResponse, Error> {
             self.last_prepare = Some(query.to_string());
+            self.prepare_count += 1;
             if self.is_prepare_err {
                 Err(Error::InternalError("error requested".to_string()))
             } else {
@@ -902,10 +1188,10 @@ mod tests {
                             col_type: Type::FLOAT8,
                         },
                     ],
-                    resultset: vec![
+                    resultset: Resultset(vec![
                         vec![Value(DataValue::Int(88)), Value(DataValue::Double(0.123))],
                         vec![Value(DataValue::Int(22)), Value(DataValue::Double(0.456))],
-                    ],
+                    ]),
                 })
             } else {
                 Ok(QueryResponse::Delete(5))
@@ -916,6 +1202,28 @@ mod tests {
             self.last_close = Some(statement_id);
             Ok(())
         }
+
+        async fn on_cancel(&mut self, process_id: i32, secret_key: i32) {
+            self.last_cancel = Some((process_id, secret_key));
+        }
+
+        async fn on_copy_in(&mut self, query: &str) -> Result<(), Error> {
+            self.last_copy_in = Some(query.to_string());
+            Ok(())
+        }
+
+        async fn on_copy_data(&mut self, data: Bytes) -> Result<(), Error> {
+            self.copied_data.push(data);
+            Ok(())
+        }
+
+        async fn on_copy_done(&mut self) -> Result<u64, Error> {
+            Ok(self.copied_data.len() as u64)
+        }
+
+        async fn on_copy_fail(&mut self) {
+            self.copy_failed = true;
+        }
     }
 
     // A dummy `AsyncRead + AsyncWrite` that does not read or write any data.
@@ -962,6 +1270,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn cancel_request() {
+        let mut protocol = Protocol::new();
+        let request = FrontendMessage::CancelRequest {
+            process_id: 42,
+            secret_key: 1234,
+        };
+        let mut backend = Backend::new();
+        let mut channel = Channel::<NullBytestream, Vec<Value>>::new(NullBytestream);
+        // A CancelRequest is forwarded to the backend, and no response is sent
+        assert_eq!(
+            block_on(protocol.on_request(request, &mut backend, &mut channel)).unwrap(),
+            Response::Empty
+        );
+        assert_eq!(backend.last_cancel, Some((42, 1234)));
+        assert_eq!(protocol.state, State::StartingUp);
+    }
+
     #[test]
     fn startup_message() {
         let mut protocol = Protocol::new();
@@ -970,6 +1296,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         let mut backend = Backend::new();
         let mut channel = Channel::<NullBytestream, Vec<Value>>::new(NullBytestream);
@@ -1017,6 +1344,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(expected_username.clone()),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         let mut backend = Backend::new();
         backend.needed_credentials = Some(Credentials::Cleartext {
@@ -1079,6 +1407,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(expected_username.clone()),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         let mut backend = Backend::new();
         backend.needed_credentials = Some(Credentials::Cleartext {
@@ -1115,6 +1444,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: None,
+            application_name: None,
         };
         let mut backend = Backend::new();
         let mut channel = Channel::<NullBytestream, Vec<Value>>::new(NullBytestream);
@@ -1142,6 +1472,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1150,6 +1481,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(request, &mut backend, &mut channel)).unwrap_err();
     }
@@ -1164,6 +1496,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1185,6 +1518,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1206,6 +1540,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1238,10 +1573,10 @@ mod tests {
                         },
                     ],
                 }),
-                resultset: vec![
+                resultset: Resultset(vec![
                     vec![Value(DataValue::Int(88)), Value(DataValue::Double(0.123))],
                     vec![Value(DataValue::Int(22)), Value(DataValue::Double(0.456))]
-                ],
+                ]),
                 result_transfer_formats: None,
                 trailer: Some(BackendMessage::ready_for_query_idle())
             }
@@ -1260,6 +1595,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1281,6 +1617,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1310,6 +1647,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1343,6 +1681,8 @@ mod tests {
                         col_type: Type::FLOAT8
                     },
                 ],
+                query: "SELECT * FROM test WHERE x = $1 AND y = $2;".to_string(),
+                parameter_data_types: vec![],
             }
         );
     }
@@ -1358,6 +1698,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1380,6 +1721,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1426,6 +1768,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1470,6 +1813,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1517,6 +1861,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1551,6 +1896,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1581,6 +1927,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1615,6 +1962,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1638,6 +1986,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1681,6 +2030,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1704,6 +2054,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1761,6 +2112,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1781,6 +2133,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1844,6 +2197,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1864,6 +2218,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1895,10 +2250,10 @@ mod tests {
             block_on(protocol.on_request(request, &mut backend, &mut channel)).unwrap(),
             Response::Select {
                 header: None,
-                resultset: vec![
+                resultset: Resultset(vec![
                     vec![Value(DataValue::Int(88)), Value(DataValue::Double(0.123))],
                     vec![Value(DataValue::Int(22)), Value(DataValue::Double(0.456))]
-                ],
+                ]),
                 result_transfer_formats: Some(Arc::new(vec![
                     TransferFormat::Text,
                     TransferFormat::Binary
@@ -1924,6 +2279,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -1965,6 +2321,7 @@ mod tests {
             protocol_version: 12345,
             user: Some(bytes_str("user_name")),
             database: Some(bytes_str("database_name")),
+            application_name: None,
         };
         block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
 
@@ -2005,6 +2362,321 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_once_execute_many() {
+        let mut protocol = Protocol::new();
+        let mut backend = Backend::new();
+        backend.is_query_read = false;
+        let mut channel = Channel::<NullBytestream, Vec<Value>>::new(NullBytestream);
+
+        let startup_request = FrontendMessage::StartupMessage {
+            protocol_version: 12345,
+            user: Some(bytes_str("user_name")),
+            database: Some(bytes_str("database_name")),
+            application_name: None,
+        };
+        block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
+
+        let parse_request = |name, query| FrontendMessage::Parse {
+            prepared_statement_name: bytes_str(name),
+            query: bytes_str(query),
+            parameter_data_types: vec![],
+        };
+        let query = "SELECT * FROM test WHERE x = $1 AND y = $2;";
+
+        for i in 0..3 {
+            // Re-parsing the same query as the unnamed statement reuses the prepared statement
+            assert_eq!(
+                block_on(protocol.on_request(parse_request("", query), &mut backend, &mut channel))
+                    .unwrap(),
+                Response::Message(ParseComplete)
+            );
+            let bind_request = FrontendMessage::Bind {
+                prepared_statement_name: bytes_str(""),
+                portal_name: bytes_str("portal1"),
+                params: vec![DataValue::Double(0.8887), DataValue::Int(i)],
+                result_transfer_formats: vec![],
+            };
+            block_on(protocol.on_request(bind_request, &mut backend, &mut channel)).unwrap();
+            let execute_request = FrontendMessage::Execute {
+                portal_name: bytes_str("portal1"),
+                limit: 0,
+            };
+            block_on(protocol.on_request(execute_request, &mut backend, &mut channel)).unwrap();
+            assert_eq!(
+                backend.last_execute_params.take().unwrap(),
+                vec![DataValue::Double(0.8887), DataValue::Int(i)]
+            );
+        }
+        assert_eq!(backend.prepare_count, 1);
+        assert_eq!(backend.last_close, None);
+
+        // Parsing a different query as the unnamed statement replaces the prepared statement
+        block_on(protocol.on_request(
+            parse_request("", "SELECT * FROM test WHERE x = $1"),
+            &mut backend,
+            &mut channel,
+        ))
+        .unwrap();
+        assert_eq!(backend.prepare_count, 2);
+        assert_eq!(backend.last_close, Some(0));
+        assert_eq!(
+            protocol.prepared_statements.get("").unwrap().query,
+            "SELECT * FROM test WHERE x = $1"
+        );
+        assert!(protocol.portals.get("portal1").is_none());
+
+        // Named statements can't be parsed again until they're closed, even with the same query
+        block_on(protocol.on_request(
+            parse_request("prepared1", query),
+            &mut backend,
+            &mut channel,
+        ))
+        .unwrap();
+        let err = block_on(protocol.on_request(
+            parse_request("prepared1", query),
+            &mut backend,
+            &mut channel,
+        ))
+        .unwrap_err();
+        assert!(matches!(err, Error::DuplicatePreparedStatement(name) if name == "prepared1"));
+        assert_eq!(backend.prepare_count, 3);
+    }
+
+    #[test]
+    fn deallocate() {
+        let mut protocol = Protocol::new();
+        let mut backend = Backend::new();
+        backend.is_query_read = false;
+        let mut channel = Channel::<NullBytestream, Vec<Value>>::new(NullBytestream);
+
+        let startup_request = FrontendMessage::StartupMessage {
+            protocol_version: 12345,
+            user: Some(bytes_str("user_name")),
+            database: Some(bytes_str("database_name")),
+            application_name: None,
+        };
+        block_on(protocol.on_request(startup_request, &mut backend, &mut channel)).unwrap();
+
+        for name in ["prepared1", "Prepared2", "prepared3"] {
+            let parse_request = FrontendMessage::Parse {
+                prepared_statement_name: bytes_str(name),
+                query: bytes_str("SELECT * FROM test WHERE x = $1 AND y = $2;"),
+                parameter_data_types: vec![],
+            };
+            block_on(protocol.on_request(parse_request, &mut backend, &mut channel)).unwrap();
+        }
+
+        let query_request = |query| FrontendMessage::Query {
+            query: bytes_str(query),
+        };
+        let deallocate_complete = |tag| {
+            Response::Messages(smallvec![
+                CommandComplete { tag },
+                BackendMessage::ready_for_query_idle()
+            ])
+        };
+
+        assert_eq!(
+            block_on(protocol.on_request(
+                query_request("DEALLOCATE prepared1"),
+                &mut backend,
+                &mut channel
+            ))
+            .unwrap(),
+            deallocate_complete(CommandCompleteTag::Deallocate)
+        );
+        assert!(protocol.prepared_statements.get("prepared1").is_none());
+        assert_eq!(
+            block_on(protocol.on_request(
+                query_request("deallocate prepare \"Prepared2\";"),
+                &mut backend,
+                &mut channel
+            ))
+            .unwrap(),
+            deallocate_complete(CommandCompleteTag::Deallocate)
+        );
+        assert!(protocol.prepared_statements.get("Prepared2").is_none());
+        assert_eq!(backend.last_query, None);
+
+        // Statements we didn't prepare are passed through to the backend
+        block_on(protocol.on_request(
+            query_request("DEALLOCATE unknown"),
+            &mut backend,
+            &mut channel,
+        ))
+        .unwrap();
+        assert_eq!(backend.last_query.as_deref(), Some("DEALLOCATE unknown"));
+
+        assert_eq!(
+            block_on(protocol.on_request(
+                query_request("DEALLOCATE ALL"),
+                &mut backend,
+                &mut channel
+            ))
+            .unwrap(),
+            deallocate_complete(CommandCompleteTag::DeallocateAll)
+        );
+        assert!(protocol.prepared_statements.is_empty());
+        // Statements prepared with SQL `PREPARE` are deallocated by the backend
+        assert_eq!(backend.last_query.as_deref(), Some("DEALLOCATE ALL"));
+    }
+
+    #[test]
+    fn parse_deallocate_statements() {
+        assert_eq!(
+            parse_deallocate("DEALLOCATE foo"),
+            Some(Deallocate::Name("foo".into()))
+        );
+        assert_eq!(
+            parse_deallocate("  deallocate   PREPARE Foo ; "),
+            Some(Deallocate::Name("foo".into()))
+        );
+        assert_eq!(
+            parse_deallocate(r#"DEALLOCATE "Foo""Bar""#),
+            Some(Deallocate::Name(r#"Foo"Bar"#.into()))
+        );
+        assert_eq!(parse_deallocate("DEALLOCATE ALL"), Some(Deallocate::All));
+        assert_eq!(
+            parse_deallocate("DEALLOCATE PREPARE all;"),
+            Some(Deallocate::All)
+        );
+        assert_eq!(parse_deallocate("DEALLOCATE"), None);
+        assert_eq!(parse_deallocate("DEALLOCATE a b"), None);
+        assert_eq!(parse_deallocate("SELECT 1"), None);
+    }
+
+    #[test]
+    fn parse_copy_statements() {
+        assert_eq!(
+            parse_copy("COPY t FROM STDIN"),
+            Some((CopyDirection::In, Text))
+        );
+        assert_eq!(
+            parse_copy("copy t (a, b) from stdin with (format csv);"),
+            Some((CopyDirection::In, Text))
+        );
+        assert_eq!(
+            parse_copy("COPY t FROM STDIN (FORMAT binary)"),
+            Some((CopyDirection::In, Binary))
+        );
+        assert_eq!(
+            parse_copy("COPY t TO STDOUT WITH BINARY"),
+            Some((CopyDirection::Out, Binary))
+        );
+        assert_eq!(
+            parse_copy("COPY (SELECT * FROM t) TO STDOUT;"),
+            Some((CopyDirection::Out, Text))
+        );
+        assert_eq!(parse_copy("COPY t FROM '/tmp/t.csv'"), None);
+        assert_eq!(parse_copy("SELECT * FROM stdin"), None);
+    }
+
+    #[test]
+    fn parse_copy_text_options_statements() {
+        assert_eq!(
+            parse_copy_text_options("COPY (SELECT csv, header FROM t) TO STDOUT"),
+            CopyTextOptions::default()
+        );
+        assert_eq!(
+            parse_copy_text_options("COPY t TO STDOUT WITH CSV HEADER"),
+            CopyTextOptions {
+                csv: true,
+                header: true
+            }
+        );
+        assert_eq!(
+            parse_copy_text_options("COPY t TO STDOUT (FORMAT csv, HEADER false);"),
+            CopyTextOptions {
+                csv: true,
+                header: false
+            }
+        );
+    }
+
+    #[test]
+    fn copy_in() {
+        let mut protocol = Protocol::new();
+        let mut backend = Backend::new();
+        let mut channel = Channel::<NullBytestream, Vec<Value>>::new(NullBytestream);
+        protocol.state = State::Ready;
+
+        let request = FrontendMessage::Query {
+            query: bytes_str("COPY test FROM STDIN"),
+        };
+        assert_eq!(
+            block_on(protocol.on_request(request, &mut backend, &mut channel)).unwrap(),
+            Response::Message(CopyInResponse { format: Text })
+        );
+        assert_eq!(protocol.state, State::CopyIn);
+        assert_eq!(
+            backend.last_copy_in.as_deref(),
+            Some("COPY test FROM STDIN")
+        );
+
+        for row in ["1\tone\n", "2\ttwo\n"] {
+            let request = FrontendMessage::CopyData {
+                data: Bytes::from_static(row.as_bytes()),
+            };
+            assert_eq!(
+                block_on(protocol.on_request(request, &mut backend, &mut channel)).unwrap(),
+                Response::Empty
+            );
+        }
+        assert_eq!(
+            block_on(protocol.on_request(FrontendMessage::CopyDone, &mut backend, &mut channel))
+                .unwrap(),
+            Response::Messages(smallvec![
+                CommandComplete {
+                    tag: CommandCompleteTag::Copy(2)
+                },
+                BackendMessage::ready_for_query_idle()
+            ])
+        );
+        assert_eq!(protocol.state, State::Ready);
+        assert_eq!(
+            backend.copied_data,
+            vec![
+                Bytes::from_static(b"1\tone\n"),
+                Bytes::from_static(b"2\ttwo\n")
+            ]
+        );
+        assert!(!backend.copy_failed);
+    }
+
+    #[test]
+    fn copy_in_fail() {
+        let mut protocol = Protocol::new();
+        let mut backend = Backend::new();
+        let mut channel = Channel::<NullBytestream, Vec<Value>>::new(NullBytestream);
+        protocol.state = State::Ready;
+
+        let request = FrontendMessage::Query {
+            query: bytes_str("COPY test FROM STDIN"),
+        };
+        block_on(protocol.on_request(request, &mut backend, &mut channel)).unwrap();
+
+        let request = FrontendMessage::CopyFail {
+            message: bytes_str("client gave up"),
+        };
+        assert!(matches!(
+            block_on(protocol.on_request(request, &mut backend, &mut channel)),
+            Err(Error::CopyFailed(msg)) if msg == "client gave up"
+        ));
+        assert_eq!(protocol.state, State::Ready);
+        assert!(backend.copy_failed);
+
+        // Data the client sent before it noticed the failure is ignored
+        let request = FrontendMessage::CopyData {
+            data: Bytes::from_static(b"1\n"),
+        };
+        assert_eq!(
+            block_on(protocol.on_request(request, &mut backend, &mut channel)).unwrap(),
+            Response::Empty
+        );
+        assert!(backend.copied_data.is_empty());
+    }
+
     #[test]
     fn on_error_starting_up() {
         let mut protocol = Protocol::new();
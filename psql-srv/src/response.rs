@@ -1,13 +1,19 @@
 use std::convert::TryInto;
+use std::fmt::{self, Debug};
 use std::sync::Arc;
 
+use bytes::Bytes;
 use futures::prelude::*;
+use postgres::SimpleQueryMessage;
 use smallvec::SmallVec;
+use tokio_postgres::CommandCompleteContents;
 
 use crate::codec::EncodeError;
 use crate::error::Error;
 use crate::message::{BackendMessage, CommandCompleteTag, TransferFormat};
+use crate::protocol::make_error_response;
 use crate::value::Value;
+use crate::{CopyOutStream, SimpleQueryStream};
 
 /// An encapsulation of a complete response produced by a Postgresql backend in response to a
 /// request. The response will be sent to the frontend as a sequence of zero or more
@@ -21,19 +27,78 @@ pub enum Response<R, S> {
     Messages(SmallVec<[BackendMessage<R>; 2]>),
 
     /// `Select` is the most complex variant, containing data rows to be sent to the frontend in
-    /// response to a select query.
+    /// response to a select query. Rows are written to the frontend as they're read from the
+    /// resultset, rather than all at once after the resultset has been exhausted.
     Select {
         header: Option<BackendMessage<R>>,
         resultset: S,
         result_transfer_formats: Option<Arc<Vec<TransferFormat>>>,
         trailer: Option<BackendMessage<R>>,
     },
+
+    /// The response to a `COPY ... TO STDOUT` statement, containing the copied data to be sent to
+    /// the frontend in `CopyData` messages.
+    CopyOut {
+        format: TransferFormat,
+        options: CopyTextOptions,
+        data: CopyOutData,
+    },
+
+    /// The response to a statement run with the simple query protocol, containing the messages
+    /// to pass through to the frontend as they're read from the upstream database.
+    SimpleQuery(SimpleQueryData),
+}
+
+/// The options of a text format `COPY ... TO STDOUT` statement which determine how the copied
+/// rows are laid out in its data
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CopyTextOptions {
+    /// Whether the data is in CSV format, where values may be quoted and contain newlines
+    pub csv: bool,
+    /// Whether the first line of the data is a header line, rather than a row
+    pub header: bool,
+}
+
+/// The data copied out by a `COPY ... TO STDOUT` statement.
+pub struct CopyOutData(pub CopyOutStream);
+
+impl Debug for CopyOutData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CopyOutData").finish_non_exhaustive()
+    }
+}
+
+/// Streams can't be compared, so a [`CopyOutData`] is only ever equal to itself.
+impl PartialEq for CopyOutData {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
 }
 
+impl Eq for CopyOutData {}
+
+/// The messages produced by a statement run with the simple query protocol.
+pub struct SimpleQueryData(pub SimpleQueryStream);
+
+impl Debug for SimpleQueryData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SimpleQueryData").finish_non_exhaustive()
+    }
+}
+
+/// Streams can't be compared, so a [`SimpleQueryData`] is only ever equal to itself.
+impl PartialEq for SimpleQueryData {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for SimpleQueryData {}
+
 impl<R, S> Response<R, S>
 where
     R: IntoIterator<Item: TryInto<Value, Error = Error>>,
-    S: IntoIterator<Item = R>,
+    S: Stream<Item = Result<R, Error>> + Unpin,
 {
     pub async fn write<K>(self, sink: &mut K) -> Result<(), EncodeError>
     where
@@ -59,7 +124,7 @@ where
 
             Select {
                 header,
-                resultset,
+                mut resultset,
                 result_transfer_formats,
                 trailer,
             } => {
@@ -68,19 +133,46 @@ where
                 }
 
                 let mut n_rows = 0;
-                for r in resultset {
-                    sink.feed(BackendMessage::DataRow {
-                        values: r,
-                        explicit_transfer_formats: result_transfer_formats.clone(),
-                    })
-                    .await?;
-                    n_rows += 1;
+                let mut error = None;
+                loop {
+                    let next = match resultset.next().now_or_never() {
+                        Some(next) => next,
+                        None => {
+                            // The next row isn't ready yet, so make sure the client gets the rows
+                            // we've already written while we wait for it. Feeding rows to the
+                            // sink only flushes once its buffer fills up, which is what bounds
+                            // the rows we hold in memory, but would otherwise hold back the first
+                            // rows of a result set that's slow to produce.
+                            sink.flush().await?;
+                            resultset.next().await
+                        }
+                    };
+                    match next {
+                        Some(Ok(r)) => {
+                            sink.feed(BackendMessage::DataRow {
+                                values: r,
+                                explicit_transfer_formats: result_transfer_formats.clone(),
+                            })
+                            .await?;
+                            n_rows += 1;
+                        }
+                        Some(Err(e)) => {
+                            error = Some(e);
+                            break;
+                        }
+                        None => break,
+                    }
                 }
 
-                sink.feed(BackendMessage::CommandComplete {
-                    tag: CommandCompleteTag::Select(n_rows),
-                })
-                .await?;
+                match error {
+                    None => {
+                        sink.feed(BackendMessage::CommandComplete {
+                            tag: CommandCompleteTag::Select(n_rows),
+                        })
+                        .await?
+                    }
+                    Some(e) => sink.feed(make_error_response(e)).await?,
+                }
 
                 if let Some(trailer) = trailer {
                     sink.feed(trailer).await?;
@@ -88,6 +180,268 @@ where
 
                 sink.flush().await
             }
+
+            CopyOut {
+                format,
+                options,
+                data: CopyOutData(mut data),
+            } => {
+                sink.feed(BackendMessage::CopyOutResponse { format })
+                    .await?;
+
+                let mut rows = CopyRowCounter::new(format, options);
+                let mut error = None;
+                while let Some(chunk) = data.next().await {
+                    match chunk {
+                        Ok(chunk) => {
+                            rows.feed(&chunk);
+                            sink.feed(BackendMessage::CopyData(chunk)).await?;
+                        }
+                        Err(e) => {
+                            error = Some(e);
+                            break;
+                        }
+                    }
+                }
+
+                match error {
+                    None => {
+                        sink.feed(BackendMessage::CopyDone).await?;
+                        sink.feed(BackendMessage::CommandComplete {
+                            tag: CommandCompleteTag::Copy(rows.rows()),
+                        })
+                        .await?;
+                    }
+                    Some(e) => sink.feed(make_error_response(e)).await?,
+                }
+
+                sink.feed(BackendMessage::ready_for_query_idle()).await?;
+                sink.flush().await
+            }
+
+            SimpleQuery(SimpleQueryData(mut messages)) => {
+                let mut processing_select = false;
+                // The number of rows of the current result set sent to the client
+                let mut n_rows = 0;
+                let mut error = None;
+                while let Some(message) = messages.next().await {
+                    match message {
+                        Ok(SimpleQueryMessage::Row(row)) => {
+                            if !processing_select {
+                                // Create a message for the RowDescription. We use the
+                                // PassThrough version since this message comes directly from
+                                // tokio-postgres.
+                                sink.feed(BackendMessage::PassThroughRowDescription(
+                                    row.fields().to_vec(),
+                                ))
+                                .await?;
+                                processing_select = true;
+                            }
+                            sink.feed(BackendMessage::PassThroughDataRow(row)).await?;
+                            n_rows += 1;
+                        }
+                        Ok(SimpleQueryMessage::CommandComplete(CommandCompleteContents {
+                            fields,
+                            tag,
+                            ..
+                        })) => {
+                            let returned_rows = processing_select || fields.is_some();
+                            if let Some(f) = fields {
+                                sink.feed(BackendMessage::PassThroughRowDescription(f.to_vec()))
+                                    .await?;
+                            }
+                            let tag = if returned_rows {
+                                with_sent_row_count(tag, n_rows)
+                            } else {
+                                tag
+                            };
+                            sink.feed(BackendMessage::PassThroughCommandComplete(tag))
+                                .await?;
+                            processing_select = false;
+                            n_rows = 0;
+                        }
+                        Ok(_) => {
+                            error = Some(Error::InternalError(
+                                "Unexpected SimpleQuery message variant".to_string(),
+                            ));
+                            break;
+                        }
+                        Err(e) => {
+                            error = Some(e);
+                            break;
+                        }
+                    }
+                }
+
+                if let Some(e) = error {
+                    sink.feed(make_error_response(e)).await?;
+                }
+                sink.feed(BackendMessage::ready_for_query_idle()).await?;
+                sink.flush().await
+            }
+        }
+    }
+}
+
+/// Replace the row count in `tag`, the tag of the `CommandComplete` message for a `SELECT` or
+/// `FETCH` whose rows were passed through to the client, with `n_rows`, the number of rows that
+/// were actually sent. These differ if the result set was truncated before reaching us.
+fn with_sent_row_count(tag: Bytes, n_rows: u64) -> Bytes {
+    std::str::from_utf8(&tag)
+        .ok()
+        .and_then(|tag| tag.split_once(' '))
+        .filter(|(command, count)| {
+            matches!(*command, "SELECT" | "FETCH") && count.parse::<u64>().is_ok()
+        })
+        .map(|(command, _)| Bytes::from(format!("{command} {n_rows}")))
+        .unwrap_or(tag)
+}
+
+/// Counts the rows in the data copied out by a `COPY ... TO STDOUT` statement, to report in its
+/// `CommandComplete` message.
+///
+/// The data is sent to the frontend in whatever chunks the backend produces it in, which needn't
+/// line up with rows (a chunk may contain a partial row, several rows, or only the header of binary
+/// format data), so rows are counted by following the structure of the data itself.
+enum CopyRowCounter {
+    /// Text and CSV formats, which have one row per line
+    Text {
+        options: CopyTextOptions,
+        /// Whether we're in the middle of a quoted CSV value, where newlines don't end the row
+        in_quotes: bool,
+        lines: u64,
+    },
+    /// Binary format, which is a header followed by a sequence of tuples, each of which is a field
+    /// count followed by the length and data of each field, and terminated by a field count of -1
+    Binary {
+        state: BinaryCopyState,
+        /// The bytes read so far of the fixed-size value currently being read
+        pending: Vec<u8>,
+        /// The number of bytes of variable-size data (header extensions or field values) left to
+        /// skip over
+        skip: usize,
+        tuples: u64,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum BinaryCopyState {
+    /// Reading the signature, flags and header extension length
+    Header,
+    /// Reading the field count of the next tuple
+    Tuple,
+    /// Reading the length of the next field, with this many fields left in the tuple
+    Field(u16),
+    /// Past the trailer
+    Done,
+}
+
+impl CopyRowCounter {
+    /// The length of the signature, flags field and header extension length at the start of
+    /// binary format data
+    const BINARY_HEADER_LEN: usize = 19;
+
+    fn new(format: TransferFormat, options: CopyTextOptions) -> Self {
+        match format {
+            TransferFormat::Text => Self::Text {
+                options,
+                in_quotes: false,
+                lines: 0,
+            },
+            TransferFormat::Binary => Self::Binary {
+                state: BinaryCopyState::Header,
+                pending: Vec::with_capacity(Self::BINARY_HEADER_LEN),
+                skip: 0,
+                tuples: 0,
+            },
+        }
+    }
+
+    fn feed(&mut self, mut data: &[u8]) {
+        match self {
+            Self::Text {
+                options,
+                in_quotes,
+                lines,
+            } => {
+                for &b in data {
+                    match b {
+                        // Quotes inside quoted values are escaped by doubling them, which toggles
+                        // `in_quotes` back again
+                        b'"' if options.csv => *in_quotes = !*in_quotes,
+                        b'\n' if !*in_quotes => *lines += 1,
+                        _ => {}
+                    }
+                }
+            }
+            Self::Binary {
+                state,
+                pending,
+                skip,
+                tuples,
+            } => loop {
+                let n = (*skip).min(data.len());
+                *skip -= n;
+                data = &data[n..];
+
+                let len = match state {
+                    BinaryCopyState::Header => Self::BINARY_HEADER_LEN,
+                    BinaryCopyState::Tuple => 2,
+                    BinaryCopyState::Field(_) => 4,
+                    BinaryCopyState::Done => return,
+                };
+                let n = (len - pending.len()).min(data.len());
+                pending.extend_from_slice(&data[..n]);
+                data = &data[n..];
+                if pending.len() < len {
+                    return;
+                }
+
+                *state = match *state {
+                    BinaryCopyState::Header => {
+                        *skip = u32::from_be_bytes([
+                            pending[15],
+                            pending[16],
+                            pending[17],
+                            pending[18],
+                        ]) as usize;
+                        BinaryCopyState::Tuple
+                    }
+                    BinaryCopyState::Tuple => match i16::from_be_bytes([pending[0], pending[1]]) {
+                        -1 => BinaryCopyState::Done,
+                        0 => {
+                            *tuples += 1;
+                            BinaryCopyState::Tuple
+                        }
+                        fields => {
+                            *tuples += 1;
+                            BinaryCopyState::Field(fields as u16)
+                        }
+                    },
+                    BinaryCopyState::Field(fields) => {
+                        let field_len =
+                            i32::from_be_bytes([pending[0], pending[1], pending[2], pending[3]]);
+                        // A length of -1 means the field is NULL, and has no data
+                        *skip = field_len.max(0) as usize;
+                        if fields > 1 {
+                            BinaryCopyState::Field(fields - 1)
+                        } else {
+                            BinaryCopyState::Tuple
+                        }
+                    }
+                    BinaryCopyState::Done => BinaryCopyState::Done,
+                };
+                pending.clear();
+            },
+        }
+    }
+
+    /// The number of rows in the data fed to the counter
+    fn rows(&self) -> u64 {
+        match self {
+            Self::Text { options, lines, .. } if options.header => lines.saturating_sub(1),
+            Self::Text { lines, .. } => *lines,
+            Self::Binary { tuples, .. } => *tuples,
         }
     }
 }
@@ -96,16 +450,25 @@ where
 mod tests {
 
     use std::convert::TryFrom;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
 
+    use bytes::Bytes;
     use smallvec::smallvec;
+    use tokio::io::AsyncReadExt;
+    use tokio::time::{sleep, timeout};
     use tokio_test::block_on;
+    use tokio_util::codec::FramedWrite;
 
     use super::*;
+    use crate::codec::Codec;
     use crate::value::Value as DataValue;
 
     #[derive(Clone, Debug, PartialEq)]
     struct Value(DataValue);
 
+    type Resultset = stream::Iter<std::vec::IntoIter<Result<Vec<Value>, Error>>>;
+
     impl TryFrom<Value> for DataValue {
         type Error = Error;
 
@@ -116,7 +479,7 @@ mod tests {
 
     #[test]
     fn write_empty() {
-        let response = Response::<Vec<Value>, Vec<Vec<Value>>>::Empty;
+        let response = Response::<Vec<Value>, Resultset>::Empty;
         let validating_sink = sink::unfold(0, |_i, _m: BackendMessage<Vec<Value>>| {
             async move {
                 // No messages are expected.
@@ -129,8 +492,7 @@ mod tests {
 
     #[test]
     fn write_message() {
-        let response =
-            Response::<Vec<Value>, Vec<Vec<Value>>>::Message(BackendMessage::BindComplete);
+        let response = Response::<Vec<Value>, Resultset>::Message(BackendMessage::BindComplete);
         let validating_sink = sink::unfold(0, |i, m: BackendMessage<Vec<Value>>| {
             async move {
                 match i {
@@ -147,7 +509,7 @@ mod tests {
 
     #[test]
     fn write_message2() {
-        let response = Response::<Vec<Value>, Vec<Vec<Value>>>::Messages(smallvec![
+        let response = Response::<Vec<Value>, Resultset>::Messages(smallvec![
             BackendMessage::BindComplete,
             BackendMessage::CloseComplete,
         ]);
@@ -168,9 +530,9 @@ mod tests {
 
     #[test]
     fn write_select_simple_empty() {
-        let response = Response::<Vec<Value>, Vec<Vec<Value>>>::Select {
+        let response = Response::<Vec<Value>, Resultset>::Select {
             header: None,
-            resultset: vec![],
+            resultset: stream::iter(vec![]),
             result_transfer_formats: None,
             trailer: None,
         };
@@ -195,14 +557,20 @@ mod tests {
 
     #[test]
     fn write_select() {
-        let response = Response::<Vec<Value>, Vec<Vec<Value>>>::Select {
+        let response = Response::<Vec<Value>, Resultset>::Select {
             header: Some(BackendMessage::RowDescription {
                 field_descriptions: vec![],
             }),
-            resultset: vec![
-                vec![Value(DataValue::Int(5)), Value(DataValue::Double(0.123))],
-                vec![Value(DataValue::Int(99)), Value(DataValue::Double(0.456))],
-            ],
+            resultset: stream::iter(vec![
+                Ok(vec![
+                    Value(DataValue::Int(5)),
+                    Value(DataValue::Double(0.123)),
+                ]),
+                Ok(vec![
+                    Value(DataValue::Int(99)),
+                    Value(DataValue::Double(0.456)),
+                ]),
+            ]),
             result_transfer_formats: Some(Arc::new(vec![
                 TransferFormat::Text,
                 TransferFormat::Binary,
@@ -257,4 +625,246 @@ mod tests {
         futures::pin_mut!(validating_sink);
         block_on(response.write(&mut validating_sink)).unwrap();
     }
+
+    #[test]
+    fn write_select_error() {
+        let response = Response::<Vec<Value>, Resultset>::Select {
+            header: None,
+            resultset: stream::iter(vec![
+                Ok(vec![Value(DataValue::Int(5))]),
+                Err(Error::InternalError("upstream went away".to_owned())),
+                Ok(vec![Value(DataValue::Int(6))]),
+            ]),
+            result_transfer_formats: None,
+            trailer: Some(BackendMessage::ready_for_query_idle()),
+        };
+        let validating_sink = sink::unfold(0, |i, m: BackendMessage<Vec<Value>>| {
+            async move {
+                match i {
+                    0 => assert_eq!(
+                        m,
+                        BackendMessage::DataRow {
+                            values: vec![Value(DataValue::Int(5))],
+                            explicit_transfer_formats: None
+                        }
+                    ),
+                    1 => assert!(matches!(m, BackendMessage::ErrorResponse { .. })),
+                    2 => assert_eq!(m, BackendMessage::ready_for_query_idle()),
+                    // No further messages are expected.
+                    _ => panic!(),
+                }
+                Ok::<_, EncodeError>(i + 1)
+            }
+        });
+        futures::pin_mut!(validating_sink);
+        block_on(response.write(&mut validating_sink)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_select_large_resultset_is_bounded_by_backpressure() {
+        const ROWS: usize = 100_000;
+
+        let produced = Arc::new(AtomicUsize::new(0));
+        let resultset = stream::iter(0..ROWS).map({
+            let produced = Arc::clone(&produced);
+            move |i| {
+                produced.fetch_add(1, Ordering::Relaxed);
+                Ok(vec![Value(DataValue::BigInt(i as _))])
+            }
+        });
+        let response = Response::Select {
+            header: None,
+            resultset,
+            result_transfer_formats: None,
+            trailer: None,
+        };
+
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        let write = tokio::spawn(async move {
+            let mut sink = FramedWrite::new(server, Codec::<Vec<Value>>::new());
+            response.write(&mut sink).await
+        });
+
+        // The first row reaches the client without waiting for the rest of the resultset
+        let mut tag = [0u8];
+        timeout(Duration::from_secs(5), client.read_exact(&mut tag))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tag[0], b'D');
+
+        // Until the client reads more, the writer stops pulling rows from the resultset once the
+        // connection's buffers fill up, rather than buffering the rest of the resultset
+        sleep(Duration::from_millis(100)).await;
+        let produced_while_blocked = produced.load(Ordering::Relaxed);
+        assert!(
+            produced_while_blocked < ROWS / 10,
+            "{produced_while_blocked} rows were read from the resultset without backpressure"
+        );
+
+        let mut rest = vec![];
+        client.read_to_end(&mut rest).await.unwrap();
+        write.await.unwrap().unwrap();
+        assert_eq!(produced.load(Ordering::Relaxed), ROWS);
+    }
+
+    #[tokio::test]
+    async fn write_select_flushes_rows_while_waiting_for_more() {
+        let resultset =
+            stream::iter(vec![Ok(vec![Value(DataValue::Int(1))])]).chain(stream::pending());
+        let response = Response::Select {
+            header: None,
+            resultset,
+            result_transfer_formats: None,
+            trailer: None,
+        };
+
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        let write = tokio::spawn(async move {
+            let mut sink = FramedWrite::new(server, Codec::<Vec<Value>>::new());
+            response.write(&mut sink).await
+        });
+
+        let mut tag = [0u8];
+        timeout(Duration::from_secs(5), client.read_exact(&mut tag))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tag[0], b'D');
+        write.abort();
+    }
+
+    #[test]
+    fn write_copy_out() {
+        let response = Response::<Vec<Value>, Resultset>::CopyOut {
+            format: TransferFormat::Text,
+            options: CopyTextOptions::default(),
+            data: CopyOutData(
+                stream::iter(vec![
+                    Ok(Bytes::from_static(b"1\n")),
+                    Ok(Bytes::from_static(b"2\n")),
+                ])
+                .boxed(),
+            ),
+        };
+        let validating_sink = sink::unfold(0, |i, m: BackendMessage<Vec<Value>>| {
+            async move {
+                match i {
+                    0 => assert_eq!(
+                        m,
+                        BackendMessage::CopyOutResponse {
+                            format: TransferFormat::Text
+                        }
+                    ),
+                    1 => assert_eq!(m, BackendMessage::CopyData(Bytes::from_static(b"1\n"))),
+                    2 => assert_eq!(m, BackendMessage::CopyData(Bytes::from_static(b"2\n"))),
+                    3 => assert_eq!(m, BackendMessage::CopyDone),
+                    4 => assert_eq!(
+                        m,
+                        BackendMessage::CommandComplete {
+                            tag: CommandCompleteTag::Copy(2)
+                        }
+                    ),
+                    5 => assert_eq!(m, BackendMessage::ready_for_query_idle()),
+                    // No further messages are expected.
+                    _ => panic!(),
+                }
+                Ok::<_, EncodeError>(i + 1)
+            }
+        });
+        futures::pin_mut!(validating_sink);
+        block_on(response.write(&mut validating_sink)).unwrap();
+    }
+
+    #[test]
+    fn write_copy_out_binary() {
+        let header = Bytes::from_static(b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0");
+        let row = Bytes::from_static(b"\0\x01\0\0\0\x04\0\0\0\x01");
+        let trailer = Bytes::from_static(b"\xff\xff");
+        let response = Response::<Vec<Value>, Resultset>::CopyOut {
+            format: TransferFormat::Binary,
+            options: CopyTextOptions::default(),
+            data: CopyOutData(
+                stream::iter(vec![
+                    Ok(header.clone()),
+                    Ok(row.clone()),
+                    Ok(row.clone()),
+                    Ok(trailer.clone()),
+                ])
+                .boxed(),
+            ),
+        };
+        let validating_sink = sink::unfold(0, move |i, m: BackendMessage<Vec<Value>>| {
+            let (header, row, trailer) = (header.clone(), row.clone(), trailer.clone());
+            async move {
+                match i {
+                    0 => assert_eq!(
+                        m,
+                        BackendMessage::CopyOutResponse {
+                            format: TransferFormat::Binary
+                        }
+                    ),
+                    1 => assert_eq!(m, BackendMessage::CopyData(header)),
+                    2 | 3 => assert_eq!(m, BackendMessage::CopyData(row)),
+                    4 => assert_eq!(m, BackendMessage::CopyData(trailer)),
+                    5 => assert_eq!(m, BackendMessage::CopyDone),
+                    6 => assert_eq!(
+                        m,
+                        BackendMessage::CommandComplete {
+                            tag: CommandCompleteTag::Copy(2)
+                        }
+                    ),
+                    7 => assert_eq!(m, BackendMessage::ready_for_query_idle()),
+                    // No further messages are expected.
+                    _ => panic!(),
+                }
+                Ok::<_, EncodeError>(i + 1)
+            }
+        });
+        futures::pin_mut!(validating_sink);
+        block_on(response.write(&mut validating_sink)).unwrap();
+    }
+
+    #[test]
+    fn copy_row_counter_csv_header() {
+        let mut rows = CopyRowCounter::new(
+            TransferFormat::Text,
+            CopyTextOptions {
+                csv: true,
+                header: true,
+            },
+        );
+        rows.feed(b"id,name\n1,\"multi\n");
+        rows.feed(b"line\"\n2,\"say \"\"hi\"\"\"\n");
+        assert_eq!(rows.rows(), 2);
+    }
+
+    #[test]
+    fn copy_row_counter_binary_split_across_chunks() {
+        let mut data = b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\x02ab".to_vec();
+        // One tuple with an int4 and a NULL, one with no fields, and one with an empty text value
+        data.extend_from_slice(b"\0\x02\0\0\0\x04\0\0\0\x01\xff\xff\xff\xff");
+        data.extend_from_slice(b"\0\0");
+        data.extend_from_slice(b"\0\x01\0\0\0\0");
+        data.extend_from_slice(b"\xff\xff");
+
+        for chunk_size in [1, 3, data.len()] {
+            let mut rows = CopyRowCounter::new(TransferFormat::Binary, CopyTextOptions::default());
+            for chunk in data.chunks(chunk_size) {
+                rows.feed(chunk);
+            }
+            assert_eq!(rows.rows(), 3, "chunk size {chunk_size}");
+        }
+    }
+
+    #[test]
+    fn sent_row_count() {
+        let tag = |s: &'static str| Bytes::from_static(s.as_bytes());
+        assert_eq!(with_sent_row_count(tag("SELECT 1000"), 2), tag("SELECT 2"));
+        assert_eq!(with_sent_row_count(tag("FETCH 10"), 0), tag("FETCH 0"));
+        assert_eq!(with_sent_row_count(tag("SELECT 3"), 3), tag("SELECT 3"));
+        // Rows returned by DML report the number of rows affected, not the number returned
+        assert_eq!(with_sent_row_count(tag("INSERT 0 5"), 2), tag("INSERT 0 5"));
+        assert_eq!(with_sent_row_count(tag("SHOW"), 1), tag("SHOW"));
+    }
 }
@@ -1,3 +1,5 @@
+use std::io;
+
 use tokio::io::{AsyncRead, AsyncWrite};
 
 use crate::channel::Channel;
@@ -16,7 +18,12 @@ pub struct Runner<B: Backend, C> {
 impl<B: Backend, C: AsyncRead + AsyncWrite + Unpin> Runner<B, C> {
     /// A simple run loop. For each `FrontendMessage` received on `channel`, use `protocol` to
     /// generate a response. Then send the response. If an error occurs, use `protocol` to generate
-    /// an error response, then send the error response.
+    /// an error response, then send the error response. If the error is
+    /// [fatal](Error::is_fatal), the connection is then closed.
+    ///
+    /// If reading from `byte_channel` fails with [`io::ErrorKind::ConnectionAborted`], the server
+    /// is assumed to be shutting down, so the frontend is sent a fatal `admin_shutdown` error and
+    /// the connection is closed.
     pub async fn run(backend: B, byte_channel: C) {
         let mut runner = Runner {
             backend,
@@ -28,12 +35,17 @@ impl<B: Backend, C: AsyncRead + AsyncWrite + Unpin> Runner<B, C> {
             match runner.handle_request(message).await {
                 Ok(_) => {}
                 Err(e) => {
+                    let fatal = e.is_fatal();
                     runner
                         .handle_error(e)
                         .await
                         .unwrap_or_else(|e| eprintln!("{}", e));
+                    if fatal {
+                        break;
+                    }
                 }
             };
+            runner.backend.on_request_complete();
         }
     }
 
@@ -41,7 +53,14 @@ impl<B: Backend, C: AsyncRead + AsyncWrite + Unpin> Runner<B, C> {
         &mut self,
         request: Result<FrontendMessage, codec::DecodeError>,
     ) -> Result<(), Error> {
-        let request = request?;
+        let request = request.map_err(|e| match e {
+            codec::DecodeError::IoError(e) if e.kind() == io::ErrorKind::ConnectionAborted => {
+                Error::AdminShutdown(
+                    "terminating connection due to administrator command".to_owned(),
+                )
+            }
+            e => e.into(),
+        })?;
         if request == FrontendMessage::Flush {
             self.channel.flush().await?;
         }
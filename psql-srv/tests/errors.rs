@@ -1,16 +1,21 @@
 use std::convert::TryFrom;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::{io, vec};
 
 use async_trait::async_trait;
-use futures::Future;
+use futures::{stream, Future};
 use postgres::NoTls;
 use postgres_types::Type;
 use psql_srv::{
     run_backend, Backend, Column, Credentials, CredentialsNeeded, Error, PrepareResponse,
     QueryResponse,
 };
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio::join;
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::oneshot;
+use tokio_postgres::error::SqlState;
 use tokio_postgres::Client;
 
 struct Value(Result<psql_srv::Value, Error>);
@@ -38,7 +43,7 @@ struct ErrorBackend(ErrorPosition);
 impl Backend for ErrorBackend {
     type Value = Value;
     type Row = Vec<Value>;
-    type Resultset = Vec<Self::Row>;
+    type Resultset = stream::Iter<vec::IntoIter<Result<Self::Row, Error>>>;
 
     async fn on_init(&mut self, _database: &str) -> Result<CredentialsNeeded, Error> {
         Ok(CredentialsNeeded::None)
@@ -54,7 +59,7 @@ impl Backend for ErrorBackend {
         } else {
             Ok(QueryResponse::Select {
                 schema: vec![],
-                resultset: vec![],
+                resultset: stream::iter(vec![]),
             })
         }
     }
@@ -86,11 +91,13 @@ impl Backend for ErrorBackend {
                     name: "x".to_owned(),
                     col_type: Type::BOOL,
                 }],
-                resultset: vec![vec![Value(Err(Error::InternalError("factory".to_owned())))]],
+                resultset: stream::iter(vec![Ok(vec![Value(Err(Error::InternalError(
+                    "factory".to_owned(),
+                )))])]),
             }),
             _ => Ok(QueryResponse::Select {
                 schema: vec![],
-                resultset: vec![],
+                resultset: stream::iter(vec![]),
             }),
         }
     }
@@ -223,3 +230,81 @@ async fn serialize_error() {
     })
     .await
 }
+
+/// A connection which fails reads that would wait for the client with
+/// [`io::ErrorKind::ConnectionAborted`] once `abort` has been sent, the way connections are closed
+/// when the server shuts down
+struct AbortableStream {
+    inner: TcpStream,
+    abort: oneshot::Receiver<()>,
+}
+
+impl AsyncRead for AbortableStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if let Poll::Ready(res) = Pin::new(&mut self.inner).poll_read(cx, buf) {
+            return Poll::Ready(res);
+        }
+        match Pin::new(&mut self.abort).poll(cx) {
+            Poll::Ready(_) => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "server is shutting down",
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl AsyncWrite for AbortableStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn admin_shutdown_error() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (abort_tx, abort) = oneshot::channel();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let stream = AbortableStream {
+            inner: socket,
+            abort,
+        };
+        run_backend(ErrorBackend(ErrorPosition::Query), stream).await;
+    });
+
+    let (_client, conn) = tokio_postgres::Config::default()
+        .host("localhost")
+        .port(port)
+        .dbname("noria")
+        .connect(NoTls)
+        .await
+        .unwrap();
+    abort_tx.send(()).unwrap();
+
+    // The error isn't a response to any request, so it's returned by the connection itself
+    let err = conn.await.unwrap_err();
+    assert_eq!(err.code(), Some(&SqlState::ADMIN_SHUTDOWN));
+    assert_eq!(
+        err.as_db_error().unwrap().message(),
+        "terminating connection due to administrator command"
+    );
+    server.await.unwrap();
+}
@@ -0,0 +1,206 @@
+//! Tagging of client connections with the name of the application that opened them.
+//!
+//! PostgreSQL clients send an `application_name` startup parameter, and MySQL clients send a
+//! `program_name` connection attribute. A [`Backend`](crate::Backend) which is told its client's
+//! application name records it on the current (connection) tracing span, as the
+//! `application_name` field, and as the `application_name` label on the
+//! [`CONNECTED_CLIENTS_BY_APPLICATION`](recorded::CONNECTED_CLIENTS_BY_APPLICATION) gauge.
+//!
+//! Since application names are chosen by clients, the number of distinct names used as labels is
+//! capped at [`MAX_APPLICATION_NAMES`] - once that many have been seen, connections from any other
+//! application are labelled [`OTHER_APPLICATION_NAME`].
+
+use std::collections::HashSet;
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use readyset_client_metrics::recorded;
+use tracing::Span;
+
+/// The maximum number of distinct application names used as metric labels
+pub const MAX_APPLICATION_NAMES: usize = 32;
+
+/// The maximum length, in bytes, of an application name used as a metric label. Longer names are
+/// truncated.
+pub const MAX_APPLICATION_NAME_LEN: usize = 64;
+
+/// The label used for connections from applications beyond the first [`MAX_APPLICATION_NAMES`]
+pub const OTHER_APPLICATION_NAME: &str = "other";
+
+lazy_static! {
+    static ref APPLICATION_NAMES: ApplicationNames = ApplicationNames::new(MAX_APPLICATION_NAMES);
+}
+
+/// The set of application names seen so far, used to cap the cardinality of the labels we emit
+struct ApplicationNames {
+    max: usize,
+    seen: Mutex<HashSet<String>>,
+}
+
+impl ApplicationNames {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            seen: Default::default(),
+        }
+    }
+
+    /// Returns the label to use for connections from the application with the given name
+    fn label(&self, name: &str) -> String {
+        let mut end = name.len().min(MAX_APPLICATION_NAME_LEN);
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        let name = &name[..end];
+
+        let mut seen = self.seen.lock();
+        if seen.contains(name) {
+            return name.to_owned();
+        }
+        if seen.len() >= self.max {
+            return OTHER_APPLICATION_NAME.to_owned();
+        }
+        seen.insert(name.to_owned());
+        name.to_owned()
+    }
+}
+
+/// The application name of a single client connection, which is counted in the
+/// [`CONNECTED_CLIENTS_BY_APPLICATION`](recorded::CONNECTED_CLIENTS_BY_APPLICATION) gauge for as
+/// long as it is alive
+pub(crate) struct ApplicationName {
+    label: String,
+}
+
+impl ApplicationName {
+    /// Tag the current connection with the given application name, recording it on the current
+    /// span and in per-connection metrics
+    pub(crate) fn new(name: &str) -> Self {
+        Self::with_names(&APPLICATION_NAMES, name)
+    }
+
+    fn with_names(names: &ApplicationNames, name: &str) -> Self {
+        let label = names.label(name);
+        Span::current().record("application_name", label.as_str());
+        metrics::increment_gauge!(
+            recorded::CONNECTED_CLIENTS_BY_APPLICATION,
+            1.0,
+            "application_name" => label.clone()
+        );
+        Self { label }
+    }
+
+    /// The label used for this connection's application name in metrics and traces
+    pub(crate) fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+impl Drop for ApplicationName {
+    fn drop(&mut self) {
+        metrics::decrement_gauge!(
+            recorded::CONNECTED_CLIENTS_BY_APPLICATION,
+            1.0,
+            "application_name" => self.label.clone()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{info_span, Subscriber};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::Layer;
+
+    use super::*;
+
+    /// A tracing layer which remembers the last value recorded for the `application_name` field
+    /// of any span
+    #[derive(Clone, Default)]
+    struct RecordedApplicationName(Arc<Mutex<Option<String>>>);
+
+    impl Visit for RecordedApplicationName {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if field.name() == "application_name" {
+                *self.0.lock() = Some(value.to_owned());
+            }
+        }
+
+        fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+    }
+
+    impl<S: Subscriber> Layer<S> for RecordedApplicationName {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+            attrs.record(&mut self.clone());
+        }
+
+        fn on_record(&self, _: &Id, values: &Record<'_>, _: Context<'_, S>) {
+            values.record(&mut self.clone());
+        }
+    }
+
+    fn connected_clients(application_name: &str) -> Option<f64> {
+        Snapshotter::current_thread_snapshot()?
+            .into_vec()
+            .into_iter()
+            .find_map(|(key, _, _, value)| match value {
+                DebugValue::Gauge(v)
+                    if key.key().name() == recorded::CONNECTED_CLIENTS_BY_APPLICATION
+                        && key.key().labels().any(|l| {
+                            l.key() == "application_name" && l.value() == application_name
+                        }) =>
+                {
+                    Some(v.into_inner())
+                }
+                _ => None,
+            })
+    }
+
+    #[test]
+    fn application_name_on_span_and_metrics() {
+        // Ignore the error if another test on this thread already installed the recorder
+        let _ = DebuggingRecorder::per_thread().install();
+        let recorded = RecordedApplicationName::default();
+        let subscriber = tracing_subscriber::registry().with(recorded.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = info_span!("connection", application_name = tracing::field::Empty);
+            let _guard = span.enter();
+
+            let names = ApplicationNames::new(MAX_APPLICATION_NAMES);
+            let first = ApplicationName::with_names(&names, "checkout");
+            assert_eq!(recorded.0.lock().as_deref(), Some("checkout"));
+            assert_eq!(connected_clients("checkout"), Some(1.0));
+
+            let second = ApplicationName::with_names(&names, "checkout");
+            assert_eq!(connected_clients("checkout"), Some(2.0));
+
+            drop(first);
+            drop(second);
+            assert_eq!(connected_clients("checkout"), Some(0.0));
+        });
+    }
+
+    #[test]
+    fn application_names_are_capped() {
+        let names = ApplicationNames::new(2);
+        assert_eq!(names.label("a"), "a");
+        assert_eq!(names.label("b"), "b");
+        assert_eq!(names.label("c"), OTHER_APPLICATION_NAME);
+        assert_eq!(names.label("a"), "a");
+    }
+
+    #[test]
+    fn long_application_names_are_truncated() {
+        let names = ApplicationNames::new(MAX_APPLICATION_NAMES);
+        let long = "é".repeat(MAX_APPLICATION_NAME_LEN);
+        let label = names.label(&long);
+        assert!(label.len() <= MAX_APPLICATION_NAME_LEN);
+        assert!(long.starts_with(&label));
+    }
+}
@@ -74,15 +74,16 @@ use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
 use std::fmt::{self, Debug};
 use std::marker::PhantomData;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
 use futures::future::{self, OptionFuture};
 use mysql_common::row::convert::{FromRow, FromRowError};
+use nom_sql::analysis::visit::{walk_function_expr, Visitor};
 use nom_sql::{
-    CacheInner, CreateCacheStatement, DeleteStatement, Dialect, DropCacheStatement,
+    CacheInner, CreateCacheStatement, DeleteStatement, Dialect, DropCacheStatement, FunctionExpr,
     InsertStatement, Relation, SelectStatement, SetStatement, ShowStatement, SqlIdentifier,
-    SqlQuery, UpdateStatement, UseStatement,
+    SqlQuery, StatementComments, UpdateStatement, UseStatement, Variable,
 };
 use readyset_client::consistency::Timestamp;
 use readyset_client::query::*;
@@ -94,18 +95,26 @@ use readyset_data::{DfType, DfValue};
 use readyset_errors::ReadySetError::{self, PreparedStatementMissing};
 use readyset_errors::{internal, internal_err, unsupported, ReadySetResult};
 use readyset_telemetry_reporter::{TelemetryBuilder, TelemetryEvent, TelemetrySender};
-use readyset_tracing::{error, trace, warn};
+use readyset_tracing::propagation::RequestContext;
+use readyset_tracing::{debug, error, trace, warn};
 use readyset_util::redacted::Sensitive;
 use readyset_version::READYSET_VERSION;
 use timestamp_service::client::{TimestampClient, WriteId, WriteKey};
 use tokio::sync::mpsc::UnboundedSender;
-use tracing::instrument;
+use tokio::sync::OwnedSemaphorePermit;
+use tracing::{instrument, Span};
 
+use crate::application_name::ApplicationName;
 use crate::backend::noria_connector::ExecuteSelectContext;
+use crate::cache_stats_reporter::CacheHitCounter;
+use crate::cancel::{CancelAuth, CancelKey, CancelRegistration, CancelRegistry, QueryCanceller};
+use crate::connection_stats::ConnectionStats;
+use crate::drain::{ConnectionDrain, DrainableStream};
 use crate::query_handler::SetBehavior;
 use crate::query_status_cache::QueryStatusCache;
-use crate::upstream_database::NoriaCompare;
 pub use crate::upstream_database::UpstreamPrepare;
+use crate::upstream_database::{IsFatalError, NoriaCompare, UpstreamRoutes};
+use crate::upstream_pool::{PooledUpstream, UpstreamPool};
 use crate::{rewrite, QueryHandler, UpstreamDatabase, UpstreamDestination};
 
 pub mod noria_connector;
@@ -209,6 +218,12 @@ impl ProxyState {
         )
     }
 
+    /// Returns true if we're inside a transaction on the upstream database, either explicitly or
+    /// because autocommit is turned off
+    fn in_transaction(&self) -> bool {
+        matches!(self, Self::InTransaction | Self::AutocommitOff)
+    }
+
     /// Perform the appropriate state transition for this proxy state to begin a new transaction.
     fn start_transaction(&mut self) {
         if self.is_fallback() {
@@ -247,13 +262,18 @@ impl ProxyState {
     }
 }
 
+/// The default duration above which queries are considered slow, for the purposes of the slow
+/// query log
+pub const DEFAULT_SLOW_QUERY_THRESHOLD: Duration = Duration::from_millis(5);
+
 /// Builder for a [`Backend`]
 #[must_use]
 #[derive(Clone)]
 pub struct BackendBuilder {
     slowlog: bool,
+    slow_query_threshold: Duration,
     dialect: Dialect,
-    users: HashMap<String, String>,
+    users: Arc<RwLock<HashMap<String, String>>>,
     require_authentication: bool,
     ticket: Option<Timestamp>,
     timestamp_client: Option<TimestampClient>,
@@ -266,12 +286,20 @@ pub struct BackendBuilder {
     query_max_failure_seconds: u64,
     fallback_recovery_seconds: u64,
     telemetry_sender: Option<TelemetrySender>,
+    cancel_registry: Option<Arc<CancelRegistry>>,
+    upstream_routes: Option<Arc<UpstreamRoutes>>,
+    cache_stats: Option<Arc<CacheHitCounter>>,
+    query_tag_from_comment: Option<String>,
+    enable_protocol_compression: bool,
+    idle_timeout: Option<Duration>,
+    connection_drain: ConnectionDrain,
 }
 
 impl Default for BackendBuilder {
     fn default() -> Self {
         BackendBuilder {
             slowlog: false,
+            slow_query_threshold: DEFAULT_SLOW_QUERY_THRESHOLD,
             dialect: Dialect::MySQL,
             users: Default::default(),
             require_authentication: true,
@@ -286,6 +314,13 @@ impl Default for BackendBuilder {
             query_max_failure_seconds: (i64::MAX / 1000) as u64,
             fallback_recovery_seconds: 0,
             telemetry_sender: None,
+            cancel_registry: None,
+            upstream_routes: None,
+            cache_stats: None,
+            query_tag_from_comment: None,
+            enable_protocol_compression: false,
+            idle_timeout: None,
+            connection_drain: ConnectionDrain::new(),
         }
     }
 }
@@ -299,7 +334,7 @@ impl BackendBuilder {
         self,
         noria: NoriaConnector,
         upstream: Option<DB>,
-        query_status_cache: &'static QueryStatusCache,
+        query_status_cache: Arc<QueryStatusCache>,
     ) -> Backend<DB, Handler> {
         metrics::increment_gauge!(recorded::CONNECTED_CLIENTS, 1.0);
 
@@ -309,10 +344,21 @@ impl BackendBuilder {
             ProxyState::Never
         };
 
+        let cancel_registration = self.cancel_registry.map(|registry| {
+            let registration = registry.register();
+            registration
+                .canceller()
+                .set_upstream(upstream.as_ref().and_then(|u| u.query_canceller()));
+            registration
+        });
+
         Backend {
             noria,
             upstream,
-            users: self.users,
+            upstream_pool: None,
+            upstream_permit: None,
+            upstream_pinned: false,
+            users: self.users.read().unwrap().clone(),
             query_log_sender: self.query_log_sender,
             last_query: None,
             state: BackendState {
@@ -325,6 +371,7 @@ impl BackendBuilder {
             },
             settings: BackendSettings {
                 slowlog: self.slowlog,
+                slow_query_threshold: self.slow_query_threshold,
                 dialect: self.dialect,
                 require_authentication: self.require_authentication,
                 validate_queries: self.validate_queries,
@@ -334,8 +381,17 @@ impl BackendBuilder {
                 query_max_failure_duration: Duration::new(self.query_max_failure_seconds, 0),
                 query_log_ad_hoc_queries: self.query_log_ad_hoc_queries,
                 fallback_recovery_duration: Duration::new(self.fallback_recovery_seconds, 0),
+                query_tag_from_comment: self.query_tag_from_comment,
+                enable_protocol_compression: self.enable_protocol_compression,
+                idle_timeout: self.idle_timeout,
+                connection_drain: self.connection_drain,
             },
             telemetry_sender: self.telemetry_sender,
+            connection_stats: Arc::default(),
+            cache_stats: self.cache_stats,
+            cancel_registration,
+            upstream_routes: self.upstream_routes,
+            application_name: None,
             _query_handler: PhantomData,
         }
     }
@@ -345,6 +401,21 @@ impl BackendBuilder {
         self
     }
 
+    /// Set the duration above which queries are considered slow, and logged if
+    /// [`slowlog`](Self::slowlog) is enabled. Defaults to 5ms.
+    pub fn slow_query_threshold(mut self, slow_query_threshold: Duration) -> Self {
+        self.slow_query_threshold = slow_query_threshold;
+        self
+    }
+
+    /// Set the key of the tag to extract from the comments of each query (for example `app` in
+    /// `/* app:checkout */ SELECT ...`), which is recorded in the query log and used as a label on
+    /// query metrics
+    pub fn query_tag_from_comment(mut self, key: Option<String>) -> Self {
+        self.query_tag_from_comment = key;
+        self
+    }
+
     pub fn dialect(mut self, dialect: Dialect) -> Self {
         self.dialect = dialect;
         self
@@ -360,7 +431,11 @@ impl BackendBuilder {
         self
     }
 
-    pub fn users(mut self, users: HashMap<String, String>) -> Self {
+    /// Set the usernames and passwords which clients may authenticate with.
+    ///
+    /// The set of users may be replaced while backends are being built; each backend uses the
+    /// users as they were when it was built, so replacing them doesn't affect existing connections.
+    pub fn users(mut self, users: Arc<RwLock<HashMap<String, String>>>) -> Self {
         self.users = users;
         self
     }
@@ -370,6 +445,27 @@ impl BackendBuilder {
         self
     }
 
+    /// Specifies whether clients may negotiate compression of the wire protocol, for protocols
+    /// which support it (currently only MySQL). Defaults to false.
+    pub fn enable_protocol_compression(mut self, enable_protocol_compression: bool) -> Self {
+        self.enable_protocol_compression = enable_protocol_compression;
+        self
+    }
+
+    /// Close connections which have been idle, waiting for the client to send anything, for longer
+    /// than the given duration. Defaults to `None`, meaning idle connections are never closed.
+    pub fn idle_timeout(mut self, idle_timeout: Option<Duration>) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Close the connection once it has finished executing its current statement when the given
+    /// [`ConnectionDrain`] is drained. Defaults to a `ConnectionDrain` which is never drained.
+    pub fn connection_drain(mut self, connection_drain: ConnectionDrain) -> Self {
+        self.connection_drain = connection_drain;
+        self
+    }
+
     /// Specifies whether RYW consistency should be enabled. If true, RYW consistency
     /// constraints will be enforced on all reads.
     pub fn enable_ryw(mut self, enable_ryw: bool) -> Self {
@@ -415,6 +511,27 @@ impl BackendBuilder {
         self.telemetry_sender = Some(telemetry_sender);
         self
     }
+
+    /// Register each backend built by this builder in the given [`CancelRegistry`], allowing
+    /// queries running on it to be cancelled from other connections
+    pub fn cancel_registry(mut self, cancel_registry: Arc<CancelRegistry>) -> Self {
+        self.cancel_registry = Some(cancel_registry);
+        self
+    }
+
+    /// Record whether each read served by backends built by this builder was a cache hit in the
+    /// given [`CacheHitCounter`], which is shared by all of them
+    pub fn cache_stats(mut self, cache_stats: Arc<CacheHitCounter>) -> Self {
+        self.cache_stats = Some(cache_stats);
+        self
+    }
+
+    /// Route the upstream connections of backends built by this builder to different upstream
+    /// databases depending on their default schema
+    pub fn upstream_routes(mut self, upstream_routes: Arc<UpstreamRoutes>) -> Self {
+        self.upstream_routes = Some(upstream_routes);
+        self
+    }
 }
 
 /// A [`CachedPreparedStatement`] stores the data needed for an immediate
@@ -443,6 +560,8 @@ where
     /// If statement was successfully rewritten, will store all information necessary to install
     /// the view in readyset
     view_request: Option<ViewCreateRequest>,
+    /// The comments surrounding the statement when it was prepared
+    comments: StatementComments,
 }
 
 impl<DB> CachedPreparedStatement<DB>
@@ -483,6 +602,17 @@ where
     noria: NoriaConnector,
     /// Optional connector to the upstream DB. Used for fallback reads and all writes if it exists
     upstream: Option<DB>,
+    /// The pool that `upstream` was checked out from, if upstream connections are pooled. Unless
+    /// `upstream_pinned` is set, the connection is released back into the pool after every
+    /// statement (leaving `upstream` empty until the next statement checks one out), and it's
+    /// always released when this backend is dropped
+    upstream_pool: Option<Arc<UpstreamPool<DB>>>,
+    /// The permit which counts `upstream` against the size of `upstream_pool`, if it was checked
+    /// out of the pool
+    upstream_permit: Option<OwnedSemaphorePermit>,
+    /// Whether this connection has created state scoped to its upstream connection, such as
+    /// prepared statements or session variables, and so can't share it with other clients
+    upstream_pinned: bool,
     /// Map from username to password for all users allowed to connect to the db
     pub users: HashMap<String, String>,
 
@@ -500,6 +630,23 @@ where
     /// Provides the ability to send [`TelemetryEvent`]s to Segment
     telemetry_sender: Option<TelemetrySender>,
 
+    /// Counters for the activity on this connection, reported by `SHOW READYSET CONNECTION STATS`
+    connection_stats: Arc<ConnectionStats>,
+
+    /// Counts the cache hits and misses of reads served by all of the adapter's connections, if
+    /// configured
+    cache_stats: Option<Arc<CacheHitCounter>>,
+
+    /// This connection's registration in the [`CancelRegistry`], if cancelling queries is enabled
+    cancel_registration: Option<CancelRegistration>,
+
+    /// Routes used to select the upstream database for this connection based on its default
+    /// schema, if configured
+    upstream_routes: Option<Arc<UpstreamRoutes>>,
+
+    /// The name of the client application, if the client sent one when connecting
+    application_name: Option<ApplicationName>,
+
     _query_handler: PhantomData<Handler>,
 }
 
@@ -510,7 +657,7 @@ where
 {
     proxy_state: ProxyState,
     /// A cache of queries that we've seen, and their current state, used for processing
-    query_status_cache: &'static QueryStatusCache,
+    query_status_cache: Arc<QueryStatusCache>,
     // a cache of all previously parsed queries
     parsed_query_cache: HashMap<String, SqlQuery>,
     // all queries previously prepared on noria or upstream, mapped by their ID.
@@ -531,6 +678,8 @@ struct BackendSettings {
     /// SQL dialect to use when parsing queries from clients
     dialect: Dialect,
     slowlog: bool,
+    /// Queries taking longer than this are logged, if `slowlog` is enabled
+    slow_query_threshold: Duration,
     require_authentication: bool,
     /// Whether to log ad-hoc queries by full query text in the query logger.
     query_log_ad_hoc_queries: bool,
@@ -547,6 +696,22 @@ struct BackendSettings {
     /// repeatedly failed for query_max_failure_duration.
     fallback_recovery_duration: Duration,
     fail_invalidated_queries: bool,
+    /// The key of the tag to extract from query comments, if any
+    query_tag_from_comment: Option<String>,
+    /// Whether clients may negotiate compression of the wire protocol
+    enable_protocol_compression: bool,
+    /// How long the connection may be idle before it is closed, if ever
+    idle_timeout: Option<Duration>,
+    /// Signals the connection to close when the adapter shuts down
+    connection_drain: ConnectionDrain,
+}
+
+impl BackendSettings {
+    /// Extract the value of the configured query tag from the comments of a query, if any
+    fn query_tag(&self, comments: &StatementComments) -> Option<String> {
+        let key = self.query_tag_from_comment.as_deref()?;
+        comments.tag(key).map(|tag| tag.to_owned())
+    }
 }
 
 /// QueryInfo holds information regarding the last query that was sent along this connection
@@ -602,6 +767,22 @@ pub enum MigrationMode {
     OutOfBand,
 }
 
+/// A per-statement override of how a query is routed, given by a `/* readyset: <directive> */`
+/// comment before or after the statement.
+///
+/// Directives only apply to `SELECT` statements, and are ignored for all other statements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutingDirective {
+    /// `/* readyset: proxy */` - always proxy the query to the upstream database, even if it is
+    /// cached in ReadySet. Ignored if there is no upstream database.
+    Proxy,
+    /// `/* readyset: cache */` - always attempt to execute the query against ReadySet, migrating
+    /// it synchronously if it has not been migrated yet, regardless of the [`MigrationMode`].
+    /// Queries that must be proxied anyway (such as queries within a transaction, or queries
+    /// ReadySet does not support) are still proxied.
+    Cache,
+}
+
 #[derive(Debug, Clone)]
 pub struct SelectSchema<'a> {
     pub use_bogo: bool,
@@ -725,6 +906,190 @@ where
             .unwrap_or_else(|| DB::DEFAULT_DB_VERSION.to_string())
     }
 
+    /// Returns a handle to the [`ConnectionStats`] for this backend's connection, so that the
+    /// number of bytes sent to the client can be recorded by the connection handler
+    pub fn connection_stats(&self) -> Arc<ConnectionStats> {
+        self.connection_stats.clone()
+    }
+
+    /// Record the name of the client application which opened this connection, as sent by the
+    /// client during the handshake, on the current tracing span and in per-connection metrics.
+    ///
+    /// See [the `application_name` module](crate::application_name) for more information.
+    pub fn set_application_name(&mut self, name: &str) {
+        if name.is_empty() {
+            return;
+        }
+        self.application_name = Some(ApplicationName::new(name));
+    }
+
+    /// Returns the (possibly truncated or replaced, to limit metric cardinality) name of the client
+    /// application which opened this connection, if known
+    pub fn application_name(&self) -> Option<&str> {
+        self.application_name.as_ref().map(|a| a.label())
+    }
+
+    /// Returns the key which clients can use to cancel queries running on this connection, if
+    /// cancelling queries is enabled
+    pub fn cancel_key(&self) -> Option<CancelKey> {
+        self.cancel_registration.as_ref().map(|r| r.key())
+    }
+
+    /// Record the user this connection authenticated as, so that its connections can be drained
+    /// with [`CancelRegistry::drain_user`]
+    pub fn set_user(&mut self, user: &str) {
+        if let Some(registration) = &self.cancel_registration {
+            registration.set_user(user);
+        }
+    }
+
+    /// Cancel the query currently running on the connection with the given id and secret,
+    /// returning `true` if such a connection exists.
+    ///
+    /// Note that this cancels a query on *another* connection, which need not be the connection for
+    /// this backend.
+    pub fn cancel_query(&self, connection_id: u32, secret: u32) -> bool {
+        self.cancel_registration.as_ref().map_or(false, |r| {
+            r.registry()
+                .cancel(connection_id, CancelAuth::Secret(secret))
+        })
+    }
+
+    /// Kill the connection with the given id, or only the query currently running on it if
+    /// `query_only` is set, returning `true` if such a connection exists.
+    ///
+    /// Only connections which authenticated as the same user as this connection can be killed.
+    pub fn kill(&self, connection_id: u32, query_only: bool) -> bool {
+        let registration = match &self.cancel_registration {
+            Some(registration) => registration,
+            None => return false,
+        };
+        let user = match registration.user() {
+            Some(user) => user,
+            None => return false,
+        };
+        let auth = CancelAuth::User(&user);
+        if query_only {
+            registration.registry().cancel(connection_id, auth)
+        } else {
+            registration.registry().kill(connection_id, auth)
+        }
+    }
+
+    fn query_canceller(&self) -> Option<Arc<QueryCanceller>> {
+        self.cancel_registration.as_ref().map(|r| r.canceller())
+    }
+
+    /// If this backend shares upstream connections through a pool and has released its own, check
+    /// out a connection from the pool to run the next statement on
+    async fn checkout_upstream(&mut self) -> Result<(), DB::Error> {
+        Self::checkout_upstream_into(
+            &mut self.upstream,
+            &mut self.upstream_permit,
+            self.upstream_pool.as_deref(),
+            self.cancel_registration.as_ref(),
+        )
+        .await
+    }
+
+    /// Implementation of [`checkout_upstream`](Self::checkout_upstream) which only borrows the
+    /// fields of the backend it needs, so that it can be called while a result borrowed from the
+    /// ReadySet connector is still alive
+    async fn checkout_upstream_into(
+        upstream: &mut Option<DB>,
+        upstream_permit: &mut Option<OwnedSemaphorePermit>,
+        pool: Option<&UpstreamPool<DB>>,
+        cancel_registration: Option<&CancelRegistration>,
+    ) -> Result<(), DB::Error> {
+        if let (None, Some(pool)) = (&upstream, pool) {
+            let PooledUpstream {
+                upstream: pooled,
+                permit,
+            } = pool.checkout().await?;
+            if let Some(registration) = cancel_registration {
+                registration
+                    .canceller()
+                    .set_upstream(pooled.query_canceller());
+            }
+            *upstream = Some(pooled);
+            *upstream_permit = Some(permit);
+        }
+        Ok(())
+    }
+
+    /// Make sure this backend has an upstream connection, and keep it until the client disconnects
+    /// rather than sharing it with other clients, for protocol features that span multiple
+    /// requests from the client and so must run on a single upstream connection.
+    pub async fn pin_upstream(&mut self) -> Result<(), DB::Error> {
+        self.checkout_upstream().await?;
+        self.upstream_pinned = true;
+        Ok(())
+    }
+
+    /// If this backend shares upstream connections through a pool, release its upstream connection
+    /// back into the pool for other clients to use, unless the connection is pinned to this client.
+    ///
+    /// This should be called by protocol handlers once each statement is complete.
+    pub fn release_upstream(&mut self) {
+        if self.upstream_pinned
+            || self.state.proxy_state.in_transaction()
+            || self.upstream_interrupted()
+        {
+            return;
+        }
+        if let (Some(pool), Some(upstream)) = (&self.upstream_pool, &self.upstream) {
+            // Connections which were routed to a different upstream database stay with their
+            // client
+            if upstream.url() != pool.url() || self.upstream_permit.is_none() {
+                return;
+            }
+            if let (Some(upstream), Some(permit)) =
+                (self.upstream.take(), self.upstream_permit.take())
+            {
+                pool.release(PooledUpstream { upstream, permit }, false, false);
+            }
+            self.refresh_upstream_canceller();
+        }
+    }
+
+    /// Returns true if a statement on this connection was interrupted by a cancel request, and so
+    /// its upstream connection may have been left part-way through a request or response
+    fn upstream_interrupted(&self) -> bool {
+        self.query_canceller()
+            .map_or(false, |canceller| canceller.interrupted())
+    }
+
+    /// If the last statement on this connection was interrupted by a cancel request, reset the
+    /// upstream connection it was running on, which may have been left part-way through a request
+    /// or response and so can't be used for anything else.
+    ///
+    /// Resetting the connection loses all of its session state, including any open transaction.
+    async fn discard_interrupted_upstream(&mut self) -> Result<(), DB::Error> {
+        if !self
+            .query_canceller()
+            .map_or(false, |canceller| canceller.take_interrupted())
+        {
+            return Ok(());
+        }
+        if let Some(upstream) = &mut self.upstream {
+            debug!("Resetting upstream connection after interrupted statement");
+            self.state.proxy_state.end_transaction();
+            upstream.reset().await?;
+            self.refresh_upstream_canceller();
+        }
+        Ok(())
+    }
+
+    /// Update the handle used to cancel queries on this connection's upstream database, which must
+    /// be done whenever the upstream connection is replaced
+    fn refresh_upstream_canceller(&self) {
+        if let Some(registration) = &self.cancel_registration {
+            registration
+                .canceller()
+                .set_upstream(self.upstream.as_ref().and_then(|u| u.query_canceller()));
+        }
+    }
+
     /// The identifier of the last prepared statement (which is always the last in the vector)
     pub fn last_prepared_id(&self) -> u32 {
         (self.state.prepared_statements.len() - 1)
@@ -742,8 +1107,16 @@ where
     /// Switch the active database for this backend to the given named database.
     ///
     /// Internally, this will set the schema search path to a single-element vector with the
-    /// database, and send a `USE` command to the upstream, if any.
+    /// database, and send a `USE` command to the upstream, if any. If an upstream route is
+    /// configured for the database, the upstream connection is first switched over to the routed
+    /// upstream.
     pub async fn set_database(&mut self, db: &str) -> Result<(), DB::Error> {
+        self.discard_interrupted_upstream().await?;
+        self.checkout_upstream().await?;
+        if self.database() != Some(db) {
+            self.upstream_pinned = true;
+        }
+        self.route_upstream(db).await?;
         if let Some(upstream) = &mut self.upstream {
             upstream
                 .query(
@@ -758,6 +1131,45 @@ where
         Ok(())
     }
 
+    /// Returns true if a statement which failed with `error`, and which has already been retried
+    /// `retries` times, should be retried.
+    ///
+    /// Only [retryable](crate::upstream_database::UpstreamErrorClass::Retryable) errors are
+    /// retried, and never inside a transaction, since the upstream database will generally have
+    /// rolled back the rest of the transaction.
+    pub fn should_retry(&self, error: &DB::Error, retries: u32) -> bool {
+        if !error.classify().should_retry(retries) || self.state.proxy_state.in_transaction() {
+            return false;
+        }
+
+        debug!(%error, %retries, "Retrying statement after upstream error");
+        true
+    }
+
+    /// If the upstream connection for this backend isn't already connected to the upstream that the
+    /// given schema is routed to, reconnect to it. Schemas without a route are routed to the
+    /// default upstream, so switching from a routed schema to an unrouted one reconnects to the
+    /// default upstream.
+    pub async fn route_upstream(&mut self, schema: &str) -> Result<(), DB::Error> {
+        let url = match self
+            .upstream_routes
+            .as_ref()
+            .and_then(|routes| routes.url_for_schema(schema))
+        {
+            Some(url) => url,
+            None => return Ok(()),
+        };
+
+        if let Some(upstream) = &mut self.upstream {
+            if upstream.url() != url {
+                debug!(%schema, "Routing connection to upstream for schema");
+                upstream.reconnect_to(url).await?;
+                self.refresh_upstream_canceller();
+            }
+        }
+        Ok(())
+    }
+
     /// Executes query on the upstream database, for when it cannot be parsed or executed by noria.
     /// Returns the query result, or an error if fallback is not configured
     #[instrument(skip_all)]
@@ -966,7 +1378,11 @@ where
     }
 
     /// Provides metadata required to prepare a select query
-    fn plan_prepare_select(&mut self, stmt: nom_sql::SelectStatement) -> PrepareMeta {
+    fn plan_prepare_select(
+        &mut self,
+        stmt: nom_sql::SelectStatement,
+        routing_directive: Option<RoutingDirective>,
+    ) -> PrepareMeta {
         match self.rewrite_select_and_check_noria(&stmt) {
             Some((rewritten, should_do_noria)) => {
                 let status = self
@@ -984,9 +1400,11 @@ where
                         rewritten,
                         should_do_noria,
                         // For select statements only InRequestPath should trigger migrations
-                        // synchronously, or if no upstream is present.
+                        // synchronously, or if no upstream is present, or if the query asked to
+                        // be cached.
                         must_migrate: self.settings.migration_mode == MigrationMode::InRequestPath
-                            || !self.has_fallback(),
+                            || !self.has_fallback()
+                            || routing_directive == Some(RoutingDirective::Cache),
                         always: status.always,
                     })
                 }
@@ -1027,13 +1445,19 @@ where
     }
 
     /// Provides metadata required to prepare a query
-    async fn plan_prepare(&mut self, query: &str) -> PrepareMeta {
+    async fn plan_prepare(&mut self, query: &str, comments: &StatementComments) -> PrepareMeta {
         if self.state.proxy_state == ProxyState::ProxyAlways {
             return PrepareMeta::Proxy;
         }
 
+        let routing_directive = routing_directive(comments);
         match self.parse_query(query) {
-            Ok(SqlQuery::Select(stmt)) => self.plan_prepare_select(stmt),
+            Ok(SqlQuery::Select(_))
+                if routing_directive == Some(RoutingDirective::Proxy) && self.has_fallback() =>
+            {
+                PrepareMeta::Proxy
+            }
+            Ok(SqlQuery::Select(stmt)) => self.plan_prepare_select(stmt, routing_directive),
             Ok(
                 query @ SqlQuery::Insert(_)
                 | query @ SqlQuery::Update(_)
@@ -1095,10 +1519,19 @@ where
     /// to the calling struct's map of prepared queries with a unique id.
     #[instrument(skip_all)]
     pub async fn prepare(&mut self, query: &str) -> Result<&PrepareResult<DB>, DB::Error> {
+        if let Some(canceller) = self.query_canceller() {
+            canceller.check_drained()?;
+        }
+        self.discard_interrupted_upstream().await?;
+        // Statements are also prepared on the upstream connection, which then can't be shared
+        self.checkout_upstream().await?;
+        self.upstream_pinned = true;
         self.last_query = None;
+        let comments = StatementComments::extract(self.settings.dialect, query);
+        link_trace_context(&comments);
         let mut query_event = QueryExecutionEvent::new(EventType::Prepare);
 
-        let meta = self.plan_prepare(query).await;
+        let meta = self.plan_prepare(query, &comments).await;
         let res = self.do_prepare(&meta, query, &mut query_event).await?;
 
         let (id, parsed_query, migration_state, view_request, always) = match meta {
@@ -1136,6 +1569,7 @@ where
             query_event.query = Some(parsed.clone());
         }
         query_event.query_id = id;
+        query_event.query_tag = self.settings.query_tag(&comments);
 
         let cache_entry = CachedPreparedStatement {
             query_id: id,
@@ -1145,6 +1579,7 @@ where
             parsed_query,
             view_request,
             always,
+            comments,
         };
 
         self.state.prepared_statements.push(cache_entry);
@@ -1350,14 +1785,31 @@ where
     /// `params`.
     /// A [`QueryExecutionEvent`], is used to track metrics and behavior scoped to the
     /// execute operation.
+    ///
+    /// If cancelling queries is enabled, execution will be interrupted by a cancel request for
+    /// this connection, after which the upstream connection is reset before running the next
+    /// statement.
     // TODO(andrew, justin): add RYW support for executing prepared queries
+    pub async fn execute(
+        &mut self,
+        id: u32,
+        params: &[DfValue],
+    ) -> Result<QueryResult<'_, DB>, DB::Error> {
+        self.discard_interrupted_upstream().await?;
+        match self.query_canceller() {
+            Some(canceller) => canceller.run(self.execute_inner(id, params)).await,
+            None => self.execute_inner(id, params).await,
+        }
+    }
+
     #[instrument(skip_all)]
     #[inline]
-    pub async fn execute(
+    async fn execute_inner(
         &mut self,
         id: u32,
         params: &[DfValue],
     ) -> Result<QueryResult<'_, DB>, DB::Error> {
+        self.checkout_upstream().await?;
         self.last_query = None;
         let cached_statement = self
             .state
@@ -1368,6 +1820,11 @@ where
         let mut event = QueryExecutionEvent::new(EventType::Execute);
         event.query = cached_statement.parsed_query.clone();
         event.query_id = cached_statement.query_id;
+        event.query_tag = self.settings.query_tag(&cached_statement.comments);
+        let is_read = matches!(
+            cached_statement.parsed_query.as_deref(),
+            Some(SqlQuery::Select(_))
+        );
 
         let upstream = &mut self.upstream;
         let noria = &mut self.noria;
@@ -1476,7 +1933,19 @@ where
                 .map(|e| e.to_string())
                 .unwrap_or_default(),
         });
-        log_query(self.query_log_sender.as_ref(), event, self.settings.slowlog);
+        record_connection_stats(
+            &self.connection_stats,
+            self.cache_stats.as_deref(),
+            &event,
+            is_read,
+        );
+        log_query(
+            self.query_log_sender.as_ref(),
+            event,
+            self.settings
+                .slowlog
+                .then_some(self.settings.slow_query_threshold),
+        );
 
         result
     }
@@ -1544,6 +2013,30 @@ where
         ]))
     }
 
+    /// Generates response to the `SHOW READYSET CONNECTION STATS` query
+    fn connection_stats_result(&self) -> ReadySetResult<noria_connector::QueryResult<'static>> {
+        Ok(noria_connector::QueryResult::Meta(vec![
+            ("Queries", self.connection_stats.queries().to_string()).into(),
+            ("Cache_hits", self.connection_stats.cache_hits().to_string()).into(),
+            ("Bytes_sent", self.connection_stats.bytes_sent().to_string()).into(),
+        ]))
+    }
+
+    /// Returns the schema search path for this connection, as resolved from the upstream database
+    /// and any overrides set by the client, in response to `SHOW READYSET SEARCH_PATH`
+    fn search_path_result(&self) -> ReadySetResult<noria_connector::QueryResult<'static>> {
+        Ok(noria_connector::QueryResult::Meta(vec![(
+            "Search_path",
+            self.noria
+                .schema_search_path()
+                .iter()
+                .map(|schema| schema.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+            .into()]))
+    }
+
     /// Forwards a `CREATE CACHE` request to noria
     #[instrument(skip(self))]
     async fn create_cached_query(
@@ -1695,6 +2188,9 @@ where
             SqlQuery::Explain(nom_sql::ExplainStatement::Graphviz { simplified }) => {
                 self.noria.graphviz(*simplified).await
             }
+            SqlQuery::Explain(nom_sql::ExplainStatement::Cache { name }) => {
+                self.noria.explain_cache(name).await
+            }
             SqlQuery::CreateCache(CreateCacheStatement {
                 name,
                 inner,
@@ -1758,6 +2254,10 @@ where
             SqlQuery::Show(ShowStatement::ReadySetStatus) => self.noria.readyset_status().await,
             SqlQuery::Show(ShowStatement::ReadySetVersion) => readyset_version(),
             SqlQuery::Show(ShowStatement::ReadySetTables) => self.noria.table_statuses().await,
+            SqlQuery::Show(ShowStatement::ReadySetConnectionStats) => {
+                self.connection_stats_result()
+            }
+            SqlQuery::Show(ShowStatement::ReadySetSearchPath) => self.search_path_result(),
             SqlQuery::Show(ShowStatement::ProxiedQueries(q_id)) => {
                 // Log a telemetry event
                 if let Some(ref telemetry_sender) = self.telemetry_sender {
@@ -1792,6 +2292,7 @@ where
         original_stmt: SelectStatement,
         view_request: &ViewCreateRequest,
         status: Option<QueryStatus>,
+        force_cache: bool,
         event: &mut QueryExecutionEvent,
     ) -> Result<QueryResult<'a, DB>, DB::Error> {
         let mut status = status.unwrap_or(QueryStatus {
@@ -1812,6 +2313,7 @@ where
         if !status.always
             && (upstream.is_some()
                 && (settings.migration_mode != MigrationMode::InRequestPath
+                    && !force_cache
                     && status.migration_state != MigrationState::Successful)
                 || (status.migration_state == MigrationState::Unsupported)
                 || (status
@@ -1838,7 +2340,8 @@ where
             let ctx = ExecuteSelectContext::AdHoc {
                 statement: original_stmt,
                 query: original_query,
-                create_if_missing: settings.migration_mode == MigrationMode::InRequestPath,
+                create_if_missing: settings.migration_mode == MigrationMode::InRequestPath
+                    || force_cache,
             };
             let res = noria.execute_select(ctx, state.ticket.clone(), event).await;
             event.readyset_duration = Some(start.elapsed());
@@ -2089,6 +2592,11 @@ where
                         upstream.query(raw_query).await.map(QueryResult::Upstream)
                     }
 
+                    // Savepoints don't start or end a transaction, so we can just proxy them
+                    SqlQuery::Savepoint(_) => {
+                        event.sql_type = SqlQueryType::Other;
+                        upstream.query(raw_query).await.map(QueryResult::Upstream)
+                    }
                     SqlQuery::StartTransaction(_) | SqlQuery::Commit(_) | SqlQuery::Rollback(_) => {
                         Self::handle_transaction_boundaries(
                             Some(upstream),
@@ -2147,17 +2655,48 @@ where
     }
 
     /// Executes `query` using the reader/writer belonging to the calling `Backend` struct.
+    ///
+    /// If cancelling queries is enabled, execution will be interrupted by a cancel request for
+    /// this connection, after which the upstream connection is reset before running the next
+    /// statement.
+    pub async fn query<'a>(&'a mut self, query: &'a str) -> Result<QueryResult<'a, DB>, DB::Error> {
+        self.discard_interrupted_upstream().await?;
+        match self.query_canceller() {
+            Some(canceller) => canceller.run(self.query_inner(query)).await,
+            None => self.query_inner(query).await,
+        }
+    }
+
     #[instrument(skip_all)]
     #[inline]
-    pub async fn query<'a>(&'a mut self, query: &'a str) -> Result<QueryResult<'a, DB>, DB::Error> {
+    async fn query_inner<'a>(
+        &'a mut self,
+        query: &'a str,
+    ) -> Result<QueryResult<'a, DB>, DB::Error> {
+        let comments = StatementComments::extract(self.settings.dialect, query);
+        link_trace_context(&comments);
         let mut event = QueryExecutionEvent::new(EventType::Query);
+        event.query_tag = self.settings.query_tag(&comments);
+        let routing_directive = routing_directive(&comments);
         let query_log_sender = self.query_log_sender.clone();
-        let slowlog = self.settings.slowlog;
+        let slowlog = self
+            .settings
+            .slowlog
+            .then_some(self.settings.slow_query_threshold);
 
         let parse_result = {
             let _t = event.start_parse_timer();
             self.parse_query(query)
         };
+        if self.upstream_pool.is_some() && !parse_result.as_ref().map_or(false, is_session_neutral)
+        {
+            self.upstream_pinned = true;
+        }
+        // Reads might be served by ReadySet without ever touching the upstream database, so we only
+        // check out a pooled upstream connection for them once we know they won't be
+        if !matches!(parse_result, Ok(SqlQuery::Select(_))) {
+            self.checkout_upstream().await?;
+        }
 
         let result = match parse_result {
             // Parse error, but no fallback exists
@@ -2194,9 +2733,11 @@ where
                 }
                 fallback_res
             }
-            // Check for COMMIT+ROLLBACK before we check whether we should proxy, since we need to
-            // know when a COMMIT or ROLLBACK happens so we can leave `ProxyState::InTransaction`
-            Ok(parsed_query @ (SqlQuery::Commit(_) | SqlQuery::Rollback(_))) => {
+            // Check for transaction control statements before we check whether we should proxy,
+            // since we need to know when a COMMIT or ROLLBACK happens so we can leave
+            // `ProxyState::InTransaction`, and all of them must go to the same upstream connection
+            // as the rest of the transaction
+            Ok(parsed_query) if parsed_query.is_transaction_control() => {
                 Self::query_adhoc_non_select(
                     &mut self.noria,
                     self.upstream.as_mut(),
@@ -2231,6 +2772,7 @@ where
             Ok(ref parsed_query) if Handler::requires_fallback(parsed_query) => {
                 if self.has_fallback() {
                     // Query requires a fallback and we can send it to fallback
+                    self.checkout_upstream().await?;
                     Self::query_fallback(self.upstream.as_mut(), query, &mut event).await
                 } else {
                     // Query requires a fallback, but none is available
@@ -2239,29 +2781,78 @@ where
                         .map_err(Into::into)
                 }
             }
+            Ok(SqlQuery::Select(_))
+                if routing_directive == Some(RoutingDirective::Proxy) && self.has_fallback() =>
+            {
+                self.checkout_upstream().await?;
+                Self::query_fallback(self.upstream.as_mut(), query, &mut event).await
+            }
             Ok(SqlQuery::Select(stmt)) => {
                 let mut view_request = ViewCreateRequest::new(
                     stmt.clone(),
                     self.noria.schema_search_path().to_owned(),
                 );
                 let (noria_should_try, status) = self.noria_should_try_select(&mut view_request);
+                // If the query is cached, try ReadySet without checking out a pooled upstream
+                // connection, and only check one out if we need to fall back
+                let cached = status
+                    .as_ref()
+                    .map_or(false, |s| s.migration_state == MigrationState::Successful);
+                let always = status.as_ref().map_or(false, |s| s.always);
+                if !(noria_should_try && cached) {
+                    self.checkout_upstream().await?;
+                }
                 if noria_should_try {
                     event.sql_type = SqlQueryType::Read;
                     if self.settings.query_log_ad_hoc_queries {
                         event.query = Some(Arc::new(SqlQuery::Select(stmt.clone())));
                     }
-                    Self::query_adhoc_select(
-                        &mut self.noria,
-                        self.upstream.as_mut(),
-                        &self.settings,
-                        &mut self.state,
-                        query,
-                        stmt,
-                        &view_request,
-                        status,
-                        &mut event,
-                    )
-                    .await
+                    if self.upstream.is_some() || self.upstream_pool.is_none() {
+                        Self::query_adhoc_select(
+                            &mut self.noria,
+                            self.upstream.as_mut(),
+                            &self.settings,
+                            &mut self.state,
+                            query,
+                            stmt,
+                            &view_request,
+                            status,
+                            routing_directive == Some(RoutingDirective::Cache),
+                            &mut event,
+                        )
+                        .await
+                    } else {
+                        let res = Self::query_adhoc_select(
+                            &mut self.noria,
+                            None,
+                            &self.settings,
+                            &mut self.state,
+                            query,
+                            stmt,
+                            &view_request,
+                            status,
+                            routing_directive == Some(RoutingDirective::Cache),
+                            &mut event,
+                        )
+                        .await;
+                        match res {
+                            Err(_) if !always => {
+                                Self::checkout_upstream_into(
+                                    &mut self.upstream,
+                                    &mut self.upstream_permit,
+                                    self.upstream_pool.as_deref(),
+                                    self.cancel_registration.as_ref(),
+                                )
+                                .await?;
+                                event.destination = Some(QueryDestination::ReadysetThenUpstream);
+                                #[allow(clippy::unwrap_used)] // Just checked out
+                                let upstream = self.upstream.as_mut().unwrap();
+                                let _t = event.start_upstream_timer();
+                                upstream.query(query).await.map(QueryResult::Upstream)
+                            }
+                            res => res,
+                        }
+                    }
                 } else {
                     Self::query_fallback(self.upstream.as_mut(), query, &mut event).await
                 }
@@ -2292,6 +2883,13 @@ where
                 .unwrap_or_default(),
         });
 
+        let is_read = event.sql_type == SqlQueryType::Read;
+        record_connection_stats(
+            &self.connection_stats,
+            self.cache_stats.as_deref(),
+            &event,
+            is_read,
+        );
         log_query(query_log_sender.as_ref(), event, slowlog);
 
         result
@@ -2299,7 +2897,30 @@ where
 
     /// Whether or not we have fallback enabled.
     pub fn has_fallback(&self) -> bool {
-        self.upstream.is_some()
+        self.upstream.is_some() || self.upstream_pool.is_some()
+    }
+
+    /// Returns a mutable reference to the upstream database connection, if we have one, for
+    /// protocol features that bypass query handling entirely and are proxied directly to the
+    /// upstream.
+    pub fn upstream_mut(&mut self) -> Option<&mut DB> {
+        self.upstream.as_mut()
+    }
+
+    /// Share upstream connections with other clients through `pool`, releasing this backend's
+    /// upstream connection back into the pool after each statement and when the backend is dropped,
+    /// rather than holding it for the lifetime of the connection.
+    ///
+    /// `permit` is the permit for the upstream connection this backend was built with, if that
+    /// connection was checked out of `pool`.
+    pub fn with_upstream_pool(
+        mut self,
+        pool: Arc<UpstreamPool<DB>>,
+        permit: Option<OwnedSemaphorePermit>,
+    ) -> Self {
+        self.upstream_pool = Some(pool);
+        self.upstream_permit = permit;
+        self
     }
 
     /// If we are using fallback, this will return the database that was in the original connection
@@ -2335,6 +2956,69 @@ where
     pub fn does_require_authentication(&self) -> bool {
         self.settings.require_authentication
     }
+
+    /// Returns true if clients may negotiate compression of the wire protocol
+    pub fn protocol_compression_enabled(&self) -> bool {
+        self.settings.enable_protocol_compression
+    }
+
+    /// Returns how long the connection handler should allow this backend's connection to be idle
+    /// before closing it, if ever
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.settings.idle_timeout
+    }
+
+    /// Wrap the stream of this backend's client connection so that it closes when the adapter
+    /// shuts down (see [`ConnectionDrain`]), or when the connection's user is drained with
+    /// [`CancelRegistry::drain_user`], even if the connection is idle
+    pub fn drainable_stream<S>(&self, inner: S) -> DrainableStream<S> {
+        match self.query_canceller() {
+            Some(canceller) => self
+                .settings
+                .connection_drain
+                .stream_until(inner, canceller.drained()),
+            None => self.settings.connection_drain.stream(inner),
+        }
+    }
+}
+
+/// Extract the [`RoutingDirective`] from the comments of a query, if any
+fn routing_directive(comments: &StatementComments) -> Option<RoutingDirective> {
+    let directive = comments.directive("readyset")?;
+    if directive.eq_ignore_ascii_case("proxy") {
+        Some(RoutingDirective::Proxy)
+    } else if directive.eq_ignore_ascii_case("cache") {
+        Some(RoutingDirective::Cache)
+    } else {
+        // Logged at debug, since the directive comes from the client, and may be repeated on
+        // every query it runs
+        debug!(%directive, "Ignoring unknown routing directive");
+        None
+    }
+}
+
+/// If the current span is enabled and a query's comments carry a W3C trace context in a
+/// `/* traceparent: ... */` comment, make the current span a child of that trace context so that
+/// traces started by the client link up with the adapter's spans
+fn link_trace_context(comments: &StatementComments) {
+    let mut span = Span::current();
+    if span.is_disabled() {
+        return;
+    }
+    if let Some(ctx) = trace_context(comments) {
+        ctx.set_spans_parent(&mut span);
+    }
+}
+
+/// Extract the W3C trace context from the `traceparent` directive in the comments of a query, if
+/// any
+fn trace_context(comments: &StatementComments) -> Option<RequestContext> {
+    let traceparent = comments.directive("traceparent")?;
+    let ctx = RequestContext::from_traceparent(traceparent);
+    if ctx.is_none() {
+        warn!(%traceparent, "Ignoring invalid traceparent");
+    }
+    ctx
 }
 
 impl<DB, Handler> Drop for Backend<DB, Handler>
@@ -2343,24 +3027,121 @@ where
 {
     fn drop(&mut self) {
         metrics::decrement_gauge!(recorded::CONNECTED_CLIENTS, 1.0);
+
+        // Upstream connections left in an unknown state by an interrupted statement are closed
+        // rather than returned to the pool
+        if self.upstream_interrupted() {
+            return;
+        }
+        if let (Some(pool), Some(upstream), Some(permit)) = (
+            self.upstream_pool.take(),
+            self.upstream.take(),
+            self.upstream_permit.take(),
+        ) {
+            pool.release(
+                PooledUpstream { upstream, permit },
+                self.upstream_pinned,
+                self.state.proxy_state.in_transaction(),
+            );
+        }
     }
 }
 
+/// Returns true if running `query` can't change the state of the upstream session it's proxied to,
+/// so the upstream connection can still be shared with other clients through an [`UpstreamPool`]
+/// afterwards. Statements which start or end transactions are neutral since connections are
+/// pinned to their client for the duration of a transaction anyway.
+///
+/// Writes are never neutral, since they set session state such as `LAST_INSERT_ID()` or
+/// `lastval()` which the client may read afterwards. Neither are `SELECT`s which call functions or
+/// reference variables, since those may have side effects on the session (such as `set_config`,
+/// `GET_LOCK` or `pg_advisory_lock`) or read state left by previous statements (such as
+/// `FOUND_ROWS()` or `ROW_COUNT()`).
+fn is_session_neutral(query: &SqlQuery) -> bool {
+    /// Visitor which fails if it finds a call to a function other than a built-in aggregate, or a
+    /// reference to a variable
+    struct SessionNeutralVisitor;
+
+    impl<'ast> Visitor<'ast> for SessionNeutralVisitor {
+        type Error = ();
+
+        fn visit_function_expr(
+            &mut self,
+            function_expr: &'ast FunctionExpr,
+        ) -> Result<(), Self::Error> {
+            if matches!(function_expr, FunctionExpr::Call { .. }) {
+                return Err(());
+            }
+            walk_function_expr(self, function_expr)
+        }
+
+        fn visit_variable(&mut self, _variable: &'ast Variable) -> Result<(), Self::Error> {
+            Err(())
+        }
+    }
+
+    match query {
+        SqlQuery::Select(stmt) => SessionNeutralVisitor.visit_select_statement(stmt).is_ok(),
+        SqlQuery::CompoundSelect(stmt) => SessionNeutralVisitor
+            .visit_compound_select_statement(stmt)
+            .is_ok(),
+        SqlQuery::Show(_)
+        | SqlQuery::Explain(_)
+        | SqlQuery::CreateCache(_)
+        | SqlQuery::DropCache(_)
+        | SqlQuery::DropAllCaches(_)
+        | SqlQuery::StartTransaction(_)
+        | SqlQuery::Commit(_)
+        | SqlQuery::Rollback(_) => true,
+        _ => false,
+    }
+}
+
+/// Records a query (or execution of a prepared statement) which was served on a connection in that
+/// connection's [`ConnectionStats`], and, if it was a read, in the adapter-wide `cache_stats`.
+/// Reads which were successfully served by ReadySet are counted as cache hits.
+fn record_connection_stats(
+    stats: &ConnectionStats,
+    cache_stats: Option<&CacheHitCounter>,
+    event: &QueryExecutionEvent,
+    is_read: bool,
+) {
+    let cache_hit = is_read
+        && event.destination == Some(QueryDestination::Readyset)
+        && event.noria_error.is_none();
+    stats.record_query(cache_hit);
+    if is_read {
+        if let Some(cache_stats) = cache_stats {
+            cache_stats.record_read(cache_hit);
+        }
+    }
+}
+
+/// Returns true if the query recorded in `event` took longer than `threshold` to execute, either
+/// against ReadySet or against the upstream database
+fn is_slow_query(event: &QueryExecutionEvent, threshold: Duration) -> bool {
+    event.execution_duration() > threshold
+}
+
 /// Offloads recording query metrics to a separate thread. Sends a
 /// message over a mpsc channel.
+///
+/// If `slowlog` is set, also logs the query if it was slower than the given threshold.
 fn log_query(
     sender: Option<&UnboundedSender<QueryExecutionEvent>>,
     event: QueryExecutionEvent,
-    slowlog: bool,
+    slowlog: Option<Duration>,
 ) {
-    const SLOW_DURATION: std::time::Duration = std::time::Duration::from_millis(5);
-
-    if slowlog
-        && (event.upstream_duration.unwrap_or_default() > SLOW_DURATION
-            || event.readyset_duration.unwrap_or_default() > SLOW_DURATION)
-    {
+    if let Some(threshold) = slowlog.filter(|threshold| is_slow_query(&event, *threshold)) {
         if let Some(query) = &event.query {
-            warn!(query = %Sensitive(&query), readyset_time = ?event.readyset_duration, upstream_time = ?event.upstream_duration, "slow query");
+            warn!(
+                query = %Sensitive(&query),
+                duration = ?event.execution_duration(),
+                ?threshold,
+                readyset_time = ?event.readyset_duration,
+                upstream_time = ?event.upstream_duration,
+                "slow query"
+            );
         }
     }
 
@@ -2380,3 +3161,79 @@ fn readyset_version() -> ReadySetResult<noria_connector::QueryResult<'static>> {
             .collect(),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_query_threshold() {
+        let mut event = QueryExecutionEvent::new(EventType::Query);
+        event.readyset_duration = Some(Duration::from_millis(20));
+        assert!(is_slow_query(&event, DEFAULT_SLOW_QUERY_THRESHOLD));
+        assert!(!is_slow_query(&event, Duration::from_millis(100)));
+
+        event.readyset_duration = None;
+        event.upstream_duration = Some(Duration::from_millis(150));
+        assert!(is_slow_query(&event, Duration::from_millis(100)));
+
+        event.readyset_duration = Some(Duration::from_millis(20));
+        assert_eq!(event.execution_duration(), Duration::from_millis(150));
+    }
+
+    #[test]
+    fn trace_context_from_comment() {
+        let traceparent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+        let trace_context =
+            |dialect, query: &str| trace_context(&StatementComments::extract(dialect, query));
+        let ctx = trace_context(
+            Dialect::PostgreSQL,
+            &format!("/* traceparent: {traceparent} */ SELECT * FROM t"),
+        )
+        .unwrap();
+        assert_eq!(ctx, RequestContext::from_traceparent(traceparent).unwrap());
+
+        assert!(trace_context(Dialect::MySQL, "SELECT * FROM t").is_none());
+        assert!(trace_context(Dialect::MySQL, "SELECT * FROM t /* traceparent: nope */").is_none());
+    }
+
+    #[test]
+    fn routing_directive_from_comment() {
+        let routing_directive =
+            |query: &str| routing_directive(&StatementComments::extract(Dialect::MySQL, query));
+        assert_eq!(
+            routing_directive("/* readyset: proxy */ SELECT * FROM t"),
+            Some(RoutingDirective::Proxy)
+        );
+        assert_eq!(
+            routing_directive("SELECT * FROM t /* readyset: CACHE */"),
+            Some(RoutingDirective::Cache)
+        );
+        assert_eq!(
+            routing_directive("/* readyset: upstream */ SELECT * FROM t"),
+            None
+        );
+        assert_eq!(routing_directive("SELECT * FROM t"), None);
+    }
+
+    #[test]
+    fn session_neutral_statements() {
+        let neutral = |dialect, query: &str| {
+            is_session_neutral(&nom_sql::parse_query(dialect, query).unwrap())
+        };
+        assert!(neutral(Dialect::MySQL, "SELECT * FROM t"));
+        assert!(neutral(Dialect::MySQL, "SELECT count(*) FROM t"));
+        assert!(neutral(Dialect::MySQL, "BEGIN"));
+
+        assert!(!neutral(Dialect::MySQL, "INSERT INTO t VALUES (1)"));
+        assert!(!neutral(Dialect::MySQL, "UPDATE t SET x = 1"));
+        assert!(!neutral(Dialect::MySQL, "SELECT LAST_INSERT_ID()"));
+        assert!(!neutral(Dialect::MySQL, "SELECT GET_LOCK('l', 1)"));
+        assert!(!neutral(Dialect::MySQL, "SELECT @x"));
+        assert!(!neutral(
+            Dialect::PostgreSQL,
+            "SELECT set_config('a.b', 'c', false)"
+        ));
+        assert!(!neutral(Dialect::PostgreSQL, "SELECT lastval()"));
+    }
+}
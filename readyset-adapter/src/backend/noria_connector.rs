@@ -31,7 +31,7 @@ use tracing::instrument;
 
 use crate::backend::SelectSchema;
 use crate::rewrite::{self, ProcessedQueryParams};
-use crate::utils;
+use crate::{lock_contention, utils};
 
 type StatementID = u32;
 
@@ -314,9 +314,7 @@ impl ViewCache {
             .entry(view_request.clone())
             .or_insert_with(|| name.clone());
         tokio::task::block_in_place(move || {
-            self.global
-                .write()
-                .unwrap()
+            lock_contention::write("query_cache", &self.global)
                 .entry(view_request)
                 .or_insert_with(|| name.clone());
         });
@@ -330,7 +328,8 @@ impl ViewCache {
             return Some(name.clone());
         } else {
             // Didn't find it in local, so let's check global.
-            let gc = tokio::task::block_in_place(|| self.global.read().unwrap());
+            let gc =
+                tokio::task::block_in_place(|| lock_contention::read("query_cache", &self.global));
             gc.get(view_request).cloned()
         };
 
@@ -345,7 +344,7 @@ impl ViewCache {
     pub fn remove_statement(&mut self, name: &Relation) {
         self.local.retain(|_, v| v != name);
         tokio::task::block_in_place(|| {
-            self.global.write().unwrap().retain(|_, v| v != name);
+            lock_contention::write("query_cache", &self.global).retain(|_, v| v != name);
         });
     }
 
@@ -353,7 +352,7 @@ impl ViewCache {
     fn clear(&mut self) {
         self.local.clear();
         tokio::task::block_in_place(|| {
-            self.global.write().unwrap().clear();
+            lock_contention::write("query_cache", &self.global).clear();
         })
     }
 
@@ -366,9 +365,7 @@ impl ViewCache {
             .map(|(v, _)| v.clone())
             .or_else(|| {
                 tokio::task::block_in_place(|| {
-                    self.global
-                        .read()
-                        .unwrap()
+                    lock_contention::read("query_cache", &self.global)
                         .iter()
                         .find(|(_, n)| *n == name)
                         .map(|(v, _)| v.clone())
@@ -534,6 +531,18 @@ impl NoriaConnector {
         Ok(QueryResult::Meta(vec![(label, graphviz).into()]))
     }
 
+    /// Returns a simplified graphviz representation of the dataflow plan for the cache with the
+    /// given name. Nodes are only described by their operator, so the plan never includes literal
+    /// values from the cached query.
+    pub(crate) async fn explain_cache(
+        &mut self,
+        name: &Relation,
+    ) -> ReadySetResult<QueryResult<'static>> {
+        let noria = &mut self.inner.get_mut()?.noria;
+        let plan = noria.graphviz_for_query(name.clone()).await?;
+        Ok(QueryResult::Meta(vec![("PLAN", plan).into()]))
+    }
+
     pub(crate) async fn verbose_views(
         &mut self,
         query_id: &Option<String>,
@@ -1136,6 +1145,10 @@ impl NoriaConnector {
         q: &InsertStatement,
         data: Vec<Vec<DfValue>>,
     ) -> ReadySetResult<QueryResult<'_>> {
+        if q.on_conflict.is_some() {
+            unsupported!("INSERT ... ON CONFLICT is not supported");
+        }
+
         let table = &q.table;
 
         // create a mutator if we don't have one for this table already
@@ -1175,11 +1188,10 @@ impl NoriaConnector {
 
         let ai = &mut self.auto_increments;
         tokio::task::block_in_place(|| {
-            let ai_lock = ai.read().unwrap();
+            let ai_lock = lock_contention::read("auto_increments", ai);
             if ai_lock.get(table).is_none() {
                 drop(ai_lock);
-                ai.write()
-                    .unwrap()
+                lock_contention::write("auto_increments", ai)
                     .entry(table.clone())
                     .or_insert_with(|| atomic::AtomicUsize::new(0));
             }
@@ -1187,7 +1199,7 @@ impl NoriaConnector {
         let mut buf = vec![vec![DfValue::None; schema.fields.len()]; data.len()];
         let mut first_inserted_id = None;
         tokio::task::block_in_place(|| -> ReadySetResult<_> {
-            let ai_lock = ai.read().unwrap();
+            let ai_lock = lock_contention::read("auto_increments", ai);
             let last_insert_id = &ai_lock[table];
 
             // handle default values
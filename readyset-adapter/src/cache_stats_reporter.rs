@@ -0,0 +1,184 @@
+//! Periodic telemetry reporting of how effectively ReadySet's caches are serving reads.
+//!
+//! The [`CacheStatsReporter`] reads cumulative statistics from a [`CacheStatsSource`], such as the
+//! [`CacheHitCounter`] shared by all of an adapter's connections, and reports the change in those
+//! statistics since its last report as a [`TelemetryEvent::CacheStats`] event.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use readyset_telemetry_reporter::{
+    PeriodicReport, ReporterResult as Result, Telemetry, TelemetryBuilder, TelemetryEvent,
+};
+use tokio::sync::Mutex;
+
+/// Cumulative statistics about the reads served by ReadySet's caches
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of reads which were served from a cache
+    pub hits: u64,
+    /// The number of reads which couldn't be served from a cache, and were either proxied to the
+    /// upstream database or failed
+    pub misses: u64,
+    /// The number of entries evicted from the cache, if the source tracks evictions
+    pub evictions: Option<u64>,
+}
+
+/// A source of cumulative [`CacheStats`], read by a [`CacheStatsReporter`]
+pub trait CacheStatsSource: Send + Sync {
+    fn cache_stats(&self) -> CacheStats;
+}
+
+/// Counts the cache hits and misses of the reads served by all of an adapter's connections.
+///
+/// Backends record their reads in the counter passed to
+/// [`BackendBuilder::cache_stats`](crate::BackendBuilder::cache_stats).
+#[derive(Debug, Default)]
+pub struct CacheHitCounter {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheHitCounter {
+    /// Record that a read was served, and whether it was served from a ReadySet cache
+    pub fn record_read(&self, cache_hit: bool) {
+        if cache_hit {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl CacheStatsSource for CacheHitCounter {
+    fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: None,
+        }
+    }
+}
+
+pub struct CacheStatsReporter {
+    source: Arc<dyn CacheStatsSource>,
+    /// The statistics read from `source` by the previous report
+    last_reported: Mutex<CacheStats>,
+}
+
+impl CacheStatsReporter {
+    pub fn new(source: Arc<dyn CacheStatsSource>) -> Self {
+        Self {
+            source,
+            last_reported: Mutex::new(CacheStats::default()),
+        }
+    }
+}
+
+#[async_trait]
+impl PeriodicReport for CacheStatsReporter {
+    fn name(&self) -> &str {
+        "cache-stats"
+    }
+
+    /// Reports the cache hits, misses and evictions since the previous report, or nothing if
+    /// there weren't any
+    async fn report(&self) -> Result<Vec<(TelemetryEvent, Telemetry)>> {
+        let stats = self.source.cache_stats();
+        let mut last_reported = self.last_reported.lock().await;
+        let hits = stats.hits.saturating_sub(last_reported.hits);
+        let misses = stats.misses.saturating_sub(last_reported.misses);
+        let evictions = stats
+            .evictions
+            .map(|evictions| evictions.saturating_sub(last_reported.evictions.unwrap_or(0)));
+        *last_reported = stats;
+
+        if hits == 0 && misses == 0 && evictions.unwrap_or(0) == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut telemetry = TelemetryBuilder::new();
+        telemetry.cache_hits(hits).cache_misses(misses);
+        if let Some(evictions) = evictions {
+            telemetry.cache_evictions(evictions);
+        }
+        Ok(vec![(TelemetryEvent::CacheStats, telemetry.build())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_hits_and_misses_since_last_report() {
+        let counter = Arc::new(CacheHitCounter::default());
+        let reporter = CacheStatsReporter::new(counter.clone());
+
+        counter.record_read(true);
+        counter.record_read(true);
+        counter.record_read(false);
+        assert_eq!(
+            reporter.report().await.unwrap(),
+            vec![(
+                TelemetryEvent::CacheStats,
+                TelemetryBuilder::new()
+                    .cache_hits(2u64)
+                    .cache_misses(1u64)
+                    .build()
+            )]
+        );
+
+        counter.record_read(false);
+        assert_eq!(
+            reporter.report().await.unwrap(),
+            vec![(
+                TelemetryEvent::CacheStats,
+                TelemetryBuilder::new()
+                    .cache_hits(0u64)
+                    .cache_misses(1u64)
+                    .build()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_nothing_while_idle() {
+        let counter = Arc::new(CacheHitCounter::default());
+        let reporter = CacheStatsReporter::new(counter.clone());
+        assert!(reporter.report().await.unwrap().is_empty());
+
+        counter.record_read(true);
+        assert_eq!(reporter.report().await.unwrap().len(), 1);
+        assert!(reporter.report().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reports_evictions_from_source() {
+        struct Evicting;
+
+        impl CacheStatsSource for Evicting {
+            fn cache_stats(&self) -> CacheStats {
+                CacheStats {
+                    hits: 10,
+                    misses: 5,
+                    evictions: Some(3),
+                }
+            }
+        }
+
+        let reporter = CacheStatsReporter::new(Arc::new(Evicting));
+        assert_eq!(
+            reporter.report().await.unwrap(),
+            vec![(
+                TelemetryEvent::CacheStats,
+                TelemetryBuilder::new()
+                    .cache_hits(10u64)
+                    .cache_misses(5u64)
+                    .cache_evictions(3u64)
+                    .build()
+            )]
+        );
+    }
+}
@@ -0,0 +1,453 @@
+//! Support for cancelling a query running on one client connection from another connection.
+//!
+//! Clients cancel queries out-of-band: PostgreSQL clients open a new connection and send a
+//! `CancelRequest` containing the process ID and secret key they were sent at startup, while
+//! MySQL clients run `KILL QUERY <connection id>` (or send `COM_PROCESS_KILL`) on a second
+//! connection. To support this, every [`Backend`](crate::Backend) built with a
+//! [`CancelRegistry`] registers itself under a [`CancelKey`] for the lifetime of the connection,
+//! which the backend handling the cancel request then uses to look up the connection to cancel.
+//! Every request must be [authorized](CancelAuth), either with the connection's secret or by
+//! coming from a connection authenticated as the same user.
+//!
+//! Cancelling a connection interrupts the query currently in flight on that connection, if any,
+//! and makes a best-effort attempt to cancel the query on the upstream database as well. Since the
+//! connection ids sent to clients are local to ReadySet, the query is cancelled on the upstream
+//! using the id of the connection's own upstream connection, which is refreshed whenever that
+//! connection changes. Since interrupting a query may leave the upstream connection part-way
+//! through sending a request or reading its response, a connection whose query was interrupted
+//! discards its upstream connection before running anything else on it.
+//!
+//! The registry also records the user each connection authenticated as, which allows an
+//! administrator to [drain](CancelRegistry::drain_user) all the connections belonging to a single
+//! user. Draining a connection cancels its in-flight query, if any, and causes it and all
+//! subsequent queries on the connection to fail with [`ReadySetError::ConnectionDrained`], after
+//! which the connection is closed. Idle connections are closed straight away, by failing the
+//! read waiting for their next statement (see [`Backend::drainable_stream`]).
+//!
+//! [`Backend::drainable_stream`]: crate::Backend::drainable_stream
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use readyset_errors::ReadySetError;
+use readyset_tracing::{debug, warn};
+use tokio::sync::Notify;
+
+use crate::upstream_database::UpstreamQueryCanceller;
+
+/// The key under which a connection is registered in a [`CancelRegistry`], which is sent to the
+/// client so it can later cancel queries on that connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CancelKey {
+    /// An identifier for the connection, unique among all connections currently registered with
+    /// the same [`CancelRegistry`]
+    pub connection_id: u32,
+    /// A randomly generated secret which must be provided along with the connection id to cancel
+    /// queries, for protocols which support it
+    pub secret: u32,
+}
+
+/// How a request to cancel a query on a connection in a [`CancelRegistry`] shows that it's allowed
+/// to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelAuth<'a> {
+    /// The secret the connection was registered with, which PostgreSQL clients send in their
+    /// cancel requests
+    Secret(u32),
+    /// The user the requesting connection authenticated as, which must be the same user that the
+    /// connection being cancelled authenticated as. MySQL's `KILL` doesn't include a secret, so
+    /// (like MySQL itself) only a user's own connections can be killed this way.
+    User(&'a str),
+}
+
+/// A registry of all the client connections whose queries can currently be cancelled, shared
+/// between all the connections to a single adapter
+#[derive(Default)]
+pub struct CancelRegistry {
+    next_connection_id: AtomicU32,
+    connections: DashMap<u32, Arc<QueryCanceller>>,
+}
+
+impl CancelRegistry {
+    /// Create a new, empty, [`CancelRegistry`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new connection, returning a [`CancelRegistration`] which removes the connection
+    /// from the registry when dropped
+    pub(crate) fn register(self: &Arc<Self>) -> CancelRegistration {
+        let canceller = Arc::new(QueryCanceller::default());
+        let connection_id = loop {
+            // Connection ids are never 0, and may wrap around on very long-lived adapters, so
+            // skip any which are still in use
+            let id = self
+                .next_connection_id
+                .fetch_add(1, Ordering::Relaxed)
+                .wrapping_add(1);
+            if id == 0 {
+                continue;
+            }
+            if let Entry::Vacant(entry) = self.connections.entry(id) {
+                entry.insert(canceller.clone());
+                break id;
+            }
+        };
+
+        CancelRegistration {
+            registry: self.clone(),
+            key: CancelKey {
+                connection_id,
+                secret: canceller.secret,
+            },
+            canceller,
+        }
+    }
+
+    /// Look up the connection with the given id, if `auth` allows cancelling its queries
+    fn authorized(&self, connection_id: u32, auth: CancelAuth<'_>) -> Option<Arc<QueryCanceller>> {
+        let canceller = match self.connections.get(&connection_id) {
+            Some(canceller) => canceller.value().clone(),
+            None => {
+                debug!(%connection_id, "Received cancel request for unknown connection");
+                return None;
+            }
+        };
+        let authorized = match auth {
+            CancelAuth::Secret(secret) => secret == canceller.secret,
+            CancelAuth::User(user) => canceller.user.lock().as_deref() == Some(user),
+        };
+        if !authorized {
+            debug!(%connection_id, "Received unauthorized cancel request");
+            return None;
+        }
+        Some(canceller)
+    }
+
+    /// Cancel the query currently running on the connection with the given id, if any.
+    ///
+    /// Returns `true` if a connection with the given id was found, and `auth` allows cancelling
+    /// its queries
+    pub fn cancel(&self, connection_id: u32, auth: CancelAuth<'_>) -> bool {
+        match self.authorized(connection_id, auth) {
+            Some(canceller) => {
+                debug!(%connection_id, "Cancelling query");
+                canceller.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Close the connection with the given id, cancelling the query currently running on it, if
+    /// any. The connection fails its current and all subsequent queries, and is closed when it
+    /// next sends a response.
+    ///
+    /// Returns `true` if a connection with the given id was found, and `auth` allows cancelling
+    /// its queries
+    pub fn kill(&self, connection_id: u32, auth: CancelAuth<'_>) -> bool {
+        match self.authorized(connection_id, auth) {
+            Some(canceller) => {
+                debug!(%connection_id, "Killing connection");
+                canceller.drained.store(true, Ordering::Release);
+                canceller.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drain all the connections which authenticated as the given user, cancelling any queries
+    /// they are running. Connections which are running a query are closed once the query's error
+    /// is sent to the client, and idle connections are closed straight away.
+    ///
+    /// Returns the number of connections drained
+    pub fn drain_user(&self, user: &str) -> usize {
+        let mut drained = 0;
+        for canceller in self.connections.iter() {
+            if canceller.user.lock().as_deref() == Some(user) {
+                canceller.drained.store(true, Ordering::Release);
+                canceller.cancel();
+                drained += 1;
+            }
+        }
+        debug!(%user, %drained, "Drained connections");
+        drained
+    }
+}
+
+/// A connection's registration in a [`CancelRegistry`], which deregisters the connection when
+/// dropped
+pub(crate) struct CancelRegistration {
+    registry: Arc<CancelRegistry>,
+    key: CancelKey,
+    canceller: Arc<QueryCanceller>,
+}
+
+impl CancelRegistration {
+    pub(crate) fn key(&self) -> CancelKey {
+        self.key
+    }
+
+    pub(crate) fn registry(&self) -> &CancelRegistry {
+        &self.registry
+    }
+
+    /// Returns the user the connection authenticated as, if it has
+    pub(crate) fn user(&self) -> Option<String> {
+        self.canceller.user.lock().clone()
+    }
+
+    pub(crate) fn canceller(&self) -> Arc<QueryCanceller> {
+        self.canceller.clone()
+    }
+
+    /// Record the user the connection authenticated as
+    pub(crate) fn set_user(&self, user: &str) {
+        *self.canceller.user.lock() = Some(user.to_owned());
+    }
+}
+
+impl Drop for CancelRegistration {
+    fn drop(&mut self) {
+        self.registry.connections.remove(&self.key.connection_id);
+    }
+}
+
+/// Cancels the query in flight on a single connection
+pub(crate) struct QueryCanceller {
+    secret: u32,
+    notify: Notify,
+    upstream: Mutex<Option<Arc<dyn UpstreamQueryCanceller>>>,
+    user: Mutex<Option<String>>,
+    drained: AtomicBool,
+    /// Set when a query was dropped part-way through by a cancellation, and cleared once the
+    /// upstream connection it may have been using has been discarded
+    interrupted: AtomicBool,
+}
+
+impl Default for QueryCanceller {
+    fn default() -> Self {
+        Self {
+            secret: rand::random(),
+            notify: Notify::new(),
+            upstream: Mutex::new(None),
+            user: Mutex::new(None),
+            drained: AtomicBool::new(false),
+            interrupted: AtomicBool::new(false),
+        }
+    }
+}
+
+impl QueryCanceller {
+    /// Set the handle used to cancel queries running on the connection's upstream database
+    pub(crate) fn set_upstream(&self, upstream: Option<Arc<dyn UpstreamQueryCanceller>>) {
+        *self.upstream.lock() = upstream;
+    }
+
+    fn cancel(&self) {
+        self.notify.notify_waiters();
+        if let Some(upstream) = self.upstream.lock().clone() {
+            tokio::spawn(async move {
+                if let Err(error) = upstream.cancel().await {
+                    warn!(%error, "Failed to cancel query on upstream database");
+                }
+            });
+        }
+    }
+
+    /// Returns [`ReadySetError::ConnectionDrained`] if the connection has been drained
+    pub(crate) fn check_drained(&self) -> Result<(), ReadySetError> {
+        if self.drained.load(Ordering::Acquire) {
+            Err(ReadySetError::ConnectionDrained {
+                user: self.user.lock().clone().unwrap_or_default(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns a future which completes once the connection has been drained, with the error to
+    /// report to the client
+    pub(crate) async fn drained(self: Arc<Self>) -> String {
+        loop {
+            // Construct the `Notified` future before checking, so that a drain which happens in
+            // between isn't missed
+            let notified = self.notify.notified();
+            if let Err(error) = self.check_drained() {
+                return error.to_string();
+            }
+            notified.await;
+        }
+    }
+
+    /// Returns true if a query on the connection was interrupted by a cancellation since the last
+    /// call to [`take_interrupted`](Self::take_interrupted)
+    pub(crate) fn interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::Acquire)
+    }
+
+    /// Returns true if a query on the connection was interrupted by a cancellation, and so the
+    /// connection's upstream connection must be discarded, clearing the flag
+    pub(crate) fn take_interrupted(&self) -> bool {
+        self.interrupted.swap(false, Ordering::AcqRel)
+    }
+
+    /// Run `fut` to completion, unless the connection is cancelled first, in which case `fut` is
+    /// dropped, the connection is marked as [interrupted](Self::take_interrupted), and
+    /// [`ReadySetError::QueryCancelled`] is returned. If the connection has been drained, `fut` is
+    /// not run (or is dropped) and [`ReadySetError::ConnectionDrained`] is returned instead.
+    pub(crate) async fn run<F, T, E>(&self, fut: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+        E: From<ReadySetError>,
+    {
+        // Construct the `Notified` future before starting the query, so that a cancellation which
+        // arrives before we first poll it isn't missed
+        let cancelled = self.notify.notified();
+        self.check_drained()?;
+        tokio::select! {
+            biased;
+            res = fut => res,
+            _ = cancelled => {
+                self.interrupted.store(true, Ordering::Release);
+                self.check_drained()?;
+                Err(ReadySetError::QueryCancelled.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn cancel_in_flight_query() {
+        let registry = Arc::new(CancelRegistry::new());
+        let registration = registry.register();
+        let key = registration.key();
+        let canceller = registration.canceller();
+
+        let query = tokio::spawn(async move {
+            canceller
+                .run(async {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    Ok::<_, ReadySetError>(())
+                })
+                .await
+        });
+        tokio::task::yield_now().await;
+
+        assert!(!registry.cancel(
+            key.connection_id,
+            CancelAuth::Secret(key.secret.wrapping_add(1))
+        ));
+        assert!(registry.cancel(key.connection_id, CancelAuth::Secret(key.secret)));
+        assert_eq!(
+            query.await.unwrap().unwrap_err(),
+            ReadySetError::QueryCancelled
+        );
+        let canceller = registration.canceller();
+        assert!(canceller.interrupted());
+        assert!(canceller.take_interrupted());
+        assert!(!canceller.interrupted());
+    }
+
+    #[tokio::test]
+    async fn cancel_without_query_does_not_affect_next_query() {
+        let registry = Arc::new(CancelRegistry::new());
+        let registration = registry.register();
+        let key = registration.key();
+        assert!(registry.cancel(key.connection_id, CancelAuth::Secret(key.secret)));
+
+        let res = registration
+            .canceller()
+            .run(async { Ok::<_, ReadySetError>(1) })
+            .await;
+        assert_eq!(res, Ok(1));
+        assert!(!registration.canceller().interrupted());
+    }
+
+    #[tokio::test]
+    async fn drain_only_matching_user() {
+        let registry = Arc::new(CancelRegistry::new());
+        let alice_1 = registry.register();
+        alice_1.set_user("alice");
+        let alice_2 = registry.register();
+        alice_2.set_user("alice");
+        let bob = registry.register();
+        bob.set_user("bob");
+
+        let canceller = alice_1.canceller();
+        let in_flight = tokio::spawn(async move {
+            canceller
+                .run(async {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    Ok::<_, ReadySetError>(())
+                })
+                .await
+        });
+        let idle = tokio::spawn(alice_2.canceller().drained());
+        tokio::task::yield_now().await;
+
+        assert_eq!(registry.drain_user("alice"), 2);
+        let drained = ReadySetError::ConnectionDrained {
+            user: "alice".into(),
+        };
+        assert_eq!(in_flight.await.unwrap().unwrap_err(), drained);
+        assert_eq!(idle.await.unwrap(), drained.to_string());
+        assert_eq!(
+            alice_2
+                .canceller()
+                .run(async { Ok::<_, ReadySetError>(1) })
+                .await,
+            Err(drained)
+        );
+
+        let res = bob
+            .canceller()
+            .run(async { Ok::<_, ReadySetError>(1) })
+            .await;
+        assert_eq!(res, Ok(1));
+        assert_eq!(registry.drain_user("carol"), 0);
+    }
+
+    #[test]
+    fn users_can_only_cancel_their_own_connections() {
+        let registry = Arc::new(CancelRegistry::new());
+        let alice = registry.register();
+        alice.set_user("alice");
+        let unauthenticated = registry.register();
+
+        let id = alice.key().connection_id;
+        assert!(!registry.cancel(id, CancelAuth::User("bob")));
+        assert!(!registry.kill(id, CancelAuth::User("bob")));
+        assert!(alice.canceller().check_drained().is_ok());
+        assert!(!registry.cancel(unauthenticated.key().connection_id, CancelAuth::User("")));
+
+        assert!(registry.cancel(id, CancelAuth::User("alice")));
+        assert!(registry.kill(id, CancelAuth::User("alice")));
+        assert!(alice.canceller().check_drained().is_err());
+    }
+
+    #[test]
+    fn deregister_on_drop() {
+        let registry = Arc::new(CancelRegistry::new());
+        let first = registry.register();
+        let second = registry.register();
+        assert_ne!(first.key().connection_id, second.key().connection_id);
+
+        let key = first.key();
+        drop(first);
+        assert!(!registry.cancel(key.connection_id, CancelAuth::Secret(key.secret)));
+        let key = second.key();
+        assert!(registry.cancel(key.connection_id, CancelAuth::Secret(key.secret)));
+    }
+}
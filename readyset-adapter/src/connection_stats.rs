@@ -0,0 +1,131 @@
+//! Per-connection statistics, exposed to clients via the `SHOW READYSET CONNECTION STATS` command.
+//!
+//! The query and cache hit counters are maintained by the [`Backend`](crate::Backend) itself,
+//! while the number of bytes sent is maintained by wrapping the client's socket in a
+//! [`ByteCountingStream`] when processing the connection.
+
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Counters tracking the activity of a single client connection
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    queries: AtomicU64,
+    cache_hits: AtomicU64,
+    bytes_sent: AtomicU64,
+}
+
+impl ConnectionStats {
+    /// Record that a query (or execution of a prepared statement) was served on this connection,
+    /// and whether it was served from a ReadySet cache
+    pub fn record_query(&self, cache_hit: bool) {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        if cache_hit {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Record that `n` bytes were sent to the client on this connection
+    pub fn record_bytes_sent(&self, n: u64) {
+        self.bytes_sent.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// The number of queries (and executions of prepared statements) served on this connection
+    pub fn queries(&self) -> u64 {
+        self.queries.load(Ordering::Relaxed)
+    }
+
+    /// The number of queries served on this connection which were read from a ReadySet cache
+    pub fn cache_hits(&self) -> u64 {
+        self.cache_hits.load(Ordering::Relaxed)
+    }
+
+    /// The number of bytes sent to the client on this connection
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+}
+
+/// A wrapper around an [`AsyncWrite`] (and, optionally, [`AsyncRead`]) which records the number of
+/// bytes written to it in a [`ConnectionStats`]
+pub struct ByteCountingStream<S> {
+    inner: S,
+    stats: Arc<ConnectionStats>,
+}
+
+impl<S> ByteCountingStream<S> {
+    /// Wrap the given stream, recording bytes written to it in `stats`
+    pub fn new(inner: S, stats: Arc<ConnectionStats>) -> Self {
+        Self { inner, stats }
+    }
+}
+
+impl<S> AsyncRead for ByteCountingStream<S>
+where
+    S: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S> AsyncWrite for ByteCountingStream<S>
+where
+    S: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let res = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = res {
+            self.stats.record_bytes_sent(n as u64);
+        }
+        res
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn counts_bytes_written() {
+        let stats = Arc::new(ConnectionStats::default());
+        let mut stream = ByteCountingStream::new(Vec::new(), stats.clone());
+        stream.write_all(b"hello").await.unwrap();
+        stream.write_all(b", world").await.unwrap();
+        assert_eq!(stats.bytes_sent(), 12);
+        assert_eq!(stream.inner, b"hello, world");
+    }
+
+    #[test]
+    fn counts_queries_and_cache_hits() {
+        let stats = ConnectionStats::default();
+        stats.record_query(true);
+        stats.record_query(false);
+        stats.record_query(true);
+        assert_eq!(stats.queries(), 3);
+        assert_eq!(stats.cache_hits(), 2);
+    }
+}
@@ -0,0 +1,317 @@
+//! Draining client connections when the adapter shuts down.
+//!
+//! Every client connection accepted by the adapter is tracked by a [`ConnectionDrain`] for as
+//! long as it's open, and its stream is wrapped in a [`DrainableStream`]. When the adapter starts
+//! shutting down it stops accepting connections and calls [`ConnectionDrain::drain`], which
+//! broadcasts the drain signal to every connection. A connection which is waiting for the client
+//! to send its next statement fails the read with [`io::ErrorKind::ConnectionAborted`], which the
+//! protocol servers handle by sending the client a shutdown error (`admin_shutdown` for
+//! PostgreSQL, `ER_SERVER_SHUTDOWN` for MySQL) and closing the connection. A connection which is
+//! executing a statement finishes it (and sends the results to the client) first. The adapter then
+//! [waits](ConnectionDrain::wait) for the outstanding connections to close before tearing down the
+//! runtime.
+//!
+//! Streams can also be [closed individually](ConnectionDrain::stream_until), which is used to close
+//! idle connections whose user is drained with
+//! [`CancelRegistry::drain_user`](crate::cancel::CancelRegistry::drain_user).
+
+use std::future::{self, Future};
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::{broadcast, Notify};
+use tokio::time::Instant;
+
+/// The reason reads fail with once the adapter starts shutting down
+const SHUTDOWN_REASON: &str = "Server shutdown in progress";
+
+#[derive(Debug)]
+struct Inner {
+    signal: broadcast::Sender<()>,
+    /// Set before the drain signal is broadcast, so that connections which subscribe after the
+    /// signal was sent still see it
+    draining: AtomicBool,
+    outstanding: AtomicUsize,
+    /// Notified whenever the last outstanding connection closes
+    closed: Notify,
+}
+
+/// Tracks the client connections open on an adapter, and signals them to close when the adapter
+/// shuts down. Cloning a `ConnectionDrain` returns a handle to the same set of connections.
+#[derive(Debug, Clone)]
+pub struct ConnectionDrain {
+    inner: Arc<Inner>,
+}
+
+impl Default for ConnectionDrain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConnectionDrain {
+    /// Create a new `ConnectionDrain` with no outstanding connections
+    pub fn new() -> Self {
+        let (signal, _) = broadcast::channel(1);
+        Self {
+            inner: Arc::new(Inner {
+                signal,
+                draining: AtomicBool::new(false),
+                outstanding: AtomicUsize::new(0),
+                closed: Notify::new(),
+            }),
+        }
+    }
+
+    /// Start tracking a new connection, which is considered outstanding until the returned
+    /// [`TrackedConnection`] is dropped
+    pub fn track(&self) -> TrackedConnection {
+        self.inner.outstanding.fetch_add(1, Ordering::AcqRel);
+        TrackedConnection {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Returns the number of connections which are currently open
+    pub fn outstanding(&self) -> usize {
+        self.inner.outstanding.load(Ordering::Acquire)
+    }
+
+    /// Returns true if [`drain`](Self::drain) has been called
+    pub fn is_draining(&self) -> bool {
+        self.inner.draining.load(Ordering::Acquire)
+    }
+
+    /// Signal all connections, including ones which haven't started reading from their streams
+    /// yet, to close once they've finished executing their current statement
+    pub fn drain(&self) {
+        self.inner.draining.store(true, Ordering::Release);
+        // An error here just means that no connections are currently subscribed
+        let _ = self.inner.signal.send(());
+    }
+
+    /// Wait up to `timeout` for all outstanding connections to close, returning the number of
+    /// connections which are still open
+    pub async fn wait(&self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        loop {
+            // Construct the `Notified` future before checking the count, so that the last
+            // connection closing in between isn't missed
+            let closed = self.inner.closed.notified();
+            let outstanding = self.outstanding();
+            if outstanding == 0 {
+                return 0;
+            }
+            tokio::select! {
+                _ = closed => {}
+                _ = tokio::time::sleep_until(deadline) => return self.outstanding(),
+            }
+        }
+    }
+
+    /// Wrap the stream of a client connection so that it closes when the connections are drained
+    pub fn stream<S>(&self, inner: S) -> DrainableStream<S> {
+        self.stream_until(inner, future::pending())
+    }
+
+    /// Wrap the stream of a client connection so that it closes when either the connections are
+    /// drained, or `closed` completes with the reason the connection is being closed
+    pub fn stream_until<S, F>(&self, inner: S, closed: F) -> DrainableStream<S>
+    where
+        F: Future<Output = String> + Send + Sync + 'static,
+    {
+        let mut signal = self.inner.signal.subscribe();
+        let inner_drain = self.inner.clone();
+        let shutdown = async move {
+            if !inner_drain.draining.load(Ordering::Acquire) {
+                // Either the signal is received, or the sender has been dropped; in both cases
+                // there's nothing else to wait for.
+                let _ = signal.recv().await;
+            }
+        };
+        DrainableStream {
+            inner,
+            drained: Box::pin(async move {
+                tokio::select! {
+                    _ = shutdown => SHUTDOWN_REASON.to_owned(),
+                    reason = closed => reason,
+                }
+            }),
+            reason: None,
+        }
+    }
+}
+
+/// A connection tracked by a [`ConnectionDrain`], which stops being counted as outstanding when
+/// dropped
+#[derive(Debug)]
+pub struct TrackedConnection {
+    inner: Arc<Inner>,
+}
+
+impl Drop for TrackedConnection {
+    fn drop(&mut self) {
+        if self.inner.outstanding.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.inner.closed.notify_waiters();
+        }
+    }
+}
+
+/// A wrapper around an [`AsyncRead`] (and, optionally, [`AsyncWrite`]) which fails reads that
+/// would wait for the client once its [`ConnectionDrain`] has been drained, or the connection has
+/// been [closed individually](ConnectionDrain::stream_until). The error's message is the reason
+/// the connection was closed.
+///
+/// Data already sent by the client is still returned, and writes are unaffected, so statements
+/// which are being executed when the drain starts run to completion.
+pub struct DrainableStream<S> {
+    inner: S,
+    /// Completes, with the reason the connection is being closed, once the drain signal has been
+    /// sent
+    drained: Pin<Box<dyn Future<Output = String> + Send + Sync>>,
+    reason: Option<String>,
+}
+
+impl<S> AsyncRead for DrainableStream<S>
+where
+    S: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if let Poll::Ready(res) = Pin::new(&mut this.inner).poll_read(cx, buf) {
+            return Poll::Ready(res);
+        }
+
+        if this.reason.is_none() {
+            if let Poll::Ready(reason) = this.drained.as_mut().poll(cx) {
+                this.reason = Some(reason);
+            }
+        }
+        match &this.reason {
+            Some(reason) => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                reason.clone(),
+            ))),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<S> AsyncWrite for DrainableStream<S>
+where
+    S: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn drain_closes_idle_connections() {
+        let drain = ConnectionDrain::new();
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut stream = drain.stream(server);
+
+        client.write_u8(1).await.unwrap();
+        assert_eq!(stream.read_u8().await.unwrap(), 1);
+
+        let reader = tokio::spawn(async move { stream.read_u8().await });
+        tokio::task::yield_now().await;
+        drain.drain();
+        assert_eq!(
+            reader.await.unwrap().unwrap_err().kind(),
+            io::ErrorKind::ConnectionAborted
+        );
+    }
+
+    #[tokio::test]
+    async fn buffered_data_is_read_before_closing() {
+        let drain = ConnectionDrain::new();
+        let (mut client, server) = tokio::io::duplex(64);
+        client.write_u8(1).await.unwrap();
+        drain.drain();
+
+        // Connections which start reading after the drain signal was sent still see it
+        let mut stream = drain.stream(server);
+        assert_eq!(stream.read_u8().await.unwrap(), 1);
+        assert_eq!(
+            stream.read_u8().await.unwrap_err().kind(),
+            io::ErrorKind::ConnectionAborted
+        );
+    }
+
+    #[tokio::test]
+    async fn close_individual_connection() {
+        let drain = ConnectionDrain::new();
+        let (_client, server) = tokio::io::duplex(64);
+        let (close, closed) = tokio::sync::oneshot::channel::<String>();
+        let mut stream = drain.stream_until(server, async move { closed.await.unwrap() });
+        let (_other_client, other_server) = tokio::io::duplex(64);
+        let mut other_stream = drain.stream(other_server);
+
+        let reader = tokio::spawn(async move { stream.read_u8().await });
+        let other_reader = tokio::spawn(async move { other_stream.read_u8().await });
+        tokio::task::yield_now().await;
+        close.send("closed by an administrator".to_owned()).unwrap();
+
+        let err = reader.await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+        assert_eq!(err.to_string(), "closed by an administrator");
+        assert!(!other_reader.is_finished());
+
+        drain.drain();
+        let err = other_reader.await.unwrap().unwrap_err();
+        assert_eq!(err.to_string(), SHUTDOWN_REASON);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_for_outstanding_connections() {
+        let drain = ConnectionDrain::new();
+        assert_eq!(drain.wait(Duration::from_secs(1)).await, 0);
+
+        let first = drain.track();
+        let second = drain.track();
+        assert_eq!(drain.outstanding(), 2);
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            drop(first);
+        });
+        assert_eq!(drain.wait(Duration::from_secs(5)).await, 1);
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            drop(second);
+        });
+        let start = Instant::now();
+        assert_eq!(drain.wait(Duration::from_secs(5)).await, 0);
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+}
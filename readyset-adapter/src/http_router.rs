@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::future::Future;
 use std::io;
 use std::net::SocketAddr;
@@ -8,20 +9,26 @@ use std::task::{Context, Poll};
 use anyhow::anyhow;
 use futures::TryFutureExt;
 use health_reporter::{HealthReporter as AdapterHealthReporter, State};
-use hyper::header::CONTENT_TYPE;
+use hyper::header::{ACCEPT, CONTENT_TYPE};
 use hyper::service::make_service_fn;
 use hyper::{self, Body, Method, Request, Response};
 use metrics_exporter_prometheus::PrometheusHandle;
 use readyset_client::query::DeniedQuery;
 use readyset_client_metrics::recorded;
 use readyset_sql_passes::anonymize::Anonymizer;
+use readyset_telemetry_reporter::PeriodicReporters;
 use stream_cancel::Valve;
 use tokio::net::TcpListener;
 use tokio::sync::mpsc::Sender;
 use tokio_stream::wrappers::TcpListenerStream;
 use tower::Service;
 
+use crate::cancel::CancelRegistry;
 use crate::query_status_cache::QueryStatusCache;
+use crate::readiness::Readiness;
+
+/// The content type of metrics rendered in the OpenMetrics text format
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
 
 /// Routes requests from an HTTP server to expose metrics data from the adapter.
 /// To see the supported http requests and their respective routing, see
@@ -31,7 +38,7 @@ pub struct NoriaAdapterHttpRouter {
     /// The address to attempt to listen on.
     pub listen_addr: SocketAddr,
     /// A reference to the QueryStatusCache that is in use by the adapter.
-    pub query_cache: &'static QueryStatusCache,
+    pub query_cache: Arc<QueryStatusCache>,
     /// A valve for the http stream to trigger closing.
     pub valve: Valve,
     /// Used to retrieve the current health of the adapter.
@@ -45,6 +52,16 @@ pub struct NoriaAdapterHttpRouter {
     /// Used to retrieve the prometheus scrape's render as a String when servicing
     /// HTTP requests on /metrics.
     pub prometheus_handle: Option<PrometheusHandle>,
+
+    /// Used to list and toggle the periodic telemetry reporters registered with the adapter.
+    /// `None` if telemetry reporting is disabled.
+    pub periodic_reporters: Option<PeriodicReporters>,
+
+    /// The registry of client connections to the adapter, used to drain connections on request.
+    pub cancel_registry: Option<Arc<CancelRegistry>>,
+
+    /// Used to determine whether the adapter is ready to serve client traffic.
+    pub readiness: Readiness,
 }
 
 impl NoriaAdapterHttpRouter {
@@ -91,11 +108,12 @@ impl Service<Request<Body>> for NoriaAdapterHttpRouter {
     ///
     /// ## Health Check
     ///
-    /// Get the health of the adapter. Return 200 code without a response body if the service is
-    /// considered healthy or return no response at all if the service is unhealthy.
+    /// Get the health of the adapter. Return 200 code if the service is considered alive (healthy,
+    /// degraded, or shutting down), and 500 otherwise.
     ///
     /// "Healthy" _only_ indicates that the HTTP router is active but no further checks are
-    /// performed.
+    /// performed. This is suitable for use as a liveness check; use `/ready` to determine whether
+    /// the adapter can serve traffic.
     ///
     /// * **URL**
     ///
@@ -113,6 +131,33 @@ impl Service<Request<Body>> for NoriaAdapterHttpRouter {
     ///
     ///   `curl -X GET <adapter>:<adapter-port>/health`
     ///
+    /// ## Readiness Check
+    ///
+    /// Whether the adapter is ready to serve client traffic: it has established a session with the
+    /// authority, confirmed that the ReadySet server's version is compatible with its own, and
+    /// successfully connected to the upstream database. Suitable for use as a readiness check.
+    ///
+    /// * **URL**
+    ///
+    ///   `/ready`
+    ///
+    /// * **Method:**
+    ///
+    ///   `GET`
+    ///
+    /// * **Success Response:**
+    ///
+    ///     * **Code:** 200 <br />
+    ///
+    /// * **Error Response:**
+    ///
+    ///     * **Code:** 503 Service Unavailable <br /> **Content:** The checks which haven't passed
+    ///       yet, or that the adapter is degraded or shutting down
+    ///
+    /// * **Sample Call:**
+    ///
+    ///   `curl -X GET <adapter>:<adapter-port>/ready`
+    ///
     /// ## Allow List
     ///
     /// List of SQL queries that will be handled by ReadySet as opposed to being passed through to
@@ -181,6 +226,12 @@ impl Service<Request<Body>> for NoriaAdapterHttpRouter {
     ///
     ///   `GET`
     ///
+    /// * **Headers:**
+    ///
+    ///   If the `Accept` header prefers `application/openmetrics-text` over `text/plain`, metrics
+    ///   are rendered in the OpenMetrics text format. Otherwise, they are rendered in the legacy
+    ///   Prometheus text format.
+    ///
     /// * **Success Response:**
     ///
     ///     * **Code:** 200 <br /> **Content:** `{ ... }`
@@ -205,6 +256,95 @@ impl Service<Request<Body>> for NoriaAdapterHttpRouter {
     ///
     ///   This endpoint is intended to be scraped by Prometheus. For almost all cases you want to
     /// query Prometheus directly to get metrics data.
+    ///
+    /// ## Periodic Telemetry Reporters
+    ///
+    /// List the periodic telemetry reporters registered with the adapter, and whether each is
+    /// currently enabled.
+    ///
+    /// * **URL**
+    ///
+    ///   `/telemetry/periodic-reporters`
+    ///
+    /// * **Method:**
+    ///
+    ///   `GET`
+    ///
+    /// * **Success Response:**
+    ///
+    ///     * **Code:** 200 <br /> **Content:** `[{ "name": ..., "enabled": ... }, ...]`
+    ///
+    /// * **Error Response:**
+    ///
+    ///     * **Code:** 404 Not Found <br /> **Content:** `"Telemetry reporting is disabled"`
+    ///
+    /// * **Sample Call:**
+    ///
+    ///   `curl -X GET <adapter>:<adapter-port>/telemetry/periodic-reporters`
+    ///
+    /// ## Toggle Periodic Telemetry Reporter
+    ///
+    /// Disable or re-enable a single periodic telemetry reporter, by name. Disabled reporters stay
+    /// registered, but are skipped until they are enabled again.
+    ///
+    /// * **URL**
+    ///
+    ///   `/telemetry/periodic-reporters/<name>/enable` or
+    ///   `/telemetry/periodic-reporters/<name>/disable`
+    ///
+    /// * **Method:**
+    ///
+    ///   `POST`
+    ///
+    /// * **Success Response:**
+    ///
+    ///     * **Code:** 200 <br />
+    ///
+    /// * **Error Response:**
+    ///
+    ///   Returns 404 if telemetry reporting is disabled, or if no reporter with the given name is
+    /// registered.
+    ///
+    ///     * **Code:** 404 Not Found <br />
+    ///
+    /// * **Sample Call:**
+    ///
+    ///   `curl -X POST <adapter>:<adapter-port>/telemetry/periodic-reporters/<name>/disable`
+    ///
+    /// ## Drain Connections
+    ///
+    /// Close all client connections which authenticated as the given user, without affecting
+    /// connections belonging to any other user. Queries in flight on the drained connections are
+    /// cancelled, and clients receive an error explaining that the connection was terminated by an
+    /// administrator.
+    ///
+    /// * **URL**
+    ///
+    ///   `/drain-connections`
+    ///
+    /// * **Method:**
+    ///
+    ///   `POST`
+    ///
+    /// * **Data Params:**
+    ///
+    ///   The name of the user whose connections should be drained, as plain text.
+    ///
+    /// * **Success Response:**
+    ///
+    ///     * **Code:** 200 <br /> **Content:** The number of connections drained
+    ///
+    /// * **Error Response:**
+    ///
+    ///     * **Code:** 400 Bad Request <br /> **Content:** `"a user name must be provided"`
+    ///
+    ///   OR
+    ///
+    ///     * **Code:** 404 Not Found <br />
+    ///
+    /// * **Sample Call:**
+    ///
+    ///   `curl -X POST <adapter>:<adapter-port>/drain-connections -d 'alice'`
     fn call(&mut self, req: Request<Body>) -> Self::Future {
         let res = Response::builder()
             // disable CORS to allow use as API server
@@ -245,7 +385,7 @@ impl Service<Request<Body>> for NoriaAdapterHttpRouter {
                 })
             }
             (&Method::GET, "/allow-list") => {
-                let query_cache = self.query_cache;
+                let query_cache = self.query_cache.clone();
                 Box::pin(async move {
                     let allow_list = query_cache.allow_list();
                     let res = match serde_json::to_string(&allow_list) {
@@ -262,7 +402,7 @@ impl Service<Request<Body>> for NoriaAdapterHttpRouter {
                 })
             }
             (&Method::GET, "/deny-list") => {
-                let query_cache = self.query_cache;
+                let query_cache = self.query_cache.clone();
                 Box::pin(async move {
                     let mut anonymizer = Anonymizer::new();
                     let deny_list = query_cache
@@ -290,7 +430,7 @@ impl Service<Request<Body>> for NoriaAdapterHttpRouter {
                 Box::pin(async move {
                     let body = format!("Adapter is in {} state", &state).into();
                     let res = match state {
-                        State::Healthy | State::ShuttingDown => res
+                        State::Healthy | State::Degraded | State::ShuttingDown => res
                             .status(200)
                             .header(CONTENT_TYPE, "text/plain")
                             .body(body),
@@ -303,17 +443,125 @@ impl Service<Request<Body>> for NoriaAdapterHttpRouter {
                     Ok(res.unwrap())
                 })
             }
+            (&Method::GET, "/ready") => {
+                let state = self.health_reporter.health().state;
+                let pending = self.readiness.pending();
+                let (status, body) = if state == State::ShuttingDown {
+                    (503, "Adapter is shutting down".to_owned())
+                } else if state == State::Degraded {
+                    (503, "Adapter is degraded".to_owned())
+                } else if pending.is_empty() {
+                    (200, "Adapter is ready".to_owned())
+                } else {
+                    (
+                        503,
+                        format!(
+                            "Adapter is not ready. Waiting for: {}",
+                            pending
+                                .iter()
+                                .map(|check| check.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                    )
+                };
+                let res = res
+                    .status(status)
+                    .header(CONTENT_TYPE, "text/plain")
+                    .body(hyper::Body::from(body));
+                Box::pin(async move { Ok(res.unwrap()) })
+            }
             (&Method::GET, "/metrics") => {
+                let openmetrics = req
+                    .headers()
+                    .get(ACCEPT)
+                    .and_then(|accept| accept.to_str().ok())
+                    .map_or(false, prefers_openmetrics);
                 let body = self.prometheus_handle.as_ref().map(|x| x.render());
-                let res = res.header(CONTENT_TYPE, "text/plain");
                 let res = match body {
-                    Some(metrics) => res.body(hyper::Body::from(metrics)),
+                    Some(metrics) if openmetrics => res
+                        .header(CONTENT_TYPE, OPENMETRICS_CONTENT_TYPE)
+                        .body(hyper::Body::from(render_openmetrics(&metrics))),
+                    Some(metrics) => res
+                        .header(CONTENT_TYPE, "text/plain")
+                        .body(hyper::Body::from(metrics)),
                     None => res
                         .status(404)
+                        .header(CONTENT_TYPE, "text/plain")
                         .body(hyper::Body::from("Prometheus metrics were not enabled. To fix this, run the adapter with --prometheus-metrics".to_string())),
                 };
                 Box::pin(async move { Ok(res.unwrap()) })
             }
+            (&Method::GET, "/telemetry/periodic-reporters") => {
+                let periodic_reporters = self.periodic_reporters.clone();
+                Box::pin(async move {
+                    let res = match periodic_reporters {
+                        Some(periodic_reporters) => {
+                            match serde_json::to_string(&periodic_reporters.list().await) {
+                                Ok(json) => res
+                                    .header(CONTENT_TYPE, "application/json")
+                                    .body(hyper::Body::from(json)),
+                                Err(_) => {
+                                    res.status(500)
+                                        .header(CONTENT_TYPE, "text/plain")
+                                        .body(hyper::Body::from(
+                                        "periodic reporters failed to be converted into a json \
+                                         string"
+                                            .to_string(),
+                                    ))
+                                }
+                            }
+                        }
+                        None => res
+                            .status(404)
+                            .header(CONTENT_TYPE, "text/plain")
+                            .body(hyper::Body::from("Telemetry reporting is disabled")),
+                    };
+                    Ok(res.unwrap())
+                })
+            }
+            (&Method::POST, "/drain-connections") => {
+                let cancel_registry = self.cancel_registry.clone();
+                Box::pin(async move {
+                    let cancel_registry = match cancel_registry {
+                        Some(cancel_registry) => cancel_registry,
+                        None => return Ok(res.status(404).body(hyper::Body::empty()).unwrap()),
+                    };
+                    let body = hyper::body::to_bytes(req.into_body()).await?;
+                    let user = String::from_utf8_lossy(&body);
+                    let user = user.trim();
+                    let res = if user.is_empty() {
+                        res.status(400)
+                            .header(CONTENT_TYPE, "text/plain")
+                            .body(hyper::Body::from("a user name must be provided"))
+                    } else {
+                        let drained = cancel_registry.drain_user(user);
+                        res.status(200)
+                            .header(CONTENT_TYPE, "text/plain")
+                            .body(hyper::Body::from(drained.to_string()))
+                    };
+                    Ok(res.unwrap())
+                })
+            }
+            (&Method::POST, path)
+                if let Some((name, enabled)) = parse_periodic_reporter_toggle(path) =>
+            {
+                let name = name.to_owned();
+                let periodic_reporters = self.periodic_reporters.clone();
+                Box::pin(async move {
+                    let found = match periodic_reporters {
+                        Some(periodic_reporters) => {
+                            periodic_reporters.set_enabled(&name, enabled).await
+                        }
+                        None => false,
+                    };
+                    let res = res
+                        .status(if found { 200 } else { 404 })
+                        .header(CONTENT_TYPE, "text/plain")
+                        .body(hyper::Body::empty());
+                    Ok(res.unwrap())
+                })
+            }
             _ => Box::pin(async move {
                 let res = res
                     .status(404)
@@ -325,3 +573,273 @@ impl Service<Request<Body>> for NoriaAdapterHttpRouter {
         }
     }
 }
+
+/// Parses a path of the form `/telemetry/periodic-reporters/<name>/(enable|disable)` into the
+/// name of the reporter and whether it should be enabled
+fn parse_periodic_reporter_toggle(path: &str) -> Option<(&str, bool)> {
+    let rest = path.strip_prefix("/telemetry/periodic-reporters/")?;
+    let (name, action) = rest.rsplit_once('/')?;
+    if name.is_empty() {
+        return None;
+    }
+    match action {
+        "enable" => Some((name, true)),
+        "disable" => Some((name, false)),
+        _ => None,
+    }
+}
+
+/// Returns true if the media ranges in the given `Accept` header value prefer the OpenMetrics
+/// text format over the legacy Prometheus text format
+fn prefers_openmetrics(accept: &str) -> bool {
+    let mut openmetrics_q = 0.0;
+    let mut text_q = 0.0;
+    for media_range in accept.to_ascii_lowercase().split(',') {
+        let mut params = media_range.split(';').map(str::trim);
+        let media_type = params.next().unwrap_or_default();
+        let q = params
+            .find_map(|param| param.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        match media_type {
+            "application/openmetrics-text" => openmetrics_q = f32::max(openmetrics_q, q),
+            "text/plain" | "text/*" | "*/*" => text_q = f32::max(text_q, q),
+            _ => {}
+        }
+    }
+    openmetrics_q > 0.0 && openmetrics_q >= text_q
+}
+
+/// Converts metrics rendered in the legacy Prometheus text format into the OpenMetrics text
+/// format.
+///
+/// Counter families are named without, and their samples with, a `_total` suffix; `untyped`
+/// metrics become `unknown`; blank lines and comments other than `HELP` and `TYPE` are removed; and
+/// the output is terminated with `# EOF`. The Prometheus exporter never records exemplars, so
+/// none are rendered.
+fn render_openmetrics(prometheus: &str) -> String {
+    let counters = prometheus
+        .lines()
+        .filter_map(|line| line.strip_prefix("# TYPE ")?.strip_suffix(" counter"))
+        .collect::<HashSet<_>>();
+    let family_name = |name: &str| -> String {
+        if counters.contains(name) {
+            name.strip_suffix("_total").unwrap_or(name).to_owned()
+        } else {
+            name.to_owned()
+        }
+    };
+
+    let mut res = String::with_capacity(prometheus.len() + 6);
+    for line in prometheus.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(comment) = line.strip_prefix('#') {
+            let comment = comment.trim_start();
+            if let Some(help) = comment.strip_prefix("HELP ") {
+                let (name, text) = help.split_once(' ').unwrap_or((help, ""));
+                res.push_str(&format!("# HELP {} {text}\n", family_name(name)));
+            } else if let Some(ty) = comment.strip_prefix("TYPE ") {
+                let (name, ty) = ty.split_once(' ').unwrap_or((ty, "unknown"));
+                let ty = if ty == "untyped" { "unknown" } else { ty };
+                res.push_str(&format!("# TYPE {} {ty}\n", family_name(name)));
+            }
+            continue;
+        }
+
+        let name_len = line.find(|c| c == '{' || c == ' ').unwrap_or(line.len());
+        let (name, rest) = line.split_at(name_len);
+        res.push_str(name);
+        if counters.contains(name) && !name.ends_with("_total") {
+            res.push_str("_total");
+        }
+        res.push_str(rest);
+        res.push('\n');
+    }
+    res.push_str("# EOF\n");
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics_exporter_prometheus::PrometheusBuilder;
+
+    use super::*;
+    use crate::readiness::ReadinessCheck;
+
+    #[test]
+    fn negotiate_metrics_format() {
+        assert!(prefers_openmetrics("application/openmetrics-text"));
+        assert!(prefers_openmetrics(
+            "application/openmetrics-text;version=1.0.0,application/openmetrics-text;\
+             version=0.0.1;q=0.75,text/plain;version=0.0.4;q=0.5,*/*;q=0.1"
+        ));
+        assert!(!prefers_openmetrics("text/plain"));
+        assert!(!prefers_openmetrics("*/*"));
+        assert!(!prefers_openmetrics(
+            "text/plain;version=0.0.4,application/openmetrics-text;q=0.5"
+        ));
+        assert!(!prefers_openmetrics("application/openmetrics-text;q=0"));
+    }
+
+    #[test]
+    fn convert_to_openmetrics() {
+        let prometheus = "# HELP queries_total Number of queries\n\
+                          # TYPE queries_total counter\n\
+                          queries_total{db=\"a\"} 3\n\
+                          \n\
+                          # TYPE requests counter\n\
+                          requests 1\n\
+                          \n\
+                          # TYPE connections gauge\n\
+                          connections 2\n\
+                          \n\
+                          # TYPE other untyped\n\
+                          other 4\n";
+        assert_eq!(
+            render_openmetrics(prometheus),
+            "# HELP queries Number of queries\n\
+             # TYPE queries counter\n\
+             queries_total{db=\"a\"} 3\n\
+             # TYPE requests counter\n\
+             requests_total 1\n\
+             # TYPE connections gauge\n\
+             connections 2\n\
+             # TYPE other unknown\n\
+             other 4\n\
+             # EOF\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn metrics_content_negotiation() {
+        let (_trigger, valve) = Valve::new();
+        let mut router = NoriaAdapterHttpRouter {
+            listen_addr: "127.0.0.1:0".parse().unwrap(),
+            query_cache: Arc::new(QueryStatusCache::new()),
+            valve,
+            health_reporter: AdapterHealthReporter::new(),
+            failpoint_channel: None,
+            prometheus_handle: Some(PrometheusBuilder::new().build_recorder().handle()),
+            periodic_reporters: None,
+            cancel_registry: None,
+            readiness: Readiness::new(),
+        };
+
+        let mut get_metrics = |accept: Option<&str>| {
+            let mut req = Request::builder().method(Method::GET).uri("/metrics");
+            if let Some(accept) = accept {
+                req = req.header(ACCEPT, accept);
+            }
+            router.call(req.body(Body::empty()).unwrap())
+        };
+
+        let res = get_metrics(Some("application/openmetrics-text; version=1.0.0"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers()[CONTENT_TYPE], OPENMETRICS_CONTENT_TYPE);
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert!(body.ends_with(b"# EOF\n"));
+
+        let res = get_metrics(None).await.unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers()[CONTENT_TYPE], "text/plain");
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert!(!body.ends_with(b"# EOF\n"));
+    }
+
+    #[tokio::test]
+    async fn ready_only_after_all_checks_pass() {
+        let (_trigger, valve) = Valve::new();
+        let mut health_reporter = AdapterHealthReporter::new();
+        let readiness = Readiness::new();
+        let mut router = NoriaAdapterHttpRouter {
+            listen_addr: "127.0.0.1:0".parse().unwrap(),
+            query_cache: Arc::new(QueryStatusCache::new()),
+            valve,
+            health_reporter: health_reporter.clone(),
+            failpoint_channel: None,
+            prometheus_handle: None,
+            periodic_reporters: None,
+            cancel_registry: None,
+            readiness: readiness.clone(),
+        };
+
+        let mut get = |path: &str| {
+            router.call(
+                Request::builder()
+                    .method(Method::GET)
+                    .uri(path)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        // Alive, but not yet ready
+        health_reporter.set_state(State::Healthy);
+        assert_eq!(get("/health").await.unwrap().status(), 200);
+        let res = get("/ready").await.unwrap();
+        assert_eq!(res.status(), 503);
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(
+            body,
+            "Adapter is not ready. Waiting for: ReadySet server version compatible, upstream \
+             database reachable"
+        );
+
+        readiness.pass(ReadinessCheck::ServerCompatible);
+        assert_eq!(get("/ready").await.unwrap().status(), 503);
+        readiness.pass(ReadinessCheck::UpstreamConnected);
+        assert_eq!(get("/ready").await.unwrap().status(), 200);
+
+        health_reporter.set_state(State::ShuttingDown);
+        assert_eq!(get("/ready").await.unwrap().status(), 503);
+        assert_eq!(get("/health").await.unwrap().status(), 200);
+    }
+
+    #[tokio::test]
+    async fn degraded_is_alive_but_not_ready() {
+        let (_trigger, valve) = Valve::new();
+        let mut health_reporter = AdapterHealthReporter::new();
+        let readiness = Readiness::new();
+        readiness.pass(ReadinessCheck::ServerCompatible);
+        readiness.pass(ReadinessCheck::UpstreamConnected);
+        let mut router = NoriaAdapterHttpRouter {
+            listen_addr: "127.0.0.1:0".parse().unwrap(),
+            query_cache: Arc::new(QueryStatusCache::new()),
+            valve,
+            health_reporter: health_reporter.clone(),
+            failpoint_channel: None,
+            prometheus_handle: None,
+            periodic_reporters: None,
+            cancel_registry: None,
+            readiness,
+        };
+
+        let mut get = |path: &str| {
+            router.call(
+                Request::builder()
+                    .method(Method::GET)
+                    .uri(path)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        health_reporter.set_state(State::Unhealthy);
+        assert_eq!(get("/health").await.unwrap().status(), 500);
+
+        health_reporter.set_state(State::Degraded);
+        assert_eq!(get("/health").await.unwrap().status(), 200);
+        let res = get("/ready").await.unwrap();
+        assert_eq!(res.status(), 503);
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "Adapter is degraded");
+
+        health_reporter.set_state(State::Healthy);
+        assert_eq!(get("/ready").await.unwrap().status(), 200);
+    }
+}
@@ -0,0 +1,185 @@
+//! Closing client connections which have been idle for too long.
+//!
+//! A connection's stream can be wrapped in an [`IdleTimeoutStream`], which fails reads with
+//! [`io::ErrorKind::TimedOut`] once the connection has spent longer than the configured idle
+//! timeout waiting for the client to send anything. The protocol implementations treat that like
+//! any other error reading from the client, and close the connection. [`is_idle_timeout`] can be
+//! used to tell those errors apart from real problems with the connection.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use std::{error, fmt, io};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+
+/// The error inside the [`io::Error`] returned by reads from an [`IdleTimeoutStream`] once the
+/// connection has been idle for longer than its timeout
+#[derive(Debug)]
+struct IdleTimeout(Duration);
+
+impl fmt::Display for IdleTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Connection was idle for longer than {:?}", self.0)
+    }
+}
+
+impl error::Error for IdleTimeout {}
+
+/// Returns true if `error` was returned by a read from an [`IdleTimeoutStream`] because the
+/// connection was idle for too long, rather than because of a problem with the connection itself
+pub fn is_idle_timeout(error: &io::Error) -> bool {
+    error
+        .get_ref()
+        .map_or(false, |inner| inner.is::<IdleTimeout>())
+}
+
+/// A wrapper around an [`AsyncRead`] (and, optionally, [`AsyncWrite`]) which fails reads once no
+/// data has been received from the underlying stream for longer than a timeout.
+///
+/// Only time spent waiting for the client counts towards the timeout, so a connection is never
+/// considered idle while its queries are being executed.
+pub struct IdleTimeoutStream<S> {
+    inner: S,
+    timeout: Option<Duration>,
+    /// Completes once the connection has been idle for `timeout`. Started when a read has to wait
+    /// for the client to send data, and cleared as soon as the read completes.
+    idle: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> IdleTimeoutStream<S> {
+    /// Wrap the given stream, failing reads from it once it has been idle for longer than
+    /// `timeout`. If `timeout` is `None`, reads never time out.
+    pub fn new(inner: S, timeout: Option<Duration>) -> Self {
+        Self {
+            inner,
+            timeout,
+            idle: None,
+        }
+    }
+}
+
+impl<S> AsyncRead for IdleTimeoutStream<S>
+where
+    S: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if let Poll::Ready(res) = Pin::new(&mut this.inner).poll_read(cx, buf) {
+            this.idle = None;
+            return Poll::Ready(res);
+        }
+
+        let timeout = match this.timeout {
+            Some(timeout) => timeout,
+            None => return Poll::Pending,
+        };
+        let idle = this
+            .idle
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        match idle.as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                this.idle = None;
+                Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    IdleTimeout(timeout),
+                )))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<S> AsyncWrite for IdleTimeoutStream<S>
+where
+    S: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::time::Instant;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_connection_times_out() {
+        let (_client, server) = tokio::io::duplex(64);
+        let mut stream = IdleTimeoutStream::new(server, Some(TIMEOUT));
+
+        let start = Instant::now();
+        let err = stream.read_u8().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(is_idle_timeout(&err));
+        assert_eq!(start.elapsed(), TIMEOUT);
+
+        // Timeouts from the connection itself aren't idle timeouts
+        assert!(!is_idle_timeout(&io::ErrorKind::TimedOut.into()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn activity_resets_timeout() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut stream = IdleTimeoutStream::new(server, Some(TIMEOUT));
+
+        tokio::spawn(async move {
+            for i in 0..5 {
+                tokio::time::sleep(TIMEOUT / 2).await;
+                client.write_u8(i).await.unwrap();
+            }
+            // Keep the connection open, but idle
+            tokio::time::sleep(TIMEOUT * 10).await;
+            drop(client);
+        });
+
+        for i in 0..5 {
+            assert_eq!(stream.read_u8().await.unwrap(), i);
+        }
+
+        // Time spent not reading from the client doesn't count as idle time
+        tokio::time::sleep(TIMEOUT * 2).await;
+        let start = Instant::now();
+        let err = stream.read_u8().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(start.elapsed(), TIMEOUT);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn no_timeout() {
+        let (client, server) = tokio::io::duplex(64);
+        let mut stream = IdleTimeoutStream::new(server, None);
+
+        assert!(tokio::time::timeout(TIMEOUT * 100, stream.read_u8())
+            .await
+            .is_err());
+        drop(client);
+        assert_eq!(
+            stream.read_u8().await.unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
@@ -9,21 +9,32 @@
 #![feature(generic_associated_types)]
 #![deny(unreachable_pub)]
 
+pub mod application_name;
 pub mod backend;
+pub mod cache_stats_reporter;
+pub mod cancel;
+pub mod connection_stats;
+pub mod drain;
 pub mod fallback_cache;
 pub mod http_router;
+pub mod idle_timeout;
+pub mod lock_contention;
 pub mod migration_handler;
 pub mod proxied_queries_reporter;
 mod query_handler;
 pub mod query_status_cache;
+pub mod readiness;
 pub mod rewrite;
+pub mod session_capture;
 pub mod upstream_database;
+pub mod upstream_pool;
 mod utils;
 pub mod views_synchronizer;
 
 pub use crate::backend::{Backend, BackendBuilder};
 pub use crate::query_handler::{QueryHandler, SetBehavior};
 pub use crate::upstream_database::{
-    UpstreamConfig, UpstreamDatabase, UpstreamDestination, UpstreamPrepare,
+    ProxyOptions, ResultRowLimit, UpstreamConfig, UpstreamDatabase, UpstreamDestination,
+    UpstreamPrepare,
 };
 pub use crate::views_synchronizer::ViewsSynchronizer;
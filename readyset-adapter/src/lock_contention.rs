@@ -0,0 +1,125 @@
+//! Optional instrumentation of contention on the locks shared between all client connections to
+//! an adapter.
+//!
+//! The global view cache (`query_cache`) and the table of auto-increment counters
+//! (`auto_increments`) are each shared between every connection's
+//! [`NoriaConnector`](crate::backend::noria_connector::NoriaConnector), and under high concurrency
+//! the locks guarding them can become contention points. If [enabled](enable), the time spent
+//! waiting to acquire each of those locks is recorded in the
+//! [`LOCK_WAIT_TIME`](recorded::LOCK_WAIT_TIME) histogram, labelled by the name of the lock.
+//!
+//! Instrumentation is disabled by default, in which case acquiring a lock through this module
+//! costs a single relaxed atomic load over acquiring it directly.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Instant;
+
+use readyset_client_metrics::recorded;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Start recording the time spent waiting to acquire shared locks, for the rest of the life of
+/// the process
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+fn timed<R>(lock: &'static str, acquire: impl FnOnce() -> R) -> R {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return acquire();
+    }
+
+    let start = Instant::now();
+    let guard = acquire();
+    metrics::histogram!(
+        recorded::LOCK_WAIT_TIME,
+        start.elapsed().as_secs_f64(),
+        "lock" => lock
+    );
+    guard
+}
+
+/// Acquire a read lock on `rw_lock`, recording the time spent waiting for it under the given name
+/// if instrumentation is enabled.
+///
+/// # Panics
+///
+/// Panics if the lock is poisoned
+pub(crate) fn read<'a, T>(lock: &'static str, rw_lock: &'a RwLock<T>) -> RwLockReadGuard<'a, T> {
+    timed(lock, || rw_lock.read().unwrap())
+}
+
+/// Acquire a write lock on `rw_lock`, recording the time spent waiting for it under the given
+/// name if instrumentation is enabled.
+///
+/// # Panics
+///
+/// Panics if the lock is poisoned
+pub(crate) fn write<'a, T>(lock: &'static str, rw_lock: &'a RwLock<T>) -> RwLockWriteGuard<'a, T> {
+    timed(lock, || rw_lock.write().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+    use std::time::Duration;
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+
+    use super::*;
+
+    fn lock_wait_times(lock: &str) -> Vec<f64> {
+        Snapshotter::current_thread_snapshot()
+            .map(|snapshot| snapshot.into_vec())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(key, _, _, value)| match value {
+                DebugValue::Histogram(values)
+                    if key.key().name() == recorded::LOCK_WAIT_TIME
+                        && key
+                            .key()
+                            .labels()
+                            .any(|l| l.key() == "lock" && l.value() == lock) =>
+                {
+                    Some(values.into_iter().map(|v| v.into_inner()))
+                }
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    #[test]
+    fn records_wait_time_under_contention() {
+        // Ignore the error if another test on this thread already installed the recorder
+        let _ = DebuggingRecorder::per_thread().install();
+        enable();
+
+        let rw_lock = Arc::new(RwLock::new(0));
+        let barrier = Arc::new(Barrier::new(2));
+        let holder = thread::spawn({
+            let rw_lock = Arc::clone(&rw_lock);
+            let barrier = Arc::clone(&barrier);
+            move || {
+                let mut guard = rw_lock.write().unwrap();
+                barrier.wait();
+                thread::sleep(Duration::from_millis(50));
+                *guard += 1;
+            }
+        });
+
+        barrier.wait();
+        assert_eq!(*read("test_contended", &rw_lock), 1);
+        holder.join().unwrap();
+        *write("test_contended", &rw_lock) += 1;
+
+        let wait_times = lock_wait_times("test_contended");
+        assert_eq!(wait_times.len(), 2);
+        assert!(
+            wait_times.iter().any(|t| *t >= 0.04),
+            "expected a wait of at least 40ms, got {wait_times:?}"
+        );
+    }
+}
@@ -6,17 +6,21 @@
 //! The migration handler may change a queries state based on the
 //! response from ReadySet.
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Instant;
 
 use dataflow_expression::Dialect;
+use health_reporter::{HealthReporter as AdapterHealthReporter, State};
 use metrics::{counter, register_counter};
 use readyset_client::query::{MigrationState, Query};
 use readyset_client::recipe::changelist::{Change, ChangeList};
 use readyset_client::{ReadySetHandle, ReadySetResult, ViewCreateRequest};
 use readyset_client_metrics::recorded;
-use readyset_tracing::{debug, error, info};
+use readyset_tracing::{debug, error, info, warn};
+use readyset_util::backoff::Backoff;
+use readyset_util::futures::run_until_cancelled;
 use readyset_util::redacted::Sensitive;
-use tokio::select;
+use tokio::sync::broadcast;
 use tracing::instrument;
 
 use crate::backend::noria_connector::{SelectPrepareResult, SelectPrepareResultInner};
@@ -25,6 +29,79 @@ use crate::query_status_cache::QueryStatusCache;
 use crate::upstream_database::{IsFatalError, NoriaCompare};
 use crate::{utils, UpstreamDatabase};
 
+/// The number of consecutive polls of the query status cache in which every attempted migration
+/// failed, after which the migration handler reports the adapter as [`State::Degraded`]
+const DEGRADED_AFTER_FAILED_POLLS: usize = 5;
+
+/// Schedules the migration handler's polls of the query status cache. If jitter is configured,
+/// each interval between polls is randomly shortened, so that many adapters started at the same
+/// time don't all poll (and perform migrations against the server) in lockstep.
+struct PollSchedule {
+    next_poll: tokio::time::Instant,
+    intervals: Backoff,
+}
+
+impl PollSchedule {
+    /// Create a schedule whose first poll is due immediately, and whose subsequent polls are due
+    /// `interval` apart, each reduced by a random amount of up to `jitter` times the interval.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `jitter` is not between 0 and 1
+    fn new(interval: std::time::Duration, jitter: f64) -> Self {
+        Self {
+            next_poll: tokio::time::Instant::now(),
+            intervals: Backoff::new(interval).factor(1.0).jitter(jitter),
+        }
+    }
+
+    /// Wait until the next poll is due, and schedule the one after it
+    async fn tick(&mut self) {
+        tokio::time::sleep_until(self.next_poll).await;
+        // Backoff is an infinite iterator, so this never uses the default
+        self.next_poll = tokio::time::Instant::now() + self.intervals.next().unwrap_or_default();
+    }
+}
+
+/// Tracks whether the migration handler is repeatedly failing to perform migrations, and reports
+/// that to the adapter's health reporter so that orchestration can react before the failures
+/// become fatal
+#[derive(Default)]
+struct MigrationHealth {
+    reporter: Option<AdapterHealthReporter>,
+    consecutive_failed_polls: usize,
+}
+
+impl MigrationHealth {
+    /// Record the outcome of a single poll of the query status cache, in which `completed`
+    /// migrations were performed and `retrying` migrations failed and will be retried on a later
+    /// poll
+    fn record_poll(&mut self, completed: u64, retrying: u64) {
+        if completed == 0 && retrying > 0 {
+            self.consecutive_failed_polls += 1;
+        } else {
+            self.consecutive_failed_polls = 0;
+        }
+
+        let reporter = match &mut self.reporter {
+            Some(reporter) => reporter,
+            None => return,
+        };
+        if self.consecutive_failed_polls >= DEGRADED_AFTER_FAILED_POLLS {
+            if reporter.transition(State::Healthy, State::Degraded) {
+                warn!(
+                    failed_polls = self.consecutive_failed_polls,
+                    "Migration handler is repeatedly failing to perform migrations"
+                );
+            }
+        } else if self.consecutive_failed_polls == 0
+            && reporter.transition(State::Degraded, State::Healthy)
+        {
+            info!("Migration handler recovered from repeated failures");
+        }
+    }
+}
+
 pub struct MigrationHandler<DB> {
     /// Connection used to issue prepare requests to ReadySet.
     noria: NoriaConnector,
@@ -40,7 +117,7 @@ pub struct MigrationHandler<DB> {
 
     /// The query status cache is polled on a regular interval to
     /// determine which queries require processing.
-    query_status_cache: &'static QueryStatusCache,
+    query_status_cache: Arc<QueryStatusCache>,
 
     /// Whether the queries should be validated against MySQL during
     /// migration.
@@ -51,17 +128,22 @@ pub struct MigrationHandler<DB> {
     /// that require processing take longer than `min_poll_interval`.
     min_poll_interval: std::time::Duration,
 
+    /// The maximum fraction of `min_poll_interval` by which each interval between polls may be
+    /// randomly shortened.
+    poll_jitter: f64,
+
     /// The maximum amount of time the migration handler will retry a
     /// query for before marking it as Unsupported.
     max_retry: std::time::Duration,
 
-    /// Receiver to return the broadcast signal on.
-    shutdown_recv: tokio::sync::broadcast::Receiver<()>,
-
     /// The time that we began performing migrations on the query.
     /// Queries are removed when a migration yields success or unsupported
     /// and re-added when they are found in the pending migration list.
     start_time: HashMap<ViewCreateRequest, Instant>,
+
+    /// Tracks repeated failures to perform migrations, to report them to the adapter's health
+    /// reporter
+    health: MigrationHealth,
 }
 
 impl<DB> MigrationHandler<DB>
@@ -73,12 +155,11 @@ where
         noria: NoriaConnector,
         upstream: Option<DB>,
         controller: Option<ReadySetHandle>,
-        query_status_cache: &'static QueryStatusCache,
+        query_status_cache: Arc<QueryStatusCache>,
         dialect: Dialect,
         validate_queries: bool,
         min_poll_interval: std::time::Duration,
         max_retry: std::time::Duration,
-        shutdown_recv: tokio::sync::broadcast::Receiver<()>,
     ) -> MigrationHandler<DB> {
         MigrationHandler {
             noria,
@@ -88,55 +169,90 @@ where
             query_status_cache,
             validate_queries,
             min_poll_interval,
+            poll_jitter: 0.0,
             max_retry,
-            shutdown_recv,
             start_time: HashMap::new(),
+            health: MigrationHealth::default(),
         }
     }
 
-    #[instrument(level = "warn", name = "migration_handler", skip(self))]
-    pub async fn run(&mut self) -> ReadySetResult<()> {
-        let mut interval = tokio::time::interval(self.min_poll_interval);
+    /// Report the health of the migration handler to the given health reporter, by moving it to
+    /// [`State::Degraded`] while migrations are repeatedly failing
+    pub fn with_health_reporter(mut self, health_reporter: AdapterHealthReporter) -> Self {
+        self.health.reporter = Some(health_reporter);
+        self
+    }
+
+    /// Randomly shorten each interval between polls of the query status cache by up to `jitter`
+    /// times the interval, so that the migration handlers of many adapters spread their polls out.
+    /// Defaults to 0 (no jitter).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `jitter` is not between 0 and 1
+    pub fn with_poll_jitter(mut self, jitter: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&jitter),
+            "Migration handler poll jitter must be between 0 and 1"
+        );
+        self.poll_jitter = jitter;
+        self
+    }
+
+    /// Perform migrations for queries pending migration until a shutdown signal is received on
+    /// `shutdown_recv`
+    #[instrument(level = "warn", name = "migration_handler", skip_all)]
+    pub async fn run(&mut self, shutdown_recv: broadcast::Receiver<()>) -> ReadySetResult<()> {
+        let mut schedule = PollSchedule::new(self.min_poll_interval, self.poll_jitter);
         let success_counter = register_counter!(recorded::MIGRATION_HANDLER_SUCCESSES);
         let failure_counter = register_counter!(recorded::MIGRATION_HANDLER_FAILURES);
 
-        loop {
-            select! {
-                _ = interval.tick() => {
+        run_until_cancelled(
+            async {
+                loop {
+                    schedule.tick().await;
                     let to_process = self.query_status_cache.pending_migration();
                     let has_controller = self.controller.is_some();
                     let mut successes = 0;
                     let mut failures = 0;
+                    let mut retrying = 0;
                     for q in to_process {
                         match &q.0 {
                             Query::Parsed(req) => {
-                                if has_controller {
+                                let retry = if has_controller {
                                     self.perform_dry_run_migration(req).await
                                 } else {
                                     self.perform_migration(req).await
+                                };
+                                if retry {
+                                    retrying += 1;
                                 }
                                 successes += 1;
                             }
                             Query::ParseFailed(_) => {
-                                error!("Should not be migrating query that failed to parse. Ignoring");
+                                error!(
+                                    "Should not be migrating query that failed to parse. Ignoring"
+                                );
                                 failures += 1;
-                            },
+                            }
                         }
                     }
 
                     success_counter.increment(successes);
                     failure_counter.increment(failures);
+                    self.health.record_poll(successes - retrying, retrying);
                 }
-                _ = self.shutdown_recv.recv() => {
-                    info!("Migration handler shutting down after shut down signal received");
-                    break;
-                }
-            }
-        }
+            },
+            shutdown_recv,
+        )
+        .await;
+        info!("Migration handler shutting down after shut down signal received");
         Ok(())
     }
 
-    async fn perform_migration(&mut self, view_request: &ViewCreateRequest) {
+    /// Returns `true` if the migration failed for a reason unrelated to the query itself, and will
+    /// be retried on a later poll
+    async fn perform_migration(&mut self, view_request: &ViewCreateRequest) -> bool {
         // If this is the first migration we are performing, add the query to the
         // start_time map.
         if !self.start_time.contains_key(view_request) {
@@ -159,7 +275,7 @@ where
                                 query = %Sensitive(&view_request.statement),
                                 "MigrationHandler dropped conn to Upstream and failed to reconnnect",
                             );
-                            return;
+                            return true;
                         } else {
                             // Succeeded on reconnecting. Retry prepare.
                             upstream_result = db.prepare(view_request.statement.to_string()).await;
@@ -174,7 +290,7 @@ where
                         query = %Sensitive(&view_request.statement),
                         "Query failed to be prepared against upstream",
                     );
-                    return;
+                    return false;
                 }
 
                 Some(upstream_result)
@@ -220,7 +336,7 @@ where
                                 /*self.query_status_cache
                                 .update_query_migration_state(stmt, MigrationState::Unsupported)
                                 .await;*/
-                                return;
+                                return false;
                             }
                         }
                         noria_connector::PrepareResult::Select(SelectPrepareResult::NoSchema(
@@ -229,7 +345,7 @@ where
                             debug!("Cannot compare schema for borrowed query");
                         }
                         _ => {
-                            return;
+                            return false;
                         }
                     }
                 }
@@ -237,6 +353,7 @@ where
                 self.start_time.remove(view_request);
                 self.query_status_cache
                     .update_query_migration_state(view_request, MigrationState::Successful);
+                false
             }
             Err(e) if e.caused_by_unsupported() => {
                 debug!(
@@ -248,6 +365,7 @@ where
                 self.start_time.remove(view_request);
                 self.query_status_cache
                     .update_query_migration_state(view_request, MigrationState::Unsupported);
+                false
             }
             // Errors that were not caused by unsupported may be transient, do nothing
             // so we may retry the migration on this query.
@@ -261,16 +379,21 @@ where
                     // Query failed for long enough, it is unsupported.
                     self.query_status_cache
                         .update_query_migration_state(view_request, MigrationState::Unsupported);
+                    false
+                } else {
+                    true
                 }
             }
         }
     }
 
-    async fn perform_dry_run_migration(&mut self, view_request: &ViewCreateRequest) {
+    /// Returns `true` if the dry run migration failed for a reason unrelated to the query itself,
+    /// and will be retried on a later poll
+    async fn perform_dry_run_migration(&mut self, view_request: &ViewCreateRequest) -> bool {
         let controller = if let Some(ref mut c) = self.controller {
             c
         } else {
-            return;
+            return false;
         };
         let start_time = self
             .start_time
@@ -284,7 +407,7 @@ where
                 self.max_retry,
                 view_request.to_anonymized_string()
             );
-            return;
+            return false;
         }
         let qname =
             utils::generate_query_name(&view_request.statement, &view_request.schema_search_path);
@@ -298,13 +421,117 @@ where
                 self.start_time.remove(view_request);
                 self.query_status_cache
                     .update_query_migration_state(view_request, MigrationState::DryRunSucceeded);
+                false
             }
             Err(e) if e.caused_by_unsupported() => {
                 self.start_time.remove(view_request);
                 self.query_status_cache
                     .update_query_migration_state(view_request, MigrationState::Unsupported);
+                false
             }
-            _ => {} // Leave it as pending.
+            _ => true, // Leave it as pending.
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the times of the first `polls` polls made on a schedule with the given interval and
+    /// jitter
+    async fn poll_times(
+        interval: std::time::Duration,
+        jitter: f64,
+        polls: usize,
+    ) -> Vec<tokio::time::Instant> {
+        let mut schedule = PollSchedule::new(interval, jitter);
+        let mut times = Vec::with_capacity(polls);
+        for _ in 0..polls {
+            schedule.tick().await;
+            times.push(tokio::time::Instant::now());
+        }
+        times
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn jittered_poll_times_diverge() {
+        let interval = std::time::Duration::from_secs(20);
+        let (a, b) = tokio::join!(poll_times(interval, 0.5, 10), poll_times(interval, 0.5, 10));
+        assert_eq!(a[0], b[0]);
+        assert_ne!(a[1..], b[1..]);
+        for times in [a, b] {
+            for (prev, next) in times.iter().zip(&times[1..]) {
+                let elapsed = *next - *prev;
+                assert!(elapsed <= interval, "{elapsed:?} > {interval:?}");
+                assert!(elapsed >= interval / 2, "{elapsed:?} < {:?}", interval / 2);
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unjittered_poll_times_match() {
+        let interval = std::time::Duration::from_secs(20);
+        let (a, b) = tokio::join!(poll_times(interval, 0.0, 10), poll_times(interval, 0.0, 10));
+        assert_eq!(a, b);
+    }
+
+    fn healthy_reporter() -> AdapterHealthReporter {
+        let mut reporter = AdapterHealthReporter::new();
+        reporter.set_state(State::Healthy);
+        reporter
+    }
+
+    #[test]
+    fn repeated_failures_degrade_health() {
+        let reporter = healthy_reporter();
+        let mut health = MigrationHealth {
+            reporter: Some(reporter.clone()),
+            ..Default::default()
+        };
+
+        for _ in 1..DEGRADED_AFTER_FAILED_POLLS {
+            health.record_poll(0, 3);
+            assert_eq!(reporter.state(), State::Healthy);
         }
+        health.record_poll(0, 3);
+        assert_eq!(reporter.state(), State::Degraded);
+
+        // Partial progress counts as recovery
+        health.record_poll(1, 2);
+        assert_eq!(reporter.state(), State::Healthy);
+    }
+
+    #[test]
+    fn intermittent_failures_dont_degrade_health() {
+        let reporter = healthy_reporter();
+        let mut health = MigrationHealth {
+            reporter: Some(reporter.clone()),
+            ..Default::default()
+        };
+
+        for _ in 0..(DEGRADED_AFTER_FAILED_POLLS * 2) {
+            health.record_poll(0, 1);
+            health.record_poll(0, 0);
+        }
+        assert_eq!(reporter.state(), State::Healthy);
+    }
+
+    #[test]
+    fn recovery_doesnt_override_shutdown() {
+        let mut reporter = healthy_reporter();
+        let mut health = MigrationHealth {
+            reporter: Some(reporter.clone()),
+            ..Default::default()
+        };
+
+        for _ in 0..DEGRADED_AFTER_FAILED_POLLS {
+            health.record_poll(0, 1);
+        }
+        assert_eq!(reporter.state(), State::Degraded);
+
+        reporter.set_state(State::ShuttingDown);
+        health.record_poll(1, 0);
+        assert_eq!(reporter.state(), State::ShuttingDown);
     }
 }
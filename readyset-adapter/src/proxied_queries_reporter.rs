@@ -1,24 +1,37 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use readyset_client::query::{DeniedQuery, MigrationState, QueryId};
 use readyset_sql_passes::anonymize::Anonymizer;
 use readyset_telemetry_reporter::{
-    PeriodicReport, ReporterResult as Result, Telemetry, TelemetryBuilder, TelemetryEvent,
+    PeriodicReport, ReportLimit, ReporterResult as Result, Telemetry, TelemetryBuilder,
+    TelemetryEvent,
 };
 use readyset_tracing::debug;
 use tokio::sync::Mutex;
 
 use crate::query_status_cache::QueryStatusCache;
 
+/// Limits on the number of proxied queries reported at once. Each query is only reported once, so
+/// a large number of distinct queries (eg on startup) could otherwise produce a payload too large
+/// for the telemetry collector. Queries beyond the limit are reported on subsequent runs.
+///
+/// The default interval of 30 seconds between reports, at 50 events per report, keeps within the
+/// rate limit for [`TelemetryEvent::ProxiedQuery`] events.
+const PROXIED_QUERIES_REPORT_LIMIT: ReportLimit = ReportLimit {
+    max_events: Some(50),
+    max_bytes: Some(64 * 1024),
+};
+
 pub struct ProxiedQueriesReporter {
-    query_status_cache: &'static QueryStatusCache,
+    query_status_cache: Arc<QueryStatusCache>,
     reported_queries: Mutex<HashMap<QueryId, MigrationState>>,
     anonymizer: Mutex<Anonymizer>,
 }
 
 impl ProxiedQueriesReporter {
-    pub fn new(query_status_cache: &'static QueryStatusCache) -> Self {
+    pub fn new(query_status_cache: Arc<QueryStatusCache>) -> Self {
         Self {
             query_status_cache,
             reported_queries: Mutex::new(HashMap::new()),
@@ -66,6 +79,14 @@ impl ProxiedQueriesReporter {
 
 #[async_trait]
 impl PeriodicReport for ProxiedQueriesReporter {
+    fn name(&self) -> &str {
+        "proxied-queries"
+    }
+
+    fn report_limit(&self) -> ReportLimit {
+        PROXIED_QUERIES_REPORT_LIMIT
+    }
+
     async fn report(&self) -> Result<Vec<(TelemetryEvent, Telemetry)>> {
         debug!("running report for proxied queries");
         let mut denied_queries = self.query_status_cache.deny_list();
@@ -85,18 +106,15 @@ impl PeriodicReport for ProxiedQueriesReporter {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
-
     use readyset_client::query::{Query, QueryStatus};
+    use readyset_telemetry_reporter::TelemetryInitializer;
 
     use super::*;
     use crate::query_status_cache::MigrationStyle;
 
     #[tokio::test]
     async fn test_update_migration_state() {
-        let query_status_cache = Box::leak(Box::new(QueryStatusCache::with_style(
-            MigrationStyle::Explicit,
-        )));
+        let query_status_cache = Arc::new(QueryStatusCache::with_style(MigrationStyle::Explicit));
         let proxied_queries_reporter = Arc::new(ProxiedQueriesReporter::new(query_status_cache));
 
         let query_id = QueryId::new(42);
@@ -136,4 +154,25 @@ mod tests {
         };
         assert_eq!(MigrationState::Successful, status);
     }
+
+    #[tokio::test]
+    async fn query_status_cache_released_on_drop() {
+        // Simulate starting and stopping the adapter twice in the same process - each time, the
+        // query status cache must be freed once everything using it has been dropped
+        for _ in 0..2 {
+            let query_status_cache =
+                Arc::new(QueryStatusCache::with_style(MigrationStyle::Explicit));
+            let weak = Arc::downgrade(&query_status_cache);
+
+            let (telemetry_sender, mut reporter) = TelemetryInitializer::test_init();
+            reporter
+                .register_periodic_reporter(Arc::new(ProxiedQueriesReporter::new(
+                    query_status_cache.clone(),
+                )))
+                .await;
+            drop((telemetry_sender, reporter, query_status_cache));
+
+            assert!(weak.upgrade().is_none());
+        }
+    }
 }
@@ -1,7 +1,10 @@
 //! The query status cache provides a thread-safe window into an adapter's
 //! knowledge about queries, currently the migration status of a query in
 //! ReadySet.
+use std::fs::{self, File};
 use std::hash::Hash;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -10,8 +13,36 @@ use anyhow::anyhow;
 use dashmap::DashMap;
 use readyset_client::query::*;
 use readyset_client::ViewCreateRequest;
-use readyset_tracing::error;
+use readyset_client_metrics::recorded;
+use readyset_tracing::{error, info, warn};
 use readyset_util::hash::hash;
+use readyset_version::RELEASE_VERSION;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+/// The version of the format used by [`QueryStatusCache::persist`]. This must be incremented any
+/// time the persisted representation changes, so that state written by an older adapter is
+/// discarded rather than misinterpreted.
+const PERSISTED_FORMAT_VERSION: u32 = 1;
+
+/// The on-disk representation of a [`QueryStatusCache`]
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedQueryStatusCache {
+    format_version: u32,
+    /// The release version of the adapter which wrote this state. The serialized representation of
+    /// queries may change between releases, so state written by a different release is discarded.
+    release_version: String,
+    queries: Vec<PersistedQueryStatus>,
+}
+
+/// The persisted subset of the [`QueryStatus`] of a single query. Execution info is intentionally
+/// not persisted, since it is relative to the lifetime of the process.
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedQueryStatus {
+    query: Query,
+    migration_state: MigrationState,
+    always: bool,
+}
 
 /// A metadata cache for all queries that have been processed by this
 /// adapter. Thread-safe.
@@ -32,6 +63,10 @@ pub struct QueryStatusCache {
     /// Holds the current style of migration, whether async or explicit, which may change the
     /// behavior of some internal methods.
     style: MigrationStyle,
+
+    /// Notified whenever a query newly becomes pending migration, so that the
+    /// [`ViewsSynchronizer`](crate::ViewsSynchronizer) can check on it promptly
+    pending_added: Notify,
 }
 
 /// Keys into the queries stored in `QueryStatusCache`
@@ -118,6 +153,7 @@ impl QueryStatusCache {
             failed_parses: DashMap::new(),
             ids: DashMap::new(),
             style: MigrationStyle::InRequestPath,
+            pending_added: Notify::new(),
         }
     }
 
@@ -165,14 +201,26 @@ impl QueryStatusCache {
             }
         };
         let id = QueryId::new(hash(&q));
-        self.ids.insert(id, q.clone());
-        match q {
+        if self.ids.insert(id, q.clone()).is_none() {
+            metrics::gauge!(recorded::CACHED_QUERY_SHAPES, self.ids.len() as f64);
+        }
+        let pending = status.is_pending();
+        let old_status = match q {
             Query::Parsed(q) => self.statuses.insert(q, status),
             Query::ParseFailed(q) => self.failed_parses.insert(q, status),
         };
+        if pending && !old_status.map_or(false, |s| s.is_pending()) {
+            self.pending_added.notify_one();
+        }
         id
     }
 
+    /// Waits until a query newly becomes pending migration. If one has done so since the last time
+    /// this was called, returns immediately.
+    pub async fn pending_migration_added(&self) {
+        self.pending_added.notified().await
+    }
+
     /// This function returns the id and query migration state of a query. If the query does not
     /// exist within the query status cache, an entry is created and the query is set to
     /// PendingMigration.
@@ -378,13 +426,18 @@ impl QueryStatusCache {
 
     /// Clear all queries currently marked as successful from the cache.
     pub fn clear(&self) {
+        let mut cleared = false;
         self.statuses
             .iter_mut()
             .filter(|v| v.is_successful())
             .for_each(|mut v| {
                 v.migration_state = MigrationState::Pending;
                 v.always = false;
+                cleared = true;
             });
+        if cleared {
+            self.pending_added.notify_one();
+        }
     }
 
     /// Returns a list of queries that currently need the be processed to determine
@@ -469,6 +522,113 @@ impl QueryStatusCache {
         let id = QueryId::new(u64::from_str_radix(id.strip_prefix("q_")?, 16).ok()?);
         self.ids.get(&id).map(|r| (*r.value()).clone())
     }
+
+    /// Write the migration state of all queries in this cache to `writer`, in a format which can
+    /// be read back with [`QueryStatusCache::restore`]
+    pub fn persist<W>(&self, writer: W) -> anyhow::Result<()>
+    where
+        W: Write,
+    {
+        let queries = self
+            .ids
+            .iter()
+            .filter_map(|r| {
+                r.value().with_status(self, |s| {
+                    s.map(|s| PersistedQueryStatus {
+                        query: r.value().clone(),
+                        migration_state: s.migration_state,
+                        always: s.always,
+                    })
+                })
+            })
+            .collect();
+
+        bincode::serialize_into(
+            writer,
+            &PersistedQueryStatusCache {
+                format_version: PERSISTED_FORMAT_VERSION,
+                release_version: RELEASE_VERSION.to_owned(),
+                queries,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Load the migration state of queries previously written with [`QueryStatusCache::persist`]
+    /// from `reader` into this cache, returning the number of queries that were loaded.
+    ///
+    /// If the persisted state was written by an incompatible version of the adapter, or cannot be
+    /// read, it is discarded and no queries are loaded.
+    pub fn restore<R>(&self, reader: R) -> usize
+    where
+        R: Read,
+    {
+        let persisted: PersistedQueryStatusCache = match bincode::deserialize_from(reader) {
+            Ok(persisted) => persisted,
+            Err(error) => {
+                warn!(%error, "Could not read persisted query status; discarding");
+                return 0;
+            }
+        };
+
+        if persisted.format_version != PERSISTED_FORMAT_VERSION
+            || persisted.release_version != RELEASE_VERSION
+        {
+            warn!(
+                format_version = persisted.format_version,
+                release_version = %persisted.release_version,
+                "Persisted query status was written by an incompatible version; discarding"
+            );
+            return 0;
+        }
+
+        let num_queries = persisted.queries.len();
+        for PersistedQueryStatus {
+            query,
+            migration_state,
+            always,
+        } in persisted.queries
+        {
+            self.insert_with_status(
+                query,
+                QueryStatus {
+                    migration_state,
+                    execution_info: None,
+                    always,
+                },
+            );
+        }
+        num_queries
+    }
+
+    /// Persist this cache to the file at `path`, replacing it atomically if it already exists.
+    ///
+    /// See [`QueryStatusCache::persist`]
+    pub fn persist_to_file(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        self.persist(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Restore this cache from the file at `path`, if it exists, returning the number of queries
+    /// that were loaded.
+    ///
+    /// See [`QueryStatusCache::restore`]
+    pub fn restore_from_file(&self, path: &Path) -> io::Result<usize> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                info!(path = %path.display(), "No persisted query status found");
+                return Ok(0);
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(self.restore(BufReader::new(file)))
+    }
 }
 
 /// MigrationStyle is used to communicate which style of managing migrations we have configured.
@@ -559,6 +719,49 @@ mod tests {
         assert_eq!(*cache.failed_parses.get(&q1).unwrap().value(), status);
     }
 
+    #[test]
+    fn cached_query_shapes_gauge() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+
+        // Ignore the error if another test on this thread already installed the recorder
+        let _ = DebuggingRecorder::per_thread().install();
+        let gauge_value = || {
+            Snapshotter::current_thread_snapshot()
+                .unwrap()
+                .into_vec()
+                .into_iter()
+                .find_map(|(key, _, _, value)| match value {
+                    DebugValue::Gauge(v) if key.key().name() == recorded::CACHED_QUERY_SHAPES => {
+                        Some(v.into_inner())
+                    }
+                    _ => None,
+                })
+        };
+
+        let cache = QueryStatusCache::new();
+        for (i, query) in ["SELECT * FROM t1", "SELECT * FROM t2", "SELECT * FROM t3"]
+            .into_iter()
+            .enumerate()
+        {
+            cache.insert(ViewCreateRequest::new(
+                select_statement(query).unwrap(),
+                vec![],
+            ));
+            assert_eq!(gauge_value(), Some((i + 1) as f64));
+        }
+
+        // Queries which failed to parse are counted too
+        cache.insert("SELECT * FROM t4 WHERE unparseable".to_string());
+        assert_eq!(gauge_value(), Some(4.0));
+
+        // Re-inserting an existing query shape doesn't change the count
+        cache.insert(ViewCreateRequest::new(
+            select_statement("SELECT * FROM t1").unwrap(),
+            vec![],
+        ));
+        assert_eq!(gauge_value(), Some(4.0));
+    }
+
     #[test]
     fn query_is_referenced_by_hash() {
         let cache = QueryStatusCache::new();
@@ -659,4 +862,99 @@ mod tests {
         cache.clear();
         assert_eq!(cache.allow_list().len(), 0);
     }
+
+    #[test]
+    fn persist_and_restore() {
+        let cache = QueryStatusCache::new();
+        let successful =
+            ViewCreateRequest::new(select_statement("SELECT * FROM t1").unwrap(), vec![]);
+        let unsupported = ViewCreateRequest::new(
+            select_statement("SELECT * FROM t2").unwrap(),
+            vec!["s1".into()],
+        );
+        let pending = ViewCreateRequest::new(select_statement("SELECT * FROM t3").unwrap(), vec![]);
+        let failed_parse = "SELECT unparseable FROM t4".to_string();
+
+        cache.update_query_migration_state(&successful, MigrationState::Successful);
+        cache.always_attempt_readyset(&successful, true);
+        cache.update_query_migration_state(&unsupported, MigrationState::Unsupported);
+        cache.query_migration_state(&pending);
+        cache.insert(failed_parse.clone());
+
+        let mut buf = Vec::new();
+        cache.persist(&mut buf).unwrap();
+
+        let restored = QueryStatusCache::new();
+        assert_eq!(restored.restore(buf.as_slice()), 4);
+
+        assert_eq!(
+            restored.query_status(&successful),
+            QueryStatus {
+                migration_state: MigrationState::Successful,
+                execution_info: None,
+                always: true,
+            }
+        );
+        assert_eq!(
+            restored.query_migration_state(&unsupported),
+            cache.query_migration_state(&unsupported)
+        );
+        assert_eq!(
+            restored.query_migration_state(&pending),
+            cache.query_migration_state(&pending)
+        );
+        assert_eq!(
+            restored.query_migration_state(&failed_parse),
+            cache.query_migration_state(&failed_parse)
+        );
+        assert_eq!(restored.allow_list(), cache.allow_list());
+        assert_eq!(restored.deny_list().len(), cache.deny_list().len());
+    }
+
+    #[test]
+    fn restore_discards_incompatible_state() {
+        let mut buf = Vec::new();
+        bincode::serialize_into(
+            &mut buf,
+            &PersistedQueryStatusCache {
+                format_version: PERSISTED_FORMAT_VERSION + 1,
+                release_version: RELEASE_VERSION.to_owned(),
+                queries: vec![PersistedQueryStatus {
+                    query: "SELECT 1".into(),
+                    migration_state: MigrationState::Unsupported,
+                    always: false,
+                }],
+            },
+        )
+        .unwrap();
+
+        let cache = QueryStatusCache::new();
+        assert_eq!(cache.restore(buf.as_slice()), 0);
+        assert!(cache.ids.is_empty());
+
+        assert_eq!(cache.restore(&b"garbage"[..]), 0);
+        assert!(cache.ids.is_empty());
+    }
+
+    #[tokio::test]
+    async fn new_pending_queries_are_notified() {
+        let cache = QueryStatusCache::new();
+        let pending_added =
+            || tokio::time::timeout(Duration::from_millis(10), cache.pending_migration_added());
+        pending_added().await.unwrap_err();
+
+        let q = ViewCreateRequest::new(select_statement("SELECT * FROM t1").unwrap(), vec![]);
+        cache.insert(q.clone());
+        pending_added().await.unwrap();
+
+        // Queries which were already pending, or which aren't pending, don't notify
+        cache.insert(q.clone());
+        cache.update_query_migration_state(&q, MigrationState::Successful);
+        cache.insert("SELECT * FROM".to_owned());
+        pending_added().await.unwrap_err();
+
+        // Clearing successful queries makes them pending again
+        cache.clear();
+        pending_added().await.unwrap();
+    }
 }
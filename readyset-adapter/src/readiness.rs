@@ -0,0 +1,93 @@
+//! Tracking whether the adapter is ready to serve client traffic.
+//!
+//! An adapter which is alive (as reported by the `/health` endpoint of the
+//! [`NoriaAdapterHttpRouter`](crate::http_router::NoriaAdapterHttpRouter)) may still not be able to
+//! serve queries, for example because it can't yet reach the upstream database. The `/ready`
+//! endpoint reports the adapter as ready only once every [`ReadinessCheck`] has passed, so that
+//! orchestrators such as Kubernetes don't route traffic to it before then.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A condition which must be met before the adapter is ready to serve client traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadinessCheck {
+    /// The adapter has established a session with the authority, and confirmed that the
+    /// ReadySet server's version is compatible with its own
+    ServerCompatible,
+    /// The adapter has successfully connected to the upstream database, if one is configured
+    UpstreamConnected,
+}
+
+impl ReadinessCheck {
+    /// All of the checks which must pass for the adapter to be ready
+    pub const ALL: [ReadinessCheck; 2] = [Self::ServerCompatible, Self::UpstreamConnected];
+}
+
+impl fmt::Display for ReadinessCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ServerCompatible => write!(f, "ReadySet server version compatible"),
+            Self::UpstreamConnected => write!(f, "upstream database reachable"),
+        }
+    }
+}
+
+/// Records which [`ReadinessCheck`]s have passed. Cloning a `Readiness` returns a handle to the
+/// same set of checks.
+#[derive(Debug, Clone, Default)]
+pub struct Readiness {
+    server_compatible: Arc<AtomicBool>,
+    upstream_connected: Arc<AtomicBool>,
+}
+
+impl Readiness {
+    /// Create a new `Readiness` on which no checks have passed yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn flag(&self, check: ReadinessCheck) -> &AtomicBool {
+        match check {
+            ReadinessCheck::ServerCompatible => &self.server_compatible,
+            ReadinessCheck::UpstreamConnected => &self.upstream_connected,
+        }
+    }
+
+    /// Record that `check` has passed. Checks never go back to failing once they've passed.
+    pub fn pass(&self, check: ReadinessCheck) {
+        self.flag(check).store(true, Ordering::Release);
+    }
+
+    /// Returns the checks which haven't passed yet
+    pub fn pending(&self) -> Vec<ReadinessCheck> {
+        ReadinessCheck::ALL
+            .into_iter()
+            .filter(|check| !self.flag(*check).load(Ordering::Acquire))
+            .collect()
+    }
+
+    /// Returns true if every check has passed
+    pub fn is_ready(&self) -> bool {
+        self.pending().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ready_once_all_checks_pass() {
+        let readiness = Readiness::new();
+        assert!(!readiness.is_ready());
+        assert_eq!(readiness.pending(), ReadinessCheck::ALL);
+
+        readiness.clone().pass(ReadinessCheck::UpstreamConnected);
+        assert_eq!(readiness.pending(), vec![ReadinessCheck::ServerCompatible]);
+
+        readiness.pass(ReadinessCheck::ServerCompatible);
+        assert!(readiness.is_ready());
+    }
+}
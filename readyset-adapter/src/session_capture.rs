@@ -0,0 +1,638 @@
+//! Capturing the bytes sent by clients, so that their sessions can be replayed when debugging
+//! protocol-level issues.
+//!
+//! When the adapter is run with `--capture-sessions <dir>`, the client's socket for each connection
+//! is wrapped in a [`CapturingStream`], which records everything the client sends to its own file
+//! in that directory. The bytes are split up into individual protocol messages, and authentication
+//! data is overwritten with zeroes before it's written, so captures never contain credentials.
+//! This means that replaying a captured session requires the target adapter to allow
+//! unauthenticated connections, and messages which consist only of authentication data (such as
+//! the PostgreSQL `PasswordMessage`) are skipped when replaying, since such an adapter doesn't ask
+//! for them.
+//!
+//! Captured sessions can be read back with [`read_capture`] and replayed against an adapter with
+//! [`replay`] (or with the `replay_session` tool).
+//!
+//! Each message in a capture is stored as its offset from the start of the session in
+//! milliseconds (as a big-endian `u64`), a byte which is 1 if the message was entirely redacted
+//! and 0 otherwise, its length in bytes (as a big-endian `u32`), then the bytes of the message
+//! itself.
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use database_utils::DatabaseType;
+use readyset_tracing::warn;
+use tokio::fs::File;
+use tokio::io::{
+    AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter, ReadBuf,
+};
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+/// The startup message codes for the PostgreSQL `SSLRequest` and `GSSENCRequest` messages, which
+/// are followed by another untagged startup message
+const PG_SSL_REQUEST: u32 = 80877103;
+const PG_GSSENC_REQUEST: u32 = 80877104;
+
+/// MySQL capability flags which determine the layout of the handshake response packet
+const CLIENT_PROTOCOL_41: u32 = 0x0000_0200;
+const CLIENT_SECURE_CONNECTION: u32 = 0x0000_8000;
+const CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA: u32 = 0x0020_0000;
+
+/// A single message sent by the client during a captured session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedMessage {
+    /// The time between the start of the session and the message being received
+    pub offset: Duration,
+    /// The (redacted) bytes of the message
+    pub bytes: Vec<u8>,
+    /// Whether the message consisted only of authentication data, and so was redacted entirely.
+    /// These messages are skipped by [`replay`].
+    pub redacted: bool,
+}
+
+/// Splits the bytes sent by a client into the individual messages of its wire protocol, redacting
+/// authentication data along the way
+struct MessageSplitter {
+    protocol: DatabaseType,
+    buf: Vec<u8>,
+    /// Whether the client is still authenticating. For PostgreSQL this means the next message is
+    /// an untagged startup message, and for MySQL that the client hasn't yet sent its first
+    /// command.
+    authenticating: bool,
+}
+
+impl MessageSplitter {
+    fn new(protocol: DatabaseType) -> Self {
+        Self {
+            protocol,
+            buf: Vec::new(),
+            authenticating: true,
+        }
+    }
+
+    /// Add `bytes` read from the client, returning all the messages which are now complete, along
+    /// with whether each one was redacted entirely
+    fn push(&mut self, bytes: &[u8]) -> Vec<(Vec<u8>, bool)> {
+        self.buf.extend_from_slice(bytes);
+        let mut messages = vec![];
+        while let Some(len) = self.next_message_len() {
+            if self.buf.len() < len {
+                break;
+            }
+            let mut message = self.buf.drain(..len).collect::<Vec<_>>();
+            let redacted = self.redact(&mut message);
+            messages.push((message, redacted));
+        }
+        messages
+    }
+
+    /// Take any bytes of a message which hasn't been completely received yet
+    fn take_partial(&mut self) -> Option<Vec<u8>> {
+        (!self.buf.is_empty()).then(|| std::mem::take(&mut self.buf))
+    }
+
+    /// Returns the length of the next message in the buffer, if enough of it has been received to
+    /// know
+    fn next_message_len(&self) -> Option<usize> {
+        match self.protocol {
+            DatabaseType::PostgreSQL if self.authenticating => {
+                let len = u32::from_be_bytes(self.buf.get(..4)?.try_into().ok()?);
+                Some((len as usize).max(4))
+            }
+            DatabaseType::PostgreSQL => {
+                let len = u32::from_be_bytes(self.buf.get(1..5)?.try_into().ok()?);
+                Some(1 + (len as usize).max(4))
+            }
+            DatabaseType::MySQL => {
+                let header = self.buf.get(..4)?;
+                Some(4 + u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize)
+            }
+        }
+    }
+
+    /// Overwrite any authentication data in `message` with zeroes, returning whether the message
+    /// consisted only of authentication data
+    fn redact(&mut self, message: &mut [u8]) -> bool {
+        match self.protocol {
+            DatabaseType::PostgreSQL if self.authenticating => {
+                let code = message
+                    .get(4..8)
+                    .and_then(|code| code.try_into().ok())
+                    .map(u32::from_be_bytes);
+                if !matches!(code, Some(PG_SSL_REQUEST | PG_GSSENC_REQUEST)) {
+                    self.authenticating = false;
+                }
+                false
+            }
+            DatabaseType::PostgreSQL => {
+                // Password, SASLInitialResponse, SASLResponse, and GSSResponse messages are all
+                // tagged with 'p'
+                if message[0] == b'p' {
+                    message[5..].fill(0);
+                    true
+                } else {
+                    false
+                }
+            }
+            DatabaseType::MySQL if self.authenticating => match message[3] {
+                // Commands always start a new sequence, so the first packet with a sequence ID of
+                // 0 is the first command sent after authenticating
+                0 => {
+                    self.authenticating = false;
+                    false
+                }
+                // The handshake response carries the connection's settings as well as the auth
+                // response, so it's needed to replay the session
+                1 => {
+                    redact_handshake_response(&mut message[4..]);
+                    false
+                }
+                _ => {
+                    message[4..].fill(0);
+                    true
+                }
+            },
+            DatabaseType::MySQL => false,
+        }
+    }
+}
+
+/// Overwrite the auth response in the payload of a MySQL handshake response packet with zeroes,
+/// or the entire payload if it can't be found
+fn redact_handshake_response(payload: &mut [u8]) {
+    match auth_response_range(payload) {
+        Some(range) => payload[range].fill(0),
+        None => payload.fill(0),
+    }
+}
+
+/// Returns the position of the auth response within the payload of a MySQL handshake response
+/// packet, or `None` if the packet is malformed or uses the pre-4.1 protocol
+fn auth_response_range(payload: &[u8]) -> Option<Range<usize>> {
+    let capabilities = u32::from_le_bytes(payload.get(..4)?.try_into().ok()?);
+    if capabilities & CLIENT_PROTOCOL_41 == 0 {
+        return None;
+    }
+
+    // The username follows the capability flags, max packet size, character set, and 23 bytes of
+    // filler
+    let username_start = 32;
+    let username_len = payload
+        .get(username_start..)?
+        .iter()
+        .position(|b| *b == 0)?;
+    let mut start = username_start + username_len + 1;
+    let len = if capabilities & CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA != 0 {
+        let (len, n) = lenenc_int(payload.get(start..)?)?;
+        start += n;
+        len
+    } else if capabilities & CLIENT_SECURE_CONNECTION != 0 {
+        let len = *payload.get(start)? as usize;
+        start += 1;
+        len
+    } else {
+        payload.get(start..)?.iter().position(|b| *b == 0)?
+    };
+
+    let end = start.checked_add(len)?;
+    (end <= payload.len()).then_some(start..end)
+}
+
+/// Parse a MySQL length-encoded integer, returning its value and the number of bytes it took up
+fn lenenc_int(buf: &[u8]) -> Option<(usize, usize)> {
+    match *buf.first()? {
+        n @ 0..=0xfa => Some((n as usize, 1)),
+        0xfc => Some((
+            u16::from_le_bytes(buf.get(1..3)?.try_into().ok()?) as usize,
+            3,
+        )),
+        0xfd => {
+            let b = buf.get(1..4)?;
+            Some((u32::from_le_bytes([b[0], b[1], b[2], 0]) as usize, 4))
+        }
+        0xfe => Some((
+            u64::from_le_bytes(buf.get(1..9)?.try_into().ok()?)
+                .try_into()
+                .ok()?,
+            9,
+        )),
+        _ => None,
+    }
+}
+
+/// A handle to the capture of a single client session, which records the messages sent by the
+/// client
+pub struct SessionCapture {
+    splitter: MessageSplitter,
+    start: Instant,
+    messages: mpsc::UnboundedSender<CapturedMessage>,
+}
+
+impl SessionCapture {
+    /// Start capturing a session, writing the captured messages to `writer`.
+    ///
+    /// Returns the capture along with a future which writes the messages, which must be run for
+    /// the capture to make progress. The future resolves (returning the writer) once the capture
+    /// has been dropped and all of its messages have been written.
+    pub fn new<W>(
+        writer: W,
+        protocol: DatabaseType,
+    ) -> (Self, impl Future<Output = io::Result<W>> + Send)
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (
+            Self {
+                splitter: MessageSplitter::new(protocol),
+                start: Instant::now(),
+                messages: tx,
+            },
+            write_messages(writer, rx),
+        )
+    }
+
+    /// Start capturing the session of the client connected from `peer_addr`, writing the captured
+    /// messages to a new file in `dir`
+    pub async fn create(
+        dir: &Path,
+        peer_addr: SocketAddr,
+        protocol: DatabaseType,
+    ) -> io::Result<Self> {
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let file_name = format!("{started_at}-{peer_addr}.capture").replace(':', "_");
+        let path = dir.join(file_name);
+        let file = File::create(&path).await?;
+
+        let (capture, write) = Self::new(BufWriter::new(file), protocol);
+        tokio::spawn(async move {
+            if let Err(error) = write.await {
+                warn!(%error, path = %path.display(), "Error writing session capture");
+            }
+        });
+        Ok(capture)
+    }
+
+    /// Record bytes read from the client
+    fn record(&mut self, bytes: &[u8]) {
+        for (message, redacted) in self.splitter.push(bytes) {
+            self.send(message, redacted);
+        }
+    }
+
+    fn send(&self, bytes: Vec<u8>, redacted: bool) {
+        // If the writer has failed, it's already logged the error
+        let _ = self.messages.send(CapturedMessage {
+            offset: self.start.elapsed(),
+            bytes,
+            redacted,
+        });
+    }
+}
+
+impl Drop for SessionCapture {
+    fn drop(&mut self) {
+        if let Some(partial) = self.splitter.take_partial() {
+            self.send(partial, false);
+        }
+    }
+}
+
+async fn write_messages<W>(
+    mut writer: W,
+    mut messages: mpsc::UnboundedReceiver<CapturedMessage>,
+) -> io::Result<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    while let Some(message) = messages.recv().await {
+        writer
+            .write_u64(message.offset.as_millis().try_into().unwrap_or(u64::MAX))
+            .await?;
+        writer.write_u8(message.redacted.into()).await?;
+        writer.write_u32(message.bytes.len() as u32).await?;
+        writer.write_all(&message.bytes).await?;
+    }
+    writer.flush().await?;
+    Ok(writer)
+}
+
+/// Read all the messages in a captured session from `reader`
+pub async fn read_messages<R>(mut reader: R) -> io::Result<Vec<CapturedMessage>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut messages = vec![];
+    loop {
+        let offset = match reader.read_u64().await {
+            Ok(offset) => offset,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+        let redacted = reader.read_u8().await? != 0;
+        let len = reader.read_u32().await?;
+        let mut bytes = vec![0; len as usize];
+        reader.read_exact(&mut bytes).await?;
+        messages.push(CapturedMessage {
+            offset: Duration::from_millis(offset),
+            bytes,
+            redacted,
+        });
+    }
+    Ok(messages)
+}
+
+/// Read all the messages in the captured session in the file at `path`
+pub async fn read_capture(path: impl AsRef<Path>) -> io::Result<Vec<CapturedMessage>> {
+    read_messages(BufReader::new(File::open(path).await?)).await
+}
+
+/// Replay the given captured messages over `stream`, returning the bytes received in response to
+/// each message.
+///
+/// After sending each message, everything the server sends is collected until it's been silent
+/// for `response_timeout` (or closes the connection), and the next message is then sent. Messages
+/// which were [`redacted`](CapturedMessage::redacted) entirely aren't sent, and have an empty
+/// response.
+pub async fn replay<S>(
+    stream: &mut S,
+    messages: &[CapturedMessage],
+    response_timeout: Duration,
+) -> io::Result<Vec<Vec<u8>>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut responses = Vec::with_capacity(messages.len());
+    let mut buf = [0; 8192];
+    for message in messages {
+        if message.redacted {
+            responses.push(vec![]);
+            continue;
+        }
+        stream.write_all(&message.bytes).await?;
+        stream.flush().await?;
+
+        let mut response = vec![];
+        while let Ok(n) = timeout(response_timeout, stream.read(&mut buf)).await {
+            match n? {
+                0 => break,
+                n => response.extend_from_slice(&buf[..n]),
+            }
+        }
+        responses.push(response);
+    }
+    Ok(responses)
+}
+
+/// A wrapper around an [`AsyncRead`] (and, optionally, [`AsyncWrite`]) which records the bytes
+/// read from it in a [`SessionCapture`], if given one
+pub struct CapturingStream<S> {
+    inner: S,
+    capture: Option<SessionCapture>,
+}
+
+impl<S> CapturingStream<S> {
+    /// Wrap the given stream, recording bytes read from it in `capture`
+    pub fn new(inner: S, capture: Option<SessionCapture>) -> Self {
+        Self { inner, capture }
+    }
+}
+
+impl<S> AsyncRead for CapturingStream<S>
+where
+    S: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let res = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let (Poll::Ready(Ok(())), Some(capture)) = (&res, &mut this.capture) {
+            capture.record(&buf.filled()[filled..]);
+        }
+        res
+    }
+}
+
+impl<S> AsyncWrite for CapturingStream<S>
+where
+    S: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use async_trait::async_trait;
+    use futures::stream;
+    use psql_srv::{Credentials, CredentialsNeeded, PrepareResponse, QueryResponse};
+    use tokio::io::duplex;
+
+    use super::*;
+
+    fn pg_message(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut message = vec![tag];
+        message.extend((body.len() as u32 + 4).to_be_bytes());
+        message.extend(body);
+        message
+    }
+
+    struct Value(psql_srv::Value);
+
+    impl TryFrom<Value> for psql_srv::Value {
+        type Error = psql_srv::Error;
+
+        fn try_from(v: Value) -> Result<Self, Self::Error> {
+            Ok(v.0)
+        }
+    }
+
+    /// A backend which responds to every query as an insert of one row, optionally asking clients
+    /// for a password
+    struct TestBackend {
+        password: bool,
+    }
+
+    #[async_trait]
+    impl psql_srv::Backend for TestBackend {
+        type Value = Value;
+        type Row = Vec<Value>;
+        type Resultset = stream::Iter<vec::IntoIter<Result<Self::Row, psql_srv::Error>>>;
+
+        fn version(&self) -> String {
+            "13.4 ReadySet".to_owned()
+        }
+
+        async fn on_init(&mut self, _database: &str) -> Result<CredentialsNeeded, psql_srv::Error> {
+            Ok(if self.password {
+                CredentialsNeeded::Cleartext
+            } else {
+                CredentialsNeeded::None
+            })
+        }
+
+        async fn on_auth(&mut self, _credentials: Credentials) -> Result<(), psql_srv::Error> {
+            Ok(())
+        }
+
+        async fn on_query(
+            &mut self,
+            _query: &str,
+        ) -> Result<QueryResponse<Self::Resultset>, psql_srv::Error> {
+            Ok(QueryResponse::Insert(1))
+        }
+
+        async fn on_prepare(&mut self, _query: &str) -> Result<PrepareResponse, psql_srv::Error> {
+            Err(psql_srv::Error::Unsupported("prepare".to_owned()))
+        }
+
+        async fn on_execute(
+            &mut self,
+            _statement_id: u32,
+            _params: &[psql_srv::Value],
+        ) -> Result<QueryResponse<Self::Resultset>, psql_srv::Error> {
+            Err(psql_srv::Error::Unsupported("execute".to_owned()))
+        }
+
+        async fn on_close(&mut self, _statement_id: u32) -> Result<(), psql_srv::Error> {
+            Ok(())
+        }
+    }
+
+    fn unredacted(bytes: Vec<u8>) -> CapturedMessage {
+        CapturedMessage {
+            offset: Duration::ZERO,
+            bytes,
+            redacted: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn capture_and_replay_psql_session() {
+        let mut startup = 196608u32.to_be_bytes().to_vec();
+        startup.extend(b"user\0alice\0database\0noria\0\0");
+        let mut startup_message = (startup.len() as u32 + 4).to_be_bytes().to_vec();
+        startup_message.extend(startup);
+        let messages = [
+            startup_message.clone(),
+            pg_message(b'p', b"hunter2\0"),
+            pg_message(b'Q', b"insert into t values (1)\0"),
+            pg_message(b'Q', b"insert into t values (2)\0"),
+            pg_message(b'X', b""),
+        ]
+        .map(unredacted);
+
+        let (capture, write) = SessionCapture::new(vec![], DatabaseType::PostgreSQL);
+        let (mut client, server) = duplex(1024);
+        let server = tokio::spawn(psql_srv::run_backend(
+            TestBackend { password: true },
+            CapturingStream::new(server, Some(capture)),
+        ));
+        let write = tokio::spawn(write);
+        let responses = replay(&mut client, &messages, Duration::from_millis(50))
+            .await
+            .unwrap();
+        drop(client);
+        server.await.unwrap();
+
+        let captured = read_messages(&write.await.unwrap().unwrap()[..])
+            .await
+            .unwrap();
+        assert_eq!(
+            captured
+                .iter()
+                .map(|message| (message.bytes.clone(), message.redacted))
+                .collect::<Vec<_>>(),
+            vec![
+                (startup_message, false),
+                (pg_message(b'p', &[0; 8]), true),
+                (pg_message(b'Q', b"insert into t values (1)\0"), false),
+                (pg_message(b'Q', b"insert into t values (2)\0"), false),
+                (pg_message(b'X', b""), false),
+            ]
+        );
+
+        // Replaying against a server which doesn't ask for a password skips the redacted password
+        // message, and gets the same responses to the rest of the session
+        let (mut client, server) = duplex(1024);
+        tokio::spawn(psql_srv::run_backend(
+            TestBackend { password: false },
+            server,
+        ));
+        let replayed = replay(&mut client, &captured, Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert!(replayed[1].is_empty());
+        assert_eq!(replayed[2..], responses[2..]);
+        assert!(!replayed[2].is_empty());
+    }
+
+    #[test]
+    fn splits_messages_across_reads() {
+        let mut splitter = MessageSplitter::new(DatabaseType::PostgreSQL);
+        splitter.authenticating = false;
+        let message = pg_message(b'Q', b"select 1\0");
+        assert!(splitter.push(&message[..3]).is_empty());
+        assert_eq!(
+            splitter.push(&[&message[3..], &message[..2]].concat()),
+            vec![(message.clone(), false)]
+        );
+        assert_eq!(splitter.take_partial(), Some(message[..2].to_vec()));
+    }
+
+    #[test]
+    fn redacts_mysql_auth_response() {
+        let capabilities = CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION;
+        let mut payload = capabilities.to_le_bytes().to_vec();
+        payload.extend([0; 28]);
+        payload.extend(b"root\0");
+        payload.push(4);
+        payload.extend(b"pass");
+        payload.extend(b"mysql_native_password\0");
+
+        let mut handshake_response = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+        handshake_response.push(1);
+        handshake_response.extend(&payload);
+        let mut query = vec![9, 0, 0, 0, 3];
+        query.extend(b"SELECT 1");
+
+        let mut splitter = MessageSplitter::new(DatabaseType::MySQL);
+        let messages = splitter.push(&[handshake_response.clone(), query.clone()].concat());
+
+        let mut redacted = handshake_response;
+        let pass_start = redacted.len() - b"pass".len() - b"mysql_native_password\0".len();
+        redacted[pass_start..pass_start + 4].fill(0);
+        assert_eq!(messages, vec![(redacted, false), (query, false)]);
+        assert!(!splitter.authenticating);
+    }
+}
@@ -0,0 +1,202 @@
+//! A pool of upstream database connections shared across client connections.
+//!
+//! Without a pool, every client connection opens a new connection to the upstream database when
+//! it's accepted, and holds it until the client disconnects, which is expensive for workloads with
+//! many clients. With a pool, a client only holds an upstream connection while it's running a
+//! statement: once the statement completes, the connection is returned to the pool and can be used
+//! by any other client, and the next statement the client runs checks out a connection again.
+//! Reads served from ReadySet's caches don't check out a connection at all.
+//!
+//! The pool never opens more than its configured number of connections: each connection holds a
+//! permit from the pool for as long as it's open, whether it's idle or checked out by a client,
+//! and clients wait for a connection to be released or closed once they've all been opened.
+//!
+//! Some state is scoped to the upstream connection it was created on, so clients are pinned to
+//! their connection (and hold it until they disconnect) once they create any: while they're in a
+//! transaction, once they prepare a statement upstream, or once they run a statement which may
+//! change the upstream session's state, such as `SET`, `USE`, any write (which may set
+//! `LAST_INSERT_ID()` or similar), or a `SELECT` that calls a function or assigns a variable.
+//! Idle connections in the pool are always clean: connections released by pinned clients have
+//! their session state reset in the background before they're returned to the pool (and are
+//! closed if that fails), and connections released in the middle of a transaction are closed
+//! (rolling back the transaction), so no state leaks from one client to another.
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use readyset_tracing::warn;
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+
+use crate::fallback_cache::FallbackCache;
+use crate::upstream_database::{ProxyOptions, UpstreamConfig, UpstreamDatabase};
+
+/// A pool of connections to the upstream database, shared by all of an adapter's clients
+pub struct UpstreamPool<DB: UpstreamDatabase> {
+    /// The configuration used to open new connections to the upstream database
+    upstream_config: UpstreamConfig,
+    proxy_options: ProxyOptions,
+    fallback_cache: Option<FallbackCache<DB::CachedReadResult>>,
+    /// One permit for each connection the pool may open
+    permits: Arc<Semaphore>,
+    idle: IdleConnections<PooledUpstream<DB>>,
+    /// Notified whenever a connection is returned to the pool
+    released: Notify,
+}
+
+/// A connection checked out of an [`UpstreamPool`], along with the permit which counts it against
+/// the pool's size. The permit is returned to the pool when it's dropped, so it must be held for as
+/// long as the connection is open.
+pub struct PooledUpstream<DB> {
+    pub upstream: DB,
+    pub permit: OwnedSemaphorePermit,
+}
+
+impl<DB> UpstreamPool<DB>
+where
+    DB: UpstreamDatabase,
+{
+    /// Create a new, empty pool of connections to the upstream database configured by
+    /// `upstream_config`, which will open at most `size` connections
+    pub fn new(
+        upstream_config: UpstreamConfig,
+        proxy_options: ProxyOptions,
+        fallback_cache: Option<FallbackCache<DB::CachedReadResult>>,
+        size: usize,
+    ) -> Self {
+        Self {
+            upstream_config,
+            proxy_options,
+            fallback_cache,
+            permits: Arc::new(Semaphore::new(size)),
+            idle: IdleConnections::new(size),
+            released: Notify::new(),
+        }
+    }
+
+    /// The URL of the upstream database the connections in the pool are connected to
+    pub fn url(&self) -> &str {
+        self.upstream_config
+            .upstream_db_url
+            .as_deref()
+            .map_or("", |url| url.as_str())
+    }
+
+    /// The number of idle connections currently in the pool
+    pub fn idle(&self) -> usize {
+        self.idle.len()
+    }
+
+    /// Check out a connection to the upstream database, reusing an idle connection from the pool
+    /// if there is one, and connecting a new one if the pool has fewer than its maximum number of
+    /// connections open. Otherwise, waits for another client to release or close a connection.
+    pub async fn checkout(&self) -> Result<PooledUpstream<DB>, DB::Error> {
+        loop {
+            if let Some(pooled) = self.idle.take() {
+                return Ok(pooled);
+            }
+
+            tokio::select! {
+                permit = self.permits.clone().acquire_owned() => {
+                    #[allow(clippy::expect_used)] // We never close the semaphore
+                    let permit = permit.expect("Upstream pool semaphore closed");
+                    let upstream = DB::connect(
+                        self.upstream_config.clone(),
+                        self.proxy_options.clone(),
+                        self.fallback_cache.clone(),
+                    )
+                    .await?;
+                    return Ok(PooledUpstream { upstream, permit });
+                }
+                _ = self.released.notified() => {}
+            }
+        }
+    }
+
+    /// Release a connection that's no longer being used by its client back into the pool, so it
+    /// can be reused by other clients.
+    ///
+    /// Connections which are `dirty`, because their client created session state on them, have
+    /// their session reset in the background before they're returned to the pool. Connections
+    /// released while `in_transaction` are pinned to the client that started the transaction, and
+    /// are closed instead of being reused, as are connections which aren't connected to the pool's
+    /// upstream database.
+    pub fn release(
+        self: &Arc<Self>,
+        pooled: PooledUpstream<DB>,
+        dirty: bool,
+        in_transaction: bool,
+    ) {
+        if in_transaction || pooled.upstream.url() != self.url() {
+            return;
+        }
+        if !dirty {
+            self.return_idle(pooled);
+            return;
+        }
+
+        let pool = Arc::clone(self);
+        let reset = async move {
+            let mut pooled = pooled;
+            match pooled.upstream.reset_session().await {
+                Ok(()) => pool.return_idle(pooled),
+                Err(error) => warn!(%error, "Closing pooled upstream connection"),
+            }
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(reset);
+            }
+            Err(_) => warn!("No runtime to reset pooled upstream connection; closing it"),
+        }
+    }
+
+    fn return_idle(&self, pooled: PooledUpstream<DB>) {
+        self.idle.release(pooled);
+        self.released.notify_one();
+    }
+}
+
+/// The idle connections in an [`UpstreamPool`]
+struct IdleConnections<T> {
+    conns: Mutex<Vec<T>>,
+}
+
+impl<T> IdleConnections<T> {
+    fn new(size: usize) -> Self {
+        Self {
+            conns: Mutex::new(Vec::with_capacity(size)),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.conns.lock().len()
+    }
+
+    fn take(&self) -> Option<T> {
+        self.conns.lock().pop()
+    }
+
+    fn release(&self, conn: T) {
+        self.conns.lock().push(conn);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_released_connections() {
+        let idle = IdleConnections::new(2);
+        assert_eq!(idle.take(), None);
+
+        idle.release(1);
+        idle.release(2);
+        assert_eq!(idle.len(), 2);
+
+        // The most recently released connection is reused first
+        assert_eq!(idle.take(), Some(2));
+        assert_eq!(idle.take(), Some(1));
+        assert_eq!(idle.take(), None);
+    }
+}
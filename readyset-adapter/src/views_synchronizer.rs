@@ -1,10 +1,13 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use dataflow_expression::Dialect;
 use readyset_client::query::MigrationState;
 use readyset_client::ReadySetHandle;
 use readyset_tracing::{debug, info, trace, warn};
-use tokio::select;
+use readyset_util::futures::run_until_cancelled;
+use tokio::sync::broadcast;
+use tokio::time::{interval_at, Instant};
 use tracing::instrument;
 
 use crate::query_status_cache::QueryStatusCache;
@@ -13,49 +16,143 @@ pub struct ViewsSynchronizer {
     /// The noria connector used to query
     controller: ReadySetHandle,
     /// The query status cache is updated according to which queries exist in noria
-    query_status_cache: &'static QueryStatusCache,
+    query_status_cache: Arc<QueryStatusCache>,
     /// The interval between subsequent pollings of the Leader for migrated queries
-    poll_interval: std::time::Duration,
+    poll_interval: PollInterval,
     /// Dialect to pass to ReadySet to control the expression semantics used for all queries
     dialect: Dialect,
-    /// Receiver to return the shutdown signal on
-    shutdown_recv: tokio::sync::broadcast::Receiver<()>,
+}
+
+/// The maximum number of consecutive polls at the initial interval which find queries still
+/// pending migration, before relaxing to the steady-state interval anyway. With explicit
+/// migrations, queries which are never cached stay pending forever, so without a limit we'd never
+/// leave the initial interval.
+const MAX_INITIAL_POLLS: u32 = 12;
+
+/// The interval between pollings of the Leader, which starts out at a short initial interval
+/// while there are queries pending migration, and relaxes to a longer steady-state interval once
+/// every pending query has been migrated, or after [`MAX_INITIAL_POLLS`] polls.
+#[derive(Debug, Clone, Copy)]
+struct PollInterval {
+    initial: Duration,
+    steady: Duration,
+    caught_up: bool,
+    /// The number of consecutive polls which found queries still pending migration
+    initial_polls: u32,
+    /// The interval currently being polled at
+    period: Duration,
+}
+
+impl PollInterval {
+    fn new(initial: Duration, steady: Duration) -> Self {
+        Self {
+            initial,
+            steady,
+            caught_up: false,
+            initial_polls: 0,
+            period: initial,
+        }
+    }
+
+    /// The current interval between pollings
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Whether polling has relaxed to the steady-state interval, either because the last poll
+    /// found that all pending queries had been migrated, or because we gave up waiting for them
+    fn relaxed(&self) -> bool {
+        self.caught_up || self.initial_polls >= MAX_INITIAL_POLLS
+    }
+
+    /// Restart polling at the initial interval, because new queries are pending migration
+    fn restart(&mut self) {
+        self.initial_polls = 0;
+    }
+
+    /// Record whether the last poll found that all pending queries had been migrated, returning
+    /// the new interval between pollings if it changed as a result
+    fn record_poll(&mut self, caught_up: bool) -> Option<Duration> {
+        self.caught_up = caught_up;
+        self.initial_polls = if caught_up {
+            0
+        } else {
+            self.initial_polls.saturating_add(1)
+        };
+        let period = if self.relaxed() {
+            self.steady
+        } else {
+            self.initial
+        };
+        if period == self.period {
+            return None;
+        }
+        self.period = period;
+        Some(period)
+    }
 }
 
 impl ViewsSynchronizer {
+    /// Create a new [`ViewsSynchronizer`], which polls the Leader every `initial_poll_interval`
+    /// while there are queries pending migration, and every `poll_interval` once all pending
+    /// queries have been migrated.
     pub fn new(
         controller: ReadySetHandle,
-        query_status_cache: &'static QueryStatusCache,
-        poll_interval: std::time::Duration,
+        query_status_cache: Arc<QueryStatusCache>,
+        initial_poll_interval: Duration,
+        poll_interval: Duration,
         dialect: Dialect,
-        shutdown_recv: tokio::sync::broadcast::Receiver<()>,
     ) -> Self {
         ViewsSynchronizer {
             controller,
             query_status_cache,
-            poll_interval,
+            poll_interval: PollInterval::new(initial_poll_interval, poll_interval),
             dialect,
-            shutdown_recv,
         }
     }
 
     //TODO(DAN): add metrics on views synchronizer performance (e.g., number of queries polled,
     //time spent processing)
-    #[instrument(level = "info", name = "views_synchronizer", skip(self))]
-    pub async fn run(&mut self) {
-        let mut interval = tokio::time::interval(self.poll_interval);
-        loop {
-            select! {
-                _ = interval.tick() => self.poll().await,
-                _ = self.shutdown_recv.recv() => {
-                    info!("Views Synchronizer shutting down after shut down signal received");
-                    break;
+    /// Poll the Leader for migrated queries until a shutdown signal is received on `shutdown_recv`.
+    ///
+    /// Once polling has relaxed to the steady-state interval, queries which newly become pending
+    /// are polled for immediately rather than at the next steady-state polling, which also
+    /// switches back to the initial polling interval until they've been migrated.
+    #[instrument(level = "info", name = "views_synchronizer", skip_all)]
+    pub async fn run(&mut self, shutdown_recv: broadcast::Receiver<()>) {
+        let mut interval = tokio::time::interval(self.poll_interval.period());
+        let query_status_cache = self.query_status_cache.clone();
+        run_until_cancelled(
+            async {
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {}
+                        _ = query_status_cache.pending_migration_added(),
+                            if self.poll_interval.relaxed() =>
+                        {
+                            debug!("New queries pending migration; polling immediately");
+                            self.poll_interval.restart();
+                        }
+                    }
+                    let caught_up = self.poll().await;
+                    if let Some(period) = self.poll_interval.record_poll(caught_up) {
+                        debug!(
+                            ?period,
+                            caught_up, "Changing views synchronizer polling interval"
+                        );
+                        interval = interval_at(Instant::now() + period, period);
+                    }
                 }
-            }
-        }
+            },
+            shutdown_recv,
+        )
+        .await;
+        info!("Views Synchronizer shutting down after shut down signal received");
     }
 
-    async fn poll(&mut self) {
+    /// Poll the Leader for the migration status of all queries pending migration, returning
+    /// whether all of them have now been migrated
+    async fn poll(&mut self) -> bool {
         debug!("Views synchronizer polling");
         let queries = self
             .query_status_cache
@@ -70,6 +167,7 @@ impl ViewsSynchronizer {
             .await
         {
             Ok(statuses) => {
+                let mut caught_up = true;
                 for (query, migrated) in queries.into_iter().zip(statuses) {
                     trace!(
                         query = %query.statement,
@@ -79,10 +177,61 @@ impl ViewsSynchronizer {
                     if migrated {
                         self.query_status_cache
                             .update_query_migration_state(&query, MigrationState::Successful)
+                    } else {
+                        caught_up = false;
                     }
                 }
+                caught_up
+            }
+            Err(error) => {
+                warn!(%error, "Could not get view statuses from leader");
+                false
             }
-            Err(error) => warn!(%error, "Could not get view statuses from leader"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poll_interval_relaxes_once_caught_up() {
+        let initial = Duration::from_secs(1);
+        let steady = Duration::from_secs(300);
+        let mut interval = PollInterval::new(initial, steady);
+
+        // Polls quickly until all pending queries have been migrated
+        assert_eq!(interval.period(), initial);
+        assert_eq!(interval.record_poll(false), None);
+        assert_eq!(interval.period(), initial);
+
+        // Then relaxes to the steady interval
+        assert_eq!(interval.record_poll(true), Some(steady));
+        assert_eq!(interval.period(), steady);
+        assert_eq!(interval.record_poll(true), None);
+
+        // And speeds back up if new queries are found pending migration
+        assert_eq!(interval.record_poll(false), Some(initial));
+        assert_eq!(interval.period(), initial);
+    }
+
+    #[test]
+    fn poll_interval_relaxes_if_queries_stay_pending() {
+        let initial = Duration::from_secs(1);
+        let steady = Duration::from_secs(300);
+        let mut interval = PollInterval::new(initial, steady);
+
+        for _ in 1..MAX_INITIAL_POLLS {
+            assert_eq!(interval.record_poll(false), None);
+        }
+        assert_eq!(interval.record_poll(false), Some(steady));
+        assert!(interval.relaxed());
+        assert_eq!(interval.record_poll(false), None);
+
+        // New pending queries restart polling at the initial interval
+        interval.restart();
+        assert_eq!(interval.record_poll(false), Some(initial));
+        assert!(!interval.relaxed());
+    }
+}
@@ -0,0 +1,123 @@
+//! Starting and stopping the adapter repeatedly within a single process (as tests do) must free the
+//! state it shares between its tasks each time, rather than leaking it.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use health_reporter::HealthReporter;
+use hyper::{Body, Request};
+use readyset_adapter::http_router::NoriaAdapterHttpRouter;
+use readyset_adapter::proxied_queries_reporter::ProxiedQueriesReporter;
+use readyset_adapter::query_status_cache::{MigrationStyle, QueryStatusCache};
+use readyset_adapter::readiness::Readiness;
+use readyset_client::query::Query;
+use readyset_telemetry_reporter::{PeriodicReport, TelemetryInitializer};
+use stream_cancel::Valve;
+use tower::Service;
+
+/// The number of queries recorded in the query status cache each time the adapter is started
+const QUERIES: usize = 1000;
+
+/// How much the number of bytes allocated may vary between runs without being considered a leak.
+/// Leaking the query status cache alone would leak far more than this.
+const SLACK_BYTES: usize = 16 * 1024;
+
+/// A global allocator which keeps track of the number of bytes currently allocated
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Build the state that the adapter shares between its tasks, use it the way those tasks do, then
+/// shut everything down, asserting that the shared state has been freed
+fn start_and_stop() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let query_status_cache = Arc::new(QueryStatusCache::with_style(MigrationStyle::Explicit));
+    let weak = Arc::downgrade(&query_status_cache);
+
+    rt.block_on(async {
+        for i in 0..QUERIES {
+            query_status_cache.insert(Query::ParseFailed(Arc::new(format!(
+                "SELECT {i} FROM unparseable"
+            ))));
+        }
+
+        let (telemetry_sender, mut reporter) = TelemetryInitializer::test_init();
+        let proxied_queries_reporter =
+            Arc::new(ProxiedQueriesReporter::new(query_status_cache.clone()));
+        reporter
+            .register_periodic_reporter(proxied_queries_reporter.clone())
+            .await;
+        assert_eq!(
+            proxied_queries_reporter.report().await.unwrap().len(),
+            QUERIES
+        );
+
+        let (trigger, valve) = Valve::new();
+        let mut router = NoriaAdapterHttpRouter {
+            listen_addr: "127.0.0.1:0".parse().unwrap(),
+            query_cache: query_status_cache.clone(),
+            valve,
+            health_reporter: HealthReporter::new(),
+            failpoint_channel: None,
+            prometheus_handle: None,
+            periodic_reporters: None,
+            cancel_registry: None,
+            readiness: Readiness::new(),
+        };
+        let res = router
+            .call(Request::get("/deny-list").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+
+        drop((
+            trigger,
+            router,
+            reporter,
+            telemetry_sender,
+            proxied_queries_reporter,
+        ));
+    });
+    drop(rt);
+    drop(query_status_cache);
+
+    assert!(weak.upgrade().is_none(), "Query status cache was leaked");
+}
+
+#[test]
+fn repeated_startup_does_not_leak() {
+    // The first run initializes process-wide state which is never freed, such as the logger
+    start_and_stop();
+    let baseline = ALLOCATED.load(Ordering::Relaxed);
+
+    for _ in 0..2 {
+        start_and_stop();
+        let allocated = ALLOCATED.load(Ordering::Relaxed);
+        assert!(
+            allocated <= baseline + SLACK_BYTES,
+            "Allocated memory grew from {baseline} to {allocated} bytes"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! Capping the cardinality of metric labels whose values are chosen by clients.
+//!
+//! Every distinct set of labels creates a new time series, so labels whose values come from
+//! clients - such as application names, or tags extracted from query comments - are passed through
+//! a [`BoundedLabelValues`], which truncates long values and, once a fixed number of distinct
+//! values have been seen, maps any other value to [`OTHER_LABEL_VALUE`].
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// The label value used in place of values seen after the maximum number of distinct values
+pub const OTHER_LABEL_VALUE: &str = "other";
+
+/// The set of values seen so far for a single client-controlled label, used to cap the number of
+/// distinct values we emit for it
+pub struct BoundedLabelValues {
+    /// The maximum number of distinct values
+    max_values: usize,
+    /// The maximum length of each value, in bytes. Longer values are truncated.
+    max_len: usize,
+    seen: Mutex<HashSet<String>>,
+}
+
+impl BoundedLabelValues {
+    /// Create a new, empty set of label values, which will keep at most `max_values` distinct
+    /// values, each at most `max_len` bytes long
+    pub fn new(max_values: usize, max_len: usize) -> Self {
+        Self {
+            max_values,
+            max_len,
+            seen: Default::default(),
+        }
+    }
+
+    /// Returns the label value to use in place of the given value
+    pub fn label(&self, value: &str) -> String {
+        let mut end = value.len().min(self.max_len);
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        let value = &value[..end];
+
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        if seen.contains(value) {
+            return value.to_owned();
+        }
+        if seen.len() >= self.max_values {
+            return OTHER_LABEL_VALUE.to_owned();
+        }
+        seen.insert(value.to_owned());
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_values_are_capped() {
+        let values = BoundedLabelValues::new(2, 64);
+        assert_eq!(values.label("a"), "a");
+        assert_eq!(values.label("b"), "b");
+        assert_eq!(values.label("c"), OTHER_LABEL_VALUE);
+        assert_eq!(values.label("a"), "a");
+    }
+
+    #[test]
+    fn long_label_values_are_truncated() {
+        let values = BoundedLabelValues::new(32, 64);
+        let long = "é".repeat(64);
+        let label = values.label(&long);
+        assert!(label.len() <= 64);
+        assert!(long.starts_with(&label));
+    }
+}
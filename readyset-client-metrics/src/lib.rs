@@ -9,6 +9,7 @@ use readyset_client::query::QueryId;
 use readyset_client::ReadySetError;
 use serde::Serialize;
 
+pub mod labels;
 pub mod recorded;
 
 #[derive(Debug, Serialize, Clone)]
@@ -44,6 +45,10 @@ pub struct QueryExecutionEvent {
 
     /// Number of cache misses which occurred as part of a query
     pub cache_misses: Option<u64>,
+
+    /// The value of the tag extracted from the query's comments, if the adapter is configured to
+    /// extract one and the query was tagged
+    pub query_tag: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Eq, Serialize, Clone, Copy, Default)]
@@ -162,6 +167,7 @@ impl QueryExecutionEvent {
             destination: None,
             cache_misses: None,
             num_keys: None,
+            query_tag: None,
         }
     }
 
@@ -180,6 +186,14 @@ impl QueryExecutionEvent {
     pub fn set_noria_error(&mut self, error: &ReadySetError) {
         self.noria_error = Some(error.clone());
     }
+
+    /// How long the query took to execute: the longer of the time it spent executing on ReadySet
+    /// and on the upstream database
+    pub fn execution_duration(&self) -> Duration {
+        self.readyset_duration
+            .unwrap_or_default()
+            .max(self.upstream_duration.unwrap_or_default())
+    }
 }
 
 /// A handle to updating the durations in a `QueryExecutionEvent`. Once dropped,
@@ -45,6 +45,19 @@ pub const QUERY_LOG_TOTAL_CACHE_MISSES: &str = "query-log.total_cache_misses";
 /// | query | The query text being executed. |
 pub const QUERY_LOG_QUERY_CACHE_MISSED: &str = "query-log.query_cache_missed";
 
+/// Counter: The number of query log records which were dropped because the query logger's backlog
+/// was full.
+pub const QUERY_LOG_EVENTS_DROPPED: &str = "query-log.events_dropped";
+
+/// Counter: The number of times a query took longer than the slow query threshold
+/// (`--slow-query-threshold-ms`) to execute.
+///
+/// | Tag | Description |
+/// | --- | ----------- |
+/// | query | The query text being executed. |
+/// | query_id | The ID of the query being executed, if known. |
+pub const QUERY_LOG_SLOW_QUERIES: &str = "query-log.slow_queries";
+
 /// Counter: The number of successful queries (dry runs/real) processed by the migration handler.
 pub const MIGRATION_HANDLER_SUCCESSES: &str = "migration-handler.successes";
 
@@ -62,3 +75,32 @@ pub const ADAPTER_EXTERNAL_REQUESTS: &str = "noria-client.external_requests";
 
 /// Gauge: The number of currently connected SQL clients
 pub const CONNECTED_CLIENTS: &str = "noria-client.connected_clients";
+
+/// Gauge: The number of currently connected SQL clients which sent an application name, labelled
+/// by that name.
+///
+/// | Tag | Description |
+/// | --- | ----------- |
+/// | application_name | The client's application name, or "other" once too many distinct names have been seen |
+pub const CONNECTED_CLIENTS_BY_APPLICATION: &str = "noria-client.connected_clients_by_application";
+
+/// Gauge: The number of distinct query shapes currently tracked in the adapter's query status
+/// cache, including queries which failed to parse
+pub const CACHED_QUERY_SHAPES: &str = "readyset_cached_query_shapes";
+
+/// Histogram: The time in seconds spent waiting to acquire one of the locks shared between all
+/// connections to the adapter. Only recorded if the adapter was started with
+/// `--instrument-lock-contention`.
+///
+/// | Tag | Description |
+/// | --- | ----------- |
+/// | lock | The name of the shared structure being locked, eg `query_cache` or `auto_increments` |
+pub const LOCK_WAIT_TIME: &str = "noria-client.lock_wait_time";
+
+/// Counter: The number of telemetry events which were dropped without being sent, because they
+/// couldn't be added to the telemetry reporter's queue (eg because the queue was full).
+pub const TELEMETRY_EVENTS_DROPPED: &str = "telemetry-reporter.events_dropped";
+
+/// Gauge: The number of telemetry events currently waiting in the telemetry reporter's queue to be
+/// sent.
+pub const TELEMETRY_QUEUE_DEPTH: &str = "telemetry-reporter.queue_depth";
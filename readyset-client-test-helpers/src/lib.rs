@@ -11,7 +11,8 @@ use nom_sql::Relation;
 use readyset_adapter::backend::noria_connector::{NoriaConnector, ReadBehavior};
 use readyset_adapter::backend::{BackendBuilder, MigrationMode};
 use readyset_adapter::query_status_cache::QueryStatusCache;
-use readyset_adapter::{Backend, QueryHandler, UpstreamConfig, UpstreamDatabase};
+use readyset_adapter::upstream_pool::UpstreamPool;
+use readyset_adapter::{Backend, ProxyOptions, QueryHandler, UpstreamConfig, UpstreamDatabase};
 use readyset_client::consensus::{Authority, LocalAuthorityStore};
 use readyset_client::ViewCreateRequest;
 use readyset_server::{Builder, Handle, LocalAuthority, ReadySetHandle};
@@ -34,13 +35,13 @@ pub trait Adapter: Send {
 
     const DIALECT: nom_sql::Dialect;
 
-    const EXPR_DIALECT: readyset_data::Dialect;
+    const EXPR_DIALECT: readyset_data::Dialect = readyset_data::Dialect::default_for(Self::DIALECT);
 
     fn connection_opts_with_port(port: u16) -> Self::ConnectionOpts;
     fn url() -> String;
 
-    async fn make_upstream(addr: String) -> Self::Upstream {
-        Self::Upstream::connect(UpstreamConfig::from_url(addr), None)
+    async fn make_upstream(addr: String, proxy_options: ProxyOptions) -> Self::Upstream {
+        Self::Upstream::connect(UpstreamConfig::from_url(addr), proxy_options, None)
             .await
             .unwrap()
     }
@@ -58,12 +59,14 @@ pub struct TestBuilder {
     backend_builder: BackendBuilder,
     fallback: bool,
     fallback_url: Option<String>,
+    proxy_options: ProxyOptions,
     partial: bool,
     wait_for_backend: bool,
     read_behavior: ReadBehavior,
     migration_mode: MigrationMode,
     recreate_database: bool,
-    query_status_cache: Option<&'static QueryStatusCache>,
+    query_status_cache: Option<Arc<QueryStatusCache>>,
+    upstream_pool_size: Option<usize>,
 }
 
 impl Default for TestBuilder {
@@ -78,12 +81,14 @@ impl TestBuilder {
             backend_builder,
             fallback: false,
             fallback_url: None,
+            proxy_options: ProxyOptions::default(),
             partial: true,
             wait_for_backend: true,
             read_behavior: ReadBehavior::Blocking,
             migration_mode: MigrationMode::InRequestPath,
             recreate_database: true,
             query_status_cache: None,
+            upstream_pool_size: None,
         }
     }
 
@@ -98,6 +103,12 @@ impl TestBuilder {
         self
     }
 
+    /// Configure how queries are proxied to the fallback database
+    pub fn proxy_options(mut self, proxy_options: ProxyOptions) -> Self {
+        self.proxy_options = proxy_options;
+        self
+    }
+
     pub fn partial(mut self, partial: bool) -> Self {
         self.partial = partial;
         self
@@ -118,11 +129,18 @@ impl TestBuilder {
         self
     }
 
-    pub fn query_status_cache(mut self, query_status_cache: &'static QueryStatusCache) -> Self {
+    pub fn query_status_cache(mut self, query_status_cache: Arc<QueryStatusCache>) -> Self {
         self.query_status_cache = Some(query_status_cache);
         self
     }
 
+    /// Share connections to the fallback database between clients through an [`UpstreamPool`]
+    /// which keeps at most `size` idle connections
+    pub fn upstream_pool_size(mut self, size: usize) -> Self {
+        self.upstream_pool_size = Some(size);
+        self
+    }
+
     pub async fn build<A>(self) -> (A::ConnectionOpts, Handle)
     where
         A: Adapter + 'static,
@@ -134,7 +152,7 @@ impl TestBuilder {
 
         let query_status_cache = self
             .query_status_cache
-            .unwrap_or_else(|| Box::leak(Box::new(QueryStatusCache::new())));
+            .unwrap_or_else(|| Arc::new(QueryStatusCache::new()));
 
         let fallback_url = self
             .fallback
@@ -168,6 +186,16 @@ impl TestBuilder {
         let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
         let addr = listener.local_addr().unwrap();
 
+        let upstream_pool = match (&fallback_url, self.upstream_pool_size) {
+            (Some(f), Some(size)) => Some(Arc::new(UpstreamPool::<A::Upstream>::new(
+                UpstreamConfig::from_url(f),
+                self.proxy_options.clone(),
+                None,
+                size,
+            ))),
+            _ => None,
+        };
+
         tokio::spawn(async move {
             loop {
                 let (s, _) = listener.accept().await.unwrap();
@@ -177,8 +205,13 @@ impl TestBuilder {
                 let authority = authority.clone();
 
                 // backend either has upstream or noria writer
-                let mut upstream = if let Some(f) = &fallback_url {
-                    Some(A::make_upstream(f.clone()).await)
+                let mut upstream_permit = None;
+                let mut upstream = if let Some(pool) = &upstream_pool {
+                    let pooled = pool.checkout().await.unwrap();
+                    upstream_permit = Some(pooled.permit);
+                    Some(pooled.upstream)
+                } else if let Some(f) = &fallback_url {
+                    Some(A::make_upstream(f.clone(), self.proxy_options.clone()).await)
                 } else {
                     None
                 };
@@ -202,10 +235,13 @@ impl TestBuilder {
                 )
                 .await;
 
-                let backend = backend_builder
+                let mut backend = backend_builder
                     .dialect(A::DIALECT)
                     .migration_mode(self.migration_mode)
-                    .build(noria, upstream, query_status_cache);
+                    .build(noria, upstream, query_status_cache.clone());
+                if let Some(pool) = &upstream_pool {
+                    backend = backend.with_upstream_pool(pool.clone(), upstream_permit);
+                }
 
                 tokio::spawn(A::run_backend(backend, s));
             }
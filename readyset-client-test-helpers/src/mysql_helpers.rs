@@ -68,8 +68,6 @@ impl Adapter for MySQLAdapter {
 
     const DIALECT: nom_sql::Dialect = nom_sql::Dialect::MySQL;
 
-    const EXPR_DIALECT: readyset_data::Dialect = readyset_data::Dialect::DEFAULT_MYSQL;
-
     fn connection_opts_with_port(port: u16) -> Self::ConnectionOpts {
         mysql_async::OptsBuilder::default().tcp_port(port).into()
     }
@@ -86,7 +84,10 @@ impl Adapter for MySQLAdapter {
         backend: readyset_adapter::Backend<Self::Upstream, Self::Handler>,
         s: TcpStream,
     ) {
-        MySqlIntermediary::run_on_tcp(Backend::new(backend), s)
+        s.set_nodelay(true).unwrap();
+        let (reader, writer) = s.into_split();
+        let reader = backend.drainable_stream(reader);
+        MySqlIntermediary::run_on(Backend::new(backend), reader, writer)
             .await
             .unwrap()
     }
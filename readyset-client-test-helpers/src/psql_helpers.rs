@@ -38,8 +38,6 @@ impl Adapter for PostgreSQLAdapter {
 
     const DIALECT: nom_sql::Dialect = nom_sql::Dialect::PostgreSQL;
 
-    const EXPR_DIALECT: readyset_data::Dialect = readyset_data::Dialect::DEFAULT_POSTGRESQL;
-
     fn connection_opts_with_port(port: u16) -> Self::ConnectionOpts {
         let mut config = tokio_postgres::Config::new();
         config.host("127.0.0.1").port(port).dbname("noria");
@@ -70,6 +68,7 @@ impl Adapter for PostgreSQLAdapter {
     }
 
     async fn run_backend(backend: Backend<Self::Upstream, Self::Handler>, s: TcpStream) {
+        let s = backend.drainable_stream(s);
         psql_srv::run_backend(readyset_psql::Backend(backend), s).await
     }
 }
@@ -1016,6 +1016,15 @@ impl AuthorityControl for ConsulAuthority {
         }
     }
 
+    async fn adapter_heartbeat(
+        &self,
+        id: AdapterId,
+    ) -> Result<AuthorityWorkerHeartbeatResponse, Error> {
+        // Adapters are registered under their session, so renewing the session keeps the
+        // registration alive
+        self.worker_heartbeat(id).await
+    }
+
     async fn get_adapters(&self) -> Result<HashSet<SocketAddr>, Error> {
         set_failpoint!(failpoints::AUTHORITY, |_| bail!(
             ConsulAuthorityError::RequestFailed("authority->server failure injected".to_string())
@@ -20,7 +20,7 @@ use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLock
 
 use anyhow::{anyhow, bail, Error};
 use async_trait::async_trait;
-use readyset_errors::internal_err;
+use readyset_errors::{internal, internal_err};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
@@ -438,6 +438,13 @@ impl AuthorityControl for LocalAuthority {
         todo!();
     }
 
+    async fn adapter_heartbeat(
+        &self,
+        _: AdapterId,
+    ) -> Result<AuthorityWorkerHeartbeatResponse, Error> {
+        internal!("LocalAuthority does not support `adapter_heartbeat`.");
+    }
+
     async fn get_adapters(&self) -> Result<HashSet<SocketAddr>, Error> {
         todo!();
     }
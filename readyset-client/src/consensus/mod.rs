@@ -218,6 +218,13 @@ pub trait AuthorityControl: Send + Sync {
     /// Register an adapters http port.
     async fn register_adapter(&self, endpoint: SocketAddr) -> Result<Option<AdapterId>, Error>;
 
+    /// Adapters run this function on a regular cadence to confirm that their registration, with
+    /// the id returned by [`register_adapter`](Self::register_adapter), is still present.
+    async fn adapter_heartbeat(
+        &self,
+        id: AdapterId,
+    ) -> Result<AuthorityWorkerHeartbeatResponse, Error>;
+
     /// Retrieves the current set of adapter endpoints from the authority.
     async fn get_adapters(&self) -> Result<HashSet<SocketAddr>, Error>;
 }
@@ -267,21 +274,34 @@ impl ToString for AuthorityType {
 }
 
 impl AuthorityType {
+    /// Connect to the authority of this type at `addr`, for the given `deployment`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if connecting to the authority fails. See [`try_to_authority`] for a fallible
+    /// version of this function.
+    ///
+    /// [`try_to_authority`]: AuthorityType::try_to_authority
     pub async fn to_authority(&self, addr: &str, deployment: &str) -> Authority {
-        match self {
-            AuthorityType::Zookeeper => Authority::from(
-                ZookeeperAuthority::new(&format!("{}/{}", addr, deployment))
-                    .await
-                    .unwrap(),
-            ),
-            AuthorityType::Consul => Authority::from(
-                ConsulAuthority::new(&format!("http://{}/{}", addr, deployment)).unwrap(),
-            ),
+        self.try_to_authority(addr, deployment).await.unwrap()
+    }
+
+    /// Connect to the authority of this type at `addr`, for the given `deployment`, returning an
+    /// error if connecting to the authority fails
+    pub async fn try_to_authority(&self, addr: &str, deployment: &str) -> Result<Authority, Error> {
+        Ok(match self {
+            AuthorityType::Zookeeper => {
+                Authority::from(ZookeeperAuthority::new(&format!("{}/{}", addr, deployment)).await?)
+            }
+            AuthorityType::Consul => Authority::from(ConsulAuthority::new(&format!(
+                "http://{}/{}",
+                addr, deployment
+            ))?),
             AuthorityType::Local => Authority::from(LocalAuthority::new()),
             AuthorityType::Standalone => {
-                Authority::from(StandaloneAuthority::new(addr, deployment).unwrap())
+                Authority::from(StandaloneAuthority::new(addr, deployment)?)
             }
-        }
+        })
     }
 }
 
@@ -263,6 +263,13 @@ impl AuthorityControl for StandaloneAuthority {
         internal!("StandaloneAuthority does not support `register_adapter`.");
     }
 
+    async fn adapter_heartbeat(
+        &self,
+        _: AdapterId,
+    ) -> Result<AuthorityWorkerHeartbeatResponse, Error> {
+        internal!("StandaloneAuthority does not support `adapter_heartbeat`.");
+    }
+
     /// Retrieves the current set of adapter endpoints from the authority.
     async fn get_adapters(&self) -> Result<HashSet<SocketAddr>, Error> {
         internal!("StandaloneAuthority does not support `get_adapters`.");
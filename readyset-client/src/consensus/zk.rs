@@ -30,6 +30,8 @@ pub const CONTROLLER_KEY: &str = "/controller";
 pub const STATE_KEY: &str = "/state";
 pub const WORKER_PATH: &str = "/workers";
 pub const WORKER_PREFIX: &str = "/workers/guid-";
+pub const ADAPTER_PATH: &str = "/adapters";
+pub const ADAPTER_PREFIX: &str = "/adapters/adapter-";
 const BACKOFF_MAX_TIME: Duration = Duration::from_secs(10);
 
 struct EventWatcher;
@@ -45,6 +47,7 @@ impl Watcher for EventWatcher {
 struct ZookeeperAuthorityInner {
     leader_create_epoch: Option<i64>,
     worker_id: Option<WorkerId>,
+    adapter_id: Option<AdapterId>,
 }
 
 /// Coordinator that shares connection information between workers and clients using ZooKeeper.
@@ -65,6 +68,17 @@ fn worker_id_to_path(id: &str) -> String {
     WORKER_PREFIX.to_owned() + id
 }
 
+fn path_to_adapter_id(path: &str) -> AdapterId {
+    // See `adapter_id_to_path` for the type of path this is called on. Unlike worker ids, adapter
+    // ids keep the `adapter-` prefix of the node name.
+    #[allow(clippy::unwrap_used)]
+    path[(path.rfind('/').unwrap() + 1)..].to_owned()
+}
+
+fn adapter_id_to_path(id: &str) -> String {
+    format!("{ADAPTER_PATH}/{id}")
+}
+
 impl ZookeeperAuthority {
     async fn new_with_inner(
         connect_string: &str,
@@ -119,6 +133,7 @@ impl ZookeeperAuthority {
         let inner = Some(RwLock::new(ZookeeperAuthorityInner {
             leader_create_epoch: None,
             worker_id: None,
+            adapter_id: None,
         }));
         Self::new_with_inner(connect_string, inner).await
     }
@@ -396,8 +411,7 @@ impl AuthorityControl for ZookeeperAuthority {
         &self,
         id: WorkerId,
     ) -> Result<AuthorityWorkerHeartbeatResponse, Error> {
-        let path = worker_id_to_path(&id);
-        Ok(match self.zk.exists(&path, false).await {
+        Ok(match self.zk.exists(&worker_id_to_path(&id), false).await {
             Ok(Some(_)) => AuthorityWorkerHeartbeatResponse::Alive,
             _ => AuthorityWorkerHeartbeatResponse::Failed,
         })
@@ -430,12 +444,82 @@ impl AuthorityControl for ZookeeperAuthority {
         Ok(worker_descriptors)
     }
 
-    async fn register_adapter(&self, _: SocketAddr) -> Result<Option<AdapterId>, Error> {
-        todo!();
+    async fn register_adapter(&self, endpoint: SocketAddr) -> Result<Option<AdapterId>, Error> {
+        let data = serde_json::to_vec(&endpoint)?;
+
+        // If we've already registered, update our endpoint in place in case it's changed
+        let adapter_id = self.read_inner()?.adapter_id.clone();
+        if let Some(adapter_id) = adapter_id {
+            match self
+                .zk
+                .set_data(&adapter_id_to_path(&adapter_id), data.clone(), None)
+                .await
+            {
+                Ok(_) => return Ok(Some(adapter_id)),
+                // Our node was removed along with our session, so register again
+                Err(ZkError::NoNode) => {}
+                Err(e) => bail!(e),
+            }
+        }
+
+        // Attempt to create the base path in case we are the first adapter.
+        let _ = self
+            .zk
+            .create(
+                ADAPTER_PATH,
+                Vec::new(),
+                Acl::open_unsafe().clone(),
+                CreateMode::Persistent,
+            )
+            .await;
+
+        // Each adapter is associated with an ephemeral node, which is removed when the adapter's
+        // session with ZooKeeper ends
+        let path = self
+            .zk
+            .create(
+                ADAPTER_PREFIX,
+                data,
+                Acl::open_unsafe().clone(),
+                CreateMode::EphemeralSequential,
+            )
+            .await?;
+        let adapter_id = path_to_adapter_id(&path);
+        self.write_inner()?.adapter_id = Some(adapter_id.clone());
+        Ok(Some(adapter_id))
+    }
+
+    async fn adapter_heartbeat(
+        &self,
+        id: AdapterId,
+    ) -> Result<AuthorityWorkerHeartbeatResponse, Error> {
+        Ok(
+            match self.zk.exists(&adapter_id_to_path(&id), false).await {
+                Ok(Some(_)) => AuthorityWorkerHeartbeatResponse::Alive,
+                _ => AuthorityWorkerHeartbeatResponse::Failed,
+            },
+        )
     }
 
     async fn get_adapters(&self) -> Result<HashSet<SocketAddr>, Error> {
-        todo!();
+        let children = match self.zk.get_children(ADAPTER_PATH, false).await {
+            Ok(v) => v,
+            Err(ZkError::NoNode) => Vec::new(),
+            Err(e) => bail!(e),
+        };
+
+        let mut adapters = HashSet::new();
+        for adapter_id in children {
+            // Skip adapters whose node has been removed since we listed them
+            if let Ok((data, _)) = self
+                .zk
+                .get_data(&adapter_id_to_path(&adapter_id), false)
+                .await
+            {
+                adapters.insert(serde_json::from_slice(&data)?);
+            }
+        }
+        Ok(adapters)
     }
 }
 
@@ -552,4 +636,53 @@ mod tests {
         );
         assert_eq!(workers.len(), 0);
     }
+
+    #[tokio::test]
+    #[ignore]
+    async fn register_adapters() {
+        let authority = Arc::new(
+            ZookeeperAuthority::new("127.0.0.1:2181/register_adapters")
+                .await
+                .unwrap(),
+        );
+        assert!(authority.get_adapters().await.unwrap().is_empty());
+
+        let endpoint = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 6034);
+        let adapter_id = authority.register_adapter(endpoint).await.unwrap().unwrap();
+        assert_eq!(
+            authority.get_adapters().await.unwrap(),
+            HashSet::from([endpoint])
+        );
+        assert_eq!(
+            authority
+                .adapter_heartbeat(adapter_id.clone())
+                .await
+                .unwrap(),
+            AuthorityWorkerHeartbeatResponse::Alive
+        );
+
+        // Registering again updates the existing registration
+        let new_endpoint = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)), 6034);
+        assert_eq!(
+            authority.register_adapter(new_endpoint).await.unwrap(),
+            Some(adapter_id.clone())
+        );
+        assert_eq!(
+            authority.get_adapters().await.unwrap(),
+            HashSet::from([new_endpoint])
+        );
+
+        // Kill the session, this should remove the adapter's registration.
+        drop(authority);
+        let authority = Arc::new(
+            ZookeeperAuthority::new("127.0.0.1:2181/register_adapters")
+                .await
+                .unwrap(),
+        );
+        assert!(authority.get_adapters().await.unwrap().is_empty());
+        assert_eq!(
+            authority.adapter_heartbeat(adapter_id).await.unwrap(),
+            AuthorityWorkerHeartbeatResponse::Failed
+        );
+    }
 }
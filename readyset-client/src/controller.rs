@@ -632,6 +632,17 @@ impl ReadySetHandle {
         self.rpc("simple_graphviz", (), self.request_timeout)
     }
 
+    /// Fetch a simplified graphviz description of the dataflow plan for the cached query with the
+    /// given name.
+    ///
+    /// `Self::poll_ready` must have returned `Async::Ready` before you call this method.
+    pub fn graphviz_for_query(
+        &mut self,
+        name: Relation,
+    ) -> impl Future<Output = ReadySetResult<String>> + '_ {
+        self.rpc("graphviz_for_query", name, self.request_timeout)
+    }
+
     /// Replicate the readers associated with the list of queries to the given worker.
     ///
     /// `Self::poll_ready` must have returned `Async::Ready` before you call this method.
@@ -2161,10 +2161,7 @@ mod tests {
                 shards: Vec1::new(c), // Not used for test
                 shard_addrs: vec![],  // Not used for test
             };
-            let dataflow_dialect = match dialect {
-                Dialect::MySQL => DfDialect::DEFAULT_MYSQL,
-                Dialect::PostgreSQL => DfDialect::DEFAULT_POSTGRESQL,
-            };
+            let dataflow_dialect = DfDialect::default_for(dialect);
             let mut view = View::Single(reader_handle);
             view.build_view_query(
                 raw_keys,
@@ -45,6 +45,21 @@ impl Dialect {
         engine: SqlEngine::MySQL,
     };
 
+    /// Returns the [`Dialect`] corresponding to the expression evaluation semantics of a
+    /// default-configured database which uses the given parsing dialect.
+    ///
+    /// This is equivalent to converting the parsing dialect with [`From`], but can be used in
+    /// constant expressions.
+    pub const fn default_for(parse_dialect: nom_sql::Dialect) -> Dialect {
+        match parse_dialect {
+            nom_sql::Dialect::PostgreSQL => Self::DEFAULT_POSTGRESQL,
+            nom_sql::Dialect::MySQL => Self::DEFAULT_MYSQL,
+            // There's no SQLite engine yet; MySQL's loosely-typed evaluation semantics are the
+            // closest to SQLite's
+            nom_sql::Dialect::SQLite => Self::DEFAULT_MYSQL,
+        }
+    }
+
     /// Return an enum corresponding to the underlying SQL engine for this dialect.
     ///
     /// This function should ideally be used quite sparingly, instead opting to encode
@@ -109,3 +124,74 @@ impl Dialect {
         }
     }
 }
+
+/// Converts a parsing dialect to the evaluation semantics of a *default-configured* database of
+/// the same engine - any runtime configuration of the database (such as the MySQL `SQL_MODE`) must
+/// be applied separately.
+impl From<nom_sql::Dialect> for Dialect {
+    fn from(parse_dialect: nom_sql::Dialect) -> Self {
+        Self::default_for(parse_dialect)
+    }
+}
+
+impl From<SqlEngine> for nom_sql::Dialect {
+    fn from(engine: SqlEngine) -> Self {
+        match engine {
+            SqlEngine::PostgreSQL => nom_sql::Dialect::PostgreSQL,
+            SqlEngine::MySQL => nom_sql::Dialect::MySQL,
+        }
+    }
+}
+
+/// Converts evaluation semantics to the dialect used to parse queries for the same engine. This
+/// conversion is lossy: the parsing dialect depends only on the [`SqlEngine`], so any other
+/// configuration of the evaluation semantics is discarded, and converting the result back to a
+/// [`Dialect`] yields the default configuration for that engine.
+impl From<Dialect> for nom_sql::Dialect {
+    fn from(dialect: Dialect) -> Self {
+        dialect.engine.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mysql_conversions() {
+        assert_eq!(
+            Dialect::from(nom_sql::Dialect::MySQL),
+            Dialect::DEFAULT_MYSQL
+        );
+        assert_eq!(
+            nom_sql::Dialect::from(Dialect::DEFAULT_MYSQL),
+            nom_sql::Dialect::MySQL
+        );
+        assert_eq!(
+            nom_sql::Dialect::from(SqlEngine::MySQL),
+            nom_sql::Dialect::MySQL
+        );
+    }
+
+    #[test]
+    fn postgresql_conversions() {
+        assert_eq!(
+            Dialect::from(nom_sql::Dialect::PostgreSQL),
+            Dialect::DEFAULT_POSTGRESQL
+        );
+        assert_eq!(
+            nom_sql::Dialect::from(Dialect::DEFAULT_POSTGRESQL),
+            nom_sql::Dialect::PostgreSQL
+        );
+        assert_eq!(
+            nom_sql::Dialect::from(SqlEngine::PostgreSQL),
+            nom_sql::Dialect::PostgreSQL
+        );
+    }
+
+    #[test]
+    fn default_for_is_const() {
+        const DIALECT: Dialect = Dialect::default_for(nom_sql::Dialect::PostgreSQL);
+        assert_eq!(DIALECT, Dialect::DEFAULT_POSTGRESQL);
+    }
+}
@@ -649,6 +649,26 @@ pub enum ReadySetError {
     /// Error that the upstream database reports a server version the ReadySet could not parse.
     #[error("Upstream server version could not be parsed")]
     UnparseableServerVersion,
+
+    /// The query was cancelled by a request from the client, made via another connection
+    #[error("Query execution was interrupted by a cancel request")]
+    QueryCancelled,
+
+    /// The connection was closed by an administrator draining all connections for its user
+    #[error(
+        "Terminating connection due to administrator command: draining connections for user {user}"
+    )]
+    ConnectionDrained {
+        /// The user whose connections were drained
+        user: String,
+    },
+
+    /// A result set proxied from the upstream database had more rows than the configured maximum
+    #[error("Result set exceeded the maximum of {max_rows} rows")]
+    ResultSetTooLarge {
+        /// The maximum number of rows allowed in a single result set
+        max_rows: usize,
+    },
 }
 
 impl ReadySetError {
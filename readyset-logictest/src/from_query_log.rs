@@ -98,6 +98,7 @@ fn is_ddl(query: &SqlQuery) -> bool {
         | SqlQuery::StartTransaction(_)
         | SqlQuery::Commit(_)
         | SqlQuery::Rollback(_)
+        | SqlQuery::Savepoint(_)
         | SqlQuery::Show(_)
         | SqlQuery::Explain(_) => false,
         SqlQuery::CreateTable(_)
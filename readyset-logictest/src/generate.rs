@@ -293,6 +293,7 @@ impl Seed {
                         .collect(),
                     ignore: false,
                     on_duplicate: None,
+                    on_conflict: None,
                 }
             })
             .collect::<Vec<_>>();
@@ -18,7 +18,7 @@ use nom_sql::{Dialect, Relation};
 use readyset_adapter::backend::noria_connector::ReadBehavior;
 use readyset_adapter::backend::{BackendBuilder, NoriaConnector};
 use readyset_adapter::query_status_cache::QueryStatusCache;
-use readyset_adapter::{UpstreamConfig, UpstreamDatabase};
+use readyset_adapter::{ProxyOptions, UpstreamConfig, UpstreamDatabase};
 use readyset_client::consensus::{Authority, LocalAuthorityStore};
 use readyset_client::{ReadySetHandle, ViewCreateRequest};
 use readyset_mysql::{MySqlQueryHandler, MySqlUpstream};
@@ -534,7 +534,7 @@ impl TestScript {
                 server_supports_pagination,
             )
             .await;
-            let query_status_cache: &'static _ = Box::leak(Box::new(QueryStatusCache::new()));
+            let query_status_cache = Arc::new(QueryStatusCache::new());
 
             macro_rules! make_backend {
                 ($upstream:ty, $handler:ty, $dialect:expr $(,)?) => {{
@@ -544,6 +544,7 @@ impl TestScript {
                         Some(url) => Some(
                             <$upstream as UpstreamDatabase>::connect(
                                 UpstreamConfig::from_url(url),
+                                ProxyOptions::default(),
                                 None,
                             )
                             .await
@@ -12,7 +12,7 @@ use mysql_async::consts::StatusFlags;
 use mysql_common::bigdecimal03::ToPrimitive;
 use mysql_srv::{
     CachedSchema, Column, ColumnFlags, ColumnType, InitWriter, MsqlSrvError, MySqlShim,
-    QueryResultWriter, RowWriter, StatementMetaWriter,
+    QueryResultWriter, RowWriter, StatementMetaWriter, DEFAULT_CONNECTION_ID,
 };
 use readyset_adapter::backend::noria_connector::{
     MetaVariable, SelectPrepareResult, SelectPrepareResultInner,
@@ -20,6 +20,7 @@ use readyset_adapter::backend::noria_connector::{
 use readyset_adapter::backend::{
     noria_connector, QueryResult, SinglePrepareResult, UpstreamPrepare,
 };
+use readyset_adapter::upstream_database::{IsFatalError, UpstreamErrorClass};
 use readyset_data::{DfType, DfValue, DfValueKind};
 use readyset_errors::{internal, ReadySetError};
 use readyset_tracing::{error, trace};
@@ -275,6 +276,29 @@ async fn write_meta_with_header<W: AsyncWrite + Unpin>(
     writer.finish().await
 }
 
+/// If `query` is a `KILL [QUERY | CONNECTION] <connection id>` statement, returns the connection
+/// id, and whether only the query running on the connection should be killed
+fn parse_kill(query: &str) -> Option<(u32, bool)> {
+    let mut words = query.trim().trim_end_matches(';').split_ascii_whitespace();
+    if !words.next()?.eq_ignore_ascii_case("KILL") {
+        return None;
+    }
+    let (query_only, connection_id) = match (words.next()?, words.next()) {
+        (modifier, Some(connection_id)) if modifier.eq_ignore_ascii_case("QUERY") => {
+            (true, connection_id)
+        }
+        (modifier, Some(connection_id)) if modifier.eq_ignore_ascii_case("CONNECTION") => {
+            (false, connection_id)
+        }
+        (connection_id, None) => (false, connection_id),
+        _ => return None,
+    };
+    if words.next().is_some() {
+        return None;
+    }
+    Some((connection_id.parse().ok()?, query_only))
+}
+
 pub struct Backend {
     /// Handle to the backing noria client
     noria: readyset_adapter::Backend<MySqlUpstream, MySqlQueryHandler>,
@@ -352,6 +376,13 @@ macro_rules! handle_error {
                 // should re-initiate a connection with us so we can start with a fresh slate.
                 Err(e)
             }
+            Error::ReadySet(e @ ReadySetError::ConnectionDrained { .. }) => {
+                // Report the error to the client, then close the connection
+                $writer
+                    .error(mysql_srv::ErrorKind::ER_SERVER_SHUTDOWN, e.to_string().as_bytes())
+                    .await?;
+                Err(io::Error::new(io::ErrorKind::ConnectionAborted, e.to_string()))
+            }
             err => {
                 $writer
                     .error(err.error_kind(), err.to_string().as_bytes())
@@ -437,6 +468,7 @@ where
         upstream::QueryResult::ReadResult {
             mut stream,
             columns,
+            mut row_limit,
         } => {
             let formatted_cols = columns.iter().map(|c| c.into()).collect::<Vec<_>>();
             let mut rw = writer.start(&formatted_cols).await?;
@@ -445,6 +477,13 @@ where
                     Ok(row) => row,
                     Err(err) => return handle_error!(Error::MySql(err), rw),
                 };
+                match row_limit.check_row() {
+                    Ok(true) => {}
+                    // Keep reading the rest of the truncated result set, so that it's not left
+                    // on the connection
+                    Ok(false) => continue,
+                    Err(err) => return handle_error!(Error::ReadySet(err), rw),
+                }
 
                 for (i, _) in row.columns_ref().iter().enumerate() {
                     rw.write_col(row.as_ref(i).expect("Must match column number"))?;
@@ -683,8 +722,59 @@ where
     async fn on_close(&mut self, _: u32) {}
 
     async fn on_query(&mut self, query: &str, results: QueryResultWriter<'_, W>) -> io::Result<()> {
-        let query_result = self.query(query).await;
-        handle_query_result(query_result, results).await
+        // Connection ids we send to clients refer to connections to ReadySet, not to the upstream,
+        // so we have to handle `KILL` ourselves rather than proxying it. Killing a connection's
+        // query also kills it on that connection's own upstream connection, by its upstream id.
+        if self.cancel_key().is_some() {
+            if let Some((connection_id, query_only)) = parse_kill(query) {
+                return if self.kill(connection_id, query_only) {
+                    results.completed(0, 0, None).await
+                } else {
+                    results
+                        .error(
+                            mysql_srv::ErrorKind::ER_NO_SUCH_THREAD,
+                            format!("Unknown thread id: {connection_id}").as_bytes(),
+                        )
+                        .await
+                };
+            }
+        }
+
+        let mut retries = 0;
+        let error = loop {
+            let error = match self.query(query).await {
+                Err(error) if error.classify() == UpstreamErrorClass::Retryable => error,
+                query_result => return handle_query_result(query_result, results).await,
+            };
+            if !self.should_retry(&error, retries) {
+                break error;
+            }
+            retries += 1;
+        };
+        handle_query_result(Err(error), results).await
+    }
+
+    fn connection_id(&self) -> u32 {
+        self.cancel_key()
+            .map_or(DEFAULT_CONNECTION_ID, |key| key.connection_id)
+    }
+
+    async fn on_process_kill(&mut self, connection_id: u32) -> bool {
+        self.kill(connection_id, false)
+    }
+
+    fn on_authenticated(&mut self, username: &str) {
+        self.noria.set_user(username);
+    }
+
+    fn on_command_complete(&mut self) {
+        self.noria.release_upstream();
+    }
+
+    fn on_connect_attributes(&mut self, attributes: &[(&str, &str)]) {
+        if let Some((_, program_name)) = attributes.iter().find(|(k, _)| *k == "program_name") {
+            self.noria.set_application_name(program_name);
+        }
     }
 
     fn password_for_username(&self, username: &str) -> Option<Vec<u8>> {
@@ -695,6 +785,10 @@ where
         self.does_require_authentication()
     }
 
+    fn enable_protocol_compression(&self) -> bool {
+        self.protocol_compression_enabled()
+    }
+
     fn version(&self) -> String {
         self.noria.version()
     }
@@ -1,7 +1,7 @@
 use std::io;
 
 use mysql_srv::MsqlSrvError;
-use readyset_adapter::upstream_database::IsFatalError;
+use readyset_adapter::upstream_database::{IsFatalError, UpstreamErrorClass};
 use readyset_client::ReadySetError;
 use thiserror::Error;
 
@@ -42,6 +42,9 @@ impl Error {
          */
         match self {
             Self::MySql(mysql_async::Error::Server(e)) => e.code.into(),
+            Self::ReadySet(ReadySetError::QueryCancelled) => {
+                mysql_srv::ErrorKind::ER_QUERY_INTERRUPTED
+            }
             Self::MySql(_) => {
                 // TODO(peter): We need to translate these to appropriate
                 // mysql error codes. Currently mysql_async is only used by fallback.
@@ -56,4 +59,99 @@ impl IsFatalError for Error {
     fn is_fatal(&self) -> bool {
         matches!(self, Self::MySql(e) if e.is_fatal())
     }
+
+    fn classify(&self) -> UpstreamErrorClass {
+        match self {
+            Self::MySql(mysql_async::Error::Server(e))
+                if e.code == mysql_srv::ErrorKind::ER_LOCK_DEADLOCK as u16
+                    || e.code == mysql_srv::ErrorKind::ER_LOCK_WAIT_TIMEOUT as u16 =>
+            {
+                UpstreamErrorClass::Retryable
+            }
+            Self::MySql(e) if e.is_fatal() => UpstreamErrorClass::Fatal,
+            _ => UpstreamErrorClass::Passthrough,
+        }
+    }
+
+    fn is_authentication_error(&self) -> bool {
+        use mysql_srv::ErrorKind::*;
+        matches!(
+            self,
+            Self::MySql(mysql_async::Error::Server(e))
+                if [
+                    ER_ACCESS_DENIED_ERROR,
+                    ER_DBACCESS_DENIED_ERROR,
+                    ER_ACCESS_DENIED_NO_PASSWORD_ERROR,
+                ]
+                .into_iter()
+                .any(|kind| e.code == kind as u16)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mysql_async::{DriverError, IoError, ServerError};
+
+    use super::*;
+
+    fn server_error(code: u16) -> Error {
+        Error::MySql(mysql_async::Error::Server(ServerError {
+            code,
+            message: "error".into(),
+            state: "HY000".into(),
+        }))
+    }
+
+    #[test]
+    fn classify_mysql_errors() {
+        // ER_LOCK_DEADLOCK
+        assert_eq!(server_error(1213).classify(), UpstreamErrorClass::Retryable);
+        // ER_LOCK_WAIT_TIMEOUT
+        assert_eq!(server_error(1205).classify(), UpstreamErrorClass::Retryable);
+        // ER_PARSE_ERROR
+        assert_eq!(
+            server_error(1064).classify(),
+            UpstreamErrorClass::Passthrough
+        );
+        // ER_DUP_ENTRY
+        assert_eq!(
+            server_error(1062).classify(),
+            UpstreamErrorClass::Passthrough
+        );
+
+        let connection_lost = Error::MySql(mysql_async::Error::Io(IoError::Io(
+            io::ErrorKind::ConnectionReset.into(),
+        )));
+        assert_eq!(connection_lost.classify(), UpstreamErrorClass::Fatal);
+        assert!(connection_lost.is_fatal());
+        assert!(!connection_lost.classify().should_retry(0));
+
+        let driver_error = Error::MySql(mysql_async::Error::Driver(DriverError::ConnectionClosed));
+        assert_eq!(driver_error.classify(), UpstreamErrorClass::Fatal);
+
+        assert_eq!(
+            Error::ReadySet(ReadySetError::QueryCancelled).classify(),
+            UpstreamErrorClass::Passthrough
+        );
+    }
+
+    #[test]
+    fn deadlock_is_retried_once() {
+        let class = server_error(1213).classify();
+        assert!(class.should_retry(0));
+        assert!(!class.should_retry(1));
+        assert!(!server_error(1064).classify().should_retry(0));
+    }
+
+    #[test]
+    fn authentication_errors() {
+        // ER_ACCESS_DENIED_ERROR
+        assert!(server_error(1045).is_authentication_error());
+        // ER_DBACCESS_DENIED_ERROR
+        assert!(server_error(1044).is_authentication_error());
+        // ER_LOCK_DEADLOCK
+        assert!(!server_error(1213).is_authentication_error());
+        assert!(!Error::Io(io::ErrorKind::ConnectionRefused.into()).is_authentication_error());
+    }
 }
@@ -4,7 +4,9 @@ use std::collections::HashMap;
 use std::convert::TryInto;
 #[cfg(feature = "fallback_cache")]
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use futures_util::Stream;
@@ -20,13 +22,17 @@ use pin_project::pin_project;
 use readyset_adapter::fallback_cache::FallbackCache;
 #[cfg(feature = "fallback_cache")]
 use readyset_adapter::fallback_cache::FallbackCacheApi;
-use readyset_adapter::upstream_database::{NoriaCompare, UpstreamDestination};
-use readyset_adapter::{UpstreamConfig, UpstreamDatabase, UpstreamPrepare};
+use readyset_adapter::upstream_database::{
+    DnsCache, NoriaCompare, UpstreamDestination, UpstreamQueryCanceller,
+};
+use readyset_adapter::{
+    ProxyOptions, ResultRowLimit, UpstreamConfig, UpstreamDatabase, UpstreamPrepare,
+};
 use readyset_client::ColumnSchema;
 use readyset_client_metrics::QueryDestination;
 use readyset_data::DfValue;
-use readyset_errors::{internal_err, ReadySetError};
-use readyset_tracing::{error, info};
+use readyset_errors::{internal_err, unsupported_err, ReadySetError};
+use readyset_tracing::{debug, error, info, warn};
 use tracing::{info_span, Instrument};
 
 use crate::schema::{convert_column, is_subtype};
@@ -38,6 +44,34 @@ type StatementID = u32;
 /// during connection phase if the version for the upstream server is too low.
 const MIN_UPSTREAM_VERSION: u16 = 8;
 
+/// The newest upstream server major version that ReadySet has been tested against. Newer
+/// versions are allowed, but a warning is logged when connecting to them.
+const MAX_TESTED_UPSTREAM_VERSION: u16 = 8;
+
+/// Checks that an upstream server with the given `(major, minor, patch)` version is supported.
+///
+/// Servers newer than [`MAX_TESTED_UPSTREAM_VERSION`] are allowed, with a warning. The client only
+/// uses the capability flags advertised by the server which it knows about and requested itself,
+/// so flags added by newer servers are ignored during the handshake rather than failing it.
+fn check_server_version((major, minor, patch): (u16, u16, u16)) -> Result<(), Error> {
+    if major < MIN_UPSTREAM_VERSION {
+        return Err(Error::ReadySet(ReadySetError::UnsupportedServerVersion {
+            major,
+            minor: minor.to_string(),
+            min: MIN_UPSTREAM_VERSION,
+        }));
+    }
+    if major > MAX_TESTED_UPSTREAM_VERSION {
+        warn!(
+            version = %format!("{major}.{minor}.{patch}"),
+            max_tested_major_version = MAX_TESTED_UPSTREAM_VERSION,
+            "Upstream server is newer than any version ReadySet has been tested against; \
+             proceeding with the capabilities it shares with ReadySet"
+        );
+    }
+    Ok(())
+}
+
 fn dt_to_value_params(
     dt: &[DfValue],
 ) -> Result<Vec<mysql_async::Value>, readyset_client::ReadySetError> {
@@ -77,6 +111,8 @@ pub enum QueryResult<'a> {
     ReadResult {
         stream: ReadResultStream<'a>,
         columns: Arc<[Column]>,
+        /// Limits the number of rows read from `stream` which are sent to the client
+        row_limit: ResultRowLimit,
     },
     CachedReadResult(CachedReadResult),
     Command {
@@ -121,6 +157,7 @@ impl<'a> QueryResult<'a> {
             QueryResult::ReadResult {
                 mut stream,
                 columns,
+                ..
             } => {
                 let mut rows = vec![];
                 while let Some(row) = stream.next().await {
@@ -154,10 +191,35 @@ pub struct MySqlUpstream {
     conn: Conn,
     prepared_statements: HashMap<StatementID, mysql_async::Statement>,
     upstream_config: UpstreamConfig,
+    proxy_options: ProxyOptions,
+    /// The id of `conn` on the upstream server, shared with any [`MySqlQueryCanceller`]s for this
+    /// upstream, since resetting the connection may reconnect it with a new id
+    connection_id: Arc<AtomicU32>,
     #[cfg(feature = "fallback_cache")]
     fallback_cache: Option<FallbackCache<CachedReadResult>>,
 }
 
+/// Cancels queries running on a [`MySqlUpstream`] by running `KILL QUERY` against the upstream on
+/// a new connection
+struct MySqlQueryCanceller {
+    opts: Opts,
+    connection_id: Arc<AtomicU32>,
+}
+
+#[async_trait]
+impl UpstreamQueryCanceller for MySqlQueryCanceller {
+    async fn cancel(&self) -> anyhow::Result<()> {
+        let mut conn = Conn::new(self.opts.clone()).await?;
+        conn.query_drop(format!(
+            "KILL QUERY {}",
+            self.connection_id.load(Ordering::Acquire)
+        ))
+        .await?;
+        conn.disconnect().await?;
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StatementMeta {
     /// Metadata about the query parameters for this statement
@@ -227,7 +289,7 @@ impl NoriaCompare for StatementMeta {
 }
 
 macro_rules! handle_query_result {
-    ($result: expr) => {{
+    ($result: expr, $row_limit: expr) => {{
         let columns = ($result).columns().ok_or_else(|| {
             ReadySetError::Internal("The mysql_async result was already consumed".to_string())
         })?;
@@ -244,6 +306,7 @@ macro_rules! handle_query_result {
                     })?
                     .into(),
                 columns,
+                row_limit: $row_limit,
             })
         } else {
             // Kinda sad that can't get status from conn, since it is mutably borrowed above
@@ -267,9 +330,49 @@ macro_rules! handle_query_result {
     }};
 }
 
+/// Open a new connection to the upstream database with the given options.
+///
+/// If `dns_refresh_interval` is set, the connection is made to the upstream host's addresses in the
+/// global [`DnsCache`], falling back to resolving the host afresh if none of them can be connected
+/// to. Connections using TLS always resolve the host afresh, since the hostname is needed to
+/// validate the server's certificate.
+async fn connect(opts: Opts, dns_refresh_interval: Option<Duration>) -> Result<Conn, Error> {
+    let refresh_interval = match dns_refresh_interval {
+        Some(refresh_interval) if opts.ssl_opts().is_none() => refresh_interval,
+        _ => return Ok(Conn::new(opts).await?),
+    };
+
+    let host = opts.ip_or_hostname().to_owned();
+    let port = opts.tcp_port();
+    let dns_cache = DnsCache::global();
+    match dns_cache.resolve(&host, port, refresh_interval).await {
+        Ok(addrs) => {
+            for addr in addrs {
+                let addr_opts = OptsBuilder::from_opts(opts.clone())
+                    .ip_or_hostname(addr.ip().to_string())
+                    .tcp_port(addr.port());
+                match Conn::new(addr_opts).await {
+                    Ok(conn) => return Ok(conn),
+                    // Errors from the server itself (eg authentication failures) won't be fixed
+                    // by connecting to a different address
+                    Err(e @ mysql_async::Error::Server(_)) => return Err(e.into()),
+                    Err(error) => {
+                        debug!(%error, %addr, "Failed to connect to cached upstream address")
+                    }
+                }
+            }
+            dns_cache.invalidate(&host, port);
+        }
+        Err(error) => warn!(%error, %host, "Failed to resolve upstream database host"),
+    }
+
+    Ok(Conn::new(opts).await?)
+}
+
 impl MySqlUpstream {
     async fn connect_inner(
         upstream_config: UpstreamConfig,
+        proxy_options: &ProxyOptions,
     ) -> Result<
         (
             Conn,
@@ -278,6 +381,13 @@ impl MySqlUpstream {
         ),
         Error,
     > {
+        // mysql_async opens its sockets itself, and has no way for us to set their buffer sizes
+        if !proxy_options.tcp_buffer_sizes.is_default() {
+            return Err(Error::ReadySet(unsupported_err!(
+                "TCP buffer sizes can't be set for connections to a MySQL upstream database"
+            )));
+        }
+
         // CLIENT_SESSION_TRACK is required for GTID information to be sent in OK packets on commits
         // GTID information is used for RYW
         // Currently this causes rows affected to return an incorrect result, so this is feature
@@ -302,27 +412,17 @@ impl MySqlUpstream {
             user = %opts.user().unwrap_or("<NO USER>"),
         );
         span.in_scope(|| info!("Establishing connection"));
-        let conn = if cfg!(feature = "ryw") {
-            Conn::new(
-                OptsBuilder::from_opts(opts).add_capability(CapabilityFlags::CLIENT_SESSION_TRACK),
-            )
-            .instrument(span.clone())
-            .await?
+        let opts = if cfg!(feature = "ryw") {
+            OptsBuilder::from_opts(opts).add_capability(CapabilityFlags::CLIENT_SESSION_TRACK)
         } else {
-            Conn::new(OptsBuilder::from_opts(opts))
-                .instrument(span.clone())
-                .await?
+            OptsBuilder::from_opts(opts)
         };
+        let conn = connect(opts.into(), upstream_config.upstream_dns_refresh_interval())
+            .instrument(span.clone())
+            .await?;
 
         // Check that the server version is supported.
-        let (major, minor, _) = conn.server_version();
-        if major < MIN_UPSTREAM_VERSION {
-            return Err(Error::ReadySet(ReadySetError::UnsupportedServerVersion {
-                major,
-                minor: minor.to_string(),
-                min: MIN_UPSTREAM_VERSION,
-            }));
-        }
+        span.in_scope(|| check_server_version(conn.server_version()))?;
 
         span.in_scope(|| info!("Established connection to upstream"));
         let prepared_statements = HashMap::new();
@@ -341,14 +441,17 @@ impl UpstreamDatabase for MySqlUpstream {
     #[cfg(feature = "fallback_cache")]
     async fn connect(
         upstream_config: UpstreamConfig,
+        proxy_options: ProxyOptions,
         fallback_cache: Option<FallbackCache<Self::CachedReadResult>>,
     ) -> Result<Self, Error> {
         let (conn, prepared_statements, upstream_config) =
-            Self::connect_inner(upstream_config).await?;
+            Self::connect_inner(upstream_config, &proxy_options).await?;
         Ok(Self {
+            connection_id: Arc::new(AtomicU32::new(conn.id())),
             conn,
             prepared_statements,
             upstream_config,
+            proxy_options,
             fallback_cache,
         })
     }
@@ -356,14 +459,17 @@ impl UpstreamDatabase for MySqlUpstream {
     #[cfg(not(feature = "fallback_cache"))]
     async fn connect(
         upstream_config: UpstreamConfig,
+        proxy_options: ProxyOptions,
         _: Option<FallbackCache<Self::CachedReadResult>>,
     ) -> Result<Self, Error> {
         let (conn, prepared_statements, upstream_config) =
-            Self::connect_inner(upstream_config).await?;
+            Self::connect_inner(upstream_config, &proxy_options).await?;
         Ok(Self {
+            connection_id: Arc::new(AtomicU32::new(conn.id())),
             conn,
             prepared_statements,
             upstream_config,
+            proxy_options,
         })
     }
 
@@ -379,6 +485,13 @@ impl UpstreamDatabase for MySqlUpstream {
         self.conn.opts().db_name()
     }
 
+    fn query_canceller(&self) -> Option<Arc<dyn UpstreamQueryCanceller>> {
+        Some(Arc::new(MySqlQueryCanceller {
+            opts: self.conn.opts().clone(),
+            connection_id: self.connection_id.clone(),
+        }))
+    }
+
     fn version(&self) -> String {
         // The server's version relayed back to the client as the current server version. Most
         // clients will interpret the version numbers and use that to dictate which dialect they
@@ -394,6 +507,7 @@ impl UpstreamDatabase for MySqlUpstream {
         let conn = Conn::new(opts).await?;
         let prepared_statements = HashMap::new();
         let upstream_config = self.upstream_config.clone();
+        let proxy_options = self.proxy_options.clone();
         let fallback_cache = if let Some(ref cache) = self.fallback_cache {
             cache.clear().await;
             Some(cache.clone())
@@ -403,12 +517,15 @@ impl UpstreamDatabase for MySqlUpstream {
         let old_self = std::mem::replace(
             self,
             Self {
+                connection_id: self.connection_id.clone(),
                 conn,
                 prepared_statements,
                 upstream_config,
+                proxy_options,
                 fallback_cache,
             },
         );
+        self.connection_id.store(self.conn.id(), Ordering::Release);
         let _ = old_self.conn.disconnect().await as Result<(), _>;
         Ok(())
     }
@@ -419,18 +536,54 @@ impl UpstreamDatabase for MySqlUpstream {
         let conn = Conn::new(opts).await?;
         let prepared_statements = HashMap::new();
         let upstream_config = self.upstream_config.clone();
+        let proxy_options = self.proxy_options.clone();
         let old_self = std::mem::replace(
             self,
             Self {
+                connection_id: self.connection_id.clone(),
                 conn,
                 prepared_statements,
                 upstream_config,
+                proxy_options,
             },
         );
+        self.connection_id.store(self.conn.id(), Ordering::Release);
         let _ = old_self.conn.disconnect().await as Result<(), _>;
         Ok(())
     }
 
+    async fn reset_session(&mut self) -> Result<(), Error> {
+        // Resetting the connection deallocates all of its prepared statements
+        self.prepared_statements.clear();
+        self.conn.reset().await?;
+        self.connection_id.store(self.conn.id(), Ordering::Release);
+        // `COM_RESET_CONNECTION` keeps the connection's default database, which a previous client
+        // may have changed, so switch back to the one the connection was configured with
+        if let Some(db) = self.conn.opts().db_name() {
+            let use_db = format!("USE {}", nom_sql::Dialect::MySQL.quote_identifier(db));
+            self.conn.query_drop(use_db).await?;
+        }
+        Ok(())
+    }
+
+    async fn reconnect_to(&mut self, url: &str) -> Result<(), Error> {
+        let mut upstream_config = self.upstream_config.clone();
+        upstream_config.upstream_db_url = Some(url.to_owned().into());
+        let (conn, prepared_statements, upstream_config) =
+            Self::connect_inner(upstream_config, &self.proxy_options).await?;
+        #[cfg(feature = "fallback_cache")]
+        if let Some(ref cache) = self.fallback_cache {
+            // Results cached from the previous upstream aren't valid for the new one
+            cache.clear().await;
+        }
+        let old_conn = std::mem::replace(&mut self.conn, conn);
+        self.connection_id.store(self.conn.id(), Ordering::Release);
+        self.prepared_statements = prepared_statements;
+        self.upstream_config = upstream_config;
+        let _ = old_conn.disconnect().await as Result<(), _>;
+        Ok(())
+    }
+
     /// Prepares the given query using the mysql connection. Note, queries are prepared on a
     /// per connection basis. They are not universal.
     async fn prepare<'a, S>(&'a mut self, query: S) -> Result<UpstreamPrepare<Self>, Error>
@@ -472,7 +625,7 @@ impl UpstreamDatabase for MySqlUpstream {
                     params,
                 )
                 .await?;
-            let r = handle_query_result!(result);
+            let r = handle_query_result!(result, ResultRowLimit::new(&self.proxy_options));
             match r {
                 Ok(query_result @ QueryResult::ReadResult { .. }) => {
                     let cached_result: CachedReadResult = query_result.async_try_into().await?;
@@ -492,7 +645,7 @@ impl UpstreamDatabase for MySqlUpstream {
                     params,
                 )
                 .await?;
-            handle_query_result!(result)
+            handle_query_result!(result, ResultRowLimit::new(&self.proxy_options))
         }
     }
 
@@ -512,7 +665,7 @@ impl UpstreamDatabase for MySqlUpstream {
                 params,
             )
             .await?;
-        handle_query_result!(result)
+        handle_query_result!(result, ResultRowLimit::new(&self.proxy_options))
     }
 
     #[cfg(feature = "fallback_cache")]
@@ -526,7 +679,7 @@ impl UpstreamDatabase for MySqlUpstream {
             }
             let query_str = query.as_ref().to_owned();
             let result = self.conn.query_iter(query).await?;
-            let r = handle_query_result!(result);
+            let r = handle_query_result!(result, ResultRowLimit::new(&self.proxy_options));
             match r {
                 Ok(query_result @ QueryResult::ReadResult { .. }) => {
                     let cached_result: CachedReadResult = query_result.async_try_into().await?;
@@ -537,7 +690,7 @@ impl UpstreamDatabase for MySqlUpstream {
             }
         } else {
             let result = self.conn.query_iter(query).await?;
-            handle_query_result!(result)
+            handle_query_result!(result, ResultRowLimit::new(&self.proxy_options))
         }
     }
 
@@ -547,7 +700,7 @@ impl UpstreamDatabase for MySqlUpstream {
         S: AsRef<str> + Send + Sync + 'a,
     {
         let result = self.conn.query_iter(query).await?;
-        handle_query_result!(result)
+        handle_query_result!(result, ResultRowLimit::new(&self.proxy_options))
     }
 
     /// Executes the given query on the mysql backend.
@@ -737,4 +890,100 @@ mod tests {
 
         assert!(s.compare(&schema_spec, &param_specs).is_err());
     }
+
+    #[test]
+    fn server_versions() {
+        check_server_version((8, 0, 26)).unwrap();
+        // Newer servers are allowed, with a warning
+        check_server_version((9, 1, 0)).unwrap();
+        check_server_version((5, 7, 40)).unwrap_err();
+    }
+
+    /// A capability flag which ReadySet doesn't know about
+    fn unknown_capability() -> u32 {
+        (0..32)
+            .map(|i| 1 << i)
+            .find(|&flag| CapabilityFlags::from_bits(flag).is_none())
+            .unwrap()
+    }
+
+    /// The payload of a handshake packet advertising the given capability flags
+    fn handshake_packet(capabilities: u32) -> Vec<u8> {
+        let mut payload = vec![10];
+        payload.extend_from_slice(b"9.9.9-future\0");
+        payload.extend_from_slice(&42u32.to_le_bytes());
+        payload.extend_from_slice(&[b'a'; 8]);
+        payload.push(0);
+        payload.extend_from_slice(&capabilities.to_le_bytes()[..2]);
+        payload.push(0x21);
+        payload.extend_from_slice(&StatusFlags::SERVER_STATUS_AUTOCOMMIT.bits().to_le_bytes());
+        payload.extend_from_slice(&capabilities.to_le_bytes()[2..]);
+        payload.push(21);
+        payload.extend_from_slice(&[0; 10]);
+        payload.extend_from_slice(&[b'b'; 12]);
+        payload.push(0);
+        payload.extend_from_slice(b"mysql_native_password\0");
+        payload
+    }
+
+    /// Reads a packet sent by the client, returning its payload
+    async fn read_packet(stream: &mut tokio::net::TcpStream) -> Vec<u8> {
+        use tokio::io::AsyncReadExt;
+
+        // Packets start with a 3-byte little-endian payload length and a 1-byte sequence number
+        let mut header = [0u8; 4];
+        stream.read_exact(&mut header).await.unwrap();
+        let mut payload =
+            vec![0; u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize];
+        stream.read_exact(&mut payload).await.unwrap();
+        payload
+    }
+
+    /// Writes a packet with the given sequence number and payload
+    async fn write_packet(stream: &mut tokio::net::TcpStream, seq: u8, payload: &[u8]) {
+        use tokio::io::AsyncWriteExt;
+
+        let mut packet = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+        packet.push(seq);
+        packet.extend_from_slice(payload);
+        stream.write_all(&packet).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn connect_to_upstream_with_unknown_capabilities() {
+        let advertised = (CapabilityFlags::CLIENT_PROTOCOL_41
+            | CapabilityFlags::CLIENT_SECURE_CONNECTION
+            | CapabilityFlags::CLIENT_PLUGIN_AUTH
+            | CapabilityFlags::CLIENT_DEPRECATE_EOF)
+            .bits()
+            | unknown_capability();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            write_packet(&mut stream, 0, &handshake_packet(advertised)).await;
+            // Accept whatever the client authenticates with
+            read_packet(&mut stream).await;
+            let mut ok = vec![0, 0, 0];
+            ok.extend_from_slice(&StatusFlags::SERVER_STATUS_AUTOCOMMIT.bits().to_le_bytes());
+            ok.extend_from_slice(&[0, 0]);
+            write_packet(&mut stream, 2, &ok).await;
+            // Wait for the client to quit
+            read_packet(&mut stream).await;
+        });
+
+        let opts = OptsBuilder::default()
+            .ip_or_hostname(addr.ip().to_string())
+            .tcp_port(addr.port())
+            .user(Some("root"))
+            .prefer_socket(false)
+            // Don't query the server for these once connected
+            .max_allowed_packet(Some(16 * 1024 * 1024))
+            .wait_timeout(Some(28800));
+        // The unknown flag is ignored, and the newer server version is logged with a warning
+        let conn = connect(opts.into(), None).await.unwrap();
+        assert_eq!(conn.server_version(), (9, 9, 9));
+        check_server_version(conn.server_version()).unwrap();
+        conn.disconnect().await.unwrap();
+    }
 }
@@ -1,9 +1,17 @@
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use mysql_async::prelude::*;
 use readyset_adapter::backend::UnsupportedSetMode;
+use readyset_adapter::cancel::CancelRegistry;
+use readyset_adapter::upstream_database::{UpstreamRoute, UpstreamRoutes};
 use readyset_adapter::BackendBuilder;
 use readyset_client::query::QueryId;
 use readyset_client_metrics::QueryDestination;
-use readyset_client_test_helpers::mysql_helpers::{last_query_info, MySQLAdapter};
+use readyset_client_test_helpers::mysql_helpers::{
+    last_query_info, recreate_database, MySQLAdapter,
+};
 use readyset_client_test_helpers::{self, sleep, TestBuilder};
 use readyset_server::Handle;
 use readyset_util::hash::hash;
@@ -662,6 +670,52 @@ async fn transaction_proxies() {
     );
 }
 
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+#[skip_flaky_finder]
+async fn transaction_with_savepoints_proxies() {
+    let (opts, _handle) = setup().await;
+    let mut conn = mysql_async::Conn::new(opts).await.unwrap();
+
+    conn.query_drop("CREATE TABLE t (x int)").await.unwrap();
+    sleep().await;
+
+    conn.query_drop("CREATE CACHE FROM SELECT * FROM t")
+        .await
+        .unwrap();
+
+    conn.query_drop("BEGIN;").await.unwrap();
+    conn.query_drop("SAVEPOINT sp1;").await.unwrap();
+    conn.query_drop("INSERT INTO t (x) VALUES (1);")
+        .await
+        .unwrap();
+    conn.query_drop("ROLLBACK TO SAVEPOINT sp1;").await.unwrap();
+
+    // Rolling back to a savepoint doesn't end the transaction, so reads should still be proxied
+    // (and see the effects of the rollback)
+    let rows: Vec<i32> = conn.query("SELECT * FROM t;").await.unwrap();
+    assert!(rows.is_empty());
+    assert_eq!(
+        last_query_info(&mut conn).await.destination,
+        QueryDestination::Upstream
+    );
+
+    conn.query_drop("RELEASE SAVEPOINT sp1;").await.unwrap();
+    conn.query_drop("SELECT * FROM t;").await.unwrap();
+    assert_eq!(
+        last_query_info(&mut conn).await.destination,
+        QueryDestination::Upstream
+    );
+
+    conn.query_drop("COMMIT;").await.unwrap();
+
+    conn.query_drop("SELECT * FROM t;").await.unwrap();
+    assert_eq!(
+        last_query_info(&mut conn).await.destination,
+        QueryDestination::Readyset
+    );
+}
+
 #[tokio::test(flavor = "multi_thread")]
 #[serial]
 async fn valid_sql_parsing_failed_shows_proxied() {
@@ -849,6 +903,357 @@ async fn replication_failure_ignores_table() {
     }
 }
 
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn kill_query_from_another_connection() {
+    let (opts, _handle) = setup_with(
+        BackendBuilder::new()
+            .require_authentication(false)
+            .cancel_registry(Arc::new(CancelRegistry::new())),
+    )
+    .await;
+    let mut conn = mysql_async::Conn::new(opts.clone()).await.unwrap();
+    let mut killer = mysql_async::Conn::new(opts).await.unwrap();
+    let connection_id = conn.id();
+
+    let start = Instant::now();
+    let slow_query = tokio::spawn(async move {
+        let err = conn.query_drop("SELECT SLEEP(30)").await.unwrap_err();
+        (conn, err)
+    });
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    killer
+        .query_drop(format!("KILL QUERY {connection_id}"))
+        .await
+        .unwrap();
+
+    let (mut conn, err) = slow_query.await.unwrap();
+    match err {
+        mysql_async::Error::Server(e) => assert_eq!(e.code, 1317), // ER_QUERY_INTERRUPTED
+        e => panic!("Unexpected error: {e}"),
+    }
+    assert!(start.elapsed() < Duration::from_secs(30));
+
+    // The connection is still usable after its query was killed
+    let res: Option<i32> = conn.query_first("SELECT 1").await.unwrap();
+    assert_eq!(res, Some(1));
+
+    killer
+        .query_drop("KILL QUERY 4294967295")
+        .await
+        .expect_err("Killing an unknown connection should fail");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn kill_query_from_another_user() {
+    let (opts, _handle) = setup_with(
+        BackendBuilder::new()
+            .require_authentication(false)
+            .cancel_registry(Arc::new(CancelRegistry::new())),
+    )
+    .await;
+    let mut conn = mysql_async::Conn::new(
+        mysql_async::OptsBuilder::from_opts(opts.clone()).user(Some("alice")),
+    )
+    .await
+    .unwrap();
+    let mut killer =
+        mysql_async::Conn::new(mysql_async::OptsBuilder::from_opts(opts).user(Some("bob")))
+            .await
+            .unwrap();
+    let connection_id = conn.id();
+
+    let slow_query = tokio::spawn(async move { conn.query_drop("SELECT SLEEP(2)").await });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    for kill in [
+        format!("KILL QUERY {connection_id}"),
+        format!("KILL CONNECTION {connection_id}"),
+        format!("KILL {connection_id}"),
+    ] {
+        let err = killer
+            .query_drop(kill)
+            .await
+            .expect_err("Killing another user's connection should fail");
+        match err {
+            mysql_async::Error::Server(e) => assert_eq!(e.code, 1094), // ER_NO_SUCH_THREAD
+            e => panic!("Unexpected error: {e}"),
+        }
+    }
+
+    slow_query.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn drain_idle_connections_for_user() {
+    let cancel_registry = Arc::new(CancelRegistry::new());
+    let (opts, _handle) = setup_with(
+        BackendBuilder::new()
+            .require_authentication(false)
+            .cancel_registry(cancel_registry.clone()),
+    )
+    .await;
+    let mut alice = mysql_async::Conn::new(
+        mysql_async::OptsBuilder::from_opts(opts.clone()).user(Some("alice")),
+    )
+    .await
+    .unwrap();
+    let mut bob =
+        mysql_async::Conn::new(mysql_async::OptsBuilder::from_opts(opts).user(Some("bob")))
+            .await
+            .unwrap();
+    alice.query_drop("SELECT 1").await.unwrap();
+    bob.query_drop("SELECT 1").await.unwrap();
+
+    // Alice's connection is idle, but is still closed (and so deregistered) without waiting for
+    // her next query
+    assert_eq!(cancel_registry.drain_user("alice"), 1);
+    let start = Instant::now();
+    while cancel_registry.drain_user("alice") != 0 {
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "Idle connection was not closed"
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    alice
+        .query_drop("SELECT 1")
+        .await
+        .expect_err("Drained connection should be closed");
+    bob.query_drop("SELECT 1").await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn pooled_upstream_session_state_is_isolated_between_clients() {
+    let (opts, _handle) = TestBuilder::new(BackendBuilder::new().require_authentication(false))
+        .fallback(true)
+        .upstream_pool_size(1)
+        .build::<MySQLAdapter>()
+        .await;
+
+    let mut first = mysql_async::Conn::new(opts.clone()).await.unwrap();
+    let upstream_id: u32 = first
+        .query_first("SELECT CONNECTION_ID()")
+        .await
+        .unwrap()
+        .unwrap();
+    first.query_drop("SET @pool_isolation = 1").await.unwrap();
+    first.query_drop("USE information_schema").await.unwrap();
+    first.disconnect().await.unwrap();
+    sleep().await;
+
+    let mut second = mysql_async::Conn::new(opts).await.unwrap();
+    let (reused_id, var, db): (u32, Option<i64>, String) = second
+        .query_first("SELECT CONNECTION_ID(), @pool_isolation, DATABASE()")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        reused_id, upstream_id,
+        "Upstream connection should be reused"
+    );
+    assert_eq!(var, None);
+    assert_eq!(db, "noria");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn pooled_upstream_keeps_connection_for_writes_and_transactions() {
+    let (opts, _handle) = TestBuilder::new(BackendBuilder::new().require_authentication(false))
+        .fallback(true)
+        .upstream_pool_size(2)
+        .build::<MySQLAdapter>()
+        .await;
+
+    let mut setup = mysql_async::Conn::new(opts.clone()).await.unwrap();
+    setup
+        .query_drop("CREATE TABLE pooled_writes (id INT AUTO_INCREMENT PRIMARY KEY, x INT)")
+        .await
+        .unwrap();
+    setup.disconnect().await.unwrap();
+    sleep().await;
+
+    let mut first = mysql_async::Conn::new(opts.clone()).await.unwrap();
+    let mut second = mysql_async::Conn::new(opts).await.unwrap();
+
+    first.query_drop("BEGIN").await.unwrap();
+    first
+        .query_drop("INSERT INTO pooled_writes (x) VALUES (1)")
+        .await
+        .unwrap();
+    let first_id: u64 = first
+        .query_first("SELECT LAST_INSERT_ID()")
+        .await
+        .unwrap()
+        .unwrap();
+
+    second
+        .query_drop("INSERT INTO pooled_writes (x) VALUES (2)")
+        .await
+        .unwrap();
+    let second_id: u64 = second
+        .query_first("SELECT LAST_INSERT_ID()")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_ne!(first_id, second_id);
+
+    // The first client's transaction is still open on its own connection, so the second client
+    // can't see its write
+    let visible: Vec<i32> = second
+        .query("SELECT x FROM pooled_writes WHERE x = 1 /* readyset: proxy */")
+        .await
+        .unwrap();
+    assert!(visible.is_empty());
+
+    // And both clients still see the ID of their own last insert
+    let id: u64 = first
+        .query_first("SELECT LAST_INSERT_ID()")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(id, first_id);
+    first.query_drop("COMMIT").await.unwrap();
+    let id: u64 = first
+        .query_first("SELECT LAST_INSERT_ID()")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(id, first_id);
+
+    let id: u64 = second
+        .query_first("SELECT LAST_INSERT_ID()")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(id, second_id);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn upstream_routes_select_upstream_by_schema() {
+    // Each route connects to the upstream as a different user, so we can tell which upstream a
+    // query reached from `CURRENT_USER()`
+    let mut management = mysql_async::Conn::from_url(MySQLAdapter::url())
+        .await
+        .unwrap();
+    for shard in ["shard_a", "shard_b"] {
+        recreate_database(shard).await;
+        management
+            .query_drop(format!(
+                "CREATE USER IF NOT EXISTS '{shard}'@'%' \
+                 IDENTIFIED WITH mysql_native_password BY 'shard'"
+            ))
+            .await
+            .unwrap();
+        management
+            .query_drop(format!("GRANT ALL ON *.* TO '{shard}'@'%'"))
+            .await
+            .unwrap();
+    }
+    management.disconnect().await.unwrap();
+
+    let host = env::var("MYSQL_HOST").unwrap_or_else(|_| "127.0.0.1".into());
+    let port = env::var("MYSQL_TCP_PORT").unwrap_or_else(|_| "3306".into());
+    let routes = ["shard_a", "shard_b"]
+        .into_iter()
+        .map(|shard| {
+            format!("{shard}=mysql://{shard}:shard@{host}:{port}/{shard}")
+                .parse::<UpstreamRoute>()
+                .unwrap()
+        })
+        .collect::<UpstreamRoutes>()
+        .with_default_url(Some(MySQLAdapter::url().into()));
+    let (opts, _handle) = setup_with(
+        BackendBuilder::new()
+            .require_authentication(false)
+            .upstream_routes(Arc::new(routes)),
+    )
+    .await;
+
+    async fn current_user(conn: &mut mysql_async::Conn) -> String {
+        conn.query_first("SELECT CURRENT_USER() /* readyset: proxy */")
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    // Connections are routed by the schema they connect to...
+    let mut conn = mysql_async::Conn::new(
+        mysql_async::OptsBuilder::from_opts(opts.clone()).db_name(Some("shard_a")),
+    )
+    .await
+    .unwrap();
+    assert_eq!(current_user(&mut conn).await, "shard_a@%");
+
+    // ...and rerouted when they switch schemas, including back to the default upstream
+    conn.query_drop("USE shard_b").await.unwrap();
+    assert_eq!(current_user(&mut conn).await, "shard_b@%");
+    conn.query_drop("USE noria").await.unwrap();
+    assert!(current_user(&mut conn).await.starts_with("root@"));
+    conn.query_drop("USE shard_a").await.unwrap();
+    assert_eq!(current_user(&mut conn).await, "shard_a@%");
+
+    let mut conn =
+        mysql_async::Conn::new(mysql_async::OptsBuilder::from_opts(opts).db_name(Some("shard_b")))
+            .await
+            .unwrap();
+    assert_eq!(current_user(&mut conn).await, "shard_b@%");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn write_is_not_retried_after_upstream_connection_loss() {
+    let (opts, _handle) = setup().await;
+    let mut conn = mysql_async::Conn::new(opts).await.unwrap();
+    conn.query_drop("CREATE TABLE write_not_retried (x INT)")
+        .await
+        .unwrap();
+    sleep().await;
+
+    let write = tokio::spawn(async move {
+        conn.query_drop("INSERT INTO write_not_retried SELECT SLEEP(5)")
+            .await
+    });
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    // Kill the adapter's connection to the upstream database while it's running the write
+    let mut upstream = mysql_async::Conn::from_url(MySQLAdapter::url_with_db("noria"))
+        .await
+        .unwrap();
+    let upstream_id: u64 = upstream
+        .query_first(
+            "SELECT ID FROM information_schema.PROCESSLIST \
+             WHERE INFO LIKE 'INSERT INTO write_not_retried%'",
+        )
+        .await
+        .unwrap()
+        .expect("Write should be running on the upstream database");
+    upstream
+        .query_drop(format!("KILL CONNECTION {upstream_id}"))
+        .await
+        .unwrap();
+
+    write
+        .await
+        .unwrap()
+        .expect_err("Losing the upstream connection should fail the write");
+
+    // Give a (wrongly) retried write time to finish before checking that it never ran again
+    tokio::time::sleep(Duration::from_secs(6)).await;
+    let rows: u64 = upstream
+        .query_first("SELECT COUNT(*) FROM write_not_retried")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(rows, 0);
+}
+
 #[allow(dead_code)]
 async fn last_statement_matches(dest: &str, status: &str, client: &mut mysql_async::Conn) -> bool {
     let rows: Vec<(String, String)> = client
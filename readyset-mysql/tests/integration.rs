@@ -1,6 +1,6 @@
+use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, RwLock};
 
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use mysql_async::prelude::Queryable;
@@ -1595,6 +1595,43 @@ async fn explain_graphviz() {
     );
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn explain_cache() {
+    let (opts, _handle) = setup().await;
+    let mut conn = mysql_async::Conn::new(opts).await.unwrap();
+    conn.query_drop("CREATE TABLE cats (id INT, name TEXT);")
+        .await
+        .unwrap();
+    conn.query_drop("CREATE TABLE dogs (id INT, name TEXT);")
+        .await
+        .unwrap();
+    sleep().await;
+
+    conn.query_drop(
+        "CREATE CACHE pets FROM SELECT cats.name FROM cats JOIN dogs ON cats.id = dogs.id \
+         WHERE cats.id = ? AND dogs.name = 'a secret name';",
+    )
+    .await
+    .unwrap();
+    sleep().await;
+
+    let res: mysql_async::Row = conn
+        .query_first("EXPLAIN CACHE pets;")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(res.columns().as_ref().first().unwrap().name_str(), "PLAN");
+    let plan: String = res.get(0).unwrap();
+    assert!(plan.contains("cats"), "{plan}");
+    assert!(plan.contains("dogs"), "{plan}");
+    // Literal values are never included in the plan
+    assert!(!plan.contains("a secret name"), "{plan}");
+
+    conn.query_drop("EXPLAIN CACHE nonexistent;")
+        .await
+        .unwrap_err();
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn explain_last_statement() {
     let (opts, _handle) = setup().await;
@@ -1620,6 +1657,43 @@ async fn explain_last_statement() {
     assert_eq!(destination.destination, QueryDestination::Readyset);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn show_readyset_connection_stats() {
+    let (opts, _handle) = setup().await;
+    let mut conn = mysql_async::Conn::new(opts).await.unwrap();
+    conn.query_drop("CREATE TABLE test (x int, y int)")
+        .await
+        .unwrap();
+    conn.query_drop("INSERT INTO test (x, y) VALUES (4, 2)")
+        .await
+        .unwrap();
+    sleep().await;
+
+    let (queries_before, cache_hits_before, _): (u64, u64, u64) = conn
+        .query_first("SHOW READYSET CONNECTION STATS")
+        .await
+        .unwrap()
+        .unwrap();
+
+    conn.query_drop("SELECT * FROM test").await.unwrap();
+    conn.query_drop("SELECT * FROM test WHERE x = 4")
+        .await
+        .unwrap();
+    conn.query_drop("INSERT INTO test (x, y) VALUES (5, 3)")
+        .await
+        .unwrap();
+
+    let (queries, cache_hits, _): (u64, u64, u64) = conn
+        .query_first("SHOW READYSET CONNECTION STATS")
+        .await
+        .unwrap()
+        .unwrap();
+
+    // The first `SHOW READYSET CONNECTION STATS` also counts as a query, but not a cache hit
+    assert_eq!(queries - queries_before, 4);
+    assert_eq!(cache_hits - cache_hits_before, 2);
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn create_query_cache_where_in() {
     let (opts, _handle) = setup().await;
@@ -1753,9 +1827,7 @@ async fn test_show_proxied_queries_telemetry() {
         .is_empty());
 
     conn.query_drop("SHOW PROXIED QUERIES").await.unwrap();
-    reporter
-        .test_run_once(&mut tokio::time::interval(Duration::from_nanos(1)))
-        .await;
+    reporter.test_run_once().await;
 
     assert_eq!(
         1,
@@ -1779,9 +1851,7 @@ async fn test_show_caches_queries_telemetry() {
         .is_empty());
 
     conn.query_drop("SHOW CACHES").await.unwrap();
-    reporter
-        .test_run_once(&mut tokio::time::interval(Duration::from_nanos(1)))
-        .await;
+    reporter.test_run_once().await;
 
     assert_eq!(
         1,
@@ -1794,10 +1864,9 @@ async fn test_proxied_queries_telemetry() {
     readyset_tracing::init_test_logging();
     // This variation on setup_telemetry sets up a periodic reporter for proxied queries.
     let (mut reporter, opts, _handle) = {
-        let query_status_cache = Box::leak(Box::new(QueryStatusCache::with_style(
-            MigrationStyle::Explicit,
-        )));
-        let proxied_queries_reporter = Arc::new(ProxiedQueriesReporter::new(query_status_cache));
+        let query_status_cache = Arc::new(QueryStatusCache::with_style(MigrationStyle::Explicit));
+        let proxied_queries_reporter =
+            Arc::new(ProxiedQueriesReporter::new(query_status_cache.clone()));
         let (telemetry_sender, mut reporter) = TelemetryInitializer::test_init();
         reporter
             .register_periodic_reporter(proxied_queries_reporter)
@@ -1835,13 +1904,7 @@ async fn test_proxied_queries_telemetry() {
         QueryDestination::Upstream
     );
 
-    let mut interval = tokio::time::interval(Duration::from_millis(1));
-
-    // Run once to get the new event, and once to run the periodic reporter
-    reporter.test_run_once(&mut interval).await;
-    tokio::time::sleep(Duration::from_millis(5)).await;
-    reporter.test_run_once(&mut interval).await;
-    tokio::time::sleep(Duration::from_millis(5)).await;
+    reporter.test_run_periodic_reports().await;
 
     let telemetry = reporter
         .check_event(TelemetryEvent::ProxiedQuery)
@@ -1860,3 +1923,37 @@ async fn test_proxied_queries_telemetry() {
     // with its initial value
     assert_eq!(telemetry.migration_status, Some("pending".to_string()));
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replacing_users_keeps_open_connections() {
+    readyset_tracing::init_test_logging();
+    let users = Arc::new(RwLock::new(HashMap::from([(
+        "alice".to_owned(),
+        "old".to_owned(),
+    )])));
+    let (opts, _handle) = TestBuilder::new(BackendBuilder::new().users(users.clone()))
+        .build::<MySQLAdapter>()
+        .await;
+    let with_password = |password: &str| -> mysql_async::Opts {
+        OptsBuilder::from_opts(opts.clone())
+            .user(Some("alice"))
+            .pass(Some(password))
+            .into()
+    };
+    let mut conn = mysql_async::Conn::new(with_password("old")).await.unwrap();
+
+    // This is what the adapter does when it reloads --auth-file on SIGHUP
+    *users.write().unwrap() = HashMap::from([("alice".to_owned(), "new".to_owned())]);
+
+    // The connection which authenticated with the old password keeps working...
+    conn.query_drop("CREATE TABLE t (x int)").await.unwrap();
+    conn.query_drop("INSERT INTO t (x) VALUES (1)")
+        .await
+        .unwrap();
+
+    // ...but new connections have to use the new one
+    mysql_async::Conn::new(with_password("old"))
+        .await
+        .unwrap_err();
+    mysql_async::Conn::new(with_password("new")).await.unwrap();
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use mysql_async::prelude::*;
 use mysql_async::{Conn, Result, Row, Statement};
 use readyset_adapter::backend::{MigrationMode, QueryInfo, UnsupportedSetMode};
@@ -10,7 +12,7 @@ use readyset_server::Handle;
 use serial_test::serial;
 
 pub async fn setup(
-    query_status_cache: &'static QueryStatusCache,
+    query_status_cache: Arc<QueryStatusCache>,
     fallback: bool,
     migration_mode: MigrationMode,
     set_mode: UnsupportedSetMode,
@@ -33,9 +35,9 @@ pub async fn setup(
 #[tokio::test(flavor = "multi_thread")]
 #[serial]
 async fn in_request_path_query_with_fallback() {
-    let query_status_cache: &'static _ = Box::leak(Box::new(QueryStatusCache::new()));
+    let query_status_cache = Arc::new(QueryStatusCache::new());
     let (opts, _handle) = setup(
-        query_status_cache,
+        query_status_cache.clone(),
         true, // fallback enabled
         MigrationMode::InRequestPath,
         UnsupportedSetMode::Error,
@@ -79,9 +81,9 @@ async fn in_request_path_query_with_fallback() {
 #[tokio::test(flavor = "multi_thread")]
 #[serial]
 async fn in_request_path_query_without_fallback() {
-    let query_status_cache: &'static _ = Box::leak(Box::new(QueryStatusCache::new()));
+    let query_status_cache = Arc::new(QueryStatusCache::new());
     let (opts, _handle) = setup(
-        query_status_cache,
+        query_status_cache.clone(),
         false, // fallback disabled
         MigrationMode::InRequestPath,
         UnsupportedSetMode::Error,
@@ -110,9 +112,9 @@ async fn in_request_path_query_without_fallback() {
 #[tokio::test(flavor = "multi_thread")]
 #[serial]
 async fn out_of_band_query_with_fallback() {
-    let query_status_cache: &'static _ = Box::leak(Box::new(QueryStatusCache::new()));
+    let query_status_cache = Arc::new(QueryStatusCache::new());
     let (opts, _handle) = setup(
-        query_status_cache,
+        query_status_cache.clone(),
         true, // fallback enabled
         MigrationMode::OutOfBand,
         UnsupportedSetMode::Error,
@@ -158,7 +160,7 @@ async fn out_of_band_query_with_fallback() {
 #[tokio::test(flavor = "multi_thread")]
 #[serial]
 async fn autocommit_state_query() {
-    let _query_status_cache: &'static _ = Box::leak(Box::new(QueryStatusCache::new()));
+    let _query_status_cache = Arc::new(QueryStatusCache::new());
     let (opts, _handle) = setup(
         _query_status_cache,
         true, // fallback enabled
@@ -274,7 +276,7 @@ async fn autocommit_state_query() {
 #[tokio::test(flavor = "multi_thread")]
 #[serial]
 async fn autocommit_prepare_execute() {
-    let _query_status_cache: &'static _ = Box::leak(Box::new(QueryStatusCache::new()));
+    let _query_status_cache = Arc::new(QueryStatusCache::new());
     let (opts, _handle) = setup(
         _query_status_cache,
         true, // fallback enabled
@@ -359,9 +361,9 @@ async fn autocommit_prepare_execute() {
 #[tokio::test(flavor = "multi_thread")]
 #[serial]
 async fn in_request_path_prep_exec_with_fallback() {
-    let query_status_cache: &'static _ = Box::leak(Box::new(QueryStatusCache::new()));
+    let query_status_cache = Arc::new(QueryStatusCache::new());
     let (opts, _handle) = setup(
-        query_status_cache,
+        query_status_cache.clone(),
         true, // fallback enabled
         MigrationMode::InRequestPath,
         UnsupportedSetMode::Error,
@@ -437,9 +439,9 @@ async fn in_request_path_prep_exec_with_fallback() {
 #[tokio::test(flavor = "multi_thread")]
 #[serial]
 async fn in_request_path_prep_without_fallback() {
-    let query_status_cache: &'static _ = Box::leak(Box::new(QueryStatusCache::new()));
+    let query_status_cache = Arc::new(QueryStatusCache::new());
     let (opts, _handle) = setup(
-        query_status_cache,
+        query_status_cache.clone(),
         false, // fallback disabled
         MigrationMode::InRequestPath,
         UnsupportedSetMode::Error,
@@ -468,9 +470,9 @@ async fn in_request_path_prep_without_fallback() {
 #[tokio::test(flavor = "multi_thread")]
 #[serial]
 async fn out_of_band_prep_exec_with_fallback() {
-    let query_status_cache: &'static _ = Box::leak(Box::new(QueryStatusCache::new()));
+    let query_status_cache = Arc::new(QueryStatusCache::new());
     let (opts, _handle) = setup(
-        query_status_cache,
+        query_status_cache.clone(),
         true, // fallback enabled
         MigrationMode::OutOfBand,
         UnsupportedSetMode::Error,
@@ -564,9 +566,9 @@ async fn out_of_band_prep_exec_with_fallback() {
 #[tokio::test(flavor = "multi_thread")]
 #[serial]
 async fn in_request_path_rewritten_query_without_fallback() {
-    let query_status_cache: &'static _ = Box::leak(Box::new(QueryStatusCache::new()));
+    let query_status_cache = Arc::new(QueryStatusCache::new());
     let (opts, _handle) = setup(
-        query_status_cache,
+        query_status_cache.clone(),
         false, // fallback disabled
         MigrationMode::InRequestPath,
         UnsupportedSetMode::Error,
@@ -597,9 +599,9 @@ async fn in_request_path_rewritten_query_without_fallback() {
 #[tokio::test(flavor = "multi_thread")]
 #[serial]
 async fn out_of_band_rewritten_query_without_fallback() {
-    let query_status_cache: &'static _ = Box::leak(Box::new(QueryStatusCache::new()));
+    let query_status_cache = Arc::new(QueryStatusCache::new());
     let (opts, _handle) = setup(
-        query_status_cache,
+        query_status_cache.clone(),
         false, // fallback disabled
         MigrationMode::OutOfBand,
         UnsupportedSetMode::Error,
@@ -625,9 +627,9 @@ async fn out_of_band_rewritten_query_without_fallback() {
 
 #[tokio::test(flavor = "multi_thread")]
 async fn drop_all_caches() {
-    let query_status_cache: &'static _ = Box::leak(Box::new(QueryStatusCache::new()));
+    let query_status_cache = Arc::new(QueryStatusCache::new());
     let (opts, _handle) = setup(
-        query_status_cache,
+        query_status_cache.clone(),
         false, // fallback disabled
         MigrationMode::OutOfBand,
         UnsupportedSetMode::Error,
@@ -12,12 +12,13 @@
 //! $ cargo criterion -p noria-psql --bench proxy
 //! ```
 
-use std::io;
 use std::net::SocketAddr;
+use std::{io, vec};
 
 use async_trait::async_trait;
 use criterion::{criterion_group, criterion_main, Criterion};
 use futures::future::{try_select, Either};
+use futures::stream;
 use psql_srv::{Credentials, CredentialsNeeded, QueryResponse};
 use readyset_psql::Value;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -78,7 +79,7 @@ struct Backend {
 impl psql_srv::Backend for Backend {
     type Value = Value;
     type Row = Vec<Self::Value>;
-    type Resultset = Vec<Self::Row>;
+    type Resultset = stream::Iter<vec::IntoIter<Result<Self::Row, psql_srv::Error>>>;
 
     fn version(&self) -> String {
         "14".into()
@@ -115,17 +116,18 @@ impl psql_srv::Backend for Backend {
                         .collect()
                 })
                 .unwrap_or_default(),
-            resultset: res
-                .into_iter()
-                .map(|r| {
-                    (0..r.len())
-                        .map(|i| Value {
-                            col_type: r.columns()[i].type_().clone(),
-                            value: r.get(i),
-                        })
-                        .collect()
-                })
-                .collect(),
+            resultset: stream::iter(
+                res.into_iter()
+                    .map(|r| {
+                        Ok((0..r.len())
+                            .map(|i| Value {
+                                col_type: r.columns()[i].type_().clone(),
+                                value: r.get(i),
+                            })
+                            .collect())
+                    })
+                    .collect::<Vec<_>>(),
+            ),
         })
     }
 
@@ -3,10 +3,14 @@ use std::ops::Deref;
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use bytes::Bytes;
 use eui48::MacAddressFormat;
+use futures::{StreamExt, TryStreamExt};
 use psql_srv as ps;
 use readyset_adapter::backend as cl;
+use readyset_adapter::upstream_database::{IsFatalError, UpstreamErrorClass};
 use readyset_data::DfValue;
+use readyset_errors::ReadySetError;
 
 use crate::error::Error;
 use crate::query_handler::PostgreSqlQueryHandler;
@@ -42,6 +46,14 @@ impl Backend {
     async fn execute(&mut self, id: u32, params: &[DfValue]) -> Result<QueryResponse<'_>, Error> {
         Ok(QueryResponse(self.0.execute(id, params).await?))
     }
+
+    /// `COPY` statements are always proxied directly to the upstream database, so they're only
+    /// supported if we have one
+    fn copy_upstream(&mut self) -> Result<&mut PostgreSqlUpstream, Error> {
+        self.0.upstream_mut().ok_or_else(|| {
+            ReadySetError::Unsupported("COPY requires an upstream database".into()).into()
+        })
+    }
 }
 
 #[async_trait]
@@ -54,15 +66,35 @@ impl ps::Backend for Backend {
         self.0.version()
     }
 
-    async fn on_init(&mut self, _database: &str) -> Result<ps::CredentialsNeeded, ps::Error> {
+    async fn on_init(&mut self, database: &str) -> Result<ps::CredentialsNeeded, ps::Error> {
+        self.route_upstream(database).await?;
         match self.does_require_authentication() {
             true => Ok(ps::CredentialsNeeded::Cleartext),
             false => Ok(ps::CredentialsNeeded::None),
         }
     }
 
+    fn on_application_name(&mut self, application_name: &str) {
+        self.0.set_application_name(application_name);
+    }
+
+    fn on_user(&mut self, user: &str) {
+        self.0.set_user(user);
+    }
+
     async fn on_query(&mut self, query: &str) -> Result<ps::QueryResponse<Resultset>, ps::Error> {
-        self.query(query).await?.try_into()
+        let mut retries = 0;
+        let error = loop {
+            let error = match self.query(query).await {
+                Err(error) if error.classify() == UpstreamErrorClass::Retryable => error,
+                res => return res?.try_into(),
+            };
+            if !self.0.should_retry(&error, retries) {
+                break error;
+            }
+            retries += 1;
+        };
+        Err(error.into())
     }
 
     async fn on_prepare(&mut self, query: &str) -> Result<ps::PrepareResponse, ps::Error> {
@@ -96,6 +128,47 @@ impl ps::Backend for Backend {
             }
         }
     }
+
+    fn backend_key_data(&self) -> Option<(i32, i32)> {
+        // The process ID and secret key are opaque to clients, so we reinterpret the bits of our
+        // (unsigned) cancel key as signed integers to send them over the wire
+        self.cancel_key()
+            .map(|key| (key.connection_id as i32, key.secret as i32))
+    }
+
+    async fn on_cancel(&mut self, process_id: i32, secret_key: i32) {
+        self.cancel_query(process_id as u32, secret_key as u32);
+    }
+
+    fn on_request_complete(&mut self) {
+        self.0.release_upstream();
+    }
+
+    async fn on_copy_in(&mut self, query: &str) -> Result<(), ps::Error> {
+        // The rest of the copy is sent in separate requests, on the same upstream connection
+        self.0.pin_upstream().await?;
+        Ok(self.copy_upstream()?.copy_in(query).await?)
+    }
+
+    async fn on_copy_data(&mut self, data: Bytes) -> Result<(), ps::Error> {
+        Ok(self.copy_upstream()?.copy_in_data(data).await?)
+    }
+
+    async fn on_copy_done(&mut self) -> Result<u64, ps::Error> {
+        Ok(self.copy_upstream()?.copy_in_done().await?)
+    }
+
+    async fn on_copy_fail(&mut self) {
+        if let Some(upstream) = self.0.upstream_mut() {
+            upstream.copy_in_fail();
+        }
+    }
+
+    async fn on_copy_out(&mut self, query: &str) -> Result<ps::CopyOutStream, ps::Error> {
+        self.0.pin_upstream().await?;
+        let data = self.copy_upstream()?.copy_out(query).await?;
+        Ok(data.map_err(ps::Error::from).boxed())
+    }
 }
 
 /// A simple wrapper around a request parameter `psql_srv::Value` reference, facilitiating
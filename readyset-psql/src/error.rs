@@ -1,9 +1,10 @@
 use std::io;
 
 use psql_srv as ps;
-use readyset_adapter::upstream_database::IsFatalError;
+use readyset_adapter::upstream_database::{IsFatalError, UpstreamErrorClass};
 use readyset_client::ReadySetError;
 use thiserror::Error;
+use tokio_postgres::error::SqlState;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -27,6 +28,10 @@ impl From<Error> for ps::Error {
                 ps::Error::MissingPreparedStatement(statement_id.to_string())
             }
             ReadySet(ReadySetError::Unsupported(s)) => ps::Error::Unsupported(s),
+            ReadySet(ReadySetError::QueryCancelled) => ps::Error::QueryCancelled,
+            ReadySet(e @ ReadySetError::ConnectionDrained { .. }) => {
+                ps::Error::AdminShutdown(e.to_string())
+            }
             ReadySet(e) => ps::Error::Unknown(e.to_string()),
             PostgreSql(e) => e.into(),
         }
@@ -39,4 +44,75 @@ impl IsFatalError for Error {
         // we can't detect io, or other fatal errors.
         matches!(self, Self::PostgreSql(e) if e.is_closed())
     }
+
+    fn classify(&self) -> UpstreamErrorClass {
+        match self {
+            Self::PostgreSql(e) if e.is_closed() => UpstreamErrorClass::Fatal,
+            Self::PostgreSql(e) => e
+                .code()
+                .map_or(UpstreamErrorClass::Passthrough, classify_sqlstate),
+            _ => UpstreamErrorClass::Passthrough,
+        }
+    }
+
+    fn is_authentication_error(&self) -> bool {
+        matches!(
+            self,
+            Self::PostgreSql(e) if e.code().map_or(false, is_authentication_sqlstate)
+        )
+    }
+}
+
+/// Classify an error returned by the upstream database with the given SQLSTATE code
+fn classify_sqlstate(code: &SqlState) -> UpstreamErrorClass {
+    if *code == SqlState::T_R_DEADLOCK_DETECTED
+        || *code == SqlState::T_R_SERIALIZATION_FAILURE
+        || *code == SqlState::LOCK_NOT_AVAILABLE
+    {
+        UpstreamErrorClass::Retryable
+    } else {
+        UpstreamErrorClass::Passthrough
+    }
+}
+
+/// Returns true if the given SQLSTATE code means the upstream database rejected our credentials
+fn is_authentication_sqlstate(code: &SqlState) -> bool {
+    *code == SqlState::INVALID_AUTHORIZATION_SPECIFICATION || *code == SqlState::INVALID_PASSWORD
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_postgres_sqlstates() {
+        for (code, class) in [
+            ("40P01", UpstreamErrorClass::Retryable),
+            ("40001", UpstreamErrorClass::Retryable),
+            ("55P03", UpstreamErrorClass::Retryable),
+            ("42601", UpstreamErrorClass::Passthrough),
+            ("23505", UpstreamErrorClass::Passthrough),
+            ("42P01", UpstreamErrorClass::Passthrough),
+        ] {
+            assert_eq!(
+                classify_sqlstate(&SqlState::from_code(code)),
+                class,
+                "{code}"
+            );
+        }
+    }
+
+    #[test]
+    fn deadlock_is_retried_once() {
+        let class = classify_sqlstate(&SqlState::T_R_DEADLOCK_DETECTED);
+        assert!(class.should_retry(0));
+        assert!(!class.should_retry(1));
+    }
+
+    #[test]
+    fn authentication_sqlstates() {
+        assert!(is_authentication_sqlstate(&SqlState::from_code("28P01")));
+        assert!(is_authentication_sqlstate(&SqlState::from_code("28000")));
+        assert!(!is_authentication_sqlstate(&SqlState::from_code("57P01")));
+    }
 }
@@ -1,9 +1,9 @@
 use std::borrow::Cow;
 use std::convert::{TryFrom, TryInto};
 
+use futures::{StreamExt, TryStreamExt};
 use nom_sql::SqlIdentifier;
 use psql_srv as ps;
-use psql_srv::Column;
 use readyset_adapter::backend::{
     self as cl, noria_connector, SinglePrepareResult, UpstreamPrepare,
 };
@@ -199,28 +199,17 @@ impl<'a> TryFrom<QueryResponse<'a>> for ps::QueryResponse<Resultset> {
                     resultset,
                 })
             }
-            Upstream(upstream::QueryResult::Read { data: rows }) => {
-                let schema = match rows.first() {
-                    None => vec![],
-                    Some(row) => row
-                        .columns()
-                        .iter()
-                        .map(|c| Column {
-                            name: c.name().to_owned(),
-                            col_type: c.type_().clone(),
-                        })
-                        .collect(),
-                };
-                Ok(ps::QueryResponse::Select {
-                    schema,
-                    resultset: Resultset::try_from(rows)?,
-                })
+            Upstream(upstream::QueryResult::Read { schema, data }) => {
+                let resultset = Resultset::from_upstream(data, &schema);
+                Ok(ps::QueryResponse::Select { schema, resultset })
             }
             Upstream(upstream::QueryResult::Write { num_rows_affected }) => {
                 Ok(Insert(num_rows_affected))
             }
             Upstream(upstream::QueryResult::Command) => Ok(Command),
-            Upstream(upstream::QueryResult::SimpleQuery(resp)) => Ok(SimpleQuery(resp)),
+            Upstream(upstream::QueryResult::SimpleQuery(upstream::SimpleQueryStream(messages))) => {
+                Ok(SimpleQuery(messages.map_err(ps::Error::from).boxed()))
+            }
         }
     }
 }
@@ -1,26 +1,40 @@
-use std::convert::TryFrom;
-use std::iter;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll};
 
+use futures::stream::BoxStream;
+use futures::{ready, Stream, StreamExt};
 use psql_srv as ps;
-use readyset_client::results::{ResultIterator, Results};
+use readyset_client::results::ResultIterator;
 use readyset_data::DfValue;
 use tokio_postgres::types::Type;
 
 use crate::row::Row;
 use crate::schema::{type_to_pgsql, SelectSchema};
+use crate::upstream::RowStream;
+use crate::Error;
 
-/// A structure that contains a `ResultIterator` and facilitates iteration over these results as
-/// `Row` values.
+/// A structure that contains the rows of a query's result set, either as a `ResultIterator` of
+/// results read from ReadySet or as a stream of rows proxied from the upstream database, and
+/// facilitates streaming these results as `Row` values.
 pub struct Resultset {
-    /// The query result data, comprising nested `Vec`s of rows that may come from separate
-    /// ReadySet interface lookups performed by the backend.
-    results: ResultIterator,
+    /// The query result data.
+    results: ResultsetRows,
 
     /// The data types of the projected fields for each row.
     project_field_types: Arc<Vec<Type>>,
 }
 
+/// The source of the rows of a [`Resultset`]
+enum ResultsetRows {
+    /// Rows comprising nested `Vec`s of results that may come from separate ReadySet interface
+    /// lookups performed by the backend.
+    ReadySet(<ResultIterator as IntoIterator>::IntoIter),
+
+    /// Rows which are read from the upstream database as the resultset is polled.
+    Upstream(BoxStream<'static, Result<tokio_postgres::Row, Error>>),
+}
+
 impl Resultset {
     pub fn try_new(results: ResultIterator, schema: &SelectSchema) -> Result<Self, ps::Error> {
         // Extract the appropriate `tokio_postgres` `Type` for each column in the schema.
@@ -33,56 +47,59 @@ impl Resultset {
                 .collect::<Result<Vec<_>, _>>()?,
         );
         Ok(Resultset {
-            results,
+            results: ResultsetRows::ReadySet(results.into_iter()),
             project_field_types,
         })
     }
-}
 
-// An iterator over the rows contained within the `Resultset`.
-impl IntoIterator for Resultset {
-    type Item = Row;
-    type IntoIter = impl Iterator<Item = Row>;
-
-    fn into_iter(self) -> Self::IntoIter {
-        self.results
-            .into_iter()
-            .zip(iter::repeat(self.project_field_types))
-            .map(|(values, project_field_types)| Row {
-                values,
-                project_field_types,
-            })
+    /// Create a new `Resultset` from the rows of a result set proxied from the upstream database,
+    /// with the given schema
+    pub fn from_upstream(rows: RowStream, schema: &[ps::Column]) -> Self {
+        Resultset {
+            results: ResultsetRows::Upstream(rows.0),
+            project_field_types: Arc::new(schema.iter().map(|c| c.col_type.clone()).collect()),
+        }
     }
 }
 
-impl TryFrom<Vec<tokio_postgres::Row>> for Resultset {
-    type Error = psql_srv::Error;
+/// Extract the values of a row returned by the upstream database
+fn upstream_row_values(row: &tokio_postgres::Row) -> Result<Vec<DfValue>, ps::Error> {
+    (0..row.len())
+        .map(|i| {
+            row.try_get(i).map_err(|e| {
+                ps::Error::InternalError(format!(
+                    "could not retrieve expected column index {} from row while parsing psql result: {}",
+                    i,
+                    e
+                ))
+            })
+        })
+        .collect()
+}
 
-    fn try_from(rows: Vec<tokio_postgres::Row>) -> Result<Self, Self::Error> {
-        let columns = match rows.first() {
-            Some(row) => row.columns(),
-            None => &[],
+// A stream of the rows contained within the `Resultset`.
+impl Stream for Resultset {
+    type Item = Result<Row, ps::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let values = match &mut self.results {
+            ResultsetRows::ReadySet(results) => match results.next() {
+                Some(values) => values,
+                None => return Poll::Ready(None),
+            },
+            ResultsetRows::Upstream(rows) => match ready!(rows.poll_next_unpin(cx)) {
+                Some(Ok(row)) => match upstream_row_values(&row) {
+                    Ok(values) => values,
+                    Err(e) => return Poll::Ready(Some(Err(e))),
+                },
+                Some(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+                None => return Poll::Ready(None),
+            },
         };
-        let mut result_rows = Vec::new();
-        for row in rows.iter() {
-            let mut result_row = Vec::new();
-            for i in 0..columns.len() {
-                let val: DfValue = row.try_get(i).map_err(|e| {
-                    psql_srv::Error::InternalError(format!(
-                        "could not retrieve expected column index {} from row while parsing psql result: {}",
-                        i,
-                        e
-                    ))
-                })?;
-                result_row.push(val);
-            }
-            result_rows.push(result_row);
-        }
-        let column_types: Vec<Type> = columns.iter().map(|c| c.type_().clone()).collect();
-        Ok(Resultset {
-            results: ResultIterator::owned(vec![Results::new(result_rows)]),
-            project_field_types: Arc::new(column_types),
-        })
+        Poll::Ready(Some(Ok(Row {
+            values,
+            project_field_types: Arc::clone(&self.project_field_types),
+        })))
     }
 }
 
@@ -91,21 +108,27 @@ mod tests {
     use std::borrow::Cow;
     use std::convert::TryFrom;
 
+    use futures::executor::block_on;
+    use futures::stream;
     use readyset_adapter::backend as cl;
+    use readyset_client::results::Results;
     use readyset_client::ColumnSchema;
     use readyset_data::{DfType, DfValue};
+    use readyset_errors::ReadySetError;
 
     use super::*;
 
     fn collect_resultset_values(resultset: Resultset) -> Vec<Vec<ps::Value>> {
-        resultset
-            .into_iter()
-            .map(|r| {
-                r.into_iter()
-                    .map(|v| ps::Value::try_from(v).unwrap())
-                    .collect::<Vec<ps::Value>>()
-            })
-            .collect::<Vec<Vec<ps::Value>>>()
+        block_on(
+            resultset
+                .map(|r| {
+                    r.unwrap()
+                        .into_iter()
+                        .map(|v| ps::Value::try_from(v).unwrap())
+                        .collect::<Vec<ps::Value>>()
+                })
+                .collect::<Vec<Vec<ps::Value>>>(),
+        )
     }
 
     #[test]
@@ -121,8 +144,29 @@ mod tests {
             columns: Cow::Owned(vec!["col1".into()]),
         });
         let resultset = Resultset::try_new(ResultIterator::owned(results), &schema).unwrap();
-        assert_eq!(resultset.results.into_vec(), Vec::<Vec<DfValue>>::new());
         assert_eq!(resultset.project_field_types, Arc::new(vec![Type::INT8]));
+        assert_eq!(
+            collect_resultset_values(resultset),
+            Vec::<Vec<ps::Value>>::new()
+        );
+    }
+
+    #[test]
+    fn upstream_resultset() {
+        let schema = vec![ps::Column {
+            name: "col1".into(),
+            col_type: Type::INT8,
+        }];
+        let rows = RowStream(
+            stream::iter(vec![Err(Error::ReadySet(
+                ReadySetError::ResultSetTooLarge { max_rows: 0 },
+            ))])
+            .boxed(),
+        );
+        let mut resultset = Resultset::from_upstream(rows, &schema);
+        assert_eq!(resultset.project_field_types, Arc::new(vec![Type::INT8]));
+        assert!(block_on(resultset.next()).unwrap().is_err());
+        assert!(block_on(resultset.next()).is_none());
     }
 
     #[test]
@@ -1,22 +1,37 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::ffi::OsStr;
 use std::os::unix::ffi::OsStrExt;
+use std::pin::Pin;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fmt, io};
 
 use async_trait::async_trait;
-use futures::TryStreamExt;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::{future, stream, SinkExt, Stream, StreamExt, TryStreamExt};
 use nom_sql::SqlIdentifier;
 use pgsql::config::Host;
+use pgsql::tls::MakeTlsConnect;
 use pgsql::types::Type;
-use pgsql::{GenericResult, Row, SimpleQueryMessage};
+use pgsql::{CancelToken, GenericResult, Row, SimpleQueryMessage};
+use postgres_native_tls::MakeTlsConnector;
 use psql_srv::Column;
 use readyset_adapter::fallback_cache::FallbackCache;
-use readyset_adapter::upstream_database::{NoriaCompare, UpstreamDestination};
-use readyset_adapter::{UpstreamConfig, UpstreamDatabase, UpstreamPrepare};
+use readyset_adapter::upstream_database::{
+    postgresql_tcp_host, DnsCache, NoriaCompare, TcpBufferSizes, UpstreamDestination,
+    UpstreamQueryCanceller,
+};
+use readyset_adapter::{
+    ProxyOptions, ResultRowLimit, UpstreamConfig, UpstreamDatabase, UpstreamPrepare,
+};
 use readyset_client::ColumnSchema;
 use readyset_data::DfValue;
-use readyset_errors::{unsupported, ReadySetError};
-use readyset_tracing::{debug, info};
+use readyset_errors::{unsupported, unsupported_err, ReadySetError};
+use readyset_tracing::{debug, info, warn};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
 use tokio::process::Command;
 use tokio_postgres as pgsql;
 use tracing::info_span;
@@ -29,12 +44,22 @@ use crate::Error;
 /// during connection phase if the version for the upstream server is too low.
 const MIN_UPSTREAM_VERSION: u16 = 13;
 
+/// The newest upstream server major version that ReadySet has been tested against. Newer
+/// versions are allowed, but a warning is logged when connecting to them.
+const MAX_TESTED_UPSTREAM_VERSION: u16 = 15;
+
 /// A connector to an underlying PostgreSQL database
 pub struct PostgreSqlUpstream {
     /// This is the underlying (regular) PostgreSQL client
     client: pgsql::Client,
     /// A tokio task that handles the connection, required by `tokio_postgres` to operate
     _connection_handle: tokio::task::JoinHandle<Result<(), pgsql::Error>>,
+    /// The TLS connector used to connect to the upstream, which is also used to send cancel
+    /// requests
+    tls: MakeTlsConnector,
+    /// The upstream host, if we open TCP connections to it ourselves rather than letting
+    /// `tokio_postgres` do so
+    upstream_host: Option<UpstreamHost>,
     /// Map from prepared statement IDs to prepared statements
     prepared_statements: HashMap<u32, pgsql::Statement>,
     /// ID for the next prepared statement
@@ -43,6 +68,10 @@ pub struct PostgreSqlUpstream {
     user: Option<String>,
     /// Upstream db configuration
     upstream_config: UpstreamConfig,
+    proxy_options: ProxyOptions,
+    /// The sink for the data of the `COPY ... FROM STDIN` statement currently being proxied to the
+    /// upstream, if any
+    copy_in: Option<Pin<Box<pgsql::CopyInSink<Bytes>>>>,
 
     /// ReadySet-wrapped Postgresql version string, to return to clients
     version: String,
@@ -50,14 +79,291 @@ pub struct PostgreSqlUpstream {
 
 #[derive(Debug)]
 pub enum QueryResult {
-    Read { data: Vec<Row> },
-    Write { num_rows_affected: u64 },
+    Read {
+        /// The columns of the rows returned by the query
+        schema: Vec<Column>,
+        data: RowStream,
+    },
+    Write {
+        num_rows_affected: u64,
+    },
     Command,
-    SimpleQuery(Vec<SimpleQueryMessage>),
+    SimpleQuery(SimpleQueryStream),
 }
 
 impl UpstreamDestination for QueryResult {}
 
+/// The rows of a result set proxied from the upstream database. Rows are read from the upstream
+/// connection as the stream is polled, rather than buffering the whole result set in memory.
+pub struct RowStream(pub BoxStream<'static, Result<Row, Error>>);
+
+impl fmt::Debug for RowStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RowStream").finish_non_exhaustive()
+    }
+}
+
+/// The messages produced by a query run with the simple query protocol, which are read from the
+/// upstream connection as the stream is polled, like [`RowStream`].
+pub struct SimpleQueryStream(pub BoxStream<'static, Result<SimpleQueryMessage, Error>>);
+
+impl fmt::Debug for SimpleQueryStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SimpleQueryStream").finish_non_exhaustive()
+    }
+}
+
+/// Enforce `row_limit` on the result sets in `results` as they're streamed, where `is_row`
+/// identifies the rows of each result set, and any other item ends the current result set.
+///
+/// If result sets which are too large fail with an error, rows are held back until their result
+/// set is complete (or has failed), so that the client never receives part of a result set
+/// followed by an error.
+fn limit_rows<T, E, S>(
+    results: S,
+    row_limit: ResultRowLimit,
+    is_row: fn(&T) -> bool,
+) -> BoxStream<'static, Result<T, Error>>
+where
+    T: Send + 'static,
+    Error: From<E>,
+    S: Stream<Item = Result<T, E>> + Send + 'static,
+{
+    let hold_rows = row_limit.holds_rows();
+    let state = (Box::pin(results), row_limit, VecDeque::new(), Vec::new());
+    stream::try_unfold(
+        state,
+        move |(mut results, mut row_limit, mut ready, mut held)| async move {
+            loop {
+                if let Some(item) = ready.pop_front() {
+                    return Ok::<_, Error>(Some((item, (results, row_limit, ready, held))));
+                }
+                match results.try_next().await? {
+                    Some(row) if is_row(&row) => {
+                        if !row_limit.check_row()? {
+                            continue;
+                        }
+                        if hold_rows {
+                            held.push(row);
+                        } else {
+                            ready.push_back(row);
+                        }
+                    }
+                    Some(item) => {
+                        row_limit.reset();
+                        ready.extend(held.drain(..));
+                        ready.push_back(item);
+                    }
+                    None if held.is_empty() => return Ok(None),
+                    None => ready.extend(held.drain(..)),
+                }
+            }
+        },
+    )
+    .boxed()
+}
+
+/// An upstream host which we open TCP connections to ourselves, rather than letting
+/// `tokio_postgres` do so, either to use its addresses cached in the global [`DnsCache`] or to
+/// configure the buffer sizes of the connection's socket
+#[derive(Debug, Clone)]
+struct UpstreamHost {
+    host: String,
+    port: u16,
+    /// If set, connections are made to the host's addresses in the global [`DnsCache`], which are
+    /// refreshed at this interval
+    refresh_interval: Option<Duration>,
+    buffer_sizes: TcpBufferSizes,
+}
+
+impl PostgreSqlUpstream {
+    /// Starts running a `COPY ... FROM STDIN` statement against the upstream database. The data to
+    /// copy is then sent with [`copy_in_data`](Self::copy_in_data), until the copy is either
+    /// completed with [`copy_in_done`](Self::copy_in_done) or aborted with
+    /// [`copy_in_fail`](Self::copy_in_fail).
+    pub async fn copy_in(&mut self, query: &str) -> Result<(), Error> {
+        self.copy_in = Some(Box::pin(self.client.copy_in(query).await?));
+        Ok(())
+    }
+
+    /// Sends a chunk of data to the upstream for the current `COPY ... FROM STDIN` statement
+    pub async fn copy_in_data(&mut self, data: Bytes) -> Result<(), Error> {
+        let sink = self
+            .copy_in
+            .as_mut()
+            .ok_or_else(|| ReadySetError::Internal("No COPY FROM STDIN in progress".into()))?;
+        sink.send(data).await?;
+        Ok(())
+    }
+
+    /// Completes the current `COPY ... FROM STDIN` statement, returning the number of rows copied
+    pub async fn copy_in_done(&mut self) -> Result<u64, Error> {
+        let mut sink = self
+            .copy_in
+            .take()
+            .ok_or_else(|| ReadySetError::Internal("No COPY FROM STDIN in progress".into()))?;
+        Ok(sink.as_mut().finish().await?)
+    }
+
+    /// Aborts the current `COPY ... FROM STDIN` statement, if any
+    pub fn copy_in_fail(&mut self) {
+        // Dropping the sink without finishing it aborts the copy on the upstream
+        self.copy_in = None;
+    }
+
+    /// Runs a `COPY ... TO STDOUT` statement against the upstream database, returning a stream of
+    /// the copied data
+    pub async fn copy_out(
+        &mut self,
+        query: &str,
+    ) -> Result<BoxStream<'static, Result<Bytes, Error>>, Error> {
+        Ok(self
+            .client
+            .copy_out(query)
+            .await?
+            .map_err(Error::from)
+            .boxed())
+    }
+}
+
+impl UpstreamHost {
+    /// Returns the upstream host to open TCP connections to ourselves, if either caching DNS
+    /// resolution is enabled or TCP buffer sizes are configured, and `pg_config` connects over TCP
+    /// to a single host.
+    ///
+    /// Returns an error if TCP buffer sizes are configured but `pg_config` doesn't connect over TCP
+    /// to a single host, since the buffer sizes couldn't be applied.
+    fn from_config(
+        pg_config: &pgsql::Config,
+        upstream_config: &UpstreamConfig,
+        proxy_options: &ProxyOptions,
+    ) -> Result<Option<Self>, Error> {
+        let refresh_interval = upstream_config.upstream_dns_refresh_interval();
+        let buffer_sizes = proxy_options.tcp_buffer_sizes;
+        if refresh_interval.is_none() && buffer_sizes.is_default() {
+            return Ok(None);
+        }
+        let (host, port) = match postgresql_tcp_host(pg_config) {
+            Some(host_and_port) => host_and_port,
+            None if buffer_sizes.is_default() => return Ok(None),
+            None => {
+                return Err(unsupported_err!(
+                    "TCP buffer sizes can only be set for connections to a single upstream \
+                     PostgreSQL host over TCP"
+                )
+                .into())
+            }
+        };
+        Ok(Some(Self {
+            host: host.to_owned(),
+            port,
+            refresh_interval,
+            buffer_sizes,
+        }))
+    }
+
+    /// Open a new TCP connection to the host, along with a TLS connector for the host's domain
+    async fn connect(
+        &self,
+        tls: &MakeTlsConnector,
+    ) -> io::Result<(
+        TcpStream,
+        <MakeTlsConnector as MakeTlsConnect<TcpStream>>::TlsConnect,
+    )> {
+        let stream = match self.refresh_interval {
+            Some(refresh_interval) => {
+                DnsCache::global()
+                    .connect(&self.host, self.port, refresh_interval, self.buffer_sizes)
+                    .await?
+            }
+            None => {
+                self.buffer_sizes
+                    .connect((self.host.as_str(), self.port))
+                    .await?
+            }
+        };
+        let tls_connect =
+            MakeTlsConnect::<TcpStream>::make_tls_connect(&mut tls.clone(), &self.host)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        Ok((stream, tls_connect))
+    }
+}
+
+/// Cancels queries running on a [`PostgreSqlUpstream`] by sending a cancel request to the
+/// upstream on a new connection
+struct PostgreSqlQueryCanceller {
+    cancel_token: CancelToken,
+    tls: MakeTlsConnector,
+    upstream_host: Option<UpstreamHost>,
+}
+
+#[async_trait]
+impl UpstreamQueryCanceller for PostgreSqlQueryCanceller {
+    async fn cancel(&self) -> anyhow::Result<()> {
+        match &self.upstream_host {
+            // Connections opened with `connect_raw` don't know how to reconnect to the upstream, so
+            // we have to open the connection for the cancel request ourselves
+            Some(host) => {
+                let (stream, tls_connect) = host.connect(&self.tls).await?;
+                self.cancel_token
+                    .cancel_query_raw(stream, tls_connect)
+                    .await?;
+            }
+            None => self.cancel_token.cancel_query(self.tls.clone()).await?,
+        }
+        Ok(())
+    }
+}
+
+/// Checks that an upstream server reporting the given `server_version` is supported.
+///
+/// Servers newer than [`MAX_TESTED_UPSTREAM_VERSION`] are allowed, with a warning, as are
+/// development and pre-release versions (eg `16beta1`), whose major version isn't followed by a
+/// `.`. Unlike MySQL, PostgreSQL servers don't advertise capability flags: we always request
+/// version 3.0 of the protocol, which every supported version speaks, without any protocol
+/// extensions, so newer servers have nothing further to negotiate. Runtime parameters reported
+/// by newer servers that we don't use are ignored.
+fn check_server_version(version: &str) -> Result<(), Error> {
+    let (major, minor) = match version.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => (&version[..idx], version[idx..].trim_start_matches('.')),
+        None => (version, ""),
+    };
+    let major = major
+        .parse()
+        .map_err(|_| Error::ReadySet(ReadySetError::UnparseableServerVersion))?;
+    if major < MIN_UPSTREAM_VERSION {
+        return Err(Error::ReadySet(ReadySetError::UnsupportedServerVersion {
+            major,
+            minor: minor.to_owned(),
+            min: MIN_UPSTREAM_VERSION,
+        }));
+    }
+    if major > MAX_TESTED_UPSTREAM_VERSION {
+        warn!(
+            %version,
+            max_tested_major_version = MAX_TESTED_UPSTREAM_VERSION,
+            "Upstream server is newer than any version ReadySet has been tested against; \
+             proceeding anyway"
+        );
+    }
+    Ok(())
+}
+
+/// Returns the server version sent by the upstream database when establishing `connection`
+fn server_version<S, T>(connection: &pgsql::Connection<S, T>) -> Result<String, Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    connection
+        .parameter("server_version")
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| {
+            ReadySetError::Internal("Upstream database failed to send server version".to_string())
+                .into()
+        })
+}
+
 #[derive(Debug, Clone)]
 pub struct StatementMeta {
     /// The types of the query parameters used for this statement
@@ -121,6 +427,7 @@ impl UpstreamDatabase for PostgreSqlUpstream {
 
     async fn connect(
         upstream_config: UpstreamConfig,
+        proxy_options: ProxyOptions,
         _: Option<FallbackCache<Self::CachedReadResult>>,
     ) -> Result<Self, Error> {
         let url = upstream_config
@@ -140,41 +447,50 @@ impl UpstreamDatabase for PostgreSqlUpstream {
             }
             builder.build().unwrap() // Never returns an error
         };
-        let tls = postgres_native_tls::MakeTlsConnector::new(connector);
+        let tls = MakeTlsConnector::new(connector);
         let span = info_span!(
             "Connecting to PostgreSQL upstream",
             host = ?pg_config.get_hosts(),
             port = ?pg_config.get_ports()
         );
         span.in_scope(|| info!("Establishing connection"));
-        let (client, connection) = pg_config.connect(tls).instrument(span.clone()).await?;
-        let version = connection.parameter("server_version").ok_or_else(|| {
-            ReadySetError::Internal("Upstream database failed to send server version".to_string())
-        })?;
-        let (major, minor) = version
-            .split_once('.')
-            .ok_or(Error::ReadySet(ReadySetError::UnparseableServerVersion))?;
-        let major = major
-            .parse()
-            .map_err(|_| Error::ReadySet(ReadySetError::UnparseableServerVersion))?;
-        if major < MIN_UPSTREAM_VERSION {
-            return Err(Error::ReadySet(ReadySetError::UnsupportedServerVersion {
-                major,
-                minor: minor.to_owned(),
-                min: MIN_UPSTREAM_VERSION,
-            }));
-        }
+        let upstream_host =
+            UpstreamHost::from_config(&pg_config, &upstream_config, &proxy_options)?;
+        let (client, version, _connection_handle) = match &upstream_host {
+            Some(host) => {
+                let (client, connection) = async {
+                    let (stream, tls_connect) = host.connect(&tls).await?;
+                    Ok::<_, Error>(pg_config.connect_raw(stream, tls_connect).await?)
+                }
+                .instrument(span.clone())
+                .await?;
+                let version = server_version(&connection)?;
+                (client, version, tokio::spawn(connection))
+            }
+            None => {
+                let (client, connection) = pg_config
+                    .connect(tls.clone())
+                    .instrument(span.clone())
+                    .await?;
+                let version = server_version(&connection)?;
+                (client, version, tokio::spawn(connection))
+            }
+        };
+        span.in_scope(|| check_server_version(&version))?;
         let version = format!("{version} ReadySet");
-        let _connection_handle = tokio::spawn(connection);
         span.in_scope(|| info!("Established connection to upstream"));
 
         Ok(Self {
             client,
             _connection_handle,
+            tls,
+            upstream_host,
             prepared_statements: Default::default(),
             statement_id_counter: 0,
             user,
             upstream_config,
+            proxy_options,
+            copy_in: None,
             version,
         })
     }
@@ -187,14 +503,42 @@ impl UpstreamDatabase for PostgreSqlUpstream {
         self.upstream_config.upstream_db_url.as_deref().unwrap()
     }
 
+    fn query_canceller(&self) -> Option<Arc<dyn UpstreamQueryCanceller>> {
+        Some(Arc::new(PostgreSqlQueryCanceller {
+            cancel_token: self.client.cancel_token(),
+            tls: self.tls.clone(),
+            upstream_host: self.upstream_host.clone(),
+        }))
+    }
+
     async fn reset(&mut self) -> Result<(), Error> {
         let old_self = std::mem::replace(
             self,
-            Self::connect(self.upstream_config.clone(), None).await?,
+            Self::connect(
+                self.upstream_config.clone(),
+                self.proxy_options.clone(),
+                None,
+            )
+            .await?,
         );
         drop(old_self);
         Ok(())
     }
+
+    async fn reset_session(&mut self) -> Result<(), Error> {
+        self.prepared_statements.clear();
+        self.copy_in = None;
+        self.client.batch_execute("DISCARD ALL").await?;
+        Ok(())
+    }
+
+    async fn reconnect_to(&mut self, url: &str) -> Result<(), Error> {
+        let mut upstream_config = self.upstream_config.clone();
+        upstream_config.upstream_db_url = Some(url.to_owned().into());
+        *self = Self::connect(upstream_config, self.proxy_options.clone(), None).await?;
+        Ok(())
+    }
+
     // Returns the upstream server's version, with ReadySet's info appended, to indicate to clients
     // that they're going via ReadySet
     fn version(&self) -> String {
@@ -233,8 +577,14 @@ impl UpstreamDatabase for PostgreSqlUpstream {
     where
         S: AsRef<str> + Send + Sync + 'a,
     {
-        let res = self.client.simple_query(query.as_ref()).await?;
-        Ok(QueryResult::SimpleQuery(res))
+        // Stream the results to the client as they arrive, checking the row limit as we go. Each
+        // statement in the query has its own result set, ended by its CommandComplete.
+        let messages = self.client.simple_query_raw(query.as_ref()).await?;
+        Ok(QueryResult::SimpleQuery(SimpleQueryStream(limit_rows(
+            messages,
+            ResultRowLimit::new(&self.proxy_options),
+            |message| matches!(message, SimpleQueryMessage::Row(_)),
+        ))))
     }
 
     async fn handle_ryw_write<'a, S>(
@@ -256,31 +606,46 @@ impl UpstreamDatabase for PostgreSqlUpstream {
             .prepared_statements
             .get(&statement_id)
             .ok_or(ReadySetError::PreparedStatementMissing { statement_id })?;
+        let schema = statement
+            .columns()
+            .iter()
+            .map(|col| Column {
+                name: col.name().to_owned(),
+                col_type: col.type_().clone(),
+            })
+            .collect();
 
-        let results: Vec<GenericResult> = self
-            .client
-            .generic_query_raw(statement, params)
-            .await?
-            .try_collect()
-            .await?;
-
-        let mut results = results.into_iter().peekable();
+        let mut results = Box::pin(self.client.generic_query_raw(statement, params).await?);
+        let row_limit = ResultRowLimit::new(&self.proxy_options);
 
         // If results starts with a command complete then return a write result.
         // This could happen if a write returns no results, which is fine
         //
-        // Otherwise return all the rows we get and ignore the command complete at the end
-        if let Some(GenericResult::NumRows(n)) = results.peek() {
-            Ok(QueryResult::Write {
-                num_rows_affected: *n,
-            })
-        } else {
-            let mut data = Vec::new();
-            while let Some(GenericResult::Row(r)) = results.next() {
-                data.push(r);
+        // Otherwise stream the rows to the client as we get them, and ignore the command complete
+        // at the end
+        let first = match results.try_next().await? {
+            Some(GenericResult::NumRows(n)) => {
+                return Ok(QueryResult::Write {
+                    num_rows_affected: n,
+                })
             }
-            Ok(QueryResult::Read { data })
-        }
+            first => first,
+        };
+        let rows = stream::iter(first.map(Ok))
+            .chain(results)
+            .map_err(Error::from)
+            .try_take_while(|result| future::ready(Ok(matches!(result, GenericResult::Row(_)))))
+            .try_filter_map(|result| {
+                future::ready(Ok(match result {
+                    GenericResult::Row(r) => Some(r),
+                    _ => None,
+                }))
+            });
+        let data = limit_rows(rows, row_limit, |_| true);
+        Ok(QueryResult::Read {
+            schema,
+            data: RowStream(data),
+        })
     }
 
     /// Handle starting a transaction with the upstream database.
@@ -460,4 +825,43 @@ mod tests {
 
         assert!(s.compare(&schema_spec, &param_specs).is_err());
     }
+
+    #[test]
+    fn server_versions() {
+        check_server_version("14.5 (Debian 14.5-1.pgdg110+1)").unwrap();
+        // Newer and pre-release servers are allowed, with a warning
+        check_server_version("17.2").unwrap();
+        check_server_version("18beta1").unwrap();
+        check_server_version("16").unwrap();
+
+        check_server_version("12.9").unwrap_err();
+        check_server_version("devel").unwrap_err();
+    }
+
+    #[test]
+    fn upstream_host_buffer_sizes() {
+        let upstream_config = UpstreamConfig::default();
+        let proxy_options = ProxyOptions {
+            tcp_buffer_sizes: TcpBufferSizes {
+                send: Some(1 << 20),
+                recv: None,
+            },
+            ..Default::default()
+        };
+
+        let single: pgsql::Config = "postgresql://postgres@db:5433/noria".parse().unwrap();
+        let host = UpstreamHost::from_config(&single, &upstream_config, &proxy_options)
+            .unwrap()
+            .unwrap();
+        assert_eq!((host.host.as_str(), host.port), ("db", 5433));
+        assert_eq!(host.buffer_sizes, proxy_options.tcp_buffer_sizes);
+
+        let multiple: pgsql::Config = "postgresql://postgres@db1,db2/noria".parse().unwrap();
+        UpstreamHost::from_config(&multiple, &upstream_config, &proxy_options).unwrap_err();
+        assert!(
+            UpstreamHost::from_config(&multiple, &upstream_config, &ProxyOptions::default())
+                .unwrap()
+                .is_none()
+        );
+    }
 }
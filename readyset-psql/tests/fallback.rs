@@ -1,6 +1,13 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
 use chrono::NaiveDate;
+use futures::SinkExt;
 use readyset_adapter::backend::{MigrationMode, UnsupportedSetMode};
-use readyset_adapter::BackendBuilder;
+use readyset_adapter::cancel::CancelRegistry;
+use readyset_adapter::upstream_database::MaxResultRowsBehavior;
+use readyset_adapter::{BackendBuilder, ProxyOptions};
 use readyset_client_test_helpers::psql_helpers::{upstream_config, PostgreSQLAdapter};
 use readyset_client_test_helpers::{sleep, Adapter, TestBuilder};
 use readyset_server::Handle;
@@ -9,7 +16,8 @@ use serial_test::serial;
 mod common;
 use common::connect;
 use postgres_types::{FromSql, ToSql};
-use tokio_postgres::{Client, CommandCompleteContents, SimpleQueryMessage};
+use tokio_postgres::error::SqlState;
+use tokio_postgres::{Client, CommandCompleteContents, NoTls, SimpleQueryMessage};
 
 async fn setup() -> (tokio_postgres::Config, Handle) {
     TestBuilder::default()
@@ -298,6 +306,46 @@ async fn schema_resolution_with_unreplicated_tables() {
     assert!(last_statement_matches("readyset", "ok", &client).await)
 }
 
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn routing_directives() {
+    let (config, _handle) = TestBuilder::default()
+        .fallback(true)
+        .migration_mode(MigrationMode::OutOfBand)
+        .build::<PostgreSQLAdapter>()
+        .await;
+    let client = connect(config).await;
+
+    client.simple_query("CREATE TABLE t (x int)").await.unwrap();
+    client
+        .simple_query("INSERT INTO t (x) VALUES (1)")
+        .await
+        .unwrap();
+    sleep().await;
+
+    // Without a directive, an unmigrated query goes upstream
+    client.simple_query("SELECT x FROM t").await.unwrap();
+    assert!(last_statement_matches("upstream", "ok", &client).await);
+
+    // `cache` forces a migration, and executes the query against ReadySet
+    let res = client
+        .query_one("/* readyset: cache */ SELECT x FROM t", &[])
+        .await
+        .unwrap()
+        .get::<_, i32>(0);
+    assert_eq!(res, 1);
+    assert!(last_statement_matches("readyset", "ok", &client).await);
+
+    // `proxy` forces the query upstream, even though it's now cached
+    let res = client
+        .query_one("SELECT x FROM t /* readyset: proxy */", &[])
+        .await
+        .unwrap()
+        .get::<_, i32>(0);
+    assert_eq!(res, 1);
+    assert!(last_statement_matches("upstream", "ok", &client).await);
+}
+
 #[tokio::test(flavor = "multi_thread")]
 #[serial]
 async fn generated_columns() {
@@ -769,3 +817,149 @@ async fn replication_failure_retries_if_failed_to_drop(failpoint: &str) {
         .collect();
     assert_eq!(result, expected);
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn cancel_query_from_another_connection() {
+    let (config, _handle) = TestBuilder::new(
+        BackendBuilder::new()
+            .require_authentication(false)
+            .cancel_registry(Arc::new(CancelRegistry::new())),
+    )
+    .fallback(true)
+    .build::<PostgreSQLAdapter>()
+    .await;
+    let client = connect(config).await;
+    let cancel_token = client.cancel_token();
+
+    let start = Instant::now();
+    let slow_query = tokio::spawn(async move {
+        let err = client
+            .simple_query("SELECT pg_sleep(30)")
+            .await
+            .unwrap_err();
+        (client, err)
+    });
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    cancel_token.cancel_query(NoTls).await.unwrap();
+
+    let (client, err) = slow_query.await.unwrap();
+    assert_eq!(err.code(), Some(&SqlState::QUERY_CANCELED));
+    assert!(start.elapsed() < Duration::from_secs(30));
+
+    // The connection is still usable after its query was cancelled
+    let res = client.query_one("SELECT 1::integer", &[]).await.unwrap();
+    assert_eq!(res.get::<_, i32>(0), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn drain_idle_connections_for_user() {
+    let cancel_registry = Arc::new(CancelRegistry::new());
+    let (config, _handle) = TestBuilder::new(
+        BackendBuilder::new()
+            .require_authentication(false)
+            .cancel_registry(cancel_registry.clone()),
+    )
+    .fallback(true)
+    .build::<PostgreSQLAdapter>()
+    .await;
+    let alice = connect(config.clone().user("alice").to_owned()).await;
+    let bob = connect(config.clone().user("bob").to_owned()).await;
+    alice.simple_query("SELECT 1").await.unwrap();
+    bob.simple_query("SELECT 1").await.unwrap();
+
+    // Alice's connection is idle, but is still closed without waiting for her next query
+    assert_eq!(cancel_registry.drain_user("alice"), 1);
+    let start = Instant::now();
+    while !alice.is_closed() {
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "Idle connection was not closed"
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    bob.simple_query("SELECT 1").await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn copy_from_stdin() {
+    let (config, _handle) = setup().await;
+    let client = connect(config).await;
+
+    client
+        .simple_query("DROP TABLE IF EXISTS copy_t CASCADE")
+        .await
+        .unwrap();
+    client
+        .simple_query("CREATE TABLE copy_t (id int PRIMARY KEY, name text)")
+        .await
+        .unwrap();
+    sleep().await;
+
+    let sink = client.copy_in("COPY copy_t FROM STDIN").await.unwrap();
+    futures::pin_mut!(sink);
+    sink.send(Bytes::from_static(b"1\tone\n2\ttwo\n"))
+        .await
+        .unwrap();
+    let n_rows = sink.as_mut().finish().await.unwrap();
+    assert_eq!(n_rows, 2);
+
+    let mut upstream_config = upstream_config();
+    upstream_config.dbname("noria");
+    let upstream = connect(upstream_config).await;
+    let rows = upstream
+        .query("SELECT id, name FROM copy_t ORDER BY id", &[])
+        .await
+        .unwrap()
+        .iter()
+        .map(|row| (row.get::<_, i32>(0), row.get::<_, String>(1)))
+        .collect::<Vec<_>>();
+    assert_eq!(rows, vec![(1, "one".to_owned()), (2, "two".to_owned())]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn max_result_rows() {
+    let setup = |max_result_rows_behavior| {
+        TestBuilder::default()
+            .fallback(true)
+            .proxy_options(ProxyOptions {
+                max_result_rows: Some(2),
+                max_result_rows_behavior,
+                ..Default::default()
+            })
+            .build::<PostgreSQLAdapter>()
+    };
+    // generate_series isn't supported by ReadySet, so these queries are proxied to the upstream
+    let query = "SELECT generate_series(1, 3)";
+
+    let (config, _handle) = setup(MaxResultRowsBehavior::Error).await;
+    let client = connect(config).await;
+    let err = client.query(query, &[]).await.unwrap_err();
+    assert!(
+        err.to_string().contains("exceeded the maximum of 2 rows"),
+        "{err}"
+    );
+    client.simple_query(query).await.unwrap_err();
+
+    let (config, _handle) = setup(MaxResultRowsBehavior::Truncate).await;
+    let client = connect(config).await;
+    assert_eq!(client.query(query, &[]).await.unwrap().len(), 2);
+    let messages = client.simple_query(query).await.unwrap();
+    let rows = messages
+        .iter()
+        .filter(|m| matches!(m, SimpleQueryMessage::Row(_)))
+        .count();
+    assert_eq!(rows, 2);
+    // The command tag reports the rows that were sent, not the rows the upstream returned
+    assert!(matches!(
+        messages.last(),
+        Some(SimpleQueryMessage::CommandComplete(
+            CommandCompleteContents { rows: 2, .. }
+        ))
+    ));
+}
@@ -1356,6 +1356,41 @@ async fn schema_search_path() {
     conn.simple_query("SELECT c FROM t2").await.unwrap();
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn show_readyset_search_path() {
+    let (opts, _handle) = setup().await;
+    let conn = connect(opts).await;
+
+    let search_path = |rows: Vec<SimpleQueryMessage>| {
+        rows.into_iter()
+            .find_map(|msg| match msg {
+                SimpleQueryMessage::Row(r) => Some(r.get(1).unwrap().to_owned()),
+                _ => None,
+            })
+            .unwrap()
+    };
+
+    conn.simple_query("SET search_path = s1, s2").await.unwrap();
+    assert_eq!(
+        search_path(
+            conn.simple_query("SHOW READYSET SEARCH_PATH")
+                .await
+                .unwrap()
+        ),
+        "s1, s2"
+    );
+
+    conn.simple_query("SET search_path = s2").await.unwrap();
+    assert_eq!(
+        search_path(
+            conn.simple_query("SHOW READYSET SEARCH_PATH")
+                .await
+                .unwrap()
+        ),
+        "s2"
+    );
+}
+
 /// Tests that two queries that are syntactically equivalent, but semantically different due to
 /// different search paths, are executed as separate queries
 #[tokio::test(flavor = "multi_thread")]
@@ -307,6 +307,12 @@ impl Leader {
                     check_quorum!(ds);
                     return_serialized!(ds.verbose_views())
                 }
+                (&Method::POST, "/graphviz_for_query") => {
+                    let name = bincode::deserialize(&body)?;
+                    let ds = futures::executor::block_on(self.dataflow_state_handle.read());
+                    check_quorum!(ds);
+                    return_serialized!(ds.graphviz_for_query(&name)?)
+                }
                 (&Method::POST, "/view_statuses") => {
                     let (queries, dialect) = bincode::deserialize(&body)?;
                     let ds = futures::executor::block_on(self.dataflow_state_handle.read());
@@ -650,7 +650,8 @@ fn classify_conditionals(
                     | Expr::Between { .. }
                     | Expr::Cast { .. }
                     | Expr::In { .. }
-                    | Expr::Variable(_) => {
+                    | Expr::Variable(_)
+                    | Expr::WindowFunction { .. } => {
                         unsupported!(
                             "Unsupported right-hand side of condition expression: {}",
                             rhs
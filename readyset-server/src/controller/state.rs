@@ -33,7 +33,7 @@ use metrics::{gauge, histogram};
 use nom_sql::{
     CacheInner, CreateCacheStatement, Relation, SelectStatement, SqlIdentifier, SqlQuery,
 };
-use petgraph::visit::Bfs;
+use petgraph::visit::{Bfs, Reversed};
 use readyset_client::builders::{
     ReaderHandleBuilder, ReusedReaderHandleBuilder, TableBuilder, ViewBuilder,
 };
@@ -711,6 +711,43 @@ impl DfState {
         )
     }
 
+    /// Build a simplified graphviz representation of the dataflow plan for the cached query with
+    /// the given name: the node the query's reader is attached to, all of its ancestors, and its
+    /// readers.
+    ///
+    /// Nodes are described by their operator rather than their full definition, so no literal
+    /// values from the query are included.
+    pub(super) fn graphviz_for_query(&self, name: &Relation) -> ReadySetResult<String> {
+        let node = self
+            .recipe
+            .node_addr_for(name)
+            .ok()
+            .or_else(|| self.views().get(name).copied())
+            .ok_or_else(|| ReadySetError::ViewNotFound(name.to_string()))?;
+
+        let mut nodes = HashSet::new();
+        let mut ancestors = Bfs::new(Reversed(&self.ingredients), node);
+        while let Some(ancestor) = ancestors.next(Reversed(&self.ingredients)) {
+            nodes.insert(ancestor);
+        }
+        let mut descendants = Bfs::new(&self.ingredients, node);
+        while let Some(descendant) = descendants.next(&self.ingredients) {
+            #[allow(clippy::indexing_slicing)] // just came from self.ingredients
+            if self.ingredients[descendant].is_reader_for(node) {
+                nodes.insert(descendant);
+            }
+        }
+
+        Ok(graphviz_nodes(
+            &self.ingredients,
+            false,
+            None,
+            &self.materializations,
+            Some(&self.domain_nodes),
+            Some(&nodes),
+        ))
+    }
+
     /// List data-flow nodes, on a specific worker if `worker` specified.
     pub(super) fn nodes_on_worker(
         &self,
@@ -1665,6 +1702,29 @@ pub(super) fn graphviz(
     materializations: &Materializations,
     domain_nodes: Option<&HashMap<DomainIndex, NodeMap<NodeIndex>>>,
 ) -> String {
+    graphviz_nodes(
+        graph,
+        detailed,
+        node_sizes,
+        materializations,
+        domain_nodes,
+        None,
+    )
+}
+
+/// Build a graphviz [dot][] representation of the subgraph of `graph` consisting of only the nodes
+/// in `nodes` (or the whole graph, if `nodes` is `None`), and the edges between them.
+///
+/// [dot]: https://graphviz.org/doc/info/lang.html
+fn graphviz_nodes(
+    graph: &Graph,
+    detailed: bool,
+    node_sizes: Option<HashMap<NodeIndex, NodeSize>>,
+    materializations: &Materializations,
+    domain_nodes: Option<&HashMap<DomainIndex, NodeMap<NodeIndex>>>,
+    nodes: Option<&HashSet<NodeIndex>>,
+) -> String {
+    let included = |index: NodeIndex| nodes.map_or(true, |nodes| nodes.contains(&index));
     let mut s = String::new();
     let indentln = |s: &mut String| s.push_str("    ");
     let node_sizes = node_sizes.unwrap_or_default();
@@ -1698,7 +1758,7 @@ pub(super) fn graphviz(
         .flat_map(|(di, nodes)| nodes.iter().map(|(_, ni)| (*ni, *di)))
         .collect::<HashMap<_, _>>();
     let mut domains_to_nodes = HashMap::new();
-    for index in graph.node_indices() {
+    for index in graph.node_indices().filter(|&index| included(index)) {
         let domain = domain_for_node.get(&index).copied();
         domains_to_nodes
             .entry(domain)
@@ -1735,6 +1795,9 @@ pub(super) fn graphviz(
 
     // edges.
     for (_, edge) in graph.raw_edges().iter().enumerate() {
+        if !included(edge.source()) || !included(edge.target()) {
+            continue;
+        }
         indentln(&mut s);
         s.push_str(&format!(
             "n{} -> n{} [ {} ]",
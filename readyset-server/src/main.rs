@@ -3,6 +3,7 @@ use std::process;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use anyhow::Context;
 use clap::Parser;
 use futures_util::future::{self, Either};
 use metrics_exporter_prometheus::PrometheusBuilder;
@@ -75,8 +76,13 @@ struct Options {
     #[clap(long)]
     use_aws_external_address: bool,
 
-    /// ReadySet deployment ID.
-    #[clap(long, env = "DEPLOYMENT", forbid_empty_values = true)]
+    /// ReadySet deployment ID. May only contain ASCII letters, digits, `-`, and `_`.
+    #[clap(
+        long,
+        env = "DEPLOYMENT",
+        forbid_empty_values = true,
+        parse(try_from_str = readyset_util::deployment::parse_deployment_id)
+    )]
     deployment: String,
 
     /// Authority connection string.
@@ -126,7 +132,11 @@ struct Options {
 }
 
 fn main() -> anyhow::Result<()> {
-    let opts: Options = Options::parse();
+    let mut opts: Options = Options::parse();
+    opts.worker_options
+        .replicator_config
+        .load_upstream_db_url_file()
+        .context("Failed to read --upstream-db-url-file")?;
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .thread_name("worker")
@@ -142,12 +152,15 @@ fn main() -> anyhow::Result<()> {
 
     info!(version = %VERSION_STR_ONELINE);
 
-    let telemetry_sender = rt.block_on(TelemetryInitializer::init(
-        opts.disable_telemetry,
-        std::env::var("RS_API_KEY").ok(),
-        vec![],
-        opts.deployment.clone(),
-    ));
+    let telemetry_sender = rt.block_on(
+        TelemetryInitializer::builder()
+            .disable(opts.disable_telemetry)
+            .api_key(std::env::var("RS_API_KEY").ok())
+            .secondary_write_key(std::env::var("RS_SECONDARY_SEGMENT_WRITE_KEY").ok())
+            .deployment_id(opts.deployment.clone())
+            .hmac_secret(std::env::var("RS_TELEMETRY_HMAC_SECRET").ok())
+            .build(),
+    );
 
     let external_addr = if opts.use_aws_external_address {
         Either::Left(get_aws_private_ip())
@@ -266,7 +279,11 @@ fn main() -> anyhow::Result<()> {
         let shutdown_timeout = std::time::Duration::from_secs(5);
 
         match telemetry_sender.graceful_shutdown(shutdown_timeout).await {
-            Ok(_) => info!("TelemetrySender shutdown gracefully"),
+            Ok(summary) => info!(
+                delivered = %summary.delivered,
+                dropped = %summary.dropped,
+                "TelemetrySender shutdown gracefully"
+            ),
             Err(e) => info!(error=%e, "TelemetrySender did not shut down gracefully"),
         }
     });
@@ -27,10 +27,12 @@ pub struct RecorderInstalledTwice;
 /// Installs a new global recorder
 pub fn install_global_recorder(rec: GlobalRecorder) -> Result<(), RecorderInstalledTwice> {
     let rec = Box::leak(Box::new(rec));
+    // A recorder may have been installed by something other than this function, so check that
+    // first to avoid leaving a recorder in `METRICS_RECORDER` that isn't actually installed
+    metrics::set_recorder(rec).map_err(|_| RecorderInstalledTwice)?;
     METRICS_RECORDER
         .set(rec)
         .map_err(|_| RecorderInstalledTwice)?;
-    metrics::set_recorder(rec).expect("Would fail on OnceCell");
     Ok(())
 }
 
@@ -0,0 +1,82 @@
+//! Fingerprint
+//!
+//! Provides a stable fingerprint of the *shape* of a query, so that features which group queries
+//! together (deduplication, reporting, per-query circuit breaking, caching) all agree on which
+//! queries are the same.
+
+use std::hash::{Hash, Hasher};
+
+use nom_sql::{Dialect, SqlQuery};
+use siphasher::sip::SipHasher13;
+
+use crate::strip_literals::StripLiterals;
+
+/// Computes a fingerprint of the shape of `query` - its structure, with all literals (including
+/// placeholders) normalized out.
+///
+/// Two queries that differ only in the values of their literals, or in the style of their
+/// placeholders, have the same fingerprint. The fingerprint is computed with a fixed hash
+/// algorithm and keys (unlike [`DefaultHasher`](std::collections::hash_map::DefaultHasher), whose
+/// algorithm may change between Rust releases), so it's stable across processes and can be
+/// compared between adapters running the same version of ReadySet. Changes to the representation
+/// of parsed queries may change fingerprints between versions.
+pub fn query_shape_fingerprint(query: &SqlQuery, dialect: Dialect) -> u64 {
+    let mut shape = query.clone();
+    shape.strip_literals();
+    let mut hasher = SipHasher13::new_with_keys(0, 0);
+    (dialect, shape).hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use nom_sql::parse_query;
+
+    use super::*;
+
+    fn fingerprint(dialect: Dialect, query: &str) -> u64 {
+        query_shape_fingerprint(&parse_query(dialect, query).unwrap(), dialect)
+    }
+
+    #[test]
+    fn literal_values_are_ignored() {
+        assert_eq!(
+            fingerprint(
+                Dialect::MySQL,
+                "SELECT a FROM t WHERE b = 1 AND c = 'x' LIMIT 10"
+            ),
+            fingerprint(
+                Dialect::MySQL,
+                "SELECT a FROM t WHERE b = 2 AND c = 'y' LIMIT 5"
+            ),
+        );
+        assert_eq!(
+            fingerprint(Dialect::PostgreSQL, "SELECT a FROM t WHERE b = $1"),
+            fingerprint(Dialect::PostgreSQL, "SELECT a FROM t WHERE b = 42"),
+        );
+        assert_eq!(
+            fingerprint(Dialect::MySQL, "INSERT INTO t (a, b) VALUES (1, 'one')"),
+            fingerprint(Dialect::MySQL, "INSERT INTO t (a, b) VALUES (?, ?)"),
+        );
+    }
+
+    #[test]
+    fn different_shapes_differ() {
+        let base = fingerprint(Dialect::MySQL, "SELECT a FROM t WHERE b = 1");
+        for other in [
+            "SELECT a, b FROM t WHERE b = 1",
+            "SELECT a FROM t2 WHERE b = 1",
+            "SELECT a FROM t WHERE c = 1",
+            "SELECT a FROM t WHERE b > 1",
+            "SELECT a FROM t WHERE b = 1 AND c = 2",
+            "DELETE FROM t WHERE b = 1",
+        ] {
+            assert_ne!(base, fingerprint(Dialect::MySQL, other), "{other}");
+        }
+
+        assert_ne!(
+            base,
+            fingerprint(Dialect::PostgreSQL, "SELECT a FROM t WHERE b = 1")
+        );
+    }
+}
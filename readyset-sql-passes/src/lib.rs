@@ -12,6 +12,7 @@ mod count_star_rewrite;
 mod create_table_columns;
 mod detect_problematic_self_joins;
 pub mod expr;
+pub mod fingerprint;
 mod implied_tables;
 mod key_def_coalescing;
 mod normalize_topk_with_aggregate;
@@ -2,7 +2,7 @@ use std::borrow::Borrow;
 use std::mem;
 
 use nom_sql::analysis::visit_mut::VisitorMut;
-use nom_sql::{ItemPlaceholder, Literal, SelectStatement};
+use nom_sql::{ItemPlaceholder, Literal, SelectStatement, SqlQuery};
 use serde::{Deserialize, Serialize};
 
 /// Visitor used to remove and return all literals in the order that that are visited. Removed
@@ -61,6 +61,14 @@ impl StripLiterals for SelectStatement {
     }
 }
 
+impl StripLiterals for SqlQuery {
+    fn strip_literals(&mut self) -> Vec<Literal> {
+        let mut visitor = StripLiteralsVisitor::new();
+        let Ok(()) = visitor.visit_sql_query(self);
+        visitor.literals
+    }
+}
+
 impl Borrow<SelectStatement> for SelectStatementSkeleton {
     fn borrow(&self) -> &SelectStatement {
         &self.0
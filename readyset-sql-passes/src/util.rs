@@ -106,7 +106,11 @@ pub fn map_aggregates(expr: &mut Expr) -> Vec<(FunctionExpr, SqlIdentifier)> {
                 ret.append(&mut map_aggregates(else_expr));
             }
         }
-        Expr::Call(_) | Expr::Literal(_) | Expr::Column(_) | Expr::Variable(_) => {}
+        Expr::Call(_)
+        | Expr::Literal(_)
+        | Expr::Column(_)
+        | Expr::Variable(_)
+        | Expr::WindowFunction { .. } => {}
         Expr::BinaryOp { lhs, rhs, .. }
         | Expr::OpAny { lhs, rhs, .. }
         | Expr::OpSome { lhs, rhs, .. }
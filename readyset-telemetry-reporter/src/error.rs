@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use reqwest::header::InvalidHeaderValue;
 use reqwest::StatusCode;
 use thiserror::Error;
@@ -21,6 +23,17 @@ pub enum ReporterError {
     #[error("Error sending telemetry payload (status {status}): {body}")]
     HTTPError { status: StatusCode, body: String },
 
+    #[error(
+        "Telemetry endpoint asked for the payload to be sent again later (status {status}): {body}"
+    )]
+    RetryLater {
+        status: StatusCode,
+        /// How long the endpoint asked us to wait before sending again, from its `Retry-After`
+        /// header
+        retry_after: Option<Duration>,
+        body: String,
+    },
+
     #[error("Request timed out")]
     Timeout(#[from] Elapsed),
 
@@ -29,18 +42,135 @@ pub enum ReporterError {
 
     #[error("Error making client: {0}")]
     Client(String),
+
+    #[error("Invalid telemetry endpoint: {0}")]
+    InvalidEndpoint(String),
+
+    #[error("Telemetry reporter is not running")]
+    NotRunning,
+}
+
+impl ReporterError {
+    /// Returns the error for an unsuccessful HTTP response from a telemetry endpoint with the
+    /// given `status`, `Retry-After` header, and `body`
+    pub fn from_status(status: StatusCode, retry_after: Option<Duration>, body: String) -> Self {
+        match status {
+            status if status.is_server_error() => Self::Server(body),
+            StatusCode::UNAUTHORIZED => Self::Unauthorized,
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::REQUEST_TIMEOUT => Self::RetryLater {
+                status,
+                retry_after,
+                body,
+            },
+            status => Self::HTTPError { status, body },
+        }
+    }
+
+    /// Returns true if a send which failed with this error might succeed if retried.
+    ///
+    /// Network errors, timeouts, 5XX responses, and 429 (Too Many Requests) and 408 (Request
+    /// Timeout) responses are retriable, but other 4XX responses (such as an invalid API key) and
+    /// errors building or serializing the request will fail the same way every time.
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            Self::Server(_) | Self::Timeout(_) | Self::RetryLater { .. } => true,
+            Self::HTTPError { status, .. } => is_retriable_status(*status),
+            Self::Reqwest(error) => {
+                !error.is_builder() && error.status().map_or(true, is_retriable_status)
+            }
+            Self::InvalidAPIKeyHeader(_)
+            | Self::Unauthorized
+            | Self::Client(_)
+            | Self::Json(_)
+            | Self::InvalidEndpoint(_)
+            | Self::NotRunning => false,
+        }
+    }
+
+    /// Returns how long the telemetry endpoint asked us to wait before sending again, if it did
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RetryLater { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+fn is_retriable_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || matches!(
+            status,
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::REQUEST_TIMEOUT
+        )
 }
 
 /// Result type alias for the telemetry reporter
 pub type ReporterResult<T> = std::result::Result<T, ReporterError>;
 
-/// Errors that can occur when sending telemetry payloads from the sender to the reporter
+/// Errors that can occur when queueing a telemetry event to be sent by the reporter
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum TelemetrySendError {
+    /// The reporter's queue of events waiting to be sent is full, so the event was dropped
+    #[error("Telemetry event queue is full")]
+    QueueFull,
 
-#[derive(Debug, Error)]
-pub enum SenderError {
-    #[error("Error sending telemetry event {0}")]
-    Sender(String),
+    /// The reporter has shut down, so it can't send any more events
+    #[error("Telemetry reporter has shut down")]
+    ReporterShutDown,
+
+    /// The sender isn't connected to a reporter
+    #[error("Telemetry reporting is disabled")]
+    Disabled,
 }
 
-/// Result type alias for the telemetry reporter
-pub type SenderResult<T> = std::result::Result<T, SenderError>;
+impl TelemetrySendError {
+    /// Returns true if this error is expected in the normal course of running with telemetry
+    /// disabled or shutting down, and so isn't worth warning about
+    pub fn is_benign(&self) -> bool {
+        matches!(self, Self::ReporterShutDown | Self::Disabled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retriable_statuses() {
+        for status in [
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::REQUEST_TIMEOUT,
+            StatusCode::SERVICE_UNAVAILABLE,
+        ] {
+            assert!(
+                ReporterError::from_status(status, None, String::new()).is_retriable(),
+                "{status}"
+            );
+        }
+        for status in [
+            StatusCode::BAD_REQUEST,
+            StatusCode::UNAUTHORIZED,
+            StatusCode::NOT_FOUND,
+        ] {
+            assert!(
+                !ReporterError::from_status(status, None, String::new()).is_retriable(),
+                "{status}"
+            );
+        }
+    }
+
+    #[test]
+    fn retry_after() {
+        let retry_after = Some(Duration::from_secs(3));
+        assert_eq!(
+            ReporterError::from_status(StatusCode::TOO_MANY_REQUESTS, retry_after, String::new())
+                .retry_after(),
+            retry_after
+        );
+        assert_eq!(
+            ReporterError::from_status(StatusCode::REQUEST_TIMEOUT, None, String::new())
+                .retry_after(),
+            None
+        );
+    }
+}
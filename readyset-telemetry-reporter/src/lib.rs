@@ -7,40 +7,148 @@
 mod error;
 pub use error::*;
 
+mod rate_limit;
+pub use rate_limit::*;
+
 mod reporter;
 pub use reporter::*;
 
+mod retry;
+pub use retry::*;
+
+mod segment;
+pub use segment::*;
+
 mod sender;
 pub use sender::*;
 
 mod telemetry;
 pub use telemetry::*;
+
+mod transport;
+use reqwest::Url;
 use tokio::sync::mpsc::channel;
 use tokio::sync::oneshot;
+pub use transport::*;
 
-pub const TELMETRY_CHANNEL_LEN: usize = 1024;
+/// The default number of events which can be queued to be sent before further events are dropped
+pub const TELEMETRY_CHANNEL_LEN: usize = 1024;
+
+#[deprecated(note = "renamed to TELEMETRY_CHANNEL_LEN")]
+pub const TELMETRY_CHANNEL_LEN: usize = TELEMETRY_CHANNEL_LEN;
 
 pub struct TelemetryInitializer {}
 
 impl TelemetryInitializer {
     /// Initializes a background task and returns a TelemetrySender handle
+    ///
+    /// If `secondary_write_key` is provided, requests to ReadySet's Segment source which are
+    /// rejected as unauthorized with the primary Segment write key are retried with it, which
+    /// allows rotating the write key without a restart: the new key should be configured as the
+    /// primary key, and the old one passed as `secondary_write_key`.
+    ///
+    /// If `hmac_secret` is provided, the body of each telemetry request is signed with an
+    /// HMAC-SHA256 using that secret, and the hex-encoded signature is sent in the `X-Signature`
+    /// header.
+    ///
+    /// If `endpoint` is provided, events are sent to it in place of ReadySet's Segment source, for
+    /// example to route telemetry through a self-hosted relay. It should be validated with
+    /// [`parse_endpoint`].
+    ///
+    /// Every event is sent to each of `transports`, in addition to ReadySet's Segment source. Sends
+    /// which fail are retried according to `retry_policy`. Events are sent to Segment in batches,
+    /// as configured by `batch_config`, and are rate limited per event type according to
+    /// [`default_rate_limits`].
+    ///
+    /// Up to `channel_len` events can be queued to be sent before further events are dropped, which
+    /// should be raised above [`TELEMETRY_CHANNEL_LEN`] if bursts of events are expected.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel_len` is 0
+    ///
+    /// New code should prefer [`TelemetryInitializer::builder`], which doesn't require every
+    /// option to be passed positionally.
+    #[allow(clippy::too_many_arguments)]
     pub async fn init(
         disable_telemetry: bool,
         api_key: Option<String>,
+        secondary_write_key: Option<String>,
         periodic_reporters: Vec<PeriodicReporter>,
         deployment_id: String,
+        hmac_secret: Option<String>,
+        endpoint: Option<Url>,
+        transports: Vec<Transport>,
+        retry_policy: RetryPolicy,
+        batch_config: BatchConfig,
+        channel_len: usize,
+    ) -> TelemetrySender {
+        Self::builder()
+            .disable(disable_telemetry)
+            .api_key(api_key)
+            .secondary_write_key(secondary_write_key)
+            .periodic_reporters(periodic_reporters)
+            .deployment_id(deployment_id)
+            .hmac_secret(hmac_secret)
+            .endpoint(endpoint)
+            .transports(transports)
+            .retry_policy(retry_policy)
+            .batch_config(batch_config)
+            .channel_len(channel_len)
+            .build()
+            .await
+    }
+
+    /// Returns a builder which configures and then initializes the telemetry background task, as
+    /// an alternative to [`init`](TelemetryInitializer::init). Any options which aren't set on the
+    /// builder take their default values.
+    pub fn builder() -> TelemetryInitializerBuilder {
+        TelemetryInitializerBuilder::default()
+    }
+
+    /// Initializes a background task which sends events to the given primary `transport` rather
+    /// than to ReadySet's Segment source, and returns a TelemetrySender handle
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel_len` is 0
+    pub async fn init_with_transport<T: TelemetryTransport + 'static>(
+        disable_telemetry: bool,
+        transport: T,
+        periodic_reporters: Vec<PeriodicReporter>,
+        transports: Vec<Transport>,
+        retry_policy: RetryPolicy,
+        batch_config: BatchConfig,
+        channel_len: usize,
     ) -> TelemetrySender {
         if disable_telemetry {
             return TelemetrySender::new_no_op();
         }
-        let (tx, rx) = channel(TELMETRY_CHANNEL_LEN); // Arbitrary number of metrics to allow in queue before dropping them
+        let (tx, rx) = channel(channel_len);
         let (shutdown_tx, shutdown_rx) = oneshot::channel();
         let (shutdown_ack_tx, shutdown_ack_rx) = oneshot::channel();
-        let sender = TelemetrySender::new(tx, shutdown_tx, shutdown_ack_rx);
+        let mut telemetry_reporter = TelemetryReporter::with_transport(
+            rx,
+            transport,
+            shutdown_rx,
+            shutdown_ack_tx,
+            retry_policy,
+            batch_config,
+        );
+        for transport in transports {
+            telemetry_reporter.add_transport(transport);
+        }
+        telemetry_reporter.set_rate_limits(default_rate_limits());
+        let sender = TelemetrySender::new(
+            tx,
+            shutdown_tx,
+            shutdown_ack_rx,
+            telemetry_reporter.periodic_reporters(),
+            telemetry_reporter.flush_requests(),
+            telemetry_reporter.dropped_events(),
+        );
 
         tokio::spawn(async move {
-            let mut telemetry_reporter =
-                TelemetryReporter::new(rx, api_key, shutdown_rx, shutdown_ack_tx, deployment_id);
             for reporter in periodic_reporters {
                 telemetry_reporter
                     .register_periodic_reporter(reporter)
@@ -55,19 +163,171 @@ impl TelemetryInitializer {
     #[cfg(any(test, feature = "test-util"))]
     pub fn test_init() -> (TelemetrySender, TelemetryReporter) {
         readyset_tracing::init_test_logging();
-        let (tx, rx) = channel(TELMETRY_CHANNEL_LEN); // Arbitrary number of metrics to allow in queue before dropping them
+        let (tx, rx) = channel(TELEMETRY_CHANNEL_LEN); // Arbitrary number of metrics to allow in queue before dropping them
         let (shutdown_tx, shutdown_rx) = oneshot::channel();
         let (shutdown_ack_tx, shutdown_ack_rx) = oneshot::channel();
-        let sender = TelemetrySender::new(tx, shutdown_tx, shutdown_ack_rx);
-
         let reporter = TelemetryReporter::new(
             rx,
             Some("api-key".into()),
             shutdown_rx,
             shutdown_ack_tx,
             "deployment_id".into(),
+            None,
+            None,
+            RetryPolicy::default(),
+            BatchConfig::default(),
+        );
+        let sender = TelemetrySender::new(
+            tx,
+            shutdown_tx,
+            shutdown_ack_rx,
+            reporter.periodic_reporters(),
+            reporter.flush_requests(),
+            reporter.dropped_events(),
         );
 
         (sender, reporter)
     }
 }
+
+/// A builder for the telemetry background task, returned by [`TelemetryInitializer::builder`]. See
+/// [`TelemetryInitializer::init`] for the meaning of each option.
+pub struct TelemetryInitializerBuilder {
+    disable: bool,
+    api_key: Option<String>,
+    secondary_write_key: Option<String>,
+    periodic_reporters: Vec<PeriodicReporter>,
+    deployment_id: String,
+    hmac_secret: Option<String>,
+    endpoint: Option<Url>,
+    transports: Vec<Transport>,
+    retry_policy: RetryPolicy,
+    batch_config: BatchConfig,
+    channel_len: usize,
+}
+
+impl Default for TelemetryInitializerBuilder {
+    fn default() -> Self {
+        Self {
+            disable: false,
+            api_key: None,
+            secondary_write_key: None,
+            periodic_reporters: vec![],
+            deployment_id: String::new(),
+            hmac_secret: None,
+            endpoint: None,
+            transports: vec![],
+            retry_policy: RetryPolicy::default(),
+            batch_config: BatchConfig::default(),
+            channel_len: TELEMETRY_CHANNEL_LEN,
+        }
+    }
+}
+
+impl TelemetryInitializerBuilder {
+    /// If `true`, no telemetry is sent, and [`build`](Self::build) returns a no-op sender
+    pub fn disable(mut self, disable: bool) -> Self {
+        self.disable = disable;
+        self
+    }
+
+    pub fn api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+
+    pub fn secondary_write_key(mut self, secondary_write_key: Option<String>) -> Self {
+        self.secondary_write_key = secondary_write_key;
+        self
+    }
+
+    pub fn periodic_reporters(mut self, periodic_reporters: Vec<PeriodicReporter>) -> Self {
+        self.periodic_reporters = periodic_reporters;
+        self
+    }
+
+    pub fn deployment_id(mut self, deployment_id: String) -> Self {
+        self.deployment_id = deployment_id;
+        self
+    }
+
+    pub fn hmac_secret(mut self, hmac_secret: Option<String>) -> Self {
+        self.hmac_secret = hmac_secret;
+        self
+    }
+
+    pub fn endpoint(mut self, endpoint: Option<Url>) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    pub fn transports(mut self, transports: Vec<Transport>) -> Self {
+        self.transports = transports;
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn batch_config(mut self, batch_config: BatchConfig) -> Self {
+        self.batch_config = batch_config;
+        self
+    }
+
+    /// Defaults to [`TELEMETRY_CHANNEL_LEN`]
+    pub fn channel_len(mut self, channel_len: usize) -> Self {
+        self.channel_len = channel_len;
+        self
+    }
+
+    /// Initializes a background task and returns a TelemetrySender handle
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel length is 0
+    pub async fn build(self) -> TelemetrySender {
+        if self.disable {
+            return TelemetrySender::new_no_op();
+        }
+        TelemetryInitializer::init_with_transport(
+            false,
+            SegmentTransport::new(
+                self.api_key,
+                self.deployment_id,
+                self.hmac_secret,
+                self.endpoint,
+            )
+            .with_secondary_write_key(self.secondary_write_key),
+            self.periodic_reporters,
+            self.transports,
+            self.retry_policy,
+            self.batch_config,
+            self.channel_len,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn builder_disable() {
+        let sender = TelemetryInitializer::builder().disable(true).build().await;
+        assert!(sender.periodic_reporters().is_none());
+    }
+
+    #[tokio::test]
+    async fn builder_enabled() {
+        let sender = TelemetryInitializer::builder()
+            .deployment_id("deployment_id".into())
+            .channel_len(1)
+            .build()
+            .await;
+        assert!(sender.periodic_reporters().is_some());
+        sender.shutdown().await;
+    }
+}
@@ -0,0 +1,214 @@
+//! Per-event-type rate limiting of telemetry events.
+//!
+//! Each [`TelemetryEvent`] type may be given a [`Quota`], which limits how many events of that
+//! type the reporter sends using a token bucket. Events beyond the quota aren't sent individually;
+//! instead, the number of events suppressed is counted, and once the quota's window has passed
+//! they're summarized in a single [`TelemetryEvent::RateLimited`] event carrying that count.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use readyset_util::rate_limit::TokenBucket;
+use tokio::time::Instant;
+
+use crate::telemetry::{Telemetry, TelemetryBuilder, TelemetryEvent};
+
+/// A limit on the rate at which events of a single type are sent: at most
+/// [`max_events`](Quota::max_events) at once, replenished at a rate of `max_events` every
+/// [`per`](Quota::per)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    /// The maximum number of events which may be sent in a burst
+    pub max_events: u32,
+    /// The time it takes for the full quota of events to be replenished, and the window over which
+    /// events beyond the quota are summarized
+    pub per: Duration,
+}
+
+/// The [`Quota`] for each rate-limited event type. Event types without a quota, or whose quota has
+/// a zero-length window, aren't limited.
+pub type RateLimits = HashMap<TelemetryEvent, Quota>;
+
+/// The rate limits applied by [`TelemetryInitializer`](crate::TelemetryInitializer) by default,
+/// which keep bursts of proxied queries (for example during schema churn) from overwhelming the
+/// reporter
+pub fn default_rate_limits() -> RateLimits {
+    HashMap::from([(
+        TelemetryEvent::ProxiedQuery,
+        Quota {
+            max_events: 100,
+            per: Duration::from_secs(60),
+        },
+    )])
+}
+
+/// The rate limiting state for a single event type
+struct Bucket {
+    quota: Quota,
+    /// The tokens available for sending events, or `None` if the quota doesn't allow any events to
+    /// be sent
+    tokens: Option<TokenBucket>,
+    /// The number of events suppressed since the last summary was sent
+    suppressed: u64,
+    /// When the summary of the suppressed events should be sent. Set when the first event is
+    /// suppressed.
+    summary_due: Option<Instant>,
+}
+
+/// Tracks the rate of events of each type against their [`RateLimits`]
+#[derive(Default)]
+pub(crate) struct RateLimiter {
+    buckets: HashMap<TelemetryEvent, Bucket>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limits: RateLimits) -> Self {
+        Self {
+            buckets: limits
+                .into_iter()
+                .filter(|(_, quota)| !quota.per.is_zero())
+                .map(|(event, quota)| {
+                    let max_events = quota.max_events as f64;
+                    let tokens = (quota.max_events > 0).then(|| {
+                        TokenBucket::new(max_events / quota.per.as_secs_f64(), max_events)
+                    });
+                    (
+                        event,
+                        Bucket {
+                            quota,
+                            tokens,
+                            suppressed: 0,
+                            summary_due: None,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns true if an event of type `event` received now is within its quota, and should be
+    /// sent. Otherwise, the event is counted towards the next summary for its type.
+    pub(crate) fn check(&mut self, event: TelemetryEvent) -> bool {
+        let bucket = match self.buckets.get_mut(&event) {
+            Some(bucket) => bucket,
+            None => return true,
+        };
+
+        if bucket
+            .tokens
+            .as_mut()
+            .map_or(false, TokenBucket::try_acquire)
+        {
+            return true;
+        }
+
+        if bucket.suppressed == 0 {
+            bucket.summary_due = Some(Instant::now() + bucket.quota.per);
+        }
+        bucket.suppressed += 1;
+        false
+    }
+
+    /// Take a [`TelemetryEvent::RateLimited`] event summarizing the suppressed events of each type
+    /// whose summary is due at `now`, or of every type with suppressed events if `now` is `None`
+    pub(crate) fn take_summaries(
+        &mut self,
+        now: Option<Instant>,
+    ) -> Vec<(TelemetryEvent, Telemetry)> {
+        self.buckets
+            .iter_mut()
+            .filter(|(_, bucket)| {
+                bucket.suppressed > 0
+                    && match (now, bucket.summary_due) {
+                        (Some(now), Some(due)) => due <= now,
+                        _ => true,
+                    }
+            })
+            .map(|(event, bucket)| {
+                let suppressed = std::mem::take(&mut bucket.suppressed);
+                bucket.summary_due = None;
+                (
+                    TelemetryEvent::RateLimited,
+                    TelemetryBuilder::new()
+                        .rate_limited_event(event_name(*event))
+                        .suppressed_events(suppressed)
+                        .build(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// The name of `event` as it's sent to Segment
+fn event_name(event: TelemetryEvent) -> String {
+    serde_json::to_value(event)
+        .ok()
+        .and_then(|name| name.as_str().map(str::to_owned))
+        .unwrap_or_else(|| format!("{event:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(max_events: u32) -> RateLimiter {
+        RateLimiter::new(HashMap::from([(
+            TelemetryEvent::ProxiedQuery,
+            Quota {
+                max_events,
+                per: Duration::from_secs(10),
+            },
+        )]))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn events_within_quota_are_sent() {
+        let mut limiter = limiter(2);
+        assert!(limiter.check(TelemetryEvent::ProxiedQuery));
+        assert!(limiter.check(TelemetryEvent::ProxiedQuery));
+        assert!(!limiter.check(TelemetryEvent::ProxiedQuery));
+
+        // Events without a quota are never limited
+        for _ in 0..10 {
+            assert!(limiter.check(TelemetryEvent::QueryParseFailed));
+        }
+
+        // Half the window replenishes half the quota
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert!(limiter.check(TelemetryEvent::ProxiedQuery));
+        assert!(!limiter.check(TelemetryEvent::ProxiedQuery));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn empty_quota_suppresses_all_events() {
+        let mut limiter = limiter(0);
+        assert!(!limiter.check(TelemetryEvent::ProxiedQuery));
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert!(!limiter.check(TelemetryEvent::ProxiedQuery));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn suppressed_events_are_summarized_after_window() {
+        let mut limiter = limiter(1);
+        let now = Instant::now();
+        assert!(limiter.check(TelemetryEvent::ProxiedQuery));
+        for _ in 0..5 {
+            assert!(!limiter.check(TelemetryEvent::ProxiedQuery));
+        }
+
+        assert!(limiter
+            .take_summaries(Some(now + Duration::from_secs(9)))
+            .is_empty());
+        assert_eq!(
+            limiter.take_summaries(Some(now + Duration::from_secs(10))),
+            vec![(
+                TelemetryEvent::RateLimited,
+                TelemetryBuilder::new()
+                    .rate_limited_event("proxied_query")
+                    .suppressed_events(5u64)
+                    .build()
+            )]
+        );
+        assert!(limiter.take_summaries(None).is_empty());
+    }
+}
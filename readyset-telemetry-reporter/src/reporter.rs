@@ -2,256 +2,645 @@
 //! TelemetryReporter
 //! The Telemetry Reporter acts asynchronously by spawning a background task that listens for
 //! [`TelemetryEvent`]s sent from [`TelemetryReporter`]s. When it receives one, it forwards the
-//! request to Segment.
+//! request to its primary transport (by default, Segment) and to any additional transports.
 #[cfg(any(test, feature = "test-util"))]
 use std::collections::HashMap;
-#[cfg(any(test, feature = "test-util"))]
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
-use backoff::ExponentialBackoffBuilder;
-use blake2::digest::{Update, VariableOutput};
-use blake2::Blake2bVar;
-use lazy_static::lazy_static;
+use futures::future::join_all;
+use metrics::decrement_gauge;
+use readyset_client_metrics::recorded;
 use readyset_tracing::{debug, info, trace, warn};
-use readyset_version::COMMIT_ID;
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
-use reqwest::{Client, RequestBuilder, Response, StatusCode, Url};
-use tokio::sync::mpsc::Receiver;
+use reqwest::Url;
+use serde::Serialize;
+use tokio::sync::mpsc::{self, Receiver, Sender};
 use tokio::sync::{oneshot, Mutex};
-use tokio::time::Interval;
-use uuid::Uuid;
+use tokio::time::Instant;
 
-use crate::error::{ReporterError as Error, ReporterResult as Result};
+use crate::error::ReporterResult as Result;
+use crate::rate_limit::{RateLimiter, RateLimits};
+use crate::retry::RetryPolicy;
+use crate::segment::SegmentTransport;
 use crate::telemetry::*;
+use crate::transport::{TelemetryTransport, Transport};
 
-/// Maximum time to retry sending telemetry payloads before giving up
-const TIMEOUT: Duration = Duration::from_secs(2);
+#[async_trait]
+pub trait PeriodicReport: Send + Sync {
+    /// A name identifying this reporter, used to enable or disable it at runtime via
+    /// [`PeriodicReporters`]
+    fn name(&self) -> &str;
 
-/// User agent to use for all telemetry payload requests
-const APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
+    async fn report(&self) -> Result<Vec<(TelemetryEvent, Telemetry)>>;
 
-/// URL to report telemetry to
-const TELEMETRY_BASE_URL: &str = "https://api.segment.io/v1/";
+    /// Limits on the size of each report returned by [`report`](PeriodicReport::report). Defaults
+    /// to no limit.
+    fn report_limit(&self) -> ReportLimit {
+        ReportLimit::default()
+    }
 
-/// Length to which DEPLOYMENT_ENV will be truncated
-const DEPLOYMENT_ENV_LEN_MAX: usize = 20;
+    /// How often [`report`](PeriodicReport::report) is run. Each reporter is run on its own
+    /// schedule, independently of any other reporters registered with the same
+    /// [`TelemetryReporter`]. Defaults to [`DEFAULT_PERIODIC_REPORT_INTERVAL`].
+    fn interval(&self) -> Duration {
+        DEFAULT_PERIODIC_REPORT_INTERVAL
+    }
 
-/// Silently succeed if the client is None.
-macro_rules! client {
-    ($self: expr) => {
-        if let Some(client) = &$self.client {
-            client
-        } else {
-            return Ok(());
-        }
-    };
+    /// How long [`report`](PeriodicReport::report) may take before it's abandoned, so that a slow
+    /// reporter can't hold up the other reporters or the rest of the [`TelemetryReporter`].
+    /// Defaults to [`DEFAULT_PERIODIC_REPORT_TIMEOUT`].
+    fn timeout(&self) -> Duration {
+        DEFAULT_PERIODIC_REPORT_TIMEOUT
+    }
 }
 
-lazy_static! {
-    /// Identifies the ReadySet Segment source. Common between all users.
-    /// In priority order, the value is:
-    /// - `RS_SEGMENT_WRITE_KEY` from the run-time environment
-    /// - `RS_SEGMENT_WRITE_KEY` from the compile-time environment
-    /// - `None`
-    ///
-    /// If `None`, no-op telemetry reporters will be created, which do not send HTTP requests.
+/// The interval at which a [`PeriodicReport`] is run, unless it overrides
+/// [`interval`](PeriodicReport::interval)
+pub const DEFAULT_PERIODIC_REPORT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The time a [`PeriodicReport`] may take to run, unless it overrides
+/// [`timeout`](PeriodicReport::timeout)
+pub const DEFAULT_PERIODIC_REPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Limits on the size of the report sent by a single [`PeriodicReport`] each time it runs.
+///
+/// Events beyond the limit are carried over to the reporter's next report, and sent ahead of the
+/// events it returns then. At most [`MAX_CARRIED_OVER_EVENTS`] events are carried over per
+/// reporter; any beyond that are dropped, and summarized in a single
+/// [`TelemetryEvent::PeriodicReportTruncated`] event carrying the number of events dropped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReportLimit {
+    /// The maximum number of events to send per report
+    pub max_events: Option<usize>,
+    /// The maximum total size, in bytes, of the JSON-serialized payloads of the events sent per
+    /// report
+    pub max_bytes: Option<usize>,
+}
+
+/// The maximum number of events from a single [`PeriodicReport`] which may be carried over to its
+/// next report because they didn't fit within its [`ReportLimit`]
+pub const MAX_CARRIED_OVER_EVENTS: usize = 1000;
+
+impl ReportLimit {
+    /// Truncate `report` to fit within this limit, returning the events removed from it.
     ///
-    /// If `Some` but the key doesn't correspond to a valid Segment source, HTTP requests will be
-    /// sent and silently ignored.
-    static ref SEGMENT_WRITE_KEY: Option<String> = {
-        std::env::var("RS_SEGMENT_WRITE_KEY")
-            .ok()
-            .filter(|s| !s.is_empty())
-            .or_else(|| option_env!("RS_SEGMENT_WRITE_KEY").map(str::to_owned))
-    };
+    /// The first event is always kept (unless `max_events` is 0), even if it's larger than
+    /// `max_bytes` by itself, so that an oversized event can't hold back every report after it.
+    fn truncate(
+        &self,
+        report: &mut Vec<(TelemetryEvent, Telemetry)>,
+    ) -> Vec<(TelemetryEvent, Telemetry)> {
+        let mut keep = report.len().min(self.max_events.unwrap_or(usize::MAX));
+        if let Some(max_bytes) = self.max_bytes {
+            let mut bytes = 0;
+            let fits = report[..keep]
+                .iter()
+                .take_while(|(_, telemetry)| {
+                    bytes += serde_json::to_vec(telemetry).map_or(0, |body| body.len());
+                    bytes <= max_bytes
+                })
+                .count();
+            keep = fits.max(keep.min(1));
+        }
+
+        report.split_off(keep)
+    }
 }
 
-fn telemetry_url(path: &str) -> Url {
-    Url::parse(TELEMETRY_BASE_URL).unwrap().join(path).unwrap()
+pub type PeriodicReporter = Arc<dyn PeriodicReport>;
+
+/// Configures how events are batched before being sent to Segment.
+///
+/// Rather than making one HTTP request per event, the reporter buffers events and sends them to
+/// Segment's batch endpoint together, once either [`max_events`](BatchConfig::max_events) events
+/// have been buffered or the oldest buffered event has waited for
+/// [`max_interval`](BatchConfig::max_interval), whichever comes first. Any partial batch is sent
+/// when the reporter shuts down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchConfig {
+    /// The maximum number of events to send in a single request. A value of 0 is treated as 1.
+    pub max_events: usize,
+    /// The maximum time an event may be buffered before the batch containing it is sent
+    pub max_interval: Duration,
 }
 
-#[async_trait]
-pub trait PeriodicReport: Send + Sync {
-    async fn report(&self) -> Result<Vec<(TelemetryEvent, Telemetry)>>;
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_events: 50,
+            max_interval: Duration::from_secs(5),
+        }
+    }
 }
 
-pub type PeriodicReporter = Arc<dyn PeriodicReport>;
+/// A request to a [`TelemetryReporter`] to deliver all of its pending events immediately, which is
+/// answered with the number of events successfully delivered to its primary transport
+pub type FlushRequest = oneshot::Sender<usize>;
+
+/// The outcome of shutting down a [`TelemetryReporter`], returned by
+/// [`TelemetrySender::graceful_shutdown`](crate::TelemetrySender::graceful_shutdown)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownSummary {
+    /// The total number of events delivered to the primary transport while the reporter was
+    /// running, including those delivered while it was shutting down
+    pub delivered: usize,
+    /// The total number of events which were dropped while the reporter was running, either
+    /// because the primary transport failed to deliver them, because the reporter's queue was full
+    /// when they were sent, or because they exceeded a rate limit or a [`ReportLimit`]
+    pub dropped: usize,
+}
 
-pub struct TelemetryReporter {
-    client: Option<Client>,
+/// A count of dropped telemetry events, shared between a [`TelemetryReporter`] and the
+/// [`TelemetrySender`](crate::TelemetrySender)s which send events to it
+pub type DroppedEvents = Arc<AtomicUsize>;
 
-    rx: Receiver<(TelemetryEvent, Telemetry)>,
+/// The maximum number of flush requests which may be waiting to be handled by the reporter
+const FLUSH_CHANNEL_LEN: usize = 16;
+
+/// Events buffered to be sent in the next batch
+#[derive(Default)]
+struct PendingBatch {
+    events: Vec<(TelemetryEvent, Telemetry)>,
+    /// When the batch must be sent, regardless of its size. Set when the first event is buffered.
+    deadline: Option<Instant>,
+}
+
+struct RegisteredReporter {
+    reporter: PeriodicReporter,
+    enabled: bool,
+    /// When the reporter is next due to run
+    next_report: Instant,
+    /// Events which didn't fit within the reporter's [`ReportLimit`] the last time it ran, to be
+    /// sent ahead of its next report
+    carried_over: Vec<(TelemetryEvent, Telemetry)>,
+}
+
+/// The name and current state of a registered [`PeriodicReporter`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PeriodicReporterStatus {
+    pub name: String,
+    pub enabled: bool,
+}
+
+/// A handle to the set of periodic reporters registered with a [`TelemetryReporter`], which can be
+/// used to list them and to disable or re-enable them while the reporter is running
+#[derive(Clone, Default)]
+pub struct PeriodicReporters {
+    inner: Arc<Mutex<Vec<RegisteredReporter>>>,
+}
+
+impl std::fmt::Debug for PeriodicReporters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PeriodicReporters").finish_non_exhaustive()
+    }
+}
+
+impl PeriodicReporters {
+    /// Register a new periodic reporter, which starts out enabled and is first run immediately
+    pub async fn register(&self, periodic_reporter: PeriodicReporter) {
+        debug!(
+            name = %periodic_reporter.name(),
+            interval = ?periodic_reporter.interval(),
+            "registering periodic reporter"
+        );
+        self.inner.lock().await.push(RegisteredReporter {
+            reporter: periodic_reporter,
+            enabled: true,
+            next_report: Instant::now(),
+            carried_over: vec![],
+        });
+    }
+
+    /// List the names of all registered periodic reporters, and whether they are enabled
+    pub async fn list(&self) -> Vec<PeriodicReporterStatus> {
+        self.inner
+            .lock()
+            .await
+            .iter()
+            .map(|r| PeriodicReporterStatus {
+                name: r.reporter.name().to_owned(),
+                enabled: r.enabled,
+            })
+            .collect()
+    }
+
+    /// Enable or disable all periodic reporters with the given name. Disabled reporters are skipped
+    /// when periodic reports are run, until they are enabled again.
+    ///
+    /// Returns `false` if no reporter with the given name is registered
+    pub async fn set_enabled(&self, name: &str, enabled: bool) -> bool {
+        let mut found = false;
+        for r in self
+            .inner
+            .lock()
+            .await
+            .iter_mut()
+            .filter(|r| r.reporter.name() == name)
+        {
+            info!(%name, %enabled, "toggling periodic reporter");
+            r.enabled = enabled;
+            found = true;
+        }
+        found
+    }
+
+    /// Returns when the next enabled reporter is due to run, if there are any
+    async fn next_report(&self) -> Option<Instant> {
+        self.inner
+            .lock()
+            .await
+            .iter()
+            .filter(|r| r.enabled)
+            .map(|r| r.next_report)
+            .min()
+    }
 
-    /// https://segment.com/docs/connections/spec/identify/#user-id
-    user_id: Option<String>,
+    /// Take every enabled reporter which is due to run at `now`, scheduling each of them to run
+    /// again once its interval has elapsed
+    async fn take_due(&self, now: Instant) -> Vec<PeriodicReporter> {
+        self.inner
+            .lock()
+            .await
+            .iter_mut()
+            .filter(|r| r.enabled && r.next_report <= now)
+            .map(|r| {
+                r.next_report = now + r.reporter.interval();
+                r.reporter.clone()
+            })
+            .collect()
+    }
 
-    /// Per-session generated ID
-    /// https://segment.com/docs/connections/spec/identify/#anonymous-id
-    anonymous_id: String,
+    /// Take the events carried over from the last report of the given reporter
+    async fn take_carried_over(
+        &self,
+        reporter: &PeriodicReporter,
+    ) -> Vec<(TelemetryEvent, Telemetry)> {
+        self.inner
+            .lock()
+            .await
+            .iter_mut()
+            .find(|r| Arc::ptr_eq(&r.reporter, reporter))
+            .map(|r| std::mem::take(&mut r.carried_over))
+            .unwrap_or_default()
+    }
+
+    /// Carry `events` over to the next report of the given reporter
+    async fn carry_over(
+        &self,
+        reporter: &PeriodicReporter,
+        events: Vec<(TelemetryEvent, Telemetry)>,
+    ) {
+        if let Some(r) = self
+            .inner
+            .lock()
+            .await
+            .iter_mut()
+            .find(|r| Arc::ptr_eq(&r.reporter, reporter))
+        {
+            r.carried_over = events;
+        }
+    }
+}
+
+/// Receives telemetry events from [`TelemetrySender`](crate::TelemetrySender)s and delivers them,
+/// in batches, to its primary transport - by default, ReadySet's Segment source - and to each of
+/// its additional [`Transport`]s
+pub struct TelemetryReporter<T = SegmentTransport> {
+    rx: Receiver<(TelemetryEvent, Telemetry)>,
+
+    /// The primary destination of telemetry events, to which events are sent in batches
+    transport: T,
 
     /// Will shut down the run loop upon receiving a signal
     shutdown_rx: oneshot::Receiver<()>,
 
     /// Acknowledge that we shutdown gracefully
-    shutdown_ack_tx: Option<oneshot::Sender<()>>,
+    shutdown_ack_tx: Option<oneshot::Sender<ShutdownSummary>>,
 
-    /// Deployment environment, e.g. container orchestrator framework, if any
-    deployment_env: String,
+    /// Zero or many periodic reporters that can collect and send metrics periodically
+    periodic_reporters: PeriodicReporters,
 
-    /// Deployment ID, to help differentiate between deployments with the same user ID.
-    /// (user_id, deployment_id) is not guaranteed to be unique, as they are both user-provided.
-    deployment_id: String,
+    /// Additional destinations to which every event is sent, alongside the primary transport
+    transports: Vec<Transport>,
 
-    /// Zero or many periodic reporters that can collect and send metrics periodically
-    periodic_reporters: Arc<Mutex<Vec<PeriodicReporter>>>,
+    /// How failed sends to the primary transport and to each additional transport are retried
+    retry_policy: RetryPolicy,
+
+    /// How events are batched before being sent to the primary transport
+    batch_config: BatchConfig,
+
+    /// Events waiting to be sent to the primary transport in the next batch
+    batch: Mutex<PendingBatch>,
+
+    /// Handed out to [`TelemetrySender`](crate::TelemetrySender)s so they can request a flush
+    flush_tx: Sender<FlushRequest>,
+
+    /// Requests to deliver all pending events immediately
+    flush_rx: Receiver<FlushRequest>,
+
+    /// The total number of events successfully delivered to the primary transport
+    delivered_events: AtomicUsize,
+
+    /// The total number of events which couldn't be delivered to the primary transport, were
+    /// rate limited, or were dropped by senders because the queue was full
+    dropped_events: DroppedEvents,
+
+    /// Limits on the rate at which events of each type are sent
+    rate_limiter: Mutex<RateLimiter>,
 
     #[cfg(any(test, feature = "test-util"))]
     received_events: Arc<Mutex<HashMap<TelemetryEvent, Vec<Telemetry>>>>,
 }
 
 impl TelemetryReporter {
-    const PERIODIC_REPORT_INTERVAL: Duration = Duration::from_secs(30);
-
+    /// Construct a reporter which sends events to ReadySet's Segment source, or to `endpoint` in
+    /// its place if set
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         rx: Receiver<(TelemetryEvent, Telemetry)>,
         api_key: Option<String>,
         shutdown_rx: oneshot::Receiver<()>,
-        shutdown_ack_tx: oneshot::Sender<()>,
+        shutdown_ack_tx: oneshot::Sender<ShutdownSummary>,
         deployment_id: String,
+        hmac_secret: Option<String>,
+        endpoint: Option<Url>,
+        retry_policy: RetryPolicy,
+        batch_config: BatchConfig,
     ) -> Self {
-        // If the api_key is set, use that as the user_id.
-        // If not, try to get a machine uid. If that works, anonymize it by hashing it with blake2b,
-        // a cryptographically secure hashing library, and use that as the id, otherwise, no
-        // id will be set
-        // NOTE: The machine id may not be unique across all users, since there may be many virtual
-        // machines or corporate images that have the same machine id. Still, this is a decent
-        // heuristic for unique users
-
-        let user_id = api_key.or_else(|| machine_uid::get().ok().map(blake2b_string));
+        Self::with_transport(
+            rx,
+            SegmentTransport::new(api_key, deployment_id, hmac_secret, endpoint),
+            shutdown_rx,
+            shutdown_ack_tx,
+            retry_policy,
+            batch_config,
+        )
+    }
+}
 
+impl<T: TelemetryTransport> TelemetryReporter<T> {
+    /// Construct a reporter which sends events to the given primary `transport`, rather than to
+    /// ReadySet's Segment source
+    pub fn with_transport(
+        rx: Receiver<(TelemetryEvent, Telemetry)>,
+        transport: T,
+        shutdown_rx: oneshot::Receiver<()>,
+        shutdown_ack_tx: oneshot::Sender<ShutdownSummary>,
+        retry_policy: RetryPolicy,
+        batch_config: BatchConfig,
+    ) -> Self {
+        let (flush_tx, flush_rx) = mpsc::channel(FLUSH_CHANNEL_LEN);
         Self {
             rx,
-            client: SEGMENT_WRITE_KEY.as_ref().and_then(|k| make_client(k).ok()),
-            user_id,
-            anonymous_id: Uuid::new_v4().to_string(),
+            transport,
             shutdown_rx,
             shutdown_ack_tx: Some(shutdown_ack_tx),
-            deployment_env: std::env::var("DEPLOYMENT_ENV")
-                .unwrap_or_default()
-                .chars()
-                .filter(|c| c.is_ascii_alphanumeric() || ['_', '.'].contains(c))
-                .take(DEPLOYMENT_ENV_LEN_MAX)
-                .collect(),
-            deployment_id,
-            periodic_reporters: Arc::new(Mutex::new(vec![])),
+            periodic_reporters: PeriodicReporters::default(),
+            transports: Vec::new(),
+            retry_policy,
+            batch_config: BatchConfig {
+                max_events: batch_config.max_events.max(1),
+                ..batch_config
+            },
+            batch: Default::default(),
+            flush_tx,
+            flush_rx,
+            delivered_events: AtomicUsize::new(0),
+            dropped_events: Default::default(),
+            rate_limiter: Default::default(),
             #[cfg(any(test, feature = "test-util"))]
             received_events: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
-    fn build_request(
-        &self,
-        client: &Client,
-        event: TelemetryEvent,
-        telemetry: &Telemetry,
-    ) -> RequestBuilder {
-        client.post(telemetry_url("track")).json(&Track {
-            user_id: self.user_id.as_ref(),
-            anonymous_id: &self.anonymous_id,
-            event,
-            properties: Properties {
-                telemetry,
-                commit_id: COMMIT_ID,
-                deployment_env: &self.deployment_env,
-                deployment_id: &self.deployment_id,
-            },
-        })
+    /// Send a batch of telemetry payloads to the primary transport, retrying according to the
+    /// reporter's [`RetryPolicy`]. If every attempt fails, or the transport rejects the batch with
+    /// an error which isn't [retriable](crate::ReporterError::is_retriable), the whole batch is
+    /// logged and dropped.
+    async fn send_batch(&self, events: Vec<(TelemetryEvent, Telemetry)>) {
+        debug!(transport = %self.transport.name(), events = %events.len(), "sending batch");
+        let res = self
+            .retry_policy
+            .retry(|| self.transport.send_batch(&events))
+            .await;
+
+        let error = match res {
+            Ok(()) => {
+                self.delivered_events
+                    .fetch_add(events.len(), Ordering::Relaxed);
+                return;
+            }
+            Err(error) => error,
+        };
+
+        self.dropped_events
+            .fetch_add(events.len(), Ordering::Relaxed);
+        match error {
+            error if !error.is_retriable() => warn!(
+                %error,
+                transport = %self.transport.name(),
+                events = %events.len(),
+                "telemetry batch permanently rejected; dropping batch without retrying"
+            ),
+            error => warn!(
+                %error,
+                transport = %self.transport.name(),
+                events = %events.len(),
+                "failed to send telemetry; dropping batch"
+            ),
+        }
     }
 
-    /// Send a telemetry payload to Segment. If the initial request fails for a non-permanent
-    /// reason (eg, not a 4XX or IO error), this function will retry with an exponential
-    /// backoff, timing out at [`TIMEOUT`].
-    async fn send_event_with_payload_inner(
-        &self,
-        event: TelemetryEvent,
-        telemetry: &Telemetry,
-    ) -> Result<()> {
-        handle_resp(
-            self.build_request(client!(self), event, telemetry)
-                .send()
-                .await?,
-        )
-        .await
-    }
-
-    async fn send_event(&self, event: TelemetryEvent, payload: &Telemetry) {
-        debug!(?event, ?payload, "sending event");
-        let backoff = ExponentialBackoffBuilder::new()
-            .with_max_elapsed_time(Some(TIMEOUT))
-            .build();
-        let res = tokio::time::timeout(
-            TIMEOUT,
-            backoff::future::retry(backoff, move || async move {
-                self.send_event_with_payload_inner(event, payload)
-                    .await
-                    .map_err(|e| match e {
-                        Error::Reqwest(_) | Error::Server(_) => e.into(),
-                        e @ (Error::InvalidAPIKeyHeader(_)
-                        | Error::Unauthorized
-                        | Error::HTTPError { .. }
-                        | Error::Timeout(_)
-                        | Error::Client(_)
-                        | Error::Json(_)) => backoff::Error::Permanent(e),
-                    })
-            }),
-        )
+    /// Add an event to the batch to be sent to the primary transport, sending the batch if it is
+    /// now full
+    async fn enqueue_event(&self, event: TelemetryEvent, payload: &Telemetry) {
+        let full = {
+            let mut batch = self.batch.lock().await;
+            if batch.events.is_empty() {
+                batch.deadline = Some(Instant::now() + self.batch_config.max_interval);
+            }
+            batch.events.push((event, payload.clone()));
+            batch.events.len() >= self.batch_config.max_events
+        };
+
+        if full {
+            self.flush_batch().await;
+        }
+    }
+
+    /// Send any events waiting in the current batch to the primary transport, even if the batch
+    /// isn't full
+    async fn flush_batch(&self) {
+        let events = {
+            let mut batch = self.batch.lock().await;
+            batch.deadline = None;
+            std::mem::take(&mut batch.events)
+        };
+
+        if !events.is_empty() {
+            self.send_batch(events).await;
+        }
+    }
+
+    /// Send an event to all of the additional [`Transport`]s concurrently, retrying each
+    /// independently of the others according to the reporter's [`RetryPolicy`].
+    ///
+    /// Returns the number of transports the event was successfully delivered to
+    async fn send_to_transports(&self, event: TelemetryEvent, payload: &Telemetry) -> usize {
+        let results = join_all(self.transports.iter().map(|transport| {
+            self.retry_policy
+                .retry(move || transport.send(event, payload))
+        }))
         .await;
 
-        if res.is_err() {
-            warn!(?res, ?event, ?payload, "failed to send telemetry");
+        let mut delivered = 0;
+        for (transport, res) in self.transports.iter().zip(results) {
+            match res {
+                Ok(()) => delivered += 1,
+                Err(error) if !error.is_retriable() => warn!(
+                    %error,
+                    transport = %transport.name(),
+                    ?event,
+                    "telemetry event permanently rejected by transport; dropping event without \
+                     retrying"
+                ),
+                Err(error) => warn!(
+                    %error,
+                    transport = %transport.name(),
+                    ?event,
+                    "failed to send telemetry to transport"
+                ),
+            }
         }
+        delivered
     }
 
     #[cfg(not(any(test, feature = "test-util")))]
     async fn process_event(&self, event: TelemetryEvent, payload: &Telemetry) {
-        self.send_event(event, payload).await;
+        tokio::join!(
+            self.enqueue_event(event, payload),
+            self.send_to_transports(event, payload)
+        );
     }
 
     #[cfg(any(test, feature = "test-util"))]
     async fn process_event(&self, event: TelemetryEvent, payload: &Telemetry) {
-        let mut received_events = self.received_events.lock().await;
-        let entry = received_events
-            .entry(event)
-            .or_insert_with(std::vec::Vec::new);
-        entry.push((*payload).clone());
+        {
+            let mut received_events = self.received_events.lock().await;
+            let entry = received_events
+                .entry(event)
+                .or_insert_with(std::vec::Vec::new);
+            entry.push((*payload).clone());
+        }
+        tokio::join!(
+            self.enqueue_event(event, payload),
+            self.send_to_transports(event, payload)
+        );
+    }
+
+    /// Process an event received from a sender or a periodic reporter, unless it exceeds the
+    /// [`Quota`](crate::Quota) for its type, in which case it's counted towards the next
+    /// [`TelemetryEvent::RateLimited`] summary for that type instead
+    async fn receive_event(&self, event: TelemetryEvent, payload: &Telemetry) {
+        if self.rate_limiter.lock().await.check(event) {
+            self.process_event(event, payload).await;
+        } else {
+            trace!(?event, "telemetry event rate limited");
+            self.dropped_events.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Process a [`TelemetryEvent::RateLimited`] event summarizing the events suppressed for each
+    /// rate-limited event type, either once the summary is due or, if `force` is set, immediately
+    async fn send_rate_limit_summaries(&self, force: bool) {
+        let now = (!force).then(Instant::now);
+        let summaries = self.rate_limiter.lock().await.take_summaries(now);
+        for (event, telemetry) in summaries {
+            self.process_event(event, &telemetry).await;
+        }
+    }
+
+    /// Run the given periodic reporters concurrently, and process the events they report
+    async fn run_periodic_reports(&self, reporters: Vec<PeriodicReporter>) {
+        let reports = join_all(reporters.iter().map(|reporter| async move {
+            tokio::time::timeout(reporter.timeout(), reporter.report()).await?
+        }))
+        .await;
+        for (reporter, report) in reporters.iter().zip(reports) {
+            let mut report = match report {
+                Ok(report) => report,
+                Err(error) => {
+                    debug!(%error, reporter = %reporter.name(), "periodic report failed");
+                    continue;
+                }
+            };
+            // Events carried over from the last report are older, so they're sent first
+            let mut carried_over = self.periodic_reporters.take_carried_over(reporter).await;
+            carried_over.append(&mut report);
+            let mut report = carried_over;
+
+            let mut overflow = reporter.report_limit().truncate(&mut report);
+            for (event, telemetry) in report {
+                self.receive_event(event, &telemetry).await;
+            }
+            let truncated = overflow.len().saturating_sub(MAX_CARRIED_OVER_EVENTS);
+            overflow.truncate(MAX_CARRIED_OVER_EVENTS);
+            if !overflow.is_empty() {
+                debug!(
+                    reporter = %reporter.name(),
+                    carried_over = %overflow.len(),
+                    "periodic report exceeded its limit; carrying events over to the next report"
+                );
+            }
+            self.periodic_reporters.carry_over(reporter, overflow).await;
+            if truncated > 0 {
+                self.dropped_events.fetch_add(truncated, Ordering::Relaxed);
+                warn!(
+                    reporter = %reporter.name(),
+                    %truncated,
+                    "periodic report exceeded its limit; dropped events"
+                );
+                self.process_event(
+                    TelemetryEvent::PeriodicReportTruncated,
+                    &TelemetryBuilder::new()
+                        .periodic_reporter(reporter.name())
+                        .truncated_events(truncated as u64)
+                        .build(),
+                )
+                .await;
+            }
+        }
     }
 
     pub async fn run(&mut self) {
-        let mut interval = tokio::time::interval(Self::PERIODIC_REPORT_INTERVAL);
         loop {
-            if !self.run_once(&mut interval).await {
+            if !self.run_once().await {
                 return;
             }
         }
     }
 
     /// Returns true if we are still running, false if we should shut down
-    async fn run_once(&mut self, interval: &mut Interval) -> bool {
+    async fn run_once(&mut self) -> bool {
         trace!("TelemetryReporter run_once");
+        let batch_deadline = self.batch.lock().await.deadline;
+        let next_report = self.periodic_reporters.next_report().await;
         tokio::select! {
             biased;
             _ = &mut self.shutdown_rx => {
                 info!("shutting down telemetry reporter. will attempt to drain in-flight metrics");
-                while let Ok((event, telemetry)) = self.rx.try_recv() {
-                    debug!(?event, ?telemetry, "TelemetryEvent received");
-                    self.process_event(event, &telemetry).await;
-                }
+                self.drain_queue().await;
+                self.send_rate_limit_summaries(true).await;
+                self.flush_batch().await;
+                let summary = ShutdownSummary {
+                    delivered: self.delivered_events.load(Ordering::Relaxed),
+                    dropped: self.dropped_events.load(Ordering::Relaxed),
+                };
 
                 if let Some(shutdown_ack_tx) = self.shutdown_ack_tx.take() {
-                    let _ = shutdown_ack_tx.send(());
+                    let _ = shutdown_ack_tx.send(summary);
                 } else {
                     trace!("unable to acknowledge shutdown");
                 };
@@ -259,40 +648,106 @@ impl TelemetryReporter {
                 self.rx.close();
                 return false;
             }
+            _ = tokio::time::sleep_until(batch_deadline.unwrap_or_else(Instant::now)),
+                if batch_deadline.is_some() =>
+            {
+                trace!("batch interval elapsed");
+                self.flush_batch().await;
+            }
+            Some(flush_ack_tx) = self.flush_rx.recv() => {
+                debug!("flushing pending telemetry");
+                let delivered_before = self.delivered_events.load(Ordering::Relaxed);
+                self.drain_queue().await;
+                self.send_rate_limit_summaries(true).await;
+                self.flush_batch().await;
+                let delivered = self.delivered_events.load(Ordering::Relaxed) - delivered_before;
+                // The sender may have timed out waiting for the flush
+                let _ = flush_ack_tx.send(delivered);
+            }
             Some((event, telemetry)) = Self::maybe_recv_event(&mut self.rx) => {
-                self.process_event(event, &telemetry).await;
+                self.receive_event(event, &telemetry).await;
             }
-            _ = interval.tick() => {
-                debug!("starting periodic report");
-                let periodic_reporters = self.periodic_reporters.lock().await;
-
-                for reporter in periodic_reporters.iter() {
-                    if let Ok(report) = reporter.report().await {
-                        for (event, telemetry) in report {
-                            self.process_event(event, &telemetry).await;
-                        }
-                    }
-                }
+            _ = tokio::time::sleep_until(next_report.unwrap_or_else(Instant::now)),
+                if next_report.is_some() =>
+            {
+                let due = self.periodic_reporters.take_due(Instant::now()).await;
+                debug!(reporters = %due.len(), "starting periodic reports");
+                self.run_periodic_reports(due).await;
             }
         }
+        self.send_rate_limit_summaries(false).await;
         true
     }
 
+    /// Process every event currently waiting in the queue, without waiting for any more
+    async fn drain_queue(&mut self) {
+        while let Ok((event, telemetry)) = self.rx.try_recv() {
+            decrement_gauge!(recorded::TELEMETRY_QUEUE_DEPTH, 1.0);
+            debug!(?event, ?telemetry, "TelemetryEvent received");
+            self.receive_event(event, &telemetry).await;
+        }
+    }
+
     async fn maybe_recv_event(
         rx: &mut Receiver<(TelemetryEvent, Telemetry)>,
     ) -> Option<(TelemetryEvent, Telemetry)> {
-        rx.recv().await
+        let res = rx.recv().await;
+        if res.is_some() {
+            decrement_gauge!(recorded::TELEMETRY_QUEUE_DEPTH, 1.0);
+        }
+        res
+    }
+
+    #[cfg(any(test, feature = "test-util"))]
+    pub async fn test_run_once(&mut self) -> bool {
+        self.run_once().await
     }
 
+    /// Runs every enabled periodic reporter immediately, regardless of when each is next due
     #[cfg(any(test, feature = "test-util"))]
-    pub async fn test_run_once(&mut self, interval: &mut Interval) -> bool {
-        self.run_once(interval).await
+    pub async fn test_run_periodic_reports(&self) {
+        let reporters = self
+            .periodic_reporters
+            .inner
+            .lock()
+            .await
+            .iter()
+            .filter(|r| r.enabled)
+            .map(|r| r.reporter.clone())
+            .collect();
+        self.run_periodic_reports(reporters).await;
     }
 
     pub async fn register_periodic_reporter(&mut self, periodic_reporter: PeriodicReporter) {
-        debug!("registering periodic reporter");
-        let mut periodic_reporters = self.periodic_reporters.lock().await;
-        periodic_reporters.push(periodic_reporter);
+        self.periodic_reporters.register(periodic_reporter).await;
+    }
+
+    /// Register an additional destination to which all events are sent, alongside Segment
+    pub fn add_transport(&mut self, transport: Transport) {
+        self.transports.push(transport);
+    }
+
+    /// Limit the rate at which events of each type are sent, replacing any existing limits
+    pub fn set_rate_limits(&mut self, rate_limits: RateLimits) {
+        self.rate_limiter = Mutex::new(RateLimiter::new(rate_limits));
+    }
+
+    /// Returns a handle which can be used to list, enable, and disable this reporter's periodic
+    /// reporters while it is running
+    pub fn periodic_reporters(&self) -> PeriodicReporters {
+        self.periodic_reporters.clone()
+    }
+
+    /// Returns a channel which can be used to ask this reporter to deliver all of its pending
+    /// events immediately, while it is running
+    pub fn flush_requests(&self) -> Sender<FlushRequest> {
+        self.flush_tx.clone()
+    }
+
+    /// Returns the count of events dropped by this reporter, which
+    /// [`TelemetrySender`](crate::TelemetrySender)s add the events they drop to
+    pub fn dropped_events(&self) -> DroppedEvents {
+        self.dropped_events.clone()
     }
 
     #[cfg(any(test, feature = "test-util"))]
@@ -317,61 +772,23 @@ impl TelemetryReporter {
     }
 }
 
-fn blake2b_string(user_id: String) -> String {
-    let mut hasher = Blake2bVar::new(8).expect("8 is a valid output size for Blake2bVar");
-    hasher.update(user_id.as_bytes());
-    let mut buf = [0u8; 8];
-    hasher
-        .finalize_variable(&mut buf)
-        .expect("8 is a valid output size for Blake2bVar");
-    hex::encode(&buf)
-}
-
-fn make_client(write_key: &str) -> Result<Client> {
-    let mut headers = HeaderMap::new();
-
-    // Authenticate using HTTP Basic Auth
-    // Username is the Segment write key, password is empty
-    // See: https://segment.com/docs/connections/sources/catalog/libraries/server/http-api/#authentication
-    headers.insert(AUTHORIZATION, {
-        // Append a colon and encode as base64
-        let write_key = base64::encode(format!("{write_key}:"));
-        let mut authorization = HeaderValue::from_str(&format!("Basic {write_key}"))
-            .map_err(Error::InvalidAPIKeyHeader)?;
-        authorization.set_sensitive(true);
-        authorization
-    });
-
-    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-
-    Ok(Client::builder()
-        .default_headers(headers)
-        .user_agent(APP_USER_AGENT)
-        .build()?)
-}
-
-pub async fn handle_resp(resp: Response) -> Result<()> {
-    match resp.status() {
-        status if status.is_success() => Ok(()),
-        status if status.is_server_error() => Err(Error::Server(resp.text().await?)),
-        StatusCode::UNAUTHORIZED => Err(Error::Unauthorized),
-        status => Err(Error::HTTPError {
-            status,
-            body: resp.text().await?,
-        }),
-    }
-}
-
 #[cfg(test)]
 mod tests {
 
+    use reqwest::StatusCode;
+
     use super::*;
+    use crate::error::ReporterError as Error;
     use crate::*;
 
     struct TestPeriodicReporter {}
 
     #[async_trait]
     impl PeriodicReport for TestPeriodicReporter {
+        fn name(&self) -> &str {
+            "test"
+        }
+
         async fn report(&self) -> Result<Vec<(TelemetryEvent, Telemetry)>> {
             Ok(vec![(
                 TelemetryEvent::QueryParseFailed,
@@ -390,8 +807,7 @@ mod tests {
 
         telemetry_sender.send_event(event).unwrap();
 
-        let mut interval = tokio::time::interval(Duration::from_millis(10));
-        telemetry_reporter.run_once(&mut interval).await;
+        telemetry_reporter.run_once().await;
         assert_eq!(
             telemetry,
             *telemetry_reporter
@@ -402,7 +818,7 @@ mod tests {
         );
 
         telemetry_sender.shutdown().await;
-        telemetry_reporter.run_once(&mut interval).await;
+        telemetry_reporter.run_once().await;
 
         telemetry_sender.send_event(event).unwrap_err();
     }
@@ -415,8 +831,7 @@ mod tests {
             .register_periodic_reporter(test_periodic_reporter)
             .await;
 
-        let mut interval = tokio::time::interval(Duration::from_nanos(1));
-        reporter.run_once(&mut interval).await;
+        reporter.run_once().await;
 
         assert_eq!(
             1,
@@ -427,6 +842,151 @@ mod tests {
         );
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn toggle_periodic_reporter() {
+        let (_sender, mut reporter) = TelemetryInitializer::test_init();
+        reporter
+            .register_periodic_reporter(Arc::new(TestPeriodicReporter {}))
+            .await;
+        let periodic_reporters = reporter.periodic_reporters();
+
+        reporter.run_once().await;
+        assert_eq!(
+            reporter
+                .check_event(TelemetryEvent::QueryParseFailed)
+                .await
+                .len(),
+            1
+        );
+
+        assert!(periodic_reporters.set_enabled("test", false).await);
+        assert_eq!(
+            periodic_reporters.list().await,
+            vec![PeriodicReporterStatus {
+                name: "test".into(),
+                enabled: false
+            }]
+        );
+        reporter.run_once().await;
+        assert_eq!(
+            reporter
+                .check_event(TelemetryEvent::QueryParseFailed)
+                .await
+                .len(),
+            1
+        );
+
+        assert!(periodic_reporters.set_enabled("test", true).await);
+        reporter.run_once().await;
+        assert_eq!(
+            reporter
+                .check_event(TelemetryEvent::QueryParseFailed)
+                .await
+                .len(),
+            2
+        );
+
+        assert!(!periodic_reporters.set_enabled("nonexistent", false).await);
+    }
+
+    /// A periodic reporter which reports a single event on its own interval
+    struct IntervalReporter {
+        event: TelemetryEvent,
+        interval: Duration,
+    }
+
+    #[async_trait]
+    impl PeriodicReport for IntervalReporter {
+        fn name(&self) -> &str {
+            "interval"
+        }
+
+        async fn report(&self) -> Result<Vec<(TelemetryEvent, Telemetry)>> {
+            Ok(vec![(self.event, Telemetry::default())])
+        }
+
+        fn interval(&self) -> Duration {
+            self.interval
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn periodic_reporters_run_on_independent_intervals() {
+        let (_sender, mut reporter) = TelemetryInitializer::test_init();
+        reporter
+            .register_periodic_reporter(Arc::new(IntervalReporter {
+                event: TelemetryEvent::QueryParseFailed,
+                interval: Duration::from_secs(10),
+            }))
+            .await;
+        reporter
+            .register_periodic_reporter(Arc::new(IntervalReporter {
+                event: TelemetryEvent::ProxiedQuery,
+                interval: Duration::from_secs(60),
+            }))
+            .await;
+
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(30) {
+            reporter.run_once().await;
+        }
+
+        assert_eq!(
+            reporter
+                .check_event(TelemetryEvent::QueryParseFailed)
+                .await
+                .len(),
+            4
+        );
+        assert_eq!(
+            reporter
+                .check_event(TelemetryEvent::ProxiedQuery)
+                .await
+                .len(),
+            1
+        );
+    }
+
+    /// A periodic reporter whose reports never finish
+    struct StuckReporter;
+
+    #[async_trait]
+    impl PeriodicReport for StuckReporter {
+        fn name(&self) -> &str {
+            "stuck"
+        }
+
+        async fn report(&self) -> Result<Vec<(TelemetryEvent, Telemetry)>> {
+            futures::future::pending().await
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stuck_periodic_reporter_times_out() {
+        let (sender, mut reporter) = TelemetryInitializer::test_init();
+        reporter
+            .register_periodic_reporter(Arc::new(StuckReporter))
+            .await;
+        reporter
+            .register_periodic_reporter(Arc::new(TestPeriodicReporter {}))
+            .await;
+
+        let start = Instant::now();
+        reporter.run_once().await;
+        assert_eq!(start.elapsed(), DEFAULT_PERIODIC_REPORT_TIMEOUT);
+        assert_eq!(
+            reporter
+                .check_event(TelemetryEvent::QueryParseFailed)
+                .await
+                .len(),
+            1
+        );
+
+        // The reporter is still responsive once the stuck report has been abandoned
+        sender.shutdown().await;
+        assert!(!reporter.run_once().await);
+    }
+
     #[tokio::test(start_paused = true)]
     async fn test_shutdown_drain() {
         // Tests that the TelemetryReporter will drain any incoming requests
@@ -439,8 +999,7 @@ mod tests {
             .expect("failed to send event");
         sender.shutdown().await;
 
-        let mut interval = tokio::time::interval(Duration::from_secs(1));
-        reporter.run_once(&mut interval).await;
+        reporter.run_once().await;
 
         assert_eq!(
             1,
@@ -451,12 +1010,585 @@ mod tests {
         );
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn rate_limited_events_are_summarized() {
+        let (sender, mut reporter) = TelemetryInitializer::test_init();
+        reporter.set_rate_limits(HashMap::from([(
+            TelemetryEvent::ProxiedQuery,
+            Quota {
+                max_events: 2,
+                per: Duration::from_secs(60),
+            },
+        )]));
+
+        for _ in 0..5 {
+            sender.send_event(TelemetryEvent::ProxiedQuery).unwrap();
+        }
+        sender.shutdown().await;
+        reporter.run_once().await;
+
+        assert_eq!(
+            reporter
+                .check_event(TelemetryEvent::ProxiedQuery)
+                .await
+                .len(),
+            2
+        );
+        assert_eq!(
+            reporter.check_event(TelemetryEvent::RateLimited).await,
+            vec![TelemetryBuilder::new()
+                .rate_limited_event("proxied_query")
+                .suppressed_events(3u64)
+                .build()]
+        );
+    }
+
+    /// A periodic reporter which produces a fixed number of events each time it runs
+    struct ManyEventsReporter {
+        events: usize,
+        limit: ReportLimit,
+    }
+
+    #[async_trait]
+    impl PeriodicReport for ManyEventsReporter {
+        fn name(&self) -> &str {
+            "many"
+        }
+
+        async fn report(&self) -> Result<Vec<(TelemetryEvent, Telemetry)>> {
+            Ok((0..self.events)
+                .map(|i| {
+                    (
+                        TelemetryEvent::ProxiedQuery,
+                        TelemetryBuilder::new().query_id(format!("q{i}")).build(),
+                    )
+                })
+                .collect())
+        }
+
+        fn report_limit(&self) -> ReportLimit {
+            self.limit
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn periodic_report_truncated_to_limit() {
+        let (_sender, mut reporter) = TelemetryInitializer::test_init();
+        reporter
+            .register_periodic_reporter(Arc::new(ManyEventsReporter {
+                events: 5,
+                limit: ReportLimit {
+                    max_events: Some(3),
+                    max_bytes: None,
+                },
+            }))
+            .await;
+
+        reporter.run_once().await;
+
+        let sent_query_ids = |sent: Vec<Telemetry>| {
+            sent.into_iter()
+                .map(|t| t.query_id.unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            sent_query_ids(reporter.check_event(TelemetryEvent::ProxiedQuery).await),
+            vec!["q0", "q1", "q2"]
+        );
+
+        // The events which didn't fit are sent first in the next report
+        reporter.test_run_periodic_reports().await;
+        assert_eq!(
+            sent_query_ids(reporter.check_event(TelemetryEvent::ProxiedQuery).await),
+            vec!["q0", "q1", "q2", "q3", "q4", "q0"]
+        );
+        assert!(reporter
+            .check_event(TelemetryEvent::PeriodicReportTruncated)
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn periodic_report_overflow_beyond_carry_over_is_dropped() {
+        let (_sender, mut reporter) = TelemetryInitializer::test_init();
+        reporter
+            .register_periodic_reporter(Arc::new(ManyEventsReporter {
+                events: MAX_CARRIED_OVER_EVENTS + 10,
+                limit: ReportLimit {
+                    max_events: Some(3),
+                    max_bytes: None,
+                },
+            }))
+            .await;
+
+        reporter.run_once().await;
+
+        assert_eq!(
+            reporter
+                .check_event(TelemetryEvent::ProxiedQuery)
+                .await
+                .len(),
+            3
+        );
+        assert_eq!(
+            reporter
+                .check_event(TelemetryEvent::PeriodicReportTruncated)
+                .await,
+            vec![TelemetryBuilder::new()
+                .periodic_reporter("many")
+                .truncated_events(7u64)
+                .build()]
+        );
+    }
+
     #[test]
-    fn validate_deployment_env() {
-        std::env::set_var("DEPLOYMENT_ENV", "!@#$deployment!@#$_env!@_0.1#$");
-        let mut s = "deployment_env_0.1".to_owned();
-        s.truncate(DEPLOYMENT_ENV_LEN_MAX);
-        let (_, reporter) = TelemetryInitializer::test_init();
-        assert_eq!(reporter.deployment_env, s);
+    fn report_limit_max_bytes() {
+        let event_size = serde_json::to_vec(&TelemetryBuilder::new().query_id("q0").build())
+            .unwrap()
+            .len();
+        let mut report = (0..5)
+            .map(|i| {
+                (
+                    TelemetryEvent::ProxiedQuery,
+                    TelemetryBuilder::new().query_id(format!("q{i}")).build(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let limit = ReportLimit {
+            max_events: None,
+            max_bytes: Some(event_size * 2 + 1),
+        };
+        assert_eq!(limit.truncate(&mut report).len(), 3);
+        assert_eq!(report.len(), 2);
+
+        assert!(ReportLimit::default().truncate(&mut report).is_empty());
+        assert_eq!(report.len(), 2);
+    }
+
+    #[test]
+    fn report_limit_sends_oversized_event() {
+        let mut report = (0..3)
+            .map(|i| {
+                (
+                    TelemetryEvent::ProxiedQuery,
+                    TelemetryBuilder::new().query_id(format!("q{i}")).build(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let limit = ReportLimit {
+            max_events: None,
+            max_bytes: Some(1),
+        };
+        // Each report sends one event, so the carried over events still drain
+        assert_eq!(limit.truncate(&mut report).len(), 2);
+        assert_eq!(report.len(), 1);
+
+        let limit = ReportLimit {
+            max_events: Some(0),
+            max_bytes: Some(1),
+        };
+        assert_eq!(limit.truncate(&mut report).len(), 1);
+        assert!(report.is_empty());
+    }
+
+    /// A transport which records the events and batches it's sent, optionally failing every send
+    /// as if the endpoint responded with the given HTTP status
+    struct MockTransport {
+        failure: Option<StatusCode>,
+        sent: Mutex<Vec<TelemetryEvent>>,
+        batches: Mutex<Vec<Vec<(TelemetryEvent, Telemetry)>>>,
+    }
+
+    impl MockTransport {
+        fn new(failure: Option<StatusCode>) -> Arc<Self> {
+            Arc::new(Self {
+                failure,
+                sent: Default::default(),
+                batches: Default::default(),
+            })
+        }
+
+        fn result(&self) -> Result<()> {
+            match self.failure {
+                Some(status) => Err(Error::from_status(status, None, "failed".into())),
+                None => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl TelemetryTransport for MockTransport {
+        fn name(&self) -> &str {
+            if self.failure.is_some() {
+                "failing"
+            } else {
+                "working"
+            }
+        }
+
+        async fn send(&self, event: TelemetryEvent, _payload: &Telemetry) -> Result<()> {
+            self.sent.lock().await.push(event);
+            self.result()
+        }
+
+        async fn send_batch(&self, events: &[(TelemetryEvent, Telemetry)]) -> Result<()> {
+            self.batches.lock().await.push(events.to_vec());
+            self.result()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fan_out_to_multiple_transports() {
+        let (sender, mut reporter) = TelemetryInitializer::test_init();
+        let working = MockTransport::new(None);
+        let failing = MockTransport::new(Some(StatusCode::SERVICE_UNAVAILABLE));
+        reporter.add_transport(failing.clone());
+        reporter.add_transport(working.clone());
+
+        assert_eq!(
+            reporter
+                .send_to_transports(TelemetryEvent::InstallerRun, &Default::default())
+                .await,
+            1
+        );
+        assert_eq!(
+            *working.sent.lock().await,
+            vec![TelemetryEvent::InstallerRun]
+        );
+        // Server errors are retried
+        assert!(failing.sent.lock().await.len() > 1);
+
+        sender.send_event(TelemetryEvent::AdapterStart).unwrap();
+        reporter.run_once().await;
+        assert_eq!(
+            *working.sent.lock().await,
+            vec![TelemetryEvent::InstallerRun, TelemetryEvent::AdapterStart]
+        );
+        assert_eq!(
+            failing.sent.lock().await.last(),
+            Some(&TelemetryEvent::AdapterStart)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn only_retriable_transport_errors_are_retried() {
+        let max_attempts = RetryPolicy::default().max_attempts as usize;
+        for (status, attempts) in [
+            (StatusCode::BAD_REQUEST, 1),
+            (StatusCode::UNAUTHORIZED, 1),
+            (StatusCode::FORBIDDEN, 1),
+            (StatusCode::INTERNAL_SERVER_ERROR, max_attempts),
+            (StatusCode::SERVICE_UNAVAILABLE, max_attempts),
+        ] {
+            let (_sender, mut reporter) = TelemetryInitializer::test_init();
+            let transport = MockTransport::new(Some(status));
+            reporter.add_transport(transport.clone());
+
+            assert_eq!(
+                reporter
+                    .send_to_transports(TelemetryEvent::InstallerRun, &Default::default())
+                    .await,
+                0
+            );
+            assert_eq!(transport.sent.lock().await.len(), attempts, "{status}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn permanently_rejected_batches_are_dropped_without_retrying() {
+        for (status, attempts) in [
+            (StatusCode::UNAUTHORIZED, 1),
+            (StatusCode::UNPROCESSABLE_ENTITY, 1),
+            (
+                StatusCode::BAD_GATEWAY,
+                RetryPolicy::default().max_attempts as usize,
+            ),
+        ] {
+            let (sender, mut reporter, transport) = batching_reporter_with_transport(
+                BatchConfig::default(),
+                MockTransport::new(Some(status)),
+            );
+
+            sender.send_event(TelemetryEvent::AdapterStart).unwrap();
+            reporter.run_once().await;
+            reporter.flush_batch().await;
+            assert_eq!(transport.batches.lock().await.len(), attempts, "{status}");
+            assert_eq!(reporter.delivered_events.load(Ordering::Relaxed), 0);
+
+            // The dropped batch isn't sent again with the next one
+            sender.send_event(TelemetryEvent::InstallerRun).unwrap();
+            reporter.run_once().await;
+            reporter.flush_batch().await;
+            assert_eq!(
+                batch_events(&transport).await.last(),
+                Some(&vec![TelemetryEvent::InstallerRun])
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn graceful_shutdown_summarizes_delivered_and_dropped_events() {
+        for (failure, expected) in [
+            (
+                None,
+                ShutdownSummary {
+                    delivered: 5,
+                    dropped: 0,
+                },
+            ),
+            (
+                Some(StatusCode::BAD_REQUEST),
+                ShutdownSummary {
+                    delivered: 0,
+                    dropped: 5,
+                },
+            ),
+        ] {
+            let (sender, mut reporter, _transport) = batching_reporter_with_transport(
+                BatchConfig {
+                    max_events: 2,
+                    max_interval: Duration::from_secs(3600),
+                },
+                MockTransport::new(failure),
+            );
+
+            // The first two events are sent in a full batch before shutting down, and the rest
+            // while shutting down
+            for _ in 0..5 {
+                sender.send_event(TelemetryEvent::ProxiedQuery).unwrap();
+            }
+            reporter.run_once().await;
+            reporter.run_once().await;
+
+            let reporter = tokio::spawn(async move { reporter.run().await });
+            assert_eq!(
+                sender
+                    .graceful_shutdown(Duration::from_secs(1))
+                    .await
+                    .unwrap(),
+                expected
+            );
+            reporter.await.unwrap();
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn graceful_shutdown_counts_shed_events() {
+        let (sender, mut reporter, _transport) =
+            batching_reporter_with_transport(BatchConfig::default(), MockTransport::new(None));
+        reporter.set_rate_limits(HashMap::from([(
+            TelemetryEvent::ProxiedQuery,
+            Quota {
+                max_events: 2,
+                per: Duration::from_secs(60),
+            },
+        )]));
+
+        // Three of these exceed the rate limit
+        for _ in 0..5 {
+            sender.send_event(TelemetryEvent::ProxiedQuery).unwrap();
+        }
+        // Fill up the rest of the queue, then overflow it by two events
+        for _ in 5..TELEMETRY_CHANNEL_LEN {
+            sender.send_event(TelemetryEvent::InstallerRun).unwrap();
+        }
+        for _ in 0..2 {
+            assert_eq!(
+                sender.send_event(TelemetryEvent::InstallerRun),
+                Err(TelemetrySendError::QueueFull)
+            );
+        }
+
+        let reporter = tokio::spawn(async move { reporter.run().await });
+        assert_eq!(
+            sender
+                .graceful_shutdown(Duration::from_secs(1))
+                .await
+                .unwrap(),
+            ShutdownSummary {
+                // Every event which fit in the queue and within the rate limit, plus the summary
+                // of the rate limited events
+                delivered: TELEMETRY_CHANNEL_LEN - 3 + 1,
+                dropped: 3 + 2,
+            }
+        );
+        reporter.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn graceful_shutdown_times_out() {
+        let (sender, _reporter, _transport) = batching_reporter(BatchConfig::default());
+        assert!(matches!(
+            sender.graceful_shutdown(Duration::from_secs(1)).await,
+            Err(Error::Timeout(_))
+        ));
+    }
+
+    fn batching_reporter(
+        batch_config: BatchConfig,
+    ) -> (
+        TelemetrySender,
+        TelemetryReporter<Arc<MockTransport>>,
+        Arc<MockTransport>,
+    ) {
+        batching_reporter_with_transport(batch_config, MockTransport::new(None))
+    }
+
+    fn batching_reporter_with_transport(
+        batch_config: BatchConfig,
+        transport: Arc<MockTransport>,
+    ) -> (
+        TelemetrySender,
+        TelemetryReporter<Arc<MockTransport>>,
+        Arc<MockTransport>,
+    ) {
+        let (tx, rx) = tokio::sync::mpsc::channel(TELEMETRY_CHANNEL_LEN);
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let (shutdown_ack_tx, shutdown_ack_rx) = oneshot::channel();
+        let reporter = TelemetryReporter::with_transport(
+            rx,
+            transport.clone(),
+            shutdown_rx,
+            shutdown_ack_tx,
+            RetryPolicy::default(),
+            batch_config,
+        );
+        let sender = TelemetrySender::new(
+            tx,
+            shutdown_tx,
+            shutdown_ack_rx,
+            reporter.periodic_reporters(),
+            reporter.flush_requests(),
+            reporter.dropped_events(),
+        );
+        (sender, reporter, transport)
+    }
+
+    /// Returns the events in each batch sent to `transport`
+    async fn batch_events(transport: &MockTransport) -> Vec<Vec<TelemetryEvent>> {
+        transport
+            .batches
+            .lock()
+            .await
+            .iter()
+            .map(|batch| batch.iter().map(|(event, _)| *event).collect())
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn full_batches_are_sent_and_partial_batch_flushed_on_shutdown() {
+        let (sender, mut reporter, transport) = batching_reporter(BatchConfig {
+            max_events: 3,
+            max_interval: Duration::from_secs(3600),
+        });
+
+        for _ in 0..7 {
+            sender.send_event(TelemetryEvent::ProxiedQuery).unwrap();
+            reporter.run_once().await;
+        }
+        assert_eq!(
+            batch_events(&transport).await,
+            vec![vec![TelemetryEvent::ProxiedQuery; 3]; 2]
+        );
+
+        sender.shutdown().await;
+        assert!(!reporter.run_once().await);
+        assert_eq!(
+            batch_events(&transport).await.last(),
+            Some(&vec![TelemetryEvent::ProxiedQuery])
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn partial_batch_sent_after_interval() {
+        let (sender, mut reporter, transport) = batching_reporter(BatchConfig {
+            max_events: 100,
+            max_interval: Duration::from_secs(1),
+        });
+
+        sender.send_event(TelemetryEvent::AdapterStart).unwrap();
+        sender.send_event(TelemetryEvent::InstallerRun).unwrap();
+        reporter.run_once().await;
+        reporter.run_once().await;
+        assert!(batch_events(&transport).await.is_empty());
+
+        let start = Instant::now();
+        reporter.run_once().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert_eq!(
+            batch_events(&transport).await,
+            vec![vec![
+                TelemetryEvent::AdapterStart,
+                TelemetryEvent::InstallerRun
+            ]]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn custom_transport_receives_payloads() {
+        let (sender, mut reporter, transport) = batching_reporter(BatchConfig::default());
+
+        sender
+            .send_event_with_payload(
+                TelemetryEvent::QueryParseFailed,
+                TelemetryBuilder::new().query_id("q1").build(),
+            )
+            .unwrap();
+        reporter.run_once().await;
+        sender.shutdown().await;
+        reporter.run_once().await;
+
+        assert_eq!(
+            *transport.batches.lock().await,
+            vec![vec![(
+                TelemetryEvent::QueryParseFailed,
+                TelemetryBuilder::new().query_id("q1").build()
+            )]]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn flush_delivers_pending_events() {
+        let (sender, mut reporter, transport) = batching_reporter(BatchConfig {
+            max_events: 2,
+            max_interval: Duration::from_secs(3600),
+        });
+        let reporter = tokio::spawn(async move { reporter.run().await });
+
+        for _ in 0..3 {
+            sender.send_event(TelemetryEvent::ProxiedQuery).unwrap();
+        }
+        assert_eq!(sender.flush(Duration::from_secs(1)).await.unwrap(), 3);
+        assert_eq!(
+            batch_events(&transport).await,
+            vec![
+                vec![TelemetryEvent::ProxiedQuery; 2],
+                vec![TelemetryEvent::ProxiedQuery]
+            ]
+        );
+
+        // Flushing again with nothing pending is fine, and the reporter keeps running
+        assert_eq!(sender.flush(Duration::from_secs(1)).await.unwrap(), 0);
+        sender.send_event(TelemetryEvent::AdapterStart).unwrap();
+        assert_eq!(sender.flush(Duration::from_secs(1)).await.unwrap(), 1);
+
+        sender
+            .graceful_shutdown(Duration::from_secs(1))
+            .await
+            .unwrap();
+        reporter.await.unwrap();
+        assert!(matches!(
+            sender.flush(Duration::from_secs(1)).await,
+            Err(Error::NotRunning) | Err(Error::Timeout(_))
+        ));
+        assert_eq!(
+            TelemetrySender::new_no_op()
+                .flush(Duration::from_secs(1))
+                .await
+                .unwrap(),
+            0
+        );
     }
 }
@@ -0,0 +1,274 @@
+//! Retrying of failed telemetry sends.
+//!
+//! Each attempt to send a telemetry event which fails for a transient reason (a network error, a
+//! 5XX, 429, or 408 response, or a timeout) is retried after an exponentially increasing, randomly
+//! jittered, delay, as configured by a [`RetryPolicy`]. If the endpoint asked for the send to be
+//! retried later with a `Retry-After` header, we wait at least that long, unless it's longer than
+//! [`MAX_RETRY_AFTER`]. Events which still can't be sent after the configured number of attempts
+//! or within the configured total time, or which fail for a permanent reason, are dropped.
+
+use std::future::Future;
+use std::time::Duration;
+
+use readyset_tracing::debug;
+use readyset_util::backoff::Backoff;
+use tokio::time::Instant;
+
+use crate::error::ReporterResult as Result;
+
+/// Maximum time to wait for a single attempt to send a telemetry payload before giving up on that
+/// attempt
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The longest `Retry-After` delay we'll wait before retrying a send. If the endpoint asks us to
+/// wait longer than this, the send is given up on instead, rather than holding up the events
+/// queued behind it.
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Configures how failed telemetry sends are retried
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// The delay before the first retry. Each subsequent delay is double the previous one, up to
+    /// [`max_delay`](RetryPolicy::max_delay)
+    pub base_delay: Duration,
+    /// The maximum delay between two attempts
+    pub max_delay: Duration,
+    /// The maximum number of attempts to send each event, including the first
+    pub max_attempts: u32,
+    /// The maximum total time to spend sending each event, including every attempt and the delays
+    /// between them. Since events are sent one at a time, this bounds how long a single
+    /// unreachable endpoint can hold up the events queued behind it.
+    pub max_elapsed: Duration,
+    /// The maximum fraction of each delay by which it may be randomly reduced, so that many
+    /// reporters which fail at the same time don't all retry at the same time. Must be between 0
+    /// and 1.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(2),
+            max_attempts: 4,
+            max_elapsed: Duration::from_secs(2),
+            jitter: 0.5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy which never retries failed sends
+    pub fn no_retries() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Run `send` until it succeeds, retrying it according to this policy if it fails for a
+    /// [retriable](crate::ReporterError::is_retriable) reason, and returning the last error if
+    /// every attempt fails or [`max_elapsed`](RetryPolicy::max_elapsed) runs out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`jitter`](RetryPolicy::jitter) is not between 0 and 1
+    pub(crate) async fn retry<F, Fut>(&self, send: F) -> Result<()>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let mut delays = Backoff::new(self.base_delay)
+            .max(self.max_delay)
+            .jitter(self.jitter);
+        let deadline = Instant::now() + self.max_elapsed;
+        let mut attempt = 1;
+        loop {
+            let attempt_deadline = deadline.min(Instant::now() + ATTEMPT_TIMEOUT);
+            let res = match tokio::time::timeout_at(attempt_deadline, send()).await {
+                Ok(res) => res,
+                Err(elapsed) => Err(elapsed.into()),
+            };
+            match res {
+                Err(error) if error.is_retriable() && attempt < self.max_attempts => {
+                    let mut delay = delays.next().unwrap_or(self.max_delay);
+                    if let Some(retry_after) = error.retry_after() {
+                        if retry_after > MAX_RETRY_AFTER {
+                            debug!(%error, ?retry_after, "not retrying telemetry send");
+                            return Err(error);
+                        }
+                        delay = delay.max(retry_after);
+                    }
+                    if Instant::now() + delay >= deadline {
+                        debug!(%error, ?delay, "out of time to retry telemetry send");
+                        return Err(error);
+                    }
+                    debug!(%error, %attempt, ?delay, "failed to send telemetry, retrying");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use reqwest::StatusCode;
+    use tokio::sync::Mutex;
+    use tokio::time::Instant;
+
+    use super::*;
+    use crate::error::ReporterError as Error;
+
+    /// Returns a send function which fails with a server error `failures` times before
+    /// succeeding, recording the time of each attempt in `attempts`
+    fn flaky_send<'a>(
+        failures: u32,
+        attempts: &'a Mutex<Vec<Instant>>,
+    ) -> impl Fn() -> std::pin::Pin<Box<dyn Future<Output = Result<()>> + 'a>> + 'a {
+        let remaining = AtomicU32::new(failures);
+        move || {
+            let failed = remaining
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                .is_ok();
+            Box::pin(async move {
+                attempts.lock().await.push(Instant::now());
+                if failed {
+                    Err(Error::Server("unavailable".into()))
+                } else {
+                    Ok(())
+                }
+            })
+        }
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(250),
+            max_attempts: 4,
+            max_elapsed: Duration::from_secs(60),
+            jitter: 0.0,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_with_exponential_backoff() {
+        let attempts = Mutex::new(vec![]);
+        policy().retry(flaky_send(3, &attempts)).await.unwrap();
+
+        let attempts = attempts.into_inner();
+        let delays = attempts.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(100),
+                Duration::from_millis(200),
+                Duration::from_millis(250)
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_max_attempts() {
+        let attempts = Mutex::new(vec![]);
+        policy().retry(flaky_send(10, &attempts)).await.unwrap_err();
+        assert_eq!(attempts.into_inner().len(), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_max_elapsed() {
+        let attempts = Mutex::new(vec![]);
+        RetryPolicy {
+            max_elapsed: Duration::from_millis(300),
+            ..policy()
+        }
+        .retry(flaky_send(10, &attempts))
+        .await
+        .unwrap_err();
+        // The third attempt would have been made at 300ms
+        assert_eq!(attempts.into_inner().len(), 2);
+
+        // A send which never completes is cut off at the deadline, rather than after a whole
+        // attempt timeout
+        let start = Instant::now();
+        RetryPolicy {
+            max_elapsed: Duration::from_millis(500),
+            ..policy()
+        }
+        .retry(futures::future::pending)
+        .await
+        .unwrap_err();
+        assert_eq!(Instant::now() - start, Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn does_not_retry_permanent_errors() {
+        let attempts = AtomicU32::new(0);
+        let res = policy()
+            .retry(|| async {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                Err(Error::Unauthorized)
+            })
+            .await;
+        assert!(matches!(res, Err(Error::Unauthorized)));
+        assert_eq!(attempts.into_inner(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn honours_retry_after() {
+        let send = |retry_after| {
+            let attempts = Mutex::new(vec![]);
+            async move {
+                let res = policy()
+                    .retry(|| async {
+                        let mut attempts = attempts.lock().await;
+                        attempts.push(Instant::now());
+                        if attempts.len() == 1 {
+                            Err(Error::from_status(
+                                StatusCode::TOO_MANY_REQUESTS,
+                                Some(retry_after),
+                                String::new(),
+                            ))
+                        } else {
+                            Ok(())
+                        }
+                    })
+                    .await;
+                (res, attempts.into_inner())
+            }
+        };
+
+        let (res, attempts) = send(Duration::from_secs(3)).await;
+        res.unwrap();
+        assert_eq!(attempts[1] - attempts[0], Duration::from_secs(3));
+
+        // Waiting this long would hold up every other event, so we give up instead
+        let (res, attempts) = send(MAX_RETRY_AFTER * 2).await;
+        assert!(matches!(res, Err(Error::RetryLater { .. })));
+        assert_eq!(attempts.len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn jittered_delays_stay_within_bounds() {
+        let attempts = Mutex::new(vec![]);
+        RetryPolicy {
+            jitter: 0.5,
+            ..policy()
+        }
+        .retry(flaky_send(3, &attempts))
+        .await
+        .unwrap();
+
+        let attempts = attempts.into_inner();
+        for (w, max) in attempts.windows(2).zip([100, 200, 250]) {
+            let delay = w[1] - w[0];
+            assert!(delay <= Duration::from_millis(max), "{delay:?}");
+            assert!(delay >= Duration::from_millis(max / 2), "{delay:?}");
+        }
+    }
+}
@@ -0,0 +1,663 @@
+//! Delivery of telemetry events to ReadySet's Segment source, the default destination of a
+//! [`TelemetryReporter`](crate::TelemetryReporter).
+//!
+//! Events are sent to Segment's HTTP API, in batches, using
+//! [`SegmentTransport::send_batch`](crate::TelemetryTransport::send_batch).
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use blake2::digest::{Update, VariableOutput};
+use blake2::Blake2bVar;
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use readyset_tracing::{info, warn};
+use readyset_version::COMMIT_ID;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER,
+};
+use reqwest::{Client, RequestBuilder, Response, Url};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::error::{ReporterError as Error, ReporterResult as Result};
+use crate::telemetry::*;
+use crate::transport::TelemetryTransport;
+
+/// User agent to use for all telemetry payload requests
+const APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
+
+/// URL to report telemetry to, unless overridden with a custom endpoint
+const TELEMETRY_BASE_URL: &str = "https://api.segment.io/v1/";
+
+/// Length to which DEPLOYMENT_ENV will be truncated
+const DEPLOYMENT_ENV_LEN_MAX: usize = 20;
+
+/// Header containing the HMAC signature of the request body, if an HMAC secret was configured
+const SIGNATURE_HEADER: HeaderName = HeaderName::from_static("x-signature");
+
+/// Silently succeed if the client is None.
+macro_rules! client {
+    ($self: expr) => {
+        if let Some(client) = &$self.client {
+            client
+        } else {
+            return Ok(());
+        }
+    };
+}
+
+lazy_static! {
+    /// Identifies the ReadySet Segment source. Common between all users.
+    /// In priority order, the value is:
+    /// - `RS_SEGMENT_WRITE_KEY` from the run-time environment
+    /// - `RS_SEGMENT_WRITE_KEY` from the compile-time environment
+    /// - `None`
+    ///
+    /// If `None`, no-op telemetry reporters will be created, which do not send HTTP requests.
+    ///
+    /// If `Some` but the key doesn't correspond to a valid Segment source, HTTP requests will be
+    /// sent and silently ignored.
+    static ref SEGMENT_WRITE_KEY: Option<String> = {
+        std::env::var("RS_SEGMENT_WRITE_KEY")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .or_else(|| option_env!("RS_SEGMENT_WRITE_KEY").map(str::to_owned))
+    };
+}
+
+/// Parse and validate a custom endpoint to send telemetry to in place of ReadySet's Segment source,
+/// such as a self-hosted relay. The endpoint must be an `http` or `https` URL, and is used as the
+/// base URL of Segment's HTTP API.
+pub fn parse_endpoint(s: &str) -> Result<Url> {
+    let url = Url::parse(s).map_err(|e| Error::InvalidEndpoint(format!("{s}: {e}")))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(Error::InvalidEndpoint(format!(
+            "{s}: scheme must be http or https"
+        )));
+    }
+    if url.host().is_none() {
+        return Err(Error::InvalidEndpoint(format!("{s}: missing host")));
+    }
+    Ok(url)
+}
+
+/// Sends telemetry events to ReadySet's Segment source over HTTP.
+///
+/// Test builds never send any requests to Segment.
+pub struct SegmentTransport {
+    client: Option<Client>,
+
+    /// Base URL of the Segment HTTP API, to which the path of each request is appended
+    endpoint: Url,
+
+    /// Whether `endpoint` is a custom endpoint, rather than ReadySet's Segment source, in which
+    /// case requests aren't authenticated with a Segment write key
+    custom_endpoint: bool,
+
+    /// https://segment.com/docs/connections/spec/identify/#user-id
+    user_id: Option<String>,
+
+    /// A client authenticated with a second Segment write key, used to retry requests which are
+    /// rejected as unauthorized when sent with [`SEGMENT_WRITE_KEY`]. Used to rotate write keys:
+    /// the new key is configured as the primary key and the old one as the secondary key until the
+    /// new key is accepted.
+    secondary_client: Option<Client>,
+
+    /// Whether the last request was only accepted with the secondary write key, so we can log when
+    /// that changes
+    using_secondary_write_key: AtomicBool,
+
+    /// Per-session generated ID
+    /// https://segment.com/docs/connections/spec/identify/#anonymous-id
+    anonymous_id: String,
+
+    /// Deployment environment, e.g. container orchestrator framework, if any
+    deployment_env: String,
+
+    /// Deployment ID, to help differentiate between deployments with the same user ID.
+    /// (user_id, deployment_id) is not guaranteed to be unique, as they are both user-provided.
+    deployment_id: String,
+
+    /// If set, the body of each request is signed with an HMAC-SHA256 using this secret, and the
+    /// signature attached in the [`SIGNATURE_HEADER`] header
+    hmac_secret: Option<Vec<u8>>,
+}
+
+impl SegmentTransport {
+    pub fn new(
+        api_key: Option<String>,
+        deployment_id: String,
+        hmac_secret: Option<String>,
+        endpoint: Option<Url>,
+    ) -> Self {
+        // If the api_key is set, use that as the user_id.
+        // If not, try to get a machine uid. If that works, anonymize it by hashing it with blake2b,
+        // a cryptographically secure hashing library, and use that as the id, otherwise, no
+        // id will be set
+        // NOTE: The machine id may not be unique across all users, since there may be many virtual
+        // machines or corporate images that have the same machine id. Still, this is a decent
+        // heuristic for unique users
+        let user_id = api_key.or_else(|| machine_uid::get().ok().map(blake2b_string));
+
+        let custom_endpoint = endpoint.is_some();
+        // Make sure request paths are appended to the endpoint's path, rather than replacing its
+        // last segment
+        let mut endpoint = endpoint.unwrap_or_else(|| Url::parse(TELEMETRY_BASE_URL).unwrap());
+        if !endpoint.path().ends_with('/') {
+            endpoint.set_path(&format!("{}/", endpoint.path()));
+        }
+
+        Self {
+            client: if cfg!(any(test, feature = "test-util")) {
+                None
+            } else {
+                client_for_endpoint(custom_endpoint)
+            },
+            endpoint,
+            custom_endpoint,
+            user_id,
+            secondary_client: None,
+            using_secondary_write_key: AtomicBool::new(false),
+            anonymous_id: Uuid::new_v4().to_string(),
+            deployment_env: std::env::var("DEPLOYMENT_ENV")
+                .unwrap_or_default()
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || ['_', '.'].contains(c))
+                .take(DEPLOYMENT_ENV_LEN_MAX)
+                .collect(),
+            deployment_id,
+            hmac_secret: hmac_secret.map(String::into_bytes),
+        }
+    }
+
+    /// Configure a secondary Segment write key, to fall back to if requests authenticated with the
+    /// primary write key are rejected as unauthorized. Since the write key is never sent to custom
+    /// endpoints, this has no effect if one was configured.
+    pub fn with_secondary_write_key(mut self, secondary_write_key: Option<String>) -> Self {
+        self.secondary_client = match (&self.client, secondary_write_key) {
+            (Some(_), Some(write_key)) if !self.custom_endpoint => {
+                make_client(Some(&write_key)).ok()
+            }
+            _ => None,
+        };
+        self
+    }
+
+    /// Send a request by calling `send` with the client to send it with, retrying it with the
+    /// client authenticated with the secondary write key if it's rejected as unauthorized with the
+    /// primary one
+    async fn send_with_fallback<'a, F, Fut>(&'a self, send: F) -> Result<()>
+    where
+        F: Fn(&'a Client) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let client = client!(self);
+        let secondary_client = match (send(client).await, &self.secondary_client) {
+            (Err(Error::Unauthorized), Some(secondary_client)) => secondary_client,
+            (res, _) => {
+                if res.is_ok()
+                    && self
+                        .using_secondary_write_key
+                        .swap(false, Ordering::Relaxed)
+                {
+                    info!(
+                        "Primary Segment write key accepted; the secondary write key is no longer \
+                         being used"
+                    );
+                }
+                return res;
+            }
+        };
+
+        let res = send(secondary_client).await;
+        match &res {
+            Ok(()) => {
+                if !self.using_secondary_write_key.swap(true, Ordering::Relaxed) {
+                    warn!(
+                        "Primary Segment write key was rejected; falling back to the secondary \
+                         write key"
+                    );
+                }
+            }
+            Err(Error::Unauthorized) => {
+                if self
+                    .using_secondary_write_key
+                    .swap(false, Ordering::Relaxed)
+                {
+                    warn!("Secondary Segment write key is no longer accepted");
+                }
+            }
+            Err(_) => {}
+        }
+        res
+    }
+
+    /// Build a request to Segment's batch endpoint containing all of `events`, sent with the given
+    /// `user_id`
+    fn build_request<'a>(
+        &self,
+        client: &Client,
+        user_id: Option<&String>,
+        events: impl IntoIterator<Item = (TelemetryEvent, &'a Telemetry)>,
+    ) -> Result<RequestBuilder> {
+        let body = serde_json::to_vec(&Batch {
+            batch: events
+                .into_iter()
+                .map(|(event, telemetry)| {
+                    BatchMessage::Track(Track {
+                        user_id,
+                        anonymous_id: &self.anonymous_id,
+                        event,
+                        properties: Properties {
+                            telemetry,
+                            commit_id: COMMIT_ID,
+                            deployment_env: &self.deployment_env,
+                            deployment_id: &self.deployment_id,
+                        },
+                    })
+                })
+                .collect(),
+        })?;
+
+        let url = self
+            .endpoint
+            .join("batch")
+            .map_err(|e| Error::InvalidEndpoint(format!("{}: {e}", self.endpoint)))?;
+        let mut req = client.post(url);
+        if let Some(secret) = &self.hmac_secret {
+            req = req.header(SIGNATURE_HEADER, sign_payload(secret, &body));
+        }
+        Ok(req.body(body))
+    }
+}
+
+#[async_trait]
+impl TelemetryTransport for SegmentTransport {
+    fn name(&self) -> &str {
+        "segment"
+    }
+
+    async fn send(&self, event: TelemetryEvent, payload: &Telemetry) -> Result<()> {
+        self.send_with_fallback(|client| async move {
+            handle_resp(
+                self.build_request(client, self.user_id.as_ref(), [(event, payload)])?
+                    .send()
+                    .await?,
+            )
+            .await
+        })
+        .await
+    }
+
+    async fn send_batch(&self, events: &[(TelemetryEvent, Telemetry)]) -> Result<()> {
+        self.send_with_fallback(|client| async move {
+            handle_resp(
+                self.build_request(
+                    client,
+                    self.user_id.as_ref(),
+                    events.iter().map(|(e, t)| (*e, t)),
+                )?
+                .send()
+                .await?,
+            )
+            .await
+        })
+        .await
+    }
+}
+
+fn blake2b_string(user_id: String) -> String {
+    let mut hasher = Blake2bVar::new(8).expect("8 is a valid output size for Blake2bVar");
+    hasher.update(user_id.as_bytes());
+    let mut buf = [0u8; 8];
+    hasher
+        .finalize_variable(&mut buf)
+        .expect("8 is a valid output size for Blake2bVar");
+    hex::encode(&buf)
+}
+
+/// Returns the hex-encoded HMAC-SHA256 of `body`, keyed with `secret`
+fn sign_payload(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC can take a key of any size");
+    Mac::update(&mut mac, body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Build the client used to send telemetry. Requests to ReadySet's Segment source are
+/// authenticated with [`SEGMENT_WRITE_KEY`], and no client is built without one, but the write key
+/// is never sent to a custom endpoint.
+fn client_for_endpoint(custom_endpoint: bool) -> Option<Client> {
+    let write_key = if custom_endpoint {
+        None
+    } else {
+        Some(SEGMENT_WRITE_KEY.as_deref()?)
+    };
+    make_client(write_key).ok()
+}
+
+fn make_client(write_key: Option<&str>) -> Result<Client> {
+    Ok(Client::builder()
+        .default_headers(default_headers(write_key)?)
+        .user_agent(APP_USER_AGENT)
+        .build()?)
+}
+
+/// Headers sent with every request, authenticated with `write_key` if given
+fn default_headers(write_key: Option<&str>) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+
+    // Authenticate using HTTP Basic Auth
+    // Username is the Segment write key, password is empty
+    // See: https://segment.com/docs/connections/sources/catalog/libraries/server/http-api/#authentication
+    if let Some(write_key) = write_key {
+        headers.insert(AUTHORIZATION, {
+            // Append a colon and encode as base64
+            let write_key = base64::encode(format!("{write_key}:"));
+            let mut authorization = HeaderValue::from_str(&format!("Basic {write_key}"))
+                .map_err(Error::InvalidAPIKeyHeader)?;
+            authorization.set_sensitive(true);
+            authorization
+        });
+    }
+
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    Ok(headers)
+}
+
+pub async fn handle_resp(resp: Response) -> Result<()> {
+    match resp.status() {
+        status if status.is_success() => Ok(()),
+        status => {
+            let retry_after = resp.headers().get(RETRY_AFTER).and_then(parse_retry_after);
+            Err(Error::from_status(status, retry_after, resp.text().await?))
+        }
+    }
+}
+
+/// Parse the value of a `Retry-After` header. Only the delay-seconds form is supported; an HTTP
+/// date is ignored, and the send is retried on the usual schedule instead.
+fn parse_retry_after(value: &HeaderValue) -> Option<Duration> {
+    value
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    fn transport() -> SegmentTransport {
+        SegmentTransport::new(Some("api-key".into()), "deployment_id".into(), None, None)
+    }
+
+    #[test]
+    fn retry_after_header() {
+        assert_eq!(
+            parse_retry_after(&HeaderValue::from_static("120")),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after(&HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT")),
+            None
+        );
+    }
+
+    #[test]
+    fn signature_matches_known_vector() {
+        // Test case 2 from RFC 4231
+        assert_eq!(
+            sign_payload(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn request_signature_header() {
+        let mut transport = transport();
+        let client = make_client(Some("write_key")).unwrap();
+
+        let req = transport
+            .build_request(
+                &client,
+                transport.user_id.as_ref(),
+                [(TelemetryEvent::InstallerRun, &Default::default())],
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(req.headers().get(SIGNATURE_HEADER).is_none());
+
+        transport.hmac_secret = Some(b"secret".to_vec());
+        let req = transport
+            .build_request(
+                &client,
+                transport.user_id.as_ref(),
+                [(TelemetryEvent::InstallerRun, &Default::default())],
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        let body = req.body().unwrap().as_bytes().unwrap();
+        assert_eq!(
+            req.headers().get(SIGNATURE_HEADER).unwrap(),
+            sign_payload(b"secret", body).as_str()
+        );
+    }
+
+    #[test]
+    fn batch_request_body() {
+        let client = make_client(Some("write_key")).unwrap();
+        let telemetry = Telemetry::default();
+        let req = transport()
+            .build_request(
+                &client,
+                None,
+                [
+                    (TelemetryEvent::InstallerRun, &telemetry),
+                    (TelemetryEvent::AdapterStart, &telemetry),
+                ],
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(req.url().as_str(), "https://api.segment.io/v1/batch");
+
+        let body: serde_json::Value =
+            serde_json::from_slice(req.body().unwrap().as_bytes().unwrap()).unwrap();
+        let batch = body["batch"].as_array().unwrap();
+        assert_eq!(batch.len(), 2);
+        assert!(batch.iter().all(|message| message["type"] == "track"));
+    }
+
+    /// Serve the Segment HTTP API on a local port, accepting only requests authenticated with one
+    /// of the write keys in `accepted`, and recording the write key each request was sent with
+    async fn serve_segment(
+        accepted: Arc<Mutex<Vec<&'static str>>>,
+        sent: Arc<Mutex<Vec<String>>>,
+    ) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/v1/", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut req = vec![];
+                let mut buf = [0; 4096];
+                // Read the request head and body, which is sent as soon as the headers are
+                let head_len = loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    req.extend_from_slice(&buf[..n]);
+                    if let Some(i) = req.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break i + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&req[..head_len]).into_owned();
+                let header = |name: &str| {
+                    head.lines().find_map(|line| {
+                        let (key, value) = line.split_once(": ")?;
+                        key.eq_ignore_ascii_case(name).then(|| value.to_owned())
+                    })
+                };
+                let content_len: usize = header("content-length").unwrap().parse().unwrap();
+                while req.len() < head_len + content_len {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    req.extend_from_slice(&buf[..n]);
+                }
+
+                let write_key = header("authorization")
+                    .and_then(|auth| base64::decode(auth.strip_prefix("Basic ")?).ok())
+                    .map(|key| {
+                        String::from_utf8(key)
+                            .unwrap()
+                            .trim_end_matches(':')
+                            .to_owned()
+                    })
+                    .unwrap_or_default();
+                let status = if accepted.lock().unwrap().contains(&write_key.as_str()) {
+                    "200 OK"
+                } else {
+                    "401 Unauthorized"
+                };
+                sent.lock().unwrap().push(write_key);
+                stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        )
+                        .as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn secondary_write_key_fallback() {
+        let accepted = Arc::new(Mutex::new(vec!["old-write-key"]));
+        let sent = Arc::new(Mutex::new(vec![]));
+        let endpoint = serve_segment(accepted.clone(), sent.clone()).await;
+        let transport_with_write_keys = |primary: &str, secondary: Option<&str>| {
+            let mut transport = transport();
+            transport.endpoint = endpoint.clone();
+            transport.client = Some(make_client(Some(primary)).unwrap());
+            transport.with_secondary_write_key(secondary.map(str::to_owned))
+        };
+        let telemetry = Telemetry::default();
+
+        // The primary write key is rejected, so the request is retried with the secondary one
+        let rotating = transport_with_write_keys("new-write-key", Some("old-write-key"));
+        rotating
+            .send(TelemetryEvent::InstallerRun, &telemetry)
+            .await
+            .unwrap();
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec!["new-write-key", "old-write-key"]
+        );
+        assert!(rotating.using_secondary_write_key.load(Ordering::Relaxed));
+
+        // Once the primary write key is accepted, the secondary one is no longer used
+        accepted.lock().unwrap().push("new-write-key");
+        sent.lock().unwrap().clear();
+        rotating
+            .send_batch(&[(TelemetryEvent::InstallerRun, telemetry.clone())])
+            .await
+            .unwrap();
+        assert_eq!(*sent.lock().unwrap(), vec!["new-write-key"]);
+        assert!(!rotating.using_secondary_write_key.load(Ordering::Relaxed));
+
+        // Without a secondary write key, unauthorized requests aren't retried
+        accepted.lock().unwrap().clear();
+        sent.lock().unwrap().clear();
+        assert!(matches!(
+            transport_with_write_keys("new-write-key", None)
+                .send(TelemetryEvent::InstallerRun, &telemetry)
+                .await,
+            Err(Error::Unauthorized)
+        ));
+        assert_eq!(*sent.lock().unwrap(), vec!["new-write-key"]);
+
+        // The secondary write key is never sent to custom endpoints
+        let mut custom = transport();
+        custom.custom_endpoint = true;
+        custom.client = Some(make_client(None).unwrap());
+        assert!(custom
+            .with_secondary_write_key(Some("old-write-key".into()))
+            .secondary_client
+            .is_none());
+    }
+
+    #[test]
+    fn custom_endpoint() {
+        let client = make_client(None).unwrap();
+        for endpoint in [
+            "https://relay.example.com/segment",
+            "https://relay.example.com/segment/",
+        ] {
+            let req = SegmentTransport::new(
+                None,
+                "deployment_id".into(),
+                None,
+                Some(parse_endpoint(endpoint).unwrap()),
+            )
+            .build_request(
+                &client,
+                None,
+                [(TelemetryEvent::InstallerRun, &Default::default())],
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+            assert_eq!(
+                req.url().as_str(),
+                "https://relay.example.com/segment/batch"
+            );
+        }
+    }
+
+    #[test]
+    fn write_key_is_only_sent_to_segment() {
+        assert!(client_for_endpoint(true).is_some());
+        assert!(default_headers(None).unwrap().get(AUTHORIZATION).is_none());
+        assert_eq!(
+            default_headers(Some("write_key")).unwrap()[AUTHORIZATION],
+            format!("Basic {}", base64::encode("write_key:")).as_str()
+        );
+    }
+
+    #[test]
+    fn invalid_endpoints() {
+        for endpoint in [
+            "not a url",
+            "ftp://relay.example.com",
+            "unix:/var/run/segment.sock",
+        ] {
+            assert!(
+                matches!(parse_endpoint(endpoint), Err(Error::InvalidEndpoint(_))),
+                "{endpoint}"
+            );
+        }
+    }
+
+    #[test]
+    fn validate_deployment_env() {
+        std::env::set_var("DEPLOYMENT_ENV", "!@#$deployment!@#$_env!@_0.1#$");
+        let mut s = "deployment_env_0.1".to_owned();
+        s.truncate(DEPLOYMENT_ENV_LEN_MAX);
+        assert_eq!(transport().deployment_env, s);
+    }
+}
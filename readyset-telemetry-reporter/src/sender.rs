@@ -1,11 +1,16 @@
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 
+use metrics::{decrement_gauge, increment_counter, increment_gauge};
+use readyset_client_metrics::recorded;
 use readyset_tracing::{debug, warn};
+use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::mpsc::Sender;
 use tokio::sync::{oneshot, Mutex};
 
-use crate::error::{SenderError as Error, SenderResult as Result};
+use crate::error::{ReporterError, TelemetrySendError};
+use crate::reporter::{DroppedEvents, FlushRequest, PeriodicReporters, ShutdownSummary};
 use crate::telemetry::{TelemetryBuilder, TelemetryEvent, *};
 
 /// A struct that can be used to report payloads containing arbitrary telemetry data to the ReadySet
@@ -14,7 +19,10 @@ use crate::telemetry::{TelemetryBuilder, TelemetryEvent, *};
 pub struct TelemetrySender {
     tx: Option<Sender<(TelemetryEvent, Telemetry)>>,
     shutdown_tx: Arc<Mutex<Option<oneshot::Sender<()>>>>,
-    shutdown_ack_rx: Arc<Mutex<Option<oneshot::Receiver<()>>>>,
+    shutdown_ack_rx: Arc<Mutex<Option<oneshot::Receiver<ShutdownSummary>>>>,
+    periodic_reporters: Option<PeriodicReporters>,
+    flush_tx: Option<Sender<FlushRequest>>,
+    dropped_events: DroppedEvents,
     no_op: bool,
 }
 
@@ -23,12 +31,18 @@ impl TelemetrySender {
     pub fn new(
         tx: Sender<(TelemetryEvent, Telemetry)>,
         shutdown_tx: oneshot::Sender<()>,
-        shutdown_ack: oneshot::Receiver<()>,
+        shutdown_ack: oneshot::Receiver<ShutdownSummary>,
+        periodic_reporters: PeriodicReporters,
+        flush_tx: Sender<FlushRequest>,
+        dropped_events: DroppedEvents,
     ) -> Self {
         Self {
             tx: Some(tx),
             shutdown_tx: Arc::new(Mutex::new(Some(shutdown_tx))),
             shutdown_ack_rx: Arc::new(Mutex::new(Some(shutdown_ack))),
+            periodic_reporters: Some(periodic_reporters),
+            flush_tx: Some(flush_tx),
+            dropped_events,
             no_op: false,
         }
     }
@@ -39,32 +53,91 @@ impl TelemetrySender {
             tx: None,
             shutdown_tx: Arc::new(Mutex::new(None)),
             shutdown_ack_rx: Arc::new(Mutex::new(None)),
+            periodic_reporters: None,
+            flush_tx: None,
+            dropped_events: Default::default(),
             no_op: true,
         }
     }
 
-    /// Send a telemetry payload to Segment. If the initial request fails for a non-permanent
-    /// reason (eg, not a 4XX or IO error), this function will retry with an exponential
-    /// backoff, timing out at [`TIMEOUT`].
-    pub fn send_event_with_payload(&self, event: TelemetryEvent, payload: Telemetry) -> Result<()> {
+    /// Send a telemetry payload to Segment. If the request fails for a transient reason (eg, not a
+    /// 4XX error), it will be retried according to the reporter's
+    /// [`RetryPolicy`](crate::RetryPolicy).
+    ///
+    /// If the event can't be queued to be sent (eg because the queue is full), it is dropped,
+    /// counted in the [`TELEMETRY_EVENTS_DROPPED`](recorded::TELEMETRY_EVENTS_DROPPED) metric, and
+    /// an error describing why is returned. Events dropped because the queue is full are also
+    /// counted in the [`ShutdownSummary`] returned by
+    /// [`graceful_shutdown`](TelemetrySender::graceful_shutdown). Always returns `Ok(())` in no-op
+    /// mode.
+    pub fn send_event_with_payload(
+        &self,
+        event: TelemetryEvent,
+        payload: Telemetry,
+    ) -> std::result::Result<(), TelemetrySendError> {
         debug!("sending {event:?} with payload {payload:?}");
         if self.no_op {
             debug!("Ignoring ({event:?} {payload:?}) in no-op mode");
             return Ok(());
         }
 
-        match self.tx.as_ref() {
-            Some(tx) => tx
-                .try_send((event, payload))
-                .map_err(|e| Error::Sender(e.to_string())),
-            None => Err(Error::Sender("sender missing tx".into())),
+        // Count the event as queued before we send it, so the reporter can't decrement the queue
+        // depth before we've incremented it
+        increment_gauge!(recorded::TELEMETRY_QUEUE_DEPTH, 1.0);
+        let res = match self.tx.as_ref() {
+            Some(tx) => tx.try_send((event, payload)).map_err(|e| match e {
+                TrySendError::Full(_) => TelemetrySendError::QueueFull,
+                TrySendError::Closed(_) => TelemetrySendError::ReporterShutDown,
+            }),
+            None => Err(TelemetrySendError::Disabled),
+        };
+        if let Err(error) = res {
+            decrement_gauge!(recorded::TELEMETRY_QUEUE_DEPTH, 1.0);
+            increment_counter!(recorded::TELEMETRY_EVENTS_DROPPED);
+            if error == TelemetrySendError::QueueFull {
+                self.dropped_events.fetch_add(1, Ordering::Relaxed);
+            }
         }
+        res
+    }
+
+    /// Returns a handle to the periodic reporters registered with the telemetry reporter, which can
+    /// be used to disable or re-enable them at runtime. Returns `None` in no-op mode.
+    pub fn periodic_reporters(&self) -> Option<&PeriodicReporters> {
+        self.periodic_reporters.as_ref()
     }
 
-    pub fn send_event(&self, event: TelemetryEvent) -> Result<()> {
+    /// Send a telemetry event with an empty payload. See [`send_event_with_payload`] for details.
+    ///
+    /// [`send_event_with_payload`]: TelemetrySender::send_event_with_payload
+    pub fn send_event(&self, event: TelemetryEvent) -> std::result::Result<(), TelemetrySendError> {
         self.send_event_with_payload(event, TelemetryBuilder::new().build())
     }
 
+    /// Ask the reporter to immediately deliver every event sent so far, including any waiting to be
+    /// batched, and wait up to `timeout` for it to do so. Unlike `graceful_shutdown`, the reporter
+    /// keeps running afterwards, so this can be called any number of times.
+    ///
+    /// Returns the number of events successfully delivered to the reporter's primary transport by
+    /// the flush. Events which couldn't be delivered are dropped, as usual. Always returns `Ok(0)`
+    /// in no-op mode.
+    pub async fn flush(&self, timeout: Duration) -> std::result::Result<usize, ReporterError> {
+        let flush_tx = match &self.flush_tx {
+            Some(flush_tx) => flush_tx,
+            None => return Ok(0),
+        };
+
+        let (ack_tx, ack_rx) = oneshot::channel();
+        tokio::time::timeout(timeout, async move {
+            flush_tx
+                .send(ack_tx)
+                .await
+                .map_err(|_| ReporterError::NotRunning)?;
+            ack_rx.await.map_err(|_| ReporterError::NotRunning)
+        })
+        .await?
+    }
+
     /// Any event sent after shutdown() is sent will fail
     /// Does not wait for shutdown to ack. Use `graceful_shutdown` for that behavior
     pub async fn shutdown(&self) {
@@ -78,18 +151,102 @@ impl TelemetrySender {
 
     /// Any event sent after shutdown() is sent will fail
     /// Waits until `timeout` for the TelemetryReporter to ack shutdown completion.
-    pub async fn graceful_shutdown(&self, timeout: Duration) -> Result<()> {
+    ///
+    /// Returns how many events the reporter delivered to its primary transport while it was
+    /// running, and how many were dropped.
+    pub async fn graceful_shutdown(
+        &self,
+        timeout: Duration,
+    ) -> std::result::Result<ShutdownSummary, ReporterError> {
         self.shutdown().await;
         let shutdown_ack_rx = self.shutdown_ack_rx.lock().await.take();
         match shutdown_ack_rx {
             Some(shutdown_ack_rx) => tokio::time::timeout(timeout, shutdown_ack_rx)
-                .await
-                .map_err(|_| Error::Sender("graceful shutdown timeout".to_string()))?
-                .map_err(|_| Error::Sender("sending shutdown signal to reporter failed".into())),
+                .await?
+                .map_err(|_| ReporterError::NotRunning),
             None => {
                 warn!("graceful shutdown not possible, no ack_rx found");
-                Ok(())
+                Ok(ShutdownSummary::default())
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+    use tokio::sync::mpsc::channel;
+
+    use super::*;
+
+    fn metric_value(name: &str) -> Option<DebugValue> {
+        Snapshotter::current_thread_snapshot()?
+            .into_vec()
+            .into_iter()
+            .find_map(|(key, _, _, value)| (key.key().name() == name).then_some(value))
+    }
+
+    #[test]
+    fn dropped_events_are_counted() {
+        let _ = DebuggingRecorder::per_thread().install();
+        let (tx, _rx) = channel(2);
+        let (shutdown_tx, _shutdown_rx) = oneshot::channel();
+        let (_shutdown_ack_tx, shutdown_ack_rx) = oneshot::channel();
+        let (flush_tx, _flush_rx) = channel(1);
+        let dropped_events = DroppedEvents::default();
+        let sender = TelemetrySender::new(
+            tx,
+            shutdown_tx,
+            shutdown_ack_rx,
+            PeriodicReporters::default(),
+            flush_tx,
+            dropped_events.clone(),
+        );
+
+        for _ in 0..10 {
+            let _ = sender.send_event(TelemetryEvent::AdapterStart);
+        }
+
+        assert_eq!(dropped_events.load(Ordering::Relaxed), 8);
+        assert_eq!(
+            metric_value(recorded::TELEMETRY_EVENTS_DROPPED),
+            Some(DebugValue::Counter(8))
+        );
+        assert_eq!(
+            metric_value(recorded::TELEMETRY_QUEUE_DEPTH),
+            Some(DebugValue::Gauge(2.0.into()))
+        );
+    }
+
+    #[test]
+    fn send_errors() {
+        let (tx, rx) = channel(1);
+        let (shutdown_tx, _shutdown_rx) = oneshot::channel();
+        let (_shutdown_ack_tx, shutdown_ack_rx) = oneshot::channel();
+        let (flush_tx, _flush_rx) = channel(1);
+        let sender = TelemetrySender::new(
+            tx,
+            shutdown_tx,
+            shutdown_ack_rx,
+            PeriodicReporters::default(),
+            flush_tx,
+            Default::default(),
+        );
+
+        assert_eq!(sender.send_event(TelemetryEvent::AdapterStart), Ok(()));
+        assert_eq!(
+            sender.send_event(TelemetryEvent::AdapterStart),
+            Err(TelemetrySendError::QueueFull)
+        );
+        drop(rx);
+        assert_eq!(
+            sender.send_event(TelemetryEvent::AdapterStart),
+            Err(TelemetrySendError::ReporterShutDown)
+        );
+
+        assert_eq!(
+            TelemetrySender::new_no_op().send_event(TelemetryEvent::AdapterStart),
+            Ok(())
+        );
+    }
+}
@@ -56,6 +56,20 @@ pub enum TelemetryEvent {
 
     /// A new query was run that is proxied (not cached)
     ProxiedQuery,
+
+    /// A periodic reporter produced more events than its [`ReportLimit`](crate::ReportLimit)
+    /// allows, and some were dropped
+    PeriodicReportTruncated,
+
+    /// A summary of the adapter's effective configuration, sent once at startup
+    Config,
+
+    /// Events of a single type exceeded their [`Quota`](crate::Quota), and were summarized in
+    /// this event rather than sent individually
+    RateLimited,
+
+    /// Periodic summary of how many reads were served from ReadySet's caches
+    CacheStats,
 }
 
 /// ReadySet-specific telemetry. Provide only the fields you need.
@@ -72,8 +86,8 @@ pub enum TelemetryEvent {
     build_fn(private, name = "fallible_build"),
     setter(into, strip_option)
 )]
-#[derive(Debug)]
-#[cfg_attr(any(test, feature = "test-util"), derive(Clone, Eq, PartialEq))]
+#[derive(Debug, Clone)]
+#[cfg_attr(any(test, feature = "test-util"), derive(Eq, PartialEq))]
 pub struct Telemetry {
     pub db_backend: Option<String>,
     pub db_version: Option<String>,
@@ -83,6 +97,19 @@ pub struct Telemetry {
     pub schema: Option<String>,
     pub proxied_query: Option<String>,
     pub migration_status: Option<String>,
+    pub periodic_reporter: Option<String>,
+    pub truncated_events: Option<u64>,
+    pub migration_style: Option<String>,
+    pub has_upstream: Option<bool>,
+    pub auth_mode: Option<String>,
+    pub deployment_mode: Option<String>,
+    pub rate_limited_event: Option<String>,
+    pub suppressed_events: Option<u64>,
+    pub cache_hits: Option<u64>,
+    pub cache_misses: Option<u64>,
+    pub cache_evictions: Option<u64>,
+    pub authority: Option<String>,
+    pub fallback_cache: Option<bool>,
 }
 
 impl TelemetryBuilder {
@@ -133,6 +160,24 @@ pub struct Track<'a> {
     pub properties: Properties<'a>,
 }
 
+/// A single message within a [`Batch`]
+///
+/// See: https://segment.com/docs/connections/sources/catalog/libraries/server/http-api/#batch
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BatchMessage<'a> {
+    Track(Track<'a>),
+}
+
+/// Top-level wrapper for a request to the Segment batch import endpoint, which sends many messages
+/// in a single request
+///
+/// See: https://segment.com/docs/connections/sources/catalog/libraries/server/http-api/#batch
+#[derive(Serialize)]
+pub struct Batch<'a> {
+    pub batch: Vec<BatchMessage<'a>>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,65 @@
+//! Destinations for telemetry events.
+//!
+//! By default, telemetry events are only sent to ReadySet's Segment source, via the
+//! [`SegmentTransport`](crate::SegmentTransport). A [`TelemetryReporter`](crate::TelemetryReporter)
+//! can instead be constructed with any other [`TelemetryTransport`] as its primary destination
+//! (for example, to send events to an internal collector in an air-gapped deployment, or to
+//! inspect them in tests), using
+//! [`TelemetryReporter::with_transport`](crate::TelemetryReporter::with_transport).
+//!
+//! Operators who want to also collect events themselves can register any number of additional
+//! [`Transport`]s with the reporter, each of which is sent every event the reporter processes.
+//! Delivery to each transport (and to the primary transport) is independent: a transport which
+//! fails or times out doesn't prevent the event being delivered to the others.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::error::ReporterResult as Result;
+use crate::telemetry::{Telemetry, TelemetryEvent};
+
+/// A destination to which telemetry events are sent
+#[async_trait]
+pub trait TelemetryTransport: Send + Sync {
+    /// A name for this transport, used when logging delivery failures
+    fn name(&self) -> &str;
+
+    /// Send a single telemetry event to this transport's destination.
+    ///
+    /// Sends which fail with [`ReporterError::Reqwest`](crate::ReporterError::Reqwest),
+    /// [`ReporterError::Server`](crate::ReporterError::Server), or
+    /// [`ReporterError::Timeout`](crate::ReporterError::Timeout) are retried according to the
+    /// reporter's [`RetryPolicy`](crate::RetryPolicy); all other errors are treated as permanent.
+    async fn send(&self, event: TelemetryEvent, payload: &Telemetry) -> Result<()>;
+
+    /// Send a batch of telemetry events to this transport's destination.
+    ///
+    /// Only the primary transport of a [`TelemetryReporter`](crate::TelemetryReporter) is sent
+    /// batches of events. By default, each event in the batch is sent in turn with
+    /// [`send`](TelemetryTransport::send), stopping at the first error - since failed batches are
+    /// retried in full, transports which can deliver a batch atomically should override this.
+    async fn send_batch(&self, events: &[(TelemetryEvent, Telemetry)]) -> Result<()> {
+        for (event, payload) in events {
+            self.send(*event, payload).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: TelemetryTransport + ?Sized> TelemetryTransport for Arc<T> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    async fn send(&self, event: TelemetryEvent, payload: &Telemetry) -> Result<()> {
+        (**self).send(event, payload).await
+    }
+
+    async fn send_batch(&self, events: &[(TelemetryEvent, Telemetry)]) -> Result<()> {
+        (**self).send_batch(events).await
+    }
+}
+
+pub type Transport = Arc<dyn TelemetryTransport>;
@@ -0,0 +1,70 @@
+//! Replays a client session captured by an adapter run with `--capture-sessions` against a
+//! target adapter, printing the bytes received in response to each message. This can be used to
+//! reproduce protocol-level issues seen by a particular client.
+//!
+//! Since authentication data is redacted from captured sessions, the target adapter must be run
+//! with `--allow-unauthenticated-connections`. Messages which consist only of authentication data
+//! are skipped.
+//!
+//! # Example
+//!
+//! ```bash
+//! cargo run --bin replay_session -- --target 127.0.0.1:5433 /tmp/captures/1667000000000-127.0.0.1_52814.capture
+//! ```
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Parser;
+use readyset_adapter::session_capture::{read_capture, replay};
+use tokio::net::TcpStream;
+
+#[derive(Parser)]
+#[clap(name = "replay_session")]
+struct ReplaySession {
+    /// The address of the adapter to replay the session against.
+    #[clap(short, long, default_value("127.0.0.1:3306"))]
+    target: String,
+
+    /// How long to wait for the adapter to send more bytes in response to a message, in
+    /// milliseconds, before sending the next message.
+    #[clap(long, default_value("1000"))]
+    response_timeout_ms: u64,
+
+    /// The file containing the captured session.
+    capture: PathBuf,
+}
+
+impl ReplaySession {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let messages = read_capture(&self.capture).await?;
+        let mut stream = TcpStream::connect(&self.target).await?;
+        let responses = replay(
+            &mut stream,
+            &messages,
+            Duration::from_millis(self.response_timeout_ms),
+        )
+        .await?;
+
+        for (i, (message, response)) in messages.iter().zip(responses).enumerate() {
+            if message.redacted {
+                println!("#{i} (at {:?}): skipped redacted message", message.offset);
+                continue;
+            }
+            println!(
+                "#{i} (at {:?}): sent {} bytes, received {} bytes",
+                message.offset,
+                message.bytes.len(),
+                response.len()
+            );
+            println!("  > {}", message.bytes.escape_ascii());
+            println!("  < {}", response.escape_ascii());
+        }
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let replay_session = ReplaySession::parse();
+    replay_session.run().await
+}
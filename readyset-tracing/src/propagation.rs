@@ -4,6 +4,7 @@ use std::collections::HashMap;
 use opentelemetry::propagation::text_map_propagator::TextMapPropagator;
 use opentelemetry::propagation::{Extractor, Injector};
 use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::trace::TraceContextExt;
 use serde::{Deserialize, Serialize};
 use tracing::Span;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
@@ -31,7 +32,26 @@ impl Extractor for RequestContext {
     }
 }
 
+/// The key under which a W3C trace context is stored
+const TRACEPARENT: &str = "traceparent";
+
 impl RequestContext {
+    /// Construct a [`RequestContext`] from the value of a W3C
+    /// [`traceparent`](https://www.w3.org/TR/trace-context/#traceparent-header) header, such as
+    /// one sent by a client alongside a query. Returns `None` if the value is not a valid trace
+    /// context.
+    pub fn from_traceparent(traceparent: &str) -> Option<Self> {
+        let mut ctx = RequestContext::default();
+        ctx.set(TRACEPARENT, traceparent.trim().to_owned());
+        let propagator = TraceContextPropagator::new();
+        propagator
+            .extract(&ctx)
+            .span()
+            .span_context()
+            .is_valid()
+            .then_some(ctx)
+    }
+
     #[inline]
     pub fn from_current_span() -> Option<Self> {
         let span = Span::current();
@@ -86,3 +106,43 @@ impl<T> Instrumented<T> {
         self.context.is_some()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::trace::{TraceId, TracerProvider as _};
+    use tracing_subscriber::prelude::*;
+
+    use super::*;
+
+    const TRACEPARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+    #[test]
+    fn invalid_traceparent() {
+        assert!(RequestContext::from_traceparent("").is_none());
+        assert!(RequestContext::from_traceparent("00-not-a-trace-01").is_none());
+        assert!(RequestContext::from_traceparent(
+            "00-00000000000000000000000000000000-b7ad6b7169203331-01"
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn span_parent_from_traceparent() {
+        let tracer = opentelemetry::sdk::trace::TracerProvider::builder()
+            .build()
+            .tracer("test");
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let ctx = RequestContext::from_traceparent(TRACEPARENT).unwrap();
+            let mut span = tracing::info_span!("query");
+            ctx.set_spans_parent(&mut span);
+
+            assert_eq!(
+                span.context().span().span_context().trace_id(),
+                TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap()
+            );
+        });
+    }
+}
@@ -0,0 +1,194 @@
+//! An iterator over exponentially increasing backoff durations, for use in retry loops
+
+use std::time::Duration;
+
+use rand::Rng;
+
+/// An infinite iterator over exponentially increasing [`Duration`]s, for use as the delays between
+/// attempts in a retry loop.
+///
+/// Each duration is the previous one multiplied by [`factor`](Backoff::factor), up to a maximum
+/// of [`max`](Backoff::max). If [`jitter`](Backoff::jitter) is set, each duration yielded is
+/// reduced by a random amount of up to that fraction of the duration, so that many clients
+/// retrying at once don't all retry at the same time. Jitter never increases a duration, so the
+/// durations yielded never exceed the maximum.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use readyset_util::backoff::Backoff;
+///
+/// let delays = Backoff::new(Duration::from_millis(100))
+///     .max(Duration::from_millis(500))
+///     .take(5)
+///     .collect::<Vec<_>>();
+/// assert_eq!(
+///     delays,
+///     vec![
+///         Duration::from_millis(100),
+///         Duration::from_millis(200),
+///         Duration::from_millis(400),
+///         Duration::from_millis(500),
+///         Duration::from_millis(500),
+///     ]
+/// );
+/// ```
+///
+/// In a retry loop:
+///
+/// ```rust,no_run
+/// # async fn try_connect() -> Result<(), ()> { Ok(()) }
+/// # async fn f() {
+/// use std::time::Duration;
+///
+/// use readyset_util::backoff::Backoff;
+///
+/// let mut backoff = Backoff::new(Duration::from_millis(100))
+///     .max(Duration::from_secs(10))
+///     .jitter(0.5);
+/// while try_connect().await.is_err() {
+///     tokio::time::sleep(backoff.next().unwrap()).await;
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    next: Duration,
+    factor: f64,
+    max: Duration,
+    jitter: f64,
+}
+
+impl Backoff {
+    /// Create a new [`Backoff`] whose first duration is `initial`, which doubles each time with no
+    /// maximum and no jitter
+    pub fn new(initial: Duration) -> Self {
+        Self {
+            next: initial,
+            factor: 2.0,
+            max: Duration::MAX,
+            jitter: 0.0,
+        }
+    }
+
+    /// Set the factor by which each duration is multiplied to get the next one. Defaults to 2.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `factor` is less than 1
+    pub fn factor(mut self, factor: f64) -> Self {
+        assert!(factor >= 1.0, "Backoff factor must be at least 1");
+        self.factor = factor;
+        self
+    }
+
+    /// Set the maximum duration to yield. Defaults to no maximum.
+    pub fn max(mut self, max: Duration) -> Self {
+        self.max = max;
+        self.next = self.next.min(max);
+        self
+    }
+
+    /// Set the maximum fraction of each duration by which it may be randomly reduced. Defaults to
+    /// 0 (no jitter).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `jitter` is not between 0 and 1
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&jitter),
+            "Backoff jitter must be between 0 and 1"
+        );
+        self.jitter = jitter;
+        self
+    }
+}
+
+impl Iterator for Backoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next;
+        // Multiply in nanoseconds rather than (fractional) seconds, so that integral factors
+        // produce exact results
+        let next_nanos = (current.as_nanos() as f64 * self.factor).round();
+        self.next = if next_nanos >= self.max.as_nanos() as f64 {
+            self.max
+        } else {
+            const NANOS_PER_SEC: f64 = 1_000_000_000.0;
+            Duration::new(
+                (next_nanos / NANOS_PER_SEC) as u64,
+                (next_nanos % NANOS_PER_SEC) as u32,
+            )
+        };
+
+        if self.jitter > 0.0 {
+            let scale = 1.0 - rand::thread_rng().gen_range(0.0..=self.jitter);
+            Some(current.mul_f64(scale))
+        } else {
+            Some(current)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_exponentially() {
+        let delays = Backoff::new(Duration::from_millis(10))
+            .factor(3.0)
+            .take(4)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(10),
+                Duration::from_millis(30),
+                Duration::from_millis(90),
+                Duration::from_millis(270),
+            ]
+        );
+    }
+
+    #[test]
+    fn caps_at_max() {
+        let max = Duration::from_secs(1);
+        let mut backoff = Backoff::new(Duration::from_millis(100)).max(max);
+        assert!(backoff.by_ref().take(100).all(|d| d <= max));
+        assert_eq!(backoff.next(), Some(max));
+    }
+
+    #[test]
+    fn initial_above_max_is_capped() {
+        let mut backoff = Backoff::new(Duration::from_secs(10)).max(Duration::from_secs(1));
+        assert_eq!(backoff.next(), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn does_not_overflow() {
+        let mut backoff = Backoff::new(Duration::from_secs(u64::MAX / 2));
+        backoff.next();
+        assert_eq!(backoff.next(), Some(Duration::MAX));
+        assert_eq!(backoff.next(), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let jitter = 0.25;
+        let unjittered = Backoff::new(Duration::from_millis(10)).max(Duration::from_secs(5));
+        let jittered = unjittered.clone().jitter(jitter);
+        for (base, delay) in unjittered.zip(jittered).take(1000) {
+            assert!(delay <= base, "{delay:?} > {base:?}");
+            assert!(
+                delay >= base.mul_f64(1.0 - jitter),
+                "{delay:?} < {:?}",
+                base.mul_f64(1.0 - jitter)
+            );
+        }
+    }
+}
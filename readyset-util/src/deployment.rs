@@ -0,0 +1,108 @@
+//! Validation of ReadySet deployment IDs
+
+use std::fmt::{self, Display};
+
+/// Error returned by [`parse_deployment_id`] when given an invalid deployment ID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidDeploymentId {
+    /// The deployment ID was empty
+    Empty,
+    /// The deployment ID contained a character which isn't allowed
+    InvalidCharacter {
+        /// The full deployment ID
+        id: String,
+        /// The first character in the deployment ID which isn't allowed
+        character: char,
+    },
+}
+
+impl Display for InvalidDeploymentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidDeploymentId::Empty => write!(f, "Deployment ID must not be empty"),
+            InvalidDeploymentId::InvalidCharacter { id, character } => write!(
+                f,
+                "Invalid deployment ID {id:?}: character {character:?} is not allowed; deployment \
+                 IDs may only contain ASCII letters, digits, `-`, and `_`"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvalidDeploymentId {}
+
+/// Parse and validate a deployment ID.
+///
+/// Since deployment IDs are used as part of URLs (such as the path of keys in Consul) and as the
+/// values of metrics and telemetry labels, they may only contain ASCII letters, digits, `-`, and
+/// `_`.
+///
+/// # Examples
+///
+/// ```rust
+/// use readyset_util::deployment::parse_deployment_id;
+///
+/// assert_eq!(
+///     parse_deployment_id("my-deployment_1").unwrap(),
+///     "my-deployment_1"
+/// );
+/// assert!(parse_deployment_id("my/deployment").is_err());
+/// ```
+pub fn parse_deployment_id(id: &str) -> Result<String, InvalidDeploymentId> {
+    if id.is_empty() {
+        return Err(InvalidDeploymentId::Empty);
+    }
+    match id
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_'))
+    {
+        Some(character) => Err(InvalidDeploymentId::InvalidCharacter {
+            id: id.to_owned(),
+            character,
+        }),
+        None => Ok(id.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_ids() {
+        for id in [
+            "test",
+            "readyset-prod",
+            "ct_new_leader_metrics",
+            "Deployment-01_a",
+            "1",
+        ] {
+            assert_eq!(parse_deployment_id(id), Ok(id.to_owned()));
+        }
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(parse_deployment_id(""), Err(InvalidDeploymentId::Empty));
+    }
+
+    #[test]
+    fn invalid_characters() {
+        for (id, character) in [
+            ("my/deployment", '/'),
+            ("my deployment", ' '),
+            ("déploiement", 'é'),
+            ("deployment.prod", '.'),
+            ("deploy?ment", '?'),
+        ] {
+            assert_eq!(
+                parse_deployment_id(id),
+                Err(InvalidDeploymentId::InvalidCharacter {
+                    id: id.to_owned(),
+                    character
+                }),
+                "{id}"
+            );
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Utilities for parsing human-readable durations
+
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+/// Error returned by [`parse_duration_with_suffix`] when the input is not a valid duration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseDurationError {
+    /// The input was empty
+    Empty,
+    /// The input didn't start with a number
+    MissingNumber(String),
+    /// The number was followed by something other than one of the supported unit suffixes
+    UnknownSuffix(String),
+}
+
+impl Display for ParseDurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseDurationError::Empty => write!(f, "Duration must not be empty"),
+            ParseDurationError::MissingNumber(input) => {
+                write!(f, "Duration `{input}` must start with a number")
+            }
+            ParseDurationError::UnknownSuffix(suffix) => write!(
+                f,
+                "Unknown duration unit `{suffix}`; expected one of `ms`, `s`, `m`, or `h`"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseDurationError {}
+
+/// Parse a duration consisting of a non-negative integer followed by an optional unit suffix,
+/// which may be one of `ms` (milliseconds), `s` (seconds), `m` (minutes), or `h` (hours). A number
+/// with no suffix is interpreted as a number of seconds.
+///
+/// Durations which are too large to represent saturate to the largest representable number of
+/// the given unit, rather than returning an error. Inputs with more than one unit, such as `1h30m`,
+/// are rejected.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use readyset_util::duration::parse_duration_with_suffix;
+///
+/// assert_eq!(
+///     parse_duration_with_suffix("500ms").unwrap(),
+///     Duration::from_millis(500)
+/// );
+/// assert_eq!(
+///     parse_duration_with_suffix("5m").unwrap(),
+///     Duration::from_secs(300)
+/// );
+/// assert_eq!(
+///     parse_duration_with_suffix("30").unwrap(),
+///     Duration::from_secs(30)
+/// );
+/// assert!(parse_duration_with_suffix("1h30m").is_err());
+/// ```
+pub fn parse_duration_with_suffix(s: &str) -> Result<Duration, ParseDurationError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(ParseDurationError::Empty);
+    }
+
+    let (number, suffix) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    if number.is_empty() {
+        return Err(ParseDurationError::MissingNumber(s.to_owned()));
+    }
+    // `number` consists only of digits, so the only way parsing can fail is by overflowing
+    let n = number.parse::<u64>().unwrap_or(u64::MAX);
+
+    match suffix.trim_start() {
+        "ms" => Ok(Duration::from_millis(n)),
+        "" | "s" => Ok(Duration::from_secs(n)),
+        "m" => Ok(Duration::from_secs(n.saturating_mul(60))),
+        "h" => Ok(Duration::from_secs(n.saturating_mul(60 * 60))),
+        suffix => Err(ParseDurationError::UnknownSuffix(suffix.to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suffixes() {
+        assert_eq!(
+            parse_duration_with_suffix("500ms"),
+            Ok(Duration::from_millis(500))
+        );
+        assert_eq!(parse_duration_with_suffix("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(
+            parse_duration_with_suffix("5m"),
+            Ok(Duration::from_secs(300))
+        );
+        assert_eq!(
+            parse_duration_with_suffix("1h"),
+            Ok(Duration::from_secs(3600))
+        );
+        assert_eq!(
+            parse_duration_with_suffix(" 10 ms "),
+            Ok(Duration::from_millis(10))
+        );
+    }
+
+    #[test]
+    fn bare_number_is_seconds() {
+        assert_eq!(
+            parse_duration_with_suffix("30"),
+            Ok(Duration::from_secs(30))
+        );
+        assert_eq!(parse_duration_with_suffix("0"), Ok(Duration::ZERO));
+    }
+
+    #[test]
+    fn saturates() {
+        assert_eq!(
+            parse_duration_with_suffix("99999999999999999999999"),
+            Ok(Duration::from_secs(u64::MAX))
+        );
+        assert_eq!(
+            parse_duration_with_suffix(&format!("{}h", u64::MAX / 2)),
+            Ok(Duration::from_secs(u64::MAX))
+        );
+        assert_eq!(
+            parse_duration_with_suffix("99999999999999999999999ms"),
+            Ok(Duration::from_millis(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        assert_eq!(
+            parse_duration_with_suffix(""),
+            Err(ParseDurationError::Empty)
+        );
+        assert_eq!(
+            parse_duration_with_suffix("ms"),
+            Err(ParseDurationError::MissingNumber("ms".to_owned()))
+        );
+        assert_eq!(
+            parse_duration_with_suffix("-5s"),
+            Err(ParseDurationError::MissingNumber("-5s".to_owned()))
+        );
+        assert_eq!(
+            parse_duration_with_suffix("1h30m"),
+            Err(ParseDurationError::UnknownSuffix("h30m".to_owned()))
+        );
+        assert_eq!(
+            parse_duration_with_suffix("5d"),
+            Err(ParseDurationError::UnknownSuffix("d".to_owned()))
+        );
+        assert_eq!(
+            parse_duration_with_suffix("1.5s"),
+            Err(ParseDurationError::UnknownSuffix(".5s".to_owned()))
+        );
+    }
+}
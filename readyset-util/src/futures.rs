@@ -3,9 +3,11 @@
 use std::future::Future;
 use std::panic::AssertUnwindSafe;
 use std::process;
+use std::time::Duration;
 
 use futures::{FutureExt, TryFutureExt};
 use readyset_tracing::error;
+use tokio::sync::broadcast;
 
 /// A version of the [`tokio::select`] macro that also emits an `allow` annotation for
 /// `clippy::unreachable` and `clippy::panic`, since both are internal to the expansion of the macro
@@ -38,6 +40,96 @@ where
         })
 }
 
+/// Run the given future until either it completes, returning `Some` with its output, or a
+/// cancellation signal is received on (or the sender is dropped for) `shutdown`, returning `None`.
+///
+/// If cancellation happens first, the future is dropped immediately, without being polled again.
+///
+/// # Examples
+///
+/// ```rust
+/// use readyset_util::futures::run_until_cancelled;
+///
+/// # let rt = tokio::runtime::Runtime::new().unwrap();
+/// # rt.block_on(async move {
+/// let (shutdown_tx, shutdown_rx) = tokio::sync::broadcast::channel(1);
+/// assert_eq!(
+///     run_until_cancelled(async { 1 }, shutdown_tx.subscribe()).await,
+///     Some(1)
+/// );
+///
+/// shutdown_tx.send(()).unwrap();
+/// assert_eq!(
+///     run_until_cancelled(futures::future::pending::<()>(), shutdown_rx).await,
+///     None
+/// );
+/// # })
+/// ```
+pub async fn run_until_cancelled<F, T>(f: F, mut shutdown: broadcast::Receiver<()>) -> Option<T>
+where
+    F: Future<Output = T>,
+{
+    crate::select! {
+        res = f => Some(res),
+        _ = shutdown.recv() => None,
+    }
+}
+
+/// What [`abort_on_timeout`] should do if the future it wraps doesn't complete before its deadline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutAction {
+    /// Abort the entire process
+    Abort,
+    /// Panic, which only unwinds the task running the future. Mostly useful for testing.
+    Panic,
+}
+
+/// Wrap the given future in a handler that will abort the entire process (or panic, depending on
+/// `action`) if the future doesn't complete within `timeout`, logging the given task `name` at
+/// error level first.
+///
+/// This is intended for detecting hung tasks, such as a migration task that never completes.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use readyset_util::futures::{abort_on_timeout, TimeoutAction};
+///
+/// # let rt = tokio::runtime::Runtime::new().unwrap();
+/// # rt.block_on(async move {
+/// let res = abort_on_timeout(
+///     "quick task",
+///     async { 1 },
+///     Duration::from_secs(5),
+///     TimeoutAction::Abort,
+/// )
+/// .await;
+/// assert_eq!(res, 1);
+/// # })
+/// ```
+pub async fn abort_on_timeout<F, A>(
+    name: &'static str,
+    f: F,
+    timeout: Duration,
+    action: TimeoutAction,
+) -> A
+where
+    F: Future<Output = A>,
+{
+    match tokio::time::timeout(timeout, f).await {
+        Ok(res) => res,
+        Err(_) => {
+            error!(task = %name, ?timeout, "Task did not complete before its deadline; aborting");
+            match action {
+                TimeoutAction::Abort => process::abort(),
+                TimeoutAction::Panic => panic!("Task {name} did not complete within {timeout:?}"),
+            }
+        }
+    }
+}
+
 /// Assert that the given async expression eventually succeeds after a configurable number of
 /// tries and sleeping a configurable amount between tries. Useful for testing eventually
 /// consistent parts of the system.
@@ -214,3 +306,72 @@ macro_rules! eventually {
         )
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn run_until_cancelled_drops_future_on_cancellation() {
+        struct SetOnDrop(Arc<AtomicBool>);
+        impl Drop for SetOnDrop {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let guard = SetOnDrop(dropped.clone());
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let task = tokio::spawn(run_until_cancelled(
+            async move {
+                let _guard = guard;
+                futures::future::pending::<()>().await
+            },
+            shutdown_rx,
+        ));
+
+        tokio::task::yield_now().await;
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        shutdown_tx.send(()).unwrap();
+        assert_eq!(task.await.unwrap(), None);
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn run_until_cancelled_returns_output() {
+        let (_shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        assert_eq!(run_until_cancelled(async { 1 }, shutdown_rx).await, Some(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn abort_on_timeout_completes_in_time() {
+        let res = abort_on_timeout(
+            "test",
+            async {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                1
+            },
+            Duration::from_secs(2),
+            TimeoutAction::Panic,
+        )
+        .await;
+        assert_eq!(res, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn abort_on_timeout_panics_on_timeout() {
+        let res = tokio::spawn(abort_on_timeout(
+            "test",
+            futures::future::pending::<()>(),
+            Duration::from_secs(2),
+            TimeoutAction::Panic,
+        ))
+        .await;
+        assert!(res.unwrap_err().is_panic());
+    }
+}
@@ -90,12 +90,15 @@ where
 /// # Examples:
 ///
 /// ```rust
+/// use std::ops::Bound::*;
+///
 /// use readyset_util::intervals::overlaps;
 ///
 /// assert!(overlaps(&(0..=0), &(0..)));
 /// assert!(overlaps(&(..=0), &(0..10)));
 /// assert!(overlaps(&(0..10), &(..=0)));
 /// assert!(!overlaps(&(0..10), &(10..)));
+/// assert!(!overlaps(&(Excluded(0), Unbounded), &(..0)));
 /// ```
 pub fn overlaps<Q, R, S>(r1: &R, r2: &S) -> bool
 where
@@ -106,14 +109,14 @@ where
     // start lte end
     (match (r1.start_bound(), r2.end_bound()) {
         (Included(x), Excluded(y)) if x >= y => false,
-        (Excluded(x), Included(y)) if x >= y => false,
+        (Excluded(x), Included(y) | Excluded(y)) if x >= y => false,
         (Excluded(x) | Included(x), Excluded(y) | Included(y)) if x > y => false,
         _ => true,
     }) && (
         // and end gte start
         match (r1.end_bound(), r2.start_bound()) {
             (Included(x), Excluded(y)) if x <= y => false,
-            (Excluded(x), Included(y)) if x <= y => false,
+            (Excluded(x), Included(y) | Excluded(y)) if x <= y => false,
             (Excluded(x) | Included(x), Excluded(y) | Included(y)) if x < y => false,
             _ => true,
         }
@@ -144,6 +147,22 @@ where
             if x == y)
 }
 
+/// Returns true if no values can lie within the given range, either because it is [empty] or
+/// because its start is after its end.
+///
+/// [empty]: is_empty
+fn contains_nothing<Q, R>(r: &R) -> bool
+where
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    match (r.start_bound(), r.end_bound()) {
+        (Included(x), Included(y)) => x > y,
+        (Included(x) | Excluded(x), Excluded(y)) | (Excluded(x), Included(y)) => x >= y,
+        _ => false,
+    }
+}
+
 /// Compare two bounds that are at the start of an interval
 ///
 /// This is necessary rather than just having a single compare method on bounds because at the [end
@@ -428,7 +447,8 @@ impl<'a, Q, R> ExactSizeIterator for DifferenceIterator<'a, Q, R> where R: Range
 /// Returns an iterator over the result(s) of removing the second interval from the first.
 ///
 /// The result of this is either zero, one, or two intervals:
-///   * zero, if the second interval fully covers the first,
+///   * zero, if the second interval fully covers the first, or if the first interval contains no
+///     values,
 ///   * one, if the second interval overlaps the start or end of the first, or if the intervals do
 ///     not overlap at all
 ///   * two, if the first interval fully covers the second
@@ -452,7 +472,9 @@ where
     S: RangeBounds<Q>,
     Q: Ord,
 {
-    if !overlaps(r1, r2) {
+    if contains_nothing(r1) {
+        DifferenceIterator::Empty
+    } else if contains_nothing(r2) || !overlaps(r1, r2) {
         DifferenceIterator::Unchanged(iter::once(r1))
     } else {
         let below = if matches!(
@@ -501,6 +523,106 @@ where
     }
 }
 
+/// Returns an iterator over the result(s) of removing the intersection of the two intervals from
+/// their union - that is, all the parts of either interval which are not in the other.
+///
+/// The parts of the first interval (the result of [`difference`]`(r1, r2)`) are yielded first,
+/// followed by the parts of the second (the result of `difference(r2, r1)`). The resulting
+/// intervals never overlap each other, and at most two are yielded.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::ops::Bound::*;
+///
+/// use readyset_util::intervals::symmetric_difference;
+///
+/// assert_eq!(
+///     symmetric_difference(&(1..5), &(3..8)).collect::<Vec<_>>(),
+///     vec![(Included(&1), Excluded(&3)), (Included(&5), Excluded(&8))]
+/// );
+///
+/// // When one interval covers the other, the outer interval is split in two
+/// assert_eq!(
+///     symmetric_difference(&(1..10), &(3..=5)).collect::<Vec<_>>(),
+///     vec![(Included(&1), Excluded(&3)), (Excluded(&5), Excluded(&10))]
+/// );
+/// ```
+pub fn symmetric_difference<'a, Q, R, S>(
+    r1: &'a R,
+    r2: &'a S,
+) -> iter::Chain<DifferenceIterator<'a, Q, R>, DifferenceIterator<'a, Q, S>>
+where
+    R: RangeBounds<Q>,
+    S: RangeBounds<Q>,
+    Q: Ord,
+{
+    difference(r1, r2).chain(difference(r2, r1))
+}
+
+/// Returns true if there are values between the given bound at the end of one interval and the
+/// given bound at the start of another interval, meaning that the two intervals can't be merged
+fn gap_between<Q>(end: Bound<&Q>, start: Bound<&Q>) -> bool
+where
+    Q: Ord + ?Sized,
+{
+    match (end, start) {
+        (Included(e), Included(s) | Excluded(s)) | (Excluded(e), Included(s)) => s > e,
+        (Excluded(e), Excluded(s)) => s >= e,
+        (Unbounded, _) | (_, Unbounded) => false,
+    }
+}
+
+/// Merge all overlapping or abutting intervals in the given list, returning the minimal list of
+/// disjoint intervals which covers exactly the same values, sorted by their start bounds.
+///
+/// Intervals which abut each other are only merged if the value at which they meet is included in
+/// at least one of them, so `[1, 2)` and `[2, 3)` are merged, but `[1, 2)` and `(2, 3)` are not.
+/// Intervals which contain no values are dropped.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::ops::Bound::*;
+///
+/// use readyset_util::intervals::coalesce;
+///
+/// assert_eq!(
+///     coalesce(vec![
+///         (Included(5), Excluded(7)),
+///         (Included(1), Excluded(2)),
+///         (Included(2), Excluded(3)),
+///         (Included(6), Unbounded),
+///     ]),
+///     vec![(Included(1), Excluded(3)), (Included(5), Unbounded)]
+/// );
+///
+/// assert_eq!(
+///     coalesce(vec![(Included(1), Excluded(2)), (Excluded(2), Excluded(3))]),
+///     vec![(Included(1), Excluded(2)), (Excluded(2), Excluded(3))]
+/// );
+/// ```
+pub fn coalesce<T>(mut intervals: Vec<BoundPair<T>>) -> Vec<BoundPair<T>>
+where
+    T: Ord,
+{
+    intervals.retain(|r| !contains_nothing(r));
+    intervals.sort_by(|r1, r2| cmp_startbound(r1.0.as_ref(), r2.0.as_ref()));
+
+    let mut res: Vec<BoundPair<T>> = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals {
+        match res.last_mut() {
+            Some(last) if !gap_between(last.1.as_ref(), start.as_ref()) => {
+                if cmp_endbound(end.as_ref(), last.1.as_ref()) == Greater {
+                    last.1 = end;
+                }
+            }
+            _ => res.push((start, end)),
+        }
+    }
+    res
+}
+
 /// Returns the (non-empty) intersection of the two ranges, if any exists
 ///
 /// # Examples
@@ -679,12 +801,174 @@ mod tests {
             )
         }
 
+        #[test]
+        fn open_and_closed_bounds() {
+            assert_eq!(
+                difference(&(1..=10), &(Excluded(3), Included(5))).collect::<Vec<_>>(),
+                vec![(Included(&1), Included(&3)), (Excluded(&5), Included(&10))]
+            );
+            assert_eq!(
+                difference(&(Excluded(1), Excluded(10)), &(1..=5)).collect::<Vec<_>>(),
+                vec![(Excluded(&5), Excluded(&10))]
+            );
+            assert_eq!(
+                difference(&(..), &(Excluded(0), Excluded(5))).collect::<Vec<_>>(),
+                vec![(Unbounded, Included(&0)), (Included(&5), Unbounded)]
+            );
+        }
+
         #[proptest]
         fn idempotent(r1: (Bound<i8>, Bound<i8>), r2: (Bound<i8>, Bound<i8>)) {
             let once: Vec<_> = difference(&r1, &r2).collect();
             let twice: Vec<_> = once.iter().flat_map(|r| difference(r, &r2)).collect();
             assert_eq!(once, twice);
         }
+
+        #[proptest]
+        fn contains_exactly_set_difference(r1: (Bound<i8>, Bound<i8>), r2: (Bound<i8>, Bound<i8>)) {
+            let diff: Vec<_> = difference(&r1, &r2).collect();
+            for x in i8::MIN..=i8::MAX {
+                assert_eq!(
+                    diff.iter().any(|r| r.contains(&x)),
+                    r1.contains(&x) && !r2.contains(&x),
+                    "{x}"
+                );
+            }
+        }
+    }
+
+    mod symmetric_difference {
+        use test_strategy::proptest;
+
+        use super::*;
+
+        #[test]
+        fn equal() {
+            assert_eq!(
+                symmetric_difference(&(1..=5), &(1..=5)).collect::<Vec<_>>(),
+                vec![]
+            );
+        }
+
+        #[test]
+        fn disjoint() {
+            assert_eq!(
+                symmetric_difference(&(1..3), &(3..5)).collect::<Vec<_>>(),
+                vec![(Included(&1), Excluded(&3)), (Included(&3), Excluded(&5))]
+            );
+        }
+
+        #[test]
+        fn shared_endpoint() {
+            assert_eq!(
+                symmetric_difference(&(1..=5), &(Excluded(1), Included(5))).collect::<Vec<_>>(),
+                vec![(Included(&1), Included(&1))]
+            );
+        }
+
+        #[proptest]
+        fn commutative(r1: (Bound<i8>, Bound<i8>), r2: (Bound<i8>, Bound<i8>)) {
+            let mut forwards: Vec<_> = symmetric_difference(&r1, &r2).collect();
+            let mut backwards: Vec<_> = symmetric_difference(&r2, &r1).collect();
+            forwards.sort_by(|x, y| cmp_startbound(x.0, y.0));
+            backwards.sort_by(|x, y| cmp_startbound(x.0, y.0));
+            assert_eq!(forwards, backwards);
+        }
+
+        #[proptest]
+        fn contains_exactly_symmetric_difference(
+            r1: (Bound<i8>, Bound<i8>),
+            r2: (Bound<i8>, Bound<i8>),
+        ) {
+            let diff: Vec<_> = symmetric_difference(&r1, &r2).collect();
+            for x in i8::MIN..=i8::MAX {
+                assert_eq!(
+                    diff.iter().filter(|r| r.contains(&x)).count(),
+                    usize::from(r1.contains(&x) != r2.contains(&x)),
+                    "{x}"
+                );
+            }
+        }
+    }
+
+    mod coalesce {
+        use test_strategy::proptest;
+
+        use super::*;
+
+        #[test]
+        fn overlapping_and_contained() {
+            assert_eq!(
+                coalesce(vec![
+                    (Included(1), Included(5)),
+                    (Excluded(4), Excluded(8)),
+                    (Included(2), Included(3)),
+                ]),
+                vec![(Included(1), Excluded(8))]
+            );
+        }
+
+        #[test]
+        fn abutting() {
+            assert_eq!(
+                coalesce(vec![(Included(2), Included(3)), (Included(1), Excluded(2))]),
+                vec![(Included(1), Included(3))]
+            );
+            assert_eq!(
+                coalesce(vec![(Included(1), Included(2)), (Excluded(2), Included(3))]),
+                vec![(Included(1), Included(3))]
+            );
+            assert_eq!(
+                coalesce(vec![(Excluded(2), Included(3)), (Included(1), Excluded(2))]),
+                vec![(Included(1), Excluded(2)), (Excluded(2), Included(3))]
+            );
+        }
+
+        #[test]
+        fn unbounded() {
+            assert_eq!(
+                coalesce(vec![
+                    (Included(10), Unbounded),
+                    (Unbounded, Included(0)),
+                    (Excluded(0), Excluded(10)),
+                ]),
+                vec![(Unbounded, Unbounded)]
+            );
+        }
+
+        #[test]
+        fn drops_empty() {
+            assert_eq!(
+                coalesce(vec![(Included(1), Excluded(1)), (Included(5), Included(3))]),
+                vec![]
+            );
+        }
+
+        #[proptest]
+        fn covers_same_values(intervals: Vec<(Bound<i8>, Bound<i8>)>) {
+            let coalesced = coalesce(intervals.clone());
+            for x in i8::MIN..=i8::MAX {
+                assert_eq!(
+                    coalesced.iter().any(|r| r.contains(&x)),
+                    intervals.iter().any(|r| r.contains(&x)),
+                    "{x}"
+                );
+            }
+        }
+
+        #[proptest]
+        fn sorted_and_disjoint(intervals: Vec<(Bound<i8>, Bound<i8>)>) {
+            let coalesced = coalesce(intervals);
+            for pair in coalesced.windows(2) {
+                assert!(gap_between(pair[0].1.as_ref(), pair[1].0.as_ref()));
+            }
+        }
+
+        #[proptest]
+        fn idempotent(intervals: Vec<(Bound<i8>, Bound<i8>)>) {
+            let once = coalesce(intervals);
+            assert_eq!(coalesce(once.clone()), once);
+        }
     }
 
     mod intersection {
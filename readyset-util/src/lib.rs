@@ -8,17 +8,21 @@ use std::collections::{BTreeMap, HashMap};
 use std::hash::Hash;
 
 pub mod arbitrary;
+pub mod backoff;
+pub mod deployment;
 pub mod display;
+pub mod duration;
 pub mod futures;
 pub mod hash;
 pub mod intervals;
 pub mod math;
 pub mod nonmaxusize;
 pub mod properties;
+pub mod rate_limit;
 pub mod redacted;
 
-/// Error (returned by [`Indices::indices`] and [`Indices::cloned_indices`]) for an out-of-bounds
-/// index access
+/// Error (returned by [`Indices::indices`], [`Indices::cloned_indices`],
+/// [`Indices::index_map`] and [`Indices::all_indices`]) for an out-of-bounds index access
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub struct IndexOutOfBounds<Idx>(Idx);
 
@@ -46,6 +50,36 @@ pub trait Indices<'idx, Idx: 'idx> {
     where
         I: IntoIterator<Item = Idx> + 'idx;
 
+    /// Return a lazy iterator over references to the values in self corresponding to the indices
+    /// in `indices`, yielding an error for each index which is out of bounds.
+    ///
+    /// Unlike [`indices`](Indices::indices), this doesn't allocate, so it can be fused with
+    /// further processing of the values.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use readyset_util::Indices;
+    ///
+    /// let v = vec![0, 1, 2, 3, 4];
+    /// let sum: i32 = v.indices_iter([1, 3]).map(|r| r.unwrap() * 10).sum();
+    /// assert_eq!(sum, 40);
+    ///
+    /// let mut iter = v.indices_iter([2, 7]);
+    /// assert_eq!(iter.next(), Some(Ok(&2)));
+    /// assert!(iter.next().unwrap().is_err());
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    fn indices_iter<'a, I>(&'a self, indices: I) -> IndicesIter<'a, Self, I::IntoIter>
+    where
+        I: IntoIterator<Item = Idx> + 'idx,
+    {
+        IndicesIter {
+            collection: self,
+            indices: indices.into_iter(),
+        }
+    }
+
     /// Return a vector of clones of all the values in self corresponding to the indices in
     /// `indices`, or, if any of the indices were out of bounds, an error indicating the first such
     /// out-of-bound index
@@ -63,31 +97,219 @@ pub trait Indices<'idx, Idx: 'idx> {
     where
         I: IntoIterator<Item = Idx> + 'idx,
         Self::Output: Clone;
+
+    /// Return the positions of, and references to, all the values in self for which `pred`
+    /// returns true.
+    ///
+    /// The positions can be passed back to [`indices`](Indices::indices). Only available for
+    /// slices (and so for [`Vec`]s), since maps have no meaningful positions for their values.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use readyset_util::Indices;
+    ///
+    /// let v = vec![1, 2, 3, 4, 5, 6];
+    /// let (idxs, evens) = v.indices_where(|x| x % 2 == 0);
+    /// assert_eq!(idxs, vec![1, 3, 5]);
+    /// assert_eq!(evens, vec![&2, &4, &6]);
+    /// assert_eq!(v.indices(idxs).unwrap(), evens);
+    /// ```
+    fn indices_where<F>(&self, pred: F) -> (Vec<usize>, Vec<&Self::Output>)
+    where
+        F: Fn(&Self::Output) -> bool,
+        Self: AsRef<[Self::Output]>,
+        Self::Output: Sized,
+    {
+        self.as_ref()
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| pred(v))
+            .unzip()
+    }
+
+    /// Return a map from each of the indices in `indices` to a reference to the corresponding
+    /// value in self, or, if any of the indices were out of bounds, an error indicating the first
+    /// such out-of-bound index
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    ///
+    /// use readyset_util::Indices;
+    ///
+    /// let v = vec![0, 10, 20, 30, 40];
+    /// let res = v.index_map(vec![1, 3]).unwrap();
+    /// assert_eq!(res, HashMap::from([(1, &10), (3, &30)]));
+    ///
+    /// let m = HashMap::from([("a", 1), ("b", 2), ("c", 3)]);
+    /// let res = m.index_map(vec![&"a", &"c"]).unwrap();
+    /// assert_eq!(res, HashMap::from([(&"a", &1), (&"c", &3)]));
+    /// assert!(m.index_map(vec![&"d"]).is_err());
+    /// ```
+    fn index_map<'a, I>(
+        &'a self,
+        indices: I,
+    ) -> Result<HashMap<Idx, &'a Self::Output>, IndexOutOfBounds<Idx>>
+    where
+        I: IntoIterator<Item = Idx> + 'idx,
+        Idx: Hash + Eq + Clone;
+
+    /// Return a vector of references to all the values in self corresponding to the indices in
+    /// `indices`, or, if any of the indices were out of bounds, an error containing *all* such
+    /// out-of-bound indices, in the order they appear in `indices`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::collections::{BTreeMap, HashMap};
+    ///
+    /// use readyset_util::Indices;
+    ///
+    /// let v = vec![0, 1, 2, 3, 4];
+    /// assert_eq!(v.all_indices(vec![1, 2]).unwrap(), vec![&1, &2]);
+    /// assert_eq!(v.all_indices(vec![7, 1, 5]).unwrap_err().len(), 2);
+    ///
+    /// let m = HashMap::from([("a", 1), ("b", 2)]);
+    /// assert_eq!(m.all_indices(vec![&"b", &"a"]).unwrap(), vec![&2, &1]);
+    /// assert_eq!(m.all_indices(vec![&"c", &"a", &"d"]).unwrap_err().len(), 2);
+    ///
+    /// let m = BTreeMap::from([("a", 1), ("b", 2)]);
+    /// assert_eq!(m.all_indices(vec![&"x", &"a", &"y"]).unwrap_err().len(), 2);
+    /// ```
+    fn all_indices<'a, I>(
+        &'a self,
+        indices: I,
+    ) -> Result<Vec<&'a Self::Output>, Vec<IndexOutOfBounds<Idx>>>
+    where
+        I: IntoIterator<Item = Idx> + 'idx;
+}
+
+/// Iterator over references to the values in a collection at a sequence of indices, returned by
+/// [`Indices::indices_iter`]
+#[derive(Debug, Clone)]
+pub struct IndicesIter<'a, C: ?Sized, I> {
+    collection: &'a C,
+    indices: I,
+}
+
+impl<'a, A, I> Iterator for IndicesIter<'a, [A], I>
+where
+    I: Iterator<Item = usize>,
+{
+    type Item = Result<&'a A, IndexOutOfBounds<usize>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = self.indices.next()?;
+        Some(self.collection.get(i).ok_or(IndexOutOfBounds(i)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl<'a, 'idx, K, Q, V, I> Iterator for IndicesIter<'a, HashMap<K, V>, I>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + 'idx,
+    I: Iterator<Item = &'idx Q>,
+{
+    type Item = Result<&'a V, IndexOutOfBounds<&'idx Q>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = self.indices.next()?;
+        Some(self.collection.get(i).ok_or(IndexOutOfBounds(i)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl<'a, 'idx, K, Q, V, I> Iterator for IndicesIter<'a, BTreeMap<K, V>, I>
+where
+    K: Eq + Ord + Borrow<Q>,
+    Q: Eq + Ord + 'idx,
+    I: Iterator<Item = &'idx Q>,
+{
+    type Item = Result<&'a V, IndexOutOfBounds<&'idx Q>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = self.indices.next()?;
+        Some(self.collection.get(i).ok_or(IndexOutOfBounds(i)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+/// Collect the results of looking up a sequence of indices, returning either all the values or
+/// every one of the out-of-bounds indices
+fn collect_all_indices<T, Idx>(
+    results: impl Iterator<Item = Result<T, IndexOutOfBounds<Idx>>>,
+) -> Result<Vec<T>, Vec<IndexOutOfBounds<Idx>>> {
+    let mut values = Vec::new();
+    let mut errors = Vec::new();
+    for res in results {
+        match res {
+            Ok(v) => values.push(v),
+            Err(e) => errors.push(e),
+        }
+    }
+    if errors.is_empty() {
+        Ok(values)
+    } else {
+        Err(errors)
+    }
 }
 
 impl<'a, A> Indices<'a, usize> for [A] {
     type Output = A;
 
     fn indices<I>(&self, indices: I) -> Result<Vec<&Self::Output>, IndexOutOfBounds<usize>>
+    where
+        I: IntoIterator<Item = usize> + 'a,
+    {
+        self.indices_iter(indices).collect()
+    }
+
+    fn cloned_indices<I>(&self, indices: I) -> Result<Vec<Self::Output>, IndexOutOfBounds<usize>>
     where
         I: IntoIterator<Item = usize>,
+        A: Clone,
     {
         indices
             .into_iter()
-            .map(|i| self.get(i).ok_or(IndexOutOfBounds(i)))
+            .map(|i| self.get(i).cloned().ok_or(IndexOutOfBounds(i)))
             .collect()
     }
 
-    fn cloned_indices<I>(&self, indices: I) -> Result<Vec<Self::Output>, IndexOutOfBounds<usize>>
+    fn index_map<I>(
+        &self,
+        indices: I,
+    ) -> Result<HashMap<usize, &Self::Output>, IndexOutOfBounds<usize>>
     where
         I: IntoIterator<Item = usize>,
-        A: Clone,
     {
         indices
             .into_iter()
-            .map(|i| self.get(i).cloned().ok_or(IndexOutOfBounds(i)))
+            .map(|i| Ok((i, self.get(i).ok_or(IndexOutOfBounds(i))?)))
             .collect()
     }
+
+    fn all_indices<I>(&self, indices: I) -> Result<Vec<&Self::Output>, Vec<IndexOutOfBounds<usize>>>
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        collect_all_indices(
+            indices
+                .into_iter()
+                .map(|i| self.get(i).ok_or(IndexOutOfBounds(i))),
+        )
+    }
 }
 
 impl<'idx, K, Q, V> Indices<'idx, &'idx Q> for HashMap<K, V>
@@ -98,25 +320,50 @@ where
     type Output = V;
 
     fn indices<I>(&self, indices: I) -> Result<Vec<&Self::Output>, IndexOutOfBounds<&'idx Q>>
+    where
+        I: IntoIterator<Item = &'idx Q> + 'idx,
+    {
+        self.indices_iter(indices).collect()
+    }
+
+    fn cloned_indices<I>(&self, indices: I) -> Result<Vec<Self::Output>, IndexOutOfBounds<&'idx Q>>
     where
         I: IntoIterator<Item = &'idx Q>,
+        V: Clone,
     {
         indices
             .into_iter()
-            .map(|i| self.get(i).ok_or(IndexOutOfBounds(i)))
+            .map(|i| self.get(i).cloned().ok_or(IndexOutOfBounds(i)))
             .collect()
     }
 
-    fn cloned_indices<I>(&self, indices: I) -> Result<Vec<Self::Output>, IndexOutOfBounds<&'idx Q>>
+    fn index_map<I>(
+        &self,
+        indices: I,
+    ) -> Result<HashMap<&'idx Q, &Self::Output>, IndexOutOfBounds<&'idx Q>>
     where
         I: IntoIterator<Item = &'idx Q>,
-        V: Clone,
+        &'idx Q: Hash + Eq + Clone,
     {
         indices
             .into_iter()
-            .map(|i| self.get(i).cloned().ok_or(IndexOutOfBounds(i)))
+            .map(|i| Ok((i, self.get(i).ok_or(IndexOutOfBounds(i))?)))
             .collect()
     }
+
+    fn all_indices<I>(
+        &self,
+        indices: I,
+    ) -> Result<Vec<&Self::Output>, Vec<IndexOutOfBounds<&'idx Q>>>
+    where
+        I: IntoIterator<Item = &'idx Q>,
+    {
+        collect_all_indices(
+            indices
+                .into_iter()
+                .map(|i| self.get(i).ok_or(IndexOutOfBounds(i))),
+        )
+    }
 }
 
 impl<'idx, K, Q, V> Indices<'idx, &'idx Q> for BTreeMap<K, V>
@@ -127,23 +374,48 @@ where
     type Output = V;
 
     fn indices<I>(&self, indices: I) -> Result<Vec<&Self::Output>, IndexOutOfBounds<&'idx Q>>
+    where
+        I: IntoIterator<Item = &'idx Q> + 'idx,
+    {
+        self.indices_iter(indices).collect()
+    }
+
+    fn cloned_indices<I>(&self, indices: I) -> Result<Vec<Self::Output>, IndexOutOfBounds<&'idx Q>>
     where
         I: IntoIterator<Item = &'idx Q>,
+        V: Clone,
     {
         indices
             .into_iter()
-            .map(|i| self.get(i).ok_or(IndexOutOfBounds(i)))
+            .map(|i| self.get(i).cloned().ok_or(IndexOutOfBounds(i)))
             .collect()
     }
 
-    fn cloned_indices<I>(&self, indices: I) -> Result<Vec<Self::Output>, IndexOutOfBounds<&'idx Q>>
+    fn index_map<I>(
+        &self,
+        indices: I,
+    ) -> Result<HashMap<&'idx Q, &Self::Output>, IndexOutOfBounds<&'idx Q>>
     where
         I: IntoIterator<Item = &'idx Q>,
-        V: Clone,
+        &'idx Q: Hash + Eq + Clone,
     {
         indices
             .into_iter()
-            .map(|i| self.get(i).cloned().ok_or(IndexOutOfBounds(i)))
+            .map(|i| Ok((i, self.get(i).ok_or(IndexOutOfBounds(i))?)))
             .collect()
     }
+
+    fn all_indices<I>(
+        &self,
+        indices: I,
+    ) -> Result<Vec<&Self::Output>, Vec<IndexOutOfBounds<&'idx Q>>>
+    where
+        I: IntoIterator<Item = &'idx Q>,
+    {
+        collect_all_indices(
+            indices
+                .into_iter()
+                .map(|i| self.get(i).ok_or(IndexOutOfBounds(i))),
+        )
+    }
 }
@@ -10,10 +10,124 @@ pub fn integer_rnd(val: i128, prec: i32) -> i128 {
     ((val as f64 / 10.0_f64.powf(-(prec as f64))).round() * 10.0_f64.powf(-(prec as f64))) as i128
 }
 
+/// Compute `count` histogram bucket upper bounds, logarithmically spaced between `min` and `max`
+/// (inclusive), following the conventions of Prometheus's `ExponentialBucketsRange`.
+///
+/// The returned boundaries do not include the implicit `+Inf` bucket.
+///
+/// # Panics
+///
+/// Panics if `count` is less than 2, if `min` is not positive, or if `max` is not greater than
+/// `min`
+pub fn log_buckets(min: f64, max: f64, count: usize) -> Vec<f64> {
+    assert!(count >= 2, "log_buckets needs a count of at least 2");
+    assert!(min > 0.0, "log_buckets needs a positive min");
+    assert!(max > min, "log_buckets needs a max greater than min");
+
+    let factor = (max / min).powf(1.0 / (count - 1) as f64);
+    let mut buckets = (0..count)
+        .map(|i| min * factor.powi(i as i32))
+        .collect::<Vec<_>>();
+    // Avoid accumulated floating-point error in the last boundary
+    buckets[count - 1] = max;
+    buckets
+}
+
+/// Compute `count` histogram bucket upper bounds, each `width` wide, the lowest of which is
+/// `start`, following the conventions of Prometheus's `LinearBuckets`.
+///
+/// The returned boundaries do not include the implicit `+Inf` bucket.
+///
+/// # Panics
+///
+/// Panics if `count` is zero, or if `width` is not positive
+pub fn linear_buckets(start: f64, width: f64, count: usize) -> Vec<f64> {
+    assert!(count >= 1, "linear_buckets needs a positive count");
+    assert!(width > 0.0, "linear_buckets needs a positive width");
+
+    (0..count).map(|i| start + width * i as f64).collect()
+}
+
+/// Compute `numerator / denominator` as a floating-point ratio, returning `None` if `denominator`
+/// is zero rather than producing an infinite or NaN result.
+///
+/// # Examples
+///
+/// ```rust
+/// use readyset_util::math::safe_ratio;
+///
+/// assert_eq!(safe_ratio(1, 4), Some(0.25));
+/// assert_eq!(safe_ratio(1, 0), None);
+/// ```
+pub fn safe_ratio(numerator: u64, denominator: u64) -> Option<f64> {
+    if denominator == 0 {
+        return None;
+    }
+    Some(numerator as f64 / denominator as f64)
+}
+
+/// Compute `part` as a percentage of `whole`, clamped to the range `[0, 100]`, returning `None` if
+/// `whole` is zero.
+///
+/// # Examples
+///
+/// ```rust
+/// use readyset_util::math::percentage;
+///
+/// assert_eq!(percentage(1, 4), Some(25.0));
+/// assert_eq!(percentage(5, 4), Some(100.0));
+/// assert_eq!(percentage(0, 0), None);
+/// ```
+pub fn percentage(part: u64, whole: u64) -> Option<f64> {
+    safe_ratio(part, whole).map(|ratio| (ratio * 100.0).clamp(0.0, 100.0))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn safe_ratio_zero() {
+        assert_eq!(safe_ratio(0, 0), None);
+        assert_eq!(safe_ratio(u64::MAX, 0), None);
+        assert_eq!(safe_ratio(0, 10), Some(0.0));
+    }
+
+    #[test]
+    fn safe_ratio_overflow() {
+        assert_eq!(safe_ratio(u64::MAX, u64::MAX), Some(1.0));
+        assert_eq!(safe_ratio(u64::MAX, 1), Some(u64::MAX as f64));
+        assert!(safe_ratio(1, u64::MAX).unwrap() > 0.0);
+    }
+
+    #[test]
+    fn safe_ratio_normal() {
+        assert_eq!(safe_ratio(3, 4), Some(0.75));
+        assert_eq!(safe_ratio(10, 4), Some(2.5));
+    }
+
+    #[test]
+    fn percentage_zero() {
+        assert_eq!(percentage(0, 0), None);
+        assert_eq!(percentage(10, 0), None);
+        assert_eq!(percentage(0, 10), Some(0.0));
+    }
+
+    #[test]
+    fn percentage_overflow() {
+        assert_eq!(percentage(u64::MAX, u64::MAX), Some(100.0));
+        assert_eq!(percentage(u64::MAX, 1), Some(100.0));
+        let tiny = percentage(1, u64::MAX).unwrap();
+        assert!(tiny > 0.0 && tiny < 1.0);
+    }
+
+    #[test]
+    fn percentage_normal() {
+        assert_eq!(percentage(1, 4), Some(25.0));
+        assert_eq!(percentage(4, 4), Some(100.0));
+        assert_eq!(percentage(11, 10), Some(100.0));
+    }
+
     #[test]
     fn integer_rnd_skips_pos_prec() {
         let want = 53;
@@ -27,4 +141,48 @@ mod tests {
         let got = integer_rnd(888, -3);
         assert_eq!(got, want);
     }
+
+    fn assert_increasing(buckets: &[f64]) {
+        assert!(
+            buckets.windows(2).all(|w| w[0] < w[1]),
+            "{buckets:?} is not strictly increasing"
+        );
+    }
+
+    #[test]
+    fn log_buckets_boundaries() {
+        let buckets = log_buckets(1.0, 1000.0, 4);
+        assert_eq!(buckets.len(), 4);
+        for (got, want) in buckets.iter().zip([1.0, 10.0, 100.0, 1000.0]) {
+            assert!((got - want).abs() < 1e-9, "{got} != {want}");
+        }
+        assert_increasing(&buckets);
+
+        let buckets = log_buckets(0.0005, 30.0, 20);
+        assert_eq!(buckets.len(), 20);
+        assert_eq!(buckets[0], 0.0005);
+        assert_eq!(buckets[19], 30.0);
+        assert_increasing(&buckets);
+    }
+
+    #[test]
+    #[should_panic]
+    fn log_buckets_rejects_non_positive_min() {
+        log_buckets(0.0, 10.0, 5);
+    }
+
+    #[test]
+    fn linear_buckets_boundaries() {
+        let buckets = linear_buckets(5.0, 10.0, 4);
+        assert_eq!(buckets, vec![5.0, 15.0, 25.0, 35.0]);
+        assert_increasing(&buckets);
+
+        assert_eq!(linear_buckets(-1.0, 0.5, 1), vec![-1.0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn linear_buckets_rejects_zero_width() {
+        linear_buckets(0.0, 0.0, 5);
+    }
 }
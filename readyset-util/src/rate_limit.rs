@@ -0,0 +1,367 @@
+//! Utilities for limiting the rate at which some operation is performed
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// A token bucket rate limiter.
+///
+/// The bucket holds up to `capacity` tokens, and is refilled continuously at a rate of
+/// `rate_per_sec` tokens per second. Each call to [`acquire`](TokenBucket::acquire) consumes a
+/// single token, waiting until one becomes available if the bucket is empty - callers are delayed
+/// rather than rejected.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    rate_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Create a new, full, [`TokenBucket`] which allows bursts of up to `capacity` operations and
+    /// refills at a rate of `rate_per_sec` operations per second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate_per_sec` is not positive, or if `capacity` is less than 1
+    pub fn new(rate_per_sec: f64, capacity: f64) -> Self {
+        assert!(rate_per_sec > 0.0, "TokenBucket rate must be positive");
+        assert!(capacity >= 1.0, "TokenBucket capacity must be at least 1");
+        Self {
+            capacity,
+            tokens: capacity,
+            rate_per_sec,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// Attempt to consume a single token without waiting, returning `true` if a token was
+    /// available
+    pub fn try_acquire(&mut self) -> bool {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the amount of time until a token will next be available
+    pub fn time_until_available(&mut self) -> Duration {
+        self.refill();
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.rate_per_sec)
+        }
+    }
+
+    /// Consume a single token, waiting until one is available if the bucket is currently empty
+    pub async fn acquire(&mut self) {
+        while !self.try_acquire() {
+            tokio::time::sleep(self.time_until_available()).await;
+        }
+    }
+}
+
+/// A counter of events which occur within a fixed-length window of time.
+///
+/// The window starts when the counter is created, and is restarted by
+/// [`take`](WindowedCounter::take).
+#[derive(Debug, Clone)]
+pub struct WindowedCounter {
+    window: Duration,
+    window_start: Instant,
+    count: u64,
+}
+
+impl WindowedCounter {
+    /// Create a new [`WindowedCounter`] with a count of zero, whose window is `window` long and
+    /// starts now
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            window_start: Instant::now(),
+            count: 0,
+        }
+    }
+
+    /// Record a single event in the current window
+    pub fn increment(&mut self) {
+        self.count += 1;
+    }
+
+    /// Returns the number of events recorded in the current window
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the amount of time since the current window started
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().saturating_duration_since(self.window_start)
+    }
+
+    /// Returns `true` if the current window has ended
+    pub fn window_elapsed(&self) -> bool {
+        self.elapsed() >= self.window
+    }
+
+    /// Returns the number of events recorded in the current window, and starts a new, empty,
+    /// window
+    pub fn take(&mut self) -> u64 {
+        self.window_start = Instant::now();
+        std::mem::take(&mut self.count)
+    }
+}
+
+/// What to do with a single occurrence of a message passed to [`LogRateLimiter::check`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogAction {
+    /// Log the message as normal
+    Log,
+    /// Log a summary of the message instead, which accounts for all the occurrences since the
+    /// message was last logged
+    Summarize {
+        /// The number of times the message has occurred since it was last logged, including this
+        /// occurrence
+        occurrences: u64,
+        /// The amount of time since the message was last logged
+        period: Duration,
+    },
+    /// Don't log anything - the occurrence has been counted, and will be included in a later
+    /// summary
+    Suppress,
+}
+
+/// A summary of the occurrences of a category of message which were suppressed by a
+/// [`LogRateLimiter`], returned by [`LogRateLimiter::flush`] and [`LogRateLimiter::flush_all`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogSummary {
+    /// The category of the suppressed messages
+    pub category: &'static str,
+    /// The number of occurrences which were suppressed
+    pub occurrences: u64,
+    /// The amount of time since the message was last logged
+    pub period: Duration,
+    /// The details of the most recent suppressed occurrence
+    pub last_detail: String,
+}
+
+#[derive(Debug)]
+struct CategoryState {
+    counter: WindowedCounter,
+    last_detail: String,
+}
+
+/// Limits how often log messages of the same category (for example, the same error occurring for
+/// every connection during an outage) are emitted.
+///
+/// The first occurrence of a category of message is logged as normal, and any further occurrences
+/// within the following window are suppressed and counted. The first occurrence after the window
+/// has ended then logs a single summary of the number of occurrences since the message was last
+/// logged, and starts a new window. This bounds the log volume to one line per category per
+/// window.
+///
+/// Occurrences which are suppressed in the last window before a message stops occurring are only
+/// logged if the caller [`flush`](LogRateLimiter::flush)es the limiter periodically (or
+/// [`flush_all`](LogRateLimiter::flush_all)es it when it's done with it).
+#[derive(Debug)]
+pub struct LogRateLimiter {
+    window: Duration,
+    categories: Mutex<HashMap<&'static str, CategoryState>>,
+}
+
+impl LogRateLimiter {
+    /// Create a new [`LogRateLimiter`] which logs each category of message at most once per
+    /// `window`
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            categories: Default::default(),
+        }
+    }
+
+    /// Record an occurrence of a message in `category`, with the given details (such as the
+    /// error which occurred), returning what should be logged for it
+    pub fn check(&self, category: &'static str, detail: &str) -> LogAction {
+        let mut categories = self.categories.lock().unwrap_or_else(|e| e.into_inner());
+        match categories.get_mut(category) {
+            Some(state) if state.counter.window_elapsed() => {
+                let period = state.counter.elapsed();
+                match state.counter.take() {
+                    0 => LogAction::Log,
+                    suppressed => LogAction::Summarize {
+                        occurrences: suppressed + 1,
+                        period,
+                    },
+                }
+            }
+            Some(state) => {
+                state.counter.increment();
+                state.last_detail = detail.to_owned();
+                LogAction::Suppress
+            }
+            None => {
+                categories.insert(
+                    category,
+                    CategoryState {
+                        counter: WindowedCounter::new(self.window),
+                        last_detail: String::new(),
+                    },
+                );
+                LogAction::Log
+            }
+        }
+    }
+
+    /// Returns summaries of the occurrences suppressed in every category whose window has ended,
+    /// and starts a new window for those categories. This should be called periodically (at
+    /// least once per window) so that occurrences suppressed just before a message stops
+    /// occurring are still logged.
+    pub fn flush(&self) -> Vec<LogSummary> {
+        self.take_summaries(|state| state.counter.window_elapsed())
+    }
+
+    /// Returns summaries of the occurrences suppressed in every category, whether or not their
+    /// window has ended, for when the limiter is no longer going to be used
+    pub fn flush_all(&self) -> Vec<LogSummary> {
+        self.take_summaries(|_| true)
+    }
+
+    fn take_summaries(
+        &self,
+        mut should_flush: impl FnMut(&CategoryState) -> bool,
+    ) -> Vec<LogSummary> {
+        let mut categories = self.categories.lock().unwrap_or_else(|e| e.into_inner());
+        categories
+            .iter_mut()
+            .filter(|(_, state)| state.counter.count() > 0 && should_flush(state))
+            .map(|(category, state)| {
+                let period = state.counter.elapsed();
+                LogSummary {
+                    category,
+                    occurrences: state.counter.take(),
+                    period,
+                    last_detail: std::mem::take(&mut state.last_detail),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_full() {
+        let mut bucket = TokenBucket::new(1.0, 3.0);
+        assert!(bucket.try_acquire());
+        assert!(bucket.try_acquire());
+        assert!(bucket.try_acquire());
+        assert!(!bucket.try_acquire());
+    }
+
+    #[tokio::test]
+    async fn burst_is_paced() {
+        let rate = 50.0;
+        let mut bucket = TokenBucket::new(rate, 1.0);
+        let start = Instant::now();
+        for _ in 0..6 {
+            bucket.acquire().await;
+        }
+        // The first acquire is served from the initial token; the remaining 5 must each wait for
+        // a refill
+        assert!(start.elapsed() >= Duration::from_secs_f64(5.0 / rate) - Duration::from_millis(5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn repeated_log_messages_are_summarized() {
+        let limiter = LogRateLimiter::new(Duration::from_secs(10));
+        let mut logged = 0;
+        let mut summarized_occurrences = 0;
+        let mut suppressed = 0;
+
+        // 1000 errors of the same category, one every 100ms, over 100 seconds
+        for i in 0..1000 {
+            match limiter.check(
+                "Error connecting to upstream database",
+                &format!("error {i}"),
+            ) {
+                LogAction::Log => logged += 1,
+                LogAction::Summarize {
+                    occurrences,
+                    period,
+                } => {
+                    assert!(period >= Duration::from_secs(10));
+                    logged += 1;
+                    summarized_occurrences += occurrences;
+                }
+                LogAction::Suppress => suppressed += 1,
+            }
+            tokio::time::advance(Duration::from_millis(100)).await;
+        }
+
+        // One line per 10-second window
+        assert_eq!(logged, 10);
+        assert_eq!(suppressed, 990);
+        // The first occurrence is logged individually, and every other occurrence is accounted for
+        // by a summary, except the 99 suppressed in the final window...
+        assert_eq!(1 + summarized_occurrences + 99, 1000);
+        // ...which are summarized once the window has ended, even though no more errors occurred
+        assert_eq!(
+            limiter.flush(),
+            vec![LogSummary {
+                category: "Error connecting to upstream database",
+                occurrences: 99,
+                period: Duration::from_secs(10),
+                last_detail: "error 999".to_owned(),
+            }]
+        );
+        assert!(limiter.flush().is_empty());
+
+        // A different category isn't affected by the first one
+        assert_eq!(
+            limiter.check("Connection timed out", "error"),
+            LogAction::Log
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn flush_waits_for_window_to_end() {
+        let limiter = LogRateLimiter::new(Duration::from_secs(10));
+        assert_eq!(limiter.check("error", "a"), LogAction::Log);
+        assert_eq!(limiter.check("error", "b"), LogAction::Suppress);
+        assert_eq!(limiter.check("error", "c"), LogAction::Suppress);
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert!(limiter.flush().is_empty());
+
+        // Flushing everything doesn't wait for the window
+        let summaries = limiter.flush_all();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].occurrences, 2);
+        assert_eq!(summaries[0].last_detail, "c");
+        assert!(limiter.flush_all().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn infrequent_log_messages_are_not_summarized() {
+        let limiter = LogRateLimiter::new(Duration::from_secs(1));
+        for _ in 0..5 {
+            assert_eq!(limiter.check("error", "details"), LogAction::Log);
+            tokio::time::advance(Duration::from_secs(2)).await;
+        }
+    }
+}
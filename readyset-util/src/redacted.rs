@@ -3,7 +3,7 @@
 
 use std::convert::Infallible;
 use std::fmt::{Debug, Display};
-use std::ops::Deref;
+use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
@@ -77,3 +77,205 @@ impl From<RedactedString> for String {
         s.0
     }
 }
+
+/// Wraps a value of any type, replacing it with "<redacted>" when printed with either Debug or
+/// Display, while still allowing access to the wrapped value via [`Deref`].
+///
+/// This can be used for fields which hold secrets within structs which derive [`Debug`]:
+///
+/// ```
+/// use readyset_util::redacted::Redacted;
+///
+/// #[derive(Debug)]
+/// struct Config {
+///     user: String,
+///     api_key: Redacted<String>,
+/// }
+///
+/// let config = Config {
+///     user: "root".into(),
+///     api_key: "abc123".parse().unwrap(),
+/// };
+/// assert_eq!(
+///     format!("{config:?}"),
+///     r#"Config { user: "root", api_key: <redacted> }"#
+/// );
+/// assert_eq!(config.api_key.len(), 6);
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Redacted<T>(pub T);
+
+impl<T> Redacted<T> {
+    /// Consume self, returning the wrapped value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Redacted<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Redacted<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> Debug for Redacted<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+impl<T> Display for Redacted<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+impl<T> From<T> for Redacted<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> FromStr for Redacted<T>
+where
+    T: FromStr,
+{
+    type Err = T::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+/// Placeholder printed in place of the value of a field redacted by [`redacted_debug!`]
+#[doc(hidden)]
+pub struct RedactedField;
+
+impl Debug for RedactedField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("****")
+    }
+}
+
+/// Implements [`Debug`] for a struct, printing the value of each field except those marked with
+/// `#[redact]`, which are printed as `****`.
+///
+/// Every field of the struct must be listed, in the order they should be printed - the generated
+/// implementation destructures the struct, so adding a field to the struct without listing it here
+/// is a compile error.
+///
+/// ```
+/// use readyset_util::redacted_debug;
+///
+/// struct Config {
+///     user: String,
+///     password: String,
+/// }
+///
+/// redacted_debug!(Config {
+///     user,
+///     #[redact]
+///     password,
+/// });
+///
+/// let config = Config {
+///     user: "root".into(),
+///     password: "hunter2".into(),
+/// };
+/// assert_eq!(
+///     format!("{config:?}"),
+///     r#"Config { user: "root", password: **** }"#
+/// );
+/// ```
+#[macro_export]
+macro_rules! redacted_debug {
+    ($ty:ident { $($(#[$attr:ident])? $field:ident),* $(,)? }) => {
+        impl ::std::fmt::Debug for $ty {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                let $ty { $($field),* } = self;
+                let mut debug_struct = f.debug_struct(stringify!($ty));
+                $($crate::redacted_debug!(@field debug_struct, $field $(, $attr)?);)*
+                debug_struct.finish()
+            }
+        }
+    };
+    (@field $debug_struct:ident, $field:ident) => {
+        $debug_struct.field(stringify!($field), $field);
+    };
+    (@field $debug_struct:ident, $field:ident, redact) => {
+        let _ = $field;
+        $debug_struct.field(stringify!($field), &$crate::redacted::RedactedField);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Credentials {
+        username: String,
+        password: Option<String>,
+        port: u16,
+    }
+
+    redacted_debug!(Credentials {
+        username,
+        #[redact]
+        password,
+        port,
+    });
+
+    #[test]
+    fn redacted_fields_are_hidden() {
+        let credentials = Credentials {
+            username: "root".into(),
+            password: Some("hunter2".into()),
+            port: 3306,
+        };
+        assert_eq!(
+            format!("{credentials:?}"),
+            r#"Credentials { username: "root", password: ****, port: 3306 }"#
+        );
+
+        let pretty = format!("{credentials:#?}");
+        assert!(!pretty.contains("hunter2"));
+        assert!(pretty.contains("password: ****,"));
+        assert!(pretty.contains("port: 3306,"));
+    }
+
+    #[test]
+    fn redacted_hides_value() {
+        let mut secret = Redacted(String::from("hunter2"));
+        assert_eq!(format!("{secret:?}"), "<redacted>");
+        assert_eq!(format!("{secret}"), "<redacted>");
+        assert_eq!(format!("{:?}", Some(&secret)), "Some(<redacted>)");
+
+        secret.push('!');
+        assert_eq!(*secret, "hunter2!");
+        assert_eq!(secret.into_inner(), "hunter2!");
+
+        let port: Redacted<u16> = "3306".parse().unwrap();
+        assert_eq!(*port, 3306);
+        assert!("not a port".parse::<Redacted<u16>>().is_err());
+    }
+
+    #[test]
+    fn redacted_serializes_transparently() {
+        let secret = Redacted(String::from("hunter2"));
+        let json = serde_json::to_string(&secret).unwrap();
+        assert_eq!(json, r#""hunter2""#);
+        assert_eq!(
+            serde_json::from_str::<Redacted<String>>(&json).unwrap(),
+            secret
+        );
+    }
+}
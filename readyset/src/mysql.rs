@@ -1,7 +1,10 @@
 use async_trait::async_trait;
 use mysql_srv::MySqlIntermediary;
+use readyset_adapter::connection_stats::ByteCountingStream;
+use readyset_adapter::idle_timeout::{is_idle_timeout, IdleTimeoutStream};
+use readyset_adapter::session_capture::{CapturingStream, SessionCapture};
 use readyset_mysql::{MySqlQueryHandler, MySqlUpstream};
-use readyset_tracing::error;
+use readyset_tracing::{debug, error};
 use tokio::net::TcpStream;
 use tracing::instrument;
 
@@ -20,11 +23,22 @@ impl ConnectionHandler for MySqlHandler {
         &mut self,
         stream: TcpStream,
         backend: readyset_adapter::Backend<MySqlUpstream, MySqlQueryHandler>,
+        capture: Option<SessionCapture>,
     ) {
-        if let Err(e) =
-            MySqlIntermediary::run_on_tcp(readyset_mysql::Backend::new(backend), stream).await
+        if let Err(e) = stream.set_nodelay(true) {
+            error!(err = %e, "could not set TCP_NODELAY on connection");
+        }
+        let (reader, writer) = stream.into_split();
+        let reader = backend.drainable_stream(IdleTimeoutStream::new(
+            CapturingStream::new(reader, capture),
+            backend.idle_timeout(),
+        ));
+        let writer = ByteCountingStream::new(writer, backend.connection_stats());
+        match MySqlIntermediary::run_on(readyset_mysql::Backend::new(backend), reader, writer).await
         {
-            error!(err = %e, "connection lost");
+            Ok(()) => {}
+            Err(e) if is_idle_timeout(&e) => debug!(err = %e, "closed idle connection"),
+            Err(e) => error!(err = %e, "connection lost"),
         }
     }
 
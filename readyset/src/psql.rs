@@ -1,6 +1,10 @@
 use async_trait::async_trait;
+use readyset_adapter::cancel::{CancelAuth, CancelRegistry};
+use readyset_adapter::connection_stats::ByteCountingStream;
+use readyset_adapter::idle_timeout::IdleTimeoutStream;
+use readyset_adapter::session_capture::{CapturingStream, SessionCapture};
 use readyset_psql::{PostgreSqlQueryHandler, PostgreSqlUpstream};
-use readyset_tracing::error;
+use readyset_tracing::{debug, error};
 use tokio::net;
 use tracing::instrument;
 
@@ -19,7 +23,15 @@ impl ConnectionHandler for PsqlHandler {
         &mut self,
         stream: net::TcpStream,
         backend: readyset_adapter::Backend<PostgreSqlUpstream, PostgreSqlQueryHandler>,
+        capture: Option<SessionCapture>,
     ) {
+        let stream = ByteCountingStream::new(
+            backend.drainable_stream(IdleTimeoutStream::new(
+                CapturingStream::new(stream, capture),
+                backend.idle_timeout(),
+            )),
+            backend.connection_stats(),
+        );
         psql_srv::run_backend(readyset_psql::Backend(backend), stream).await;
     }
 
@@ -33,4 +45,28 @@ impl ConnectionHandler for PsqlHandler {
             error!(%error, "Could not send immediate error packet")
         }
     }
+
+    async fn handle_cancel_request(
+        &mut self,
+        stream: net::TcpStream,
+        cancel_registry: &CancelRegistry,
+    ) -> Option<net::TcpStream> {
+        // Clients send the whole cancel request at once, so if the first packet isn't one this
+        // isn't a cancel request (and if it's split across packets, the backend will handle it)
+        let mut buf = [0; psql_srv::CANCEL_REQUEST_LENGTH];
+        let len = match stream.peek(&mut buf).await {
+            Ok(len) => len,
+            Err(error) => {
+                debug!(%error, "Could not read from new connection");
+                return Some(stream);
+            }
+        };
+        match psql_srv::parse_cancel_request(&buf[..len]) {
+            Some((process_id, secret_key)) => {
+                cancel_registry.cancel(process_id as u32, CancelAuth::Secret(secret_key as u32));
+                None
+            }
+            None => Some(stream),
+        }
+    }
 }
@@ -1,22 +1,240 @@
-use std::collections::{BTreeMap, HashMap};
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::future;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use metrics::{register_counter, register_histogram, Counter, Histogram, SharedString};
+use anyhow::bail;
+use metrics::{counter, register_counter, register_histogram, Counter, Histogram, SharedString};
 use nom_sql::SqlQuery;
 use readyset_client::query::QueryId;
+use readyset_client_metrics::labels::BoundedLabelValues;
 use readyset_client_metrics::{
     recorded, DatabaseType, EventType, QueryExecutionEvent, SqlQueryType,
 };
 use readyset_sql_passes::anonymize::anonymize_literals;
-use readyset_tracing::info;
-use tokio::select;
+use readyset_tracing::{info, warn};
+use readyset_util::futures::run_until_cancelled;
+use serde::Serialize;
 use tokio::sync::broadcast;
 use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::{Instant, MissedTickBehavior};
 use tracing::info_span;
 
+/// The minimum interval between log messages summarizing the records dropped by the
+/// [`QueryLogger`]
+const DROP_SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The interval between the per-query summaries written by the [`QueryLogger`], if it's configured
+/// to write them
+const QUERY_SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The upper bounds, in milliseconds, of the latency buckets in per-query summaries. Executions
+/// which take longer than the last bound are counted in a final, unbounded bucket.
+const SUMMARY_LATENCY_BUCKETS_MS: [u64; 10] = [1, 5, 10, 25, 50, 100, 250, 500, 1000, 5000];
+
+/// The maximum number of distinct query tags used as the `query_tag` metric label. Queries with
+/// tags beyond the first this many are labelled
+/// [`OTHER_LABEL_VALUE`](readyset_client_metrics::labels::OTHER_LABEL_VALUE).
+const MAX_QUERY_TAGS: usize = 32;
+
+/// The maximum length, in bytes, of a query tag used as the `query_tag` metric label. Longer tags
+/// are truncated.
+const MAX_QUERY_TAG_LEN: usize = 64;
+
+/// The format of the per-query summaries written by the [`QueryLogger`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum QueryLogFormat {
+    /// One human-readable line per query and destination
+    Text,
+    /// One JSON object per query and destination, with each object on its own line
+    /// ([NDJSON](http://ndjson.org/))
+    Json,
+}
+
+impl FromStr for QueryLogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => bail!("Invalid query log format; expected one of \"text\" or \"json\""),
+        }
+    }
+}
+
+/// Configures how the [`QueryLogger`] buffers and batches the records it receives
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct QueryLoggerConfig {
+    /// The maximum number of records to process in a single batch
+    pub(crate) batch_size: usize,
+    /// How long to wait for more records to arrive before processing a batch that isn't full
+    pub(crate) batch_window: Duration,
+    /// The maximum number of records to buffer while waiting to be processed. Once this many
+    /// records are buffered, new records are dropped. If `None`, records are never dropped.
+    pub(crate) max_backlog: Option<usize>,
+    /// Queries which take longer than this to execute are counted in the
+    /// [`QUERY_LOG_SLOW_QUERIES`](recorded::QUERY_LOG_SLOW_QUERIES) metric
+    pub(crate) slow_query_threshold: Duration,
+}
+
 pub(crate) struct QueryLogger {
     per_id_metrics: BTreeMap<QueryId, QueryMetrics>,
     per_query_metrics: HashMap<Arc<SqlQuery>, QueryMetrics>,
+    config: QueryLoggerConfig,
+    summaries: Option<SummaryWriter>,
+    /// The query tags seen so far, used to cap the cardinality of the `query_tag` label
+    query_tags: BoundedLabelValues,
+}
+
+/// Periodically writes a summary of the executions of each query recorded by the
+/// [`QueryLogger`] since the last summary, in a [`QueryLogFormat`]
+pub(crate) struct SummaryWriter {
+    format: QueryLogFormat,
+    /// Shared with the blocking task that writes each batch of summaries
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl SummaryWriter {
+    pub(crate) fn new(format: QueryLogFormat, writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            format,
+            writer: Arc::new(Mutex::new(writer)),
+        }
+    }
+
+    /// Append the summary of the executions of `query` against `destination` to `out`, followed
+    /// by a newline
+    fn format_summary(
+        &self,
+        out: &mut String,
+        timestamp: &str,
+        query: &str,
+        query_id: Option<&str>,
+        destination: &'static str,
+        summary: &LatencySummary,
+    ) {
+        let buckets = summary.buckets();
+        match self.format {
+            QueryLogFormat::Text => {
+                let _ = write!(out, "{timestamp} destination={destination}");
+                if let Some(query_id) = query_id {
+                    let _ = write!(out, " query_id={query_id}");
+                }
+                let _ = write!(out, " count={} latency_ms=[", summary.count);
+                let mut first = true;
+                for bucket in buckets.iter().filter(|bucket| bucket.count > 0) {
+                    if !first {
+                        out.push(' ');
+                    }
+                    first = false;
+                    let _ = match bucket.le {
+                        Some(le) => write!(out, "<={le}:{}", bucket.count),
+                        None => write!(
+                            out,
+                            ">{}:{}",
+                            SUMMARY_LATENCY_BUCKETS_MS[SUMMARY_LATENCY_BUCKETS_MS.len() - 1],
+                            bucket.count
+                        ),
+                    };
+                }
+                let _ = write!(out, "] query={query}");
+            }
+            QueryLogFormat::Json => {
+                let record = SummaryRecord {
+                    timestamp,
+                    query,
+                    query_id,
+                    destination,
+                    count: summary.count,
+                    latency_buckets_ms: buckets,
+                };
+                // Serializing a struct of strings and integers can't fail
+                out.push_str(&serde_json::to_string(&record).unwrap());
+            }
+        }
+        out.push('\n');
+    }
+
+    /// Write the summaries formatted into `out` to the underlying writer. The writer does blocking
+    /// I/O, so this is done on the blocking thread pool rather than on the runtime's workers.
+    async fn write(&self, out: String) {
+        if out.is_empty() {
+            return;
+        }
+        let writer = self.writer.clone();
+        let res = tokio::task::spawn_blocking(move || {
+            let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+            writer
+                .write_all(out.as_bytes())
+                .and_then(|()| writer.flush())
+        })
+        .await
+        .unwrap_or_else(|e| Err(io::Error::from(e)));
+        if let Err(error) = res {
+            warn!(%error, "Failed to write query log summaries");
+        }
+    }
+}
+
+/// A single summary written by a [`SummaryWriter`] in [`QueryLogFormat::Json`]
+#[derive(Serialize)]
+struct SummaryRecord<'a> {
+    timestamp: &'a str,
+    /// The query, with its literals anonymized
+    query: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query_id: Option<&'a str>,
+    /// Where the query was executed; either `readyset` or `upstream`
+    destination: &'static str,
+    /// The number of times the query was executed against `destination` since the last summary
+    count: u64,
+    latency_buckets_ms: Vec<LatencyBucket>,
+}
+
+/// The number of executions which took longer than the previous bucket's upper bound, and at
+/// most `le` milliseconds
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct LatencyBucket {
+    /// The upper bound of the bucket, or `None` (serialized as `null`) for the final, unbounded
+    /// bucket
+    le: Option<u64>,
+    count: u64,
+}
+
+/// The executions of a query against a single destination since the last summary was written
+#[derive(Debug, Default)]
+struct LatencySummary {
+    count: u64,
+    /// The number of executions in each of the [`SUMMARY_LATENCY_BUCKETS_MS`], followed by the
+    /// number of executions slower than all of them
+    bucket_counts: [u64; SUMMARY_LATENCY_BUCKETS_MS.len() + 1],
+}
+
+impl LatencySummary {
+    fn record(&mut self, duration: Duration) {
+        let ms = duration.as_secs_f64() * 1000.0;
+        let bucket = SUMMARY_LATENCY_BUCKETS_MS
+            .iter()
+            .position(|le| ms <= *le as f64)
+            .unwrap_or(SUMMARY_LATENCY_BUCKETS_MS.len());
+        self.count += 1;
+        self.bucket_counts[bucket] += 1;
+    }
+
+    fn buckets(&self) -> Vec<LatencyBucket> {
+        self.bucket_counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| LatencyBucket {
+                le: SUMMARY_LATENCY_BUCKETS_MS.get(i).copied(),
+                count: *count,
+            })
+            .collect()
+    }
 }
 
 struct QueryMetrics {
@@ -25,7 +243,10 @@ struct QueryMetrics {
     num_keys: Counter,
     cache_misses: Counter,
     cache_keys_missed: Counter,
-    histograms: BTreeMap<(EventType, SqlQueryType), QueryHistograms>,
+    slow_queries: Counter,
+    histograms: BTreeMap<(EventType, SqlQueryType, Option<String>), QueryHistograms>,
+    readyset_summary: LatencySummary,
+    upstream_summary: LatencySummary,
 }
 
 #[derive(Default)]
@@ -36,9 +257,13 @@ struct QueryHistograms {
 }
 
 impl QueryMetrics {
-    fn parse_histogram(&mut self, kind: (EventType, SqlQueryType)) -> &mut Histogram {
+    fn parse_histogram(
+        &mut self,
+        kind: (EventType, SqlQueryType),
+        tag: Option<&String>,
+    ) -> &mut Histogram {
         self.histograms
-            .entry(kind)
+            .entry((kind.0, kind.1, tag.cloned()))
             .or_default()
             .parse_time
             .get_or_insert_with(|| {
@@ -52,13 +277,21 @@ impl QueryMetrics {
                     labels.push(("query_id", id.clone()));
                 }
 
+                if let Some(tag) = tag {
+                    labels.push(("query_tag", SharedString::from(tag.clone())));
+                }
+
                 register_histogram!(recorded::QUERY_LOG_PARSE_TIME, &labels)
             })
     }
 
-    fn readyset_histogram(&mut self, kind: (EventType, SqlQueryType)) -> &mut Histogram {
+    fn readyset_histogram(
+        &mut self,
+        kind: (EventType, SqlQueryType),
+        tag: Option<&String>,
+    ) -> &mut Histogram {
         self.histograms
-            .entry(kind)
+            .entry((kind.0, kind.1, tag.cloned()))
             .or_default()
             .readyset_exe_time
             .get_or_insert_with(|| {
@@ -73,13 +306,21 @@ impl QueryMetrics {
                     labels.push(("query_id", id.clone()));
                 }
 
+                if let Some(tag) = tag {
+                    labels.push(("query_tag", SharedString::from(tag.clone())));
+                }
+
                 register_histogram!(recorded::QUERY_LOG_EXECUTION_TIME, &labels)
             })
     }
 
-    fn upstream_histogram(&mut self, kind: (EventType, SqlQueryType)) -> &mut Histogram {
+    fn upstream_histogram(
+        &mut self,
+        kind: (EventType, SqlQueryType),
+        tag: Option<&String>,
+    ) -> &mut Histogram {
         self.histograms
-            .entry(kind)
+            .entry((kind.0, kind.1, tag.cloned()))
             .or_default()
             .upstream_exe_time
             .get_or_insert_with(|| {
@@ -94,6 +335,10 @@ impl QueryMetrics {
                     labels.push(("query_id", id.clone()));
                 }
 
+                if let Some(tag) = tag {
+                    labels.push(("query_tag", SharedString::from(tag.clone())));
+                }
+
                 register_histogram!(recorded::QUERY_LOG_EXECUTION_TIME, &labels)
             })
     }
@@ -136,9 +381,16 @@ impl QueryLogger {
                     "query" => query_string.clone(),
                     "query_id" => query_id.clone(),
                 ),
+                slow_queries: register_counter!(
+                    recorded::QUERY_LOG_SLOW_QUERIES,
+                    "query" => query_string.clone(),
+                    "query_id" => query_id.clone(),
+                ),
                 query: query_string,
                 query_id: Some(query_id),
                 histograms: BTreeMap::new(),
+                readyset_summary: LatencySummary::default(),
+                upstream_summary: LatencySummary::default(),
             }
         })
     }
@@ -162,81 +414,584 @@ impl QueryLogger {
                         readyset_client_metrics::recorded::QUERY_LOG_TOTAL_CACHE_MISSES,
                         "query" => query_string.clone(),
                     ),
+                    slow_queries: register_counter!(
+                        readyset_client_metrics::recorded::QUERY_LOG_SLOW_QUERIES,
+                        "query" => query_string.clone(),
+                    ),
                     query: query_string,
                     query_id: None,
                     histograms: BTreeMap::new(),
+                    readyset_summary: LatencySummary::default(),
+                    upstream_summary: LatencySummary::default(),
                 }
             })
     }
 
-    /// Async task that logs query stats.
+    fn new(config: QueryLoggerConfig, summaries: Option<SummaryWriter>) -> Self {
+        QueryLogger {
+            per_query_metrics: HashMap::new(),
+            per_id_metrics: BTreeMap::new(),
+            config,
+            summaries,
+            query_tags: BoundedLabelValues::new(MAX_QUERY_TAGS, MAX_QUERY_TAG_LEN),
+        }
+    }
+
+    /// Async task that logs query stats, and writes per-query summaries to `summaries` if given.
     pub(crate) async fn run(
-        mut receiver: UnboundedReceiver<QueryExecutionEvent>,
-        mut shutdown_recv: broadcast::Receiver<()>,
+        receiver: UnboundedReceiver<QueryExecutionEvent>,
+        shutdown_recv: broadcast::Receiver<()>,
+        config: QueryLoggerConfig,
+        summaries: Option<SummaryWriter>,
     ) {
         let _span = info_span!("query-logger");
 
-        let mut logger = QueryLogger {
-            per_query_metrics: HashMap::new(),
-            per_id_metrics: BTreeMap::new(),
-        };
-
-        loop {
-            select! {
-                event = receiver.recv() => {
-                    let event = match event {
-                        Some(event) => event,
-                        None => {
-                            info!("Metrics task shutting down after request handle dropped.");
-                            break;
-                        }
-                    };
+        let mut backlog = Backlog::new(config.max_backlog);
+        let mut logger = QueryLogger::new(config, summaries);
+        logger.process(receiver, shutdown_recv, &mut backlog).await;
+        logger.write_summaries().await;
+        backlog.summarize_drops();
+    }
 
-                    let query = match event.query {
-                        Some(query) => query,
-                        None => continue,
-                    };
+    /// Write a summary of the executions of each query since the last summaries were written, if
+    /// configured to, and reset them. Queries which couldn't be normalized aren't summarized.
+    async fn write_summaries(&mut self) {
+        let summaries = match &mut self.summaries {
+            Some(summaries) => summaries,
+            None => return,
+        };
 
-                    let metrics = if let Some(id) = event.query_id {
-                        logger.metrics_for_id(id, query)
-                    } else {
-                        logger.metrics_for_query(query)
-                    };
+        let timestamp = chrono::Utc::now().to_rfc3339();
+        let mut out = String::new();
+        for metrics in self
+            .per_id_metrics
+            .values_mut()
+            .chain(self.per_query_metrics.values_mut())
+        {
+            for (destination, summary) in [
+                ("readyset", &mut metrics.readyset_summary),
+                ("upstream", &mut metrics.upstream_summary),
+            ] {
+                if summary.count > 0 && !metrics.query.is_empty() {
+                    summaries.format_summary(
+                        &mut out,
+                        &timestamp,
+                        &metrics.query,
+                        metrics.query_id.as_deref(),
+                        destination,
+                        summary,
+                    );
+                }
+                *summary = LatencySummary::default();
+            }
+        }
+        summaries.write(out).await;
+    }
 
-                    if let Some(num_keys) = event.num_keys {
-                        metrics.num_keys.increment(num_keys);
-                    }
+    /// Process records from `receiver` in batches until either the channel is closed or a shutdown
+    /// signal is received, writing summaries every [`QUERY_SUMMARY_INTERVAL`] if configured to,
+    /// whether or not any records are received.
+    ///
+    /// Records which haven't been processed when the shutdown signal is received are counted as
+    /// dropped, so that they still show up in the final summary of drops.
+    async fn process(
+        &mut self,
+        mut receiver: UnboundedReceiver<QueryExecutionEvent>,
+        shutdown_recv: broadcast::Receiver<()>,
+        backlog: &mut Backlog,
+    ) {
+        let mut summary_interval = tokio::time::interval_at(
+            Instant::now() + QUERY_SUMMARY_INTERVAL,
+            QUERY_SUMMARY_INTERVAL,
+        );
+        summary_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
-                    if let Some(cache_misses) = event.cache_misses {
-                        metrics.cache_keys_missed.increment(cache_misses);
-                        if cache_misses != 0 {
-                            metrics.cache_misses.increment(1);
+        let processed = run_until_cancelled(
+            async {
+                loop {
+                    tokio::select! {
+                        // Check for summaries first, so that they're still written on time while
+                        // there's always a backlog of records to process
+                        biased;
+                        _ = summary_interval.tick(), if self.summaries.is_some() => {
+                            self.write_summaries().await;
+                            continue;
                         }
+                        event = receiver.recv(), if backlog.events.is_empty() => match event {
+                            Some(event) => backlog.push(event),
+                            None => {
+                                info!("Metrics task shutting down after request handle dropped.");
+                                break;
+                            }
+                        },
+                        _ = future::ready(()), if !backlog.events.is_empty() => {}
                     }
 
-                    if let Some(duration) = event.parse_duration {
-                        metrics
-                            .parse_histogram((event.event, event.sql_type))
-                            .record(duration);
-                    }
+                    self.fill_batch(&mut receiver, backlog).await;
 
-                    if let Some(duration) = event.readyset_duration {
-                        metrics
-                            .readyset_histogram((event.event, event.sql_type))
-                            .record(duration);
+                    let batch_len = backlog.events.len().min(self.config.batch_size);
+                    for event in backlog.events.drain(..batch_len) {
+                        self.log_event(event);
                     }
 
-                    if let Some(duration) = event.upstream_duration {
-                        metrics
-                            .upstream_histogram((event.event, event.sql_type))
-                            .record(duration);
+                    if backlog.dropped_since_summary > 0
+                        && backlog.last_summary.elapsed() >= DROP_SUMMARY_INTERVAL
+                    {
+                        backlog.summarize_drops();
                     }
                 }
-                _ = shutdown_recv.recv() => {
-                    info!("Metrics task shutting down after signal received.");
-                    break;
-                }
+            },
+            shutdown_recv,
+        )
+        .await;
+        if processed.is_none() {
+            info!("Metrics task shutting down after signal received.");
+            backlog.discard(&mut receiver);
+        }
+    }
+
+    /// Move all the records which are immediately available on `receiver` into the backlog,
+    /// waiting up to the configured batch window for more records if that isn't enough to fill a
+    /// batch.
+    ///
+    /// If the backlog is bounded, the channel is drained completely (shedding any records which
+    /// don't fit in the backlog) so that it can't grow without bound while we're processing.
+    async fn fill_batch(
+        &self,
+        receiver: &mut UnboundedReceiver<QueryExecutionEvent>,
+        backlog: &mut Backlog,
+    ) {
+        while backlog.max_len.is_some() || backlog.events.len() < self.config.batch_size {
+            match receiver.try_recv() {
+                Ok(event) => backlog.push(event),
+                Err(_) => break,
             }
         }
+
+        if self.config.batch_window.is_zero() {
+            return;
+        }
+        let deadline = Instant::now() + self.config.batch_window;
+        while backlog.events.len() < self.config.batch_size {
+            match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(event)) => backlog.push(event),
+                Ok(None) | Err(_) => break,
+            }
+        }
+    }
+
+    /// Record the metrics for a single query execution event
+    fn log_event(&mut self, event: QueryExecutionEvent) {
+        let is_slow = event.execution_duration() > self.config.slow_query_threshold;
+        let query = match event.query {
+            Some(query) => query,
+            None => return,
+        };
+        let tag = event
+            .query_tag
+            .as_deref()
+            .map(|tag| self.query_tags.label(tag));
+
+        let metrics = if let Some(id) = event.query_id {
+            self.metrics_for_id(id, query)
+        } else {
+            self.metrics_for_query(query)
+        };
+
+        if let Some(num_keys) = event.num_keys {
+            metrics.num_keys.increment(num_keys);
+        }
+
+        if is_slow {
+            metrics.slow_queries.increment(1);
+        }
+
+        if let Some(cache_misses) = event.cache_misses {
+            metrics.cache_keys_missed.increment(cache_misses);
+            if cache_misses != 0 {
+                metrics.cache_misses.increment(1);
+            }
+        }
+
+        if let Some(duration) = event.parse_duration {
+            metrics
+                .parse_histogram((event.event, event.sql_type), tag.as_ref())
+                .record(duration);
+        }
+
+        if let Some(duration) = event.readyset_duration {
+            metrics
+                .readyset_histogram((event.event, event.sql_type), tag.as_ref())
+                .record(duration);
+            metrics.readyset_summary.record(duration);
+        }
+
+        if let Some(duration) = event.upstream_duration {
+            metrics
+                .upstream_histogram((event.event, event.sql_type), tag.as_ref())
+                .record(duration);
+            metrics.upstream_summary.record(duration);
+        }
+    }
+}
+
+/// Records received by the [`QueryLogger`] which are waiting to be processed
+struct Backlog {
+    events: VecDeque<QueryExecutionEvent>,
+    /// The maximum number of records to buffer, beyond which new records are dropped
+    max_len: Option<usize>,
+    /// The largest number of records that have been buffered at once
+    peak_len: usize,
+    /// The total number of records that have been dropped
+    total_dropped: u64,
+    /// The number of records that have been dropped since we last logged a summary of drops
+    dropped_since_summary: u64,
+    last_summary: Instant,
+}
+
+impl Backlog {
+    fn new(max_len: Option<usize>) -> Self {
+        Self {
+            events: VecDeque::new(),
+            max_len,
+            peak_len: 0,
+            total_dropped: 0,
+            dropped_since_summary: 0,
+            last_summary: Instant::now(),
+        }
+    }
+
+    /// Buffer `event`, or drop it if the backlog is full
+    fn push(&mut self, event: QueryExecutionEvent) {
+        if self.max_len.map_or(false, |max| self.events.len() >= max) {
+            self.total_dropped += 1;
+            self.dropped_since_summary += 1;
+            return;
+        }
+        self.events.push_back(event);
+        self.peak_len = self.peak_len.max(self.events.len());
+    }
+
+    /// Drop every buffered record along with any still waiting on `receiver`, counting them as
+    /// dropped
+    fn discard(&mut self, receiver: &mut UnboundedReceiver<QueryExecutionEvent>) {
+        let mut discarded = self.events.len() as u64;
+        self.events.clear();
+        receiver.close();
+        while receiver.try_recv().is_ok() {
+            discarded += 1;
+        }
+        self.total_dropped += discarded;
+        self.dropped_since_summary += discarded;
+    }
+
+    /// Log a summary of the records dropped since the last summary, if any, and count them in the
+    /// [`QUERY_LOG_EVENTS_DROPPED`](recorded::QUERY_LOG_EVENTS_DROPPED) metric
+    fn summarize_drops(&mut self) {
+        if self.dropped_since_summary > 0 {
+            warn!(
+                dropped = self.dropped_since_summary,
+                max_backlog = ?self.max_len,
+                "Query log backlog full; dropped records"
+            );
+            counter!(
+                recorded::QUERY_LOG_EVENTS_DROPPED,
+                self.dropped_since_summary
+            );
+        }
+        self.dropped_since_summary = 0;
+        self.last_summary = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+    use nom_sql::{parse_query, Dialect};
+    use readyset_client_metrics::labels::OTHER_LABEL_VALUE;
+    use readyset_client_metrics::EventType;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::*;
+
+    /// A writer whose output can be read after it's been moved into a [`SummaryWriter`]
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    fn summarized_logger(format: QueryLogFormat) -> (QueryLogger, SharedBuffer) {
+        let buffer = SharedBuffer::default();
+        let config = QueryLoggerConfig {
+            batch_size: 10,
+            batch_window: Duration::ZERO,
+            max_backlog: None,
+            slow_query_threshold: Duration::from_millis(5),
+        };
+        let summaries = SummaryWriter::new(format, Box::new(buffer.clone()));
+        (QueryLogger::new(config, Some(summaries)), buffer)
+    }
+
+    fn select_query() -> SqlQuery {
+        parse_query(Dialect::MySQL, "SELECT a FROM t WHERE b = 1").unwrap()
+    }
+
+    fn select_event(readyset_ms: Option<u64>, upstream_ms: Option<u64>) -> QueryExecutionEvent {
+        let mut event = QueryExecutionEvent::new(EventType::Query);
+        event.sql_type = SqlQueryType::Read;
+        event.query = Some(Arc::new(select_query()));
+        event.readyset_duration = readyset_ms.map(Duration::from_millis);
+        event.upstream_duration = upstream_ms.map(Duration::from_millis);
+        event
+    }
+
+    #[tokio::test]
+    async fn json_summaries_are_ndjson() {
+        let (mut logger, buffer) = summarized_logger(QueryLogFormat::Json);
+        logger.log_event(select_event(Some(2), None));
+        logger.log_event(select_event(Some(3), None));
+        logger.log_event(select_event(None, Some(7000)));
+        logger.write_summaries().await;
+
+        let contents = buffer.contents();
+        assert!(contents.ends_with('\n'));
+        let records = contents
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+
+        let readyset = &records[0];
+        assert_eq!(readyset["destination"], "readyset");
+        assert_eq!(
+            readyset["query"],
+            &*QueryLogger::query_string(&select_query())
+        );
+        assert_eq!(readyset["count"], 2);
+        let buckets = readyset["latency_buckets_ms"].as_array().unwrap();
+        assert_eq!(buckets.len(), SUMMARY_LATENCY_BUCKETS_MS.len() + 1);
+        assert_eq!(buckets[1], serde_json::json!({"le": 5, "count": 2}));
+
+        let upstream = &records[1];
+        assert_eq!(upstream["destination"], "upstream");
+        assert_eq!(upstream["count"], 1);
+        assert_eq!(
+            buckets.len(),
+            upstream["latency_buckets_ms"].as_array().unwrap().len()
+        );
+        assert_eq!(
+            upstream["latency_buckets_ms"][SUMMARY_LATENCY_BUCKETS_MS.len()],
+            serde_json::json!({"le": null, "count": 1})
+        );
+
+        // Summaries are reset once they've been written
+        logger.write_summaries().await;
+        assert_eq!(buffer.contents(), contents);
+    }
+
+    #[tokio::test]
+    async fn text_summaries() {
+        let (mut logger, buffer) = summarized_logger(QueryLogFormat::Text);
+        logger.log_event(select_event(Some(2), Some(30)));
+        logger.write_summaries().await;
+
+        let contents = buffer.contents();
+        let lines = contents.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        let query = QueryLogger::query_string(&select_query());
+        assert!(
+            lines[0].ends_with(&format!(
+                " destination=readyset count=1 latency_ms=[<=5:1] query={query}"
+            )),
+            "{}",
+            lines[0]
+        );
+        assert!(
+            lines[1].contains(" destination=upstream count=1 latency_ms=[<=50:1] "),
+            "{}",
+            lines[1]
+        );
+    }
+
+    fn slow_queries() -> Option<DebugValue> {
+        Snapshotter::current_thread_snapshot()?
+            .into_vec()
+            .into_iter()
+            .find_map(|(key, _, _, value)| {
+                (key.key().name() == recorded::QUERY_LOG_SLOW_QUERIES).then_some(value)
+            })
+    }
+
+    #[test]
+    fn slow_queries_are_counted() {
+        // Ignore the error if another test on this thread already installed the recorder
+        let _ = DebuggingRecorder::per_thread().install();
+        let (mut logger, _) = summarized_logger(QueryLogFormat::Text);
+
+        // The threshold is 5ms, and a query has to exceed it to count as slow
+        logger.log_event(select_event(Some(2), None));
+        logger.log_event(select_event(Some(5), None));
+        assert_eq!(slow_queries(), Some(DebugValue::Counter(0)));
+
+        logger.log_event(select_event(Some(6), None));
+        assert_eq!(slow_queries(), Some(DebugValue::Counter(1)));
+
+        // Queries which fell back to the upstream are slow if either duration exceeds it
+        logger.log_event(select_event(Some(1), Some(20)));
+        assert_eq!(slow_queries(), Some(DebugValue::Counter(2)));
+    }
+
+    #[test]
+    fn query_tag_labels_are_capped() {
+        let (mut logger, _) = summarized_logger(QueryLogFormat::Text);
+        for i in 0..MAX_QUERY_TAGS + 10 {
+            let mut event = select_event(Some(1), None);
+            event.query_tag = Some(format!("tag-{i}"));
+            logger.log_event(event);
+        }
+
+        let metrics = &logger.per_query_metrics[&select_query()];
+        let tags = metrics
+            .histograms
+            .keys()
+            .map(|(_, _, tag)| tag.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(tags.len(), MAX_QUERY_TAGS + 1);
+        assert!(tags.contains(&"tag-0"));
+        assert!(tags.contains(&OTHER_LABEL_VALUE));
+        assert!(!tags.contains(&format!("tag-{MAX_QUERY_TAGS}").as_str()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn summaries_are_written_periodically() {
+        let (sender, receiver) = unbounded_channel();
+        let (_shutdown_send, shutdown_recv) = broadcast::channel(1);
+        let (mut logger, buffer) = summarized_logger(QueryLogFormat::Text);
+        let process = tokio::spawn(async move {
+            let mut backlog = Backlog::new(None);
+            logger.process(receiver, shutdown_recv, &mut backlog).await;
+        });
+
+        sender.send(select_event(Some(2), None)).unwrap();
+        tokio::time::sleep(QUERY_SUMMARY_INTERVAL / 2).await;
+        assert_eq!(buffer.contents(), "");
+
+        // No more records are received, but the summary is still written once the interval has
+        // elapsed
+        tokio::time::sleep(QUERY_SUMMARY_INTERVAL).await;
+        let start = std::time::Instant::now();
+        while buffer.contents().is_empty() {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "Summary was not written"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(
+            buffer.contents().contains(" destination=readyset count=1 "),
+            "{}",
+            buffer.contents()
+        );
+
+        drop(sender);
+        process.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn flooded_logger_sheds_records() {
+        let (sender, receiver) = unbounded_channel();
+        let (_shutdown_send, shutdown_recv) = broadcast::channel(1);
+        for _ in 0..10_000 {
+            sender
+                .send(QueryExecutionEvent::new(EventType::Query))
+                .unwrap();
+        }
+        drop(sender);
+
+        let config = QueryLoggerConfig {
+            batch_size: 10,
+            batch_window: Duration::ZERO,
+            max_backlog: Some(100),
+            slow_query_threshold: Duration::from_millis(5),
+        };
+        let mut backlog = Backlog::new(config.max_backlog);
+        QueryLogger::new(config, None)
+            .process(receiver, shutdown_recv, &mut backlog)
+            .await;
+
+        assert!(backlog.peak_len <= 100, "{}", backlog.peak_len);
+        assert_eq!(backlog.total_dropped, 9_900);
+        assert!(backlog.events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unbounded_logger_keeps_all_records() {
+        let (sender, receiver) = unbounded_channel();
+        let (_shutdown_send, shutdown_recv) = broadcast::channel(1);
+        for _ in 0..1_000 {
+            sender
+                .send(QueryExecutionEvent::new(EventType::Query))
+                .unwrap();
+        }
+        drop(sender);
+
+        let config = QueryLoggerConfig {
+            batch_size: 10,
+            batch_window: Duration::ZERO,
+            max_backlog: None,
+            slow_query_threshold: Duration::from_millis(5),
+        };
+        let mut backlog = Backlog::new(config.max_backlog);
+        QueryLogger::new(config, None)
+            .process(receiver, shutdown_recv, &mut backlog)
+            .await;
+
+        assert_eq!(backlog.total_dropped, 0);
+        assert!(backlog.peak_len <= 10, "{}", backlog.peak_len);
+    }
+
+    #[tokio::test]
+    async fn unprocessed_records_are_dropped_on_shutdown() {
+        let (sender, receiver) = unbounded_channel();
+        let (shutdown_send, shutdown_recv) = broadcast::channel(1);
+        for _ in 0..100 {
+            sender
+                .send(QueryExecutionEvent::new(EventType::Query))
+                .unwrap();
+        }
+        shutdown_send.send(()).unwrap();
+
+        // A batch is never full, so no records are processed before the shutdown is seen
+        let config = QueryLoggerConfig {
+            batch_size: 1_000,
+            batch_window: Duration::from_secs(3600),
+            max_backlog: None,
+            slow_query_threshold: Duration::from_millis(5),
+        };
+        let mut backlog = Backlog::new(config.max_backlog);
+        QueryLogger::new(config, None)
+            .process(receiver, shutdown_recv, &mut backlog)
+            .await;
+
+        assert_eq!(backlog.total_dropped, 100);
+        assert_eq!(backlog.dropped_since_summary, 100);
+        assert!(backlog.events.is_empty());
+        drop(sender);
     }
 }